@@ -0,0 +1,98 @@
+//! Cost of turning a processed frame into an MCP image response, for 1080p
+//! and 4K frames. Besides timing, each case reports the bytes allocated per
+//! capture so the copy-avoiding path can be compared with the old one,
+//! which base64-encoded eagerly and cloned the string into the history.
+//!
+//! Run with `cargo bench --bench capture_encode`.
+
+use base64::{engine::general_purpose, Engine as _};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use mcp_webcam::image_processing::color_bars;
+use mcp_webcam::CapturedFrame;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// System allocator that counts bytes allocated
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size.saturating_sub(layout.size()), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const SIZES: &[(&str, u32, u32)] = &[("1080p", 1920, 1080), ("4k", 3840, 2160)];
+
+/// Current path: JPEG bytes shared with the history, base64 only for the response
+fn shared_bytes(frame: &CapturedFrame) -> String {
+    let result = frame.clone().encode().unwrap();
+    let stored = result.clone();
+    let response = result.image_base64();
+    black_box(stored);
+    response
+}
+
+/// Previous path: base64 kept in the result, a decoded copy for saving and
+/// a full clone of the string for the history
+fn eager_base64(frame: &CapturedFrame) -> String {
+    let frame = frame.clone();
+    let bytes = frame.encode_jpeg().unwrap();
+    let image_data = general_purpose::STANDARD.encode(&bytes);
+    let stored = image_data.clone();
+    let decoded = general_purpose::STANDARD.decode(&image_data).unwrap();
+    black_box((stored, decoded));
+    image_data
+}
+
+fn allocated_by(f: impl FnOnce()) -> usize {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    f();
+    ALLOCATED.load(Ordering::Relaxed) - before
+}
+
+fn bench_capture_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("capture_encode");
+    group.sample_size(20);
+
+    for &(label, width, height) in SIZES {
+        let frame = CapturedFrame::new(color_bars(width, height), 0);
+
+        let shared = allocated_by(|| drop(shared_bytes(&frame)));
+        let eager = allocated_by(|| drop(eager_base64(&frame)));
+        println!(
+            "{}: {} bytes allocated per capture (was {}, {:.0}% less)",
+            label,
+            shared,
+            eager,
+            100.0 * (1.0 - shared as f64 / eager as f64)
+        );
+
+        group.bench_with_input(BenchmarkId::new("shared_bytes", label), &frame, |b, frame| {
+            b.iter(|| shared_bytes(frame))
+        });
+        group.bench_with_input(BenchmarkId::new("eager_base64", label), &frame, |b, frame| {
+            b.iter(|| eager_base64(frame))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_capture_encode);
+criterion_main!(benches);
@@ -0,0 +1,36 @@
+//! Embeds the git commit the server is built from, for `get_server_info`.
+
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    let git_dir = Path::new(".git");
+
+    // Outside a git checkout (e.g. a published crate) there is no commit to
+    // embed, so the package version stands in for it
+    let commit = git_dir
+        .exists()
+        .then(git_commit)
+        .flatten()
+        .unwrap_or_else(|| std::env::var("CARGO_PKG_VERSION").unwrap_or_default());
+    println!("cargo:rustc-env=MCP_WEBCAM_GIT_COMMIT={}", commit);
+
+    // Watching paths that don't exist would rerun this script on every build
+    if git_dir.join("HEAD").exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        println!("cargo:rerun-if-changed=.git/refs/heads");
+    } else {
+        println!("cargo:rerun-if-changed=build.rs");
+    }
+}
+
+fn git_commit() -> Option<String> {
+    Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .filter(|commit| !commit.is_empty())
+}
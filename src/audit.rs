@@ -0,0 +1,205 @@
+//! Append-only audit log of camera activations and remote fetches, one
+//! JSON object per line.
+//!
+//! Entries are handed to a writer thread over a channel, so recording one
+//! never blocks or fails a capture; write errors are logged and the entry
+//! is dropped. Each entry carries the SHA-256 of the line before it, so
+//! editing or removing a line breaks the chain from there on, which
+//! `AuditLog::read` reports.
+
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+/// Default number of entries returned by `get_audit_log`
+pub const DEFAULT_AUDIT_ENTRIES: usize = 100;
+/// Upper bound on the number of entries returned by `get_audit_log`
+pub const MAX_AUDIT_ENTRIES: usize = 1000;
+/// `prev_hash` of the first entry in a log
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+/// How long `read` waits for queued entries to be written first
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// One audited call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// RFC 3339 time the call finished
+    pub timestamp: String,
+    /// Tool name, or the MCP method for resource reads
+    pub tool: String,
+    /// Arguments with secrets replaced
+    pub arguments: Value,
+    pub camera_index: Option<u32>,
+    /// Remote webcam or API URL, for remote fetches
+    pub url: Option<String>,
+    /// Size of the result sent to the client
+    pub result_bytes: u64,
+    pub success: bool,
+    /// Error code of a failed call
+    pub error_code: Option<String>,
+    pub duration_ms: u64,
+    /// SHA-256 of the previous line, hex-encoded
+    #[serde(default)]
+    pub prev_hash: String,
+}
+
+/// Entries read back from the log
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditExcerpt {
+    pub entries: Vec<AuditEntry>,
+    /// Entries at or after `since`, before the cap was applied
+    pub matched: usize,
+    /// Whether every line parsed and carries the hash of the line before it
+    pub chain_intact: bool,
+}
+
+enum Message {
+    Record(AuditEntry),
+    /// Answered once everything queued before it is written
+    Flush(Sender<()>),
+}
+
+/// Handle on the audit log; clones share the writer. A disabled log
+/// accepts and discards entries.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    writer: Option<Sender<Message>>,
+}
+
+impl AuditLog {
+    /// Log appending to `path`, created with its directory if needed
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let (writer, messages) = mpsc::channel::<Message>();
+        let file_path = path.clone();
+        std::thread::Builder::new()
+            .name("audit-log".to_string())
+            .spawn(move || write_entries(&file_path, messages))
+            .map_err(|e| error!("Failed to start the audit log writer: {}", e))
+            .ok();
+        info!("Audit log: {}", path.display());
+        Self { path: Some(path), writer: Some(writer) }
+    }
+
+    pub fn disabled() -> Self {
+        Self { path: None, writer: None }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Queue `entry` for writing; returns immediately
+    pub fn record(&self, entry: AuditEntry) {
+        if let Some(writer) = &self.writer {
+            if writer.send(Message::Record(entry)).is_err() {
+                warn!("Audit log writer has stopped; entry dropped");
+            }
+        }
+    }
+
+    /// The last `limit` entries at or after `since`, once entries queued
+    /// so far are written
+    pub fn read(&self, since: Option<DateTime<FixedOffset>>, limit: usize) -> Result<AuditExcerpt, String> {
+        let Some(path) = &self.path else {
+            return Ok(AuditExcerpt { entries: Vec::new(), matched: 0, chain_intact: true });
+        };
+
+        if let Some(writer) = &self.writer {
+            let (done, flushed) = mpsc::channel();
+            if writer.send(Message::Flush(done)).is_ok() && flushed.recv_timeout(FLUSH_TIMEOUT).is_err() {
+                warn!("Audit log writer is behind; reading without the newest entries");
+            }
+        }
+
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(AuditExcerpt { entries: Vec::new(), matched: 0, chain_intact: true });
+            }
+            Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+        };
+
+        let mut entries = Vec::new();
+        let mut chain_intact = true;
+        let mut prev_hash = GENESIS_HASH.to_string();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            match serde_json::from_str::<AuditEntry>(&line) {
+                Ok(entry) => {
+                    chain_intact &= entry.prev_hash == prev_hash;
+                    entries.push(entry);
+                }
+                Err(_) => chain_intact = false,
+            }
+            prev_hash = hash(&line);
+        }
+
+        if let Some(since) = since {
+            entries.retain(|entry| {
+                DateTime::parse_from_rfc3339(&entry.timestamp).is_ok_and(|timestamp| timestamp >= since)
+            });
+        }
+        let matched = entries.len();
+        let entries = entries.split_off(matched.saturating_sub(limit));
+        Ok(AuditExcerpt { entries, matched, chain_intact })
+    }
+}
+
+/// Writer thread: append entries until every `AuditLog` is dropped
+fn write_entries(path: &Path, messages: mpsc::Receiver<Message>) {
+    let mut prev_hash = last_line(path).map(|line| hash(&line)).unwrap_or_else(|| GENESIS_HASH.to_string());
+    for message in messages {
+        let mut entry = match message {
+            Message::Record(entry) => entry,
+            Message::Flush(done) => {
+                let _ = done.send(());
+                continue;
+            }
+        };
+        entry.prev_hash = prev_hash.clone();
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("Failed to serialize audit entry: {}", e);
+                continue;
+            }
+        };
+        match append(path, &line) {
+            Ok(()) => {
+                debug!("Audited {} call", entry.tool);
+                prev_hash = hash(&line);
+            }
+            Err(e) => error!("Failed to write audit log {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn append(path: &Path, line: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)
+}
+
+fn last_line(path: &Path) -> Option<String> {
+    let file = std::fs::File::open(path).ok()?;
+    BufReader::new(file).lines().map_while(Result::ok).last()
+}
+
+fn hash(line: &str) -> String {
+    Sha256::digest(line.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,179 @@
+//! Bearer-token authentication for the server's network listeners. The
+//! stdio transport is only reachable by the process that spawned the
+//! server and is never authenticated.
+//!
+//! A request presents its token in an `Authorization: Bearer <token>`
+//! header or, for clients such as browsers that can't set headers, in a
+//! `token` query parameter. Addresses that keep presenting wrong tokens are
+//! locked out for a while.
+
+use crate::client_log::ClientLog;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Environment variable holding a fixed token for network listeners
+pub const AUTH_TOKEN_ENV: &str = "MCP_WEBCAM_AUTH_TOKEN";
+/// Failed attempts from one address before it is locked out
+pub const MAX_AUTH_FAILURES: u32 = 5;
+/// Failures are counted over this window, starting at an address's first
+/// failure; a locked-out address stays locked out until it ends
+pub const AUTH_FAILURE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Secret a client must present. `Debug` output never contains it.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AuthToken(String);
+
+impl AuthToken {
+    pub fn new(token: impl Into<String>) -> Result<Self, String> {
+        let token = token.into();
+        if token.is_empty() {
+            return Err("auth token must not be empty".to_string());
+        }
+        if !token.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err("auth token must be printable ASCII without spaces".to_string());
+        }
+        ClientLog::global().redact(&token);
+        Ok(Self(token))
+    }
+
+    /// Random token for a listener with no configured token
+    pub fn generate() -> Self {
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        ClientLog::global().redact(&token);
+        Self(token)
+    }
+
+    /// Token from `MCP_WEBCAM_AUTH_TOKEN`, if set and valid
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(AUTH_TOKEN_ENV).ok()?;
+        match Self::new(value) {
+            Ok(token) => Some(token),
+            Err(e) => {
+                warn!("Ignoring {}: {}", AUTH_TOKEN_ENV, e);
+                None
+            }
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The token percent-encoded for a `token` query parameter
+    pub fn query_value(&self) -> String {
+        url::form_urlencoded::byte_serialize(self.0.as_bytes()).collect()
+    }
+
+    /// Compare with a presented token in time independent of where they differ
+    pub fn matches(&self, presented: &str) -> bool {
+        constant_time_eq(self.0.as_bytes(), presented.as_bytes())
+    }
+}
+
+impl fmt::Debug for AuthToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AuthToken(<redacted>)")
+    }
+}
+
+/// Byte comparison that always looks at every byte of both inputs. Only
+/// the length can leak through timing.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let mut diff = (a.len() != b.len()) as u8;
+    for i in 0..a.len().max(b.len()) {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        diff |= x ^ y;
+    }
+    diff == 0
+}
+
+/// Token presented by an HTTP request: the `Authorization: Bearer` header
+/// if there is one, else the `token` query parameter, percent-decoded as
+/// form data (so `+` is a space)
+pub fn presented_token<'a>(request_head: &'a str, query: &'a str) -> Option<Cow<'a, str>> {
+    let from_header = request_head.lines().skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if !name.trim().eq_ignore_ascii_case("authorization") {
+            return None;
+        }
+        let (scheme, token) = value.trim().split_once(' ')?;
+        scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+    });
+    from_header.map(Cow::Borrowed).or_else(|| {
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == "token")
+            .map(|(_, value)| value)
+    })
+}
+
+/// Outcome of checking a request against an `Authenticator`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthOutcome {
+    Allowed,
+    /// Missing or wrong token; answer with 401
+    Rejected,
+    /// Too many recent failures from this address; answer with 429
+    LockedOut,
+}
+
+/// Checks presented tokens and tracks failures per source address
+#[derive(Debug)]
+pub struct Authenticator {
+    token: AuthToken,
+    max_failures: u32,
+    window: Duration,
+    /// Failures per address since the time recorded with them
+    failures: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+impl Authenticator {
+    pub fn new(token: AuthToken) -> Self {
+        Self::with_limits(token, MAX_AUTH_FAILURES, AUTH_FAILURE_WINDOW)
+    }
+
+    pub fn with_limits(token: AuthToken, max_failures: u32, window: Duration) -> Self {
+        Self {
+            token,
+            max_failures,
+            window,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn token(&self) -> &AuthToken {
+        &self.token
+    }
+
+    /// Check a request from `peer` presenting `presented` (if anything).
+    /// A locked-out address is refused without looking at its token.
+    pub fn check(&self, peer: IpAddr, presented: Option<&str>) -> AuthOutcome {
+        let mut failures = self.failures.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Instant::now();
+        failures.retain(|_, (since, _)| now.duration_since(*since) < self.window);
+
+        if failures.get(&peer).is_some_and(|(_, count)| *count >= self.max_failures) {
+            return AuthOutcome::LockedOut;
+        }
+        if presented.is_some_and(|token| self.token.matches(token)) {
+            failures.remove(&peer);
+            return AuthOutcome::Allowed;
+        }
+
+        let (_, count) = failures.entry(peer).or_insert((now, 0));
+        *count += 1;
+        let reason = if presented.is_some() { "wrong token" } else { "no token" };
+        if *count >= self.max_failures {
+            warn!("Authentication failed from {} ({}), locking it out for {:?} after {} failures",
+                  peer, reason, self.window, count);
+        } else {
+            warn!("Authentication failed from {} ({})", peer, reason);
+        }
+        AuthOutcome::Rejected
+    }
+}
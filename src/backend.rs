@@ -0,0 +1,1059 @@
+use crate::webcam::{CameraInfo, WebcamError};
+use image::RgbImage;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+#[cfg(feature = "local_cameras")]
+use {
+    nokhwa::{
+        pixel_format::RgbFormat,
+        utils::{CameraIndex, ControlValueSetter, KnownCameraControl, RequestedFormat, RequestedFormatType, Resolution},
+        Camera,
+    },
+    tracing::error,
+};
+
+#[cfg(feature = "opencv_backend")]
+use opencv::{core::Mat, imgproc, prelude::*, videoio::{self, VideoCapture}};
+
+#[cfg(feature = "gstreamer_backend")]
+use {
+    gst::prelude::*,
+    gstreamer as gst,
+    gstreamer_app as gst_app,
+    gstreamer_video as gst_video,
+    std::collections::BTreeMap,
+};
+
+/// Names `MCP_WEBCAM_BACKEND` and the per-camera `backend` setting accept
+pub const BACKEND_NAMES: &[&str] = &["nokhwa", "opencv"];
+
+/// Camera controls `WebcamManager` knows how to set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraControl {
+    /// Colour temperature in Kelvin
+    WhiteBalance,
+}
+
+/// Format negotiated with an open camera
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StreamFormat {
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: String,
+    /// Nominal frame rate; 0 if unknown
+    pub frame_rate: u32,
+}
+
+/// Access to camera devices. `WebcamManager` keeps at most one camera open
+/// through its backend at a time.
+pub trait CaptureBackend: Send {
+    /// Short name reported in camera listings and capture metadata
+    fn name(&self) -> &'static str;
+
+    /// Whether camera `index` belongs to this backend. Backends that define
+    /// their own cameras claim only those indices; platform backends serve
+    /// any index not claimed by another backend.
+    fn owns(&self, _index: u32) -> bool {
+        true
+    }
+
+    /// Cameras the backend can open
+    fn list_devices(&self) -> Result<Vec<CameraInfo>, WebcamError>;
+
+    /// Open a camera, closing any camera already open. A resolution the
+    /// camera rejects may be ignored; frames are scaled in software later.
+    fn open(&mut self, index: u32, resolution: Option<(u32, u32)>) -> Result<(), WebcamError>;
+
+    /// Close the open camera, if any
+    fn close(&mut self);
+
+    fn is_open(&self) -> bool;
+
+    /// Name of the open camera
+    fn camera_name(&self) -> Option<String>;
+
+    /// Identifier of the open camera that survives re-enumeration (unlike
+    /// its index), matching `CameraInfo::device_id`
+    fn device_id(&self) -> Option<String> {
+        None
+    }
+
+    /// Format negotiated with the open camera
+    fn format(&self) -> Option<StreamFormat>;
+
+    /// Grab the next frame from the open camera and decode it to RGB
+    fn grab_frame(&mut self) -> Result<RgbImage, WebcamError>;
+
+    /// Grab the next frame and throw it away, without decoding
+    fn skip_frame(&mut self) -> Result<(), WebcamError> {
+        self.grab_frame().map(|_| ())
+    }
+
+    /// Set a control on the open camera. Fails if the camera doesn't
+    /// support it.
+    fn set_control(&mut self, control: CameraControl, value: i64) -> Result<(), WebcamError>;
+}
+
+/// The backend named by `MCP_WEBCAM_BACKEND` if it is compiled in;
+/// otherwise nokhwa with `local_cameras`, OpenCV with `opencv_backend`, or
+/// one that reports local cameras as unsupported
+pub fn default_backend() -> Box<dyn CaptureBackend> {
+    if let Ok(name) = std::env::var("MCP_WEBCAM_BACKEND") {
+        match create_backend(&name) {
+            Some(backend) => {
+                info!("Using {} capture backend", backend.name());
+                return backend;
+            }
+            None => warn!("Capture backend '{}' from MCP_WEBCAM_BACKEND is not available in this build", name),
+        }
+    }
+
+    #[cfg(feature = "local_cameras")]
+    {
+        Box::new(NokhwaBackend::default())
+    }
+
+    #[cfg(all(not(feature = "local_cameras"), feature = "opencv_backend"))]
+    {
+        Box::new(OpenCvBackend::default())
+    }
+
+    #[cfg(not(any(feature = "local_cameras", feature = "opencv_backend")))]
+    {
+        Box::new(UnsupportedBackend)
+    }
+}
+
+/// A new backend by name, or `None` if it isn't compiled into this build
+pub fn create_backend(name: &str) -> Option<Box<dyn CaptureBackend>> {
+    match name {
+        #[cfg(feature = "local_cameras")]
+        "nokhwa" => Some(Box::new(NokhwaBackend::default())),
+        #[cfg(feature = "opencv_backend")]
+        "opencv" => Some(Box::new(OpenCvBackend::default())),
+        _ => None,
+    }
+}
+
+/// Platform cameras via nokhwa
+#[cfg(feature = "local_cameras")]
+#[derive(Default)]
+pub struct NokhwaBackend {
+    camera: Option<Camera>,
+}
+
+#[cfg(feature = "local_cameras")]
+impl NokhwaBackend {
+    fn camera(&mut self) -> Result<&mut Camera, WebcamError> {
+        self.camera.as_mut().ok_or(WebcamError::CameraNotOpen)
+    }
+}
+
+#[cfg(feature = "local_cameras")]
+impl CaptureBackend for NokhwaBackend {
+    fn name(&self) -> &'static str {
+        "nokhwa"
+    }
+
+    fn list_devices(&self) -> Result<Vec<CameraInfo>, WebcamError> {
+        info!("Listing available cameras");
+
+        match nokhwa::query_devices() {
+            Ok(devices) => {
+                let cameras: Vec<CameraInfo> = devices
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, device)| CameraInfo {
+                        index: index as u32,
+                        name: device.human_name().to_string(),
+                        description: device.description().to_string(),
+                        available: true,
+                        fake: false,
+                        backend: self.name().to_string(),
+                        device_id: Some(device.misc()),
+                    })
+                    .collect();
+
+                info!("Found {} cameras", cameras.len());
+                Ok(cameras)
+            }
+            Err(e) => {
+                warn!("Failed to query devices: {}", e);
+                Ok(vec![]) // Return empty list instead of error
+            }
+        }
+    }
+
+    fn open(&mut self, index: u32, resolution: Option<(u32, u32)>) -> Result<(), WebcamError> {
+        self.close();
+
+        let open = |format_type: RequestedFormatType| {
+            let requested_format = RequestedFormat::new::<RgbFormat>(format_type);
+            Camera::new(CameraIndex::Index(index), requested_format)
+                .and_then(|mut camera| camera.open_stream().map(|_| camera))
+        };
+        let opened = match resolution {
+            Some((width, height)) => open(RequestedFormatType::HighestResolution(Resolution::new(width, height)))
+                .or_else(|e| {
+                    debug!("Camera {} rejected {}x{} ({}), using highest resolution", index, width, height, e);
+                    open(RequestedFormatType::AbsoluteHighestResolution)
+                }),
+            None => open(RequestedFormatType::AbsoluteHighestResolution),
+        };
+
+        match opened {
+            Ok(camera) => {
+                self.camera = Some(camera);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to open camera {}: {}", index, e);
+                Err(WebcamError::CameraOpen(e))
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        if self.camera.take().is_some() {
+            debug!("Closed camera");
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.camera.is_some()
+    }
+
+    fn camera_name(&self) -> Option<String> {
+        self.camera.as_ref().map(|camera| camera.info().human_name())
+    }
+
+    fn device_id(&self) -> Option<String> {
+        self.camera.as_ref().map(|camera| camera.info().misc())
+    }
+
+    fn format(&self) -> Option<StreamFormat> {
+        self.camera.as_ref().map(|camera| {
+            let format = camera.camera_format();
+            StreamFormat {
+                width: format.resolution().width(),
+                height: format.resolution().height(),
+                pixel_format: format.format().to_string(),
+                frame_rate: format.frame_rate(),
+            }
+        })
+    }
+
+    fn grab_frame(&mut self) -> Result<RgbImage, WebcamError> {
+        let frame = self.camera()?.frame()?;
+        Ok(frame.decode_image::<RgbFormat>()?)
+    }
+
+    fn skip_frame(&mut self) -> Result<(), WebcamError> {
+        self.camera()?.frame()?;
+        Ok(())
+    }
+
+    fn set_control(&mut self, control: CameraControl, value: i64) -> Result<(), WebcamError> {
+        let known = match control {
+            CameraControl::WhiteBalance => KnownCameraControl::WhiteBalance,
+        };
+        self.camera()?.set_camera_control(known, ControlValueSetter::Integer(value))?;
+        Ok(())
+    }
+}
+
+/// Backends with cameras of their own, listed after the platform cameras:
+/// GStreamer pipelines from `MCP_WEBCAM_GST_<n>` with `gstreamer_backend`,
+/// and Raspberry Pi CSI cameras with `libcamera`
+pub fn extra_backends() -> Vec<Box<dyn CaptureBackend>> {
+    #[allow(unused_mut)]
+    let mut backends: Vec<Box<dyn CaptureBackend>> = Vec::new();
+
+    #[cfg(feature = "libcamera")]
+    if let Some(libcamera) = LibcameraBackend::detect() {
+        backends.push(Box::new(libcamera));
+    }
+
+    #[cfg(feature = "gstreamer_backend")]
+    if let Some(gstreamer) = GStreamerBackend::from_env() {
+        backends.push(Box::new(gstreamer));
+    }
+
+    backends
+}
+
+/// Highest device index OpenCV is asked to open when listing cameras, as
+/// it has no way to enumerate devices
+#[cfg(feature = "opencv_backend")]
+const OPENCV_PROBE_LIMIT: u32 = 8;
+
+/// Platform cameras via OpenCV's `VideoCapture`
+#[cfg(feature = "opencv_backend")]
+#[derive(Default)]
+pub struct OpenCvBackend {
+    capture: Option<(u32, VideoCapture)>,
+}
+
+#[cfg(feature = "opencv_backend")]
+impl OpenCvBackend {
+    fn capture(&mut self) -> Result<&mut VideoCapture, WebcamError> {
+        self.capture.as_mut().map(|(_, capture)| capture).ok_or(WebcamError::CameraNotOpen)
+    }
+
+    /// Open a device, or `None` if nothing answers at `index`
+    fn open_device(index: u32) -> Result<Option<VideoCapture>, WebcamError> {
+        let capture = VideoCapture::new(index as i32, videoio::CAP_ANY)?;
+        Ok(capture.is_opened()?.then_some(capture))
+    }
+
+    fn device_name(index: u32, capture: &VideoCapture) -> String {
+        match capture.get_backend_name() {
+            Ok(api) => format!("OpenCV Camera {} ({})", index, api),
+            Err(_) => format!("OpenCV Camera {}", index),
+        }
+    }
+}
+
+#[cfg(feature = "opencv_backend")]
+impl CaptureBackend for OpenCvBackend {
+    fn name(&self) -> &'static str {
+        "opencv"
+    }
+
+    fn list_devices(&self) -> Result<Vec<CameraInfo>, WebcamError> {
+        info!("Probing OpenCV camera indices 0-{}", OPENCV_PROBE_LIMIT - 1);
+
+        let mut cameras = Vec::new();
+        for index in 0..OPENCV_PROBE_LIMIT {
+            // The open camera can't always be opened a second time
+            let name = match &self.capture {
+                Some((open, capture)) if *open == index => Self::device_name(index, capture),
+                _ => match Self::open_device(index) {
+                    Ok(Some(capture)) => Self::device_name(index, &capture),
+                    Ok(None) => continue,
+                    Err(e) => {
+                        debug!("OpenCV failed to probe camera {}: {}", index, e);
+                        continue;
+                    }
+                },
+            };
+            cameras.push(CameraInfo {
+                index,
+                name,
+                description: "OpenCV VideoCapture device".to_string(),
+                available: true,
+                fake: false,
+                backend: self.name().to_string(),
+                device_id: None,
+            });
+        }
+
+        info!("Found {} cameras", cameras.len());
+        Ok(cameras)
+    }
+
+    fn open(&mut self, index: u32, resolution: Option<(u32, u32)>) -> Result<(), WebcamError> {
+        self.close();
+
+        let mut capture = Self::open_device(index)?.ok_or(WebcamError::CameraNotFound { index })?;
+        if let Some((width, height)) = resolution {
+            // Unsupported sizes are silently replaced by the nearest one the
+            // driver offers; frames are scaled in software afterwards
+            let accepted = capture.set(videoio::CAP_PROP_FRAME_WIDTH, width as f64)?
+                && capture.set(videoio::CAP_PROP_FRAME_HEIGHT, height as f64)?;
+            if !accepted {
+                debug!("Camera {} rejected {}x{}, using its default resolution", index, width, height);
+            }
+        }
+
+        self.capture = Some((index, capture));
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if let Some((index, mut capture)) = self.capture.take() {
+            let _ = capture.release();
+            debug!("Closed OpenCV camera {}", index);
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    fn camera_name(&self) -> Option<String> {
+        self.capture.as_ref().map(|(index, capture)| Self::device_name(*index, capture))
+    }
+
+    fn format(&self) -> Option<StreamFormat> {
+        let (_, capture) = self.capture.as_ref()?;
+        let get = |prop| capture.get(prop).unwrap_or(0.0);
+        // The pixel format is a FOURCC code packed into a double
+        let fourcc: String = (get(videoio::CAP_PROP_FOURCC) as u32)
+            .to_le_bytes()
+            .iter()
+            .filter(|b| b.is_ascii_graphic())
+            .map(|&b| b as char)
+            .collect();
+        Some(StreamFormat {
+            width: get(videoio::CAP_PROP_FRAME_WIDTH) as u32,
+            height: get(videoio::CAP_PROP_FRAME_HEIGHT) as u32,
+            pixel_format: fourcc,
+            frame_rate: get(videoio::CAP_PROP_FPS).round() as u32,
+        })
+    }
+
+    fn grab_frame(&mut self) -> Result<RgbImage, WebcamError> {
+        let capture = self.capture()?;
+        let mut bgr = Mat::default();
+        if !capture.read(&mut bgr)? || bgr.empty() {
+            return Err(WebcamError::Decode("OpenCV returned an empty frame".to_string()));
+        }
+
+        // OpenCV delivers BGR; convert to the packed RGB buffer everything else uses
+        let mut rgb = Mat::default();
+        imgproc::cvt_color(&bgr, &mut rgb, imgproc::COLOR_BGR2RGB, 0)?;
+        let (width, height) = (rgb.cols() as u32, rgb.rows() as u32);
+        RgbImage::from_raw(width, height, rgb.data_bytes()?.to_vec())
+            .ok_or_else(|| WebcamError::Decode(format!("unexpected OpenCV frame layout {}x{}", width, height)))
+    }
+
+    fn skip_frame(&mut self) -> Result<(), WebcamError> {
+        self.capture()?.grab()?;
+        Ok(())
+    }
+
+    fn set_control(&mut self, control: CameraControl, value: i64) -> Result<(), WebcamError> {
+        let prop = match control {
+            CameraControl::WhiteBalance => videoio::CAP_PROP_WB_TEMPERATURE,
+        };
+        let capture = self.capture()?;
+        // Automatic white balance would override a manual temperature
+        if control == CameraControl::WhiteBalance {
+            let _ = capture.set(videoio::CAP_PROP_AUTO_WB, 0.0);
+        }
+        if capture.set(prop, value as f64)? {
+            Ok(())
+        } else {
+            Err(WebcamError::InvalidOptions(format!("{:?} is not supported by this camera", control)))
+        }
+    }
+}
+
+/// Camera index of the pipeline in `MCP_WEBCAM_GST_0`; pipeline `n` is
+/// listed at `GSTREAMER_INDEX_BASE + n`, clear of platform devices
+pub const GSTREAMER_INDEX_BASE: u32 = 200;
+
+/// How long to wait for a pipeline to start or deliver a frame
+#[cfg(feature = "gstreamer_backend")]
+const GSTREAMER_TIMEOUT_SECS: u64 = 5;
+
+/// Cameras defined by GStreamer pipeline descriptions ending in an
+/// `appsink`, e.g. `libcamerasrc ! videoconvert ! appsink`. The appsink is
+/// forced to RGB, so the pipeline must be able to convert to it.
+#[cfg(feature = "gstreamer_backend")]
+pub struct GStreamerBackend {
+    /// Pipeline descriptions by camera index
+    pipelines: BTreeMap<u32, String>,
+    open: Option<OpenPipeline>,
+}
+
+#[cfg(feature = "gstreamer_backend")]
+struct OpenPipeline {
+    index: u32,
+    pipeline: gst::Pipeline,
+    sink: gst_app::AppSink,
+}
+
+#[cfg(feature = "gstreamer_backend")]
+impl GStreamerBackend {
+    /// Pipelines from `MCP_WEBCAM_GST_<n>` variables, or `None` if there are
+    /// none or GStreamer can't be initialised
+    pub fn from_env() -> Option<Self> {
+        let pipelines: BTreeMap<u32, String> = std::env::vars()
+            .filter_map(|(key, value)| {
+                let n = key.strip_prefix("MCP_WEBCAM_GST_")?.parse::<u32>().ok()?;
+                Some((GSTREAMER_INDEX_BASE + n, value))
+            })
+            .collect();
+        if pipelines.is_empty() {
+            return None;
+        }
+
+        match Self::new(pipelines) {
+            Ok(backend) => {
+                info!("Loaded {} GStreamer pipeline camera(s)", backend.pipelines.len());
+                Some(backend)
+            }
+            Err(e) => {
+                tracing::error!("Ignoring MCP_WEBCAM_GST_* cameras: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Backend serving `pipelines`, keyed by camera index
+    pub fn new(pipelines: BTreeMap<u32, String>) -> Result<Self, WebcamError> {
+        gst::init().map_err(|e| WebcamError::GStreamer(e.to_string()))?;
+        Ok(Self { pipelines, open: None })
+    }
+
+    fn open_pipeline(&mut self) -> Result<&mut OpenPipeline, WebcamError> {
+        self.open.as_mut().ok_or(WebcamError::CameraNotOpen)
+    }
+
+    /// Build and start the pipeline for camera `index`
+    fn launch(index: u32, description: &str) -> Result<OpenPipeline, WebcamError> {
+        let pipeline_error = |message: String| WebcamError::GStreamerPipeline { index, message };
+
+        let pipeline = gst::parse::launch(description)
+            .map_err(|e| pipeline_error(e.to_string()))?
+            .downcast::<gst::Pipeline>()
+            .map_err(|_| pipeline_error("description must contain a source and an appsink".to_string()))?;
+        let sink = pipeline
+            .iterate_sinks()
+            .into_iter()
+            .filter_map(Result::ok)
+            .find_map(|element| element.downcast::<gst_app::AppSink>().ok())
+            .ok_or_else(|| pipeline_error("pipeline has no appsink".to_string()))?;
+
+        // Keep only the newest frame, like a live camera
+        sink.set_caps(Some(&gst_video::VideoCapsBuilder::new().format(gst_video::VideoFormat::Rgb).build()));
+        sink.set_max_buffers(1);
+        sink.set_drop(true);
+
+        let started = pipeline.set_state(gst::State::Playing).is_ok()
+            && pipeline.state(gst::ClockTime::from_seconds(GSTREAMER_TIMEOUT_SECS)).0.is_ok();
+        if !started {
+            let message = bus_error(&pipeline).unwrap_or_else(|| "pipeline failed to start".to_string());
+            let _ = pipeline.set_state(gst::State::Null);
+            return Err(pipeline_error(message));
+        }
+
+        Ok(OpenPipeline { index, pipeline, sink })
+    }
+
+    /// Next sample from the open pipeline
+    fn pull(&mut self) -> Result<gst::Sample, WebcamError> {
+        let open = self.open_pipeline()?;
+        open.sink
+            .try_pull_sample(gst::ClockTime::from_seconds(GSTREAMER_TIMEOUT_SECS))
+            .ok_or_else(|| {
+                let message = bus_error(&open.pipeline).unwrap_or_else(|| "no frame from pipeline".to_string());
+                WebcamError::GStreamerPipeline { index: open.index, message }
+            })
+    }
+}
+
+/// Text of the first error posted on a pipeline's bus, with debug details
+#[cfg(feature = "gstreamer_backend")]
+fn bus_error(pipeline: &gst::Pipeline) -> Option<String> {
+    let message = pipeline.bus()?.timed_pop_filtered(gst::ClockTime::ZERO, &[gst::MessageType::Error])?;
+    match message.view() {
+        gst::MessageView::Error(err) => Some(match err.debug() {
+            Some(debug) => format!("{} ({})", err.error(), debug),
+            None => err.error().to_string(),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "gstreamer_backend")]
+impl CaptureBackend for GStreamerBackend {
+    fn name(&self) -> &'static str {
+        "gstreamer"
+    }
+
+    fn owns(&self, index: u32) -> bool {
+        self.pipelines.contains_key(&index)
+    }
+
+    fn list_devices(&self) -> Result<Vec<CameraInfo>, WebcamError> {
+        Ok(self
+            .pipelines
+            .iter()
+            .map(|(&index, description)| CameraInfo {
+                index,
+                name: format!("GStreamer Camera {}", index - GSTREAMER_INDEX_BASE),
+                description: description.clone(),
+                available: true,
+                fake: false,
+                backend: self.name().to_string(),
+                device_id: None,
+            })
+            .collect())
+    }
+
+    fn open(&mut self, index: u32, _resolution: Option<(u32, u32)>) -> Result<(), WebcamError> {
+        self.close();
+
+        // The resolution is whatever the pipeline produces; frames are
+        // scaled in software afterwards
+        let description = self.pipelines.get(&index).ok_or(WebcamError::CameraNotFound { index })?;
+        info!("Starting GStreamer pipeline for camera {}: {}", index, description);
+        self.open = Some(Self::launch(index, description)?);
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if let Some(open) = self.open.take() {
+            let _ = open.pipeline.set_state(gst::State::Null);
+            debug!("Stopped GStreamer pipeline for camera {}", open.index);
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.open.is_some()
+    }
+
+    fn camera_name(&self) -> Option<String> {
+        self.open.as_ref().map(|open| format!("GStreamer Camera {}", open.index - GSTREAMER_INDEX_BASE))
+    }
+
+    fn format(&self) -> Option<StreamFormat> {
+        let caps = self.open.as_ref()?.sink.static_pad("sink")?.current_caps()?;
+        let info = gst_video::VideoInfo::from_caps(&caps).ok()?;
+        let fps = info.fps();
+        Some(StreamFormat {
+            width: info.width(),
+            height: info.height(),
+            pixel_format: info.format().to_str().to_string(),
+            frame_rate: if fps.denom() > 0 { (fps.numer() / fps.denom()) as u32 } else { 0 },
+        })
+    }
+
+    fn grab_frame(&mut self) -> Result<RgbImage, WebcamError> {
+        let sample = self.pull()?;
+        let invalid = |message: &str| WebcamError::GStreamer(message.to_string());
+
+        let caps = sample.caps().ok_or_else(|| invalid("sample has no caps"))?;
+        let info = gst_video::VideoInfo::from_caps(caps).map_err(|e| WebcamError::GStreamer(e.to_string()))?;
+        let buffer = sample.buffer().ok_or_else(|| invalid("sample has no buffer"))?;
+        let map = buffer.map_readable().map_err(|e| WebcamError::GStreamer(e.to_string()))?;
+
+        // Rows may be padded to the stride
+        let (width, height) = (info.width(), info.height());
+        let stride = info.stride()[0] as usize;
+        let row_bytes = width as usize * 3;
+        let data = map.as_slice();
+        let mut pixels = Vec::with_capacity(row_bytes * height as usize);
+        for row in 0..height as usize {
+            let start = row * stride;
+            pixels.extend_from_slice(data.get(start..start + row_bytes).ok_or_else(|| invalid("truncated frame"))?);
+        }
+
+        RgbImage::from_raw(width, height, pixels).ok_or_else(|| invalid("truncated frame"))
+    }
+
+    fn skip_frame(&mut self) -> Result<(), WebcamError> {
+        self.pull().map(|_| ())
+    }
+
+    fn set_control(&mut self, control: CameraControl, _value: i64) -> Result<(), WebcamError> {
+        Err(WebcamError::InvalidOptions(format!("{:?} is not supported by GStreamer pipeline cameras", control)))
+    }
+}
+
+/// Camera index of libcamera camera 0; camera `n` is listed at
+/// `LIBCAMERA_INDEX_BASE + n`, clear of platform devices
+pub const LIBCAMERA_INDEX_BASE: u32 = 300;
+
+/// Still capture tools, newest name first (`libcamera-still` is the name on
+/// Raspberry Pi OS before Bookworm)
+#[cfg(feature = "libcamera")]
+const LIBCAMERA_STILL_COMMANDS: &[&str] = &["rpicam-still", "libcamera-still"];
+
+/// Time the camera runs before each still, so exposure and white balance settle
+#[cfg(feature = "libcamera")]
+const LIBCAMERA_SETTLE_MS: u32 = 500;
+
+/// A CSI camera reported by `--list-cameras`
+#[cfg(feature = "libcamera")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LibcameraDevice {
+    /// libcamera's own camera number
+    number: u32,
+    sensor: String,
+    /// Full sensor resolution
+    resolution: (u32, u32),
+}
+
+/// Raspberry Pi CSI cameras, which the Pi 5 doesn't expose as plain V4L2
+/// devices. Each frame is a still taken by `rpicam-still` into a temporary
+/// file, so captures are slow but use the full libcamera tuning.
+#[cfg(feature = "libcamera")]
+pub struct LibcameraBackend {
+    command: &'static str,
+    devices: Vec<LibcameraDevice>,
+    /// Open camera and the resolution requested for it
+    open: Option<(u32, Option<(u32, u32)>)>,
+    stills: u64,
+}
+
+#[cfg(feature = "libcamera")]
+impl LibcameraBackend {
+    /// Find a still capture tool and the cameras it reports, or `None` if
+    /// neither tool is installed or no camera is connected
+    pub fn detect() -> Option<Self> {
+        for command in LIBCAMERA_STILL_COMMANDS {
+            let output = match std::process::Command::new(command).arg("--list-cameras").output() {
+                Ok(output) => output,
+                Err(e) => {
+                    debug!("{} not usable: {}", command, e);
+                    continue;
+                }
+            };
+            // Older releases print the list on stderr
+            let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+            let devices = parse_camera_list(&text);
+            info!("{} reports {} CSI camera(s)", command, devices.len());
+            return (!devices.is_empty()).then(|| Self { command, devices, open: None, stills: 0 });
+        }
+        None
+    }
+
+    fn device(&self, index: u32) -> Option<&LibcameraDevice> {
+        let number = index.checked_sub(LIBCAMERA_INDEX_BASE)?;
+        self.devices.iter().find(|d| d.number == number)
+    }
+
+    fn device_name(device: &LibcameraDevice) -> String {
+        format!("Raspberry Pi Camera {} ({})", device.number, device.sensor)
+    }
+}
+
+/// Cameras in `rpicam-still --list-cameras` output, whose entries look like
+/// `0 : imx708 [4608x2592 10-bit RGGB] (/base/.../imx708@1a)`
+#[cfg(feature = "libcamera")]
+fn parse_camera_list(text: &str) -> Vec<LibcameraDevice> {
+    let entry = regex::Regex::new(r"^\s*(\d+)\s*:\s*(\S+)\s*\[(\d+)x(\d+)").expect("valid regex");
+    text.lines()
+        .filter_map(|line| {
+            let captures = entry.captures(line)?;
+            Some(LibcameraDevice {
+                number: captures[1].parse().ok()?,
+                sensor: captures[2].to_string(),
+                resolution: (captures[3].parse().ok()?, captures[4].parse().ok()?),
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "libcamera")]
+impl CaptureBackend for LibcameraBackend {
+    fn name(&self) -> &'static str {
+        "libcamera"
+    }
+
+    fn owns(&self, index: u32) -> bool {
+        self.device(index).is_some()
+    }
+
+    fn list_devices(&self) -> Result<Vec<CameraInfo>, WebcamError> {
+        Ok(self
+            .devices
+            .iter()
+            .map(|device| CameraInfo {
+                index: LIBCAMERA_INDEX_BASE + device.number,
+                name: Self::device_name(device),
+                description: format!(
+                    "Raspberry Pi CSI camera via libcamera ({} sensor, {}x{})",
+                    device.sensor, device.resolution.0, device.resolution.1
+                ),
+                available: true,
+                fake: false,
+                backend: self.name().to_string(),
+                device_id: None,
+            })
+            .collect())
+    }
+
+    fn open(&mut self, index: u32, resolution: Option<(u32, u32)>) -> Result<(), WebcamError> {
+        // Nothing stays open between stills; just remember the settings
+        self.device(index).ok_or(WebcamError::CameraNotFound { index })?;
+        self.open = Some((index, resolution));
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.open = None;
+    }
+
+    fn is_open(&self) -> bool {
+        self.open.is_some()
+    }
+
+    fn camera_name(&self) -> Option<String> {
+        let (index, _) = self.open?;
+        self.device(index).map(Self::device_name)
+    }
+
+    fn format(&self) -> Option<StreamFormat> {
+        let (index, resolution) = self.open?;
+        let (width, height) = resolution.or_else(|| self.device(index).map(|d| d.resolution))?;
+        Some(StreamFormat { width, height, pixel_format: "JPEG".to_string(), frame_rate: 0 })
+    }
+
+    fn grab_frame(&mut self) -> Result<RgbImage, WebcamError> {
+        let (index, resolution) = self.open.ok_or(WebcamError::CameraNotOpen)?;
+        let number = index - LIBCAMERA_INDEX_BASE;
+
+        self.stills += 1;
+        let path = std::env::temp_dir().join(format!("mcp-webcam-libcamera-{}-{}.jpg", std::process::id(), self.stills));
+        let mut command = std::process::Command::new(self.command);
+        command
+            .args(["--camera", &number.to_string()])
+            .args(["--nopreview", "--timeout", &LIBCAMERA_SETTLE_MS.to_string()])
+            .arg("--output")
+            .arg(&path);
+        if let Some((width, height)) = resolution {
+            command.args(["--width", &width.to_string(), "--height", &height.to_string()]);
+        }
+
+        debug!("Running {:?}", command);
+        let output = command
+            .output()
+            .map_err(|e| WebcamError::Libcamera(format!("failed to run {}: {}", self.command, e)))?;
+        let result = if output.status.success() {
+            image::open(&path).map(|image| image.into_rgb8()).map_err(WebcamError::from)
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("no output");
+            Err(WebcamError::Libcamera(format!("{} exited with {}: {}", self.command, output.status, message.trim())))
+        };
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    fn set_control(&mut self, control: CameraControl, _value: i64) -> Result<(), WebcamError> {
+        Err(WebcamError::InvalidOptions(format!("{:?} is not supported by libcamera cameras", control)))
+    }
+}
+
+/// Backend for builds without any platform camera support
+#[cfg(not(any(feature = "local_cameras", feature = "opencv_backend")))]
+pub struct UnsupportedBackend;
+
+#[cfg(not(any(feature = "local_cameras", feature = "opencv_backend")))]
+impl CaptureBackend for UnsupportedBackend {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn list_devices(&self) -> Result<Vec<CameraInfo>, WebcamError> {
+        tracing::warn!("Local camera support not compiled in");
+        Err(WebcamError::LocalCamerasNotSupported)
+    }
+
+    fn open(&mut self, _index: u32, _resolution: Option<(u32, u32)>) -> Result<(), WebcamError> {
+        tracing::error!("Local camera support not compiled in");
+        Err(WebcamError::LocalCamerasNotSupported)
+    }
+
+    fn close(&mut self) {}
+
+    fn is_open(&self) -> bool {
+        false
+    }
+
+    fn camera_name(&self) -> Option<String> {
+        None
+    }
+
+    fn format(&self) -> Option<StreamFormat> {
+        None
+    }
+
+    fn grab_frame(&mut self) -> Result<RgbImage, WebcamError> {
+        Err(WebcamError::LocalCamerasNotSupported)
+    }
+
+    fn set_control(&mut self, _control: CameraControl, _value: i64) -> Result<(), WebcamError> {
+        Err(WebcamError::LocalCamerasNotSupported)
+    }
+}
+
+/// Call counters of a `MockBackend`, shared so tests can read them after
+/// handing the backend to a `WebcamManager`
+#[derive(Debug, Default)]
+pub struct MockStats {
+    /// Number of times a camera was opened
+    pub opens: AtomicU64,
+    /// Frames grabbed or skipped
+    pub frames_grabbed: AtomicU64,
+    /// Number of times devices were listed
+    pub enumerations: AtomicU64,
+    /// Last white balance set, in Kelvin; zero if never set
+    pub white_balance: AtomicI64,
+}
+
+/// In-memory backend for tests: a fixed set of cameras that all return a
+/// copy of the same frame. Supports no controls unless white balance is
+/// enabled with `with_white_balance_control`.
+#[derive(Debug)]
+pub struct MockBackend {
+    devices: Vec<CameraInfo>,
+    frame: RgbImage,
+    open: Option<u32>,
+    /// Fail every open with EBUSY, as if another application had the camera
+    busy: bool,
+    /// Accept white balance settings instead of rejecting every control
+    white_balance_control: bool,
+    /// While set, every camera is gone, as if unplugged
+    unplugged: Arc<AtomicBool>,
+    stats: Arc<MockStats>,
+}
+
+impl MockBackend {
+    /// `cameras` cameras at indices 0.., returning mid-grey 320x240 frames
+    pub fn new(cameras: u32) -> Self {
+        let devices = (0..cameras)
+            .map(|index| CameraInfo {
+                index,
+                name: format!("Mock Camera {}", index),
+                description: "Mock capture backend".to_string(),
+                available: true,
+                fake: true,
+                backend: "mock".to_string(),
+                device_id: Some(format!("mock-{}", index)),
+            })
+            .collect();
+        Self {
+            devices,
+            frame: RgbImage::from_pixel(320, 240, image::Rgb([128, 128, 128])),
+            open: None,
+            busy: false,
+            white_balance_control: false,
+            unplugged: Arc::default(),
+            stats: Arc::default(),
+        }
+    }
+
+    /// Return this frame from every camera
+    pub fn with_frame(mut self, frame: RgbImage) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    /// Fail to open any camera, as if another application were using it
+    pub fn busy(mut self) -> Self {
+        self.busy = true;
+        self
+    }
+
+    /// Accept white balance settings, like a camera exposing that control
+    pub fn with_white_balance_control(mut self) -> Self {
+        self.white_balance_control = true;
+        self
+    }
+
+    pub fn stats(&self) -> Arc<MockStats> {
+        Arc::clone(&self.stats)
+    }
+
+    /// Switch that unplugs every camera while set, shared so tests can flip
+    /// it after handing the backend to a `WebcamManager`
+    pub fn unplug_switch(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.unplugged)
+    }
+
+    fn is_unplugged(&self) -> bool {
+        self.unplugged.load(Ordering::Relaxed)
+    }
+}
+
+impl CaptureBackend for MockBackend {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn list_devices(&self) -> Result<Vec<CameraInfo>, WebcamError> {
+        self.stats.enumerations.fetch_add(1, Ordering::Relaxed);
+        if self.is_unplugged() {
+            return Ok(Vec::new());
+        }
+        Ok(self.devices.clone())
+    }
+
+    fn open(&mut self, index: u32, _resolution: Option<(u32, u32)>) -> Result<(), WebcamError> {
+        if self.is_unplugged() || !self.devices.iter().any(|d| d.index == index) {
+            return Err(WebcamError::CameraNotFound { index });
+        }
+        if self.busy {
+            return Err(WebcamError::Storage(std::io::Error::new(std::io::ErrorKind::Other, "Device or resource busy")));
+        }
+        self.open = Some(index);
+        self.stats.opens.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.open = None;
+    }
+
+    fn is_open(&self) -> bool {
+        self.open.is_some()
+    }
+
+    fn camera_name(&self) -> Option<String> {
+        let index = self.open?;
+        self.devices.iter().find(|d| d.index == index).map(|d| d.name.clone())
+    }
+
+    fn device_id(&self) -> Option<String> {
+        self.open.map(|index| format!("mock-{}", index))
+    }
+
+    fn format(&self) -> Option<StreamFormat> {
+        self.open.map(|_| StreamFormat {
+            width: self.frame.width(),
+            height: self.frame.height(),
+            pixel_format: "RGB".to_string(),
+            frame_rate: 30,
+        })
+    }
+
+    fn grab_frame(&mut self) -> Result<RgbImage, WebcamError> {
+        if self.open.is_none() {
+            return Err(WebcamError::CameraNotOpen);
+        }
+        if self.is_unplugged() {
+            return Err(WebcamError::Storage(std::io::Error::new(std::io::ErrorKind::NotFound, "No such device")));
+        }
+        self.stats.frames_grabbed.fetch_add(1, Ordering::Relaxed);
+        Ok(self.frame.clone())
+    }
+
+    fn set_control(&mut self, control: CameraControl, value: i64) -> Result<(), WebcamError> {
+        match control {
+            CameraControl::WhiteBalance if self.white_balance_control => {
+                self.stats.white_balance.store(value, Ordering::Relaxed);
+                Ok(())
+            }
+            _ => Err(WebcamError::InvalidOptions(format!("{:?} is not supported by the mock backend", control))),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "libcamera"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_camera_list() {
+        let output = "Available cameras\n\
+                      -----------------\n\
+                      0 : imx708 [4608x2592 10-bit RGGB] (/base/axi/pcie@120000/rp1/i2c@88000/imx708@1a)\n    \
+                      Modes: 'SRGGB10_CSI2P' : 1536x864 [120.13 fps - (768, 432)/3072x1728 crop]\n\
+                      1 : ov5647 [2592x1944 10-bit GBRG] (/base/axi/pcie@120000/rp1/i2c@80000/ov5647@36)\n";
+
+        assert_eq!(parse_camera_list(output), vec![
+            LibcameraDevice { number: 0, sensor: "imx708".to_string(), resolution: (4608, 2592) },
+            LibcameraDevice { number: 1, sensor: "ov5647".to_string(), resolution: (2592, 1944) },
+        ]);
+        assert!(parse_camera_list("No cameras available!").is_empty());
+    }
+}
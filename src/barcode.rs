@@ -0,0 +1,117 @@
+use crate::image_processing::CropRect;
+use image::RgbImage;
+use serde::Serialize;
+
+/// A code decoded from a frame
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedCode {
+    /// Decoded text payload
+    pub payload: String,
+    /// Symbology, e.g. "QR_CODE", "EAN_13", "CODE_128"
+    pub symbology: String,
+    /// Axis-aligned bounding box around the detected points
+    pub bounding_box: CropRect,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BarcodeError {
+    #[error("Barcode support not compiled in (enable the 'barcodes' feature)")]
+    NotSupported,
+    #[error("Barcode decoding failed: {0}")]
+    Decode(String),
+}
+
+/// Detect and decode every QR code and barcode in the frame.
+/// An empty list means the frame was scanned successfully but held no codes.
+pub fn scan(img: &RgbImage) -> Result<Vec<DecodedCode>, BarcodeError> {
+    #[cfg(feature = "barcodes")]
+    {
+        use rxing::Exceptions;
+
+        let luma = image::DynamicImage::ImageRgb8(img.clone()).into_luma8();
+        let (width, height) = (luma.width(), luma.height());
+
+        let results = match rxing::helpers::detect_multiple_in_luma(luma.into_raw(), width, height) {
+            Ok(results) => results,
+            Err(Exceptions::NotFoundException(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(BarcodeError::Decode(e.to_string())),
+        };
+
+        Ok(results
+            .iter()
+            .map(|result| {
+                let points = result.getPoints();
+                let min_x = points.iter().map(|p| p.x).fold(f32::MAX, f32::min).max(0.0);
+                let min_y = points.iter().map(|p| p.y).fold(f32::MAX, f32::min).max(0.0);
+                let max_x = points.iter().map(|p| p.x).fold(0.0, f32::max);
+                let max_y = points.iter().map(|p| p.y).fold(0.0, f32::max);
+                let bounding_box = if points.is_empty() {
+                    CropRect { x: 0, y: 0, width, height }
+                } else {
+                    CropRect {
+                        x: min_x as u32,
+                        y: min_y as u32,
+                        width: (max_x - min_x).max(1.0) as u32,
+                        height: (max_y - min_y).max(1.0) as u32,
+                    }
+                };
+
+                DecodedCode {
+                    payload: result.getText().to_string(),
+                    symbology: format!("{:?}", result.getBarcodeFormat()),
+                    bounding_box,
+                }
+            })
+            .collect())
+    }
+
+    #[cfg(not(feature = "barcodes"))]
+    {
+        let _ = img;
+        Err(BarcodeError::NotSupported)
+    }
+}
+
+/// Copy of the frame with each detected code outlined
+pub fn annotate(img: &RgbImage, codes: &[DecodedCode]) -> RgbImage {
+    let mut annotated = img.clone();
+    let thickness = (img.width().max(img.height()) / 300).max(2);
+    for code in codes {
+        crate::image_processing::draw_rect(&mut annotated, &code.bounding_box, [0, 255, 0], thickness);
+    }
+    annotated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotation_outlines_each_code_and_leaves_the_rest() {
+        let img = RgbImage::from_pixel(20, 10, image::Rgb([255, 255, 255]));
+        let code = DecodedCode {
+            payload: "hello".to_string(),
+            symbology: "QR_CODE".to_string(),
+            bounding_box: CropRect { x: 4, y: 2, width: 6, height: 5 },
+        };
+        let annotated = annotate(&img, &[code]);
+        assert_eq!(annotated.get_pixel(4, 2).0, [0, 255, 0]);
+        assert_eq!(annotated.get_pixel(9, 6).0, [0, 255, 0]);
+        assert_eq!(annotated.get_pixel(0, 0).0, [255, 255, 255]);
+        assert_eq!(annotate(&img, &[]), img);
+    }
+
+    #[cfg(feature = "barcodes")]
+    #[test]
+    fn frames_without_codes_scan_as_empty() {
+        let blank = RgbImage::from_pixel(64, 64, image::Rgb([255, 255, 255]));
+        assert!(scan(&blank).unwrap().is_empty());
+    }
+
+    #[cfg(not(feature = "barcodes"))]
+    #[test]
+    fn scanning_needs_the_barcodes_feature() {
+        let blank = RgbImage::from_pixel(8, 8, image::Rgb([255, 255, 255]));
+        assert!(matches!(scan(&blank), Err(BarcodeError::NotSupported)));
+    }
+}
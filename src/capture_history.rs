@@ -0,0 +1,226 @@
+use crate::webcam::CaptureResult;
+use serde::Serialize;
+use std::collections::VecDeque;
+use tracing::debug;
+
+/// Default number of captures kept in memory
+pub const DEFAULT_HISTORY_CAPACITY: usize = 20;
+/// Default upper bound on the total size of stored image data (64 MiB)
+pub const DEFAULT_HISTORY_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// A capture kept in the history, together with its generated id
+#[derive(Debug, Clone)]
+pub struct StoredCapture {
+    pub id: String,
+    pub result: CaptureResult,
+}
+
+impl StoredCapture {
+    /// Size of the stored encoded image in bytes
+    pub fn size_bytes(&self) -> usize {
+        self.result.image_bytes.len()
+    }
+
+    pub fn summary(&self) -> CaptureSummary {
+        CaptureSummary {
+            capture_id: self.id.clone(),
+            timestamp: self.result.timestamp.clone(),
+            camera_index: self.result.camera_index,
+            width: self.result.width,
+            height: self.result.height,
+            mime_type: self.result.mime_type.clone(),
+            size_bytes: self.size_bytes(),
+        }
+    }
+}
+
+/// Capture metadata without image data, as returned by `list_captures`
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureSummary {
+    pub capture_id: String,
+    pub timestamp: String,
+    pub camera_index: u32,
+    pub width: u32,
+    pub height: u32,
+    pub mime_type: String,
+    pub size_bytes: usize,
+}
+
+/// Ring buffer of recent captures, bounded by count and total bytes.
+/// The oldest entries are evicted first.
+#[derive(Debug)]
+pub struct CaptureHistory {
+    entries: VecDeque<StoredCapture>,
+    capacity: usize,
+    max_bytes: usize,
+    total_bytes: usize,
+}
+
+impl CaptureHistory {
+    pub fn new(capacity: usize, max_bytes: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            max_bytes,
+            total_bytes: 0,
+        }
+    }
+
+    /// Build a history from `MCP_WEBCAM_HISTORY_SIZE` and
+    /// `MCP_WEBCAM_HISTORY_MAX_BYTES`, falling back to the defaults
+    pub fn from_env() -> Self {
+        let capacity = std::env::var("MCP_WEBCAM_HISTORY_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_HISTORY_CAPACITY);
+        let max_bytes = std::env::var("MCP_WEBCAM_HISTORY_MAX_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_HISTORY_MAX_BYTES);
+        Self::new(capacity, max_bytes)
+    }
+
+    /// Store a capture and return its generated id. Captures larger than the
+    /// byte budget are still given an id but are not retained.
+    pub fn insert(&mut self, result: CaptureResult) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let entry = StoredCapture { id: id.clone(), result };
+        let size = entry.size_bytes();
+
+        if self.capacity == 0 || size > self.max_bytes {
+            debug!("Capture {} ({} bytes) not retained in history", id, size);
+            return id;
+        }
+
+        while self.entries.len() >= self.capacity || self.total_bytes + size > self.max_bytes {
+            match self.entries.pop_front() {
+                Some(evicted) => {
+                    self.total_bytes -= evicted.size_bytes();
+                    debug!("Evicted capture {} from history", evicted.id);
+                }
+                None => break,
+            }
+        }
+
+        self.total_bytes += size;
+        self.entries.push_back(entry);
+        id
+    }
+
+    pub fn get(&self, id: &str) -> Option<&StoredCapture> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    /// Summaries of all stored captures, oldest first
+    pub fn list(&self) -> Vec<CaptureSummary> {
+        self.entries.iter().map(StoredCapture::summary).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+}
+
+impl Default for CaptureHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_CAPACITY, DEFAULT_HISTORY_MAX_BYTES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    /// A capture from `camera_index` whose image is `size` bytes
+    fn capture(camera_index: u32, size: usize) -> CaptureResult {
+        CaptureResult {
+            image_bytes: Bytes::from(vec![0u8; size]),
+            mime_type: "image/jpeg".to_string(),
+            width: 4,
+            height: 3,
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            camera_index,
+            enhancement: None,
+            white_balance: None,
+            privacy_masked_regions: 0,
+            face_blur: None,
+            rotation: 0,
+            quality: 85,
+            camera_name: None,
+            backend: None,
+            encoder: String::new(),
+            encode_ms: 0.0,
+        }
+    }
+
+    fn cameras(history: &CaptureHistory) -> Vec<u32> {
+        history.list().iter().map(|summary| summary.camera_index).collect()
+    }
+
+    #[test]
+    fn the_oldest_captures_are_evicted_past_the_capacity() {
+        let mut history = CaptureHistory::new(3, 1024);
+        let ids: Vec<String> = (0..5).map(|camera| history.insert(capture(camera, 10))).collect();
+
+        assert_eq!(cameras(&history), [2, 3, 4]);
+        assert_eq!(history.total_bytes(), 30);
+        assert!(history.get(&ids[1]).is_none());
+        assert_eq!(history.get(&ids[4]).unwrap().result.camera_index, 4);
+    }
+
+    #[test]
+    fn the_oldest_captures_are_evicted_past_the_byte_budget() {
+        let mut history = CaptureHistory::new(10, 100);
+        history.insert(capture(0, 40));
+        history.insert(capture(1, 30));
+        history.insert(capture(2, 20));
+        assert_eq!(cameras(&history), [0, 1, 2]);
+        assert_eq!(history.total_bytes(), 90);
+
+        // 60 more bytes only fit once the first two are gone
+        history.insert(capture(3, 60));
+        assert_eq!(cameras(&history), [2, 3]);
+        assert_eq!(history.total_bytes(), 80);
+    }
+
+    #[test]
+    fn captures_over_the_budget_get_an_id_but_are_not_kept() {
+        let mut history = CaptureHistory::new(2, 50);
+        history.insert(capture(0, 20));
+        let id = history.insert(capture(1, 51));
+        assert!(history.get(&id).is_none());
+        assert_eq!(cameras(&history), [0]);
+
+        let mut disabled = CaptureHistory::new(0, 50);
+        disabled.insert(capture(0, 1));
+        assert!(disabled.is_empty());
+        assert_eq!(disabled.total_bytes(), 0);
+    }
+
+    #[test]
+    fn summaries_carry_the_capture_metadata() {
+        let mut history = CaptureHistory::default();
+        let id = history.insert(capture(7, 12));
+        let summary = &history.list()[0];
+        assert_eq!(summary.capture_id, id);
+        assert_eq!((summary.camera_index, summary.width, summary.height, summary.size_bytes), (7, 4, 3, 12));
+        assert_eq!(summary.mime_type, "image/jpeg");
+    }
+}
@@ -0,0 +1,388 @@
+//! Censys Search API as a webcam discovery service, for accounts with
+//! Censys credits rather than Shodan's. Built with the `censys` feature
+//! and used when `CENSYS_API_ID` and `CENSYS_API_SECRET` are set.
+//!
+//! Censys has its own query language, so it runs `CENSYS_WEBCAM_QUERIES`
+//! rather than Shodan's, with the country, city and organization filters
+//! in its syntax. It has no radius search: a `near` filter is only
+//! checked against the results, as the vendor filter is.
+
+use crate::classify::classify;
+use crate::discovery::{ProviderCredits, WebcamDiscoveryProvider};
+use crate::shodan::{
+    collect_webcams_with, retry_after, QueryPlan, RemoteWebcam, RequestLimiter, SearchFilters, SecretString,
+    ShodanClientBuilder, ShodanError, ShodanHost, ShodanLocation, ShodanService, WebcamAccessType, WebcamSearch,
+    DEFAULT_SEARCH_LIMIT,
+};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tracing::{debug, error, info};
+
+/// Tag of the webcams Censys finds, in `RemoteWebcam::sources`
+pub const SOURCE: &str = "censys";
+/// Environment variable holding the Censys API ID
+pub const CENSYS_API_ID_ENV: &str = "CENSYS_API_ID";
+/// Environment variable holding the Censys API secret
+pub const CENSYS_API_SECRET_ENV: &str = "CENSYS_API_SECRET";
+pub const CENSYS_BASE_URL: &str = "https://search.censys.io/api";
+/// Webcam search queries in Censys's language, most productive first
+pub const CENSYS_WEBCAM_QUERIES: &[&str] = &[
+    "services.http.response.headers.content_type: \"multipart/x-mixed-replace\"",
+    "services.http.response.html_title: \"webcam\"",
+    "services.software.product: \"webcamXP\"",
+    "services.service_name: RTSP",
+    "services.http.response.html_title: \"IP Camera\"",
+];
+/// Requests per second sent to Censys, the pace of its free tier
+const CENSYS_REQUESTS_PER_SECOND: f64 = 0.4;
+/// Most hits Censys returns in one page
+const MAX_PER_PAGE: u32 = 100;
+
+/// Client for the Censys Search API, authenticated with an API ID and
+/// secret
+#[derive(Clone)]
+pub struct CensysClient {
+    client: Client,
+    api_id: String,
+    secret: SecretString,
+    base_url: String,
+    limiter: Arc<RequestLimiter>,
+}
+
+impl CensysClient {
+    /// Client for the account with `api_id` and `secret`, connecting with
+    /// the proxy, user agent and timeouts of `settings`
+    pub fn new(api_id: impl Into<String>, secret: impl Into<String>, settings: &ShodanClientBuilder) -> Result<Self, ShodanError> {
+        let secret = secret.into();
+        // Kept out of logs forwarded to the client
+        crate::client_log::ClientLog::global().redact(&secret);
+        Ok(Self {
+            client: settings.api_client()?,
+            api_id: api_id.into(),
+            secret: SecretString::new(secret),
+            base_url: CENSYS_BASE_URL.to_string(),
+            limiter: Arc::new(RequestLimiter::new(CENSYS_REQUESTS_PER_SECOND)),
+        })
+    }
+
+    /// Client for the credentials in `CENSYS_API_ID` and
+    /// `CENSYS_API_SECRET`, or `None` unless both are set
+    pub fn from_env(settings: &ShodanClientBuilder) -> Result<Option<Self>, ShodanError> {
+        let var = |name: &str| std::env::var(name).ok().map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+        match (var(CENSYS_API_ID_ENV), var(CENSYS_API_SECRET_ENV)) {
+            (Some(api_id), Some(secret)) => Self::new(api_id, secret, settings).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Send requests to `base_url` instead of Censys, e.g. a stand-in
+    /// server in tests
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// GET `path` of the API and parse its JSON. 401 and 403 are
+    /// `Unauthorized`, 404 `NotFound` and 429 `RateLimitExceeded`.
+    async fn get<T: DeserializeOwned>(&self, path: &str, params: &[(&str, &str)]) -> Result<T, ShodanError> {
+        self.limiter.acquire().await;
+        let response = self.client
+            .get(format!("{}{}", self.base_url, path))
+            .basic_auth(&self.api_id, Some(self.secret.expose()))
+            .query(params)
+            .send()
+            .await?;
+        match response.status() {
+            reqwest::StatusCode::OK => Ok(response.json().await?),
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Err(ShodanError::Unauthorized),
+            reqwest::StatusCode::NOT_FOUND => Err(ShodanError::NotFound(path.rsplit('/').next().unwrap_or(path).to_string())),
+            reqwest::StatusCode::TOO_MANY_REQUESTS => Err(ShodanError::RateLimitExceeded { retry_after: retry_after(&response) }),
+            status => {
+                let error_text = self.secret.scrub(&response.text().await.unwrap_or_default());
+                error!("Censys API error {}: {}", status, error_text);
+                Err(ShodanError::Generic(format!("Censys HTTP {}: {}", status, error_text)))
+            }
+        }
+    }
+
+    /// Webcams among the first `per_page` hosts matching `query`
+    pub async fn search(&self, query: &str, per_page: u32) -> Result<Vec<RemoteWebcam>, ShodanError> {
+        debug!("Executing Censys search: {}", query);
+        let per_page = per_page.clamp(1, MAX_PER_PAGE).to_string();
+        let response: CensysResponse<CensysSearch> = self.get("/v2/hosts/search", &[("q", query), ("per_page", &per_page)]).await?;
+        Ok(response.result.hits.into_iter().flat_map(CensysHost::into_webcams).collect())
+    }
+
+    /// Up to `limit` webcams matching `filters`, running as many of
+    /// `CENSYS_WEBCAM_QUERIES` as `plan` has queries
+    pub async fn search_webcams(&self, limit: Option<u32>, filters: &SearchFilters, plan: &QueryPlan) -> Result<WebcamSearch, ShodanError> {
+        let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+        let queries: Vec<String> = CENSYS_WEBCAM_QUERIES.iter()
+            .take(plan.queries.len().max(1))
+            .map(|query| query.to_string())
+            .collect();
+        let plan = QueryPlan { planned: plan.planned.clamp(1, queries.len()), queries };
+        info!("Searching for up to {} webcams via Censys with {} queries", limit, plan.queries.len());
+
+        collect_webcams_with(&plan, limit, filters, &query_suffix(filters), |query, share| async move {
+            self.search(&query, share).await
+        })
+        .await
+    }
+
+    /// Everything Censys knows about `ip`. It keeps no history of
+    /// services, so only current ones are listed.
+    pub async fn host(&self, ip: &str) -> Result<ShodanHost, ShodanError> {
+        let ip: IpAddr = ip.trim().parse()
+            .map_err(|_| ShodanError::InvalidQuery(format!("{} is not an IP address", ip)))?;
+        debug!("Looking up Censys host {}", ip);
+        let response: CensysResponse<CensysHost> = self.get(&format!("/v2/hosts/{}", ip), &[]).await?;
+        Ok(response.result.into_host())
+    }
+
+    /// Queries left of the account's quota, and when it renews
+    pub async fn account(&self) -> Result<ProviderCredits, ShodanError> {
+        let account: CensysAccount = self.get("/v1/account", &[]).await?;
+        Ok(ProviderCredits {
+            provider: SOURCE.to_string(),
+            plan: None,
+            query_credits: account.quota.allowance - account.quota.used,
+            scan_credits: None,
+            resets_at: account.quota.resets_at,
+        })
+    }
+}
+
+#[async_trait]
+impl WebcamDiscoveryProvider for CensysClient {
+    fn name(&self) -> &'static str {
+        SOURCE
+    }
+
+    async fn search_webcams(&self, limit: Option<u32>, filters: &SearchFilters, plan: &QueryPlan) -> Result<WebcamSearch, ShodanError> {
+        CensysClient::search_webcams(self, limit, filters, plan).await
+    }
+
+    async fn host(&self, ip: &str, _history: bool) -> Result<ShodanHost, ShodanError> {
+        CensysClient::host(self, ip).await
+    }
+
+    async fn credits(&self) -> Result<ProviderCredits, ShodanError> {
+        self.account().await
+    }
+}
+
+/// The filters in Censys's query language, to append to a query
+fn query_suffix(filters: &SearchFilters) -> String {
+    [("location.country_code", &filters.country), ("location.city", &filters.city), ("autonomous_system.name", &filters.org)]
+        .into_iter()
+        .filter_map(|(field, value)| value.as_ref().map(|value| format!(" and {}: \"{}\"", field, value)))
+        .collect()
+}
+
+/// Every v2 answer wraps its result
+#[derive(Deserialize)]
+struct CensysResponse<T> {
+    result: T,
+}
+
+#[derive(Deserialize)]
+struct CensysSearch {
+    #[serde(default)]
+    hits: Vec<CensysHost>,
+}
+
+/// A host, as a search hit or from `/v2/hosts/{ip}`; hits carry fewer
+/// service details
+#[derive(Deserialize)]
+struct CensysHost {
+    ip: String,
+    #[serde(default)]
+    services: Vec<CensysService>,
+    /// Of a hit, the services the query matched
+    #[serde(default)]
+    matched_services: Vec<CensysService>,
+    location: Option<CensysLocation>,
+    autonomous_system: Option<CensysAutonomousSystem>,
+    dns: Option<CensysDns>,
+    operating_system: Option<CensysSoftware>,
+    #[serde(default)]
+    labels: Vec<String>,
+    last_updated_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CensysService {
+    port: u16,
+    #[serde(default)]
+    service_name: String,
+    #[serde(default)]
+    extended_service_name: String,
+    transport_protocol: Option<String>,
+    #[serde(default)]
+    software: Vec<CensysSoftware>,
+    observed_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CensysSoftware {
+    vendor: Option<String>,
+    product: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CensysLocation {
+    country: Option<String>,
+    country_code: Option<String>,
+    city: Option<String>,
+    coordinates: Option<CensysCoordinates>,
+}
+
+#[derive(Deserialize)]
+struct CensysCoordinates {
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Deserialize)]
+struct CensysAutonomousSystem {
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CensysDns {
+    #[serde(default)]
+    names: Vec<String>,
+    reverse_dns: Option<CensysReverseDns>,
+}
+
+#[derive(Deserialize)]
+struct CensysReverseDns {
+    #[serde(default)]
+    names: Vec<String>,
+}
+
+/// `/v1/account`
+#[derive(Deserialize)]
+struct CensysAccount {
+    quota: CensysQuota,
+}
+
+#[derive(Deserialize)]
+struct CensysQuota {
+    used: i64,
+    allowance: i64,
+    resets_at: Option<String>,
+}
+
+impl CensysService {
+    fn product(&self) -> Option<String> {
+        self.software.iter().find_map(|software| software.product.clone())
+    }
+
+    /// Vendors and products, as the banner `classify` reads
+    fn banner(&self) -> String {
+        self.software.iter()
+            .flat_map(|software| [software.vendor.as_deref(), software.product.as_deref()])
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl CensysHost {
+    fn location(&self) -> Option<ShodanLocation> {
+        self.location.as_ref().map(|location| ShodanLocation {
+            country_code: location.country_code.clone(),
+            country_name: location.country.clone(),
+            city: location.city.clone(),
+            region_code: None,
+            latitude: location.coordinates.as_ref().map(|c| c.latitude),
+            longitude: location.coordinates.as_ref().map(|c| c.longitude),
+        })
+    }
+
+    fn hostnames(&self) -> Vec<String> {
+        let Some(dns) = &self.dns else { return Vec::new() };
+        let mut names = dns.names.clone();
+        for name in dns.reverse_dns.iter().flat_map(|reverse| &reverse.names) {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names
+    }
+
+    /// One webcam per HTTP or RTSP service, of those the query matched
+    /// if the hit says
+    fn into_webcams(self) -> Vec<RemoteWebcam> {
+        let location = self.location();
+        let hostname = self.hostnames().into_iter().next();
+        let org = self.autonomous_system.as_ref().and_then(|system| system.name.clone());
+        let host = if self.ip.contains(':') { format!("[{}]", self.ip) } else { self.ip.clone() };
+        let services = if self.matched_services.is_empty() { &self.services } else { &self.matched_services };
+        services.iter()
+            .filter_map(|service| {
+                let (access_type, url) = match service.service_name.as_str() {
+                    "RTSP" => (WebcamAccessType::RTSP, format!("rtsp://{}:{}/", host, service.port)),
+                    "HTTP" if service.extended_service_name == "HTTPS" => (WebcamAccessType::HTTP, format!("https://{}:{}/", host, service.port)),
+                    "HTTP" => (WebcamAccessType::HTTP, format!("http://{}:{}/", host, service.port)),
+                    _ => return None,
+                };
+                let product = service.product();
+                Some(RemoteWebcam {
+                    ip: self.ip.clone(),
+                    port: service.port,
+                    url,
+                    hostname: hostname.clone(),
+                    location: location.clone(),
+                    org: org.clone(),
+                    classification: classify(product.as_deref(), &service.banner(), service.port),
+                    product,
+                    last_seen: service.observed_at.clone().or_else(|| self.last_updated_at.clone()).unwrap_or_default(),
+                    access_type,
+                    sources: vec![SOURCE.to_string()],
+                })
+            })
+            .collect()
+    }
+
+    fn into_host(self) -> ShodanHost {
+        let location = self.location().unwrap_or(ShodanLocation {
+            country_code: None,
+            country_name: None,
+            city: None,
+            region_code: None,
+            latitude: None,
+            longitude: None,
+        });
+        let hostnames = self.hostnames();
+        let mut ports: Vec<u16> = self.services.iter().map(|service| service.port).collect();
+        ports.sort_unstable();
+        ports.dedup();
+        ShodanHost {
+            ip: self.ip,
+            ports,
+            hostnames,
+            org: self.autonomous_system.and_then(|system| system.name),
+            isp: None,
+            os: self.operating_system.and_then(|os| os.product),
+            location,
+            tags: self.labels,
+            vulns: Vec::new(),
+            last_update: self.last_updated_at,
+            services: self.services.into_iter()
+                .map(|service| ShodanService {
+                    port: service.port,
+                    transport: service.transport_protocol.map(|transport| transport.to_lowercase()),
+                    product: service.product(),
+                    timestamp: service.observed_at.unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Subject and issuer of the X.509 certificate a webcam presents, so that
+//! a TLS failure can say whose certificate it was. Only the parts of DER
+//! needed to reach the two names are read.
+
+use serde::Serialize;
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+/// `[0]`, the explicit tag of a certificate's version
+const VERSION: u8 = 0xA0;
+const BMP_STRING: u8 = 0x1E;
+
+/// Attributes of a name shown, by the last bytes of their `2.5.4.x` OID
+const ATTRIBUTES: [(u8, &str); 7] = [
+    (3, "CN"),
+    (11, "OU"),
+    (10, "O"),
+    (7, "L"),
+    (8, "ST"),
+    (6, "C"),
+    (5, "serialNumber"),
+];
+
+/// Distinguished names of a certificate, e.g. `C=GB, O=Example, CN=cam.local`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CertificateNames {
+    pub subject: String,
+    pub issuer: String,
+}
+
+impl CertificateNames {
+    /// Whether the certificate was issued by its own subject
+    pub fn self_signed(&self) -> bool {
+        self.subject == self.issuer
+    }
+}
+
+/// Subject and issuer of the DER-encoded certificate `der`, or `None` if
+/// it can't be read
+pub fn certificate_names(der: &[u8]) -> Option<CertificateNames> {
+    let (tag, certificate, _) = element(der)?;
+    if tag != SEQUENCE {
+        return None;
+    }
+    let (_, tbs, _) = element(certificate)?;
+    let (tag, _, mut rest) = element(tbs)?;
+    if tag == VERSION {
+        // The version came first; skip the serial number too
+        rest = element(rest)?.2;
+    }
+    rest = element(rest)?.2; // signature algorithm
+    let (_, issuer, rest) = element(rest)?;
+    let rest = element(rest)?.2; // validity
+    let (_, subject, _) = element(rest)?;
+    Some(CertificateNames { subject: name(subject)?, issuer: name(issuer)? })
+}
+
+/// One DER element of `input`: its tag, its content and what follows it
+fn element(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (length, rest) = if first & 0x80 == 0 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7F) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let length = rest[..count].iter().fold(0usize, |length, byte| (length << 8) | *byte as usize);
+        (length, &rest[count..])
+    };
+    (rest.len() >= length).then(|| (tag, &rest[..length], &rest[length..]))
+}
+
+/// A `Name` as `TYPE=value` pairs in certificate order, leaving out
+/// attribute types not in `ATTRIBUTES`
+fn name(mut input: &[u8]) -> Option<String> {
+    let mut parts = Vec::new();
+    while !input.is_empty() {
+        let (tag, mut set, rest) = element(input)?;
+        input = rest;
+        if tag != SET {
+            continue;
+        }
+        while !set.is_empty() {
+            let (_, attribute, rest) = element(set)?;
+            set = rest;
+            let (tag, oid, value) = element(attribute)?;
+            let label = match oid {
+                [0x55, 0x04, last] if tag == OBJECT_IDENTIFIER => ATTRIBUTES.iter().find(|(id, _)| id == last).map(|(_, label)| *label),
+                _ => None,
+            };
+            if let Some(label) = label {
+                let (tag, value, _) = element(value)?;
+                parts.push(format!("{}={}", label, text(tag, value)));
+            }
+        }
+    }
+    Some(parts.join(", "))
+}
+
+/// A directory string's text: UTF-16 for `BMPString`, else read as UTF-8
+fn text(tag: u8, value: &[u8]) -> String {
+    if tag == BMP_STRING {
+        let units: Vec<u16> = value.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(value).into_owned()
+    }
+}
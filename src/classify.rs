@@ -0,0 +1,217 @@
+//! Which make a discovered webcam is, from the product Shodan names, the
+//! banner it collected and the port. A table of rules is matched against
+//! each result; the most certain match wins and brings the make's snapshot
+//! path, which probing tries first.
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Camera make, or family of webcam software
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Vendor {
+    Axis,
+    Hikvision,
+    Dahua,
+    Foscam,
+    Mobotix,
+    Dlink,
+    Panasonic,
+    Sony,
+    Vivotek,
+    /// The Android IP Webcam app
+    IpWebcam,
+    Yawcam,
+    WebcamXp,
+    /// An MJPEG stream from no make the rules know
+    GenericMjpeg,
+}
+
+impl Vendor {
+    pub const ALL: [Vendor; 13] = [
+        Vendor::Axis,
+        Vendor::Hikvision,
+        Vendor::Dahua,
+        Vendor::Foscam,
+        Vendor::Mobotix,
+        Vendor::Dlink,
+        Vendor::Panasonic,
+        Vendor::Sony,
+        Vendor::Vivotek,
+        Vendor::IpWebcam,
+        Vendor::Yawcam,
+        Vendor::WebcamXp,
+        Vendor::GenericMjpeg,
+    ];
+
+    /// Name as serialized, e.g. `ip_webcam`
+    pub fn name(self) -> &'static str {
+        match self {
+            Vendor::Axis => "axis",
+            Vendor::Hikvision => "hikvision",
+            Vendor::Dahua => "dahua",
+            Vendor::Foscam => "foscam",
+            Vendor::Mobotix => "mobotix",
+            Vendor::Dlink => "dlink",
+            Vendor::Panasonic => "panasonic",
+            Vendor::Sony => "sony",
+            Vendor::Vivotek => "vivotek",
+            Vendor::IpWebcam => "ip_webcam",
+            Vendor::Yawcam => "yawcam",
+            Vendor::WebcamXp => "webcam_xp",
+            Vendor::GenericMjpeg => "generic_mjpeg",
+        }
+    }
+
+    /// Paths the make serves still images on, most likely first
+    pub fn snapshot_paths(self) -> &'static [&'static str] {
+        match self {
+            Vendor::Axis => &["/axis-cgi/jpg/image.cgi"],
+            Vendor::Hikvision => &["/ISAPI/Streaming/channels/101/picture", "/Streaming/channels/1/picture"],
+            Vendor::Dahua => &["/cgi-bin/snapshot.cgi"],
+            Vendor::Foscam => &["/cgi-bin/CGIProxy.fcgi?cmd=snapPicture2", "/snapshot.cgi"],
+            Vendor::Mobotix => &["/record/current.jpg"],
+            Vendor::Dlink => &["/image/jpeg.cgi", "/dms"],
+            Vendor::Panasonic => &["/SnapshotJPEG?Resolution=640x480"],
+            Vendor::Sony => &["/oneshotimage.jpg"],
+            Vendor::Vivotek => &["/cgi-bin/viewer/video.jpg"],
+            Vendor::IpWebcam => &["/shot.jpg"],
+            Vendor::Yawcam => &["/out.jpg"],
+            Vendor::WebcamXp => &["/cam_1.jpg"],
+            Vendor::GenericMjpeg => &[],
+        }
+    }
+}
+
+impl fmt::Display for Vendor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A vendor by name, ignoring case, spaces, dashes and underscores, so
+/// `D-Link` and `IP Webcam` work too
+impl FromStr for Vendor {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let squashed = |name: &str| -> String {
+            name.chars().filter(|c| !matches!(c, ' ' | '-' | '_')).collect::<String>().to_ascii_lowercase()
+        };
+        let wanted = squashed(name);
+        Vendor::ALL.into_iter().find(|vendor| squashed(vendor.name()) == wanted).ok_or_else(|| {
+            let names: Vec<&str> = Vendor::ALL.iter().map(|vendor| vendor.name()).collect();
+            format!("unknown vendor '{}'; known vendors are {}", name.trim(), names.join(", "))
+        })
+    }
+}
+
+/// What the rules made of a webcam
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Classification {
+    pub vendor: Vendor,
+    /// From 0 to 1: about 0.95 when the product names the make, lower for
+    /// a banner that only hints at it
+    pub confidence: f64,
+    /// The make's usual snapshot path, e.g. `/axis-cgi/jpg/image.cgi`
+    pub snapshot_path: Option<String>,
+}
+
+/// What a rule's pattern is matched against
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    /// Shodan's `product`
+    Product,
+    /// The banner Shodan collected, HTTP headers and all
+    Banner,
+}
+
+struct Rule {
+    vendor: Vendor,
+    field: Field,
+    /// Regular expression, matched ignoring case
+    pattern: &'static str,
+    /// Ports the make listens on out of the box; a match on one of them
+    /// is a little more certain
+    ports: &'static [u16],
+    confidence: f64,
+}
+
+/// Added to a rule's confidence when the webcam is on one of its ports
+const PORT_BONUS: f64 = 0.05;
+
+const RULES: &[Rule] = &[
+    Rule { vendor: Vendor::Axis, field: Field::Product, pattern: r"\baxis\b", ports: &[], confidence: 0.95 },
+    Rule { vendor: Vendor::Axis, field: Field::Banner, pattern: r"(?m)^server:.*\baxis\b|axis video server|live view / - axis|/axis-cgi/", ports: &[], confidence: 0.85 },
+    Rule { vendor: Vendor::Hikvision, field: Field::Product, pattern: r"hikvision|hik-?connect", ports: &[], confidence: 0.95 },
+    Rule { vendor: Vendor::Hikvision, field: Field::Banner, pattern: r"(?m)^server: *(hikvision-webs|app-webs|dvrdvs-webs|dnvrs-webs)", ports: &[], confidence: 0.85 },
+    Rule { vendor: Vendor::Hikvision, field: Field::Banner, pattern: r"/isapi/|/doc/page/login\.asp", ports: &[], confidence: 0.6 },
+    Rule { vendor: Vendor::Dahua, field: Field::Product, pattern: r"dahua", ports: &[], confidence: 0.95 },
+    Rule { vendor: Vendor::Dahua, field: Field::Banner, pattern: r"(?m)^server: *dhwebs|dahua|/rpc2_login", ports: &[37777], confidence: 0.8 },
+    Rule { vendor: Vendor::Foscam, field: Field::Product, pattern: r"foscam|netwave ip camera", ports: &[], confidence: 0.95 },
+    Rule { vendor: Vendor::Foscam, field: Field::Banner, pattern: r"foscam|(?m)^server: *netwave ip camera|ipcam_?web", ports: &[88], confidence: 0.75 },
+    Rule { vendor: Vendor::Mobotix, field: Field::Product, pattern: r"mobotix", ports: &[], confidence: 0.95 },
+    Rule { vendor: Vendor::Mobotix, field: Field::Banner, pattern: r"mobotix|/record/current\.jpg", ports: &[], confidence: 0.8 },
+    Rule { vendor: Vendor::Dlink, field: Field::Product, pattern: r"d-?link|\bdcs-\d{3,4}", ports: &[], confidence: 0.95 },
+    Rule { vendor: Vendor::Dlink, field: Field::Banner, pattern: r"d-?link|\bdcs-\d{3,4}", ports: &[], confidence: 0.75 },
+    Rule { vendor: Vendor::Panasonic, field: Field::Product, pattern: r"panasonic", ports: &[], confidence: 0.95 },
+    Rule { vendor: Vendor::Panasonic, field: Field::Banner, pattern: r"panasonic|viewerframe\?mode=|multicameraframe\?mode=", ports: &[], confidence: 0.75 },
+    Rule { vendor: Vendor::Sony, field: Field::Product, pattern: r"\bsony\b", ports: &[], confidence: 0.95 },
+    Rule { vendor: Vendor::Sony, field: Field::Banner, pattern: r"(?m)^server:.*\bsony\b|\bsnc-[a-z]{2}\d", ports: &[], confidence: 0.75 },
+    Rule { vendor: Vendor::Vivotek, field: Field::Product, pattern: r"vivotek", ports: &[], confidence: 0.95 },
+    Rule { vendor: Vendor::Vivotek, field: Field::Banner, pattern: r"vivotek|/cgi-bin/viewer/video\.jpg", ports: &[], confidence: 0.75 },
+    Rule { vendor: Vendor::IpWebcam, field: Field::Product, pattern: r"ip webcam", ports: &[], confidence: 0.95 },
+    Rule { vendor: Vendor::IpWebcam, field: Field::Banner, pattern: r"(?m)^server: *ip webcam server", ports: &[8080], confidence: 0.9 },
+    Rule { vendor: Vendor::Yawcam, field: Field::Product, pattern: r"yawcam", ports: &[], confidence: 0.95 },
+    Rule { vendor: Vendor::Yawcam, field: Field::Banner, pattern: r"(?m)^server: *yawcam", ports: &[8081], confidence: 0.9 },
+    Rule { vendor: Vendor::WebcamXp, field: Field::Product, pattern: r"webcamxp|webcam 7", ports: &[], confidence: 0.95 },
+    Rule { vendor: Vendor::WebcamXp, field: Field::Banner, pattern: r"(?m)^server: *webcamxp", ports: &[8080], confidence: 0.9 },
+    Rule { vendor: Vendor::GenericMjpeg, field: Field::Banner, pattern: r"multipart/x-mixed-replace|\bmjpe?g\b", ports: &[8080, 8081], confidence: 0.4 },
+];
+
+/// `RULES` with their patterns compiled, once
+fn compiled_rules() -> &'static [(&'static Rule, Regex)] {
+    static COMPILED: OnceLock<Vec<(&'static Rule, Regex)>> = OnceLock::new();
+    COMPILED.get_or_init(|| {
+        RULES
+            .iter()
+            .map(|rule| {
+                let regex = RegexBuilder::new(rule.pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .expect("the classification rules are valid regular expressions");
+                (rule, regex)
+            })
+            .collect()
+    })
+}
+
+/// The make of a webcam whose Shodan result has `product`, `banner` and
+/// `port`, or `None` if no rule matches. Of several matches the most
+/// certain wins, the first in `RULES` on a tie.
+pub fn classify(product: Option<&str>, banner: &str, port: u16) -> Option<Classification> {
+    let mut best: Option<(Vendor, f64)> = None;
+    for (rule, regex) in compiled_rules() {
+        let text = match rule.field {
+            Field::Product => product.unwrap_or_default(),
+            Field::Banner => banner,
+        };
+        if text.is_empty() || !regex.is_match(text) {
+            continue;
+        }
+        let bonus = if rule.ports.contains(&port) { PORT_BONUS } else { 0.0 };
+        let confidence = (rule.confidence + bonus).min(1.0);
+        if best.is_none_or(|(_, certainty)| confidence > certainty) {
+            best = Some((rule.vendor, confidence));
+        }
+    }
+    best.map(|(vendor, confidence)| Classification {
+        vendor,
+        // Two decimals, so the sum of rule and bonus reads cleanly
+        confidence: (confidence * 100.0).round() / 100.0,
+        snapshot_path: vendor.snapshot_paths().first().map(|path| path.to_string()),
+    })
+}
@@ -0,0 +1,167 @@
+//! Forwarding of server log events to the MCP client, as the spec's
+//! logging capability. Nothing is forwarded until the client sends
+//! `logging/setLevel`; from then on `tracing` events at or above that level
+//! are queued as `notifications/message` on the server's `Notifier`.
+//!
+//! The layer only queues: `serve` sends the notifications, so an event
+//! logged while a response is being written can't deadlock on the
+//! transport. Events logged while an event is being forwarded are dropped.
+//! API keys and tokens are replaced with `[REDACTED]` before forwarding.
+
+use crate::protocol::Notifier;
+use serde_json::{json, Map, Value};
+use std::cell::Cell;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock, RwLock};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// MCP log levels, least severe first
+pub const LOG_LEVELS: &[&str] = &["debug", "info", "notice", "warning", "error", "critical", "alert", "emergency"];
+/// Event fields whose values are never forwarded
+const SECRET_FIELDS: &[&str] = &["api_key", "key", "token", "auth_token"];
+const REDACTED: &str = "[REDACTED]";
+/// Level index meaning the client hasn't asked for logs
+const OFF: usize = usize::MAX;
+
+thread_local! {
+    /// Set while this thread forwards an event
+    static FORWARDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Where forwarded events go and which are wanted. There is one per
+/// process, like the `tracing` subscriber its layer is installed in.
+pub struct ClientLog {
+    /// Index into `LOG_LEVELS` of the least severe level forwarded
+    level: AtomicUsize,
+    notifier: Mutex<Option<Notifier>>,
+    /// Secret values scrubbed from forwarded text
+    secrets: RwLock<Vec<String>>,
+}
+
+impl ClientLog {
+    pub fn global() -> &'static ClientLog {
+        static LOG: OnceLock<ClientLog> = OnceLock::new();
+        LOG.get_or_init(|| ClientLog {
+            level: AtomicUsize::new(OFF),
+            notifier: Mutex::new(None),
+            secrets: RwLock::new(Vec::new()),
+        })
+    }
+
+    /// Layer to install in the `tracing` subscriber
+    pub fn layer(&'static self) -> ClientLogLayer {
+        ClientLogLayer { log: self }
+    }
+
+    /// Forward events at `level` and above to `notifier`'s client
+    pub fn set_level(&self, level: &str, notifier: Notifier) -> Result<(), String> {
+        let index = LOG_LEVELS.iter().position(|l| *l == level)
+            .ok_or_else(|| format!("Unknown log level '{}', expected one of: {}", level, LOG_LEVELS.join(", ")))?;
+        *self.notifier.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(notifier);
+        self.level.store(index, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Stop forwarding, e.g. once the client has gone
+    pub fn disable(&self) {
+        self.level.store(OFF, Ordering::SeqCst);
+        *self.notifier.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+    }
+
+    /// Scrub `secret` from every event forwarded from now on
+    pub fn redact(&self, secret: &str) {
+        if secret.is_empty() {
+            return;
+        }
+        let mut secrets = self.secrets.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !secrets.iter().any(|s| s == secret) {
+            secrets.push(secret.to_string());
+        }
+    }
+
+    fn scrub(&self, text: &str) -> String {
+        let secrets = self.secrets.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        secrets.iter().fold(text.to_string(), |text, secret| text.replace(secret.as_str(), REDACTED))
+    }
+
+    fn forward(&self, event: &Event<'_>) {
+        let index = level_index(event.metadata().level());
+        if index < self.level.load(Ordering::SeqCst) {
+            return;
+        }
+        if FORWARDING.with(|forwarding| forwarding.replace(true)) {
+            return;
+        }
+        let _reset = ResetForwarding;
+
+        let Some(notifier) = self.notifier.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone() else {
+            return;
+        };
+        let mut fields = FieldVisitor::default();
+        event.record(&mut fields);
+        let data: Map<String, Value> = fields.0.into_iter()
+            .map(|(name, value)| (name, Value::String(self.scrub(&value))))
+            .collect();
+        notifier.notify("notifications/message", Some(json!({
+            "level": LOG_LEVELS[index],
+            "logger": event.metadata().target(),
+            "data": data
+        })));
+    }
+}
+
+/// `tracing` layer forwarding events through a `ClientLog`
+pub struct ClientLogLayer {
+    log: &'static ClientLog,
+}
+
+impl<S: Subscriber> Layer<S> for ClientLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        self.log.forward(event);
+    }
+}
+
+struct ResetForwarding;
+
+impl Drop for ResetForwarding {
+    fn drop(&mut self) {
+        FORWARDING.with(|forwarding| forwarding.set(false));
+    }
+}
+
+/// Index into `LOG_LEVELS` of a `tracing` level
+fn level_index(level: &Level) -> usize {
+    if *level == Level::ERROR {
+        4
+    } else if *level == Level::WARN {
+        3
+    } else if *level == Level::INFO {
+        1
+    } else {
+        0
+    }
+}
+
+/// Event fields as text, secret ones already redacted
+#[derive(Default)]
+struct FieldVisitor(Vec<(String, String)>);
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, format!("{:?}", value));
+    }
+}
+
+impl FieldVisitor {
+    fn record(&mut self, field: &Field, value: String) {
+        let value = if SECRET_FIELDS.contains(&field.name()) { REDACTED.to_string() } else { value };
+        self.0.push((field.name().to_string(), value));
+    }
+}
@@ -0,0 +1,230 @@
+use crate::protocol::RateLimit;
+use crate::target_policy::TargetRule;
+use crate::webcam::CameraProfile;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::info;
+
+/// Contents of the optional TOML configuration file named by
+/// `MCP_WEBCAM_CONFIG`
+///
+/// ```toml
+/// # Camera used when a tool call names none
+/// default_camera = 1
+///
+/// [cameras.0]
+/// width = 1280
+/// height = 720
+/// rotation = 180
+///
+/// # Token required by network listeners such as the live preview
+/// auth_token = "..."
+///
+/// # Record of every capture and remote fetch
+/// audit_log = "/var/log/mcp-webcam/audit.jsonl"
+///
+/// # Most tool calls running at once
+/// max_concurrent_calls = 2
+///
+/// # Shodan queries search_webcams chooses from, instead of the built-in ones
+/// search_queries = ["\"Server: IP Webcam Server\"", "port:8080 \"mjpeg\""]
+///
+/// # Times a Shodan request answered with 429 is retried
+/// shodan_max_retries = 5
+///
+/// # Most requests per second sent to the Shodan API
+/// shodan_requests_per_second = 0.5
+///
+/// # Remote webcams that may be fetched from, and those never fetched from
+/// [target_policy]
+/// allow = ["203.0.113.0/24", "*.cameras.example.com"]
+/// block = ["203.0.113.66"]
+///
+/// # Seconds a tool call may run before it fails with TOOL_TIMEOUT
+/// [tool_timeouts]
+/// capture_image = 30
+///
+/// # Calls allowed per tool before RATE_LIMITED
+/// [rate_limits]
+/// capture_image = { calls = 10, per_seconds = 60 }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Per-camera default settings, keyed by camera index
+    #[serde(default)]
+    pub cameras: HashMap<String, CameraProfile>,
+    /// Camera used when a tool call names none; `--default-camera` takes
+    /// precedence
+    pub default_camera: Option<u32>,
+    /// Token required by network listeners; `MCP_WEBCAM_AUTH_TOKEN` and
+    /// `--auth-token` take precedence
+    pub auth_token: Option<String>,
+    /// Append-only JSONL file recording every capture and remote fetch;
+    /// no audit log is kept when unset
+    pub audit_log: Option<PathBuf>,
+    /// Most tool calls running at once, overriding the default
+    pub max_concurrent_calls: Option<usize>,
+    /// Per-tool call timeouts in seconds, overriding the built-in ones
+    #[serde(default)]
+    pub tool_timeouts: HashMap<String, f64>,
+    /// Per-tool rate limits, overriding the built-in ones
+    #[serde(default)]
+    pub rate_limits: HashMap<String, RateLimit>,
+    /// Shodan queries `search_webcams` chooses from, replacing the built-in
+    /// list; `MCP_WEBCAM_SEARCH_QUERIES` takes precedence
+    pub search_queries: Option<Vec<String>>,
+    /// Times a Shodan request answered with 429 Too Many Requests is
+    /// retried, overriding the default
+    pub shodan_max_retries: Option<u32>,
+    /// Most requests per second sent to the Shodan API, overriding the
+    /// default
+    pub shodan_requests_per_second: Option<f64>,
+    /// Allowlist and blocklist of remote webcam targets, added to those
+    /// given on the command line
+    #[serde(default)]
+    pub target_policy: TargetRulesConfig,
+}
+
+/// `[target_policy]` of the configuration file: networks and host names,
+/// as `TargetRule` parses them
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetRulesConfig {
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub block: Vec<String>,
+}
+
+impl ConfigFile {
+    /// Path of the configuration file, if one is configured
+    pub fn path() -> Option<PathBuf> {
+        std::env::var("MCP_WEBCAM_CONFIG").ok().map(PathBuf::from)
+    }
+
+    /// Load the configuration file, or an empty configuration if none is set
+    pub fn load() -> Result<Self, String> {
+        match Self::path() {
+            Some(path) => {
+                let text = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+                let config: Self = toml::from_str(&text)
+                    .map_err(|e| format!("failed to parse {}: {}", path.display(), e))?;
+                info!("Loaded configuration from {}", path.display());
+                Ok(config)
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Tool timeouts as durations; each must be a positive number of seconds
+    pub fn tool_timeouts(&self) -> Result<HashMap<String, Duration>, String> {
+        self.tool_timeouts
+            .iter()
+            .map(|(tool, &seconds)| {
+                if !(seconds.is_finite() && seconds > 0.0) {
+                    return Err(format!("timeout for '{}' in [tool_timeouts] must be a positive number of seconds", tool));
+                }
+                Ok((tool.clone(), Duration::from_secs_f64(seconds)))
+            })
+            .collect()
+    }
+
+    /// Rate limits, each with a positive number of calls and seconds
+    pub fn rate_limits(&self) -> Result<HashMap<String, RateLimit>, String> {
+        self.rate_limits
+            .iter()
+            .map(|(tool, limit)| {
+                limit.validate().map_err(|e| format!("rate limit for '{}' in [rate_limits]: {}", tool, e))?;
+                Ok((tool.clone(), *limit))
+            })
+            .collect()
+    }
+
+    /// Search queries, trimmed; the list and each query must be non-empty
+    pub fn search_queries(&self) -> Result<Option<Vec<String>>, String> {
+        let Some(queries) = &self.search_queries else {
+            return Ok(None);
+        };
+        if queries.is_empty() || queries.iter().any(|query| query.trim().is_empty()) {
+            return Err("search_queries must be a non-empty list of non-empty queries".to_string());
+        }
+        Ok(Some(queries.iter().map(|query| query.trim().to_string()).collect()))
+    }
+
+    /// Shodan request rate, which must be a positive number
+    pub fn shodan_requests_per_second(&self) -> Result<Option<f64>, String> {
+        match self.shodan_requests_per_second {
+            Some(rate) if !(rate.is_finite() && rate > 0.0) => {
+                Err(format!("shodan_requests_per_second must be positive, got {}", rate))
+            }
+            rate => Ok(rate),
+        }
+    }
+
+    /// Allowlist and blocklist rules of `[target_policy]`; every rule
+    /// must parse
+    pub fn target_rules(&self) -> Result<(Vec<TargetRule>, Vec<TargetRule>), String> {
+        let parse = |list: &[String], name: &str| {
+            list.iter()
+                .map(|rule| rule.parse::<TargetRule>().map_err(|e| format!("{} in [target_policy] {}", e, name)))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok((parse(&self.target_policy.allow, "allow")?, parse(&self.target_policy.block, "block")?))
+    }
+
+    /// Camera profiles with keys parsed as camera indices
+    pub fn camera_profiles(&self) -> Result<HashMap<u32, CameraProfile>, String> {
+        self.cameras
+            .iter()
+            .map(|(key, profile)| {
+                let index = key
+                    .parse::<u32>()
+                    .map_err(|_| format!("invalid camera index '{}' in [cameras]", key))?;
+                profile
+                    .validate()
+                    .map_err(|e| format!("camera {}: {}", index, e))?;
+                Ok((index, profile.clone()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_sections_are_keyed_by_index() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [cameras.0]
+            width = 1280
+            height = 720
+
+            [cameras.2]
+            rotation = 270
+            backend = "opencv"
+            "#,
+        )
+        .unwrap();
+
+        let profiles = config.camera_profiles().unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!((profiles[&0].width, profiles[&0].height), (Some(1280), Some(720)));
+        assert_eq!(profiles[&2].rotation, Some(270));
+        assert_eq!(profiles[&2].backend.as_deref(), Some("opencv"));
+    }
+
+    #[test]
+    fn bad_camera_sections_are_rejected() {
+        let parse = |text: &str| toml::from_str::<ConfigFile>(text).unwrap().camera_profiles().unwrap_err();
+        assert_eq!(parse("[cameras.front]\nrotation = 90"), "invalid camera index 'front' in [cameras]");
+        assert_eq!(parse("[cameras.1]\nrotation = 45"), "camera 1: rotation must be 0, 90, 180 or 270, got 45");
+        assert!(parse("[cameras.1]\nwidth = 640").contains("width and height must be set together"));
+        assert!(toml::from_str::<ConfigFile>("[cameras.0]\nzoom = 2").is_err());
+    }
+}
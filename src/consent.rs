@@ -0,0 +1,239 @@
+//! Consent mode: turning a camera on waits for a human to approve it.
+//!
+//! While consent is required for a scope, local cameras or remote webcams,
+//! calls of its tools fail with `CONSENT_REQUIRED` and a one-time code is
+//! shown to the person running the server, on its console by default.
+//! Passing the code to `grant_consent` unlocks the scope for a while. The
+//! code is never sent to the client, so a model can't approve itself, and a
+//! wrong code discards it.
+
+use crate::tool_error::{ErrorCode, ToolError};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How long a grant lasts unless configured otherwise
+pub const DEFAULT_CONSENT_DURATION: Duration = Duration::from_secs(600);
+/// How long a one-time code can be used
+const CODE_LIFETIME: Duration = Duration::from_secs(300);
+/// Tools that turn on a local camera
+pub const LOCAL_GATED_TOOLS: &[&str] = &[
+    "capture_image", "scan_codes", "capture_clip", "compare_captures", "self_test", "benchmark_camera",
+    "start_preview",
+];
+/// Tools that fetch from a remote webcam
+pub const REMOTE_GATED_TOOLS: &[&str] = &["capture_remote_image", "probe_webcam", "validate_webcams", "capture_onvif_snapshot"];
+
+type CodeSink = Arc<dyn Fn(ConsentScope, &str) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsentScope {
+    Local,
+    Remote,
+}
+
+impl ConsentScope {
+    pub fn tools(self) -> &'static [&'static str] {
+        match self {
+            ConsentScope::Local => LOCAL_GATED_TOOLS,
+            ConsentScope::Remote => REMOTE_GATED_TOOLS,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            ConsentScope::Local => "local camera",
+            ConsentScope::Remote => "remote webcam",
+        }
+    }
+}
+
+/// Result of `grant_consent`
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsentGrant {
+    pub scope: ConsentScope,
+    pub expires_at: String,
+    pub duration_seconds: u64,
+}
+
+/// Consent state of one scope, as reported by `get_server_info`
+#[derive(Debug, Clone, Serialize)]
+pub struct ScopeStatus {
+    pub required: bool,
+    pub granted: bool,
+    pub expires_at: Option<String>,
+    pub gated_tools: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsentStatus {
+    pub duration_seconds: u64,
+    pub local: ScopeStatus,
+    pub remote: ScopeStatus,
+}
+
+struct PendingCode {
+    code: String,
+    expires: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct State {
+    granted_until: HashMap<ConsentScope, DateTime<Utc>>,
+    pending: HashMap<ConsentScope, PendingCode>,
+}
+
+/// Which scopes need consent and which have it; clones share the state
+#[derive(Clone)]
+pub struct ConsentGate {
+    local: bool,
+    remote: bool,
+    duration: Duration,
+    state: Arc<Mutex<State>>,
+    sink: CodeSink,
+}
+
+impl ConsentGate {
+    /// Require consent for local cameras, remote webcams or both, each
+    /// grant lasting `duration`
+    pub fn new(local: bool, remote: bool, duration: Duration) -> Self {
+        Self {
+            local,
+            remote,
+            duration,
+            state: Arc::new(Mutex::new(State::default())),
+            sink: Arc::new(print_code),
+        }
+    }
+
+    /// No consent required for anything
+    pub fn disabled() -> Self {
+        Self::new(false, false, DEFAULT_CONSENT_DURATION)
+    }
+
+    /// Show one-time codes with `sink` instead of printing them to stderr,
+    /// e.g. in a dialog of an embedding application
+    pub fn on_code<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(ConsentScope, &str) + Send + Sync + 'static,
+    {
+        self.sink = Arc::new(sink);
+        self
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.local || self.remote
+    }
+
+    fn requires(&self, scope: ConsentScope) -> bool {
+        match scope {
+            ConsentScope::Local => self.local,
+            ConsentScope::Remote => self.remote,
+        }
+    }
+
+    /// Fail with `CONSENT_REQUIRED` if `tool` needs consent that hasn't
+    /// been given, issuing a code if none is outstanding
+    pub fn check(&self, tool: &str) -> Result<(), ToolError> {
+        let scope = [ConsentScope::Local, ConsentScope::Remote]
+            .into_iter()
+            .find(|scope| scope.tools().contains(&tool));
+        match scope {
+            Some(scope) => self.check_scope(scope, tool),
+            None => Ok(()),
+        }
+    }
+
+    /// Like `check`, for something other than a tool call, e.g. reading
+    /// a snapshot resource
+    pub fn check_scope(&self, scope: ConsentScope, what: &str) -> Result<(), ToolError> {
+        if !self.requires(scope) {
+            return Ok(());
+        }
+        let now = Utc::now();
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if state.granted_until.get(&scope).is_some_and(|until| *until > now) {
+            return Ok(());
+        }
+
+        if !state.pending.get(&scope).is_some_and(|pending| pending.expires > now) {
+            let code = format!("{:06}", uuid::Uuid::new_v4().as_u128() % 1_000_000);
+            let expires = now + chrono::Duration::from_std(CODE_LIFETIME).unwrap_or(chrono::Duration::zero());
+            info!("Consent required for {} use; one-time code issued", scope.describe());
+            (self.sink)(scope, &code);
+            state.pending.insert(scope, PendingCode { code, expires });
+        }
+        Err(ToolError::new(
+            ErrorCode::ConsentRequired,
+            format!(
+                "{} needs consent for {} use. Ask the user for the one-time code shown on the server's console and pass it to grant_consent",
+                what,
+                scope.describe()
+            ),
+        )
+        .with_details(json!({ "tool": what, "scope": scope })))
+    }
+
+    /// Whether `scope` may be used now, without issuing a code
+    pub fn is_granted(&self, scope: ConsentScope) -> bool {
+        if !self.requires(scope) {
+            return true;
+        }
+        let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.granted_until.get(&scope).is_some_and(|until| *until > Utc::now())
+    }
+
+    /// Unlock the scope `code` was issued for. A wrong or expired code
+    /// discards every outstanding code.
+    pub fn grant(&self, code: &str) -> Result<ConsentGrant, String> {
+        let now = Utc::now();
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let scope = state.pending.iter()
+            .find(|(_, pending)| pending.code == code.trim() && pending.expires > now)
+            .map(|(scope, _)| *scope);
+        let Some(scope) = scope else {
+            state.pending.clear();
+            warn!("Rejected a wrong or expired consent code");
+            return Err("The consent code is wrong or has expired. Call the tool again to get a new code".to_string());
+        };
+
+        state.pending.remove(&scope);
+        let until = now + chrono::Duration::from_std(self.duration).unwrap_or(chrono::Duration::zero());
+        state.granted_until.insert(scope, until);
+        info!("Consent given for {} use until {}", scope.describe(), until.to_rfc3339());
+        Ok(ConsentGrant { scope, expires_at: until.to_rfc3339(), duration_seconds: self.duration.as_secs() })
+    }
+
+    pub fn status(&self) -> ConsentStatus {
+        let now = Utc::now();
+        let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let scope_status = |scope: ConsentScope| {
+            let until = state.granted_until.get(&scope).filter(|until| **until > now);
+            ScopeStatus {
+                required: self.requires(scope),
+                granted: !self.requires(scope) || until.is_some(),
+                expires_at: until.map(|until| until.to_rfc3339()),
+                gated_tools: if self.requires(scope) { scope.tools().to_vec() } else { Vec::new() },
+            }
+        };
+        ConsentStatus {
+            duration_seconds: self.duration.as_secs(),
+            local: scope_status(ConsentScope::Local),
+            remote: scope_status(ConsentScope::Remote),
+        }
+    }
+}
+
+/// Show a code on the console; stdout carries the protocol, so stderr
+fn print_code(scope: ConsentScope, code: &str) {
+    eprintln!(
+        "\n*** mcp-webcam: a client wants to use a {}. To allow it, give the client this one-time code: {} ***\n",
+        scope.describe(),
+        code
+    );
+}
@@ -0,0 +1,270 @@
+//! A budget of Shodan search and host requests per hour and per day, so
+//! agents re-running `search_webcams` can't spend a month of query
+//! credits in an afternoon. Requests are counted, not results: a search
+//! query is one request however many webcams it returns.
+//!
+//! The hour and the day are UTC calendar windows: the hourly budget resets
+//! at the top of each hour, the daily one at midnight. A request over
+//! either fails with `SHODAN_BUDGET_EXCEEDED` and when the budget resets.
+//!
+//! A budget opened on a file keeps its usage across restarts: it is loaded
+//! when the server starts and saved, by writing a temporary file and
+//! renaming it over the old one, after each request.
+
+use chrono::{DateTime, Duration as ChronoDuration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// File name of the usage record in the state directory
+pub const SHODAN_USAGE_FILE: &str = "shodan_usage.json";
+
+/// A period the budget is counted over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetWindow {
+    Hour,
+    Day,
+}
+
+impl BudgetWindow {
+    fn length(self) -> ChronoDuration {
+        match self {
+            Self::Hour => ChronoDuration::hours(1),
+            Self::Day => ChronoDuration::days(1),
+        }
+    }
+
+    /// Start of the window `at` falls in
+    fn start(self, at: DateTime<Utc>) -> DateTime<Utc> {
+        at.duration_trunc(self.length()).unwrap_or(at)
+    }
+}
+
+impl fmt::Display for BudgetWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+        })
+    }
+}
+
+/// A request refused because a window's budget is used up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetExceeded {
+    pub window: BudgetWindow,
+    pub limit: u32,
+    /// When the window's budget resets
+    pub resets_at: DateTime<Utc>,
+}
+
+impl BudgetExceeded {
+    /// Time from `now` until the budget resets
+    pub fn retry_after(&self, now: DateTime<Utc>) -> Duration {
+        (self.resets_at - now).to_std().unwrap_or_default()
+    }
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the Shodan budget of {} request(s) per {} is used up; it resets at {}",
+               self.limit, self.window, self.resets_at.to_rfc3339())
+    }
+}
+
+/// One window's usage, for `shodan_account_info` and `get_server_stats`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WindowUsage {
+    /// Requests allowed per window, or `None` for no limit
+    pub limit: Option<u32>,
+    /// Requests made in the current window
+    pub used: u32,
+    /// Requests left in the current window, or `None` for no limit
+    pub remaining: Option<u32>,
+    pub resets_at: DateTime<Utc>,
+}
+
+/// The budget and what is left of it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BudgetStatus {
+    /// Whether either window has a limit
+    pub limited: bool,
+    pub hour: WindowUsage,
+    pub day: WindowUsage,
+    /// Requests refused since startup
+    pub refused: u64,
+}
+
+/// Requests made in the current windows, as saved
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Usage {
+    #[serde(default)]
+    hour_start: Option<DateTime<Utc>>,
+    #[serde(default)]
+    hour_requests: u32,
+    #[serde(default)]
+    day_start: Option<DateTime<Utc>>,
+    #[serde(default)]
+    day_requests: u32,
+}
+
+impl Usage {
+    /// Requests made in `window` as of `now`; zero once it has moved on
+    fn used(&self, window: BudgetWindow, now: DateTime<Utc>) -> u32 {
+        let (start, requests) = match window {
+            BudgetWindow::Hour => (self.hour_start, self.hour_requests),
+            BudgetWindow::Day => (self.day_start, self.day_requests),
+        };
+        if start == Some(window.start(now)) {
+            requests
+        } else {
+            0
+        }
+    }
+
+    fn record(&mut self, now: DateTime<Utc>) {
+        self.hour_requests = self.used(BudgetWindow::Hour, now) + 1;
+        self.hour_start = Some(BudgetWindow::Hour.start(now));
+        self.day_requests = self.used(BudgetWindow::Day, now) + 1;
+        self.day_start = Some(BudgetWindow::Day.start(now));
+    }
+}
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    usage: Usage,
+    refused: u64,
+}
+
+/// Shodan requests allowed per hour and per day, and those made, shared
+/// by every Shodan client of a server
+#[derive(Debug)]
+pub struct CreditBudget {
+    per_hour: Option<u32>,
+    per_day: Option<u32>,
+    /// File the usage is saved to, if any
+    path: Option<PathBuf>,
+    state: Mutex<BudgetState>,
+}
+
+impl Default for CreditBudget {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+impl CreditBudget {
+    /// Budget allowing every request, still counting them
+    pub fn unlimited() -> Self {
+        Self::new(None, None)
+    }
+
+    /// Budget of `per_hour` and `per_day` requests, `None` being no limit,
+    /// kept in memory only
+    pub fn new(per_hour: Option<u32>, per_day: Option<u32>) -> Self {
+        Self { per_hour, per_day, path: None, state: Mutex::new(BudgetState::default()) }
+    }
+
+    /// Budget saved to `path`, starting with the usage saved there. A file
+    /// that can't be read or parsed is logged and the count starts at zero.
+    pub fn open(path: impl Into<PathBuf>, per_hour: Option<u32>, per_day: Option<u32>) -> Self {
+        let path = path.into();
+        let mut budget = Self::new(per_hour, per_day);
+        match read_usage(&path) {
+            Ok(usage) => {
+                info!("Loaded Shodan usage from {}: {} request(s) this hour, {} today",
+                      path.display(), usage.used(BudgetWindow::Hour, Utc::now()), usage.used(BudgetWindow::Day, Utc::now()));
+                budget.state.get_mut().unwrap_or_else(|e| e.into_inner()).usage = usage;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("No Shodan usage record at {} yet", path.display());
+            }
+            Err(e) => warn!("Could not load Shodan usage from {}, counting from zero: {}", path.display(), e),
+        }
+        budget.path = Some(path);
+        budget
+    }
+
+    pub fn per_hour(&self) -> Option<u32> {
+        self.per_hour
+    }
+
+    pub fn per_day(&self) -> Option<u32> {
+        self.per_day
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.per_hour.is_some() || self.per_day.is_some()
+    }
+
+    /// Count a request now, or refuse it if a window's budget is used up
+    pub fn claim(&self) -> Result<(), BudgetExceeded> {
+        self.claim_at(Utc::now())
+    }
+
+    /// `claim` as of `now`. With both budgets used up, the day's is
+    /// reported, being the later to reset.
+    pub fn claim_at(&self, now: DateTime<Utc>) -> Result<(), BudgetExceeded> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        for (window, limit) in [(BudgetWindow::Day, self.per_day), (BudgetWindow::Hour, self.per_hour)] {
+            if let Some(limit) = limit.filter(|&limit| state.usage.used(window, now) >= limit) {
+                state.refused += 1;
+                return Err(BudgetExceeded { window, limit, resets_at: window.start(now) + window.length() });
+            }
+        }
+        state.usage.record(now);
+        if let Some(path) = &self.path {
+            if let Err(e) = write_usage(path, &state.usage) {
+                warn!("Could not save Shodan usage to {}: {}", path.display(), e);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn status(&self) -> BudgetStatus {
+        self.status_at(Utc::now())
+    }
+
+    /// `status` as of `now`
+    pub fn status_at(&self, now: DateTime<Utc>) -> BudgetStatus {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let usage = |window: BudgetWindow, limit: Option<u32>| {
+            let used = state.usage.used(window, now);
+            WindowUsage {
+                limit,
+                used,
+                remaining: limit.map(|limit| limit.saturating_sub(used)),
+                resets_at: window.start(now) + window.length(),
+            }
+        };
+        BudgetStatus {
+            limited: self.is_limited(),
+            hour: usage(BudgetWindow::Hour, self.per_hour),
+            day: usage(BudgetWindow::Day, self.per_day),
+            refused: state.refused,
+        }
+    }
+}
+
+fn read_usage(path: &Path) -> std::io::Result<Usage> {
+    let text = std::fs::read_to_string(path)?;
+    serde_json::from_str(&text).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Write `usage` to `path`, replacing the file only once the new one is
+/// complete
+fn write_usage(path: &Path, usage: &Usage) -> std::io::Result<()> {
+    let bytes = serde_json::to_vec_pretty(usage).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    std::fs::write(&temporary, bytes)?;
+    std::fs::rename(&temporary, path)
+}
@@ -0,0 +1,242 @@
+//! Webcam discovery services. Shodan was the only one; an account may
+//! have credits with another, such as Censys, instead or as well. Each
+//! service implements `WebcamDiscoveryProvider`, and `Discovery` routes a
+//! search to all of those configured, merging what they find into one
+//! list with each webcam tagged by the services that found it.
+
+use crate::shodan::{
+    QueryPlan, RemoteWebcam, SearchFilters, ShodanClient, ShodanError, ShodanHost, WebcamSearch,
+    DEFAULT_SEARCH_LIMIT,
+};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// A service that finds internet-facing webcams: what `search_webcams`,
+/// `shodan_host_info` and `shodan_account_info` need of it
+#[async_trait]
+pub trait WebcamDiscoveryProvider: Send + Sync {
+    /// Short lowercase name, e.g. `shodan`, that tags the webcams it finds
+    fn name(&self) -> &'static str;
+
+    /// Up to `limit` webcams matching `filters`. `plan` is of Shodan's
+    /// queries; a service with its own runs as many of those.
+    async fn search_webcams(&self, limit: Option<u32>, filters: &SearchFilters, plan: &QueryPlan) -> Result<WebcamSearch, ShodanError>;
+
+    /// `search_webcams`, passing `report` the webcams each query adds as
+    /// it answers. A service that can't tell its queries apart reports
+    /// everything it found at the end.
+    async fn search_webcams_reporting(
+        &self,
+        limit: Option<u32>,
+        filters: &SearchFilters,
+        plan: &QueryPlan,
+        report: &(dyn Fn(&[RemoteWebcam]) + Send + Sync),
+    ) -> Result<WebcamSearch, ShodanError> {
+        let found = self.search_webcams(limit, filters, plan).await?;
+        report(&found.webcams);
+        Ok(found)
+    }
+
+    /// Everything the service knows about `ip`; with `history`, services
+    /// it no longer sees too, where it keeps them
+    async fn host(&self, ip: &str, history: bool) -> Result<ShodanHost, ShodanError>;
+
+    /// Plan and credits left of the account in use
+    async fn credits(&self) -> Result<ProviderCredits, ShodanError>;
+}
+
+/// Plan and credits left of the account a discovery service is used with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderCredits {
+    pub provider: String,
+    #[serde(default)]
+    pub plan: Option<String>,
+    /// Search queries left this period
+    pub query_credits: i64,
+    /// On-demand scans left, for services that offer them
+    #[serde(default)]
+    pub scan_credits: Option<i64>,
+    /// When the query credits are renewed, if the service says
+    #[serde(default)]
+    pub resets_at: Option<String>,
+}
+
+#[async_trait]
+impl WebcamDiscoveryProvider for ShodanClient {
+    fn name(&self) -> &'static str {
+        crate::shodan::SOURCE
+    }
+
+    async fn search_webcams(&self, limit: Option<u32>, filters: &SearchFilters, plan: &QueryPlan) -> Result<WebcamSearch, ShodanError> {
+        ShodanClient::search_webcams(self, limit, filters, plan).await
+    }
+
+    async fn search_webcams_reporting(
+        &self,
+        limit: Option<u32>,
+        filters: &SearchFilters,
+        plan: &QueryPlan,
+        report: &(dyn Fn(&[RemoteWebcam]) + Send + Sync),
+    ) -> Result<WebcamSearch, ShodanError> {
+        let mut stream = self.search_webcams_stream(limit, filters, plan);
+        while let Some(webcams) = stream.next().await {
+            report(&webcams);
+        }
+        stream.finish().await
+    }
+
+    async fn host(&self, ip: &str, history: bool) -> Result<ShodanHost, ShodanError> {
+        ShodanClient::host(self, ip, history).await
+    }
+
+    async fn credits(&self) -> Result<ProviderCredits, ShodanError> {
+        let account = self.api_info().await?;
+        Ok(ProviderCredits {
+            provider: crate::shodan::SOURCE.to_string(),
+            plan: account.plan,
+            query_credits: account.query_credits,
+            scan_credits: Some(account.scan_credits),
+            resets_at: None,
+        })
+    }
+}
+
+/// A discovery service that failed while others answered
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProviderFailure {
+    pub provider: String,
+    pub error: String,
+}
+
+/// Webcams found by every discovery service that answered, merged
+#[derive(Debug, Clone, Default)]
+pub struct MergedSearch {
+    pub found: WebcamSearch,
+    /// Services whose results are included, in order
+    pub providers: Vec<String>,
+    pub failures: Vec<ProviderFailure>,
+}
+
+/// The discovery services a server searches, in order of preference
+#[derive(Clone, Default)]
+pub struct Discovery {
+    providers: Vec<Arc<dyn WebcamDiscoveryProvider>>,
+}
+
+impl Discovery {
+    pub fn new(providers: Vec<Arc<dyn WebcamDiscoveryProvider>>) -> Self {
+        Self { providers }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.providers.iter().map(|provider| provider.name()).collect()
+    }
+
+    /// Search every service for up to `limit` webcams. Their results are
+    /// taken alternately, so each is represented when the limit cuts the
+    /// list, and an endpoint (IP and port) found by several is listed
+    /// once, tagged with all of them. With a radius, the list is then
+    /// sorted nearest first. A service that fails is logged and left out;
+    /// if every one fails, the first error is returned.
+    pub async fn search_webcams(&self, limit: Option<u32>, filters: &SearchFilters, plan: &QueryPlan) -> Result<MergedSearch, ShodanError> {
+        self.search_webcams_reporting(limit, filters, plan, &|_, _| {}).await
+    }
+
+    /// `search_webcams`, passing `report` each service's name and the
+    /// webcams each of its queries adds, as they arrive; the merged list
+    /// is only known at the end
+    pub async fn search_webcams_reporting(
+        &self,
+        limit: Option<u32>,
+        filters: &SearchFilters,
+        plan: &QueryPlan,
+        report: &(dyn Fn(&str, &[RemoteWebcam]) + Send + Sync),
+    ) -> Result<MergedSearch, ShodanError> {
+        let mut merged = MergedSearch::default();
+        let mut results: Vec<VecDeque<RemoteWebcam>> = Vec::new();
+        let mut first_error = None;
+
+        for provider in &self.providers {
+            let name = provider.name();
+            match provider.search_webcams_reporting(limit, filters, plan, &|webcams| report(name, webcams)).await {
+                Ok(found) => {
+                    debug!("{} found {} webcam(s)", provider.name(), found.webcams.len());
+                    merged.providers.push(provider.name().to_string());
+                    merged.found.queries.extend(found.queries);
+                    merged.found.outside_radius += found.outside_radius;
+                    merged.found.reverse_dns.looked_up += found.reverse_dns.looked_up;
+                    merged.found.reverse_dns.resolved += found.reverse_dns.resolved;
+                    merged.found.reverse_dns.unfinished += found.reverse_dns.unfinished;
+                    let source = provider.name().to_string();
+                    results.push(found.webcams.into_iter().map(|mut webcam| {
+                        if !webcam.sources.contains(&source) {
+                            webcam.sources.push(source.clone());
+                        }
+                        webcam
+                    }).collect());
+                }
+                Err(e) => {
+                    warn!("Searching {} failed: {}", provider.name(), e);
+                    merged.failures.push(ProviderFailure { provider: provider.name().to_string(), error: e.to_string() });
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if merged.providers.is_empty() {
+            return Err(first_error.unwrap_or_else(|| ShodanError::Generic("no webcam discovery service is configured".to_string())));
+        }
+
+        let mut seen: HashMap<(String, u16), usize> = HashMap::new();
+        while results.iter().any(|webcams| !webcams.is_empty()) {
+            for webcam in results.iter_mut().filter_map(VecDeque::pop_front) {
+                match seen.entry((webcam.ip.clone(), webcam.port)) {
+                    Entry::Occupied(entry) => merged.found.webcams[*entry.get()].merge(webcam),
+                    Entry::Vacant(entry) => {
+                        entry.insert(merged.found.webcams.len());
+                        merged.found.webcams.push(webcam);
+                    }
+                }
+            }
+        }
+        merged.found.webcams.truncate(limit.unwrap_or(DEFAULT_SEARCH_LIMIT) as usize);
+        if let Some(near) = filters.near {
+            let distance = |webcam: &RemoteWebcam| near.distance_km(webcam.location.as_ref()).unwrap_or(f64::INFINITY);
+            merged.found.webcams.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+        }
+        Ok(merged)
+    }
+
+    /// What the first service that knows `ip` has on it, with that
+    /// service's name. If none does, the first error is returned.
+    pub async fn host(&self, ip: &str, history: bool) -> Result<(&'static str, ShodanHost), ShodanError> {
+        let mut first_error = None;
+        for provider in &self.providers {
+            match provider.host(ip, history).await {
+                Ok(host) => return Ok((provider.name(), host)),
+                Err(e) => {
+                    debug!("{} has nothing on {}: {}", provider.name(), ip, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        Err(first_error.unwrap_or_else(|| ShodanError::Generic("no webcam discovery service is configured".to_string())))
+    }
+
+    /// The credits of each service's account, in order
+    pub async fn credits(&self) -> Vec<(&'static str, Result<ProviderCredits, ShodanError>)> {
+        let mut credits = Vec::new();
+        for provider in &self.providers {
+            credits.push((provider.name(), provider.credits().await));
+        }
+        credits
+    }
+}
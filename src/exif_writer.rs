@@ -0,0 +1,236 @@
+//! Minimal EXIF writer: builds an APP1 segment with the handful of tags
+//! the server knows about and splices it into an encoded JPEG.
+
+/// Longest user comment accepted, in bytes, keeping the APP1 segment well
+/// under the 64 KiB JPEG segment limit
+pub const MAX_COMMENT_BYTES: usize = 4096;
+
+const TAG_MAKE: u16 = 0x010F;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_SOFTWARE: u16 = 0x0131;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+const TAG_USER_COMMENT: u16 = 0x9286;
+
+const TYPE_ASCII: u16 = 2;
+const TYPE_LONG: u16 = 4;
+const TYPE_UNDEFINED: u16 = 7;
+
+/// Metadata written into a JPEG
+#[derive(Debug, Clone, Default)]
+pub struct ExifMetadata {
+    /// Capture time as RFC 3339, e.g. `CaptureResult::timestamp`
+    pub timestamp: Option<String>,
+    /// Camera name, written to Make and Model
+    pub camera_name: Option<String>,
+    pub comment: Option<String>,
+}
+
+impl ExifMetadata {
+    /// Software tag value: crate name and version
+    pub fn software() -> String {
+        format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+    }
+
+    /// Insert an EXIF APP1 segment into `jpeg`. It goes after the JFIF APP0
+    /// segment if there is one, otherwise straight after SOI. Input that
+    /// isn't a JPEG is returned unchanged.
+    pub fn embed(&self, jpeg: &[u8]) -> Vec<u8> {
+        if jpeg.len() < 2 || jpeg[0..2] != [0xFF, 0xD8] {
+            return jpeg.to_vec();
+        }
+
+        let mut insert_at = 2;
+        if jpeg.len() >= 6 && jpeg[2..4] == [0xFF, 0xE0] {
+            let app0_len = u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize;
+            insert_at = (4 + app0_len).min(jpeg.len());
+        }
+
+        let segment = self.app1_segment();
+        let mut out = Vec::with_capacity(jpeg.len() + segment.len());
+        out.extend_from_slice(&jpeg[..insert_at]);
+        out.extend_from_slice(&segment);
+        out.extend_from_slice(&jpeg[insert_at..]);
+        out
+    }
+
+    /// The complete APP1 segment, marker and length included
+    fn app1_segment(&self) -> Vec<u8> {
+        let tiff = self.tiff();
+        let mut segment = Vec::with_capacity(tiff.len() + 10);
+        segment.extend_from_slice(&[0xFF, 0xE1]);
+        segment.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        segment.extend_from_slice(b"Exif\0\0");
+        segment.extend_from_slice(&tiff);
+        segment
+    }
+
+    /// Little-endian TIFF structure holding IFD0 and the Exif sub-IFD
+    fn tiff(&self) -> Vec<u8> {
+        let date_time = self.timestamp.as_deref()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok());
+
+        let mut ifd0 = Vec::new();
+        if let Some(name) = &self.camera_name {
+            ifd0.push((TAG_MAKE, TYPE_ASCII, ascii(name)));
+            ifd0.push((TAG_MODEL, TYPE_ASCII, ascii(name)));
+        }
+        ifd0.push((TAG_SOFTWARE, TYPE_ASCII, ascii(&Self::software())));
+        if let Some(dt) = &date_time {
+            ifd0.push((TAG_DATE_TIME, TYPE_ASCII, ascii(&dt.format("%Y:%m:%d %H:%M:%S").to_string())));
+        }
+
+        let mut exif_ifd = Vec::new();
+        if let Some(dt) = &date_time {
+            exif_ifd.push((TAG_DATE_TIME_ORIGINAL, TYPE_ASCII, ascii(&dt.format("%Y:%m:%d %H:%M:%S").to_string())));
+            exif_ifd.push((TAG_OFFSET_TIME_ORIGINAL, TYPE_ASCII, ascii(&dt.format("%:z").to_string())));
+        }
+        if let Some(comment) = &self.comment {
+            let mut value = b"ASCII\0\0\0".to_vec();
+            value.extend_from_slice(truncate(comment, MAX_COMMENT_BYTES).as_bytes());
+            exif_ifd.push((TAG_USER_COMMENT, TYPE_UNDEFINED, value));
+        }
+
+        // Header, then IFD0 (with a pointer to the Exif IFD), then the Exif IFD
+        let ifd0_offset = 8;
+        let ifd0_len = ifd_len(ifd0.len() + 1, &ifd0);
+        let exif_offset = ifd0_offset + ifd0_len;
+        ifd0.push((TAG_EXIF_IFD, TYPE_LONG, (exif_offset as u32).to_le_bytes().to_vec()));
+
+        let mut tiff = vec![b'I', b'I', 42, 0];
+        tiff.extend_from_slice(&(ifd0_offset as u32).to_le_bytes());
+        write_ifd(&mut tiff, &ifd0);
+        write_ifd(&mut tiff, &exif_ifd);
+        tiff
+    }
+}
+
+type Entry = (u16, u16, Vec<u8>);
+
+/// NUL-terminated ASCII, with non-ASCII characters replaced by `?`
+fn ascii(value: &str) -> Vec<u8> {
+    let mut bytes: Vec<u8> = value.chars().map(|c| if c.is_ascii() { c as u8 } else { b'?' }).collect();
+    bytes.push(0);
+    bytes
+}
+
+fn truncate(value: &str, max_bytes: usize) -> &str {
+    if value.len() <= max_bytes {
+        return value;
+    }
+    let mut end = max_bytes;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+/// Size of an IFD with `count` entries plus the out-of-line data of `entries`
+fn ifd_len(count: usize, entries: &[Entry]) -> usize {
+    let data: usize = entries.iter().map(|(_, _, v)| if v.len() > 4 { v.len() + v.len() % 2 } else { 0 }).sum();
+    2 + count * 12 + 4 + data
+}
+
+/// Append an IFD at the end of `tiff`, followed by its out-of-line values.
+/// Entries are sorted by tag as the TIFF spec requires.
+fn write_ifd(tiff: &mut Vec<u8>, entries: &[Entry]) {
+    let mut entries = entries.to_vec();
+    entries.sort_by_key(|(tag, _, _)| *tag);
+
+    let start = tiff.len();
+    let mut data_offset = start + 2 + entries.len() * 12 + 4;
+    let mut data = Vec::new();
+
+    tiff.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for (tag, kind, value) in &entries {
+        tiff.extend_from_slice(&tag.to_le_bytes());
+        tiff.extend_from_slice(&kind.to_le_bytes());
+        let count = if *kind == TYPE_LONG { value.len() / 4 } else { value.len() };
+        tiff.extend_from_slice(&(count as u32).to_le_bytes());
+        if value.len() <= 4 {
+            let mut inline = value.clone();
+            inline.resize(4, 0);
+            tiff.extend_from_slice(&inline);
+        } else {
+            tiff.extend_from_slice(&(data_offset as u32).to_le_bytes());
+            data.extend_from_slice(value);
+            if value.len() % 2 == 1 {
+                data.push(0);
+            }
+            data_offset += value.len() + value.len() % 2;
+        }
+    }
+    // No further IFDs
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+    tiff.extend_from_slice(&data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::jpeg::JpegEncoder;
+
+    fn sample_jpeg() -> Vec<u8> {
+        let image = image::RgbImage::from_pixel(16, 16, image::Rgb([120, 80, 40]));
+        let mut bytes = Vec::new();
+        JpegEncoder::new(&mut bytes).encode_image(&image).unwrap();
+        bytes
+    }
+
+    fn read_exif(jpeg: &[u8]) -> exif::Exif {
+        exif::Reader::new()
+            .read_from_container(&mut std::io::Cursor::new(jpeg))
+            .expect("EXIF should be readable")
+    }
+
+    fn ascii_field(exif: &exif::Exif, tag: exif::Tag) -> String {
+        match &exif.get_field(tag, exif::In::PRIMARY).expect("tag present").value {
+            exif::Value::Ascii(values) => String::from_utf8(values[0].clone()).unwrap(),
+            other => panic!("unexpected value {:?}", other),
+        }
+    }
+
+    #[test]
+    fn round_trips_all_tags() {
+        let metadata = ExifMetadata {
+            timestamp: Some("2024-03-05T07:08:09.123+00:00".to_string()),
+            camera_name: Some("Integrated Camera".to_string()),
+            comment: Some("front door".to_string()),
+        };
+        let jpeg = metadata.embed(&sample_jpeg());
+
+        let exif = read_exif(&jpeg);
+        assert_eq!(ascii_field(&exif, exif::Tag::DateTimeOriginal), "2024:03:05 07:08:09");
+        assert_eq!(ascii_field(&exif, exif::Tag::OffsetTimeOriginal), "+00:00");
+        assert_eq!(ascii_field(&exif, exif::Tag::Make), "Integrated Camera");
+        assert_eq!(ascii_field(&exif, exif::Tag::Model), "Integrated Camera");
+        assert_eq!(ascii_field(&exif, exif::Tag::Software), ExifMetadata::software());
+
+        match &exif.get_field(exif::Tag::UserComment, exif::In::PRIMARY).unwrap().value {
+            exif::Value::Undefined(bytes, _) => assert_eq!(bytes.as_slice(), b"ASCII\0\0\0front door"),
+            other => panic!("unexpected value {:?}", other),
+        }
+
+        // Still a valid image
+        let decoded = image::load_from_memory(&jpeg).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (16, 16));
+    }
+
+    #[test]
+    fn omits_optional_tags() {
+        let jpeg = ExifMetadata::default().embed(&sample_jpeg());
+
+        let exif = read_exif(&jpeg);
+        assert_eq!(ascii_field(&exif, exif::Tag::Software), ExifMetadata::software());
+        assert!(exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY).is_none());
+        assert!(exif.get_field(exif::Tag::UserComment, exif::In::PRIMARY).is_none());
+    }
+
+    #[test]
+    fn leaves_non_jpeg_input_alone() {
+        let data = b"not a jpeg".to_vec();
+        assert_eq!(ExifMetadata::default().embed(&data), data);
+    }
+}
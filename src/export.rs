@@ -0,0 +1,168 @@
+//! Discovered webcams as a document for other tools: a GeoJSON
+//! FeatureCollection to drop into a map viewer, a CSV table or plain JSON.
+//! GeoJSON can only place webcams with coordinates; the rest are handed back
+//! separately rather than dropped.
+
+use crate::shodan::RemoteWebcam;
+use serde_json::{json, Value};
+use std::fmt;
+use std::str::FromStr;
+
+/// Columns of a CSV export, in order
+const CSV_COLUMNS: [&str; 12] =
+    ["ip", "port", "url", "product", "last_seen", "hostname", "org", "country_code", "city", "vendor", "latitude", "longitude"];
+
+/// What to write the webcams as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    GeoJson,
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [ExportFormat::GeoJson, ExportFormat::Csv, ExportFormat::Json];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::GeoJson => "geojson",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    /// Extension of files written in the format
+    pub fn extension(self) -> &'static str {
+        self.name()
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ExportFormat::GeoJson => "application/geo+json",
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Json => "application/json",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        ExportFormat::ALL.into_iter().find(|format| format.name().eq_ignore_ascii_case(name.trim())).ok_or_else(|| {
+            let names: Vec<&str> = ExportFormat::ALL.iter().map(|format| format.name()).collect();
+            format!("unknown export format '{}'; use one of {}", name.trim(), names.join(", "))
+        })
+    }
+}
+
+/// Webcams written out by `export_webcams`
+#[derive(Debug, Clone)]
+pub struct WebcamExport {
+    pub format: ExportFormat,
+    pub document: String,
+    /// Webcams in `document`
+    pub exported: usize,
+    /// Webcams left out of `document` because the format needs coordinates
+    /// they lack; only GeoJSON leaves any out
+    pub without_coordinates: Vec<RemoteWebcam>,
+}
+
+/// Latitude and longitude of `webcam`, if Shodan located it that closely
+fn coordinates(webcam: &RemoteWebcam) -> Option<(f64, f64)> {
+    let location = webcam.location.as_ref()?;
+    Some((location.latitude?, location.longitude?))
+}
+
+/// Write `webcams` as `format`, in the order given
+pub fn export_webcams(webcams: &[RemoteWebcam], format: ExportFormat) -> WebcamExport {
+    let (exported, without_coordinates): (Vec<&RemoteWebcam>, Vec<&RemoteWebcam>) = match format {
+        ExportFormat::GeoJson => webcams.iter().partition(|webcam| coordinates(webcam).is_some()),
+        ExportFormat::Csv | ExportFormat::Json => (webcams.iter().collect(), Vec::new()),
+    };
+    let document = match format {
+        ExportFormat::GeoJson => geojson(&exported),
+        ExportFormat::Csv => csv(&exported),
+        ExportFormat::Json => serde_json::to_string_pretty(&exported).unwrap_or_default(),
+    };
+    WebcamExport {
+        format,
+        document,
+        exported: exported.len(),
+        without_coordinates: without_coordinates.into_iter().cloned().collect(),
+    }
+}
+
+/// Fields of `webcam` that become feature properties and CSV columns,
+/// coordinates aside
+fn properties(webcam: &RemoteWebcam) -> Value {
+    let location = webcam.location.as_ref();
+    json!({
+        "ip": webcam.ip,
+        "port": webcam.port,
+        "url": webcam.url,
+        "product": webcam.product,
+        "last_seen": webcam.last_seen,
+        "hostname": webcam.hostname,
+        "org": webcam.org,
+        "country_code": location.and_then(|l| l.country_code.as_deref()),
+        "city": location.and_then(|l| l.city.as_deref()),
+        "vendor": webcam.classification.as_ref().map(|classification| classification.vendor.name()),
+    })
+}
+
+/// A FeatureCollection with a Point per webcam. GeoJSON puts longitude
+/// before latitude (RFC 7946).
+fn geojson(webcams: &[&RemoteWebcam]) -> String {
+    let features: Vec<Value> = webcams
+        .iter()
+        .filter_map(|webcam| {
+            let (latitude, longitude) = coordinates(webcam)?;
+            Some(json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": [longitude, latitude] },
+                "properties": properties(webcam),
+            }))
+        })
+        .collect();
+    serde_json::to_string_pretty(&json!({ "type": "FeatureCollection", "features": features })).unwrap_or_default()
+}
+
+/// A header row and a row per webcam; coordinates are empty where unknown
+fn csv(webcams: &[&RemoteWebcam]) -> String {
+    let mut document = CSV_COLUMNS.join(",");
+    document.push_str("\r\n");
+    for webcam in webcams {
+        let mut fields = properties(webcam);
+        let (latitude, longitude) = coordinates(webcam).unzip();
+        fields["latitude"] = json!(latitude);
+        fields["longitude"] = json!(longitude);
+        let row: Vec<String> = CSV_COLUMNS
+            .iter()
+            .map(|column| match &fields[*column] {
+                Value::Null => String::new(),
+                Value::String(text) => csv_field(text),
+                other => other.to_string(),
+            })
+            .collect();
+        document.push_str(&row.join(","));
+        document.push_str("\r\n");
+    }
+    document
+}
+
+/// `text` as a CSV field, quoted when it holds a comma, quote or line break
+/// (RFC 4180)
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,61 @@
+use crate::image_processing::CropRect;
+use image::RgbImage;
+
+/// Default location of the SeetaFace frontal detection model
+const DEFAULT_MODEL_PATH: &str = "seeta_fd_frontal_v1.0.bin";
+
+#[derive(Debug, thiserror::Error)]
+pub enum FaceError {
+    #[error("Face detection not compiled in (enable the 'face_blur' feature)")]
+    NotSupported,
+    #[error("Failed to load face detection model from {path}: {message}")]
+    Model { path: String, message: String },
+}
+
+/// Path of the detection model, from `MCP_WEBCAM_FACE_MODEL`
+pub fn model_path() -> String {
+    std::env::var("MCP_WEBCAM_FACE_MODEL").unwrap_or_else(|_| DEFAULT_MODEL_PATH.to_string())
+}
+
+/// Detect faces and return their bounding boxes, clamped to the image
+pub fn detect_faces(img: &RgbImage) -> Result<Vec<CropRect>, FaceError> {
+    #[cfg(feature = "face_blur")]
+    {
+        use rustface::ImageData;
+
+        let path = model_path();
+        let mut detector = rustface::create_detector(&path).map_err(|e| FaceError::Model {
+            path: path.clone(),
+            message: e.to_string(),
+        })?;
+        detector.set_min_face_size(20);
+        detector.set_score_thresh(2.0);
+        detector.set_pyramid_scale_factor(0.8);
+        detector.set_slide_window_step(4, 4);
+
+        let gray = image::DynamicImage::ImageRgb8(img.clone()).into_luma8();
+        let (width, height) = gray.dimensions();
+        let faces = detector.detect(&ImageData::new(gray.as_raw(), width, height));
+
+        Ok(faces
+            .iter()
+            .map(|face| {
+                let bbox = face.bbox();
+                let x = bbox.x().max(0) as u32;
+                let y = bbox.y().max(0) as u32;
+                CropRect {
+                    x: x.min(width),
+                    y: y.min(height),
+                    width: bbox.width().min(width.saturating_sub(x)),
+                    height: bbox.height().min(height.saturating_sub(y)),
+                }
+            })
+            .collect())
+    }
+
+    #[cfg(not(feature = "face_blur"))]
+    {
+        let _ = img;
+        Err(FaceError::NotSupported)
+    }
+}
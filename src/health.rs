@@ -0,0 +1,94 @@
+//! Liveness report for the `health_check` tool and the startup self-check.
+//! Collecting it never opens a camera or calls Shodan: cameras are only
+//! enumerated (from the device cache when fresh) and the Shodan key status
+//! is whatever the last check found.
+
+use crate::server_stats::ServerStats;
+use crate::webcam::WebcamManager;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    /// Serving, but no camera can be enumerated or the Shodan key was rejected
+    Degraded,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShodanHealth {
+    pub enabled: bool,
+    /// Result of the last key check; `None` if it hasn't been checked
+    pub key_valid: Option<bool>,
+    pub checked_secs_ago: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub uptime_secs: u64,
+    /// Whether at least one camera could be enumerated; `None` when a
+    /// capture held the camera manager and enumeration was skipped
+    pub cameras_enumerable: Option<bool>,
+    pub camera_count: Option<usize>,
+    pub camera_error: Option<String>,
+    pub shodan: ShodanHealth,
+    /// Failed capture and Shodan tool calls since startup
+    pub failures_total: u64,
+    pub failures_by_type: BTreeMap<String, u64>,
+}
+
+impl HealthReport {
+    /// Collect the report. `manager` is `None` when it is busy.
+    pub fn collect(manager: Option<&WebcamManager>, stats: &ServerStats, shodan_enabled: bool) -> Self {
+        let (cameras_enumerable, camera_count, camera_error) = match manager.map(WebcamManager::list_cameras) {
+            Some(Ok(cameras)) => (Some(!cameras.is_empty()), Some(cameras.len()), None),
+            Some(Err(e)) => (Some(false), Some(0), Some(e.to_string())),
+            None => (None, None, None),
+        };
+
+        let key_check = stats.shodan_key_check();
+        let shodan = ShodanHealth {
+            enabled: shodan_enabled,
+            key_valid: key_check.map(|(_, valid)| valid),
+            checked_secs_ago: key_check.map(|(ago, _)| ago.as_secs()),
+        };
+
+        let status = if cameras_enumerable == Some(false) || shodan.key_valid == Some(false) {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Ok
+        };
+
+        let snapshot = stats.snapshot();
+        Self {
+            status,
+            uptime_secs: snapshot.uptime_secs,
+            cameras_enumerable,
+            camera_count,
+            camera_error,
+            shodan,
+            failures_total: snapshot.failures_total,
+            failures_by_type: snapshot.failures_by_type,
+        }
+    }
+
+    /// Log the report, warning about anything that makes it degraded
+    pub fn log(&self) {
+        match (self.cameras_enumerable, &self.camera_error) {
+            (Some(true), _) => info!("Health: {} camera(s) enumerable", self.camera_count.unwrap_or(0)),
+            (Some(false), Some(e)) => warn!("Health: cameras cannot be enumerated: {}", e),
+            (Some(false), None) => warn!("Health: no cameras found"),
+            (None, _) => info!("Health: camera enumeration skipped, a capture is in progress"),
+        }
+        match (self.shodan.enabled, self.shodan.key_valid) {
+            (false, _) => info!("Health: Shodan disabled"),
+            (true, None) => info!("Health: Shodan key not checked yet"),
+            (true, Some(true)) => info!("Health: Shodan key valid"),
+            (true, Some(false)) => warn!("Health: Shodan key was rejected"),
+        }
+        info!("Health: {:?}, up {}s, {} failed call(s)", self.status, self.uptime_secs, self.failures_total);
+    }
+}
@@ -0,0 +1,116 @@
+//! Shodan's honeypot scores of remote webcam hosts. Some "open webcams"
+//! are honeypots logging whoever connects; Shodan Labs' Honeyscore rates
+//! how likely an address is to be one, from 0.0 (not at all) to 1.0.
+//!
+//! Scores looked up by `check_honeypot` or by a search with
+//! `with_honeyscore` are remembered, so a host is looked up once. A score
+//! above the threshold earns a warning and, in strict mode, refuses
+//! `capture_remote_image` for the host with `HONEYPOT_SUSPECTED`.
+//!
+//! The table of scores is bounded: when it is full, the score looked up
+//! longest ago is forgotten.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Default score above which a host is taken for a honeypot
+pub const DEFAULT_HONEYPOT_THRESHOLD: f64 = 0.5;
+/// Scores remembered by default
+pub const DEFAULT_HONEYSCORE_CAPACITY: usize = 1024;
+/// Most hosts one search looks up scores for; the rest are left unscored
+pub const MAX_SEARCH_HONEYSCORES: usize = 20;
+
+/// The threshold, whether strict mode is on, and the scores looked up so
+/// far, shared by every Shodan client of a server
+#[derive(Debug)]
+pub struct HoneypotGuard {
+    threshold: f64,
+    strict: bool,
+    capacity: usize,
+    scores: Mutex<HashMap<String, (f64, Instant)>>,
+}
+
+/// A host's score and what it means
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HoneypotCheck {
+    pub ip: String,
+    pub honeyscore: f64,
+    pub threshold: f64,
+    /// Whether the score is above the threshold
+    pub likely_honeypot: bool,
+    pub warning: Option<String>,
+}
+
+impl Default for HoneypotGuard {
+    fn default() -> Self {
+        Self::new(DEFAULT_HONEYPOT_THRESHOLD, false)
+    }
+}
+
+/// `ip` as the table keys it: without brackets, lowercase
+fn ip_key(ip: &str) -> String {
+    ip.trim().trim_matches(['[', ']']).to_ascii_lowercase()
+}
+
+impl HoneypotGuard {
+    /// Guard warning of scores above `threshold` and, if `strict`,
+    /// refusing captures from those hosts
+    pub fn new(threshold: f64, strict: bool) -> Self {
+        Self::with_capacity(threshold, strict, DEFAULT_HONEYSCORE_CAPACITY)
+    }
+
+    /// Guard remembering at most `capacity` scores
+    pub fn with_capacity(threshold: f64, strict: bool, capacity: usize) -> Self {
+        Self { threshold, strict, capacity: capacity.max(1), scores: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// The score remembered for `ip`, if it was looked up
+    pub fn score(&self, ip: &str) -> Option<f64> {
+        let scores = self.scores.lock().unwrap_or_else(|e| e.into_inner());
+        scores.get(&ip_key(ip)).map(|(score, _)| *score)
+    }
+
+    /// Remember the score Shodan gave `ip`
+    pub fn record(&self, ip: &str, score: f64) {
+        let key = ip_key(ip);
+        let mut scores = self.scores.lock().unwrap_or_else(|e| e.into_inner());
+        if !scores.contains_key(&key) && scores.len() >= self.capacity {
+            if let Some(oldest) = scores.iter().min_by_key(|(_, (_, at))| *at).map(|(ip, _)| ip.clone()) {
+                scores.remove(&oldest);
+            }
+        }
+        scores.insert(key, (score, Instant::now()));
+    }
+
+    pub fn is_likely_honeypot(&self, score: f64) -> bool {
+        score > self.threshold
+    }
+
+    /// The warning a webcam entry of a host scoring `score` carries, if any
+    pub fn warning(&self, score: f64) -> Option<String> {
+        self.is_likely_honeypot(score).then(|| {
+            format!("Shodan rates this host {:.2} on its honeypot scale, above the {:.2} threshold: it is likely a honeypot", score, self.threshold)
+        })
+    }
+
+    /// What `score` means for `ip`
+    pub fn check(&self, ip: &str, score: f64) -> HoneypotCheck {
+        HoneypotCheck {
+            ip: ip_key(ip),
+            honeyscore: score,
+            threshold: self.threshold,
+            likely_honeypot: self.is_likely_honeypot(score),
+            warning: self.warning(score),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! A minimum interval between fetches from the same remote host. A client
+//! looping on `capture_remote_image` against one address would otherwise
+//! hammer someone's camera; with a cooldown, capturing, probing and
+//! validating a host within the interval of the last time fails with
+//! `HOST_COOLDOWN` and the time left to wait.
+//!
+//! The table of hosts is bounded: when it is full, the host fetched from
+//! longest ago is forgotten.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default time between fetches from one host
+pub const DEFAULT_HOST_COOLDOWN: Duration = Duration::from_secs(10);
+/// Hosts remembered by default
+pub const DEFAULT_COOLDOWN_CAPACITY: usize = 1024;
+/// Hosts listed in `CooldownStats::cooling`, soonest available last
+const COOLING_LISTED: usize = 20;
+
+/// Last fetch times of remote hosts, shared by every Shodan client of a
+/// server
+#[derive(Debug)]
+pub struct HostCooldown {
+    interval: Duration,
+    capacity: usize,
+    state: Mutex<CooldownState>,
+}
+
+#[derive(Debug, Default)]
+struct CooldownState {
+    last_fetch: HashMap<String, Instant>,
+    refused: u64,
+    evicted: u64,
+}
+
+/// A host still cooling down
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CoolingHost {
+    pub host: String,
+    pub remaining_ms: u64,
+}
+
+/// The cooldown table, for `get_server_stats`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CooldownStats {
+    pub interval_ms: u64,
+    pub capacity: usize,
+    /// Hosts remembered right now
+    pub tracked: usize,
+    /// Fetches refused since startup
+    pub refused: u64,
+    /// Hosts forgotten to make room
+    pub evicted: u64,
+    /// Hosts that would be refused right now, longest wait first
+    pub cooling: Vec<CoolingHost>,
+}
+
+impl Default for HostCooldown {
+    fn default() -> Self {
+        Self::new(DEFAULT_HOST_COOLDOWN)
+    }
+}
+
+/// `host` as the table keys it: IPv6 without brackets, lowercase
+fn host_key(host: &str) -> String {
+    host.trim_matches(['[', ']']).to_ascii_lowercase()
+}
+
+impl HostCooldown {
+    /// Cooldown of `interval` between fetches; zero allows every fetch
+    pub fn new(interval: Duration) -> Self {
+        Self::with_capacity(interval, DEFAULT_COOLDOWN_CAPACITY)
+    }
+
+    /// Cooldown remembering at most `capacity` hosts
+    pub fn with_capacity(interval: Duration, capacity: usize) -> Self {
+        Self { interval, capacity: capacity.max(1), state: Mutex::new(CooldownState::default()) }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Record a fetch from `host` now, or return how long until one is
+    /// allowed
+    pub fn claim(&self, host: &str) -> Result<(), Duration> {
+        self.claim_at(host, Instant::now())
+    }
+
+    /// `claim` as of `now`
+    pub fn claim_at(&self, host: &str, now: Instant) -> Result<(), Duration> {
+        if self.interval.is_zero() {
+            return Ok(());
+        }
+        let key = host_key(host);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(last) = state.last_fetch.get(&key) {
+            let elapsed = now.saturating_duration_since(*last);
+            if elapsed < self.interval {
+                state.refused += 1;
+                return Err(self.interval - elapsed);
+            }
+        }
+        if !state.last_fetch.contains_key(&key) && state.last_fetch.len() >= self.capacity {
+            // Hosts past their interval go first; if none is, the least
+            // recently fetched from
+            let interval = self.interval;
+            state.last_fetch.retain(|_, last| now.saturating_duration_since(*last) < interval);
+            if state.last_fetch.len() >= self.capacity {
+                if let Some(oldest) = state.last_fetch.iter().min_by_key(|(_, last)| **last).map(|(host, _)| host.clone()) {
+                    state.last_fetch.remove(&oldest);
+                    state.evicted += 1;
+                }
+            }
+        }
+        state.last_fetch.insert(key, now);
+        Ok(())
+    }
+
+    pub fn stats(&self) -> CooldownStats {
+        self.stats_at(Instant::now())
+    }
+
+    /// `stats` as of `now`
+    pub fn stats_at(&self, now: Instant) -> CooldownStats {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut cooling: Vec<CoolingHost> = state
+            .last_fetch
+            .iter()
+            .filter_map(|(host, last)| {
+                let remaining = self.interval.checked_sub(now.saturating_duration_since(*last)).filter(|left| !left.is_zero())?;
+                Some(CoolingHost { host: host.clone(), remaining_ms: remaining.as_millis() as u64 })
+            })
+            .collect();
+        cooling.sort_by(|a, b| b.remaining_ms.cmp(&a.remaining_ms).then_with(|| a.host.cmp(&b.host)));
+        cooling.truncate(COOLING_LISTED);
+        CooldownStats {
+            interval_ms: self.interval.as_millis() as u64,
+            capacity: self.capacity,
+            tracked: state.last_fetch.len(),
+            refused: state.refused,
+            evicted: state.evicted,
+            cooling,
+        }
+    }
+}
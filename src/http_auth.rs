@@ -0,0 +1,216 @@
+//! HTTP Basic and Digest (RFC 7616, MD5 and SHA-256) authentication for
+//! remote webcams, answering the challenge in a 401's `WWW-Authenticate`.
+
+use crate::shodan::SecretString;
+use base64::{engine::general_purpose, Engine as _};
+use std::collections::HashMap;
+
+/// Username and password for a remote webcam. The password never appears
+/// in `Debug` output.
+#[derive(Debug, Clone)]
+pub struct WebcamCredentials {
+    pub username: String,
+    pub password: SecretString,
+}
+
+impl WebcamCredentials {
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self { username: username.into(), password: SecretString::new(password) }
+    }
+}
+
+/// One challenge of a `WWW-Authenticate` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    /// As sent, e.g. `Digest`
+    pub scheme: String,
+    /// Auth parameters by lowercase name, unquoted
+    pub params: HashMap<String, String>,
+}
+
+impl Challenge {
+    pub fn realm(&self) -> Option<&str> {
+        self.params.get("realm").map(String::as_str)
+    }
+
+    fn is(&self, scheme: &str) -> bool {
+        self.scheme.eq_ignore_ascii_case(scheme)
+    }
+
+    /// Digest hash algorithm, if it is one this module implements
+    fn digest_algorithm(&self) -> Option<Algorithm> {
+        let name = self.params.get("algorithm").map(String::as_str).unwrap_or("MD5");
+        let (base, session) = match name.len().checked_sub(5).and_then(|at| Some((at, name.get(at..)?))) {
+            Some((at, suffix)) if suffix.eq_ignore_ascii_case("-sess") => (&name[..at], true),
+            _ => (name, false),
+        };
+        let hash = if base.eq_ignore_ascii_case("MD5") {
+            Hash::Md5
+        } else if base.eq_ignore_ascii_case("SHA-256") {
+            Hash::Sha256
+        } else {
+            return None;
+        };
+        Some(Algorithm { hash, session, name: name.to_string() })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hash {
+    Md5,
+    Sha256,
+}
+
+struct Algorithm {
+    hash: Hash,
+    /// `-sess` variant: the first hash also covers the nonces
+    session: bool,
+    /// As the server named it, echoed back
+    name: String,
+}
+
+impl Algorithm {
+    fn hex(&self, data: &str) -> String {
+        let bytes: Vec<u8> = match self.hash {
+            Hash::Md5 => <md5::Md5 as md5::Digest>::digest(data.as_bytes()).to_vec(),
+            Hash::Sha256 => <sha2::Sha256 as sha2::Digest>::digest(data.as_bytes()).to_vec(),
+        };
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// Challenges in a `WWW-Authenticate` value, which may hold several, e.g.
+/// `Digest realm="cam", nonce="abc", qop="auth", Basic realm="cam"`
+pub fn parse_challenges(header: &str) -> Vec<Challenge> {
+    let mut challenges = Vec::new();
+    let mut rest = header.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    while !rest.is_empty() {
+        let end = rest.find(|c: char| c == ',' || c.is_whitespace()).unwrap_or(rest.len());
+        let scheme = &rest[..end];
+        rest = &rest[end..];
+        let mut params = HashMap::new();
+        loop {
+            rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+            let Some(eq) = rest.find('=') else { break };
+            let name = rest[..eq].trim_end();
+            // A token not followed by `=` is the next challenge's scheme
+            if name.is_empty() || name.contains(|c: char| c == ',' || c.is_whitespace()) {
+                break;
+            }
+            let value = rest[eq + 1..].trim_start();
+            let (value, after) = match value.strip_prefix('"') {
+                Some(quoted) => {
+                    let mut unquoted = String::new();
+                    let mut end = quoted.len();
+                    let mut chars = quoted.char_indices();
+                    while let Some((at, c)) = chars.next() {
+                        match c {
+                            '\\' => unquoted.extend(chars.next().map(|(_, escaped)| escaped)),
+                            '"' => {
+                                end = at + 1;
+                                break;
+                            }
+                            c => unquoted.push(c),
+                        }
+                    }
+                    (unquoted, &quoted[end..])
+                }
+                None => {
+                    let end = value.find(',').unwrap_or(value.len());
+                    (value[..end].trim().to_string(), &value[end..])
+                }
+            };
+            params.insert(name.to_ascii_lowercase(), value);
+            rest = after;
+        }
+        challenges.push(Challenge { scheme: scheme.to_string(), params });
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    }
+    challenges
+}
+
+/// The challenge to answer: Digest with SHA-256, then Digest with MD5,
+/// then Basic. `None` if the server offers none of them.
+pub fn preferred_challenge(challenges: &[Challenge]) -> Option<&Challenge> {
+    let rank = |challenge: &Challenge| {
+        if challenge.is("Digest") {
+            match challenge.digest_algorithm()?.hash {
+                Hash::Sha256 => Some(0),
+                Hash::Md5 => Some(1),
+            }
+        } else if challenge.is("Basic") {
+            Some(2)
+        } else {
+            None
+        }
+    };
+    challenges
+        .iter()
+        .filter_map(|challenge| Some((rank(challenge)?, challenge)))
+        .min_by_key(|(rank, _)| *rank)
+        .map(|(_, challenge)| challenge)
+}
+
+/// `Authorization` value answering `challenge` for a `method` request of
+/// `uri` (path and query), or `None` for a scheme that isn't supported
+pub fn authorization(challenge: &Challenge, method: &str, uri: &str, credentials: &WebcamCredentials) -> Option<String> {
+    if challenge.is("Basic") {
+        let pair = format!("{}:{}", credentials.username, credentials.password.expose());
+        Some(format!("Basic {}", general_purpose::STANDARD.encode(pair)))
+    } else if challenge.is("Digest") {
+        let cnonce = uuid::Uuid::new_v4().simple().to_string();
+        digest_authorization(challenge, method, uri, credentials, &cnonce, 1)
+    } else {
+        None
+    }
+}
+
+/// Digest `Authorization` value with the given client nonce and nonce
+/// count, as RFC 7616 computes it
+pub fn digest_authorization(
+    challenge: &Challenge,
+    method: &str,
+    uri: &str,
+    credentials: &WebcamCredentials,
+    cnonce: &str,
+    nonce_count: u32,
+) -> Option<String> {
+    let algorithm = challenge.digest_algorithm()?;
+    let realm = challenge.realm().unwrap_or_default();
+    let nonce = challenge.params.get("nonce")?;
+    // Only `auth` is implemented; `auth-int` would hash the body
+    let qop = match challenge.params.get("qop") {
+        Some(offered) => Some(offered.split(',').map(str::trim).find(|qop| qop.eq_ignore_ascii_case("auth"))?),
+        None => None,
+    };
+    let nc = format!("{:08x}", nonce_count);
+
+    let mut ha1 = algorithm.hex(&format!("{}:{}:{}", credentials.username, realm, credentials.password.expose()));
+    if algorithm.session {
+        ha1 = algorithm.hex(&format!("{}:{}:{}", ha1, nonce, cnonce));
+    }
+    let ha2 = algorithm.hex(&format!("{}:{}", method, uri));
+    let response = match qop {
+        Some(qop) => algorithm.hex(&format!("{}:{}:{}:{}:{}:{}", ha1, nonce, nc, cnonce, qop, ha2)),
+        None => algorithm.hex(&format!("{}:{}:{}", ha1, nonce, ha2)),
+    };
+
+    let quote = |value: &str| format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut fields = vec![
+        format!("username={}", quote(&credentials.username)),
+        format!("realm={}", quote(realm)),
+        format!("nonce={}", quote(nonce)),
+        format!("uri={}", quote(uri)),
+        format!("algorithm={}", algorithm.name),
+        format!("response={}", quote(&response)),
+    ];
+    if let Some(qop) = qop {
+        fields.push(format!("qop={}", qop));
+        fields.push(format!("nc={}", nc));
+        fields.push(format!("cnonce={}", quote(cnonce)));
+    }
+    if let Some(opaque) = challenge.params.get("opaque") {
+        fields.push(format!("opaque={}", quote(opaque)));
+    }
+    Some(format!("Digest {}", fields.join(", ")))
+}
@@ -0,0 +1,825 @@
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+
+/// Mean luminance (0-255) below which `EnhanceMode::Auto` kicks in
+pub const AUTO_ENHANCE_LUMINANCE_THRESHOLD: f32 = 60.0;
+
+/// Low-light enhancement requested for a capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnhanceMode {
+    /// Apply gamma correction only if the frame is darker than the threshold
+    Auto,
+    /// Always apply gamma correction
+    Gamma,
+    /// Always apply histogram equalization on the luminance channel
+    Equalize,
+}
+
+impl EnhanceMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "gamma" => Some(Self::Gamma),
+            "equalize" => Some(Self::Equalize),
+            _ => None,
+        }
+    }
+}
+
+/// What the enhancement step actually did to a frame
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnhancementInfo {
+    pub requested: EnhanceMode,
+    pub applied: bool,
+    /// "gamma" or "equalize" when applied
+    pub method: Option<String>,
+    pub mean_luminance: f32,
+    /// Gamma exponent used, when gamma correction was applied
+    pub gamma: Option<f32>,
+}
+
+/// White balance requested for a capture
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WhiteBalance {
+    /// Software gray-world correction
+    Auto,
+    /// Colour temperature in Kelvin, set on the camera when supported
+    Kelvin(u32),
+}
+
+impl WhiteBalance {
+    /// Parse `"auto"` or a Kelvin value (number or numeric string, 1000-15000)
+    pub fn from_json(value: &serde_json::Value) -> Option<Self> {
+        let kelvin = match value {
+            serde_json::Value::String(s) if s.eq_ignore_ascii_case("auto") => return Some(Self::Auto),
+            serde_json::Value::String(s) => s.trim_end_matches(['K', 'k']).parse::<u32>().ok()?,
+            serde_json::Value::Number(n) => n.as_u64()? as u32,
+            _ => return None,
+        };
+        (1000..=15000).contains(&kelvin).then_some(Self::Kelvin(kelvin))
+    }
+}
+
+/// How white balance was applied to a capture
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhiteBalanceInfo {
+    pub requested: WhiteBalance,
+    /// "hardware" or "software_gray_world"
+    pub path: String,
+    /// Per-channel gains applied in software
+    pub gains: Option<[f32; 3]>,
+}
+
+/// Gray-world white balance: scale each channel so the channel means match.
+/// Returns the R, G, B gains applied.
+pub fn gray_world(img: &mut RgbImage) -> [f32; 3] {
+    let mut sums = [0f64; 3];
+    for p in img.pixels() {
+        for (sum, value) in sums.iter_mut().zip(p.0.iter()) {
+            *sum += *value as f64;
+        }
+    }
+
+    let mean = (sums[0] + sums[1] + sums[2]) / 3.0;
+    let mut gains = [1f32; 3];
+    for (gain, sum) in gains.iter_mut().zip(sums.iter()) {
+        if *sum > 0.0 {
+            *gain = ((mean / sum) as f32).clamp(0.25, 4.0);
+        }
+    }
+
+    for pixel in img.pixels_mut() {
+        for (channel, gain) in pixel.0.iter_mut().zip(gains.iter()) {
+            *channel = (*channel as f32 * gain).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    gains
+}
+
+/// Rectangular region of a frame, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CropRect {
+    /// Parse a `{"x", "y", "width", "height"}` JSON object
+    pub fn from_json(value: &serde_json::Value) -> Option<Self> {
+        serde_json::from_value(value.clone()).ok()
+    }
+
+    /// Crop the image to this region, clamped to the image bounds.
+    /// Returns `None` if the region does not overlap the image.
+    pub fn apply(&self, img: &RgbImage) -> Option<RgbImage> {
+        if self.width == 0 || self.height == 0 || self.x >= img.width() || self.y >= img.height() {
+            return None;
+        }
+        let width = self.width.min(img.width() - self.x);
+        let height = self.height.min(img.height() - self.y);
+        Some(image::imageops::crop_imm(img, self.x, self.y, width, height).to_image())
+    }
+}
+
+/// Rec. 601 luma of an RGB pixel
+fn luma(r: u8, g: u8, b: u8) -> f32 {
+    0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32
+}
+
+/// Mean luminance of the image on a 0-255 scale
+pub fn mean_luminance(img: &RgbImage) -> f32 {
+    let pixel_count = (img.width() as u64 * img.height() as u64).max(1);
+    let total: f64 = img
+        .pixels()
+        .map(|p| luma(p[0], p[1], p[2]) as f64)
+        .sum();
+    (total / pixel_count as f64) as f32
+}
+
+/// Brightness and colour summary of an image, on a 0-255 scale
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ImageStats {
+    pub mean_luminance: f32,
+    /// Standard deviation of luminance, a rough measure of contrast
+    pub luminance_stddev: f32,
+    /// Mean of the R, G and B channels
+    pub mean_rgb: [f32; 3],
+}
+
+/// Brightness and colour statistics of the image
+pub fn image_stats(img: &RgbImage) -> ImageStats {
+    let pixel_count = (img.width() as u64 * img.height() as u64).max(1) as f64;
+    let mut channels = [0f64; 3];
+    let mut luma_sum = 0f64;
+    let mut luma_squares = 0f64;
+    for p in img.pixels() {
+        for (sum, value) in channels.iter_mut().zip(p.0.iter()) {
+            *sum += *value as f64;
+        }
+        let y = luma(p[0], p[1], p[2]) as f64;
+        luma_sum += y;
+        luma_squares += y * y;
+    }
+    let mean = luma_sum / pixel_count;
+    ImageStats {
+        mean_luminance: mean as f32,
+        luminance_stddev: (luma_squares / pixel_count - mean * mean).max(0.0).sqrt() as f32,
+        mean_rgb: channels.map(|sum| (sum / pixel_count) as f32),
+    }
+}
+
+/// Apply the requested enhancement in place and report what was done
+pub fn enhance(img: &mut RgbImage, mode: EnhanceMode) -> EnhancementInfo {
+    let mean = mean_luminance(img);
+    let mut info = EnhancementInfo {
+        requested: mode,
+        applied: false,
+        method: None,
+        mean_luminance: mean,
+        gamma: None,
+    };
+
+    match mode {
+        EnhanceMode::Auto if mean >= AUTO_ENHANCE_LUMINANCE_THRESHOLD => {}
+        EnhanceMode::Auto | EnhanceMode::Gamma => {
+            let gamma = adaptive_gamma(mean);
+            apply_gamma(img, gamma);
+            info.applied = true;
+            info.method = Some("gamma".to_string());
+            info.gamma = Some(gamma);
+        }
+        EnhanceMode::Equalize => {
+            equalize_luminance(img);
+            info.applied = true;
+            info.method = Some("equalize".to_string());
+        }
+    }
+
+    info
+}
+
+/// Gamma that maps the current mean luminance to mid-grey, clamped so it
+/// only ever brightens and never blows out the image completely
+fn adaptive_gamma(mean: f32) -> f32 {
+    let normalized = (mean / 255.0).clamp(0.001, 0.999);
+    (0.5f32.ln() / normalized.ln()).clamp(0.25, 1.0)
+}
+
+/// Apply `out = in ^ gamma` to every channel using a lookup table
+pub fn apply_gamma(img: &mut RgbImage, gamma: f32) {
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        let v = (i as f32 / 255.0).powf(gamma) * 255.0;
+        *entry = v.round().clamp(0.0, 255.0) as u8;
+    }
+
+    for pixel in img.pixels_mut() {
+        for channel in pixel.0.iter_mut() {
+            *channel = lut[*channel as usize];
+        }
+    }
+}
+
+/// Histogram-equalize the luminance (Y) channel, keeping chroma (Cb/Cr) intact
+pub fn equalize_luminance(img: &mut RgbImage) {
+    let pixel_count = img.width() as u64 * img.height() as u64;
+    if pixel_count == 0 {
+        return;
+    }
+
+    let mut histogram = [0u64; 256];
+    for p in img.pixels() {
+        let y = luma(p[0], p[1], p[2]).round().clamp(0.0, 255.0) as usize;
+        histogram[y] += 1;
+    }
+
+    // Cumulative distribution mapped back onto 0-255
+    let mut cdf = [0u64; 256];
+    let mut running = 0u64;
+    for (i, count) in histogram.iter().enumerate() {
+        running += count;
+        cdf[i] = running;
+    }
+    let cdf_min = cdf.iter().copied().find(|&c| c > 0).unwrap_or(0);
+    let denominator = (pixel_count - cdf_min).max(1) as f32;
+
+    let mut lut = [0f32; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        *entry = (cdf[i].saturating_sub(cdf_min)) as f32 / denominator * 255.0;
+    }
+
+    for pixel in img.pixels_mut() {
+        let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+        let y = 0.299 * r + 0.587 * g + 0.114 * b;
+        let cb = 128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b;
+        let cr = 128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b;
+
+        let new_y = lut[y.round().clamp(0.0, 255.0) as usize];
+
+        let new_r = new_y + 1.402 * (cr - 128.0);
+        let new_g = new_y - 0.344136 * (cb - 128.0) - 0.714136 * (cr - 128.0);
+        let new_b = new_y + 1.772 * (cb - 128.0);
+
+        pixel[0] = new_r.round().clamp(0.0, 255.0) as u8;
+        pixel[1] = new_g.round().clamp(0.0, 255.0) as u8;
+        pixel[2] = new_b.round().clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// Grow a rectangle by `fraction` of its size on each side, clamped to the image
+pub fn expand_rect(rect: &CropRect, fraction: f32, img_width: u32, img_height: u32) -> CropRect {
+    let dx = (rect.width as f32 * fraction) as u32;
+    let dy = (rect.height as f32 * fraction) as u32;
+    let x = rect.x.saturating_sub(dx);
+    let y = rect.y.saturating_sub(dy);
+    CropRect {
+        x,
+        y,
+        width: (rect.x + rect.width + dx).min(img_width).saturating_sub(x),
+        height: (rect.y + rect.height + dy).min(img_height).saturating_sub(y),
+    }
+}
+
+/// Apply a strong Gaussian blur inside a rectangle
+pub fn blur_rect(img: &mut RgbImage, rect: &CropRect) {
+    let region = match rect.apply(img) {
+        Some(region) => region,
+        None => return,
+    };
+    // Scale the blur with the region so large faces stay unrecognisable
+    let sigma = (region.width().max(region.height()) as f32 / 6.0).max(8.0);
+    let blurred = image::imageops::blur(&region, sigma);
+    image::imageops::replace(img, &blurred, rect.x as i64, rect.y as i64);
+}
+
+/// Fill a rectangle with a solid colour, clipped to the image
+pub fn fill_rect(img: &mut RgbImage, rect: &CropRect, color: [u8; 3]) {
+    let x_end = rect.x.saturating_add(rect.width).min(img.width());
+    let y_end = rect.y.saturating_add(rect.height).min(img.height());
+    for y in rect.y.min(y_end)..y_end {
+        for x in rect.x.min(x_end)..x_end {
+            img.put_pixel(x, y, image::Rgb(color));
+        }
+    }
+}
+
+/// Draw a rectangle outline of the given colour and thickness, clipped to the image
+pub fn draw_rect(img: &mut RgbImage, rect: &CropRect, color: [u8; 3], thickness: u32) {
+    let (img_w, img_h) = (img.width(), img.height());
+    if rect.x >= img_w || rect.y >= img_h {
+        return;
+    }
+    let x_end = (rect.x + rect.width).min(img_w);
+    let y_end = (rect.y + rect.height).min(img_h);
+
+    for y in rect.y..y_end {
+        for x in rect.x..x_end {
+            let on_border = x < rect.x + thickness
+                || y < rect.y + thickness
+                || x + thickness >= x_end
+                || y + thickness >= y_end;
+            if on_border {
+                img.put_pixel(x, y, image::Rgb(color));
+            }
+        }
+    }
+}
+
+/// Colours of the bars drawn by `color_bars`, left to right
+pub const COLOR_BARS: [[u8; 3]; 8] = [
+    [255, 255, 255],
+    [255, 255, 0],
+    [0, 255, 255],
+    [0, 255, 0],
+    [255, 0, 255],
+    [255, 0, 0],
+    [0, 0, 255],
+    [0, 0, 0],
+];
+
+/// Test pattern: eight vertical bars of `COLOR_BARS`
+pub fn color_bars(width: u32, height: u32) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, _| {
+        let bar = (x as u64 * COLOR_BARS.len() as u64 / width.max(1) as u64) as usize;
+        image::Rgb(COLOR_BARS[bar.min(COLOR_BARS.len() - 1)])
+    })
+}
+
+/// 3x5 pixel glyph for the characters `draw_text` supports; each row's
+/// low three bits are the pixels, most significant on the left
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        _ => [0; 5],
+    }
+}
+
+/// Draw digits (and `:`, `.`, `#`) with a built-in 3x5 font, each font
+/// pixel `scale` pixels square, clipped to the image. Other characters
+/// are drawn as blanks.
+pub fn draw_text(img: &mut RgbImage, x: u32, y: u32, scale: u32, text: &str, color: [u8; 3]) {
+    let scale = scale.max(1);
+    for (i, c) in text.chars().enumerate() {
+        let char_x = x + i as u32 * 4 * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..3 {
+                if bits & (0b100 >> col) != 0 {
+                    let rect = CropRect {
+                        x: char_x + col * scale,
+                        y: y + row as u32 * scale,
+                        width: scale,
+                        height: scale,
+                    };
+                    fill_rect(img, &rect, color);
+                }
+            }
+        }
+    }
+}
+
+/// Per-channel difference (0-255) above which a pixel counts as changed
+pub const DEFAULT_DIFF_THRESHOLD: u8 = 30;
+
+/// Result of comparing two frames
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffReport {
+    /// Fraction of pixels that changed, 0.0-1.0
+    pub changed_fraction: f32,
+    /// Mean absolute per-channel difference, 0.0-1.0
+    pub mean_difference: f32,
+    pub changed_pixels: u64,
+    /// Bounding box around all changed pixels, `None` if nothing changed
+    pub changed_region: Option<CropRect>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Scale the smaller of two images up to the larger one's dimensions.
+/// Returns true when a resize was necessary.
+pub fn match_dimensions(a: &mut RgbImage, b: &mut RgbImage) -> bool {
+    if a.dimensions() == b.dimensions() {
+        return false;
+    }
+    let area_a = a.width() as u64 * a.height() as u64;
+    let area_b = b.width() as u64 * b.height() as u64;
+    let filter = image::imageops::FilterType::Triangle;
+    if area_a < area_b {
+        *a = image::imageops::resize(a, b.width(), b.height(), filter);
+    } else {
+        *b = image::imageops::resize(b, a.width(), a.height(), filter);
+    }
+    true
+}
+
+fn pixel_changed(a: &image::Rgb<u8>, b: &image::Rgb<u8>, threshold: u8) -> bool {
+    a.0.iter().zip(b.0.iter()).any(|(x, y)| x.abs_diff(*y) > threshold)
+}
+
+/// Compare two images of equal dimensions
+pub fn diff(a: &RgbImage, b: &RgbImage, threshold: u8) -> DiffReport {
+    let (width, height) = a.dimensions();
+    let mut changed_pixels = 0u64;
+    let mut total_difference = 0u64;
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0u32, 0u32);
+
+    for (x, y, pa) in a.enumerate_pixels() {
+        let pb = b.get_pixel(x, y);
+        total_difference += pa.0.iter().zip(pb.0.iter())
+            .map(|(p, q)| p.abs_diff(*q) as u64)
+            .sum::<u64>();
+        if pixel_changed(pa, pb, threshold) {
+            changed_pixels += 1;
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+
+    let pixel_count = (width as u64 * height as u64).max(1);
+    let changed_region = (changed_pixels > 0).then(|| CropRect {
+        x: min_x,
+        y: min_y,
+        width: max_x - min_x + 1,
+        height: max_y - min_y + 1,
+    });
+
+    DiffReport {
+        changed_fraction: changed_pixels as f32 / pixel_count as f32,
+        mean_difference: total_difference as f32 / (pixel_count * 3 * 255) as f32,
+        changed_pixels,
+        changed_region,
+        width,
+        height,
+    }
+}
+
+/// Visual diff: a dimmed greyscale copy of `b` with changed pixels in red
+pub fn diff_image(a: &RgbImage, b: &RgbImage, threshold: u8) -> RgbImage {
+    let mut out = RgbImage::new(b.width(), b.height());
+    for (x, y, pb) in b.enumerate_pixels() {
+        let pa = a.get_pixel(x, y);
+        let pixel = if pixel_changed(pa, pb, threshold) {
+            [255, 0, 0]
+        } else {
+            let grey = (luma(pb[0], pb[1], pb[2]) * 0.5) as u8;
+            [grey, grey, grey]
+        };
+        out.put_pixel(x, y, image::Rgb(pixel));
+    }
+    out
+}
+
+/// Encode frames as an infinitely looping animated GIF
+pub fn encode_gif(frames: &[RgbImage], frame_delay_ms: u32) -> Result<Vec<u8>, image::ImageError> {
+    let (width, height) = frames.first().map(|f| f.dimensions()).unwrap_or((0, 0));
+    let frames = frames
+        .iter()
+        .map(|frame| gif_frame(frame, frame_delay_ms))
+        .collect::<Result<Vec<_>, _>>()?;
+    write_gif(&frames, width, height)
+}
+
+fn gif_error(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> image::ImageError {
+    image::ImageError::Encoding(image::error::EncodingError::new(image::ImageFormat::Gif.into(), e))
+}
+
+/// Quantize one GIF frame. This is the expensive part of GIF encoding and
+/// is independent per frame, so frames can be prepared in parallel.
+pub fn gif_frame(img: &RgbImage, frame_delay_ms: u32) -> Result<gif::Frame<'static>, image::ImageError> {
+    let width = u16::try_from(img.width()).map_err(|_| gif_error("frame too wide for GIF"))?;
+    let height = u16::try_from(img.height()).map_err(|_| gif_error("frame too tall for GIF"))?;
+    let mut frame = gif::Frame::from_rgb_speed(width, height, img.as_raw(), 10);
+    // GIF delays are in hundredths of a second
+    frame.delay = (frame_delay_ms / 10).min(u16::MAX as u32) as u16;
+    Ok(frame)
+}
+
+/// Write frames from `gif_frame` as an infinitely looping animated GIF
+pub fn write_gif(frames: &[gif::Frame<'static>], width: u32, height: u32) -> Result<Vec<u8>, image::ImageError> {
+    let width = u16::try_from(width).map_err(|_| gif_error("frame too wide for GIF"))?;
+    let height = u16::try_from(height).map_err(|_| gif_error("frame too tall for GIF"))?;
+
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(&mut bytes, width, height, &[]).map_err(gif_error)?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(gif_error)?;
+        for frame in frames {
+            encoder.write_frame(frame).map_err(gif_error)?;
+        }
+    }
+    Ok(bytes)
+}
+
+/// Name of the pure-Rust JPEG encoder in capture metadata
+pub const IMAGE_JPEG_ENCODER: &str = "image";
+/// Name of the libjpeg-turbo encoder in capture metadata
+pub const TURBOJPEG_ENCODER: &str = "turbojpeg";
+
+/// Encode a frame as JPEG, returning the bytes and the name of the encoder
+/// used. With the `turbojpeg` feature libjpeg-turbo is tried first and the
+/// `image` crate's encoder is the fallback.
+pub fn encode_jpeg(img: &RgbImage, quality: u8) -> Result<(Vec<u8>, &'static str), image::ImageError> {
+    #[cfg(feature = "turbojpeg")]
+    match encode_turbojpeg(img, quality) {
+        Ok(bytes) => return Ok((bytes, TURBOJPEG_ENCODER)),
+        Err(e) => tracing::warn!("libjpeg-turbo encoding failed, using the image crate encoder: {}", e),
+    }
+
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, quality).encode_image(img)?;
+    Ok((buffer.into_inner(), IMAGE_JPEG_ENCODER))
+}
+
+#[cfg(feature = "turbojpeg")]
+fn encode_turbojpeg(img: &RgbImage, quality: u8) -> Result<Vec<u8>, turbojpeg::Error> {
+    let mut compressor = turbojpeg::Compressor::new()?;
+    compressor.set_quality(quality as i32)?;
+    compressor.set_subsamp(turbojpeg::Subsamp::Sub2x2)?;
+    compressor.compress_to_vec(turbojpeg::Image {
+        pixels: img.as_raw().as_slice(),
+        width: img.width() as usize,
+        pitch: img.width() as usize * 3,
+        height: img.height() as usize,
+        format: turbojpeg::PixelFormat::RGB,
+    })
+}
+
+/// Encode frames as a raw MJPEG stream (concatenated JPEGs)
+pub fn encode_mjpeg(frames: &[RgbImage]) -> Result<Vec<u8>, image::ImageError> {
+    let mut bytes = Vec::new();
+    for frame in frames {
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        frame.write_to(&mut buffer, image::ImageFormat::Jpeg)?;
+        bytes.extend_from_slice(&buffer.into_inner());
+    }
+    Ok(bytes)
+}
+
+/// Width and height from an encoded image's header, without decoding its
+/// pixels. The first bytes of an image do, as long as they hold the header.
+pub fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    image::ImageReader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+/// Downscale so the width is at most `max_width`, preserving aspect ratio
+pub fn limit_width(img: &RgbImage, max_width: u32) -> Option<RgbImage> {
+    if max_width == 0 || img.width() <= max_width {
+        return None;
+    }
+    let height = ((img.height() as u64 * max_width as u64) / img.width() as u64).max(1) as u32;
+    Some(image::imageops::resize(img, max_width, height, image::imageops::FilterType::Triangle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey(width: u32, height: u32, level: u8) -> RgbImage {
+        RgbImage::from_pixel(width, height, image::Rgb([level; 3]))
+    }
+
+    #[test]
+    fn gamma_maps_each_channel_through_the_curve() {
+        let mut img = RgbImage::from_fn(2, 1, |x, _| if x == 0 { image::Rgb([0, 64, 255]) } else { image::Rgb([128, 16, 32]) });
+        apply_gamma(&mut img, 0.5);
+        // round(sqrt(v / 255) * 255)
+        assert_eq!(img.get_pixel(0, 0).0, [0, 128, 255]);
+        assert_eq!(img.get_pixel(1, 0).0, [181, 64, 90]);
+    }
+
+    #[test]
+    fn auto_brightens_dark_frames_to_mid_grey_and_leaves_bright_ones() {
+        let mut dark = grey(4, 4, 32);
+        let info = enhance(&mut dark, EnhanceMode::Auto);
+        assert!(info.applied);
+        assert_eq!(info.method.as_deref(), Some("gamma"));
+        assert_eq!(info.mean_luminance.round(), 32.0);
+        let gamma = info.gamma.unwrap();
+        assert!((gamma - 0.334).abs() < 0.001, "{}", gamma);
+        let level = dark.get_pixel(0, 0)[0];
+        assert!((127..=128).contains(&level), "{}", level);
+
+        let mut bright = grey(4, 4, 100);
+        let info = enhance(&mut bright, EnhanceMode::Auto);
+        assert!(!info.applied);
+        assert_eq!(info.gamma, None);
+        assert_eq!(bright, grey(4, 4, 100));
+    }
+
+    #[test]
+    fn gamma_mode_never_darkens() {
+        // A bright frame gets gamma 1, the clamp's upper end
+        let mut bright = grey(2, 2, 200);
+        let info = enhance(&mut bright, EnhanceMode::Gamma);
+        assert!(info.applied);
+        assert_eq!(info.gamma, Some(1.0));
+        assert_eq!(bright, grey(2, 2, 200));
+    }
+
+    #[test]
+    fn equalize_spreads_luminance_over_the_full_range() {
+        // Two grey levels become black and white; greys stay grey
+        let mut img = RgbImage::from_fn(4, 2, |_, y| image::Rgb([if y == 0 { 50 } else { 100 }; 3]));
+        let info = enhance(&mut img, EnhanceMode::Equalize);
+        assert!(info.applied);
+        assert_eq!(info.method.as_deref(), Some("equalize"));
+        assert_eq!(info.gamma, None);
+        assert_eq!(img.get_pixel(0, 0).0, [0, 0, 0]);
+        assert_eq!(img.get_pixel(3, 1).0, [255, 255, 255]);
+    }
+
+    #[test]
+    fn equalize_keeps_the_hue_of_coloured_pixels() {
+        let mut img = RgbImage::from_fn(2, 1, |x, _| if x == 0 { image::Rgb([60, 20, 20]) } else { image::Rgb([20, 20, 60]) });
+        equalize_luminance(&mut img);
+        let (red, blue) = (img.get_pixel(0, 0), img.get_pixel(1, 0));
+        assert!(red[0] > red[1] && red[0] > red[2], "{:?}", red);
+        assert!(blue[2] > blue[0] && blue[2] > blue[1], "{:?}", blue);
+    }
+
+    #[test]
+    fn gray_world_neutralises_a_colour_cast() {
+        let mut img = RgbImage::from_pixel(4, 4, image::Rgb([200, 120, 40]));
+        let gains = gray_world(&mut img);
+        assert!(gains[0] < 1.0 && gains[2] > 1.0, "{:?}", gains);
+        assert_eq!(img.get_pixel(0, 0).0, [120, 120, 120]);
+
+        // Neutral frames are left alone
+        let mut neutral = grey(4, 4, 90);
+        assert_eq!(gray_world(&mut neutral), [1.0; 3]);
+        assert_eq!(neutral, grey(4, 4, 90));
+    }
+
+    #[test]
+    fn white_balance_accepts_auto_or_kelvin_in_range() {
+        assert_eq!(WhiteBalance::from_json(&serde_json::json!("AUTO")), Some(WhiteBalance::Auto));
+        assert_eq!(WhiteBalance::from_json(&serde_json::json!(3200)), Some(WhiteBalance::Kelvin(3200)));
+        assert_eq!(WhiteBalance::from_json(&serde_json::json!("6500K")), Some(WhiteBalance::Kelvin(6500)));
+        for invalid in [serde_json::json!(999), serde_json::json!(15001), serde_json::json!("warm"), serde_json::json!(true)] {
+            assert_eq!(WhiteBalance::from_json(&invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn face_regions_grow_by_a_margin_within_the_frame() {
+        let rect = CropRect { x: 10, y: 10, width: 50, height: 20 };
+        assert_eq!(expand_rect(&rect, 0.2, 100, 100), CropRect { x: 0, y: 6, width: 70, height: 28 });
+        assert_eq!(expand_rect(&rect, 0.2, 55, 32), CropRect { x: 0, y: 6, width: 55, height: 26 });
+    }
+
+    #[test]
+    fn blurring_stays_inside_the_rectangle() {
+        let mut img = RgbImage::from_fn(40, 40, |x, _| image::Rgb([if x % 2 == 0 { 255 } else { 0 }; 3]));
+        let original = img.clone();
+        blur_rect(&mut img, &CropRect { x: 10, y: 10, width: 20, height: 20 });
+
+        // The stripes inside are smoothed towards grey; the rest is untouched
+        let inside = img.get_pixel(20, 20)[0];
+        assert!((64..=192).contains(&inside), "{}", inside);
+        for (x, y) in [(9, 20), (30, 20), (20, 9), (20, 30), (0, 0)] {
+            assert_eq!(img.get_pixel(x, y), original.get_pixel(x, y), "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn crops_are_clamped_to_the_frame() {
+        let img = RgbImage::from_fn(8, 6, |x, y| image::Rgb([x as u8, y as u8, 0]));
+        let cropped = CropRect { x: 2, y: 1, width: 3, height: 2 }.apply(&img).unwrap();
+        assert_eq!(cropped.dimensions(), (3, 2));
+        assert_eq!(cropped.get_pixel(0, 0).0, [2, 1, 0]);
+        assert_eq!(cropped.get_pixel(2, 1).0, [4, 2, 0]);
+
+        let clamped = CropRect { x: 6, y: 4, width: 10, height: 10 }.apply(&img).unwrap();
+        assert_eq!(clamped.dimensions(), (2, 2));
+        assert!(CropRect { x: 8, y: 0, width: 1, height: 1 }.apply(&img).is_none());
+        assert!(CropRect { x: 0, y: 0, width: 0, height: 1 }.apply(&img).is_none());
+    }
+
+    #[test]
+    fn rectangles_are_outlined_inside_their_bounds() {
+        let mut img = grey(6, 6, 0);
+        draw_rect(&mut img, &CropRect { x: 1, y: 1, width: 4, height: 4 }, [0, 255, 0], 1);
+        let green: Vec<(u32, u32)> = img.enumerate_pixels().filter(|(_, _, p)| p[1] == 255).map(|(x, y, _)| (x, y)).collect();
+        // The 12 pixels of a 4x4 outline, none inside or outside it
+        assert_eq!(green.len(), 12);
+        assert!(green.iter().all(|&(x, y)| (1..5).contains(&x) && (1..5).contains(&y)));
+        assert_eq!(img.get_pixel(2, 2).0, [0, 0, 0]);
+        assert_eq!(img.get_pixel(0, 0).0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn diffs_count_changed_pixels_and_bound_them() {
+        let before = grey(10, 10, 100);
+        let mut after = before.clone();
+        after.put_pixel(2, 3, image::Rgb([200, 100, 100]));
+        after.put_pixel(6, 7, image::Rgb([100, 100, 40]));
+        // Below the threshold: not a change, but part of the mean
+        after.put_pixel(9, 9, image::Rgb([110, 100, 100]));
+
+        let report = diff(&before, &after, DEFAULT_DIFF_THRESHOLD);
+        assert_eq!(report.changed_pixels, 2);
+        assert_eq!(report.changed_fraction, 0.02);
+        assert_eq!(report.changed_region, Some(CropRect { x: 2, y: 3, width: 5, height: 5 }));
+        assert_eq!((report.width, report.height), (10, 10));
+        let expected_mean = (100 + 60 + 10) as f32 / (100 * 3 * 255) as f32;
+        assert!((report.mean_difference - expected_mean).abs() < 1e-6, "{}", report.mean_difference);
+
+        let same = diff(&before, &before, DEFAULT_DIFF_THRESHOLD);
+        assert_eq!((same.changed_pixels, same.changed_region, same.mean_difference), (0, None, 0.0));
+    }
+
+    #[test]
+    fn diff_images_mark_changes_red_over_dimmed_grey() {
+        let before = grey(2, 1, 200);
+        let mut after = before.clone();
+        after.put_pixel(1, 0, image::Rgb([0, 0, 0]));
+        let marked = diff_image(&before, &after, DEFAULT_DIFF_THRESHOLD);
+        let unchanged = marked.get_pixel(0, 0);
+        assert!(unchanged[0] == unchanged[1] && unchanged[1] == unchanged[2], "{:?}", unchanged);
+        assert!((99..=100).contains(&unchanged[0]), "{:?}", unchanged);
+        assert_eq!(marked.get_pixel(1, 0).0, [255, 0, 0]);
+    }
+
+    #[test]
+    fn the_smaller_image_is_scaled_to_the_larger() {
+        let mut small = grey(4, 3, 50);
+        let mut large = grey(8, 6, 50);
+        assert!(match_dimensions(&mut small, &mut large));
+        assert_eq!(small.dimensions(), (8, 6));
+        assert_eq!(small.get_pixel(7, 5).0, [50, 50, 50]);
+        assert!(!match_dimensions(&mut small, &mut large));
+    }
+
+    #[test]
+    fn clips_assemble_into_a_looping_gif_frame_by_frame() {
+        let frames = [RgbImage::from_pixel(8, 4, image::Rgb([255, 0, 0])), RgbImage::from_pixel(8, 4, image::Rgb([0, 0, 255]))];
+        let bytes = encode_gif(&frames, 250).unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (8, 4));
+        let mut decoded = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            // Delays are in hundredths of a second
+            assert_eq!(frame.delay, 25);
+            decoded.push(frame.buffer[..3].to_vec());
+        }
+        assert_eq!(decoded.len(), 2);
+        assert!(decoded[0][0] > 240 && decoded[0][2] < 16, "{:?}", decoded[0]);
+        assert!(decoded[1][2] > 240 && decoded[1][0] < 16, "{:?}", decoded[1]);
+        assert!(matches!(decoder.repeat(), gif::Repeat::Infinite));
+    }
+
+    #[test]
+    fn mjpeg_clips_are_back_to_back_jpegs() {
+        let frames = [grey(16, 8, 20), grey(16, 8, 220)];
+        let bytes = encode_mjpeg(&frames).unwrap();
+        let starts: Vec<usize> = bytes.windows(3).enumerate().filter(|(_, w)| *w == [0xFF, 0xD8, 0xFF]).map(|(i, _)| i).collect();
+        assert_eq!(starts.len(), 2);
+        assert_eq!(starts[0], 0);
+
+        let second = image::load_from_memory(&bytes[starts[1]..]).unwrap().into_rgb8();
+        assert_eq!(second.dimensions(), (16, 8));
+        assert!(second.get_pixel(0, 0)[0].abs_diff(220) <= 2);
+        assert_eq!(image_dimensions(&bytes), Some((16, 8)));
+    }
+
+    #[test]
+    fn frames_are_narrowed_keeping_their_aspect() {
+        let wide = grey(640, 480, 0);
+        assert_eq!(limit_width(&wide, 320).unwrap().dimensions(), (320, 240));
+        assert!(limit_width(&wide, 0).is_none());
+        assert!(limit_width(&wide, 640).is_none());
+    }
+
+    #[test]
+    fn enhance_modes_parse_case_insensitively() {
+        assert_eq!(EnhanceMode::parse("AUTO"), Some(EnhanceMode::Auto));
+        assert_eq!(EnhanceMode::parse("gamma"), Some(EnhanceMode::Gamma));
+        assert_eq!(EnhanceMode::parse("Equalize"), Some(EnhanceMode::Equalize));
+        assert_eq!(EnhanceMode::parse("hdr"), None);
+    }
+}
@@ -1,7 +1,79 @@
 pub mod webcam;
+pub mod audit;
+pub mod auth;
+pub mod backend;
+pub mod image_processing;
+pub mod capture_history;
+#[cfg(feature = "censys")]
+pub mod censys;
+pub mod certificate;
+pub mod classify;
+pub mod client_log;
+pub mod barcode;
+pub mod exif_writer;
+pub mod export;
+pub mod preview;
+pub mod face;
+pub mod health;
+pub mod honeypot;
+pub mod host_cooldown;
+pub mod http_auth;
+pub mod config;
+pub mod discovery;
+pub mod consent;
+pub mod credit_budget;
+pub mod liveness;
+pub mod local_discovery;
+pub mod logging;
 pub mod mcp_server;
+pub mod network_scan;
+pub mod onvif;
+pub mod onvif_snapshot;
+pub mod probe;
+pub mod rdns;
+pub mod protocol;
+pub mod rtsp;
+pub mod server_info;
+pub mod server_stats;
+pub mod settings;
 pub mod shodan;
+pub mod ssdp;
+pub mod target_policy;
+pub mod tool_error;
+pub mod tool_schemas;
+pub mod validate;
+pub mod webcam_cache;
 
-pub use webcam::{WebcamManager, WebcamError, CameraInfo, CaptureResult};
-pub use mcp_server::WebcamMcpServer;
-pub use shodan::{ShodanClient, ShodanError, RemoteWebcam, WebcamAccessType};
+pub use webcam::{WebcamManager, WebcamError, CameraInfo, CaptureResult, CaptureOptions, CapturedFrame, CaptureStore, CurrentCameraInfo, RetentionPolicy, PrivacyMasks, FaceBlur, FaceBlurInfo, CameraProfile, EffectiveProfile, SelfTestResult, BenchmarkReport, SyntheticCamera, FileCamera, RemoteImageOptions, OutputFormat, ProcessedImage};
+pub use backend::{CameraControl, CaptureBackend, MockBackend, StreamFormat};
+pub use audit::{AuditEntry, AuditLog};
+pub use auth::AuthToken;
+pub use config::ConfigFile;
+pub use consent::{ConsentGate, ConsentScope};
+pub use credit_budget::{BudgetExceeded, BudgetStatus, BudgetWindow, CreditBudget};
+pub use discovery::{Discovery, MergedSearch, ProviderCredits, ProviderFailure, WebcamDiscoveryProvider};
+pub use image_processing::{CropRect, EnhanceMode, EnhancementInfo, ImageStats};
+pub use barcode::{BarcodeError, DecodedCode};
+pub use exif_writer::ExifMetadata;
+pub use export::{ExportFormat, WebcamExport};
+pub use preview::{PreviewConfig, PreviewInfo, PreviewServer};
+pub use capture_history::{CaptureHistory, CaptureSummary, StoredCapture};
+#[cfg(feature = "censys")]
+pub use censys::CensysClient;
+pub use classify::{Classification, Vendor};
+pub use client_log::ClientLog;
+pub use honeypot::{HoneypotCheck, HoneypotGuard};
+pub use http_auth::WebcamCredentials;
+pub use probe::{ProbeOptions, ProbeResult};
+pub use liveness::{Liveness, LivenessReport};
+pub use local_discovery::{LocalCamera, LocalDiscovery, LocalProtocol};
+pub use network_scan::{NetworkScan, OpenPort, ScanCandidate, ScanRange};
+pub use onvif::{OnvifDevice, OnvifDiscovery};
+pub use onvif_snapshot::{MediaProfile, OnvifSnapshot};
+pub use ssdp::{SsdpDevice, SsdpDiscovery};
+pub use mcp_server::{WebcamMcpServer, WebcamMcpServerBuilder};
+pub use settings::ServerSettings;
+pub use target_policy::{TargetPolicy, TargetRule};
+pub use validate::{ValidateDepth, ValidateOptions, WebcamValidation};
+pub use webcam_cache::{CachedWebcam, WebcamCache};
+pub use shodan::{QueryCount, RequestLimiter, RetryPolicy, ScanStatus, SearchFilters, ShodanAccountInfo, ShodanClient, ShodanClientBuilder, ShodanError, ShodanHost, ShodanScan, ShodanScanStatus, RemoteImage, RemoteImageInfo, RemoteWebcam, SecretString, WebcamAccessType, WebcamCollector, WebcamEndpoint, WebcamHost, WebcamStream};
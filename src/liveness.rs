@@ -0,0 +1,148 @@
+//! Several frames of one remote webcam, a little apart, to tell a live
+//! camera from one serving a frozen or cached image. A single frame can't:
+//! a webcam whose feed died hours ago often still answers with its last
+//! picture.
+//!
+//! Consecutive frames are scored by their mean absolute per-channel
+//! difference, 0.0 for identical images to 1.0. A live camera's frames
+//! differ at least by sensor noise and recompression even when nothing in
+//! view moves, while a frozen one serves the same bytes every time. Scores
+//! above `LIVENESS_THRESHOLD` make a webcam `live`; all at or below it,
+//! `static`.
+
+use crate::http_auth::WebcamCredentials;
+use crate::image_processing;
+use crate::shodan::{sanitize_url, RemoteImage, RemoteWebcam, ShodanClient, ShodanError};
+use serde::Serialize;
+use std::time::Duration;
+use tracing::debug;
+
+/// Most frames one capture may fetch
+pub const MAX_FRAMES: u32 = 10;
+/// Time between frames unless asked otherwise
+pub const DEFAULT_FRAME_INTERVAL: Duration = Duration::from_millis(1000);
+/// Shortest time allowed between frames
+pub const MIN_FRAME_INTERVAL: Duration = Duration::from_millis(100);
+/// Longest time the frames of one capture may span, first to last, so the
+/// capture fits in the tool's timeout
+pub const MAX_FRAME_SPAN: Duration = Duration::from_secs(10);
+/// Difference score above which two frames show a live camera: a mean
+/// change of about half a level of 255 per channel
+pub const LIVENESS_THRESHOLD: f32 = 0.002;
+
+/// What the frames of a webcam say about its feed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Liveness {
+    /// Consecutive frames differed by more than the threshold
+    Live,
+    /// Every frame was the same as the one before, or nearly
+    Static,
+    /// A frame couldn't be decoded, and no pair that could differed
+    Unknown,
+}
+
+impl Liveness {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Live => "live",
+            Self::Static => "static",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Difference scores of consecutive frames and the verdict drawn from them
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LivenessReport {
+    pub verdict: Liveness,
+    /// Score of each frame against the one before, `None` where either
+    /// didn't decode
+    pub scores: Vec<Option<f32>>,
+    pub threshold: f32,
+}
+
+/// Check `frames` and `interval` make a capture that fits in
+/// `MAX_FRAME_SPAN`; the error is a message for the caller
+pub fn check_frames(frames: u32, interval: Duration) -> Result<(), String> {
+    if !(1..=MAX_FRAMES).contains(&frames) {
+        return Err(format!("Invalid frames {}: expected 1-{}", frames, MAX_FRAMES));
+    }
+    if interval < MIN_FRAME_INTERVAL {
+        return Err(format!("Invalid interval_ms {}: expected at least {}", interval.as_millis(), MIN_FRAME_INTERVAL.as_millis()));
+    }
+    if interval * (frames - 1) > MAX_FRAME_SPAN {
+        return Err(format!(
+            "{} frames {} ms apart would take longer than {} s; ask for fewer frames or a shorter interval",
+            frames, interval.as_millis(), MAX_FRAME_SPAN.as_secs()
+        ));
+    }
+    Ok(())
+}
+
+/// Difference score of two encoded frames, `None` if either doesn't
+/// decode. Identical bytes score 0.0 without decoding; frames of
+/// different sizes are compared at the larger one's.
+pub fn difference_score(a: &[u8], b: &[u8]) -> Option<f32> {
+    if a == b {
+        return Some(0.0);
+    }
+    let mut a = image::load_from_memory(a).ok()?.to_rgb8();
+    let mut b = image::load_from_memory(b).ok()?.to_rgb8();
+    image_processing::match_dimensions(&mut a, &mut b);
+    Some(image_processing::diff(&a, &b, image_processing::DEFAULT_DIFF_THRESHOLD).mean_difference)
+}
+
+/// Score each of `frames` against the one before and judge the webcam:
+/// `live` if any pair differs by more than `LIVENESS_THRESHOLD`, `static`
+/// if every pair scored at or below it, else `unknown`, as for fewer than
+/// two frames
+pub fn assess(frames: &[&[u8]]) -> LivenessReport {
+    let scores: Vec<Option<f32>> = frames.windows(2).map(|pair| difference_score(pair[0], pair[1])).collect();
+    let verdict = if scores.iter().flatten().any(|&score| score > LIVENESS_THRESHOLD) {
+        Liveness::Live
+    } else if !scores.is_empty() && scores.iter().all(Option::is_some) {
+        Liveness::Static
+    } else {
+        Liveness::Unknown
+    };
+    LivenessReport { verdict, scores, threshold: LIVENESS_THRESHOLD }
+}
+
+impl ShodanClient {
+    /// Fetch `count` frames from `webcam`, `interval` apart, logging in
+    /// with `credentials` if it asks. The host is claimed against the
+    /// cooldown once, for the first frame: the later ones of this request
+    /// are fetched however short the interval.
+    pub async fn fetch_webcam_frames(
+        &self,
+        webcam: &RemoteWebcam,
+        credentials: Option<&WebcamCredentials>,
+        count: u32,
+        interval: Duration,
+    ) -> Result<Vec<RemoteImage>, ShodanError> {
+        self.claim_host(&webcam.ip)?;
+        self.fetch_more_webcam_frames(webcam, credentials, Vec::new(), count, interval).await
+    }
+
+    /// Fetch frames from `webcam` after `frames`, already fetched from its
+    /// host, e.g. by a probe that claimed it, until there are `count`.
+    /// The host isn't claimed again.
+    pub async fn fetch_more_webcam_frames(
+        &self,
+        webcam: &RemoteWebcam,
+        credentials: Option<&WebcamCredentials>,
+        mut frames: Vec<RemoteImage>,
+        count: u32,
+        interval: Duration,
+    ) -> Result<Vec<RemoteImage>, ShodanError> {
+        while frames.len() < count as usize {
+            if !frames.is_empty() {
+                tokio::time::sleep(interval).await;
+            }
+            debug!("Fetching frame {} of {} from {}", frames.len() + 1, count, sanitize_url(&webcam.url));
+            frames.push(self.fetch_claimed_webcam_image(webcam, credentials).await?);
+        }
+        Ok(frames)
+    }
+}
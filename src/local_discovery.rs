@@ -0,0 +1,297 @@
+//! Cameras on the local network, found by the discovery protocols they
+//! answer: ONVIF WS-Discovery (`onvif`) and SSDP, UPnP's (`ssdp`). Each
+//! protocol multicasts its probes from every IPv4 interface and listens
+//! for replies for the same window; a camera that answers both is listed
+//! once, merged on its IP address.
+
+use crate::onvif::{self, OnvifDevice};
+use crate::shodan::RemoteWebcam;
+use crate::ssdp::{self, SsdpDevice};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Default time to listen for replies
+pub const DEFAULT_DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
+/// Longest time to listen for replies
+pub const MAX_DISCOVERY_TIMEOUT: Duration = Duration::from_secs(15);
+/// Probes stay on the local network
+const MULTICAST_TTL: u32 = 1;
+/// Largest datagram a reply can be
+const MAX_REPLY_BYTES: usize = 65_535;
+
+/// A local discovery protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LocalProtocol {
+    Onvif,
+    Ssdp,
+}
+
+impl LocalProtocol {
+    pub const ALL: [LocalProtocol; 2] = [LocalProtocol::Onvif, LocalProtocol::Ssdp];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LocalProtocol::Onvif => onvif::SOURCE,
+            LocalProtocol::Ssdp => ssdp::SOURCE,
+        }
+    }
+
+    /// How it is named in messages
+    pub fn label(self) -> &'static str {
+        match self {
+            LocalProtocol::Onvif => "ONVIF",
+            LocalProtocol::Ssdp => "SSDP",
+        }
+    }
+
+    /// Recorded in the webcam cache as the query that found a camera
+    pub fn query(self) -> &'static str {
+        match self {
+            LocalProtocol::Onvif => onvif::DISCOVERY_QUERY,
+            LocalProtocol::Ssdp => ssdp::DISCOVERY_QUERY,
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|protocol| protocol.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// A camera found on the local network: its webcam entry, as kept in the
+/// webcam cache, and what each protocol that found it said
+#[derive(Debug, Clone, Serialize)]
+pub struct LocalCamera {
+    #[serde(flatten)]
+    pub webcam: RemoteWebcam,
+    /// The name it was given: its ONVIF name, or its UPnP friendly name
+    pub name: Option<String>,
+    pub onvif: Option<OnvifDevice>,
+    pub ssdp: Option<SsdpDevice>,
+}
+
+/// A protocol that failed while others answered
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProtocolFailure {
+    pub protocol: LocalProtocol,
+    pub error: String,
+}
+
+/// What a discovery found, and where it looked
+#[derive(Debug, Clone, Default)]
+pub struct LocalDiscovery {
+    pub cameras: Vec<LocalCamera>,
+    /// Protocols whose results are included, in order
+    pub protocols: Vec<LocalProtocol>,
+    pub failures: Vec<ProtocolFailure>,
+    /// Local addresses the probes were sent from
+    pub interfaces: Vec<Ipv4Addr>,
+}
+
+/// Run each of `protocols` for `timeout`, at once, from every IPv4
+/// interface but loopback. A protocol that fails is logged and left out;
+/// if every one fails, the first error is returned.
+pub async fn discover(protocols: &[LocalProtocol], timeout: Duration) -> io::Result<LocalDiscovery> {
+    let interfaces = local_interfaces();
+    let run_onvif = protocols.contains(&LocalProtocol::Onvif);
+    let run_ssdp = protocols.contains(&LocalProtocol::Ssdp);
+    let (found_onvif, found_ssdp) = tokio::join!(
+        async { if run_onvif { Some(onvif::discover_via(&interfaces, onvif::WS_DISCOVERY_ADDRESS, timeout).await) } else { None } },
+        async { if run_ssdp { Some(ssdp::discover_via(&interfaces, ssdp::SSDP_ADDRESS, timeout).await) } else { None } },
+    );
+
+    let mut discovery = LocalDiscovery::default();
+    let mut first_error = None;
+    let mut failed = |protocol: LocalProtocol, e: io::Error, discovery: &mut LocalDiscovery| {
+        warn!("Local discovery with {} failed: {}", protocol.name(), e);
+        discovery.failures.push(ProtocolFailure { protocol, error: e.to_string() });
+        first_error.get_or_insert(e);
+    };
+    let mut onvif_devices = Vec::new();
+    let mut ssdp_devices = Vec::new();
+    match found_onvif {
+        Some(Ok(found)) => {
+            discovery.protocols.push(LocalProtocol::Onvif);
+            add_interfaces(&mut discovery.interfaces, &found.interfaces);
+            onvif_devices = found.devices;
+        }
+        Some(Err(e)) => failed(LocalProtocol::Onvif, e, &mut discovery),
+        None => {}
+    }
+    match found_ssdp {
+        Some(Ok(found)) => {
+            discovery.protocols.push(LocalProtocol::Ssdp);
+            add_interfaces(&mut discovery.interfaces, &found.interfaces);
+            ssdp_devices = found.devices;
+        }
+        Some(Err(e)) => failed(LocalProtocol::Ssdp, e, &mut discovery),
+        None => {}
+    }
+    if discovery.protocols.is_empty() {
+        return Err(first_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no discovery protocol selected")));
+    }
+    discovery.cameras = merge_cameras(onvif_devices, ssdp_devices);
+    Ok(discovery)
+}
+
+fn add_interfaces(interfaces: &mut Vec<Ipv4Addr>, more: &[Ipv4Addr]) {
+    for interface in more {
+        if !interfaces.contains(interface) {
+            interfaces.push(*interface);
+        }
+    }
+}
+
+/// One camera per IP address. A device found by both protocols keeps its
+/// ONVIF device service as its URL, with the details it lacks, such as
+/// its make, filled in from SSDP and tagged with both.
+pub fn merge_cameras(onvif_devices: Vec<OnvifDevice>, ssdp_devices: Vec<SsdpDevice>) -> Vec<LocalCamera> {
+    let mut cameras: Vec<LocalCamera> = Vec::new();
+    let mut by_ip: HashMap<IpAddr, usize> = HashMap::new();
+    for device in onvif_devices {
+        match by_ip.get(&device.responder) {
+            // Two ONVIF endpoints on one address, e.g. an NVR and a camera
+            // behind it; the first found stands for both
+            Some(&index) => cameras[index].webcam.merge(device.to_webcam()),
+            None => {
+                by_ip.insert(device.responder, cameras.len());
+                cameras.push(LocalCamera { webcam: device.to_webcam(), name: device.name.clone(), onvif: Some(device), ssdp: None });
+            }
+        }
+    }
+    for device in ssdp_devices {
+        match by_ip.get(&device.responder) {
+            Some(&index) => {
+                let camera = &mut cameras[index];
+                camera.webcam.merge(device.to_webcam());
+                camera.name = camera.name.take().or_else(|| device.friendly_name.clone());
+                camera.ssdp.get_or_insert(device);
+            }
+            None => {
+                by_ip.insert(device.responder, cameras.len());
+                cameras.push(LocalCamera { webcam: device.to_webcam(), name: device.friendly_name.clone(), onvif: None, ssdp: Some(device) });
+            }
+        }
+    }
+    cameras
+}
+
+/// IPv4 addresses of the interfaces to probe from: every one but
+/// loopback, or the unspecified address, leaving the choice to the
+/// system, if they can't be listed
+pub fn local_interfaces() -> Vec<Ipv4Addr> {
+    let interfaces = match if_addrs::get_if_addrs() {
+        Ok(interfaces) => interfaces,
+        Err(e) => {
+            warn!("Could not list network interfaces, probing from the default one: {}", e);
+            return vec![Ipv4Addr::UNSPECIFIED];
+        }
+    };
+    let mut addresses: Vec<Ipv4Addr> = Vec::new();
+    for interface in interfaces.iter().filter(|interface| !interface.is_loopback()) {
+        if let IpAddr::V4(address) = interface.ip() {
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+    }
+    if addresses.is_empty() {
+        addresses.push(Ipv4Addr::UNSPECIFIED);
+    }
+    addresses
+}
+
+/// Datagrams answering `messages`, sent to `target` from each of
+/// `interfaces`, received until `deadline`, with the interfaces they were
+/// sent from. Fails only if they couldn't be sent from any interface.
+pub(crate) async fn multicast(
+    interfaces: &[Ipv4Addr],
+    target: SocketAddr,
+    messages: &[String],
+    deadline: Instant,
+) -> io::Result<(Vec<Ipv4Addr>, Vec<(SocketAddr, String)>)> {
+    let mut sent_from = Vec::new();
+    let mut listening = JoinSet::new();
+    let mut last_error = None;
+    for &interface in interfaces {
+        // Bound to an interface's address, the socket sends multicast out
+        // of that interface, and its replies come back to it
+        let sent = async {
+            let socket = UdpSocket::bind((interface, 0)).await?;
+            if target.ip().is_multicast() {
+                socket.set_multicast_ttl_v4(MULTICAST_TTL)?;
+            }
+            for message in messages {
+                socket.send_to(message.as_bytes(), target).await?;
+            }
+            Ok::<_, io::Error>(socket)
+        };
+        match sent.await {
+            Ok(socket) => {
+                sent_from.push(interface);
+                listening.spawn(collect_replies(socket, deadline));
+            }
+            Err(e) => {
+                debug!("Could not probe {} from {}: {}", target, interface, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    if sent_from.is_empty() {
+        return Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no network interface to probe from")));
+    }
+
+    let mut replies = Vec::new();
+    while let Some(received) = listening.join_next().await {
+        replies.extend(received.unwrap_or_default());
+    }
+    Ok((sent_from, replies))
+}
+
+/// Replies to the probes sent from `socket`, until `deadline`
+async fn collect_replies(socket: UdpSocket, deadline: Instant) -> Vec<(SocketAddr, String)> {
+    let mut replies = Vec::new();
+    let mut buffer = vec![0u8; MAX_REPLY_BYTES];
+    loop {
+        match tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await {
+            Ok(Ok((length, from))) => replies.push((from, String::from_utf8_lossy(&buffer[..length]).into_owned())),
+            // An ICMP error from an earlier send; other replies may follow
+            Ok(Err(e)) => debug!("Receiving discovery replies: {}", e),
+            Err(_) => return replies,
+        }
+    }
+}
+
+/// The contents of every `local_name` element, with any prefix. Elements
+/// of that name don't nest in the documents read here, so the first
+/// closing tag ends each.
+pub(crate) fn elements<'a>(xml: &'a str, local_name: &str) -> Vec<&'a str> {
+    let name = regex::escape(local_name);
+    let pattern = Regex::new(&format!(r"(?s)<(?:[\w.-]+:)?{name}(?:\s[^>]*)?>(.*?)</(?:[\w.-]+:)?{name}\s*>")).unwrap();
+    pattern.captures_iter(xml).filter_map(|captures| captures.get(1)).map(|m| m.as_str()).collect()
+}
+
+/// The text of the first `local_name` element, unescaped, if it has any
+pub(crate) fn element_text(xml: &str, local_name: &str) -> Option<String> {
+    let text = elements(xml, local_name).into_iter().next()?.trim();
+    let text = text.strip_prefix("<![CDATA[").and_then(|text| text.strip_suffix("]]>")).map(str::to_string).unwrap_or_else(|| {
+        text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+    });
+    Some(text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+/// `ip` as the host of a URL, bracketed if IPv6
+pub(crate) fn host_for_url(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+    }
+}
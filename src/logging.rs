@@ -0,0 +1,138 @@
+//! Log output of the server binary. Logs go to stderr or a file, never to
+//! stdout, which carries the MCP stdio protocol: a single stray line there
+//! breaks the client's JSON-RPC parser.
+
+use crate::client_log::ClientLogLayer;
+use crate::settings::ServerSettings;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Size at which the log file is rotated unless configured otherwise
+pub const DEFAULT_LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+/// Rotated log files kept next to the current one, as `<file>.1` (newest)
+/// to `<file>.3`
+pub const LOG_FILE_BACKUPS: usize = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per event, for log collectors
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!("expected text or json, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        })
+    }
+}
+
+/// Log file that is rotated once writing to it would exceed `max_bytes`
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    /// Open `path` for appending, creating it and its directory if needed
+    pub fn open(path: impl Into<PathBuf>, max_bytes: u64) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self { path, max_bytes, file, written })
+    }
+
+    /// Path of the `n`th most recent rotated file
+    pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        for n in (1..LOG_FILE_BACKUPS).rev() {
+            let from = Self::backup_path(&self.path, n);
+            if from.exists() {
+                std::fs::rename(&from, Self::backup_path(&self.path, n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, Self::backup_path(&self.path, 1))?;
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A single event larger than the limit still goes into one file
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Install the global `tracing` subscriber: level from `log_level`, output
+/// to `log_file` (rotated by size) or else stderr, in `log_format`.
+/// `client_log` gets every event, since the client picks its own level.
+pub fn init(settings: &ServerSettings, client_log: ClientLogLayer) -> Result<(), String> {
+    let filter = EnvFilter::try_new(&settings.log_level)
+        .map_err(|e| format!("invalid log level '{}': {}", settings.log_level, e))?;
+
+    let writer = match &settings.log_file {
+        Some(path) => {
+            let file = RotatingFile::open(path, settings.log_max_bytes)
+                .map_err(|e| format!("failed to open log file {}: {}", path.display(), e))?;
+            BoxMakeWriter::new(Mutex::new(file))
+        }
+        None => BoxMakeWriter::new(io::stderr),
+    };
+
+    let output = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(settings.log_file.is_none());
+    let output = match settings.log_format {
+        LogFormat::Text => output.boxed(),
+        LogFormat::Json => output.json().boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(output.with_filter(filter))
+        .with(client_log)
+        .try_init()
+        .map_err(|e| e.to_string())
+}
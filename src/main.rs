@@ -1,23 +1,465 @@
-mod webcam;
-mod mcp_server;
-mod shodan;
-
-use crate::mcp_server::WebcamMcpServer;
+use mcp_webcam::credit_budget::SHODAN_USAGE_FILE;
+use mcp_webcam::image_processing::WhiteBalance;
+use mcp_webcam::logging::{self, LogFormat};
+use mcp_webcam::shodan::{sanitize_url, QueryPlan};
+use mcp_webcam::{
+    CameraInfo, CameraProfile, CaptureOptions, ClientLog, CreditBudget, CropRect, EnhanceMode, FaceBlur, RemoteWebcam,
+    SearchFilters, ServerSettings, ShodanClient, ShodanError, TargetRule, WebcamAccessType, WebcamCredentials,
+    WebcamMcpServer,
+};
 use anyhow::Result;
+use clap::{error::ErrorKind, Args, CommandFactory, Parser, Subcommand};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::{error, info};
-use tracing_subscriber;
+
+/// MCP server for webcam image capture over stdio
+///
+/// Flags take precedence over the environment variables shown next to them.
+/// They come before the subcommand, e.g. `mcp-webcam --max-width 640 capture`.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Camera index captured from when a tool call names none; defaults to
+    /// the configuration file's default_camera, then 0
+    #[arg(long, visible_alias = "camera", value_name = "INDEX")]
+    default_camera: Option<u32>,
+
+    /// Default capture width, for cameras without a configured resolution
+    #[arg(long, value_name = "PIXELS", requires = "height")]
+    width: Option<u32>,
+
+    /// Default capture height, for cameras without a configured resolution
+    #[arg(long, value_name = "PIXELS", requires = "width")]
+    height: Option<u32>,
+
+    /// Downscale every captured frame wider than this
+    #[arg(long, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(1..))]
+    max_width: Option<u32>,
+
+    /// Default clockwise rotation in degrees
+    #[arg(long, value_name = "DEGREES", value_parser = ["0", "90", "180", "270"])]
+    rotation: Option<String>,
+
+    /// Default JPEG quality, 1-100
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: Option<u8>,
+
+    /// Default number of frames to discard before capturing, to let exposure settle
+    #[arg(long, value_name = "FRAMES")]
+    warmup_frames: Option<u32>,
+
+    /// Disable the Shodan tools even if SHODAN_API_KEY is set
+    #[arg(long)]
+    no_shodan: bool,
+
+    /// Turn off all remote webcam access, Shodan search and remote capture,
+    /// even if a Shodan key is set; MCP_WEBCAM_DISABLE_REMOTE=1 does the same
+    #[arg(long)]
+    no_remote: bool,
+
+    /// Shodan API key; defaults to the SHODAN_API_KEY environment variable
+    #[arg(long, value_name = "KEY", conflicts_with_all = ["no_shodan", "no_remote"])]
+    shodan_key: Option<String>,
+
+    /// File holding the Shodan API key, e.g. a systemd credential or Docker
+    /// secret, re-read by the reload_credentials tool; defaults to
+    /// SHODAN_API_KEY_FILE
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["no_shodan", "no_remote", "shodan_key"])]
+    shodan_key_file: Option<PathBuf>,
+
+    /// Seconds allowed to connect to Shodan or a remote webcam
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    shodan_connect_timeout: Option<u64>,
+
+    /// Seconds allowed for a whole Shodan API request
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    shodan_timeout: Option<u64>,
+
+    /// User-Agent of Shodan and remote webcam requests; defaults to mcp-webcam/<version>
+    #[arg(long, value_name = "STRING")]
+    shodan_user_agent: Option<String>,
+
+    /// Proxy for Shodan and remote webcam requests, e.g. http://proxy:3128;
+    /// defaults to HTTPS_PROXY
+    #[arg(long, value_name = "URL")]
+    shodan_proxy: Option<String>,
+
+    /// Accept invalid TLS certificates, such as self-signed ones, from
+    /// remote webcams; Shodan's certificate is always checked
+    #[arg(long)]
+    remote_accept_invalid_certs: bool,
+
+    /// Let remote webcam tools fetch from loopback, private, link-local and
+    /// other non-public addresses, e.g. cameras on the local network
+    #[arg(long)]
+    allow_private_targets: bool,
+
+    /// Only fetch remote webcams in this network or with this host name,
+    /// e.g. 203.0.113.0/24 or *.cameras.example.com; repeat for more
+    #[arg(long = "allow-target", value_name = "RULE")]
+    allow_targets: Vec<TargetRule>,
+
+    /// Never fetch remote webcams in this network or with this host name;
+    /// repeat for more
+    #[arg(long = "block-target", value_name = "RULE")]
+    block_targets: Vec<TargetRule>,
+
+    /// Offer scan_network_for_cameras, which connects to every address of
+    /// a network of up to /22 looking for cameras. Leave off wherever
+    /// scanning is prohibited
+    #[arg(long, conflicts_with = "no_remote")]
+    allow_network_scan: bool,
+
+    /// ffmpeg used for snapshots of RTSP webcams (requires the rtsp feature);
+    /// defaults to ffmpeg on the PATH
+    #[arg(long, value_name = "PATH")]
+    ffmpeg: Option<PathBuf>,
+
+    /// Seconds allowed for an RTSP snapshot, connecting included
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    rtsp_timeout: Option<u64>,
+
+    /// Most bytes read from a remote webcam for one image, after which the
+    /// connection is closed; defaults to 10 MiB
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u64).range(1..))]
+    remote_max_download_bytes: Option<u64>,
+
+    /// Seconds a remote webcam fetch may take in all, however steadily the
+    /// webcam keeps sending; defaults to 30
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    remote_fetch_deadline: Option<u64>,
+
+    /// Milliseconds reverse DNS lookups of webcams Shodan has no hostname
+    /// for may add to a search; 0 turns them off; defaults to 2000
+    #[arg(long, value_name = "MS")]
+    remote_rdns_budget_ms: Option<u64>,
+
+    /// Token clients of network listeners such as the live preview must
+    /// present; defaults to MCP_WEBCAM_AUTH_TOKEN, then the configuration file
+    #[arg(long, value_name = "TOKEN")]
+    auth_token: Option<String>,
+
+    /// Directory captures, clips and other files are saved to; defaults to <temp dir>/mcp-webcam
+    #[arg(long, value_name = "DIR", env = "MCP_WEBCAM_DATA_DIR")]
+    data_dir: Option<PathBuf>,
+
+    /// Log filter, a level such as `debug` or a directive like `mcp_webcam=trace`
+    #[arg(long, value_name = "FILTER", env = "RUST_LOG", default_value = mcp_webcam::settings::DEFAULT_LOG_LEVEL)]
+    log_level: String,
+
+    /// Write logs to this file, rotated by size, instead of stderr. Logs never go to stdout
+    #[arg(long, value_name = "PATH", env = "MCP_WEBCAM_LOG_FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Log line format
+    #[arg(long, value_name = "FORMAT", default_value_t = LogFormat::Text, value_parser = LogFormat::parse)]
+    log_format: LogFormat,
+
+    /// Size in bytes at which the log file is rotated, keeping 3 old files
+    #[arg(long, value_name = "BYTES", default_value_t = logging::DEFAULT_LOG_MAX_BYTES)]
+    log_max_bytes: u64,
+
+    /// Seconds between snapshots of a camera while a client is subscribed to its snapshot resource
+    #[arg(long, value_name = "SECONDS", default_value_t = mcp_webcam::mcp_server::DEFAULT_SNAPSHOT_INTERVAL.as_secs())]
+    snapshot_interval: u64,
+
+    /// Seconds discovered webcams stay listed by list_remote_webcams after a search last found them
+    #[arg(long, value_name = "SECONDS", default_value_t = mcp_webcam::webcam_cache::DEFAULT_WEBCAM_CACHE_TTL.as_secs(), value_parser = clap::value_parser!(u64).range(1..))]
+    webcam_cache_ttl: u64,
+
+    /// Seconds between fetches from the same remote webcam host by capture,
+    /// probe and validation tools; 0 turns the cooldown off
+    #[arg(long, value_name = "SECONDS", default_value_t = mcp_webcam::host_cooldown::DEFAULT_HOST_COOLDOWN.as_secs())]
+    host_cooldown: u64,
+
+    /// Shodan honeyscore, 0 to 1, above which a remote webcam host is
+    /// taken for a honeypot and warned about
+    #[arg(long, value_name = "SCORE", default_value_t = mcp_webcam::honeypot::DEFAULT_HONEYPOT_THRESHOLD, value_parser = parse_honeypot_threshold)]
+    honeypot_threshold: f64,
+
+    /// Refuse capture_remote_image for hosts scoring above the honeypot
+    /// threshold, looking up the score of hosts not yet checked
+    #[arg(long)]
+    honeypot_strict: bool,
+
+    /// Most Shodan search and host requests per hour, counted across
+    /// restarts; unlimited when not given
+    #[arg(long, value_name = "N", env = "MCP_WEBCAM_SHODAN_MAX_REQUESTS_PER_HOUR", value_parser = clap::value_parser!(u32).range(1..))]
+    shodan_max_requests_per_hour: Option<u32>,
+
+    /// Most Shodan search and host requests per day (UTC); unlimited when
+    /// not given
+    #[arg(long, value_name = "N", env = "MCP_WEBCAM_SHODAN_MAX_REQUESTS_PER_DAY", value_parser = clap::value_parser!(u32).range(1..))]
+    shodan_max_requests_per_day: Option<u32>,
+
+    /// Make local camera tools fail with CONSENT_REQUIRED until the user
+    /// passes a one-time code, printed to stderr, to grant_consent
+    #[arg(long)]
+    require_consent: bool,
+
+    /// Likewise for remote webcam captures, independently of local cameras
+    #[arg(long)]
+    require_remote_consent: bool,
+
+    /// Seconds consent lasts once given
+    #[arg(long, value_name = "SECONDS", default_value_t = mcp_webcam::consent::DEFAULT_CONSENT_DURATION.as_secs())]
+    consent_duration: u64,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run the MCP server over stdio, the default
+    Serve,
+    /// Capture one image from a local camera and exit
+    Capture(CaptureArgs),
+    /// List the local cameras and exit
+    ListCameras {
+        /// Print the cameras as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Search Shodan or fetch a remote webcam image and exit, to check a
+    /// key, a query or a webcam from a shell
+    #[command(subcommand)]
+    Shodan(ShodanCommand),
+}
+
+#[derive(Debug, Subcommand)]
+enum ShodanCommand {
+    /// Run one Shodan query and print the webcams it finds
+    Search {
+        /// Shodan query, e.g. 'product:"Hikvision"'
+        #[arg(long)]
+        query: String,
+
+        /// Most webcams to print
+        #[arg(long, default_value_t = mcp_webcam::shodan::DEFAULT_SEARCH_LIMIT, value_parser = clap::value_parser!(u32).range(1..))]
+        limit: u32,
+
+        /// Print the webcams as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Fetch one image from a remote webcam URL; needs no Shodan key
+    Fetch {
+        /// Snapshot URL of the webcam
+        #[arg(long)]
+        url: String,
+
+        /// File the image is written to; `-` or none writes it to stdout
+        #[arg(long, short, value_name = "PATH")]
+        out: Option<PathBuf>,
+
+        /// User name, if the webcam asks for HTTP authentication
+        #[arg(long, requires = "password")]
+        username: Option<String>,
+
+        /// Password, with --username
+        #[arg(long, requires = "username")]
+        password: Option<String>,
+    },
+}
+
+/// Options of a one-shot capture, those of the capture_image tool
+#[derive(Debug, Args)]
+struct CaptureArgs {
+    /// Camera to capture from; defaults to the default camera
+    #[arg(long, value_name = "INDEX")]
+    camera: Option<u32>,
+
+    /// File the JPEG is written to; `-` or none writes it to stdout
+    #[arg(long, short, value_name = "PATH")]
+    out: Option<PathBuf>,
+
+    /// Capture width
+    #[arg(long, value_name = "PIXELS", requires = "height", value_parser = clap::value_parser!(u32).range(1..))]
+    width: Option<u32>,
+
+    /// Capture height
+    #[arg(long, value_name = "PIXELS", requires = "width", value_parser = clap::value_parser!(u32).range(1..))]
+    height: Option<u32>,
+
+    /// Downscale the image if it is wider than this
+    #[arg(long, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(1..))]
+    max_width: Option<u32>,
+
+    /// Clockwise rotation in degrees
+    #[arg(long, value_name = "DEGREES", value_parser = ["0", "90", "180", "270"])]
+    rotation: Option<String>,
+
+    /// JPEG quality, 1-100
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: Option<u8>,
+
+    /// Frames to discard before capturing, to let exposure settle
+    #[arg(long, value_name = "FRAMES", value_parser = clap::value_parser!(u32).range(0..=100))]
+    warmup_frames: Option<u32>,
+
+    /// Low-light enhancement: auto, gamma or equalize
+    #[arg(long, value_name = "MODE", value_parser = parse_enhance)]
+    enhance: Option<EnhanceMode>,
+
+    /// White balance: auto, or a colour temperature in Kelvin, 1000-15000
+    #[arg(long, value_name = "auto|KELVIN", value_parser = parse_white_balance)]
+    white_balance: Option<WhiteBalance>,
+
+    /// Region of the frame to keep, as X,Y,WIDTH,HEIGHT in pixels
+    #[arg(long, value_name = "X,Y,WIDTH,HEIGHT", value_parser = parse_crop)]
+    crop: Option<CropRect>,
+
+    /// Blur detected faces; the capture fails if detection does
+    #[arg(long)]
+    blur_faces: bool,
+
+    /// With --blur-faces, keep the unblurred image if face detection fails
+    #[arg(long, requires = "blur_faces")]
+    no_strict: bool,
+
+    /// Embed EXIF metadata: capture time, camera and software
+    #[arg(long)]
+    embed_exif: bool,
+
+    /// EXIF user comment; implies --embed-exif
+    #[arg(long, value_name = "TEXT")]
+    comment: Option<String>,
+}
+
+impl CaptureArgs {
+    fn options(&self) -> CaptureOptions {
+        CaptureOptions {
+            enhance: self.enhance,
+            crop: self.crop,
+            white_balance: self.white_balance,
+            blur_faces: self.blur_faces.then_some(FaceBlur { strict: !self.no_strict }),
+            resolution: self.width.zip(self.height),
+            rotation: self.rotation.as_ref().and_then(|r| r.parse().ok()),
+            quality: self.quality,
+            warmup_frames: self.warmup_frames,
+            max_width: self.max_width,
+            embed_exif: self.embed_exif || self.comment.is_some(),
+            exif_comment: self.comment.clone(),
+        }
+    }
+}
+
+fn parse_enhance(value: &str) -> Result<EnhanceMode, String> {
+    EnhanceMode::parse(value).ok_or_else(|| "expected auto, gamma or equalize".to_string())
+}
+
+fn parse_white_balance(value: &str) -> Result<WhiteBalance, String> {
+    WhiteBalance::from_json(&serde_json::Value::String(value.to_string()))
+        .ok_or_else(|| "expected auto or a Kelvin value between 1000 and 15000".to_string())
+}
+
+fn parse_honeypot_threshold(value: &str) -> Result<f64, String> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|score| (0.0..=1.0).contains(score))
+        .ok_or_else(|| "expected a score from 0 to 1".to_string())
+}
+
+fn parse_crop(value: &str) -> Result<CropRect, String> {
+    let parts: Vec<u32> = value
+        .split(',')
+        .map(|part| part.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| "expected four whole numbers, X,Y,WIDTH,HEIGHT".to_string())?;
+    match parts[..] {
+        [x, y, width, height] if width > 0 && height > 0 => Ok(CropRect { x, y, width, height }),
+        [_, _, _, _] => Err("width and height must be positive".to_string()),
+        _ => Err("expected four whole numbers, X,Y,WIDTH,HEIGHT".to_string()),
+    }
+}
+
+impl Cli {
+    fn into_settings(self) -> ServerSettings {
+        ServerSettings {
+            default_camera: self.default_camera,
+            capture_defaults: CameraProfile {
+                width: self.width,
+                height: self.height,
+                rotation: self.rotation.and_then(|r| r.parse().ok()),
+                quality: self.quality,
+                warmup_frames: self.warmup_frames,
+                backend: None,
+            },
+            max_width: self.max_width,
+            shodan_enabled: !self.no_shodan,
+            remote_disabled: self.no_remote,
+            shodan_api_key: self.shodan_key,
+            shodan_key_file: self.shodan_key_file,
+            shodan_connect_timeout: self.shodan_connect_timeout.map(Duration::from_secs),
+            shodan_request_timeout: self.shodan_timeout.map(Duration::from_secs),
+            shodan_user_agent: self.shodan_user_agent,
+            shodan_proxy: self.shodan_proxy,
+            remote_accept_invalid_certs: self.remote_accept_invalid_certs,
+            allow_private_targets: self.allow_private_targets,
+            allowed_targets: self.allow_targets,
+            blocked_targets: self.block_targets,
+            allow_network_scan: self.allow_network_scan,
+            ffmpeg_path: self.ffmpeg,
+            rtsp_timeout: self.rtsp_timeout.map(Duration::from_secs),
+            remote_max_download_bytes: self.remote_max_download_bytes.map(|bytes| usize::try_from(bytes).unwrap_or(usize::MAX)),
+            remote_fetch_deadline: self.remote_fetch_deadline.map(Duration::from_secs),
+            remote_rdns_budget: self.remote_rdns_budget_ms.map(Duration::from_millis),
+            auth_token: self.auth_token,
+            data_dir: self.data_dir,
+            log_level: self.log_level,
+            log_file: self.log_file,
+            log_format: self.log_format,
+            log_max_bytes: self.log_max_bytes,
+            snapshot_interval: Duration::from_secs(self.snapshot_interval),
+            webcam_cache_ttl: Duration::from_secs(self.webcam_cache_ttl),
+            host_cooldown: Duration::from_secs(self.host_cooldown),
+            honeypot_threshold: self.honeypot_threshold,
+            honeypot_strict: self.honeypot_strict,
+            shodan_max_requests_per_hour: self.shodan_max_requests_per_hour,
+            shodan_max_requests_per_day: self.shodan_max_requests_per_day,
+            require_consent: self.require_consent,
+            require_remote_consent: self.require_remote_consent,
+            consent_duration: Duration::from_secs(self.consent_duration),
+        }
+    }
+}
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize logging
-    tracing_subscriber::fmt()
-        .init();
+async fn main() -> Result<ExitCode> {
+    let mut cli = Cli::parse();
+    let command = cli.command.take().unwrap_or(Command::Serve);
+    let settings = cli.into_settings();
+    if let Err(e) = settings.validate() {
+        Cli::command().error(ErrorKind::ArgumentConflict, e).exit();
+    }
+
+    // Initialize logging; stdout is reserved for the MCP protocol, and the
+    // client can ask for events with logging/setLevel
+    if let Err(e) = logging::init(&settings, ClientLog::global().layer()) {
+        Cli::command().error(ErrorKind::InvalidValue, e).exit();
+    }
+
+    match command {
+        Command::Serve => serve(settings).await.map(|_| ExitCode::SUCCESS),
+        Command::Capture(args) => Ok(capture(&settings, &args)),
+        Command::ListCameras { json } => Ok(list_cameras(&settings, json)),
+        Command::Shodan(command) => Ok(shodan(&settings, command).await),
+    }
+}
 
+async fn serve(settings: ServerSettings) -> Result<()> {
     info!("MCP Webcam Server starting...");
 
     // Create and run the MCP server
-    let server = WebcamMcpServer::new();
-    
+    let server = WebcamMcpServer::new_with_settings(settings);
+
     match server.run().await {
         Ok(_) => {
             info!("MCP Webcam Server stopped gracefully");
@@ -29,3 +471,238 @@ async fn main() -> Result<()> {
         }
     }
 }
+
+/// Exit status of a subcommand that failed for any other reason
+const EXIT_FAILURE: u8 = 1;
+/// Exit status when there is no Shodan key or Shodan refused it
+const EXIT_UNAUTHORIZED: u8 = 3;
+/// Exit status when Shodan's rate limit or the request budget refused a request
+const EXIT_RATE_LIMITED: u8 = 4;
+/// Exit status of a Shodan search that found no webcams
+const EXIT_NO_RESULTS: u8 = 5;
+
+/// Print `message` as the error of a subcommand and fail with `code`
+fn fail(message: impl std::fmt::Display, code: u8) -> ExitCode {
+    eprintln!("error: {}", message);
+    ExitCode::from(code)
+}
+
+fn capture(settings: &ServerSettings, args: &CaptureArgs) -> ExitCode {
+    let mut manager = settings.webcam_manager();
+    let result = match manager.capture_image_with_options(args.camera, &args.options()) {
+        Ok(result) => result,
+        Err(e) => return fail(e, EXIT_FAILURE),
+    };
+
+    if let Err(e) = write_output(args.out.as_deref(), &result.image_bytes) {
+        return fail(e, EXIT_FAILURE);
+    }
+    info!("Captured {}x{} from camera {} ({} bytes)", result.width, result.height, result.camera_index, result.image_bytes.len());
+    ExitCode::SUCCESS
+}
+
+fn list_cameras(settings: &ServerSettings, json: bool) -> ExitCode {
+    let cameras = match settings.webcam_manager().list_cameras() {
+        Ok(cameras) => cameras,
+        Err(e) => return fail(e, EXIT_FAILURE),
+    };
+    if json {
+        match serde_json::to_string_pretty(&cameras) {
+            Ok(text) => println!("{}", text),
+            Err(e) => return fail(e, EXIT_FAILURE),
+        }
+    } else {
+        print!("{}", camera_table(&cameras));
+    }
+    ExitCode::SUCCESS
+}
+
+/// Cameras as a table, one per line
+fn camera_table(cameras: &[CameraInfo]) -> String {
+    let rows = cameras
+        .iter()
+        .map(|camera| {
+            let available = match (camera.available, camera.fake) {
+                (true, true) => "yes (fake)",
+                (true, false) => "yes",
+                (false, _) => "no",
+            };
+            vec![camera.index.to_string(), camera.name.clone(), camera.backend.clone(), available.to_string(), camera.description.clone()]
+        })
+        .collect();
+    table(&["INDEX", "NAME", "BACKEND", "AVAILABLE", "DESCRIPTION"], rows)
+}
+
+/// Webcams found by a search as a table, one per line
+fn webcam_table(webcams: &[RemoteWebcam]) -> String {
+    let rows = webcams
+        .iter()
+        .map(|webcam| {
+            let access = match webcam.access_type {
+                WebcamAccessType::MJPEG => "mjpeg",
+                WebcamAccessType::RTSP => "rtsp",
+                WebcamAccessType::HTTP => "http",
+                WebcamAccessType::ONVIF => "onvif",
+                WebcamAccessType::Unknown => "unknown",
+            };
+            let country = webcam.location.as_ref().and_then(|location| location.country_code.clone());
+            vec![
+                webcam.ip.clone(),
+                webcam.port.to_string(),
+                access.to_string(),
+                webcam.classification.as_ref().map(|c| c.vendor.to_string()).unwrap_or_default(),
+                country.unwrap_or_default(),
+                webcam.org.clone().unwrap_or_default(),
+                sanitize_url(&webcam.url),
+            ]
+        })
+        .collect();
+    table(&["IP", "PORT", "ACCESS", "VENDOR", "COUNTRY", "ORG", "URL"], rows)
+}
+
+/// `rows` under `header` in columns aligned with spaces
+fn table(header: &[&str], rows: Vec<Vec<String>>) -> String {
+    let header: Vec<String> = header.iter().map(|title| title.to_string()).collect();
+    let mut widths = vec![0; header.len()];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row.iter().zip(&widths).map(|(cell, &width)| format!("{:<width$}", cell, width = width)).collect();
+        table.push_str(line.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// Exit status for a failed Shodan request
+fn shodan_exit_code(error: &ShodanError) -> u8 {
+    match error {
+        ShodanError::NoApiKey | ShodanError::Unauthorized => EXIT_UNAUTHORIZED,
+        ShodanError::RateLimitExceeded { .. } | ShodanError::BudgetExceeded(_) => EXIT_RATE_LIMITED,
+        _ => EXIT_FAILURE,
+    }
+}
+
+/// Shodan client configured as the server's would be, counting requests
+/// against the same budget. Without a key it can only fetch webcams.
+fn shodan_client(settings: &ServerSettings) -> Result<ShodanClient, ShodanError> {
+    let key = settings.resolved_shodan_key().unwrap_or_default();
+    let budget = CreditBudget::open(
+        settings.capture_store().state_dir().join(SHODAN_USAGE_FILE),
+        settings.shodan_max_requests_per_hour,
+        settings.shodan_max_requests_per_day,
+    );
+    Ok(settings.shodan_client_builder().build(key)?.with_credit_budget(Arc::new(budget)))
+}
+
+async fn shodan(settings: &ServerSettings, command: ShodanCommand) -> ExitCode {
+    if !settings.remote_enabled() {
+        return fail(
+            format!("remote webcam access is turned off (--no-remote or {})", mcp_webcam::settings::DISABLE_REMOTE_ENV),
+            EXIT_FAILURE,
+        );
+    }
+    let client = match shodan_client(settings) {
+        Ok(client) => client,
+        Err(e) => return fail(e, EXIT_FAILURE),
+    };
+    match command {
+        ShodanCommand::Search { query, limit, json } => shodan_search(settings, &client, query, limit, json).await,
+        ShodanCommand::Fetch { url, out, username, password } => {
+            let credentials = username.zip(password).map(|(username, password)| WebcamCredentials::new(username, password));
+            shodan_fetch(&client, &url, out.as_deref(), credentials.as_ref()).await
+        }
+    }
+}
+
+async fn shodan_search(settings: &ServerSettings, client: &ShodanClient, query: String, limit: u32, json: bool) -> ExitCode {
+    if !settings.shodan_enabled {
+        return fail("Shodan is disabled by --no-shodan", EXIT_FAILURE);
+    }
+    if !client.has_api_key() {
+        return fail("no Shodan API key: pass --shodan-key or --shodan-key-file, or set SHODAN_API_KEY", EXIT_UNAUTHORIZED);
+    }
+    let plan = QueryPlan { queries: vec![query.clone()], planned: 1 };
+    let found = match client.search_webcams(Some(limit), &SearchFilters::default(), &plan).await {
+        Ok(found) => found,
+        Err(e) => {
+            let code = shodan_exit_code(&e);
+            return fail(e, code);
+        }
+    };
+
+    let empty = found.webcams.is_empty();
+    if json {
+        let output = serde_json::json!({ "query": query, "count": found.webcams.len(), "webcams": found.webcams });
+        match serde_json::to_string_pretty(&output) {
+            Ok(text) => println!("{}", text),
+            Err(e) => return fail(e, EXIT_FAILURE),
+        }
+    } else if !empty {
+        print!("{}", webcam_table(&found.webcams));
+    }
+    if empty {
+        eprintln!("No webcams found for {}", query);
+        return ExitCode::from(EXIT_NO_RESULTS);
+    }
+    ExitCode::SUCCESS
+}
+
+async fn shodan_fetch(
+    client: &ShodanClient,
+    url: &str,
+    out: Option<&std::path::Path>,
+    credentials: Option<&WebcamCredentials>,
+) -> ExitCode {
+    let parsed = match url::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => return fail(format!("invalid URL {}: {}", sanitize_url(url), e), EXIT_FAILURE),
+    };
+    let webcam = RemoteWebcam {
+        ip: parsed.host_str().unwrap_or_default().trim_matches(['[', ']']).to_string(),
+        port: parsed.port_or_known_default().unwrap_or(80),
+        url: url.to_string(),
+        hostname: None,
+        location: None,
+        org: None,
+        product: None,
+        last_seen: chrono::Utc::now().to_rfc3339(),
+        access_type: WebcamAccessType::HTTP,
+        classification: None,
+        sources: Vec::new(),
+    };
+    let image = match client.fetch_webcam_image_with_credentials(&webcam, credentials).await {
+        Ok(image) => image,
+        Err(e) => {
+            let code = shodan_exit_code(&e);
+            return fail(e, code);
+        }
+    };
+
+    if let Err(e) = write_output(out, &image.bytes) {
+        return fail(e, EXIT_FAILURE);
+    }
+    if image.tls_unverified {
+        eprintln!("warning: the webcam's TLS certificate could not be verified and was accepted");
+    }
+    info!("Fetched {} ({} bytes) from {}", image.mime_type, image.bytes.len(), sanitize_url(url));
+    ExitCode::SUCCESS
+}
+
+/// Write `bytes` to the file at `out`, or to stdout for `-` or none
+fn write_output(out: Option<&std::path::Path>, bytes: &[u8]) -> Result<(), String> {
+    match out {
+        Some(path) if path.as_os_str() != "-" => {
+            std::fs::write(path, bytes).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+        }
+        _ => {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(bytes).and_then(|_| stdout.flush())
+                .map_err(|e| format!("failed to write the image to stdout: {}", e))
+        }
+    }
+}
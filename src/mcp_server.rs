@@ -1,47 +1,390 @@
-use crate::webcam::WebcamManager;
-use crate::shodan::{ShodanClient, RemoteWebcam, WebcamAccessType};
+use crate::audit::{self, AuditEntry, AuditLog};
+use crate::auth::AuthToken;
+use crate::backend::CaptureBackend;
+use crate::config::ConfigFile;
+use crate::consent::{ConsentGate, ConsentScope};
+use crate::discovery::{Discovery, ProviderFailure, WebcamDiscoveryProvider};
+use crate::webcam::{self, CameraProfile, CaptureOptions, CaptureStore, FaceBlur, WebcamManager};
+use crate::image_processing::EnhanceMode;
+use crate::capture_history::CaptureHistory;
+use crate::classify::Vendor;
+use crate::image_processing::{self, CropRect, WhiteBalance};
+use crate::barcode;
+use crate::exif_writer::{self, ExifMetadata};
+use crate::export::{self, ExportFormat};
+use crate::preview::{PreviewConfig, PreviewServer};
+use crate::health::HealthReport;
+use crate::credit_budget::{CreditBudget, SHODAN_USAGE_FILE};
+use crate::honeypot::{HoneypotGuard, DEFAULT_HONEYPOT_THRESHOLD, MAX_SEARCH_HONEYSCORES};
+use crate::host_cooldown::{HostCooldown, DEFAULT_HOST_COOLDOWN};
+use crate::http_auth::WebcamCredentials;
+use crate::liveness;
+use crate::local_discovery::{self, LocalProtocol, DEFAULT_DISCOVERY_TIMEOUT, MAX_DISCOVERY_TIMEOUT};
+use crate::network_scan::{self, ScanRange, DEFAULT_CONNECT_TIMEOUT, DEFAULT_SCAN_CONCURRENCY, DEFAULT_SCAN_PORTS, MAX_CONNECT_TIMEOUT, MAX_SCAN_CONCURRENCY, MAX_SCAN_PORTS, MAX_SCAN_PREFIX};
+use crate::probe::{ProbeOptions, MAX_PROBE_CONCURRENCY, MAX_PROBE_REQUESTS};
+use crate::validate::{ValidateDepth, ValidateOptions, WebcamValidation, MAX_VALIDATE_CONCURRENCY, MAX_VALIDATE_TIMEOUT, MAX_VALIDATE_WEBCAMS};
+use crate::webcam_cache::{CacheFilter, WebcamCache, DEFAULT_LIST_LIMIT, DEFAULT_WEBCAM_CACHE_TTL, MAX_LIST_LIMIT, WEBCAM_DB_FILE};
+use crate::server_info::{self, ServerInfo};
+use crate::server_stats::{self, ServerStats};
+use crate::settings::ServerSettings;
+use crate::shodan::{group_by_host, parse_query_list, parse_scan_targets, MAX_SCAN_TARGETS, read_key_file, RequestLimiter, RetryPolicy, ShodanClientBuilder, sanitize_url, select_queries, GeoFilter, SearchFilters, ShodanLocation, DEFAULT_MAX_QUERIES, DEFAULT_SEARCH_LIMIT, DEFAULT_WEBCAM_QUERIES, SEARCH_QUERIES_ENV, DEFAULT_SHODAN_REQUESTS_PER_SECOND, ShodanClient, ShodanError, RemoteImage, RemoteImageInfo, RemoteWebcam, WebcamAccessType};
+use crate::tool_error::{ErrorCode, ToolError};
+use crate::tool_schemas;
+use crate::target_policy::{TargetRule, ALLOWED_SCHEMES, MAX_REDIRECTS};
 use anyhow::Result;
 use base64::{engine::general_purpose, Engine as _};
 use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
-// Import mcpr types
-use mcpr::server::{Server, ServerConfig};
+use crate::protocol::{self, CancellationToken, Progress, RateLimit, Resource, ResourceContents, ResourceTemplate, RpcError, Server, ServerConfig, StdioTransport, ToolAnnotations, ToolCall, ToolRegistry, Transport};
 use mcpr::schema::common::{Tool, ToolInputSchema};
-use mcpr::transport::stdio::StdioTransport;
 use mcpr::error::MCPError;
 
+/// Upper bound on the number of frames in a clip
+const MAX_CLIP_FRAMES: u64 = 50;
+/// Upper bound on the time between the first and last frame of a clip
+const MAX_CLIP_DURATION_MS: u64 = 20_000;
+/// GIF clips up to this size are returned inline, larger ones are saved to disk
+const CLIP_INLINE_LIMIT_BYTES: usize = 2 * 1024 * 1024;
+/// Webcam exports up to this size are returned inline, larger ones are saved to disk
+const EXPORT_INLINE_LIMIT_BYTES: usize = 512 * 1024;
+/// How long the startup self-check waits for Shodan to answer
+const SHODAN_KEY_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Upper bound on the length of a camera benchmark
+const MAX_BENCHMARK_SECONDS: f64 = 10.0;
+/// URI prefix of captures returned as resources; the capture id follows it
+const CAPTURE_RESOURCE_PREFIX: &str = "webcam://capture/";
+/// URI prefix of camera resources: `webcam://camera/{index}` and
+/// `webcam://camera/{index}/snapshot`
+const CAMERA_RESOURCE_PREFIX: &str = "webcam://camera/";
+/// Snapshot resource reads within this long of the last one reuse its capture
+const SNAPSHOT_MAX_AGE: Duration = Duration::from_secs(2);
+/// Default time between snapshots of a subscribed camera
+pub const DEFAULT_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(5);
+/// Shortest time allowed between snapshots of a subscribed camera
+pub const MIN_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+/// Default cap on tool calls running at once
+pub const DEFAULT_MAX_CONCURRENT_CALLS: usize = 4;
+/// Tools whose calls are recorded in the audit log: everything that
+/// activates a camera or reaches out to a remote host
+const AUDITED_TOOLS: &[&str] = &[
+    "capture_image", "scan_codes", "capture_clip", "self_test", "benchmark_camera", "compare_captures",
+    "start_preview", "search_webcams", "count_webcams", "capture_remote_image", "shodan_host_info", "check_honeypot",
+    "request_shodan_scan", "probe_webcam", "validate_webcams", "discover_local_network_cameras",
+    "scan_network_for_cameras", "capture_onvif_snapshot",
+];
+/// Default timeout of tools that capture from a local camera
+const CAPTURE_TOOL_TIMEOUT: Duration = Duration::from_secs(15);
+/// Default timeout of a Shodan search
+const SHODAN_SEARCH_TIMEOUT: Duration = Duration::from_secs(60);
+/// Default timeout of a Shodan host lookup
+const SHODAN_HOST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default timeout of a remote webcam fetch
+const REMOTE_FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+/// Default timeout of probing a webcam for its snapshot URL
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default timeout of validating a batch of webcams
+const VALIDATE_TIMEOUT: Duration = Duration::from_secs(120);
+/// Default timeout of listening for cameras on the local network, on top
+/// of the listen window itself
+const LOCAL_DISCOVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default timeout of an ONVIF snapshot: up to four SOAP requests, then
+/// the image
+const ONVIF_SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(60);
+/// Default timeout of scanning a network for cameras
+const NETWORK_SCAN_TIMEOUT: Duration = Duration::from_secs(300);
+/// Tools offered while a Shodan client is configured
+const SHODAN_TOOLS: [&str; 15] = [
+    "search_webcams", "count_webcams", "list_search_queries", "capture_remote_image", "shodan_account_info",
+    "shodan_host_info", "check_honeypot", "request_shodan_scan", "get_scan_status", "probe_webcam", "validate_webcams",
+    "list_remote_webcams", "clear_remote_webcams", "export_webcams", "get_target_policy",
+];
+
+/// The webcam MCP server: tool definitions, their handlers and the state
+/// they share.
+///
+/// - `new` / `new_with_settings` create it from the environment or from
+///   startup settings
+/// - `builder` creates it from injected parts, e.g. a `WebcamManager` with
+///   a mock backend, without reading the environment for anything supplied
+/// - `run` serves MCP over stdio until the client disconnects
+/// - `run_with_transport` does the same over any `protocol::Transport`,
+///   e.g. a socket or an in-memory channel in tests
+/// - `build_server` returns the configured `protocol::Server` without
+///   serving it, for driving requests directly with `handle_message`
+#[derive(Clone)]
 pub struct WebcamMcpServer {
+    /// Name and version reported in `initialize`
+    name: String,
+    version: String,
     webcam_manager: Arc<Mutex<WebcamManager>>,
-    shodan_client: Option<ShodanClient>,
+    /// Shared so `configure_shodan` can replace or remove it while serving
+    shodan_client: Arc<Mutex<Option<ShodanClient>>>,
+    /// Discovery services searched besides Shodan, e.g. Censys
+    discovery_providers: Vec<Arc<dyn WebcamDiscoveryProvider>>,
+    /// Whether `configure_shodan` is offered
+    shodan_configurable: bool,
+    /// Whether remote webcam access is allowed at all; when not, no Shodan
+    /// or remote capture tool is ever offered
+    remote_enabled: bool,
+    /// Whether `scan_network_for_cameras` is offered
+    network_scan_enabled: bool,
+    /// File `reload_credentials` reads the Shodan key from
+    shodan_key_file: Option<PathBuf>,
+    capture_history: Arc<Mutex<CaptureHistory>>,
+    capture_store: CaptureStore,
+    /// Latest capture served as each camera's snapshot resource
+    snapshots: Arc<Mutex<HashMap<u32, Snapshot>>>,
+    /// Time between snapshots of cameras whose snapshot is subscribed to
+    snapshot_interval: Duration,
+    preview: Arc<Mutex<Option<PreviewServer>>>,
+    /// Token the preview requires; `None` generates one per session
+    auth_token: Option<AuthToken>,
+    /// Usage counters reported by `get_server_stats`
+    stats: Arc<Mutex<ServerStats>>,
+    /// Whether any local camera was found at startup
+    local_cameras_available: bool,
+    /// Whether the live preview tools are offered
+    preview_enabled: bool,
+    /// Longest each tool call may run, enforced by the dispatch layer
+    tool_timeouts: BTreeMap<String, Duration>,
+    /// Calls allowed per tool over time, enforced by the dispatch layer
+    rate_limits: BTreeMap<String, RateLimit>,
+    /// Most tool calls running at once; unlimited when `None`
+    max_concurrent_calls: Option<usize>,
+    /// Record of camera activations and remote fetches
+    audit_log: AuditLog,
+    /// Which camera tools wait for the user's consent
+    consent: ConsentGate,
+    /// Shodan queries `search_webcams` chooses from
+    search_queries: Arc<Vec<String>>,
+    /// Webcams `search_webcams` found, with their validations
+    webcam_cache: Arc<Mutex<WebcamCache>>,
+    /// Settings of the clients `configure_shodan` and `reload_credentials`
+    /// make, sharing `shodan_limiter`
+    shodan_client_settings: ShodanClientBuilder,
+    /// Paces the API requests of every Shodan client, whatever its key
+    shodan_limiter: Arc<RequestLimiter>,
+    /// Spaces the fetches of every Shodan client from each webcam host
+    host_cooldown: Arc<HostCooldown>,
+    /// Honeypot scores looked up, and what to do about high ones
+    honeypot: Arc<HoneypotGuard>,
+    /// Search and host requests every Shodan client may make, and has made
+    credit_budget: Arc<CreditBudget>,
 }
 
 impl WebcamMcpServer {
     pub fn new() -> Self {
-        let shodan_client = std::env::var("SHODAN_API_KEY")
+        Self::new_with_settings(ServerSettings::default())
+    }
+
+    /// Server configured from startup settings, e.g. command-line flags.
+    /// `settings` should already have passed `ServerSettings::validate`.
+    pub fn new_with_settings(settings: ServerSettings) -> Self {
+        let remote_enabled = settings.remote_enabled();
+        let shodan_client_settings = settings.shodan_client_builder();
+        let shodan_client = settings.resolved_shodan_key().and_then(|key| {
+            shodan_client_settings.build(key)
+                .map_err(|e| error!("Shodan features disabled: {}", e))
+                .ok()
+        });
+        #[cfg(feature = "censys")]
+        let censys_client = crate::censys::CensysClient::from_env(&shodan_client_settings)
+            .map_err(|e| error!("Censys features disabled: {}", e))
             .ok()
-            .map(ShodanClient::new);
-            
-        if shodan_client.is_some() {
+            .flatten()
+            .filter(|_| remote_enabled);
+        #[cfg(feature = "censys")]
+        if censys_client.is_some() {
+            info!("Censys integration enabled");
+        }
+
+        if !remote_enabled {
+            warn!("🔒 Remote webcam access is administratively disabled (--no-remote or {}): no Shodan search or remote capture, whatever key is set",
+                  crate::settings::DISABLE_REMOTE_ENV);
+        } else if shodan_client.is_some() {
             info!("Shodan integration enabled");
+        } else if !settings.shodan_enabled {
+            info!("Shodan integration disabled by configuration");
         } else {
             warn!("SHODAN_API_KEY not found - Shodan features will be disabled");
         }
 
-        Self {
-            webcam_manager: Arc::new(Mutex::new(WebcamManager::new())),
-            shodan_client,
+        let auth_token = settings.resolved_auth_token();
+        if auth_token.is_some() {
+            info!("Network listeners require the configured auth token");
+        }
+
+        let mut builder = Self::builder()
+            .webcam_manager(settings.webcam_manager())
+            .shodan_configurable(settings.shodan_enabled)
+            .remote(remote_enabled)
+            .network_scan(settings.allow_network_scan)
+            .shodan_client_settings(shodan_client_settings)
+            .snapshot_interval(settings.snapshot_interval)
+            .webcam_cache_ttl(settings.webcam_cache_ttl)
+            .host_cooldown(settings.host_cooldown)
+            .honeypot(settings.honeypot_threshold, settings.honeypot_strict)
+            .shodan_budget(settings.shodan_max_requests_per_hour, settings.shodan_max_requests_per_day)
+            .consent(ConsentGate::new(settings.require_consent, settings.require_remote_consent, settings.consent_duration));
+        let capture_store = settings.capture_store();
+        builder = builder
+            .webcam_db(capture_store.state_dir().join(WEBCAM_DB_FILE))
+            .shodan_usage_db(capture_store.state_dir().join(SHODAN_USAGE_FILE))
+            .capture_store(capture_store);
+        if let Some(client) = shodan_client {
+            builder = builder.shodan_client(client);
+        }
+        #[cfg(feature = "censys")]
+        if let Some(client) = censys_client {
+            builder = builder.discovery_provider(Arc::new(client));
+        }
+        if let Some(path) = settings.resolved_shodan_key_file() {
+            info!("Shodan key file: {}", path.display());
+            builder = builder.shodan_key_file(path);
+        }
+        if let Some(token) = auth_token {
+            builder = builder.auth_token(token);
+        }
+        let config = ConfigFile::load().unwrap_or_else(|e| {
+            warn!("Ignoring the configuration file: {}", e);
+            ConfigFile::default()
+        });
+        match config.tool_timeouts() {
+            Ok(timeouts) => {
+                for (tool, timeout) in timeouts {
+                    builder = builder.tool_timeout(tool, timeout);
+                }
+            }
+            Err(e) => warn!("Ignoring tool timeouts in the configuration file: {}", e),
+        }
+        match config.rate_limits() {
+            Ok(limits) => {
+                for (tool, limit) in limits {
+                    builder = builder.rate_limit(tool, limit);
+                }
+            }
+            Err(e) => warn!("Ignoring rate limits in the configuration file: {}", e),
+        }
+        match config.max_concurrent_calls {
+            Some(0) => warn!("Ignoring max_concurrent_calls = 0 in the configuration file"),
+            Some(max) => builder = builder.max_concurrent_calls(Some(max)),
+            None => {}
+        }
+        if let Some(path) = &config.audit_log {
+            builder = builder.audit_log(AuditLog::open(path));
+        }
+        let env_queries = std::env::var(SEARCH_QUERIES_ENV).ok()
+            .map(|text| parse_query_list(&text))
+            .filter(|queries| !queries.is_empty());
+        match env_queries {
+            Some(queries) => builder = builder.search_queries(queries),
+            None => match config.search_queries() {
+                Ok(Some(queries)) => builder = builder.search_queries(queries),
+                Ok(None) => {}
+                Err(e) => warn!("Ignoring search queries in the configuration file: {}", e),
+            },
+        }
+        if let Some(retries) = config.shodan_max_retries {
+            builder = builder.shodan_retry_policy(RetryPolicy::with_max_retries(retries));
+        }
+        match config.shodan_requests_per_second() {
+            Ok(Some(rate)) => builder = builder.shodan_requests_per_second(rate),
+            Ok(None) => {}
+            Err(e) => warn!("Ignoring shodan_requests_per_second in the configuration file: {}", e),
+        }
+        builder.build()
+    }
+
+    /// Log the health report, after checking the Shodan key and its
+    /// account's credits if there is one. Problems are logged, never fatal:
+    /// a rejected key keeps its tools, whose errors then say what's wrong.
+    async fn startup_self_check(&self) {
+        let shodan_client = self.current_shodan_client();
+        if let Some(client) = &shodan_client {
+            match tokio::time::timeout(SHODAN_KEY_CHECK_TIMEOUT, client.api_info()).await {
+                Ok(Ok(account)) => {
+                    record(&self.stats, |s| s.record_shodan_key_check(true));
+                    info!("Shodan key valid: {} plan, {} query credits and {} scan credits left",
+                          account.plan.as_deref().unwrap_or("unknown"), account.query_credits, account.scan_credits);
+                    if account.query_credits <= 0 {
+                        warn!("Shodan account has no query credits left - search_webcams will fail until they renew");
+                    }
+                }
+                Ok(Err(ShodanError::Unauthorized)) => {
+                    record(&self.stats, |s| s.record_shodan_key_check(false));
+                    warn!("⚠️ Shodan REJECTED the API key - check the configured key; the Shodan tools stay registered but will fail with SHODAN_UNAUTHORIZED");
+                }
+                Ok(Err(e)) => warn!("Could not check the Shodan key: {}", e),
+                Err(_) => warn!("Could not check the Shodan key: no answer within {:?}", SHODAN_KEY_CHECK_TIMEOUT),
+            }
+        }
+
+        let manager = self.webcam_manager.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let stats = self.stats.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        HealthReport::collect(Some(&manager), &stats, shodan_client.is_some()).log();
+    }
+
+    fn current_shodan_client(&self) -> Option<ShodanClient> {
+        self.shodan_client.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// Builder for a server assembled from injected parts
+    pub fn builder() -> WebcamMcpServerBuilder {
+        WebcamMcpServerBuilder::default()
+    }
+
+    /// Description for a tool that needs a local camera, with a warning
+    /// appended when none was found at startup
+    fn local_tool_description(&self, description: impl Into<String>) -> Option<String> {
+        let description = description.into();
+        if self.local_cameras_available {
+            Some(description)
+        } else {
+            Some(format!("{}. NOTE: no local cameras were detected when the server started, so this will fail unless a camera has since been connected", description.trim_end_matches('.')))
         }
     }
 
+    /// Serve MCP over stdio until stdin is closed
     pub async fn run(&self) -> Result<()> {
+        self.run_with_transport(StdioTransport::new()).await
+    }
+
+    /// Serve MCP over `transport` until the client goes away
+    pub async fn run_with_transport<T: Transport>(&self, transport: T) -> Result<()> {
         info!("🚀 Starting MCP Webcam Server with full protocol support");
-        
+
+        let server = self.build_server()?;
+
+        self.startup_self_check().await;
+
+        // Enforce the retention policy on the capture output directory
+        let cleanup = self.capture_store.spawn_cleanup_task();
+
+        info!("✅ MCP Webcam Server ready - starting transport");
+
+        // Serve on a blocking thread; handlers may block on camera I/O
+        let served = tokio::task::spawn_blocking(move || server.serve(transport)).await;
+        cleanup.abort();
+        if let Ok(mut cache) = self.webcam_cache.lock() {
+            cache.flush();
+        }
+        served??;
+
+        Ok(())
+    }
+
+    /// Define all tools and register their handlers, without starting the
+    /// transport. Must be called within a tokio runtime.
+    pub fn build_server(&self) -> Result<Server, MCPError> {
         // Create server configuration with tools
         let mut config = ServerConfig::new()
-            .with_name("mcp-webcam")
-            .with_version("0.1.0");
+            .with_name(&self.name)
+            .with_version(&self.version);
 
         // Add local camera tools
         config = config.with_tool(Tool {
@@ -49,14 +392,98 @@ impl WebcamMcpServer {
             description: Some("List all available local camera devices".to_string()),
             input_schema: ToolInputSchema {
                 r#type: "object".to_string(),
-                properties: Some(std::collections::HashMap::new()),
+                properties: Some({
+                    let mut props = std::collections::HashMap::new();
+                    props.insert("refresh".to_string(), json!({
+                        "type": "boolean",
+                        "description": "Re-enumerate devices instead of using the list cached for a few seconds (optional, default false)"
+                    }));
+                    props
+                }),
                 required: None,
             },
         });
 
         config = config.with_tool(Tool {
             name: "capture_image".to_string(),
-            description: Some("Capture an image from a local camera".to_string()),
+            description: self.local_tool_description("Capture an image from a local camera"),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some({
+                    let mut props = std::collections::HashMap::new();
+                    props.insert("camera_index".to_string(), json!({
+                        "type": "number",
+                        "description": "Camera index to use (optional, defaults to 0)"
+                    }));
+                    props.insert("enhance".to_string(), json!({
+                        "type": "string",
+                        "enum": ["auto", "gamma", "equalize"],
+                        "description": "Low-light enhancement (optional). 'auto' only applies gamma correction when the frame is dark; omit for an unmodified capture"
+                    }));
+                    props.insert("crop".to_string(), crop_schema());
+                    props.insert("white_balance".to_string(), json!({
+                        "type": ["string", "number"],
+                        "description": "White balance (optional): 'auto' for software gray-world correction, or a colour temperature in Kelvin (1000-15000) set on the camera when supported, with software correction as fallback"
+                    }));
+                    props.insert("blur_faces".to_string(), json!({
+                        "type": "boolean",
+                        "description": "Blur detected faces before encoding (optional, defaults to false)"
+                    }));
+                    props.insert("strict".to_string(), json!({
+                        "type": "boolean",
+                        "description": "With blur_faces: fail the capture if face detection fails (optional, defaults to true). Set to false to get the unblurred frame instead"
+                    }));
+                    props.insert("as_resource".to_string(), json!({
+                        "type": "boolean",
+                        "description": "Return a webcam://capture/{id} resource link instead of inline base64, to be fetched with resources/read (optional, defaults to false). Recommended for large images"
+                    }));
+                    props.insert("save".to_string(), json!({
+                        "type": "boolean",
+                        "description": "Also write the JPEG, with EXIF metadata, to the server's data directory and return its path (optional, defaults to false)"
+                    }));
+                    props.insert("embed_exif".to_string(), json!({
+                        "type": "boolean",
+                        "description": "Embed EXIF metadata (capture time, camera name, software) in the returned image (optional, defaults to false to keep it small). Saved files always carry EXIF"
+                    }));
+                    props.insert("comment".to_string(), json!({
+                        "type": "string",
+                        "description": "EXIF user comment for the embedded or saved metadata (optional)"
+                    }));
+                    insert_profile_properties(&mut props);
+                    props
+                }),
+                required: None,
+            },
+        });
+
+        config = config.with_tool(Tool {
+            name: "scan_codes".to_string(),
+            description: self.local_tool_description("Capture a frame and decode all QR codes and barcodes (QR, EAN, Code 128, ...) in it"),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some({
+                    let mut props = std::collections::HashMap::new();
+                    props.insert("camera_index".to_string(), json!({
+                        "type": "number",
+                        "description": "Camera index to use (optional, defaults to 0)"
+                    }));
+                    props.insert("crop".to_string(), crop_schema());
+                    props.insert("include_image".to_string(), json!({
+                        "type": "boolean",
+                        "description": "Also return the frame with detected codes outlined (optional, defaults to false)"
+                    }));
+                    props
+                }),
+                required: None,
+            },
+        });
+
+        config = config.with_tool(Tool {
+            name: "capture_clip".to_string(),
+            description: self.local_tool_description(format!(
+                "Capture a short burst of frames and return it as an animated GIF (inline when under {} bytes, otherwise saved to disk) or save it as an MJPEG file. At most {} frames and {} ms in total.",
+                CLIP_INLINE_LIMIT_BYTES, MAX_CLIP_FRAMES, MAX_CLIP_DURATION_MS
+            )),
             input_schema: ToolInputSchema {
                 r#type: "object".to_string(),
                 properties: Some({
@@ -65,6 +492,29 @@ impl WebcamMcpServer {
                         "type": "number",
                         "description": "Camera index to use (optional, defaults to 0)"
                     }));
+                    props.insert("frames".to_string(), json!({
+                        "type": "number",
+                        "description": format!("Number of frames (optional, defaults to 10, max {})", MAX_CLIP_FRAMES)
+                    }));
+                    props.insert("interval_ms".to_string(), json!({
+                        "type": "number",
+                        "description": "Delay between frames in milliseconds (optional, defaults to 200)"
+                    }));
+                    props.insert("format".to_string(), json!({
+                        "type": "string",
+                        "enum": ["gif", "mjpeg"],
+                        "description": "Output format (optional, defaults to gif)"
+                    }));
+                    props.insert("max_width".to_string(), json!({
+                        "type": "number",
+                        "description": "Downscale frames to this width (optional, defaults to 480 for GIF, 0 keeps full size)"
+                    }));
+                    props.insert("enhance".to_string(), json!({
+                        "type": "string",
+                        "enum": ["auto", "gamma", "equalize"],
+                        "description": "Low-light enhancement applied to every frame (optional)"
+                    }));
+                    props.insert("crop".to_string(), crop_schema());
                     props
                 }),
                 required: None,
@@ -81,18 +531,170 @@ impl WebcamMcpServer {
             },
         });
 
-        // Add Shodan tools if API key is available
-        if self.shodan_client.is_some() {
+        config = config.with_tool(Tool {
+            name: "set_camera_defaults".to_string(),
+            description: Some("Set the default capture settings for a camera, used whenever a call doesn't override them. Omitted settings are cleared; pass only camera_index to reset the profile".to_string()),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some({
+                    let mut props = std::collections::HashMap::new();
+                    props.insert("camera_index".to_string(), json!({
+                        "type": "number",
+                        "description": "Camera the defaults apply to"
+                    }));
+                    insert_profile_properties(&mut props);
+                    props
+                }),
+                required: Some(vec!["camera_index".to_string()]),
+            },
+        });
+
+        config = config.with_tool(Tool {
+            name: "set_default_camera".to_string(),
+            description: Some("Choose the camera used by calls that don't pass camera_index, for the rest of the session".to_string()),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some({
+                    let mut props = std::collections::HashMap::new();
+                    props.insert("camera_index".to_string(), json!({
+                        "type": "number",
+                        "description": "Camera to use by default; must be one listed by list_cameras"
+                    }));
+                    props
+                }),
+                required: Some(vec!["camera_index".to_string()]),
+            },
+        });
+
+        config = config.with_tool(Tool {
+            name: "self_test".to_string(),
+            description: self.local_tool_description("Check that cameras work: open each one, grab a frame, reject all-black/all-white frames and report latencies. Returns no image data"),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some({
+                    let mut props = std::collections::HashMap::new();
+                    props.insert("camera_index".to_string(), json!({
+                        "type": "number",
+                        "description": "Test only this camera (optional, defaults to all cameras)"
+                    }));
+                    props
+                }),
+                required: None,
+            },
+        });
+
+        config = config.with_tool(Tool {
+            name: "benchmark_camera".to_string(),
+            description: self.local_tool_description("Grab frames continuously for a few seconds and report achieved FPS, frame interval statistics, dropped frames and the negotiated format"),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some({
+                    let mut props = std::collections::HashMap::new();
+                    props.insert("camera_index".to_string(), json!({
+                        "type": "number",
+                        "description": "Camera index to benchmark (optional, defaults to 0)"
+                    }));
+                    props.insert("duration_seconds".to_string(), json!({
+                        "type": "number",
+                        "description": "How long to capture for, up to 10 seconds (default: 3)",
+                        "minimum": 0.1,
+                        "maximum": MAX_BENCHMARK_SECONDS
+                    }));
+                    props
+                }),
+                required: None,
+            },
+        });
+
+        // Add capture history tools
+        config = config.with_tool(Tool {
+            name: "list_captures".to_string(),
+            description: Some("List recent captures kept in memory (ids, timestamps, camera, dimensions; no image data)".to_string()),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some(std::collections::HashMap::new()),
+                required: None,
+            },
+        });
+
+        config = config.with_tool(Tool {
+            name: "get_capture".to_string(),
+            description: Some("Return a previously captured image by its capture id".to_string()),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some({
+                    let mut props = std::collections::HashMap::new();
+                    props.insert("capture_id".to_string(), json!({
+                        "type": "string",
+                        "description": "Capture id returned in capture_image metadata"
+                    }));
+                    props
+                }),
+                required: Some(vec!["capture_id".to_string()]),
+            },
+        });
+
+        config = config.with_tool(Tool {
+            name: "compare_captures".to_string(),
+            description: Some("Compare two stored captures (or a stored capture against a new frame) and report how much changed and where".to_string()),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some({
+                    let mut props = std::collections::HashMap::new();
+                    props.insert("capture_id".to_string(), json!({
+                        "type": "string",
+                        "description": "Id of the earlier capture"
+                    }));
+                    props.insert("other_capture_id".to_string(), json!({
+                        "type": "string",
+                        "description": "Id of the later capture (optional if capture_new is true)"
+                    }));
+                    props.insert("capture_new".to_string(), json!({
+                        "type": "boolean",
+                        "description": "Capture a new frame now and compare against it (optional, defaults to false)"
+                    }));
+                    props.insert("camera_index".to_string(), json!({
+                        "type": "number",
+                        "description": "Camera for the new frame (optional, defaults to the camera of the first capture)"
+                    }));
+                    props.insert("threshold".to_string(), json!({
+                        "type": "number",
+                        "description": "Per-channel difference (0-255) for a pixel to count as changed (optional, defaults to 30)"
+                    }));
+                    props.insert("include_diff_image".to_string(), json!({
+                        "type": "boolean",
+                        "description": "Return an image with changed pixels highlighted (optional, defaults to false)"
+                    }));
+                    props
+                }),
+                required: Some(vec!["capture_id".to_string()]),
+            },
+        });
+
+        // Add preview tools unless disabled
+        if self.preview_enabled {
             config = config.with_tool(Tool {
-                name: "search_webcams".to_string(),
-                description: Some("Search for remote webcams using Shodan".to_string()),
+                name: "start_preview".to_string(),
+                description: self.local_tool_description("Start a live MJPEG preview of a local camera viewable in a browser; returns the URL (bound to localhost, protected by a per-session token)"),
                 input_schema: ToolInputSchema {
                     r#type: "object".to_string(),
                     properties: Some({
                         let mut props = std::collections::HashMap::new();
-                        props.insert("limit".to_string(), json!({
+                        props.insert("camera_index".to_string(), json!({
+                            "type": "number",
+                            "description": "Camera index to use (optional, defaults to 0)"
+                        }));
+                        props.insert("port".to_string(), json!({
+                            "type": "number",
+                            "description": "Port to listen on (optional, defaults to a random free port)"
+                        }));
+                        props.insert("fps".to_string(), json!({
                             "type": "number",
-                            "description": "Maximum number of results (optional, defaults to 20)"
+                            "description": "Frames per second, 1-30 (optional, defaults to 5)"
+                        }));
+                        props.insert("max_viewers".to_string(), json!({
+                            "type": "number",
+                            "description": "Maximum concurrent viewers (optional, defaults to 2)"
                         }));
                         props
                     }),
@@ -101,69 +703,290 @@ impl WebcamMcpServer {
             });
 
             config = config.with_tool(Tool {
-                name: "capture_remote_image".to_string(),
-                description: Some("Capture image from a remote webcam".to_string()),
+                name: "stop_preview".to_string(),
+                description: Some("Stop the running live preview".to_string()),
                 input_schema: ToolInputSchema {
                     r#type: "object".to_string(),
-                    properties: Some({
-                        let mut props = std::collections::HashMap::new();
-                        props.insert("url".to_string(), json!({
-                            "type": "string",
-                            "description": "Webcam URL to capture from"
-                        }));
-                        props.insert("ip".to_string(), json!({
-                            "type": "string",
-                            "description": "IP address (optional)"
-                        }));
-                        props.insert("port".to_string(), json!({
-                            "type": "number",
-                            "description": "Port number (optional)"
-                        }));
-                        props
-                    }),
-                    required: Some(vec!["url".to_string()]),
+                    properties: Some(std::collections::HashMap::new()),
+                    required: None,
                 },
             });
         }
 
-        // Create server and register tool handlers
-        let mut server: Server<StdioTransport> = Server::new(config);
-        
-        // Register local camera tool handlers
-        self.register_local_camera_tools(&mut server)?;
-        
-        // Register Shodan tool handlers if available
-        if self.shodan_client.is_some() {
-            self.register_shodan_tools(&mut server)?;
-        }
+        // Add storage tools
+        config = config.with_tool(Tool {
+            name: "get_storage_info".to_string(),
+            description: Some("Report the capture output directory, its current usage and the retention settings".to_string()),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some(std::collections::HashMap::new()),
+                required: None,
+            },
+        });
 
-        info!("📷 Local camera tools registered: list_cameras, capture_image, get_camera_info");
-        if self.shodan_client.is_some() {
-            info!("🌐 Shodan tools registered: search_webcams, capture_remote_image");
-        }
-        info!("✅ MCP Webcam Server ready - starting stdio transport");
+        // Add audit tools
+        config = config.with_tool(Tool {
+            name: "get_audit_log".to_string(),
+            description: Some("Return recent audit log entries: every camera activation, remote fetch and Shodan search with its time, tool, arguments, camera or URL, result size, outcome and duration. Newest entries last".to_string()),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some({
+                    let mut props = std::collections::HashMap::new();
+                    props.insert("since".to_string(), json!({
+                        "type": "string",
+                        "description": "Only entries at or after this RFC 3339 time, e.g. 2024-01-01T00:00:00Z (optional)"
+                    }));
+                    props.insert("limit".to_string(), json!({
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": audit::MAX_AUDIT_ENTRIES,
+                        "description": format!("Most entries to return, newest kept (optional, default {})", audit::DEFAULT_AUDIT_ENTRIES)
+                    }));
+                    props
+                }),
+                required: None,
+            },
+        });
 
-        // Create stdio transport and start server
-        let transport = StdioTransport::new();
-        server.start(transport)?;
+        config = config.with_tool(Tool {
+            name: "get_server_stats".to_string(),
+            description: Some("Report usage counters since the server started: captures per camera, failures by error type, bytes encoded, average capture latency, Shodan queries and uptime. Does not touch any camera".to_string()),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some(std::collections::HashMap::new()),
+                required: None,
+            },
+        });
 
-        Ok(())
+        config = config.with_tool(Tool {
+            name: "health_check".to_string(),
+            description: Some("Cheap liveness check: uptime, whether any camera can be enumerated, whether the Shodan key was accepted when last checked, and failed calls since startup. Never opens a camera".to_string()),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some(std::collections::HashMap::new()),
+                required: None,
+            },
+        });
+
+        config = config.with_tool(Tool {
+            name: "get_server_info".to_string(),
+            description: Some("Report the server version, the git commit it was built from, the cargo features it was built with, the default capture backend and whether Shodan is enabled".to_string()),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some(std::collections::HashMap::new()),
+                required: None,
+            },
+        });
+
+        if self.consent.is_required() {
+            config = config.with_tool(Tool {
+                name: "grant_consent".to_string(),
+                description: Some("Allow camera tools to run for a while, in consent mode. Camera tools fail with CONSENT_REQUIRED until this is called with the one-time code shown on the server's console; ask the user for it, never guess".to_string()),
+                input_schema: ToolInputSchema {
+                    r#type: "object".to_string(),
+                    properties: Some({
+                        let mut props = std::collections::HashMap::new();
+                        props.insert("code".to_string(), json!({
+                            "type": "string",
+                            "description": "One-time code the user read from the server's console"
+                        }));
+                        props
+                    }),
+                    required: Some(vec!["code".to_string()]),
+                },
+            });
+        }
+
+        if self.shodan_configurable {
+            config = config.with_tool(Tool {
+                name: "configure_shodan".to_string(),
+                description: Some("Enable the Shodan tools with an API key, which is checked against Shodan first, or disable them with remove. Clients are notified that the tool list changed".to_string()),
+                input_schema: ToolInputSchema {
+                    r#type: "object".to_string(),
+                    properties: Some({
+                        let mut props = std::collections::HashMap::new();
+                        props.insert("api_key".to_string(), json!({
+                            "type": "string",
+                            "description": "Shodan API key to use from now on"
+                        }));
+                        props.insert("remove".to_string(), json!({
+                            "type": "boolean",
+                            "description": "Forget the current key and remove the Shodan tools (optional, default false)"
+                        }));
+                        props
+                    }),
+                    required: None,
+                },
+            });
+        }
+
+        if self.shodan_key_file.is_some() {
+            config = config.with_tool(Tool {
+                name: "reload_credentials".to_string(),
+                description: Some("Re-read the Shodan API key from the server's key file after it was rotated, check it against Shodan and use it from now on. A key that can't be read or isn't accepted leaves the current one in place".to_string()),
+                input_schema: ToolInputSchema {
+                    r#type: "object".to_string(),
+                    properties: Some(std::collections::HashMap::new()),
+                    required: None,
+                },
+            });
+        }
+
+        for (tool, schema) in tool_schemas::output_schemas() {
+            config = config.with_output_schema(tool, schema);
+        }
+        for (tool, annotations) in tool_annotations() {
+            config = config.with_annotations(tool, annotations);
+        }
+        for (tool, timeout) in &self.tool_timeouts {
+            config = config.with_timeout(tool, *timeout);
+        }
+        for (tool, limit) in &self.rate_limits {
+            config = config.with_rate_limit(tool, *limit);
+        }
+        if let Some(max) = self.max_concurrent_calls {
+            config = config.with_max_concurrent_calls(max);
+        }
+
+        // Create server and register tool handlers
+        let mut server: Server = Server::new(config);
+
+        // Calls the dispatch layer rejects for their arguments count as failures
+        let stats_arguments = Arc::clone(&self.stats);
+        server.on_invalid_arguments(move |_tool| record(&stats_arguments, |s| s.record_failure("invalid_params")));
+        let stats_rate_limited = Arc::clone(&self.stats);
+        server.on_rate_limited(move |_tool| record(&stats_rate_limited, |s| s.record_failure("rate_limited")));
+
+        // In consent mode camera tools are refused until the user approves
+        if self.consent.is_required() {
+            let consent = self.consent.clone();
+            server.gate_tool_calls(move |tool| consent.check(tool));
+        }
+
+        // Every call that activates a camera or goes out to the network is audited
+        let audit_log = self.audit_log.clone();
+        server.on_tool_call(move |call| {
+            if AUDITED_TOOLS.contains(&call.tool) {
+                audit_log.record(audited_call(call));
+            }
+        });
+        
+        // Register local camera tool handlers
+        self.register_local_camera_tools(&mut server)?;
+
+        // Register capture history tool handlers
+        self.register_history_tools(&mut server)?;
+
+        // Register storage tool handlers
+        self.register_storage_tools(&mut server)?;
+
+        // Register audit tool handler
+        self.register_audit_tools(&mut server)?;
+
+        // Register statistics tool handler
+        self.register_stats_tools(&mut server)?;
+
+        // Register resource handlers
+        self.register_resources(&mut server);
+
+        // Register preview tool handlers
+        if self.preview_enabled {
+            self.register_preview_tools(&mut server)?;
+        }
+        
+        // Register local network discovery unless remote access is disabled
+        if self.remote_enabled {
+            self.register_local_network_tools(&mut server);
+        }
+        if self.network_scan_enabled {
+            self.register_network_scan_tools(&mut server);
+        }
+
+        // Register Shodan tools if a client or another discovery service is
+        // configured; configure_shodan adds and removes them later
+        let shodan_client = self.current_shodan_client();
+        let remote_client = shodan_client.clone()
+            .or_else(|| keyless_client(&self.shodan_client_settings, &self.discovery_providers));
+        if let Some(client) = &remote_client {
+            register_shodan_tools(&server.tool_registry(), client.clone(), &self.discovery_providers, &self.stats, &self.search_queries, &self.webcam_cache, &self.capture_store, &self.honeypot);
+        }
+        if self.shodan_configurable {
+            self.register_configure_shodan(&mut server)?;
+        }
+        if let Some(path) = &self.shodan_key_file {
+            self.register_reload_credentials(&mut server, path.clone())?;
+        }
+
+        if self.consent.is_required() {
+            self.register_consent_tools(&mut server)?;
+        }
+
+        info!("📷 Local camera tools registered: list_cameras, capture_image, scan_codes, capture_clip, get_camera_info, set_camera_defaults, set_default_camera, self_test, benchmark_camera");
+        info!("🗂️ Capture history tools registered: list_captures, get_capture, compare_captures");
+        info!("💾 Storage tools registered: get_storage_info");
+        info!("📜 Audit tools registered: get_audit_log");
+        info!("📊 Statistics tools registered: get_server_stats, get_server_info, health_check");
+        if self.preview_enabled {
+            info!("🎥 Preview tools registered: start_preview, stop_preview");
+        }
+        if self.remote_enabled {
+            info!("📡 Local network tools registered: discover_local_network_cameras, capture_onvif_snapshot");
+        }
+        if self.network_scan_enabled {
+            info!("🛰️ Network scan tools registered: scan_network_for_cameras");
+        }
+        if shodan_client.is_some() {
+            info!("🌐 Shodan tools registered: {}", SHODAN_TOOLS.join(", "));
+        } else if remote_client.is_some() {
+            let names: Vec<&str> = self.discovery_providers.iter().map(|provider| provider.name()).collect();
+            info!("🌐 Remote tools registered for {}: {}", names.join(", "), SHODAN_TOOLS.join(", "));
+        } else if !self.remote_enabled {
+            info!("🔒 Remote tools not registered: remote webcam access is administratively disabled");
+        }
+        if self.shodan_configurable {
+            info!("🔑 Shodan configuration tool registered: configure_shodan");
+        }
+        if self.shodan_key_file.is_some() {
+            info!("🔑 Credentials tool registered: reload_credentials");
+        }
+        if self.consent.is_required() {
+            info!("🛡️ Consent tool registered: grant_consent");
+        }
+
+        Ok(server)
     }
 
-    fn register_local_camera_tools(&self, server: &mut Server<StdioTransport>) -> Result<(), MCPError> {
+    fn register_local_camera_tools(&self, server: &mut Server) -> Result<(), MCPError> {
         // Clone references for closures
         let webcam_manager_list = Arc::clone(&self.webcam_manager);
         let webcam_manager_capture = Arc::clone(&self.webcam_manager);
+        let capture_store_image = self.capture_store.clone();
         let webcam_manager_info = Arc::clone(&self.webcam_manager);
+        let webcam_manager_scan = Arc::clone(&self.webcam_manager);
+        let webcam_manager_clip = Arc::clone(&self.webcam_manager);
+        let webcam_manager_defaults = Arc::clone(&self.webcam_manager);
+        let webcam_manager_default_camera = Arc::clone(&self.webcam_manager);
+        let webcam_manager_test = Arc::clone(&self.webcam_manager);
+        let webcam_manager_benchmark = Arc::clone(&self.webcam_manager);
+        let capture_store = self.capture_store.clone();
+        let capture_history = Arc::clone(&self.capture_history);
+        let stats_capture = Arc::clone(&self.stats);
+        let stats_scan = Arc::clone(&self.stats);
+        let stats_clip = Arc::clone(&self.stats);
 
         // Register list_cameras handler
-        server.register_tool_handler("list_cameras", move |_params: Value| -> Result<Value, MCPError> {
+        server.register_tool_handler("list_cameras", move |params: Value| -> Result<Value, MCPError> {
             debug!("Handling list_cameras request");
-            
+
+            let refresh = params.get("refresh")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+
             let manager = webcam_manager_list.lock()
                 .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam manager lock: {}", e)))?;
-            
-            match manager.list_cameras() {
+
+            let cameras = if refresh { manager.refresh_cameras() } else { manager.list_cameras() };
+            match cameras {
                 Ok(cameras) => {
                     info!("Found {} cameras", cameras.len());
                     Ok(json!({
@@ -176,13 +999,9 @@ impl WebcamMcpServer {
                 }
                 Err(e) => {
                     error!("Failed to list cameras: {}", e);
-                    Ok(json!({
-                        "content": [{
-                            "type": "text",
-                            "text": format!("Error listing cameras: {}", e)
-                        }],
-                        "cameras": []
-                    }))
+                    let mut response = ToolError::from(&e).into_response(format!("Error listing cameras: {}", e));
+                    response["structuredContent"]["cameras"] = json!([]);
+                    Ok(response)
                 }
             }
         })?;
@@ -196,19 +1015,84 @@ impl WebcamMcpServer {
                 .and_then(|v| v.as_u64())
                 .map(|v| v as u32);
 
+            // Parse processing options (optional)
+            let options = match parse_capture_options(&params) {
+                Ok(options) => options,
+                Err(message) => {
+                    record(&stats_capture, |s| s.record_failure("invalid_params"));
+                    return Ok(invalid_params_response(&message));
+                }
+            };
+
+            let save = params.get("save")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let as_resource = params.get("as_resource")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+
             let mut manager = webcam_manager_capture.lock()
                 .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam manager lock: {}", e)))?;
 
-            match manager.capture_image(camera_index) {
+            let started = Instant::now();
+            match manager.capture_image_with_options(camera_index, &options) {
                 Ok(result) => {
                     info!("Successfully captured image from camera {}", result.camera_index);
+                    record(&stats_capture, |s| {
+                        s.record_capture(result.camera_index, Some(started.elapsed()));
+                        s.record_encoded(result.image_bytes.len());
+                    });
+
+                    let saved_path = if save {
+                        // Saved files always carry EXIF, even if the inline image doesn't
+                        let bytes = if options.embed_exif {
+                            result.image_bytes.to_vec()
+                        } else {
+                            ExifMetadata {
+                                timestamp: Some(result.timestamp.clone()),
+                                camera_name: result.camera_name.clone(),
+                                comment: options.exif_comment.clone(),
+                            }.embed(&result.image_bytes)
+                        };
+                        match capture_store_image.save("capture", "jpg", &bytes) {
+                            Ok(path) => Some(path.display().to_string()),
+                            Err(e) => {
+                                error!("Failed to save capture: {}", e);
+                                record(&stats_capture, |s| s.record_failure("storage"));
+                                return Ok(ToolError::from(&e).into_response(format!("Error saving capture: {}", e)));
+                            }
+                        }
+                    } else {
+                        None
+                    };
+
+                    let capture_id = capture_history.lock()
+                        .map_err(|e| MCPError::Protocol(format!("Failed to acquire capture history lock: {}", e)))?
+                        .insert(result.clone());
+
+                    let image_content = if as_resource {
+                        if let Err(e) = capture_store_image.save_as(&resource_file_name(&capture_id), &result.image_bytes) {
+                            error!("Failed to store capture resource: {}", e);
+                            record(&stats_capture, |s| s.record_failure("storage"));
+                            return Ok(ToolError::from(&e).into_response(format!("Error storing capture: {}", e)));
+                        }
+                        json!({
+                            "type": "resource_link",
+                            "uri": format!("{}{}", CAPTURE_RESOURCE_PREFIX, capture_id),
+                            "name": resource_file_name(&capture_id),
+                            "mimeType": result.mime_type
+                        })
+                    } else {
+                        json!({
+                            "type": "image",
+                            "data": result.image_base64(),
+                            "mimeType": result.mime_type
+                        })
+                    };
+
                     Ok(json!({
                         "content": [
-                            {
-                                "type": "image",
-                                "data": result.image_data,
-                                "mimeType": result.mime_type
-                            },
+                            image_content,
                             {
                                 "type": "text",
                                 "text": format!(
@@ -218,184 +1102,4409 @@ impl WebcamMcpServer {
                             }
                         ],
                         "metadata": {
+                            "capture_id": capture_id,
                             "width": result.width,
                             "height": result.height,
                             "camera_index": result.camera_index,
                             "timestamp": result.timestamp,
-                            "mime_type": result.mime_type
+                            "mime_type": result.mime_type,
+                            "enhancement": result.enhancement,
+                            "white_balance": result.white_balance,
+                            "privacy_masked_regions": result.privacy_masked_regions,
+                            "face_blur": result.face_blur,
+                            "rotation": result.rotation,
+                            "quality": result.quality,
+                            "camera_name": result.camera_name,
+                            "backend": result.backend,
+                            "encoder": result.encoder,
+                            "encode_ms": result.encode_ms,
+                            "saved_path": saved_path
                         }
                     }))
                 }
                 Err(e) => {
                     error!("Failed to capture image: {}", e);
+                    record(&stats_capture, |s| s.record_failure(e.kind()));
+                    Ok(capture_error_response(&e))
+                }
+            }
+        })?;
+
+        // Register scan_codes handler
+        server.register_tool_handler("scan_codes", move |params: Value| -> Result<Value, MCPError> {
+            debug!("Handling scan_codes request with params: {}", params);
+
+            let camera_index = params.get("camera_index")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32);
+            let include_image = params.get("include_image")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let options = match parse_capture_options(&params) {
+                Ok(options) => options,
+                Err(message) => {
+                    record(&stats_scan, |s| s.record_failure("invalid_params"));
+                    return Ok(invalid_params_response(&message));
+                }
+            };
+
+            let frame = {
+                let mut manager = webcam_manager_scan.lock()
+                    .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam manager lock: {}", e)))?;
+                let started = Instant::now();
+                match manager.capture_frame(camera_index, &options) {
+                    Ok(frame) => {
+                        record(&stats_scan, |s| s.record_capture(frame.camera_index, Some(started.elapsed())));
+                        frame
+                    }
+                    Err(e) => {
+                        error!("Failed to capture frame for scan_codes: {}", e);
+                        record(&stats_scan, |s| s.record_failure(e.kind()));
+                        return Ok(capture_error_response(&e));
+                    }
+                }
+            };
+
+            match barcode::scan(&frame.image) {
+                Ok(codes) => {
+                    info!("Decoded {} code(s) from camera {}", codes.len(), frame.camera_index);
+                    let text = if codes.is_empty() {
+                        "No QR codes or barcodes found in frame".to_string()
+                    } else {
+                        codes.iter()
+                            .map(|code| format!("{}: {}", code.symbology, code.payload))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    };
+
+                    let mut content = vec![json!({ "type": "text", "text": text })];
+                    if include_image {
+                        let annotated = barcode::annotate(&frame.image, &codes);
+                        let encoded = match (crate::webcam::CapturedFrame { image: annotated, ..frame.clone() }).encode() {
+                            Ok(encoded) => encoded,
+                            Err(e) => {
+                                error!("Failed to encode annotated frame: {}", e);
+                                record(&stats_scan, |s| s.record_failure(e.kind()));
+                                return Ok(ToolError::from(&e).into_response(format!("Error encoding the annotated frame: {}", e)));
+                            }
+                        };
+                        record(&stats_scan, |s| s.record_encoded(encoded.image_bytes.len()));
+                        content.insert(0, json!({
+                            "type": "image",
+                            "data": encoded.image_base64(),
+                            "mimeType": encoded.mime_type
+                        }));
+                    }
+
                     Ok(json!({
-                        "content": [{
-                            "type": "text",
-                            "text": format!("Error capturing image: {}", e)
-                        }],
-                        "error": e.to_string()
+                        "content": content,
+                        "codes": codes,
+                        "found": !codes.is_empty(),
+                        "metadata": {
+                            "camera_index": frame.camera_index,
+                            "width": frame.image.width(),
+                            "height": frame.image.height(),
+                            "timestamp": frame.timestamp
+                        }
                     }))
                 }
+                Err(e) => {
+                    error!("Failed to decode codes: {}", e);
+                    record(&stats_scan, |s| s.record_failure("barcode"));
+                    Ok(ToolError::from(&e).into_response(format!("Error scanning codes: {}", e)))
+                }
             }
         })?;
 
-        // Register get_camera_info handler
-        server.register_tool_handler("get_camera_info", move |_params: Value| -> Result<Value, MCPError> {
-            debug!("Handling get_camera_info request");
-            
-            let manager = webcam_manager_info.lock()
-                .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam manager lock: {}", e)))?;
-            
-            let current_camera = manager.get_current_camera_info();
-            
-            match manager.list_cameras() {
-                Ok(cameras) => {
-                    let info = json!({
-                        "available_cameras": cameras,
-                        "current_camera": current_camera,
-                        "total_cameras": cameras.len()
-                    });
+        // Register capture_clip handler; cancellable between frames
+        server.register_cancellable_tool_handler("capture_clip", move |params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+            debug!("Handling capture_clip request with params: {}", params);
+
+            let camera_index = params.get("camera_index")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32);
+            let frames = params.get("frames").and_then(|v| v.as_u64()).unwrap_or(10);
+            let interval_ms = params.get("interval_ms").and_then(|v| v.as_u64()).unwrap_or(200);
+            let format = params.get("format").and_then(|v| v.as_str()).unwrap_or("gif");
+            let options = match parse_capture_options(&params) {
+                Ok(options) => options,
+                Err(message) => {
+                    record(&stats_clip, |s| s.record_failure("invalid_params"));
+                    return Ok(invalid_params_response(&message));
+                }
+            };
+
+            let duration_ms = interval_ms.saturating_mul(frames.saturating_sub(1));
+            let invalid = if format != "gif" && format != "mjpeg" {
+                Some(format!("Invalid format '{}': expected gif or mjpeg", format))
+            } else if frames == 0 || frames > MAX_CLIP_FRAMES {
+                Some(format!("frames must be between 1 and {}", MAX_CLIP_FRAMES))
+            } else if duration_ms > MAX_CLIP_DURATION_MS {
+                Some(format!("Clip duration {} ms exceeds the {} ms limit", duration_ms, MAX_CLIP_DURATION_MS))
+            } else {
+                None
+            };
+            if let Some(message) = invalid {
+                record(&stats_clip, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response(&message));
+            }
+            let default_width = if format == "gif" { 480 } else { 0 };
+            let max_width = params.get("max_width")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32)
+                .unwrap_or(default_width);
+
+            // Frames are scaled and encoded in parallel while the rest are captured
+            let gif = format == "gif";
+            let encode = move |frame: webcam::CapturedFrame| -> Result<EncodedClipFrame, webcam::WebcamError> {
+                let image = image_processing::limit_width(&frame.image, max_width).unwrap_or(frame.image);
+                let data = if gif {
+                    ClipFrameData::Gif(image_processing::gif_frame(&image, interval_ms as u32)?)
+                } else {
+                    ClipFrameData::Jpeg(image_processing::encode_jpeg(&image, frame.quality)?.0)
+                };
+                Ok(EncodedClipFrame { camera_index: frame.camera_index, dimensions: image.dimensions(), data })
+            };
+            // The manager is locked for each frame, not across the waits between them
+            let captured = WebcamManager::capture_sequence_shared(
+                &webcam_manager_clip,
+                camera_index,
+                &options,
+                frames as u32,
+                std::time::Duration::from_millis(interval_ms),
+                cancel,
+                encode,
+            );
+            let captured = match captured {
+                Ok(captured) => captured,
+                Err(webcam::WebcamError::Cancelled) => return Ok(cancelled_response("Clip capture")),
+                Err(e) => {
+                    error!("Failed to capture clip: {}", e);
+                    record(&stats_clip, |s| s.record_failure(e.kind()));
+                    return Ok(ToolError::from(&e).into_response(format!("Error capturing clip: {}", e)));
+                }
+            };
 
+            let camera = captured.first().map(|f| f.camera_index).unwrap_or(0);
+            let frame_count = captured.len();
+            let (width, height) = captured.first().map(|f| f.dimensions).unwrap_or((0, 0));
+
+            let mut gif_frames = Vec::new();
+            let mut mjpeg = Vec::new();
+            for frame in captured {
+                match frame.data {
+                    ClipFrameData::Gif(frame) => gif_frames.push(frame),
+                    ClipFrameData::Jpeg(bytes) => mjpeg.extend_from_slice(&bytes),
+                }
+            }
+            let (bytes, mime_type, extension) = if gif {
+                let bytes = match image_processing::write_gif(&gif_frames, width, height) {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        error!("Failed to encode clip: {}", e);
+                        record(&stats_clip, |s| s.record_failure("image_processing"));
+                        let e = webcam::WebcamError::from(e);
+                        return Ok(ToolError::from(&e).into_response(format!("Error encoding the clip: {}", e)));
+                    }
+                };
+                (bytes, "image/gif", "gif")
+            } else {
+                (mjpeg, "video/x-motion-jpeg", "mjpeg")
+            };
+
+            record(&stats_clip, |s| {
+                s.record_capture(camera, None);
+                s.record_encoded(bytes.len());
+            });
+
+            let metadata = json!({
+                "camera_index": camera,
+                "frames": frame_count,
+                "interval_ms": interval_ms,
+                "width": width,
+                "height": height,
+                "format": format,
+                "mime_type": mime_type,
+                "size_bytes": bytes.len(),
+                "timestamp": chrono::Utc::now().to_rfc3339()
+            });
+
+            if format == "gif" && bytes.len() <= CLIP_INLINE_LIMIT_BYTES {
+                info!("Captured {}-frame GIF clip ({} bytes) from camera {}", frame_count, bytes.len(), camera);
+                return Ok(json!({
+                    "content": [
+                        {
+                            "type": "image",
+                            "data": general_purpose::STANDARD.encode(&bytes),
+                            "mimeType": mime_type
+                        },
+                        {
+                            "type": "text",
+                            "text": format!("Captured {}-frame {}x{} clip from camera {}", frame_count, width, height, camera)
+                        }
+                    ],
+                    "metadata": metadata
+                }));
+            }
+
+            match capture_store.save("clip", extension, &bytes) {
+                Ok(path) => {
+                    info!("Saved {}-frame clip to {}", frame_count, path.display());
                     Ok(json!({
                         "content": [{
                             "type": "text",
-                            "text": format!("Camera info: {} total cameras, current: {:?}", 
-                                          cameras.len(), current_camera)
+                            "text": format!("Saved {}-frame {}x{} clip ({} bytes) from camera {} to {}",
+                                            frame_count, width, height, bytes.len(), camera, path.display())
                         }],
-                        "camera_info": info
+                        "path": path.display().to_string(),
+                        "metadata": metadata
                     }))
                 }
                 Err(e) => {
-                    error!("Failed to get camera info: {}", e);
+                    error!("Failed to save clip: {}", e);
+                    record(&stats_clip, |s| s.record_failure("storage"));
+                    Ok(ToolError::from(&e).into_response(format!("Error saving clip: {}", e)))
+                }
+            }
+        })?;
+
+        // Register set_camera_defaults handler
+        server.register_tool_handler("set_camera_defaults", move |params: Value| -> Result<Value, MCPError> {
+            debug!("Handling set_camera_defaults request with params: {}", params);
+
+            let camera_index = match params.get("camera_index").and_then(|v| v.as_u64()) {
+                Some(index) => index as u32,
+                None => return Ok(invalid_params_response("Missing required parameter 'camera_index'")),
+            };
+            let options = match parse_capture_options(&params) {
+                Ok(options) => options,
+                Err(message) => return Ok(invalid_params_response(&message)),
+            };
+            let mut manager = webcam_manager_defaults.lock()
+                .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam manager lock: {}", e)))?;
+
+            // The backend can only be chosen in server configuration, so keep it
+            let profile = CameraProfile {
+                width: options.resolution.map(|(w, _)| w),
+                height: options.resolution.map(|(_, h)| h),
+                rotation: options.rotation,
+                quality: options.quality,
+                warmup_frames: options.warmup_frames,
+                backend: manager.camera_profile(camera_index).backend,
+            };
+
+            match manager.set_camera_profile(camera_index, profile) {
+                Ok(()) => {
+                    let effective = manager.effective_profile(camera_index);
                     Ok(json!({
                         "content": [{
                             "type": "text",
-                            "text": format!("Error getting camera info: {}", e)
+                            "text": format!("Updated default settings for camera {}", camera_index)
                         }],
-                        "error": e.to_string()
+                        "camera_index": camera_index,
+                        "profile": effective
                     }))
                 }
+                Err(e) => Ok(ToolError::from(&e).into_response(e.to_string())),
             }
         })?;
 
-        Ok(())
-    }
+        // Register set_default_camera handler
+        server.register_tool_handler("set_default_camera", move |params: Value| -> Result<Value, MCPError> {
+            debug!("Handling set_default_camera request with params: {}", params);
 
-    fn register_shodan_tools(&self, server: &mut Server<StdioTransport>) -> Result<(), MCPError> {
-        let shodan_client_search = self.shodan_client.clone().unwrap();
-        let shodan_client_capture = self.shodan_client.clone().unwrap();
+            let camera_index = match params.get("camera_index").and_then(|v| v.as_u64()) {
+                Some(index) => index as u32,
+                None => return Ok(invalid_params_response("'camera_index' must be a non-negative integer")),
+            };
+            let mut manager = webcam_manager_default_camera.lock()
+                .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam manager lock: {}", e)))?;
 
-        // Register search_webcams handler
-        server.register_tool_handler("search_webcams", move |params: Value| -> Result<Value, MCPError> {
-            debug!("Handling search_webcams request with params: {}", params);
-            
-            // Parse limit from params (optional)
-            let limit = params.get("limit")
+            let available: Vec<u32> = match manager.list_cameras() {
+                Ok(cameras) => cameras.iter().map(|camera| camera.index).collect(),
+                Err(e) => return Ok(ToolError::from(&e).into_response(format!("Error listing cameras: {}", e))),
+            };
+            if !available.contains(&camera_index) {
+                let listed: Vec<String> = available.iter().map(u32::to_string).collect();
+                let message = format!(
+                    "Camera {} does not exist; available cameras: {}",
+                    camera_index,
+                    if listed.is_empty() { "none".to_string() } else { listed.join(", ") }
+                );
+                return Ok(ToolError::new(ErrorCode::CameraNotFound, message.clone())
+                    .with_details(json!({ "camera_index": camera_index, "available_indices": available }))
+                    .into_response(message));
+            }
+
+            let previous = manager.default_camera();
+            manager.set_default_camera(camera_index);
+            info!("Default camera changed from {} to {}", previous, camera_index);
+            Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": format!("Default camera is now {} (was {})", camera_index, previous)
+                }],
+                "default_camera": camera_index,
+                "previous_default_camera": previous
+            }))
+        })?;
+
+        // Register self_test handler
+        server.register_tool_handler("self_test", move |params: Value| -> Result<Value, MCPError> {
+            debug!("Handling self_test request with params: {}", params);
+
+            let camera_index = params.get("camera_index")
                 .and_then(|v| v.as_u64())
                 .map(|v| v as u32);
 
-            // Create a runtime for async execution
-            let rt = tokio::runtime::Runtime::new()
-                .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+            let mut manager = webcam_manager_test.lock()
+                .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam manager lock: {}", e)))?;
+
+            match manager.self_test(camera_index) {
+                Ok(results) => {
+                    let passed = results.iter().filter(|r| r.passed).count();
+                    info!("Self-test: {}/{} camera(s) passed", passed, results.len());
+                    let mut lines = vec![format!("Self-test: {}/{} camera(s) passed", passed, results.len())];
+                    lines.extend(results.iter().map(|r| match &r.error {
+                        None => format!("camera {} ({}): PASS, open {} ms, frame {} ms",
+                                        r.index, r.name, r.open_ms.unwrap_or(0), r.frame_ms.unwrap_or(0)),
+                        Some(e) => format!("camera {} ({}): FAIL, {}", r.index, r.name, e),
+                    }));
 
-            match rt.block_on(shodan_client_search.search_webcams(limit)) {
-                Ok(webcams) => {
-                    info!("Found {} remote webcams via Shodan", webcams.len());
                     Ok(json!({
                         "content": [{
                             "type": "text",
-                            "text": format!("Found {} remote webcam(s) via Shodan search", webcams.len())
+                            "text": lines.join("\n")
                         }],
-                        "webcams": webcams,
-                        "total": webcams.len()
+                        "results": results,
+                        "passed": passed,
+                        "total": results.len()
                     }))
                 }
                 Err(e) => {
-                    error!("Failed to search webcams via Shodan: {}", e);
-                    Ok(json!({
-                        "content": [{
-                            "type": "text",
-                            "text": format!("Error searching webcams via Shodan: {}", e)
-                        }],
-                        "error": e.to_string()
-                    }))
+                    error!("Self-test failed: {}", e);
+                    Ok(ToolError::from(&e).into_response(format!("Error running self-test: {}", e)))
                 }
             }
         })?;
 
-        // Register capture_remote_image handler
-        server.register_tool_handler("capture_remote_image", move |params: Value| -> Result<Value, MCPError> {
-            debug!("Handling capture_remote_image request with params: {}", params);
-            
-            // Parse webcam URL from params
-            let webcam_url = params.get("url")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| MCPError::Protocol("Missing required parameter 'url'".to_string()))?;
-
-            // Create a temporary RemoteWebcam struct for the fetch operation
-            let webcam = RemoteWebcam {
-                ip: params.get("ip").and_then(|v| v.as_str()).unwrap_or("unknown").to_string(),
-                port: params.get("port").and_then(|v| v.as_u64()).unwrap_or(80) as u16,
-                url: webcam_url.to_string(),
-                hostname: None,
-                location: None,
-                org: None,
-                product: None,
-                last_seen: chrono::Utc::now().to_rfc3339(),
-                access_type: WebcamAccessType::HTTP,
-            };
+        // Register benchmark_camera handler; cancellable between frames
+        server.register_cancellable_tool_handler("benchmark_camera", move |params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+            debug!("Handling benchmark_camera request with params: {}", params);
 
-            // Create a runtime for async execution
-            let rt = tokio::runtime::Runtime::new()
-                .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+            let camera_index = params.get("camera_index")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32);
+            let seconds = params.get("duration_seconds")
+                .and_then(|v| v.as_f64())
+                .unwrap_or(3.0);
+            if !(seconds > 0.0 && seconds <= MAX_BENCHMARK_SECONDS) {
+                return Ok(invalid_params_response(&format!(
+                    "duration_seconds must be greater than 0 and at most {}", MAX_BENCHMARK_SECONDS)));
+            }
+            let duration = std::time::Duration::from_secs_f64(seconds);
 
-            match rt.block_on(shodan_client_capture.fetch_webcam_image(&webcam)) {
-                Ok(image_bytes) => {
-                    let image_data = general_purpose::STANDARD.encode(&image_bytes);
-                    info!("Successfully captured remote image from {}", webcam_url);
+            // The benchmark itself runs for `duration`, so the timeout starts after it
+            let timeout = duration + webcam::capture_timeout();
+            let cancel = cancel.clone();
+            let result = WebcamManager::with_timeout(&webcam_manager_benchmark, timeout, move |manager| {
+                manager.benchmark_cancellable(camera_index, duration, &cancel)
+            });
 
-                    Ok(json!({
-                        "content": [
-                            {
-                                "type": "image",
-                                "data": image_data,
-                                "mimeType": "image/jpeg"
-                            },
-                            {
-                                "type": "text",
-                                "text": format!("Captured image from remote webcam: {}", webcam_url)
-                            }
-                        ],
-                        "metadata": {
-                            "source": "remote_webcam",
-                            "url": webcam_url,
-                            "size_bytes": image_bytes.len(),
-                            "timestamp": chrono::Utc::now().to_rfc3339()
-                        }
-                    }))
-                }
-                Err(e) => {
-                    error!("Failed to capture remote image from {}: {}", webcam_url, e);
+            match result {
+                Ok(report) => {
+                    info!("Benchmarked camera {}: {:.1} fps over {} frames",
+                          report.camera_index, report.fps, report.frames);
                     Ok(json!({
                         "content": [{
                             "type": "text",
-                            "text": format!("Error capturing remote image from {}: {}", webcam_url, e)
+                            "text": format!(
+                                "Camera {}: {:.1} fps ({} frames in {} ms, {} dropped) at {}x{} {}",
+                                report.camera_index, report.fps, report.frames, report.duration_ms,
+                                report.dropped_frames, report.width, report.height, report.pixel_format)
                         }],
-                        "error": e.to_string()
+                        "benchmark": report
                     }))
                 }
+                Err(webcam::WebcamError::Cancelled) => Ok(cancelled_response("Benchmark")),
+                Err(e) => {
+                    error!("Benchmark failed: {}", e);
+                    Ok(ToolError::from(&e).into_response(format!("Error benchmarking camera: {}", e)))
+                }
             }
         })?;
 
-        Ok(())
+        // Register get_camera_info handler
+        server.register_tool_handler("get_camera_info", move |_params: Value| -> Result<Value, MCPError> {
+            debug!("Handling get_camera_info request");
+            
+            let manager = webcam_manager_info.lock()
+                .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam manager lock: {}", e)))?;
+            
+            let current_camera = manager.get_current_camera_info();
+            let current_text = match &current_camera {
+                Some(camera) => format!("camera {} ({}), {} frame(s) captured since {}",
+                                        camera.index, camera.name, camera.frames_captured, camera.opened_at),
+                None => "no camera is currently open".to_string(),
+            };
+
+            match manager.list_cameras() {
+                Ok(cameras) => {
+                    let profiles: std::collections::HashMap<String, _> = cameras.iter()
+                        .map(|camera| (camera.index.to_string(), manager.effective_profile(camera.index)))
+                        .collect();
+                    let info = json!({
+                        "available_cameras": cameras,
+                        "current_camera": current_camera,
+                        "default_camera": manager.default_camera(),
+                        "total_cameras": cameras.len(),
+                        "profiles": profiles
+                    });
+
+                    Ok(json!({
+                        "content": [{
+                            "type": "text",
+                            "text": format!("Camera info: {} total cameras, default: camera {}, current: {}",
+                                          cameras.len(), manager.default_camera(), current_text)
+                        }],
+                        "camera_info": info
+                    }))
+                }
+                Err(e) => {
+                    error!("Failed to get camera info: {}", e);
+                    Ok(ToolError::from(&e).into_response(format!("Error getting camera info: {}", e)))
+                }
+            }
+        })?;
+
+        Ok(())
+    }
+
+    fn register_history_tools(&self, server: &mut Server) -> Result<(), MCPError> {
+        let capture_history_list = Arc::clone(&self.capture_history);
+        let capture_history_get = Arc::clone(&self.capture_history);
+        let capture_history_compare = Arc::clone(&self.capture_history);
+        let webcam_manager_compare = Arc::clone(&self.webcam_manager);
+        let stats_compare = Arc::clone(&self.stats);
+
+        // Register list_captures handler
+        server.register_tool_handler("list_captures", move |_params: Value| -> Result<Value, MCPError> {
+            debug!("Handling list_captures request");
+
+            let history = capture_history_list.lock()
+                .map_err(|e| MCPError::Protocol(format!("Failed to acquire capture history lock: {}", e)))?;
+
+            let captures = history.list();
+            Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": format!("{} capture(s) in history ({} bytes)", captures.len(), history.total_bytes())
+                }],
+                "captures": captures,
+                "total": captures.len()
+            }))
+        })?;
+
+        // Register get_capture handler
+        server.register_tool_handler("get_capture", move |params: Value| -> Result<Value, MCPError> {
+            debug!("Handling get_capture request with params: {}", params);
+
+            let capture_id = match params.get("capture_id").and_then(|v| v.as_str()) {
+                Some(capture_id) => capture_id,
+                None => return Ok(invalid_params_response("Missing required parameter 'capture_id'")),
+            };
+
+            let history = capture_history_get.lock()
+                .map_err(|e| MCPError::Protocol(format!("Failed to acquire capture history lock: {}", e)))?;
+
+            match history.get(capture_id) {
+                Some(stored) => {
+                    let result = &stored.result;
+                    Ok(json!({
+                        "content": [
+                            {
+                                "type": "image",
+                                "data": result.image_base64(),
+                                "mimeType": result.mime_type
+                            },
+                            {
+                                "type": "text",
+                                "text": format!(
+                                    "Capture {}: {}x{} image from camera {} at {}",
+                                    stored.id, result.width, result.height, result.camera_index, result.timestamp
+                                )
+                            }
+                        ],
+                        "metadata": {
+                            "capture_id": stored.id,
+                            "width": result.width,
+                            "height": result.height,
+                            "camera_index": result.camera_index,
+                            "timestamp": result.timestamp,
+                            "mime_type": result.mime_type,
+                            "enhancement": result.enhancement,
+                            "white_balance": result.white_balance,
+                            "privacy_masked_regions": result.privacy_masked_regions,
+                            "face_blur": result.face_blur,
+                            "rotation": result.rotation,
+                            "quality": result.quality
+                        }
+                    }))
+                }
+                None => {
+                    warn!("Capture {} not found in history", capture_id);
+                    Ok(capture_not_found_response(capture_id))
+                }
+            }
+        })?;
+
+        // Register compare_captures handler
+        server.register_tool_handler("compare_captures", move |params: Value| -> Result<Value, MCPError> {
+            debug!("Handling compare_captures request with params: {}", params);
+
+            let capture_id = match params.get("capture_id").and_then(|v| v.as_str()) {
+                Some(capture_id) => capture_id,
+                None => {
+                    record(&stats_compare, |s| s.record_failure("invalid_params"));
+                    return Ok(invalid_params_response("Missing required parameter 'capture_id'"));
+                }
+            };
+            let other_capture_id = params.get("other_capture_id").and_then(|v| v.as_str());
+            let capture_new = params.get("capture_new").and_then(|v| v.as_bool()).unwrap_or(false);
+            let threshold = params.get("threshold")
+                .and_then(|v| v.as_u64())
+                .map(|v| v.min(255) as u8)
+                .unwrap_or(image_processing::DEFAULT_DIFF_THRESHOLD);
+            let include_diff_image = params.get("include_diff_image")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+
+            if other_capture_id.is_some() == capture_new {
+                record(&stats_compare, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response(
+                    "Provide exactly one of 'other_capture_id' or 'capture_new: true'"
+                ));
+            }
+
+            let (first, second) = {
+                let history = capture_history_compare.lock()
+                    .map_err(|e| MCPError::Protocol(format!("Failed to acquire capture history lock: {}", e)))?;
+                let lookup = |id: &str| {
+                    history.get(id).cloned().ok_or_else(|| {
+                        record(&stats_compare, |s| s.record_failure("capture_not_found"));
+                        capture_not_found_response(id)
+                    })
+                };
+                let first = match lookup(capture_id) {
+                    Ok(stored) => stored,
+                    Err(response) => return Ok(response),
+                };
+                let second = match other_capture_id.map(lookup).transpose() {
+                    Ok(stored) => stored,
+                    Err(response) => return Ok(response),
+                };
+                (first, second)
+            };
+
+            // Either the second stored capture or a fresh frame, stored in history too
+            let second = match second {
+                Some(second) => second,
+                None => {
+                    let camera_index = params.get("camera_index")
+                        .and_then(|v| v.as_u64())
+                        .map(|v| v as u32)
+                        .unwrap_or(first.result.camera_index);
+                    let mut manager = webcam_manager_compare.lock()
+                        .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam manager lock: {}", e)))?;
+                    let started = Instant::now();
+                    let result = match manager.capture_image(Some(camera_index)) {
+                        Ok(result) => result,
+                        Err(e) => {
+                            error!("Failed to capture comparison frame: {}", e);
+                            record(&stats_compare, |s| s.record_failure(e.kind()));
+                            return Ok(capture_error_response(&e));
+                        }
+                    };
+                    drop(manager);
+                    record(&stats_compare, |s| {
+                        s.record_capture(result.camera_index, Some(started.elapsed()));
+                        s.record_encoded(result.image_bytes.len());
+                    });
+
+                    let id = capture_history_compare.lock()
+                        .map_err(|e| MCPError::Protocol(format!("Failed to acquire capture history lock: {}", e)))?
+                        .insert(result.clone());
+                    crate::capture_history::StoredCapture { id, result }
+                }
+            };
+
+            let decoded = first.result.decode_image()
+                .and_then(|a| second.result.decode_image().map(|b| (a, b)));
+            let (mut image_a, mut image_b) = match decoded {
+                Ok(images) => images,
+                Err(e) => {
+                    error!("Failed to decode captures for comparison: {}", e);
+                    record(&stats_compare, |s| s.record_failure(e.kind()));
+                    return Ok(ToolError::from(&e).into_response(format!("Error decoding captures: {}", e)));
+                }
+            };
+
+            let original_dimensions = json!({
+                first.id.clone(): [image_a.width(), image_a.height()],
+                second.id.clone(): [image_b.width(), image_b.height()]
+            });
+            let rescaled = image_processing::match_dimensions(&mut image_a, &mut image_b);
+            let report = image_processing::diff(&image_a, &image_b, threshold);
+
+            let mut text = format!(
+                "{:.2}% of pixels changed between {} and {}",
+                report.changed_fraction * 100.0, first.id, second.id
+            );
+            if let Some(region) = &report.changed_region {
+                text.push_str(&format!(
+                    "; changed region {}x{} at ({}, {})",
+                    region.width, region.height, region.x, region.y
+                ));
+            }
+            if rescaled {
+                text.push_str("; dimensions differed, the smaller capture was scaled up");
+            }
+
+            let mut content = vec![json!({ "type": "text", "text": text })];
+            if include_diff_image {
+                let visual = image_processing::diff_image(&image_a, &image_b, threshold);
+                let frame = crate::webcam::CapturedFrame::new(visual, second.result.camera_index);
+                let encoded = match frame.encode() {
+                    Ok(encoded) => encoded,
+                    Err(e) => {
+                        error!("Failed to encode diff image: {}", e);
+                        record(&stats_compare, |s| s.record_failure(e.kind()));
+                        return Ok(ToolError::from(&e).into_response(format!("Error encoding the diff image: {}", e)));
+                    }
+                };
+                record(&stats_compare, |s| s.record_encoded(encoded.image_bytes.len()));
+                content.insert(0, json!({
+                    "type": "image",
+                    "data": encoded.image_base64(),
+                    "mimeType": encoded.mime_type
+                }));
+            }
+
+            Ok(json!({
+                "content": content,
+                "comparison": {
+                    "capture_id": first.id,
+                    "other_capture_id": second.id,
+                    "threshold": threshold,
+                    "rescaled": rescaled,
+                    "original_dimensions": original_dimensions,
+                    "report": report
+                }
+            }))
+        })?;
+
+        Ok(())
+    }
+
+    fn register_storage_tools(&self, server: &mut Server) -> Result<(), MCPError> {
+        let capture_store = self.capture_store.clone();
+
+        // Register get_storage_info handler
+        server.register_tool_handler("get_storage_info", move |_params: Value| -> Result<Value, MCPError> {
+            debug!("Handling get_storage_info request");
+
+            match capture_store.usage() {
+                Ok(usage) => Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": format!("Storage at {}: {} file(s), {} bytes",
+                                        capture_store.root().display(), usage.file_count, usage.total_bytes)
+                    }],
+                    "storage": {
+                        "directory": capture_store.root().display().to_string(),
+                        "usage": usage,
+                        "retention": capture_store.policy()
+                    }
+                })),
+                Err(e) => {
+                    error!("Failed to read storage info: {}", e);
+                    Ok(ToolError::from(&e).into_response(format!("Error reading storage info: {}", e)))
+                }
+            }
+        })?;
+
+        Ok(())
+    }
+
+    fn register_audit_tools(&self, server: &mut Server) -> Result<(), MCPError> {
+        let audit_log = self.audit_log.clone();
+
+        // Register get_audit_log handler
+        server.register_tool_handler("get_audit_log", move |params: Value| -> Result<Value, MCPError> {
+            debug!("Handling get_audit_log request");
+
+            let since = match params.get("since").and_then(|v| v.as_str()).map(chrono::DateTime::parse_from_rfc3339) {
+                Some(Ok(since)) => Some(since),
+                Some(Err(e)) => return Ok(invalid_params_response(&format!("Invalid 'since' timestamp, expected RFC 3339: {}", e))),
+                None => None,
+            };
+            let limit = params.get("limit")
+                .and_then(|v| v.as_u64())
+                .map(|limit| (limit as usize).clamp(1, audit::MAX_AUDIT_ENTRIES))
+                .unwrap_or(audit::DEFAULT_AUDIT_ENTRIES);
+
+            let excerpt = match audit_log.read(since, limit) {
+                Ok(excerpt) => excerpt,
+                Err(e) => {
+                    error!("Failed to read the audit log: {}", e);
+                    return Ok(ToolError::new(ErrorCode::StorageError, e.clone())
+                        .into_response(format!("Error reading the audit log: {}", e)));
+                }
+            };
+
+            let text = match audit_log.path() {
+                None => "Audit logging is not enabled; set audit_log in the configuration file".to_string(),
+                Some(path) => format!(
+                    "{} of {} audit entries from {}{}",
+                    excerpt.entries.len(), excerpt.matched, path.display(),
+                    if excerpt.chain_intact { "" } else { " - WARNING: the hash chain is broken, the log has been modified" }
+                ),
+            };
+            Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": text
+                }],
+                "audit": {
+                    "enabled": audit_log.is_enabled(),
+                    "path": audit_log.path().map(|path| path.display().to_string()),
+                    "entries": excerpt.entries,
+                    "matched": excerpt.matched,
+                    "chain_intact": excerpt.chain_intact
+                }
+            }))
+        })?;
+
+        Ok(())
+    }
+
+    fn register_consent_tools(&self, server: &mut Server) -> Result<(), MCPError> {
+        let consent = self.consent.clone();
+
+        // Register grant_consent handler
+        server.register_tool_handler("grant_consent", move |params: Value| -> Result<Value, MCPError> {
+            debug!("Handling grant_consent request");
+
+            let Some(code) = params.get("code").and_then(|v| v.as_str()) else {
+                return Ok(invalid_params_response("Missing code"));
+            };
+            match consent.grant(code) {
+                Ok(grant) => Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": format!("Consent given for {} cameras until {}",
+                                        if grant.scope == ConsentScope::Local { "local" } else { "remote" }, grant.expires_at)
+                    }],
+                    "consent": grant
+                })),
+                Err(e) => Ok(invalid_params_response(&e)),
+            }
+        })?;
+
+        Ok(())
+    }
+
+    fn register_stats_tools(&self, server: &mut Server) -> Result<(), MCPError> {
+        let stats = Arc::clone(&self.stats);
+        let shodan_limiter = self.remote_enabled.then(|| Arc::clone(&self.shodan_limiter));
+        let host_cooldown = self.remote_enabled.then(|| Arc::clone(&self.host_cooldown));
+        let credit_budget = self.remote_enabled.then(|| Arc::clone(&self.credit_budget));
+
+        // Register get_server_stats handler; reads counters only
+        server.register_tool_handler("get_server_stats", move |_params: Value| -> Result<Value, MCPError> {
+            debug!("Handling get_server_stats request");
+
+            let mut snapshot = stats.lock()
+                .map_err(|e| MCPError::Protocol(format!("Failed to acquire server stats lock: {}", e)))?
+                .snapshot();
+            snapshot.shodan_requests = shodan_limiter.as_ref().map(|limiter| limiter.stats());
+            snapshot.host_cooldown = host_cooldown.as_ref().map(|cooldown| cooldown.stats());
+            snapshot.shodan_budget = credit_budget.as_ref().map(|budget| budget.status());
+            let average = snapshot.average_capture_ms
+                .map(|ms| format!("{:.1} ms", ms))
+                .unwrap_or_else(|| "n/a".to_string());
+
+            Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": format!(
+                        "Up {}s: {} capture(s), {} failure(s), {} bytes encoded, average capture {}, {} Shodan quer(ies)",
+                        snapshot.uptime_secs, snapshot.captures_total, snapshot.failures_total,
+                        snapshot.bytes_encoded, average, snapshot.shodan_queries
+                    )
+                }],
+                "stats": snapshot
+            }))
+        })?;
+
+        // Register health_check handler. A capture holding the manager
+        // means the server is working, so enumeration is skipped then
+        // rather than waiting.
+        let health_manager = Arc::clone(&self.webcam_manager);
+        let health_stats = Arc::clone(&self.stats);
+        let health_shodan = Arc::clone(&self.shodan_client);
+        server.register_tool_handler("health_check", move |_params: Value| -> Result<Value, MCPError> {
+            debug!("Handling health_check request");
+
+            let manager = match health_manager.try_lock() {
+                Ok(manager) => Some(manager),
+                Err(std::sync::TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+                Err(std::sync::TryLockError::WouldBlock) => None,
+            };
+            let shodan_enabled = health_shodan.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).is_some();
+            let report = {
+                let stats = health_stats.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                HealthReport::collect(manager.as_deref(), &stats, shodan_enabled)
+            };
+
+            let cameras = match (report.cameras_enumerable, report.camera_count) {
+                (Some(_), Some(count)) => format!("{} camera(s)", count),
+                _ => "cameras busy".to_string(),
+            };
+            Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": format!("{:?}: up {}s, {}, {} failed call(s)",
+                                    report.status, report.uptime_secs, cameras, report.failures_total)
+                }],
+                "health": report
+            }))
+        })?;
+
+        let info = ServerInfo {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            git_commit: server_info::GIT_COMMIT.to_string(),
+            features: server_info::enabled_features(),
+            capture_backend: self.webcam_manager.lock()
+                .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam manager lock: {}", e)))?
+                .default_backend_name()
+                .to_string(),
+            shodan_enabled: false,
+            remote_disabled: !self.remote_enabled,
+            tool_timeouts_ms: self.tool_timeouts.iter()
+                .map(|(tool, timeout)| (tool.clone(), timeout.as_millis() as u64))
+                .collect(),
+            rate_limits: self.rate_limits.clone(),
+            max_concurrent_calls: self.max_concurrent_calls,
+            consent: self.consent.status(),
+        };
+        let info_shodan = Arc::clone(&self.shodan_client);
+        let info_consent = self.consent.clone();
+
+        // Register get_server_info handler; the details are fixed at startup
+        // except Shodan, which configure_shodan can turn on and off, and
+        // consent, which comes and goes
+        server.register_tool_handler("get_server_info", move |_params: Value| -> Result<Value, MCPError> {
+            debug!("Handling get_server_info request");
+
+            let mut info = info.clone();
+            info.shodan_enabled = info_shodan.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).is_some();
+            info.consent = info_consent.status();
+
+            let features = if info.features.is_empty() { "none".to_string() } else { info.features.join(", ") };
+            Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": format!(
+                        "{} {} (commit {}), features: {}, capture backend: {}, Shodan {}",
+                        info.name, info.version, info.git_commit, features, info.capture_backend,
+                        if info.remote_disabled {
+                            "and remote access administratively disabled"
+                        } else if info.shodan_enabled {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        }
+                    )
+                }],
+                "server_info": info
+            }))
+        })?;
+
+        Ok(())
+    }
+
+    fn register_resources(&self, server: &mut Server) {
+        let capture_store = self.capture_store.clone();
+        let webcam_manager_list = Arc::clone(&self.webcam_manager);
+        let webcam_manager_read = Arc::clone(&self.webcam_manager);
+        let webcam_manager_watch = Arc::clone(&self.webcam_manager);
+        let snapshots_read = Arc::clone(&self.snapshots);
+        let snapshots_watch = Arc::clone(&self.snapshots);
+        let stats_read = Arc::clone(&self.stats);
+        let stats_watch = Arc::clone(&self.stats);
+        let audit_read = self.audit_log.clone();
+        let audit_watch = self.audit_log.clone();
+        let consent_read = self.consent.clone();
+        let consent_watch = self.consent.clone();
+        let snapshot_interval = self.snapshot_interval;
+        let notifier = server.notifier();
+
+        // Each camera and its snapshot, listed as the cameras currently are
+        server.register_resource_lister(move || {
+            let cameras = webcam_manager_list.lock()
+                .map_err(|e| e.to_string())
+                .and_then(|manager| manager.list_cameras().map_err(|e| e.to_string()));
+            match cameras {
+                Ok(cameras) => cameras.into_iter().flat_map(|camera| [
+                    Resource {
+                        uri: format!("{}{}", CAMERA_RESOURCE_PREFIX, camera.index),
+                        name: format!("Camera {}: {}", camera.index, camera.name),
+                        description: camera.description.clone(),
+                        mime_type: "application/json".to_string(),
+                    },
+                    Resource {
+                        uri: format!("{}{}/snapshot", CAMERA_RESOURCE_PREFIX, camera.index),
+                        name: format!("Camera {} snapshot", camera.index),
+                        description: format!("Latest JPEG from {}", camera.name),
+                        mime_type: "image/jpeg".to_string(),
+                    },
+                ]).collect(),
+                Err(e) => {
+                    warn!("Not listing camera resources: {}", e);
+                    Vec::new()
+                }
+            }
+        });
+
+        // Camera details as JSON, and snapshots, captured on read unless the
+        // last one is recent enough
+        server.register_resource_handler(
+            ResourceTemplate {
+                uri_template: format!("{}{{camera_index}}/snapshot", CAMERA_RESOURCE_PREFIX),
+                name: "Camera snapshot".to_string(),
+                description: format!(
+                    "JPEG from a local camera, captured on read or reused if under {} seconds old",
+                    SNAPSHOT_MAX_AGE.as_secs()
+                ),
+                mime_type: "image/jpeg".to_string(),
+            },
+            move |uri: &str| -> Result<ResourceContents, RpcError> {
+                debug!("Reading resource {}", uri);
+
+                let not_found = || RpcError::new(protocol::RESOURCE_NOT_FOUND, format!("Resource not found: {}", uri));
+                let (camera_index, snapshot) = parse_camera_resource(uri).ok_or_else(not_found)?;
+
+                let internal = |e: String| {
+                    error!("Failed to read resource {}: {}", uri, e);
+                    RpcError::new(protocol::INTERNAL_ERROR, e)
+                };
+                let camera = webcam_manager_read.lock()
+                    .map_err(|e| e.to_string())
+                    .and_then(|manager| manager.list_cameras().map_err(|e| e.to_string()))
+                    .map_err(internal)?
+                    .into_iter()
+                    .find(|camera| camera.index == camera_index)
+                    .ok_or_else(not_found)?;
+
+                if !snapshot {
+                    let bytes = serde_json::to_vec(&camera).map_err(|e| internal(e.to_string()))?;
+                    return Ok(ResourceContents { mime_type: "application/json".to_string(), bytes });
+                }
+                consent_read.check_scope(ConsentScope::Local, uri)
+                    .map_err(|e| RpcError::new(protocol::INVALID_REQUEST, e.message))?;
+                let cached = snapshots_read.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .get(&camera_index)
+                    .filter(|snapshot| snapshot.taken.elapsed() < SNAPSHOT_MAX_AGE)
+                    .map(|snapshot| snapshot.result.clone());
+                let result = match cached {
+                    Some(result) => Ok(result),
+                    None => {
+                        let mut manager = webcam_manager_read.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                        take_snapshot(&mut manager, &snapshots_read, &stats_read, &audit_read, "resources/read", camera_index)
+                    }
+                };
+                match result {
+                    Ok(result) => Ok(ResourceContents { mime_type: result.mime_type, bytes: result.image_bytes.to_vec() }),
+                    Err(webcam::WebcamError::CameraNotFound { .. }) => Err(not_found()),
+                    Err(e) => Err(internal(e.to_string())),
+                }
+            },
+        );
+
+        // A background thread per subscribed camera refreshes its snapshot
+        // and tells the client, which then reads it
+        let watches: Mutex<HashMap<u32, CancellationToken>> = Mutex::new(HashMap::new());
+        server.register_subscription_handler(CAMERA_RESOURCE_PREFIX, move |uri: &str, subscribe: bool| -> Result<(), RpcError> {
+            let not_found = || RpcError::new(protocol::RESOURCE_NOT_FOUND, format!("Resource not found: {}", uri));
+            let camera_index = match parse_camera_resource(uri) {
+                Some((camera_index, true)) => camera_index,
+                Some((_, false)) => {
+                    return Err(RpcError::new(protocol::INVALID_PARAMS, "Only camera snapshots can be subscribed to"));
+                }
+                None => return Err(not_found()),
+            };
+            let mut watches = watches.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+            if !subscribe {
+                if let Some(cancel) = watches.remove(&camera_index) {
+                    cancel.cancel();
+                }
+                return Ok(());
+            }
+
+            let exists = webcam_manager_watch.lock()
+                .map_err(|e| e.to_string())
+                .and_then(|manager| manager.list_cameras().map_err(|e| e.to_string()))
+                .map_err(|e| RpcError::new(protocol::INTERNAL_ERROR, e))?
+                .iter()
+                .any(|camera| camera.index == camera_index);
+            if !exists {
+                return Err(not_found());
+            }
+            consent_watch.check_scope(ConsentScope::Local, uri)
+                .map_err(|e| RpcError::new(protocol::INVALID_REQUEST, e.message))?;
+
+            let cancel = CancellationToken::new();
+            watches.insert(camera_index, cancel.clone());
+            let uri = uri.to_string();
+            let webcam_manager = Arc::clone(&webcam_manager_watch);
+            let snapshots = Arc::clone(&snapshots_watch);
+            let stats = Arc::clone(&stats_watch);
+            let audit_log = audit_watch.clone();
+            let consent = consent_watch.clone();
+            let notifier = notifier.clone();
+            std::thread::spawn(move || {
+                info!("Taking a snapshot of camera {} every {:?}", camera_index, snapshot_interval);
+                while !webcam::sleep_unless_cancelled(snapshot_interval, &cancel) {
+                    // Consent may have run out since the subscription
+                    if !consent.is_granted(ConsentScope::Local) {
+                        debug!("No consent, skipping snapshot of camera {}", camera_index);
+                        continue;
+                    }
+                    // Skip a round while a tool call is using the cameras
+                    // rather than queue up behind it
+                    let Ok(mut manager) = webcam_manager.try_lock() else {
+                        debug!("Cameras busy, skipping snapshot of camera {}", camera_index);
+                        continue;
+                    };
+                    let result = take_snapshot(&mut manager, &snapshots, &stats, &audit_log, "resources/subscribe", camera_index);
+                    drop(manager);
+                    match result {
+                        Ok(_) => notifier.resource_updated(&uri),
+                        Err(e) => warn!("Snapshot of camera {} failed: {}", camera_index, e),
+                    }
+                }
+                info!("Stopped snapshots of camera {}", camera_index);
+            });
+            Ok(())
+        });
+
+        // Captures stored by capture_image with as_resource; they expire with
+        // the capture store's retention policy
+        server.register_resource_handler(
+            ResourceTemplate {
+                uri_template: format!("{}{{capture_id}}", CAPTURE_RESOURCE_PREFIX),
+                name: "Webcam capture".to_string(),
+                description: "JPEG captured by capture_image with as_resource".to_string(),
+                mime_type: "image/jpeg".to_string(),
+            },
+            move |uri: &str| -> Result<ResourceContents, RpcError> {
+                debug!("Reading resource {}", uri);
+
+                let not_found = || RpcError::new(protocol::RESOURCE_NOT_FOUND, format!("Resource not found: {}", uri));
+                let capture_id = uri.strip_prefix(CAPTURE_RESOURCE_PREFIX)
+                    .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+                    .ok_or_else(not_found)?;
+
+                match capture_store.load(&resource_file_name(capture_id)) {
+                    Ok(Some(bytes)) => Ok(ResourceContents { mime_type: "image/jpeg".to_string(), bytes }),
+                    Ok(None) => Err(not_found()),
+                    Err(e) => {
+                        error!("Failed to read resource {}: {}", uri, e);
+                        Err(RpcError::new(protocol::INTERNAL_ERROR, e.to_string()))
+                    }
+                }
+            },
+        );
+    }
+
+    fn register_preview_tools(&self, server: &mut Server) -> Result<(), MCPError> {
+        let preview_start = Arc::clone(&self.preview);
+        let preview_stop = Arc::clone(&self.preview);
+        let webcam_manager = Arc::clone(&self.webcam_manager);
+        let auth_token = self.auth_token.clone();
+        let runtime = tokio::runtime::Handle::current();
+
+        // Register start_preview handler
+        server.register_tool_handler("start_preview", move |params: Value| -> Result<Value, MCPError> {
+            debug!("Handling start_preview request with params: {}", params);
+
+            let mut preview = preview_start.lock()
+                .map_err(|e| MCPError::Protocol(format!("Failed to acquire preview lock: {}", e)))?;
+
+            if let Some(running) = preview.as_ref() {
+                let info = running.info();
+                return Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": format!("Preview already running at {}", info.url)
+                    }],
+                    "preview": info
+                }));
+            }
+
+            let defaults = PreviewConfig::default();
+            let config = PreviewConfig {
+                camera_index: params.get("camera_index").and_then(|v| v.as_u64()).map(|v| v as u32),
+                port: params.get("port").and_then(|v| v.as_u64()).map(|v| v as u16).unwrap_or(defaults.port),
+                fps: params.get("fps").and_then(|v| v.as_u64()).map(|v| v as u32).unwrap_or(defaults.fps),
+                max_viewers: params.get("max_viewers")
+                    .and_then(|v| v.as_u64())
+                    .map(|v| v as usize)
+                    .unwrap_or(defaults.max_viewers),
+                auth_token: auth_token.clone(),
+                ..defaults
+            };
+
+            match PreviewServer::start(Arc::clone(&webcam_manager), config, &runtime) {
+                Ok(server) => {
+                    let info = server.info();
+                    info!("Preview started at {}", info.address);
+                    *preview = Some(server);
+                    Ok(json!({
+                        "content": [{
+                            "type": "text",
+                            "text": format!("Live preview available at {}", info.url)
+                        }],
+                        "preview": info
+                    }))
+                }
+                Err(e) => {
+                    error!("Failed to start preview: {}", e);
+                    Ok(ToolError::new(ErrorCode::PreviewFailed, e.to_string())
+                        .into_response(format!("Error starting preview: {}", e)))
+                }
+            }
+        })?;
+
+        // Register stop_preview handler
+        server.register_tool_handler("stop_preview", move |_params: Value| -> Result<Value, MCPError> {
+            debug!("Handling stop_preview request");
+
+            let mut preview = preview_stop.lock()
+                .map_err(|e| MCPError::Protocol(format!("Failed to acquire preview lock: {}", e)))?;
+
+            let text = match preview.take() {
+                Some(server) => {
+                    let address = server.info().address;
+                    server.stop();
+                    format!("Stopped preview on {}", address)
+                }
+                None => "No preview is running".to_string(),
+            };
+
+            Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": text
+                }]
+            }))
+        })?;
+
+        Ok(())
+    }
+
+    fn register_local_network_tools(&self, server: &mut Server) {
+        let registry = server.tool_registry();
+        let webcam_cache = Arc::clone(&self.webcam_cache);
+        let stats = Arc::clone(&self.stats);
+
+        // Register discover_local_network_cameras
+        let discover_local_network_cameras = Tool {
+            name: "discover_local_network_cameras".to_string(),
+            description: Some("Find cameras on the local network, which Shodan never sees, with ONVIF WS-Discovery and SSDP (UPnP) probes multicast from every network interface. Each camera is listed once with its URL, name and model and what each protocol reported, and added to the webcam cache".to_string()),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some({
+                    let mut props = std::collections::HashMap::new();
+                    props.insert("protocols".to_string(), json!({
+                        "type": "array",
+                        "items": { "type": "string", "enum": LocalProtocol::ALL.map(LocalProtocol::name) },
+                        "minItems": 1,
+                        "description": "Discovery protocols to use (optional, defaults to all of them)"
+                    }));
+                    props.insert("timeout_ms".to_string(), json!({
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": MAX_DISCOVERY_TIMEOUT.as_millis() as u64,
+                        "description": format!("How long to listen for replies, in milliseconds (optional, defaults to {})", DEFAULT_DISCOVERY_TIMEOUT.as_millis())
+                    }));
+                    props
+                }),
+                required: None,
+            },
+        };
+        registry.add_cancellable_tool(discover_local_network_cameras, move |params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+            debug!("Handling discover_local_network_cameras request with params: {}", params);
+
+            let timeout = match params.get("timeout_ms") {
+                None => DEFAULT_DISCOVERY_TIMEOUT,
+                Some(value) => match value.as_u64().map(Duration::from_millis) {
+                    Some(timeout) if !timeout.is_zero() && timeout <= MAX_DISCOVERY_TIMEOUT => timeout,
+                    _ => {
+                        record(&stats, |s| s.record_failure("invalid_params"));
+                        return Ok(invalid_params_response(&format!(
+                            "timeout_ms must be 1 to {}", MAX_DISCOVERY_TIMEOUT.as_millis()
+                        )));
+                    }
+                },
+            };
+
+            let protocols: Vec<LocalProtocol> = match params.get("protocols").and_then(|v| v.as_array()) {
+                None => LocalProtocol::ALL.to_vec(),
+                Some(names) => {
+                    let protocols: Vec<LocalProtocol> = names.iter().filter_map(|name| name.as_str()).filter_map(LocalProtocol::parse).collect();
+                    if protocols.is_empty() || protocols.len() != names.len() {
+                        record(&stats, |s| s.record_failure("invalid_params"));
+                        return Ok(invalid_params_response(&format!(
+                            "protocols must list one or more of {}", LocalProtocol::ALL.map(LocalProtocol::name).join(", ")
+                        )));
+                    }
+                    protocols
+                }
+            };
+
+            // Create a runtime for async execution
+            let rt = tokio::runtime::Runtime::new()
+                .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+
+            let discovered = match block_on_cancellable(&rt, cancel, local_discovery::discover(&protocols, timeout)) {
+                Some(discovered) => discovered,
+                None => return Ok(cancelled_response("Local network discovery")),
+            };
+            let discovery = match discovered {
+                Ok(discovery) => discovery,
+                Err(e) => {
+                    error!("Failed to probe the local network for cameras: {}", e);
+                    record(&stats, |s| s.record_failure("network"));
+                    return Ok(ToolError::new(ErrorCode::NetworkError, e.to_string())
+                        .into_response(format!("Error probing the local network for cameras: {}", e)));
+                }
+            };
+
+            let via = discovery.protocols.iter().map(|protocol| protocol.label()).collect::<Vec<_>>().join(" and ");
+            info!("Found {} camera(s) on the local network via {}", discovery.cameras.len(), via);
+            let webcams: Vec<RemoteWebcam> = discovery.cameras.iter().map(|camera| camera.webcam.clone()).collect();
+            let queries: Vec<String> = discovery.protocols.iter().map(|protocol| protocol.query().to_string()).collect();
+            match webcam_cache.lock() {
+                Ok(mut cache) => {
+                    cache.record_search(&webcams, &queries);
+                    cache.flush();
+                }
+                Err(e) => warn!("Discovered cameras not cached: {}", e),
+            }
+            Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": format!(
+                        "Found {} camera(s) on the local network via {}, listening {} ms on {} interface(s)",
+                        discovery.cameras.len(), via, timeout.as_millis(), discovery.interfaces.len()
+                    )
+                }],
+                "total": discovery.cameras.len(),
+                "cameras": discovery.cameras,
+                "metadata": {
+                    "protocols": discovery.protocols,
+                    "failed_protocols": discovery.failures,
+                    "timeout_ms": timeout.as_millis() as u64,
+                    "interfaces": discovery.interfaces
+                }
+            }))
+        });
+
+        // Register capture_onvif_snapshot
+        let client = match self.shodan_client_settings.build("") {
+            Ok(client) => client,
+            Err(e) => {
+                error!("capture_onvif_snapshot disabled: {}", e);
+                return;
+            }
+        };
+        let stats = Arc::clone(&self.stats);
+        let capture_onvif_snapshot = Tool {
+            name: "capture_onvif_snapshot".to_string(),
+            description: Some("Capture a snapshot from an ONVIF camera, given its device service URL, e.g. from discover_local_network_cameras: ask its media service for a profile's snapshot URI and fetch the image. A camera without snapshots has the profile's RTSP stream URI reported instead, to capture with capture_remote_image".to_string()),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some({
+                    let mut props = std::collections::HashMap::new();
+                    props.insert("url".to_string(), json!({
+                        "type": "string",
+                        "description": "Device service URL of the camera, e.g. http://192.168.1.64/onvif/device_service"
+                    }));
+                    props.insert("username".to_string(), json!({
+                        "type": "string",
+                        "description": "Username, sent as a WS-Security UsernameToken with the SOAP requests and for HTTP Basic or Digest authentication of the snapshot (optional, with password)"
+                    }));
+                    props.insert("password".to_string(), json!({
+                        "type": "string",
+                        "description": "Password for the camera (optional, with username)"
+                    }));
+                    props.insert("profile".to_string(), json!({
+                        "type": "string",
+                        "description": "Token of the media profile to capture (optional, defaults to the camera's first profile)"
+                    }));
+                    props.insert("accept_invalid_certs".to_string(), json!({
+                        "type": "boolean",
+                        "description": "Accept a self-signed or otherwise invalid HTTPS certificate, after a TLS_ERROR named it; the result says so in tls_unverified (optional, defaults to the server's setting, normally false)"
+                    }));
+                    insert_remote_image_option_schemas(&mut props);
+                    props
+                }),
+                required: Some(vec!["url".to_string()]),
+            },
+        };
+        registry.add_cancellable_tool(capture_onvif_snapshot, move |params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+            let invalid = |message: &str| -> Result<Value, MCPError> {
+                record(&stats, |s| s.record_failure("invalid_params"));
+                Ok(invalid_params_response(message))
+            };
+            let device_url = match params.get("url").and_then(|v| v.as_str()) {
+                Some(url) => url,
+                None => return invalid("Missing required parameter 'url'"),
+            };
+            debug!("Handling capture_onvif_snapshot request for {}", sanitize_url(device_url));
+            let credentials = match credentials_param(&params) {
+                Ok(credentials) => credentials,
+                Err(response) => {
+                    record(&stats, |s| s.record_failure("invalid_params"));
+                    return Ok(response);
+                }
+            };
+            let profile = match params.get("profile") {
+                None => None,
+                Some(value) => match value.as_str().filter(|token| !token.is_empty()) {
+                    Some(token) => Some(token),
+                    None => return invalid("profile must be a non-empty profile token"),
+                },
+            };
+            let options = match parse_remote_image_options(&params) {
+                Ok(options) => options,
+                Err(message) => return invalid(&message),
+            };
+
+            // Create a runtime for async execution
+            let rt = tokio::runtime::Runtime::new()
+                .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+
+            let client = accept_invalid_certs_param(&client, &params);
+            let started = Instant::now();
+            let fetched = match block_on_cancellable(&rt, cancel, client.onvif_snapshot(device_url, credentials.as_ref(), profile)) {
+                Some(fetched) => fetched,
+                None => return Ok(cancelled_response("ONVIF snapshot")),
+            };
+            let snapshot = match fetched {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    error!("Failed to capture an ONVIF snapshot from {}: {}", sanitize_url(device_url), e);
+                    record(&stats, |s| s.record_failure("remote_capture"));
+                    return Ok(ToolError::from(&e).into_response(format!("Error capturing an ONVIF snapshot from {}: {}", device_url, e)));
+                }
+            };
+            let mut metadata = json!({
+                "source": "onvif",
+                "device_url": device_url,
+                "media_url": snapshot.media_url,
+                "profile": snapshot.profile,
+                "profiles": snapshot.profiles,
+                "snapshot_uri": snapshot.snapshot_uri,
+                "stream_uri": snapshot.stream_uri,
+                "captured": snapshot.image.is_some(),
+                "timestamp": chrono::Utc::now().to_rfc3339()
+            });
+            let Some(image) = snapshot.image else {
+                info!("{} offers no snapshot URI for profile {}", sanitize_url(device_url), snapshot.profile.token);
+                let text = match &snapshot.stream_uri {
+                    Some(uri) => format!(
+                        "{} offers no snapshot for profile {}; its RTSP stream is {}, which capture_remote_image can capture from",
+                        device_url, snapshot.profile.token, uri
+                    ),
+                    None => format!("{} offers neither a snapshot nor a stream URI for profile {}", device_url, snapshot.profile.token),
+                };
+                return Ok(json!({
+                    "content": [{ "type": "text", "text": text }],
+                    "metadata": metadata
+                }));
+            };
+
+            let snapshot_uri = snapshot.snapshot_uri.unwrap_or_default();
+            let source_size = image.bytes.len();
+            let source_mime_type = image.mime_type.clone();
+            let processed = match webcam::process_remote_image(image.bytes, &image.mime_type, &options) {
+                Ok(processed) => processed,
+                Err(e) => {
+                    error!("Failed to process the ONVIF snapshot from {}: {}", sanitize_url(&snapshot_uri), e);
+                    record(&stats, |s| s.record_failure("remote_capture"));
+                    return Ok(ToolError::from(&e).into_response(format!("Error processing the snapshot from {}: {}", snapshot_uri, e)));
+                }
+            };
+            record(&stats, |s| s.record_capture(server_stats::REMOTE_CAMERA_KEY, Some(started.elapsed())));
+            info!("Captured the ONVIF snapshot of {} from {}", sanitize_url(device_url), sanitize_url(&snapshot_uri));
+            if let Some(metadata) = metadata.as_object_mut() {
+                metadata.extend([
+                    ("url".to_string(), json!(snapshot_uri)),
+                    ("tls_unverified".to_string(), json!(image.tls_unverified)),
+                    ("mime_type".to_string(), json!(processed.mime_type)),
+                    ("size_bytes".to_string(), json!(processed.bytes.len())),
+                    ("width".to_string(), json!(processed.dimensions.map(|(width, _)| width))),
+                    ("height".to_string(), json!(processed.dimensions.map(|(_, height)| height))),
+                    ("source_mime_type".to_string(), json!(source_mime_type)),
+                    ("source_size_bytes".to_string(), json!(source_size)),
+                    ("source_width".to_string(), json!(processed.source_dimensions.map(|(width, _)| width))),
+                    ("source_height".to_string(), json!(processed.source_dimensions.map(|(_, height)| height))),
+                    ("reencoded".to_string(), json!(processed.reencoded)),
+                    ("passthrough".to_string(), json!(options.passthrough)),
+                    ("stats".to_string(), json!(processed.stats)),
+                ]);
+            }
+            Ok(json!({
+                "content": [
+                    {
+                        "type": "image",
+                        "data": general_purpose::STANDARD.encode(&processed.bytes),
+                        "mimeType": processed.mime_type
+                    },
+                    {
+                        "type": "text",
+                        "text": format!("Captured the snapshot of ONVIF profile {} from {}{}", snapshot.profile.token, snapshot_uri,
+                                        if image.tls_unverified { " (TLS certificate not verified)" } else { "" })
+                    }
+                ],
+                "metadata": metadata
+            }))
+        });
+    }
+
+    fn register_network_scan_tools(&self, server: &mut Server) {
+        let registry = server.tool_registry();
+        let webcam_cache = Arc::clone(&self.webcam_cache);
+        let stats = Arc::clone(&self.stats);
+        let blocklist = self.shodan_client_settings.target_policy().block.clone();
+
+        // Register scan_network_for_cameras
+        let scan_network_for_cameras = Tool {
+            name: "scan_network_for_cameras".to_string(),
+            description: Some(format!("Scan an IPv4 network of up to /{} for cameras: connect to each port of every address, read the HTTP or RTSP answer of each open port and list those that look like a camera, DVR or NVR, classified by make, adding them to the webcam cache. Only scan networks you are allowed to", MAX_SCAN_PREFIX)),
+            input_schema: ToolInputSchema {
+                r#type: "object".to_string(),
+                properties: Some({
+                    let mut props = std::collections::HashMap::new();
+                    props.insert("cidr".to_string(), json!({
+                        "type": "string",
+                        "description": format!("Network to scan, e.g. 192.168.1.0/24; at most a /{}, and a bare address scans that address", MAX_SCAN_PREFIX)
+                    }));
+                    props.insert("ports".to_string(), json!({
+                        "type": "array",
+                        "items": { "type": "integer", "minimum": 1, "maximum": 65535 },
+                        "minItems": 1,
+                        "maxItems": MAX_SCAN_PORTS,
+                        "description": format!("TCP ports to try; 554 and 8554 are spoken to in RTSP, the rest in HTTP (optional, defaults to {:?})", DEFAULT_SCAN_PORTS)
+                    }));
+                    props.insert("concurrency".to_string(), json!({
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": MAX_SCAN_CONCURRENCY,
+                        "description": format!("Connections open at once (optional, defaults to {})", DEFAULT_SCAN_CONCURRENCY)
+                    }));
+                    props.insert("connect_timeout_ms".to_string(), json!({
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": MAX_CONNECT_TIMEOUT.as_millis() as u64,
+                        "description": format!("Time allowed for each connection attempt, in milliseconds (optional, defaults to {})", DEFAULT_CONNECT_TIMEOUT.as_millis())
+                    }));
+                    props
+                }),
+                required: Some(vec!["cidr".to_string()]),
+            },
+        };
+        registry.add_cancellable_tool(scan_network_for_cameras, move |params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+            debug!("Handling scan_network_for_cameras request with params: {}", params);
+
+            let invalid = |message: String| -> Result<Value, MCPError> {
+                record(&stats, |s| s.record_failure("invalid_params"));
+                Ok(invalid_params_response(&message))
+            };
+            let range: ScanRange = match params.get("cidr").and_then(|v| v.as_str()).map(str::parse) {
+                Some(Ok(range)) => range,
+                Some(Err(e)) => return invalid(e),
+                None => return invalid("Missing required parameter: cidr".to_string()),
+            };
+            let ports: Vec<u16> = match params.get("ports") {
+                None => DEFAULT_SCAN_PORTS.to_vec(),
+                Some(value) => {
+                    let listed = value.as_array().map(Vec::as_slice).unwrap_or_default();
+                    let mut ports: Vec<u16> = listed.iter()
+                        .filter_map(|port| port.as_u64().and_then(|port| u16::try_from(port).ok()))
+                        .filter(|port| *port > 0)
+                        .collect();
+                    if ports.is_empty() || ports.len() != listed.len() || ports.len() > MAX_SCAN_PORTS {
+                        return invalid(format!("ports must list 1 to {} port numbers, each 1 to 65535", MAX_SCAN_PORTS));
+                    }
+                    ports.sort_unstable();
+                    ports.dedup();
+                    ports
+                }
+            };
+            let concurrency = match params.get("concurrency") {
+                None => DEFAULT_SCAN_CONCURRENCY,
+                Some(value) => match value.as_u64().and_then(|n| usize::try_from(n).ok()) {
+                    Some(n) if (1..=MAX_SCAN_CONCURRENCY).contains(&n) => n,
+                    _ => return invalid(format!("concurrency must be 1 to {}", MAX_SCAN_CONCURRENCY)),
+                },
+            };
+            let connect_timeout = match params.get("connect_timeout_ms") {
+                None => DEFAULT_CONNECT_TIMEOUT,
+                Some(value) => match value.as_u64().map(Duration::from_millis) {
+                    Some(timeout) if !timeout.is_zero() && timeout <= MAX_CONNECT_TIMEOUT => timeout,
+                    _ => return invalid(format!("connect_timeout_ms must be 1 to {}", MAX_CONNECT_TIMEOUT.as_millis())),
+                },
+            };
+
+            // Addresses on the target blocklist are never connected to
+            let (hosts, blocked): (Vec<_>, Vec<_>) = range.hosts()
+                .into_iter()
+                .partition(|ip| !blocklist.iter().any(|rule| rule.contains(std::net::IpAddr::V4(*ip))));
+            if hosts.is_empty() {
+                record(&stats, |s| s.record_failure("target_blocked"));
+                return Ok(ToolError::new(ErrorCode::TargetBlocked, format!("every address of {} is on the target blocklist", range))
+                    .into_response(format!("Not scanning {}: every address is on the target blocklist", range)));
+            }
+
+            // Create a runtime for async execution
+            let rt = tokio::runtime::Runtime::new()
+                .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+
+            info!("Scanning {} ({} address(es)) on port(s) {:?}", range, hosts.len(), ports);
+            let scan = match block_on_cancellable(&rt, cancel, network_scan::scan(&hosts, &ports, concurrency, connect_timeout)) {
+                Some(scan) => scan,
+                None => return Ok(cancelled_response("Network scan")),
+            };
+            let candidates = scan.candidates();
+            info!("Scan of {} found {} open port(s), {} likely camera(s)", range, scan.open.len(), candidates.len());
+
+            let webcams: Vec<RemoteWebcam> = candidates.iter().map(|candidate| candidate.webcam.clone()).collect();
+            match webcam_cache.lock() {
+                Ok(mut cache) => {
+                    cache.record_search(&webcams, &[format!("{} {}", network_scan::DISCOVERY_QUERY, range)]);
+                    cache.flush();
+                }
+                Err(e) => warn!("Scanned cameras not cached: {}", e),
+            }
+            Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": format!(
+                        "Scanned {} address(es) of {} on {} port(s): {} open, {} likely camera(s)",
+                        scan.hosts, range, ports.len(), scan.open.len(), candidates.len()
+                    )
+                }],
+                "total": candidates.len(),
+                "candidates": candidates,
+                "metadata": {
+                    "cidr": range,
+                    "hosts": scan.hosts,
+                    "blocked_hosts": blocked.len(),
+                    "ports": ports,
+                    "concurrency": concurrency,
+                    "connect_timeout_ms": connect_timeout.as_millis() as u64,
+                    "open_ports": scan.open.len()
+                }
+            }))
+        });
+    }
+
+    fn register_configure_shodan(&self, server: &mut Server) -> Result<(), MCPError> {
+        let registry = server.tool_registry();
+        let shodan_client = Arc::clone(&self.shodan_client);
+        let stats = Arc::clone(&self.stats);
+        let client_settings = self.shodan_client_settings.clone();
+        let discovery_providers = self.discovery_providers.clone();
+        let search_queries = Arc::clone(&self.search_queries);
+        let webcam_cache = Arc::clone(&self.webcam_cache);
+        let capture_store = self.capture_store.clone();
+        let honeypot = Arc::clone(&self.honeypot);
+
+        // Register configure_shodan handler. A key is only stored once
+        // Shodan accepts it, so a typo can't replace a working key.
+        server.register_tool_handler("configure_shodan", move |params: Value| -> Result<Value, MCPError> {
+            debug!("Handling configure_shodan request");
+
+            let remove = params.get("remove").and_then(|v| v.as_bool()).unwrap_or(false);
+            let api_key = params.get("api_key").and_then(|v| v.as_str()).map(str::trim);
+
+            if remove {
+                if api_key.is_some() {
+                    return Ok(invalid_params_response("Pass either 'api_key' or 'remove', not both"));
+                }
+                let had_client = shodan_client.lock()
+                    .map_err(|e| MCPError::Protocol(format!("Failed to acquire Shodan client lock: {}", e)))?
+                    .take()
+                    .is_some();
+                info!("Shodan integration disabled by the client");
+                // Other discovery services keep the tools, searching
+                // without Shodan
+                let keyless = keyless_client(&client_settings, &discovery_providers);
+                let text = match &keyless {
+                    Some(client) => {
+                        register_shodan_tools(&registry, client.clone(), &discovery_providers, &stats, &search_queries, &webcam_cache, &capture_store, &honeypot);
+                        let names: Vec<&str> = discovery_providers.iter().map(|provider| provider.name()).collect();
+                        format!("{}, searches go to {} alone", if had_client { "Shodan disabled" } else { "Shodan was not enabled" }, names.join(", "))
+                    }
+                    None => {
+                        for tool in SHODAN_TOOLS {
+                            registry.remove_tool(tool);
+                        }
+                        let text = if had_client { "Shodan disabled, its tools were removed" } else { "Shodan was not enabled" };
+                        text.to_string()
+                    }
+                };
+                let tools: &[&str] = if keyless.is_some() { &SHODAN_TOOLS } else { &[] };
+                return Ok(json!({
+                    "content": [{ "type": "text", "text": text }],
+                    "shodan_enabled": false,
+                    "tools": tools
+                }));
+            }
+
+            let api_key = match api_key {
+                Some(key) if !key.is_empty() => key,
+                _ => return Ok(invalid_params_response("Pass a non-empty 'api_key', or 'remove': true")),
+            };
+
+            let client = match client_settings.build(api_key) {
+                Ok(client) => client,
+                Err(e) => return Ok(ToolError::from(&e).into_response(format!("Could not set up the Shodan client: {}", e))),
+            };
+            if let Err(response) = check_new_shodan_key(&client, &stats, "configure_shodan")? {
+                return Ok(response);
+            }
+
+            *shodan_client.lock()
+                .map_err(|e| MCPError::Protocol(format!("Failed to acquire Shodan client lock: {}", e)))? = Some(client.clone());
+            register_shodan_tools(&registry, client, &discovery_providers, &stats, &search_queries, &webcam_cache, &capture_store, &honeypot);
+            info!("Shodan integration enabled by the client");
+
+            Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": format!("Shodan enabled, tools available: {}", SHODAN_TOOLS.join(", "))
+                }],
+                "shodan_enabled": true,
+                "tools": SHODAN_TOOLS
+            }))
+        })?;
+
+        Ok(())
+    }
+
+    fn register_reload_credentials(&self, server: &mut Server, key_file: PathBuf) -> Result<(), MCPError> {
+        let registry = server.tool_registry();
+        let shodan_client = Arc::clone(&self.shodan_client);
+        let stats = Arc::clone(&self.stats);
+        let client_settings = self.shodan_client_settings.clone();
+        let discovery_providers = self.discovery_providers.clone();
+        let search_queries = Arc::clone(&self.search_queries);
+        let webcam_cache = Arc::clone(&self.webcam_cache);
+        let capture_store = self.capture_store.clone();
+        let honeypot = Arc::clone(&self.honeypot);
+
+        // Register reload_credentials handler. Like configure_shodan, the
+        // current key stays until the new one is read and accepted.
+        server.register_tool_handler("reload_credentials", move |_params: Value| -> Result<Value, MCPError> {
+            debug!("Handling reload_credentials request");
+
+            let api_key = match read_key_file(&key_file) {
+                Ok(key) => key,
+                Err(e) => {
+                    warn!("Could not reload the Shodan key: {}", e);
+                    record(&stats, |s| s.record_failure("shodan"));
+                    return Ok(ToolError::new(ErrorCode::StorageError, e.clone())
+                        .into_response(format!("Could not reload the Shodan key: {}", e)));
+                }
+            };
+
+            let client = match client_settings.build(api_key) {
+                Ok(client) => client,
+                Err(e) => return Ok(ToolError::from(&e).into_response(format!("Could not set up the Shodan client: {}", e))),
+            };
+            if let Err(response) = check_new_shodan_key(&client, &stats, "the key file")? {
+                return Ok(response);
+            }
+
+            *shodan_client.lock()
+                .map_err(|e| MCPError::Protocol(format!("Failed to acquire Shodan client lock: {}", e)))? = Some(client.clone());
+            register_shodan_tools(&registry, client, &discovery_providers, &stats, &search_queries, &webcam_cache, &capture_store, &honeypot);
+            info!("Shodan key reloaded from {}", key_file.display());
+
+            Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": format!("Shodan key reloaded from {}, tools available: {}", key_file.display(), SHODAN_TOOLS.join(", "))
+                }],
+                "shodan_enabled": true,
+                "tools": SHODAN_TOOLS
+            }))
+        })?;
+
+        Ok(())
     }
 }
 
-impl Clone for WebcamMcpServer {
-    fn clone(&self) -> Self {
+/// Check a key before it replaces the current one. `Ok(Err(response))` is
+/// the tool result reporting why it was not accepted.
+fn check_new_shodan_key(
+    client: &ShodanClient,
+    stats: &Arc<Mutex<ServerStats>>,
+    source: &str,
+) -> Result<Result<(), Value>, MCPError> {
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+    match rt.block_on(tokio::time::timeout(SHODAN_KEY_CHECK_TIMEOUT, client.validate_key())) {
+        Ok(Ok(())) => {
+            record(stats, |s| s.record_shodan_key_check(true));
+            Ok(Ok(()))
+        }
+        Ok(Err(e)) => {
+            warn!("Shodan key from {} was not accepted: {}", source, e);
+            if matches!(e, ShodanError::Unauthorized) {
+                record(stats, |s| s.record_shodan_key_check(false));
+            }
+            record(stats, |s| s.record_failure("shodan"));
+            Ok(Err(ToolError::from(&e).into_response(format!("Shodan key not accepted: {}", e))))
+        }
+        Err(_) => {
+            warn!("Shodan did not answer the key check within {:?}", SHODAN_KEY_CHECK_TIMEOUT);
+            record(stats, |s| s.record_failure("shodan"));
+            Ok(Err(ToolError::new(ErrorCode::NetworkError, "Shodan did not answer the key check")
+                .into_response(format!("Could not check the Shodan key: no answer within {:?}", SHODAN_KEY_CHECK_TIMEOUT))))
+        }
+    }
+}
+
+/// Client without a Shodan key, fetching the webcams `others` find; `None`
+/// when there are no other discovery services
+fn keyless_client(settings: &ShodanClientBuilder, others: &[Arc<dyn WebcamDiscoveryProvider>]) -> Option<ShodanClient> {
+    if others.is_empty() {
+        return None;
+    }
+    settings.build("")
+        .map_err(|e| error!("Remote tools disabled: {}", e))
+        .ok()
+}
+
+/// Offer the Shodan tools, using `client`. Searches and host and account
+/// lookups go to Shodan, if `client` has a key, and to `others`. Replaces
+/// the tools if already offered.
+fn register_shodan_tools(
+    registry: &ToolRegistry,
+    client: ShodanClient,
+    others: &[Arc<dyn WebcamDiscoveryProvider>],
+    stats: &Arc<Mutex<ServerStats>>,
+    search_queries: &Arc<Vec<String>>,
+    webcam_cache: &Arc<Mutex<WebcamCache>>,
+    capture_store: &CaptureStore,
+    honeypot: &Arc<HoneypotGuard>,
+) {
+    let mut providers: Vec<Arc<dyn WebcamDiscoveryProvider>> = Vec::new();
+    if client.has_api_key() {
+        providers.push(Arc::new(client.clone()));
+    }
+    providers.extend(others.iter().cloned());
+    let discovery = Discovery::new(providers);
+    let discovery_search = discovery.clone();
+    let discovery_account = discovery.clone();
+    let discovery_host = discovery;
+    let shodan_client_count = client.clone();
+    let queries_search = Arc::clone(search_queries);
+    let queries_count = Arc::clone(search_queries);
+    let queries_list = Arc::clone(search_queries);
+    let shodan_client_probe = client.clone();
+    let shodan_client_validate = client.clone();
+    let shodan_client_policy = client.clone();
+    let cache_search = Arc::clone(webcam_cache);
+    let cache_validate = Arc::clone(webcam_cache);
+    let cache_list = Arc::clone(webcam_cache);
+    let cache_clear = Arc::clone(webcam_cache);
+    let cache_probe = Arc::clone(webcam_cache);
+    let cache_export = Arc::clone(webcam_cache);
+    let capture_store = capture_store.clone();
+    let shodan_client_capture = client;
+    let stats_search = Arc::clone(stats);
+    let stats_count = Arc::clone(stats);
+    let stats_account = Arc::clone(stats);
+    let stats_host = Arc::clone(stats);
+    let stats_probe = Arc::clone(stats);
+    let stats_validate = Arc::clone(stats);
+    let stats_list = Arc::clone(stats);
+    let stats_export = Arc::clone(stats);
+    let stats_remote = Arc::clone(stats);
+    let stats_honeypot = Arc::clone(stats);
+    let stats_scan = Arc::clone(stats);
+    let stats_scan_status = Arc::clone(stats);
+    let shodan_client_scan = client.clone();
+    let shodan_client_scan_status = client.clone();
+    let shodan_client_search = client.clone();
+    let shodan_client_honeypot = client.clone();
+    let budget_account = Arc::clone(client.credit_budget());
+    let honeypot_search = Arc::clone(honeypot);
+    let honeypot_check = Arc::clone(honeypot);
+    let honeypot_capture = Arc::clone(honeypot);
+
+    // Register search_webcams
+    let search_webcams = Tool {
+        name: "search_webcams".to_string(),
+        description: Some("Search for remote webcams using Shodan and any other discovery service configured, such as Censys, optionally only in one country, city or organization. Each webcam lists the services that found it".to_string()),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some({
+                let mut props = std::collections::HashMap::new();
+                props.insert("limit".to_string(), json!({
+                    "type": "number",
+                    "description": format!("Maximum number of results (optional, defaults to {})", DEFAULT_SEARCH_LIMIT)
+                }));
+                props.insert("country".to_string(), json!({
+                    "type": "string",
+                    "description": "Only webcams in this country, as a two-letter ISO code such as GB (optional)"
+                }));
+                props.insert("city".to_string(), json!({
+                    "type": "string",
+                    "description": "Only webcams in this city (optional)"
+                }));
+                props.insert("org".to_string(), json!({
+                    "type": "string",
+                    "description": "Only webcams whose network belongs to an organization with this in its name (optional)"
+                }));
+                props.insert("vendor".to_string(), json!({
+                    "type": "string",
+                    "description": format!("Only webcams classified as this make, one of {} (optional). Checked against the results, so it may return fewer than limit", vendor_names())
+                }));
+                props.insert("near".to_string(), json!({
+                    "type": "object",
+                    "properties": {
+                        "lat": { "type": "number", "minimum": -90, "maximum": 90 },
+                        "lon": { "type": "number", "minimum": -180, "maximum": 180 }
+                    },
+                    "required": ["lat", "lon"],
+                    "description": "Only webcams within radius_km of this point, nearest first, each with its distance_km (optional, with radius_km). Webcams without coordinates are dropped"
+                }));
+                props.insert("radius_km".to_string(), json!({
+                    "type": "number",
+                    "description": "Distance from near in km, with near"
+                }));
+                props.insert("query_indices".to_string(), json!({
+                    "type": "array",
+                    "items": { "type": "integer", "minimum": 0 },
+                    "description": "Indices of the queries to run, from list_search_queries (optional, defaults to the first ones)"
+                }));
+                props.insert("max_queries".to_string(), json!({
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "Most queries to run, each costing a query credit (optional). Without it, further queries run while results are short of limit"
+                }));
+                props.insert("group_by_host".to_string(), json!({
+                    "type": "boolean",
+                    "description": "Return one entry per IP with its endpoints listed, instead of one entry per IP and port (optional, defaults to false)"
+                }));
+                props.insert("with_honeyscore".to_string(), json!({
+                    "type": "boolean",
+                    "description": format!("Give each entry its Shodan honeypot score, with a honeypot_warning if above the server's threshold; one more request per IP, for at most {} IPs not looked up before (optional, defaults to false, needs a Shodan API key)", MAX_SEARCH_HONEYSCORES)
+                }));
+                props
+            }),
+            required: None,
+        },
+    };
+    registry.add_reporting_tool(search_webcams, move |params: Value, cancel: &CancellationToken, progress: &Progress| -> Result<Value, MCPError> {
+        debug!("Handling search_webcams request with params: {}", params);
+
+        // Parse limit from params (optional)
+        let limit = params.get("limit")
+            .and_then(|v| v.as_u64())
+            .map(|v| v.min(u32::MAX as u64) as u32);
+        if limit == Some(0) {
+            record(&stats_search, |s| s.record_failure("invalid_params"));
+            return Ok(invalid_params_response("limit must be at least 1"));
+        }
+
+        // Checked before any query is sent, so a bad filter costs no credits
+        let text_param = |name: &str| params.get(name).and_then(|v| v.as_str());
+        let filters = match SearchFilters::new(text_param("country"), text_param("city"), text_param("org")) {
+            Ok(filters) => filters,
+            Err(e) => {
+                record(&stats_search, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response(&e));
+            }
+        };
+        let filters = match (vendor_param(&params), near_param(&params)) {
+            (Ok(vendor), Ok(near)) => filters.with_vendor(vendor).with_near(near),
+            (Err(response), _) | (_, Err(response)) => {
+                record(&stats_search, |s| s.record_failure("invalid_params"));
+                return Ok(response);
+            }
+        };
+        let indices: Option<Vec<usize>> = params.get("query_indices")
+            .and_then(|v| v.as_array())
+            .map(|indices| indices.iter().filter_map(|i| i.as_u64()).map(|i| i as usize).collect());
+        let max_queries = params.get("max_queries").and_then(|v| v.as_u64()).map(|v| v as usize);
+        let grouped = params.get("group_by_host").and_then(|v| v.as_bool()).unwrap_or(false);
+        let with_honeyscore = params.get("with_honeyscore").and_then(|v| v.as_bool()).unwrap_or(false);
+        if with_honeyscore && !shodan_client_search.has_api_key() {
+            record(&stats_search, |s| s.record_failure("invalid_params"));
+            return Ok(invalid_params_response("with_honeyscore needs a Shodan API key"));
+        }
+        let plan = match select_queries(&queries_search, indices.as_deref(), max_queries) {
+            Ok(plan) => plan,
+            Err(e) => {
+                record(&stats_search, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response(&e));
+            }
+        };
+
+        // Create a runtime for async execution
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+
+        record(&stats_search, |s| s.record_shodan_query());
+        // Each query's webcams as it completes, for a client with a progressToken
+        let report = |provider: &str, webcams: &[RemoteWebcam]| {
+            progress.report(
+                format!("{} found {} webcam(s)", provider, webcams.len()),
+                json!({ "provider": provider, "webcams": webcams }),
+            );
+        };
+        let searched = match block_on_cancellable(&rt, cancel, discovery_search.search_webcams_reporting(limit, &filters, &plan, &report)) {
+            Some(searched) => searched,
+            None => return Ok(cancelled_response("Webcam search")),
+        };
+        let via = discovery_search.names().join(" and ");
+        match searched {
+            Ok(merged) => {
+                let found = merged.found;
+                let via = merged.providers.join(" and ");
+                info!("Found {} remote webcams via {}", found.webcams.len(), via);
+                match cache_search.lock() {
+                    Ok(mut cache) => {
+                        cache.record_search(&found.webcams, &found.queries);
+                        cache.flush();
+                    }
+                    Err(e) => warn!("Search results not cached: {}", e),
+                }
+                let total = found.webcams.len();
+                let mut metadata = json!({
+                    "filters": filters,
+                    "queries": found.queries,
+                    "group_by_host": grouped,
+                    "reverse_dns": found.reverse_dns,
+                    "providers": merged.providers,
+                    "failed_providers": merged.failures
+                });
+                if with_honeyscore {
+                    let ips = found.webcams.iter().map(|webcam| webcam.ip.as_str());
+                    let lookups = match block_on_cancellable(&rt, cancel, look_up_honeyscores(&shodan_client_search, &honeypot_search, ips)) {
+                        Some(lookups) => lookups,
+                        None => return Ok(cancelled_response("Honeyscore lookup")),
+                    };
+                    metadata["honeyscore"] = lookups;
+                }
+                let honeyscores = with_honeyscore.then_some(honeypot_search.as_ref());
+                let within = match filters.near {
+                    Some(near) => format!(" within {} km ({} outside it dropped)", near.radius_km, found.outside_radius),
+                    None => String::new(),
+                };
+                if grouped {
+                    let hosts = group_by_host(found.webcams);
+                    return Ok(json!({
+                        "content": [{
+                            "type": "text",
+                            "text": format!("Found {} remote webcam(s) on {} host(s){} via {} search", total, hosts.len(), within, via)
+                        }],
+                        "total": total,
+                        "outside_radius": found.outside_radius,
+                        "hosts": with_honeyscores(with_distances(&hosts, filters.near, |host| host.location.as_ref()), honeyscores),
+                        "metadata": metadata
+                    }));
+                }
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": format!("Found {} remote webcam(s){} via {} search", total, within, via)
+                    }],
+                    "total": total,
+                    "outside_radius": found.outside_radius,
+                    "webcams": with_honeyscores(with_distances(&found.webcams, filters.near, |webcam| webcam.location.as_ref()), honeyscores),
+                    "metadata": metadata
+                }))
+            }
+            Err(e) => {
+                error!("Failed to search webcams via {}: {}", via, e);
+                record(&stats_search, |s| s.record_failure("shodan"));
+                Ok(ToolError::from(&e).into_response(format!("Error searching webcams via {}: {}", via, e)))
+            }
+        }
+    });
+
+    // Register count_webcams
+    let count_webcams = Tool {
+        name: "count_webcams".to_string(),
+        description: Some("Count the webcams Shodan would find, with the top countries and organizations, without fetching any; costs no query credits on most plans".to_string()),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some({
+                let mut props = std::collections::HashMap::new();
+                props.insert("query".to_string(), json!({
+                    "type": "string",
+                    "description": "Shodan query to count (optional, defaults to each of the search queries)"
+                }));
+                props.insert("query_indices".to_string(), json!({
+                    "type": "array",
+                    "items": { "type": "integer", "minimum": 0 },
+                    "description": "Indices of the search queries to count, from list_search_queries (optional, not with query)"
+                }));
+                props.insert("country".to_string(), json!({
+                    "type": "string",
+                    "description": "Only count webcams in this country, as a two-letter ISO code such as GB (optional)"
+                }));
+                props.insert("city".to_string(), json!({
+                    "type": "string",
+                    "description": "Only count webcams in this city (optional)"
+                }));
+                props.insert("org".to_string(), json!({
+                    "type": "string",
+                    "description": "Only count webcams whose network belongs to an organization with this in its name (optional)"
+                }));
+                props
+            }),
+            required: None,
+        },
+    };
+    registry.add_cancellable_tool(count_webcams, move |params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+        debug!("Handling count_webcams request with params: {}", params);
+
+        let text_param = |name: &str| params.get(name).and_then(|v| v.as_str());
+        let filters = match SearchFilters::new(text_param("country"), text_param("city"), text_param("org")) {
+            Ok(filters) => filters,
+            Err(e) => {
+                record(&stats_count, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response(&e));
+            }
+        };
+        let indices: Option<Vec<usize>> = params.get("query_indices")
+            .and_then(|v| v.as_array())
+            .map(|indices| indices.iter().filter_map(|i| i.as_u64()).map(|i| i as usize).collect());
+        let queries = match (text_param("query").map(str::trim), indices) {
+            (Some(_), Some(_)) => Err("Pass either query or query_indices, not both".to_string()),
+            (Some(""), None) => Err("query must not be empty".to_string()),
+            (Some(query), None) => Ok(vec![query.to_string()]),
+            (None, indices) => select_queries(&queries_count, indices.as_deref(), None).map(|plan| plan.queries),
+        };
+        let queries = match queries {
+            Ok(queries) => queries,
+            Err(e) => {
+                record(&stats_count, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response(&e));
+            }
+        };
+
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+
+        record(&stats_count, |s| s.record_shodan_query());
+        let counted = match block_on_cancellable(&rt, cancel, shodan_client_count.count_webcams(&queries, &filters)) {
+            Some(counted) => counted,
+            None => return Ok(cancelled_response("Shodan count")),
+        };
+        match counted {
+            Ok(counts) => {
+                let summary: Vec<String> = counts.iter().map(|c| format!("{}: {}", c.query, c.total)).collect();
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": format!("Shodan result counts:\n{}", summary.join("\n"))
+                    }],
+                    "counts": counts,
+                    "metadata": {
+                        "filters": filters
+                    }
+                }))
+            }
+            Err(e) => {
+                error!("Failed to count webcams via Shodan: {}", e);
+                record(&stats_count, |s| s.record_failure("shodan"));
+                Ok(ToolError::from(&e).into_response(format!("Error counting webcams via Shodan: {}", e)))
+            }
+        }
+    });
+
+    // Register list_search_queries
+    let list_search_queries = Tool {
+        name: "list_search_queries".to_string(),
+        description: Some("List the Shodan queries search_webcams chooses from, with the indices to pass as its query_indices".to_string()),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some(std::collections::HashMap::new()),
+            required: None,
+        },
+    };
+    registry.add_tool(list_search_queries, move |_params: Value| -> Result<Value, MCPError> {
+        debug!("Handling list_search_queries request");
+
+        let queries: Vec<Value> = queries_list.iter()
+            .enumerate()
+            .map(|(index, query)| json!({ "index": index, "query": query }))
+            .collect();
+        Ok(json!({
+            "content": [{
+                "type": "text",
+                "text": format!("{} search queries; by default the first {} share the limit and the rest run while results are short",
+                                queries.len(), DEFAULT_MAX_QUERIES.min(queries.len()))
+            }],
+            "queries": queries,
+            "default_max_queries": DEFAULT_MAX_QUERIES
+        }))
+    });
+
+    // Register get_target_policy
+    let get_target_policy = Tool {
+        name: "get_target_policy".to_string(),
+        description: Some("Show which remote webcam hosts capture_remote_image, probe_webcam and validate_webcams may fetch from: the allowlist, blocklist, private address rule, schemes and redirect limit. Use it to explain a TARGET_BLOCKED error".to_string()),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some(std::collections::HashMap::new()),
+            required: None,
+        },
+    };
+    registry.add_tool(get_target_policy, move |_params: Value| -> Result<Value, MCPError> {
+        debug!("Handling get_target_policy request");
+
+        let policy = shodan_client_policy.target_policy();
+        let list = |rules: &[TargetRule], empty: &str| {
+            if rules.is_empty() {
+                empty.to_string()
+            } else {
+                rules.iter().map(TargetRule::to_string).collect::<Vec<_>>().join(", ")
+            }
+        };
+        Ok(json!({
+            "content": [{
+                "type": "text",
+                "text": format!(
+                    "Remote webcams may be fetched from: {}. Never fetched from: {}. Private addresses are {}",
+                    list(&policy.allow, "any public host"),
+                    list(&policy.block, "no blocked hosts"),
+                    if policy.allow_private { "allowed" } else { "refused unless in an allowlisted range" }
+                )
+            }],
+            "target_policy": {
+                "allowlist": policy.allow,
+                "blocklist": policy.block,
+                "allow_private": policy.allow_private,
+                "schemes": ALLOWED_SCHEMES,
+                "max_redirects": MAX_REDIRECTS
+            }
+        }))
+    });
+
+    // Register shodan_account_info
+    let shodan_account_info = Tool {
+        name: "shodan_account_info".to_string(),
+        description: Some("Plan and remaining query and scan credits of the Shodan account in use, and of any other discovery service's, such as Censys, with what is left of the server's own hourly and daily budget of Shodan requests; costs no credits".to_string()),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some(std::collections::HashMap::new()),
+            required: None,
+        },
+    };
+    registry.add_cancellable_tool(shodan_account_info, move |_params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+        debug!("Handling shodan_account_info request");
+
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+        let fetched = match block_on_cancellable(&rt, cancel, discovery_account.credits()) {
+            Some(fetched) => fetched,
+            None => return Ok(cancelled_response("Account lookup")),
+        };
+
+        // One failed service doesn't hide the others' credits
+        let mut accounts = Vec::new();
+        let mut failures = Vec::new();
+        let mut first_error = None;
+        for (provider, credits) in fetched {
+            match credits {
+                Ok(credits) => accounts.push(credits),
+                Err(e) => {
+                    error!("Failed to look up the {} account: {}", provider, e);
+                    failures.push(ProviderFailure { provider: provider.to_string(), error: e.to_string() });
+                    first_error.get_or_insert((provider, e));
+                }
+            }
+        }
+        if accounts.is_empty() {
+            record(&stats_account, |s| s.record_failure("shodan"));
+            let (provider, e) = first_error.unwrap_or((crate::shodan::SOURCE, ShodanError::NoApiKey));
+            return Ok(ToolError::from(&e).into_response(format!("Error looking up the {} account: {}", provider, e)));
+        }
+        let mut lines: Vec<String> = accounts.iter()
+            .map(|account| {
+                let scans = account.scan_credits.map(|scans| format!(" and {} scan credits", scans)).unwrap_or_default();
+                format!("{} {} plan: {} query credits{} left", account.provider, account.plan.as_deref().unwrap_or("unknown"), account.query_credits, scans)
+            })
+            .collect();
+        let budget = budget_account.status();
+        if budget.limited {
+            let left = |remaining: Option<u32>| remaining.map_or("unlimited".to_string(), |n| n.to_string());
+            lines.push(format!("Server budget: {} Shodan request(s) left this hour, {} today",
+                               left(budget.hour.remaining), left(budget.day.remaining)));
+        }
+        Ok(json!({
+            "content": [{ "type": "text", "text": lines.join("\n") }],
+            "account": accounts[0],
+            "accounts": accounts,
+            "failed_providers": failures,
+            "budget": budget
+        }))
+    });
+
+    // Register shodan_host_info
+    let shodan_host_info = Tool {
+        name: "shodan_host_info".to_string(),
+        description: Some("Everything Shodan knows about one host, such as a webcam found by search_webcams: open ports, hostnames, organization, location, tags and known vulnerabilities. Other discovery services, such as Censys, are asked if Shodan has nothing".to_string()),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some({
+                let mut props = std::collections::HashMap::new();
+                props.insert("ip".to_string(), json!({
+                    "type": "string",
+                    "description": "IPv4 or IPv6 address of the host"
+                }));
+                props.insert("history".to_string(), json!({
+                    "type": "boolean",
+                    "description": "Include services Shodan saw in the past but no longer does (optional, defaults to false)"
+                }));
+                props
+            }),
+            required: Some(vec!["ip".to_string()]),
+        },
+    };
+    registry.add_cancellable_tool(shodan_host_info, move |params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+        debug!("Handling shodan_host_info request with params: {}", params);
+
+        // Checked here so a malformed address costs no query credits
+        let ip = match params.get("ip").and_then(|v| v.as_str()).map(|ip| ip.trim().parse::<std::net::IpAddr>()) {
+            Some(Ok(ip)) => ip.to_string(),
+            Some(Err(_)) => {
+                record(&stats_host, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response("ip must be an IPv4 or IPv6 address"));
+            }
+            None => {
+                record(&stats_host, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response("Missing required parameter 'ip'"));
+            }
+        };
+        let history = params.get("history").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+
+        record(&stats_host, |s| s.record_shodan_query());
+        let fetched = match block_on_cancellable(&rt, cancel, discovery_host.host(&ip, history)) {
+            Some(fetched) => fetched,
+            None => return Ok(cancelled_response("Host lookup")),
+        };
+        match fetched {
+            Ok((source, host)) => Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": format!("{} (from {}): {} open port(s), {} tag(s), {} known vulnerability(ies)",
+                                    host.ip, source, host.ports.len(), host.tags.len(), host.vulns.len())
+                }],
+                "host": host,
+                "source": source
+            })),
+            Err(e) => {
+                let via = discovery_host.names().join(" or ");
+                error!("Failed to look up host {} on {}: {}", ip, via, e);
+                record(&stats_host, |s| s.record_failure("shodan"));
+                Ok(ToolError::from(&e).into_response(format!("Error looking up host {} on {}: {}", ip, via, e)))
+            }
+        }
+    });
+
+    // Register check_honeypot
+    let check_honeypot = Tool {
+        name: "check_honeypot".to_string(),
+        description: Some("Shodan's honeypot score of a host, such as a webcam found by search_webcams, from 0.0 to 1.0, and whether it is above the server's threshold for a likely honeypot. A score already looked up is reused; otherwise one Shodan request, needing an API key".to_string()),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some({
+                let mut props = std::collections::HashMap::new();
+                props.insert("ip".to_string(), json!({
+                    "type": "string",
+                    "description": "IPv4 or IPv6 address of the host"
+                }));
+                props
+            }),
+            required: Some(vec!["ip".to_string()]),
+        },
+    };
+    registry.add_cancellable_tool(check_honeypot, move |params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+        debug!("Handling check_honeypot request with params: {}", params);
+
+        let ip = match params.get("ip").and_then(|v| v.as_str()).map(|ip| ip.trim().trim_matches(['[', ']']).parse::<std::net::IpAddr>()) {
+            Some(Ok(ip)) => ip.to_string(),
+            Some(Err(_)) => {
+                record(&stats_honeypot, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response("ip must be an IPv4 or IPv6 address"));
+            }
+            None => {
+                record(&stats_honeypot, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response("Missing required parameter 'ip'"));
+            }
+        };
+
+        let cached = honeypot_check.score(&ip);
+        let score = match cached {
+            Some(score) => score,
+            None => {
+                let rt = tokio::runtime::Runtime::new()
+                    .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+                record(&stats_honeypot, |s| s.record_shodan_query());
+                let looked_up = match block_on_cancellable(&rt, cancel, shodan_client_honeypot.honeyscore(&ip)) {
+                    Some(looked_up) => looked_up,
+                    None => return Ok(cancelled_response("Honeyscore lookup")),
+                };
+                match looked_up {
+                    Ok(score) => {
+                        honeypot_check.record(&ip, score);
+                        score
+                    }
+                    Err(e) => {
+                        error!("Failed to look up the honeyscore of {}: {}", ip, e);
+                        record(&stats_honeypot, |s| s.record_failure("shodan"));
+                        return Ok(ToolError::from(&e).into_response(format!("Error looking up the honeyscore of {}: {}", ip, e)));
+                    }
+                }
+            }
+        };
+        let check = honeypot_check.check(&ip, score);
+        let verdict = if check.likely_honeypot { "likely a honeypot" } else { "not taken for a honeypot" };
+        Ok(json!({
+            "content": [{
+                "type": "text",
+                "text": format!("{} has a honeyscore of {:.2}{}: {} (threshold {:.2})",
+                                check.ip, check.honeyscore, if cached.is_some() { ", looked up before" } else { "" }, verdict, check.threshold)
+            }],
+            "ip": check.ip,
+            "honeyscore": check.honeyscore,
+            "threshold": check.threshold,
+            "likely_honeypot": check.likely_honeypot,
+            "warning": check.warning,
+            "cached": cached.is_some(),
+            "strict": honeypot_check.strict()
+        }))
+    });
+
+    // Register request_shodan_scan
+    let request_shodan_scan = Tool {
+        name: "request_shodan_scan".to_string(),
+        description: Some(format!("Ask Shodan to scan up to {} hosts now, when what it indexed about them, e.g. in shodan_host_info, is out of date. WARNING: costs one Shodan scan credit per address, and plans have few; check shodan_account_info first and only scan when the user asks. Returns a scan id for get_scan_status; results appear in shodan_host_info once the scan is done", MAX_SCAN_TARGETS)),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some({
+                let mut props = std::collections::HashMap::new();
+                props.insert("ips".to_string(), json!({
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "minItems": 1,
+                    "maxItems": MAX_SCAN_TARGETS,
+                    "description": "Public IPv4 or IPv6 addresses to scan, each costing a scan credit"
+                }));
+                props
+            }),
+            required: Some(vec!["ips".to_string()]),
+        },
+    };
+    registry.add_cancellable_tool(request_shodan_scan, move |params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+        debug!("Handling request_shodan_scan request with params: {}", params);
+
+        // Checked here so a bad address costs no scan credits
+        let ips: Vec<&str> = match params.get("ips").and_then(|v| v.as_array()) {
+            Some(ips) => ips.iter().filter_map(|ip| ip.as_str()).collect(),
+            None => {
+                record(&stats_scan, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response("Missing required parameter 'ips'"));
+            }
+        };
+        let ips = match parse_scan_targets(&ips) {
+            Ok(ips) => ips.iter().map(|ip| ip.to_string()).collect::<Vec<_>>(),
+            Err(e) => {
+                record(&stats_scan, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response(&e));
+            }
+        };
+
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+
+        let requested = match block_on_cancellable(&rt, cancel, shodan_client_scan.scan(&ips)) {
+            Some(requested) => requested,
+            None => return Ok(cancelled_response("Scan request")),
+        };
+        match requested {
+            Ok(scan) => {
+                info!("Shodan scan {} requested for {} address(es)", scan.id, scan.count);
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": format!("Shodan scan {} requested for {}; {} scan credit(s) left. Check on it with get_scan_status",
+                                        scan.id, ips.join(", "), scan.credits_left)
+                    }],
+                    "scan": scan,
+                    "ips": ips
+                }))
+            }
+            Err(e) => {
+                error!("Failed to request a Shodan scan of {}: {}", ips.join(", "), e);
+                record(&stats_scan, |s| s.record_failure("shodan"));
+                Ok(ToolError::from(&e).into_response(format!("Error requesting a Shodan scan: {}", e)))
+            }
+        }
+    });
+
+    // Register get_scan_status
+    let get_scan_status = Tool {
+        name: "get_scan_status".to_string(),
+        description: Some("Check on a scan request_shodan_scan started: submitting, queued, processing or done. Costs no credits".to_string()),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some({
+                let mut props = std::collections::HashMap::new();
+                props.insert("id".to_string(), json!({
+                    "type": "string",
+                    "description": "Scan id request_shodan_scan returned"
+                }));
+                props
+            }),
+            required: Some(vec!["id".to_string()]),
+        },
+    };
+    registry.add_cancellable_tool(get_scan_status, move |params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+        debug!("Handling get_scan_status request with params: {}", params);
+
+        let id = match params.get("id").and_then(|v| v.as_str()).map(str::trim) {
+            Some(id) if !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric()) => id,
+            Some(_) => {
+                record(&stats_scan_status, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response("id must be a scan id from request_shodan_scan"));
+            }
+            None => {
+                record(&stats_scan_status, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response("Missing required parameter 'id'"));
+            }
+        };
+
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+
+        let checked = match block_on_cancellable(&rt, cancel, shodan_client_scan_status.scan_status(id)) {
+            Some(checked) => checked,
+            None => return Ok(cancelled_response("Scan status check")),
+        };
+        match checked {
+            Ok(scan) => {
+                let done = scan.status.is_done();
+                let next = if done { "; its results are in shodan_host_info" } else { "" };
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": format!("Shodan scan {} of {} address(es): {}{}", scan.id, scan.count, scan.shodan_status, next)
+                    }],
+                    "scan": scan,
+                    "done": done
+                }))
+            }
+            Err(e) => {
+                error!("Failed to check Shodan scan {}: {}", id, e);
+                record(&stats_scan_status, |s| s.record_failure("shodan"));
+                Ok(ToolError::from(&e).into_response(format!("Error checking Shodan scan {}: {}", id, e)))
+            }
+        }
+    });
+
+    // Register list_remote_webcams
+    let list_remote_webcams = Tool {
+        name: "list_remote_webcams".to_string(),
+        description: Some("List the remote webcams earlier search_webcams calls found, with when they were found and their latest validation, without searching Shodan again".to_string()),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some({
+                let mut props = std::collections::HashMap::new();
+                props.insert("country".to_string(), json!({
+                    "type": "string",
+                    "description": "Only webcams in this country, as a two-letter ISO code such as GB (optional)"
+                }));
+                props.insert("city".to_string(), json!({
+                    "type": "string",
+                    "description": "Only webcams in this city (optional)"
+                }));
+                props.insert("org".to_string(), json!({
+                    "type": "string",
+                    "description": "Only webcams whose network belongs to an organization with this in its name (optional)"
+                }));
+                props.insert("product".to_string(), json!({
+                    "type": "string",
+                    "description": "Only webcams whose product contains this, ignoring case (optional)"
+                }));
+                props.insert("vendor".to_string(), json!({
+                    "type": "string",
+                    "description": format!("Only webcams classified as this make, one of {} (optional)", vendor_names())
+                }));
+                props.insert("near".to_string(), json!({
+                    "type": "object",
+                    "properties": {
+                        "lat": { "type": "number", "minimum": -90, "maximum": 90 },
+                        "lon": { "type": "number", "minimum": -180, "maximum": 180 }
+                    },
+                    "required": ["lat", "lon"],
+                    "description": "Only webcams within radius_km of this point, nearest first, each with its distance_km (optional, with radius_km). Webcams without coordinates are left out"
+                }));
+                props.insert("radius_km".to_string(), json!({
+                    "type": "number",
+                    "description": "Distance from near in km, with near"
+                }));
+                props.insert("reachable_only".to_string(), json!({
+                    "type": "boolean",
+                    "description": "Only webcams validate_webcams last found reachable (optional, defaults to false)"
+                }));
+                props.insert("offset".to_string(), json!({
+                    "type": "integer",
+                    "minimum": 0,
+                    "description": "Matching webcams to skip, for the next page (optional, defaults to 0)"
+                }));
+                props.insert("limit".to_string(), json!({
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": MAX_LIST_LIMIT,
+                    "description": format!("Most webcams to return (optional, defaults to {})", DEFAULT_LIST_LIMIT)
+                }));
+                props
+            }),
+            required: None,
+        },
+    };
+    registry.add_tool(list_remote_webcams, move |params: Value| -> Result<Value, MCPError> {
+        debug!("Handling list_remote_webcams request");
+        let text_param = |name: &str| params.get(name).and_then(|v| v.as_str());
+        let filters = match SearchFilters::new(text_param("country"), text_param("city"), text_param("org")) {
+            Ok(filters) => filters,
+            Err(e) => {
+                record(&stats_list, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response(&e));
+            }
+        };
+        let filters = match (vendor_param(&params), near_param(&params)) {
+            (Ok(vendor), Ok(near)) => filters.with_vendor(vendor).with_near(near),
+            (Err(response), _) | (_, Err(response)) => {
+                record(&stats_list, |s| s.record_failure("invalid_params"));
+                return Ok(response);
+            }
+        };
+        let near = filters.near;
+        let filter = CacheFilter {
+            filters,
+            reachable_only: params.get("reachable_only").and_then(|v| v.as_bool()).unwrap_or(false),
+            product: text_param("product").map(str::trim).filter(|product| !product.is_empty()).map(str::to_string),
+        };
+        let offset = params.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        let limit = params.get("limit").and_then(|v| v.as_u64())
+            .map_or(DEFAULT_LIST_LIMIT, |limit| (limit as usize).clamp(1, MAX_LIST_LIMIT));
+
+        let page = cache_list.lock()
+            .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam cache lock: {}", e)))?
+            .list(&filter, offset, limit);
+        let next_offset = Some(offset + page.webcams.len()).filter(|next| *next < page.total);
+        let text = if page.webcams.is_empty() {
+            format!("No cached webcams to show ({} matching)", page.total)
+        } else {
+            format!("Cached webcams {} to {} of {}", offset + 1, offset + page.webcams.len(), page.total)
+        };
+        Ok(json!({
+            "content": [{ "type": "text", "text": text }],
+            "webcams": with_distances(&page.webcams, near, |cached| cached.webcam.location.as_ref()),
+            "total": page.total,
+            "outside_radius": page.outside_radius,
+            "offset": offset,
+            "next_offset": next_offset
+        }))
+    });
+
+    // Register clear_remote_webcams
+    let clear_remote_webcams = Tool {
+        name: "clear_remote_webcams".to_string(),
+        description: Some("Forget every remote webcam earlier searches found, with their validations".to_string()),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some(std::collections::HashMap::new()),
+            required: None,
+        },
+    };
+    registry.add_tool(clear_remote_webcams, move |_params: Value| -> Result<Value, MCPError> {
+        debug!("Handling clear_remote_webcams request");
+        let mut cache = cache_clear.lock()
+            .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam cache lock: {}", e)))?;
+        let cleared = cache.clear();
+        cache.flush();
+        drop(cache);
+        info!("Cleared {} cached remote webcam(s)", cleared);
+        Ok(json!({
+            "content": [{ "type": "text", "text": format!("Forgot {} cached webcam(s)", cleared) }],
+            "cleared": cleared
+        }))
+    });
+
+    // Register export_webcams
+    let export_webcams = Tool {
+        name: "export_webcams".to_string(),
+        description: Some(format!(
+            "Export every cached remote webcam as a GeoJSON FeatureCollection for a map viewer, a CSV table or JSON, inline when under {} bytes, otherwise saved to disk",
+            EXPORT_INLINE_LIMIT_BYTES
+        )),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some({
+                let mut props = std::collections::HashMap::new();
+                props.insert("format".to_string(), json!({
+                    "type": "string",
+                    "enum": ExportFormat::ALL.iter().map(|format| format.name()).collect::<Vec<_>>(),
+                    "description": "geojson, csv or json. GeoJSON leaves out webcams without coordinates and lists them in without_coordinates"
+                }));
+                props.insert("file_name".to_string(), json!({
+                    "type": "string",
+                    "description": "Save the export in the data directory under this name, prefixed with webcams_ unless it starts with that already, whatever its size; letters, digits, '_', '-' and '.' only, and refused if a file of that name exists (optional)"
+                }));
+                props
+            }),
+            required: Some(vec!["format".to_string()]),
+        },
+    };
+    registry.add_tool(export_webcams, move |params: Value| -> Result<Value, MCPError> {
+        debug!("Handling export_webcams request");
+        let format = match params.get("format").and_then(|v| v.as_str()).unwrap_or_default().parse::<ExportFormat>() {
+            Ok(format) => format,
+            Err(e) => {
+                record(&stats_export, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response(&e));
+            }
+        };
+        let webcams: Vec<RemoteWebcam> = cache_export.lock()
+            .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam cache lock: {}", e)))?
+            .list(&CacheFilter::default(), 0, usize::MAX)
+            .webcams
+            .into_iter()
+            .map(|cached| cached.webcam)
+            .collect();
+        let export = export::export_webcams(&webcams, format);
+        let size_bytes = export.document.len();
+        let left_out = if export.without_coordinates.is_empty() {
+            String::new()
+        } else {
+            format!(", {} without coordinates left out", export.without_coordinates.len())
+        };
+
+        let file_name = params.get("file_name").and_then(|v| v.as_str()).map(str::trim).filter(|name| !name.is_empty());
+        if file_name.is_none() && size_bytes <= EXPORT_INLINE_LIMIT_BYTES {
+            return Ok(json!({
+                "content": [
+                    { "type": "text", "text": format!("Exported {} webcam(s) as {}{}", export.exported, format, left_out) },
+                    { "type": "text", "text": export.document }
+                ],
+                "format": format.name(),
+                "mime_type": format.mime_type(),
+                "exported": export.exported,
+                "size_bytes": size_bytes,
+                "document": export.document,
+                "path": null,
+                "without_coordinates": export.without_coordinates
+            }));
+        }
+
+        let saved = match file_name {
+            Some(file_name) => capture_store.save_new(&export_file_name(file_name), export.document.as_bytes()),
+            None => capture_store.save("webcams", format.extension(), export.document.as_bytes()),
+        };
+        match saved {
+            Ok(path) => {
+                info!("Exported {} webcam(s) as {} to {}", export.exported, format, path.display());
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": format!("Saved {} webcam(s) as {} ({} bytes) to {}{}", export.exported, format, size_bytes, path.display(), left_out)
+                    }],
+                    "format": format.name(),
+                    "mime_type": format.mime_type(),
+                    "exported": export.exported,
+                    "size_bytes": size_bytes,
+                    "document": null,
+                    "path": path.display().to_string(),
+                    "without_coordinates": export.without_coordinates
+                }))
+            }
+            Err(e) => {
+                error!("Failed to save webcam export: {}", e);
+                record(&stats_export, |s| s.record_failure("storage"));
+                Ok(ToolError::from(&e).into_response(format!("Error saving webcam export: {}", e)))
+            }
+        }
+    });
+
+    // Register validate_webcams
+    let validate_webcams = Tool {
+        name: "validate_webcams".to_string(),
+        description: Some("Check many remote webcams at once, by default those the last search_webcams call found, and report for each whether it answers, whether it serves an image, its HTTP status, latency and content type".to_string()),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some({
+                let mut props = std::collections::HashMap::new();
+                props.insert("webcams".to_string(), json!({
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "url": { "type": "string" },
+                            "ip": { "type": "string" },
+                            "port": { "type": "integer" }
+                        },
+                        "required": ["url"]
+                    },
+                    "maxItems": MAX_VALIDATE_WEBCAMS,
+                    "description": "Webcams to check, e.g. entries of search_webcams' webcams, each with at least its url (optional, defaults to the last search's results)"
+                }));
+                props.insert("concurrency".to_string(), json!({
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": MAX_VALIDATE_CONCURRENCY,
+                    "description": format!("Webcams checked at once (optional, defaults to {})", ValidateOptions::default().concurrency)
+                }));
+                props.insert("timeout_seconds".to_string(), json!({
+                    "type": "number",
+                    "minimum": 1,
+                    "maximum": MAX_VALIDATE_TIMEOUT.as_secs(),
+                    "description": format!("Time allowed for each webcam (optional, defaults to {})", ValidateOptions::default().timeout.as_secs())
+                }));
+                props.insert("metadata_only".to_string(), json!({
+                    "type": "boolean",
+                    "description": "Read each image as far as its header and report its width, height and size_bytes (optional, defaults to false)"
+                }));
+                props.insert("thumbnail".to_string(), json!({
+                    "type": "boolean",
+                    "description": format!("Also return a {} pixel wide JPEG thumbnail of each image served, after the text, in the order of results; implies metadata_only. Each image is downloaded whole, up to the download limit (optional, defaults to false)", webcam::THUMBNAIL_WIDTH)
+                }));
+                props
+            }),
+            required: None,
+        },
+    };
+    registry.add_cancellable_tool(validate_webcams, move |params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+        debug!("Handling validate_webcams request");
+        let webcams: Vec<RemoteWebcam> = match params.get("webcams").and_then(|v| v.as_array()) {
+            Some(entries) => {
+                let mut webcams = Vec::with_capacity(entries.len());
+                for entry in entries {
+                    match entry.get("url").and_then(|v| v.as_str()) {
+                        Some(url) => webcams.push(remote_webcam_at(url, entry)),
+                        None => {
+                            record(&stats_validate, |s| s.record_failure("invalid_params"));
+                            return Ok(invalid_params_response("Every entry of 'webcams' needs a 'url'"));
+                        }
+                    }
+                }
+                webcams
+            }
+            None => cache_validate.lock()
+                .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam cache lock: {}", e)))?
+                .last_search(),
+        };
+        if webcams.is_empty() {
+            record(&stats_validate, |s| s.record_failure("invalid_params"));
+            return Ok(invalid_params_response("No webcams to validate: pass 'webcams', or run search_webcams first"));
+        }
+        if webcams.len() > MAX_VALIDATE_WEBCAMS {
+            record(&stats_validate, |s| s.record_failure("invalid_params"));
+            return Ok(invalid_params_response(&format!("At most {} webcams can be validated at once", MAX_VALIDATE_WEBCAMS)));
+        }
+        let defaults = ValidateOptions::default();
+        let flag = |name: &str| params.get(name).and_then(|v| v.as_bool()).unwrap_or(false);
+        let options = ValidateOptions {
+            concurrency: params.get("concurrency").and_then(|v| v.as_u64()).map_or(defaults.concurrency, |n| n as usize),
+            timeout: params.get("timeout_seconds").and_then(|v| v.as_f64())
+                .filter(|seconds| *seconds > 0.0)
+                .map_or(defaults.timeout, |seconds| Duration::from_secs_f64(seconds.min(MAX_VALIDATE_TIMEOUT.as_secs_f64()))),
+            depth: if flag("thumbnail") {
+                ValidateDepth::Thumbnail
+            } else if flag("metadata_only") {
+                ValidateDepth::Metadata
+            } else {
+                ValidateDepth::Sniff
+            },
+        };
+
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+        let validations = match block_on_cancellable(&rt, cancel, shodan_client_validate.validate_webcams(&webcams, &options)) {
+            Some(validations) => validations,
+            None => return Ok(cancelled_response("Webcam validation")),
+        };
+
+        let mut cache = cache_validate.lock()
+            .map_err(|e| MCPError::Protocol(format!("Failed to acquire webcam cache lock: {}", e)))?;
+        let mut thumbnails = Vec::new();
+        let results: Vec<Value> = webcams.iter().zip(&validations).map(|(webcam, validation)| match validation {
+            Ok(validation) => {
+                // Thumbnails go back to the caller, never into the cache
+                let cached = cache.record_validation(&webcam.ip, webcam.port, WebcamValidation { thumbnail: None, ..validation.clone() });
+                if let Some(thumbnail) = &validation.thumbnail {
+                    thumbnails.push(json!({ "type": "text", "text": format!("Thumbnail of {}:", sanitize_url(&webcam.url)) }));
+                    thumbnails.push(json!({ "type": "image", "data": general_purpose::STANDARD.encode(thumbnail), "mimeType": "image/jpeg" }));
+                }
+                json!({
+                    "ip": webcam.ip,
+                    "port": webcam.port,
+                    "url": sanitize_url(&webcam.url),
+                    "cached": cached,
+                    "validation": validation,
+                    "thumbnail": validation.thumbnail.is_some()
+                })
+            }
+            // Not checked, so the cached validation stays as it was
+            Err(e) => json!({
+                "ip": webcam.ip,
+                "port": webcam.port,
+                "url": sanitize_url(&webcam.url),
+                "cached": false,
+                "validation": null,
+                "error": ToolError::from(e)
+            }),
+        }).collect();
+        cache.flush();
+        drop(cache);
+        let checked: Vec<&WebcamValidation> = validations.iter().filter_map(|validation| validation.as_ref().ok()).collect();
+        let reachable = checked.iter().filter(|v| v.reachable).count();
+        let image_served = checked.iter().filter(|v| v.image_served).count();
+        let cooling_down = validations.len() - checked.len();
+        let cooldown_note = if cooling_down > 0 {
+            format!(", {} not checked: fetched from moments ago", cooling_down)
+        } else {
+            String::new()
+        };
+        let mut content = vec![json!({
+            "type": "text",
+            "text": format!("{} of {} webcam(s) reachable, {} serving an image{}",
+                            reachable, checked.len(), image_served, cooldown_note)
+        })];
+        content.extend(thumbnails);
+        Ok(json!({
+            "content": content,
+            "results": results,
+            "summary": {
+                "total": validations.len(),
+                "reachable": reachable,
+                "image_served": image_served,
+                "cooling_down": cooling_down
+            }
+        }))
+    });
+
+    // Register probe_webcam
+    let probe_webcam = Tool {
+        name: "probe_webcam".to_string(),
+        description: Some("Find a snapshot URL of a remote webcam by trying the snapshot paths of common camera makes (Axis, Hikvision, Dahua and others) on its address and port, its own make's first, until one serves an image".to_string()),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some({
+                let mut props = std::collections::HashMap::new();
+                props.insert("ip".to_string(), json!({
+                    "type": "string",
+                    "description": "IPv4 or IPv6 address of the webcam"
+                }));
+                props.insert("port".to_string(), json!({
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": 65535,
+                    "description": "Port of the webcam's web server; 443 and 8443 are tried over HTTPS (optional, defaults to 80)"
+                }));
+                props.insert("product".to_string(), json!({
+                    "type": "string",
+                    "description": "Product as search_webcams reports it, e.g. Hikvision IP Camera, so that make's paths are tried first (optional)"
+                }));
+                props.insert("username".to_string(), json!({
+                    "type": "string",
+                    "description": "Username for a webcam that asks for HTTP Basic or Digest authentication (optional, with password)"
+                }));
+                props.insert("password".to_string(), json!({
+                    "type": "string",
+                    "description": "Password for the webcam (optional, with username)"
+                }));
+                props.insert("accept_invalid_certs".to_string(), json!({
+                    "type": "boolean",
+                    "description": "Accept a self-signed or otherwise invalid HTTPS certificate, after a TLS_ERROR named it; the result says so in tls_unverified (optional, defaults to the server's setting, normally false)"
+                }));
+                props.insert("max_requests".to_string(), json!({
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": MAX_PROBE_REQUESTS,
+                    "description": format!("Most paths to try (optional, defaults to {})", ProbeOptions::default().max_requests)
+                }));
+                props.insert("concurrency".to_string(), json!({
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": MAX_PROBE_CONCURRENCY,
+                    "description": format!("Requests in flight at once (optional, defaults to {})", ProbeOptions::default().concurrency)
+                }));
+                props
+            }),
+            required: Some(vec!["ip".to_string()]),
+        },
+    };
+    registry.add_cancellable_tool(probe_webcam, move |params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+        debug!("Handling probe_webcam request");
+        let ip = match params.get("ip").and_then(|v| v.as_str()).map(|ip| ip.trim().parse::<std::net::IpAddr>()) {
+            Some(Ok(ip)) => ip.to_string(),
+            Some(Err(_)) => {
+                record(&stats_probe, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response("ip must be an IPv4 or IPv6 address"));
+            }
+            None => {
+                record(&stats_probe, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response("Missing required parameter 'ip'"));
+            }
+        };
+        let credentials = match credentials_param(&params) {
+            Ok(credentials) => credentials,
+            Err(response) => {
+                record(&stats_probe, |s| s.record_failure("invalid_params"));
+                return Ok(response);
+            }
+        };
+        let defaults = ProbeOptions::default();
+        let options = ProbeOptions {
+            max_requests: params.get("max_requests").and_then(|v| v.as_u64()).map_or(defaults.max_requests, |n| n as usize),
+            concurrency: params.get("concurrency").and_then(|v| v.as_u64()).map_or(defaults.concurrency, |n| n as usize),
+            ..defaults
+        };
+        let port = params.get("port").and_then(|v| v.as_u64()).unwrap_or(80) as u16;
+        let product = params.get("product").and_then(|v| v.as_str()).map(str::to_string);
+        // Without a product, a webcam a search found keeps the make it was
+        // classified as then, from its banner as well
+        let classification = match product {
+            Some(_) => None,
+            None => cache_probe.lock().ok()
+                .and_then(|cache| cache.get(&ip, port).and_then(|cached| cached.webcam.classification.clone())),
+        };
+        let webcam = RemoteWebcam {
+            url: String::new(),
+            hostname: None,
+            location: None,
+            org: None,
+            product,
+            last_seen: chrono::Utc::now().to_rfc3339(),
+            access_type: WebcamAccessType::HTTP,
+            classification,
+            sources: Vec::new(),
+            ip,
+            port,
+        };
+
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+        let client = accept_invalid_certs_param(&shodan_client_probe, &params);
+        let probed = match block_on_cancellable(&rt, cancel, client.probe_webcam(&webcam, credentials.as_ref(), &options)) {
+            Some(probed) => probed,
+            None => return Ok(cancelled_response("Webcam probe")),
+        };
+        match probed {
+            Ok(probe) => {
+                // A webcam a search found is now known to serve an image
+                let probe_dimensions = image_processing::image_dimensions(&probe.image.bytes);
+                let validation = WebcamValidation {
+                    checked_at: chrono::Utc::now().to_rfc3339(),
+                    reachable: true,
+                    image_served: true,
+                    http_status: Some(200),
+                    latency_ms: Some(probe.latency.as_millis() as u64),
+                    content_type: Some(probe.image.mime_type.clone()),
+                    error: None,
+                    width: probe_dimensions.map(|(width, _)| width),
+                    height: probe_dimensions.map(|(_, height)| height),
+                    size_bytes: Some(probe.image.bytes.len() as u64),
+                    thumbnail: None,
+                };
+                if let Ok(mut cache) = cache_probe.lock() {
+                    if cache.record_validation(&webcam.ip, webcam.port, validation) {
+                        cache.flush();
+                    }
+                }
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": format!("{} serves {} ({} bytes) after {} request(s)",
+                                        probe.url, probe.image.mime_type, probe.image.bytes.len(), probe.attempts)
+                    }],
+                    "probe": {
+                        "url": probe.url,
+                        "latency_ms": probe.latency.as_millis() as u64,
+                        "size_bytes": probe.image.bytes.len(),
+                        "mime_type": probe.image.mime_type,
+                        "attempts": probe.attempts,
+                        "tls_unverified": probe.image.tls_unverified
+                    }
+                }))
+            }
+            Err(e) => {
+                error!("Failed to probe {}:{}: {}", webcam.ip, webcam.port, e);
+                record(&stats_probe, |s| s.record_failure("remote_capture"));
+                Ok(ToolError::from(&e).into_response(format!("Error probing {}:{}: {}", webcam.ip, webcam.port, e)))
+            }
+        }
+    });
+
+    // Register capture_remote_image
+    let capture_remote_image = Tool {
+        name: "capture_remote_image".to_string(),
+        description: Some("Capture image from a remote webcam. With frames, capture several a little apart and say from how much they differ whether the webcam is live or serving a frozen image".to_string()),
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some({
+                let mut props = std::collections::HashMap::new();
+                props.insert("url".to_string(), json!({
+                    "type": "string",
+                    "description": "Webcam URL to capture from"
+                }));
+                props.insert("ip".to_string(), json!({
+                    "type": "string",
+                    "description": "IP address (optional)"
+                }));
+                props.insert("port".to_string(), json!({
+                    "type": "number",
+                    "description": "Port number (optional)"
+                }));
+                props.insert("username".to_string(), json!({
+                    "type": "string",
+                    "description": "Username for a webcam that asks for HTTP Basic or Digest authentication (optional, with password)"
+                }));
+                props.insert("password".to_string(), json!({
+                    "type": "string",
+                    "description": "Password for the webcam (optional, with username)"
+                }));
+                props.insert("accept_invalid_certs".to_string(), json!({
+                    "type": "boolean",
+                    "description": "Accept a self-signed or otherwise invalid HTTPS certificate, after a TLS_ERROR named it; the result says so in tls_unverified (optional, defaults to the server's setting, normally false)"
+                }));
+                props.insert("auto_probe".to_string(), json!({
+                    "type": "boolean",
+                    "description": "Instead of the URL's path, try the snapshot paths of common camera makes on its host and port (or ip and port) and capture from the first that serves an image, as probe_webcam does (optional, defaults to false)"
+                }));
+                insert_remote_image_option_schemas(&mut props);
+                props.insert("metadata_only".to_string(), json!({
+                    "type": "boolean",
+                    "description": "Check the webcam serves an image and report its type, dimensions and size without returning it; only as much as the image's header is downloaded. Cannot be combined with the processing options or frames (optional, defaults to false)"
+                }));
+                props.insert("frames".to_string(), json!({
+                    "type": "integer",
+                    "description": format!("Frames to capture, interval_ms apart, 1-{}; with more than one, consecutive frames are scored by how much they differ and liveness says whether the webcam is live, static or unknown. The host cooldown applies to the first frame only (optional, defaults to 1)", liveness::MAX_FRAMES)
+                }));
+                props.insert("interval_ms".to_string(), json!({
+                    "type": "integer",
+                    "description": format!("Milliseconds between frames, at least {}; the frames may span at most {} seconds (optional, defaults to {})",
+                                           liveness::MIN_FRAME_INTERVAL.as_millis(), liveness::MAX_FRAME_SPAN.as_secs(), liveness::DEFAULT_FRAME_INTERVAL.as_millis())
+                }));
+                props.insert("images".to_string(), json!({
+                    "type": "string",
+                    "enum": ["all", "first_only"],
+                    "description": "Return every frame, or only the first with the scores of the rest (optional, defaults to all)"
+                }));
+                props
+            }),
+            required: Some(vec!["url".to_string()]),
+        },
+    };
+    registry.add_cancellable_tool(capture_remote_image, move |params: Value, cancel: &CancellationToken| -> Result<Value, MCPError> {
+        // Parse webcam URL from params
+        let webcam_url = match params.get("url").and_then(|v| v.as_str()) {
+            Some(url) => url,
+            None => {
+                record(&stats_remote, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response("Missing required parameter 'url'"));
+            }
+        };
+        debug!("Handling capture_remote_image request for {}", sanitize_url(webcam_url));
+        let credentials = match credentials_param(&params) {
+            Ok(credentials) => credentials,
+            Err(response) => {
+                record(&stats_remote, |s| s.record_failure("invalid_params"));
+                return Ok(response);
+            }
+        };
+        let auto_probe = params.get("auto_probe").and_then(|v| v.as_bool()).unwrap_or(false);
+        let options = match parse_remote_image_options(&params) {
+            Ok(options) => options,
+            Err(message) => {
+                record(&stats_remote, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response(&message));
+            }
+        };
+        let metadata_only = params.get("metadata_only").and_then(|v| v.as_bool()).unwrap_or(false);
+        if metadata_only && (options.processes() || options.passthrough) {
+            record(&stats_remote, |s| s.record_failure("invalid_params"));
+            return Ok(invalid_params_response("metadata_only returns no image, so it can't be combined with max_width, thumbnail, grayscale, format, quality, stats or passthrough"));
+        }
+        let frames = params.get("frames").and_then(|v| v.as_u64()).unwrap_or(1).min(u32::MAX as u64) as u32;
+        let interval = params.get("interval_ms").and_then(|v| v.as_u64()).map_or(liveness::DEFAULT_FRAME_INTERVAL, Duration::from_millis);
+        if let Err(message) = liveness::check_frames(frames, interval) {
+            record(&stats_remote, |s| s.record_failure("invalid_params"));
+            return Ok(invalid_params_response(&message));
+        }
+        let first_only = match params.get("images").and_then(|v| v.as_str()) {
+            None | Some("all") => false,
+            Some("first_only") => true,
+            Some(other) => {
+                record(&stats_remote, |s| s.record_failure("invalid_params"));
+                return Ok(invalid_params_response(&format!("Invalid images '{}': expected all or first_only", other)));
+            }
+        };
+        if metadata_only && frames > 1 {
+            record(&stats_remote, |s| s.record_failure("invalid_params"));
+            return Ok(invalid_params_response("metadata_only downloads no image to compare, so it can't be combined with frames"));
+        }
+
+        // Create a temporary RemoteWebcam struct for the fetch operation
+        let webcam = remote_webcam_at(webcam_url, &params);
+
+        // Create a runtime for async execution
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| MCPError::Protocol(format!("Failed to create async runtime: {}", e)))?;
+
+        let client = accept_invalid_certs_param(&shodan_client_capture, &params);
+        let refusal = match block_on_cancellable(&rt, cancel, honeypot_refusal(&client, &honeypot_capture, &webcam.ip)) {
+            Some(refusal) => refusal,
+            None => return Ok(cancelled_response("Honeyscore lookup")),
+        };
+        if let Some((error, text)) = refusal {
+            warn!("{}", text);
+            record(&stats_remote, |s| s.record_failure("remote_capture"));
+            return Ok(error.into_response(text));
+        }
+        let started = Instant::now();
+        if metadata_only {
+            let fetch = async {
+                if auto_probe {
+                    let probe = client.probe_webcam(&webcam, credentials.as_ref(), &ProbeOptions::default()).await?;
+                    Ok::<_, ShodanError>((RemoteImageInfo::of(&probe.image), Some(probe.url)))
+                } else {
+                    Ok((client.fetch_webcam_image_info(&webcam, credentials.as_ref()).await?, None))
+                }
+            };
+            let fetched = match block_on_cancellable(&rt, cancel, fetch) {
+                Some(fetched) => fetched,
+                None => return Ok(cancelled_response("Remote image fetch")),
+            };
+            return match fetched {
+                Ok((info, probed_url)) => {
+                    let webcam_url = probed_url.as_deref().unwrap_or(webcam_url);
+                    record(&stats_remote, |s| s.record_capture(server_stats::REMOTE_CAMERA_KEY, Some(started.elapsed())));
+                    let dimensions = match (info.width, info.height) {
+                        (Some(width), Some(height)) => format!("{}x{}", width, height),
+                        _ => "of unknown size".to_string(),
+                    };
+                    let size = info.size_bytes.map_or_else(|| "size not given".to_string(), |size| format!("{} bytes", size));
+                    Ok(json!({
+                        "content": [{
+                            "type": "text",
+                            "text": format!("{} serves {} {} ({}, {} bytes downloaded){}", webcam_url, info.mime_type, dimensions, size, info.bytes_read,
+                                            if info.tls_unverified { " (TLS certificate not verified)" } else { "" })
+                        }],
+                        "metadata": {
+                            "source": "remote_webcam",
+                            "url": webcam_url,
+                            "probed": probed_url.is_some(),
+                            "tls_unverified": info.tls_unverified,
+                            "mime_type": info.mime_type,
+                            "size_bytes": info.size_bytes,
+                            "width": info.width,
+                            "height": info.height,
+                            "bytes_read": info.bytes_read,
+                            "metadata_only": true,
+                            "reencoded": false,
+                            "passthrough": false,
+                            "timestamp": chrono::Utc::now().to_rfc3339()
+                        }
+                    }))
+                }
+                Err(e) => {
+                    error!("Failed to fetch the image header from {}: {}", sanitize_url(webcam_url), e);
+                    record(&stats_remote, |s| s.record_failure("remote_capture"));
+                    Ok(ToolError::from(&e).into_response(format!("Error fetching the image from {}: {}", webcam_url, e)))
+                }
+            };
+        }
+        let fetch = async {
+            let fetched: Result<(Vec<RemoteImage>, Option<String>), ShodanError> = if auto_probe {
+                // Later frames come from the path the probe found, on the host it claimed
+                let probe = client.probe_webcam(&webcam, credentials.as_ref(), &ProbeOptions::default()).await?;
+                let probed = RemoteWebcam { url: probe.url.clone(), ..webcam.clone() };
+                let images = client.fetch_more_webcam_frames(&probed, credentials.as_ref(), vec![probe.image], frames, interval).await?;
+                Ok((images, Some(probe.url)))
+            } else {
+                let images = client.fetch_webcam_frames(&webcam, credentials.as_ref(), frames, interval).await?;
+                Ok((images, None))
+            };
+            fetched
+        };
+        let fetched = match block_on_cancellable(&rt, cancel, fetch) {
+            Some(fetched) => fetched,
+            None => return Ok(cancelled_response("Remote image fetch")),
+        };
+        match fetched {
+            Ok((images, probed_url)) => {
+                let webcam_url = probed_url.as_deref().unwrap_or(webcam_url);
+                // Scored as fetched, before any processing changes them
+                let report = (frames > 1).then(|| liveness::assess(&images.iter().map(|image| image.bytes.as_slice()).collect::<Vec<_>>()));
+                let tls_unverified = images[0].tls_unverified;
+                let source_size = images[0].bytes.len();
+                let source_mime_type = images[0].mime_type.clone();
+                let returned = if first_only { 1 } else { images.len() };
+                let mut processed_frames = Vec::with_capacity(returned);
+                for image in images.into_iter().take(returned) {
+                    match webcam::process_remote_image(image.bytes, &image.mime_type, &options) {
+                        Ok(processed) => processed_frames.push(processed),
+                        Err(e) => {
+                            error!("Failed to process the image from {}: {}", sanitize_url(webcam_url), e);
+                            record(&stats_remote, |s| s.record_failure("remote_capture"));
+                            return Ok(ToolError::from(&e).into_response(format!("Error processing the image from {}: {}", webcam_url, e)));
+                        }
+                    }
+                }
+                record(&stats_remote, |s| s.record_capture(server_stats::REMOTE_CAMERA_KEY, Some(started.elapsed())));
+                info!("Successfully captured remote image from {}", sanitize_url(webcam_url));
+
+                let tls_note = if tls_unverified { " (TLS certificate not verified)" } else { "" };
+                let text = match &report {
+                    Some(report) => {
+                        let scores: Vec<String> = report.scores.iter()
+                            .map(|score| score.map_or_else(|| "n/a".to_string(), |score| format!("{:.4}", score)))
+                            .collect();
+                        format!("Captured {} frames {} ms apart from remote webcam: {}{}; {} (difference scores {}, threshold {})",
+                                frames, interval.as_millis(), webcam_url, tls_note, report.verdict.as_str(), scores.join(", "), report.threshold)
+                    }
+                    None => format!("Captured image from remote webcam: {}{}", webcam_url, tls_note),
+                };
+                let mut content: Vec<Value> = processed_frames.iter()
+                    .map(|processed| json!({
+                        "type": "image",
+                        "data": general_purpose::STANDARD.encode(&processed.bytes),
+                        "mimeType": processed.mime_type
+                    }))
+                    .collect();
+                content.push(json!({ "type": "text", "text": text }));
+                let processed = &processed_frames[0];
+
+                let mut response = json!({
+                    "content": content,
+                    "metadata": {
+                        "source": "remote_webcam",
+                        "url": webcam_url,
+                        "probed": probed_url.is_some(),
+                        "tls_unverified": tls_unverified,
+                        "mime_type": processed.mime_type,
+                        "size_bytes": processed.bytes.len(),
+                        "width": processed.dimensions.map(|(width, _)| width),
+                        "height": processed.dimensions.map(|(_, height)| height),
+                        "source_mime_type": source_mime_type,
+                        "source_size_bytes": source_size,
+                        "source_width": processed.source_dimensions.map(|(width, _)| width),
+                        "source_height": processed.source_dimensions.map(|(_, height)| height),
+                        "reencoded": processed.reencoded,
+                        "passthrough": options.passthrough,
+                        "metadata_only": false,
+                        "stats": processed.stats,
+                        "frames": frames,
+                        "interval_ms": (frames > 1).then(|| interval.as_millis() as u64),
+                        "images": if first_only { "first_only" } else { "all" },
+                        "timestamp": chrono::Utc::now().to_rfc3339()
+                    }
+                });
+                if let Some(report) = report {
+                    response["liveness"] = json!(report);
+                }
+                Ok(response)
+            }
+            Err(e) => {
+                error!("Failed to capture remote image from {}: {}", sanitize_url(webcam_url), e);
+                record(&stats_remote, |s| s.record_failure("remote_capture"));
+                Ok(ToolError::from(&e).into_response(format!("Error capturing remote image from {}: {}", webcam_url, e)))
+            }
+        }
+    });
+}
+
+/// Assembles a `WebcamMcpServer` from injected parts. Parts that aren't
+/// supplied are created as `WebcamMcpServer::new` would, from the
+/// environment, except Shodan, which is only enabled with a client.
+pub struct WebcamMcpServerBuilder {
+    name: String,
+    version: String,
+    webcam_manager: Option<WebcamManager>,
+    shodan_client: Option<ShodanClient>,
+    discovery_providers: Vec<Arc<dyn WebcamDiscoveryProvider>>,
+    shodan_configurable: bool,
+    remote_enabled: bool,
+    network_scan_enabled: bool,
+    shodan_key_file: Option<PathBuf>,
+    capture_history: Option<CaptureHistory>,
+    capture_store: Option<CaptureStore>,
+    auth_token: Option<AuthToken>,
+    preview_enabled: bool,
+    tool_timeouts: BTreeMap<String, Duration>,
+    rate_limits: BTreeMap<String, RateLimit>,
+    max_concurrent_calls: Option<usize>,
+    snapshot_interval: Duration,
+    audit_log: Option<AuditLog>,
+    consent: Option<ConsentGate>,
+    search_queries: Option<Vec<String>>,
+    webcam_cache_ttl: Duration,
+    webcam_db: Option<PathBuf>,
+    shodan_retry: Option<RetryPolicy>,
+    shodan_requests_per_second: f64,
+    host_cooldown: Duration,
+    honeypot_threshold: f64,
+    honeypot_strict: bool,
+    shodan_max_requests_per_hour: Option<u32>,
+    shodan_max_requests_per_day: Option<u32>,
+    shodan_usage_db: Option<PathBuf>,
+    shodan_client_settings: Option<ShodanClientBuilder>,
+}
+
+impl Default for WebcamMcpServerBuilder {
+    fn default() -> Self {
         Self {
-            webcam_manager: Arc::clone(&self.webcam_manager),
-            shodan_client: self.shodan_client.clone(),
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            webcam_manager: None,
+            shodan_client: None,
+            discovery_providers: Vec::new(),
+            shodan_configurable: true,
+            remote_enabled: true,
+            network_scan_enabled: false,
+            shodan_key_file: None,
+            capture_history: None,
+            capture_store: None,
+            auth_token: None,
+            preview_enabled: true,
+            tool_timeouts: default_tool_timeouts()
+                .into_iter()
+                .map(|(tool, timeout)| (tool.to_string(), timeout))
+                .collect(),
+            rate_limits: default_rate_limits()
+                .into_iter()
+                .map(|(tool, limit)| (tool.to_string(), limit))
+                .collect(),
+            max_concurrent_calls: Some(DEFAULT_MAX_CONCURRENT_CALLS),
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            audit_log: None,
+            search_queries: None,
+            webcam_cache_ttl: DEFAULT_WEBCAM_CACHE_TTL,
+            webcam_db: None,
+            shodan_retry: None,
+            shodan_client_settings: None,
+            shodan_requests_per_second: DEFAULT_SHODAN_REQUESTS_PER_SECOND,
+            host_cooldown: DEFAULT_HOST_COOLDOWN,
+            honeypot_threshold: DEFAULT_HONEYPOT_THRESHOLD,
+            honeypot_strict: false,
+            shodan_max_requests_per_hour: None,
+            shodan_max_requests_per_day: None,
+            shodan_usage_db: None,
+            consent: None,
+        }
+    }
+}
+
+impl WebcamMcpServerBuilder {
+    /// Server name reported in `initialize`
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Server version reported in `initialize`
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    pub fn webcam_manager(mut self, manager: WebcamManager) -> Self {
+        self.webcam_manager = Some(manager);
+        self
+    }
+
+    /// Use a `WebcamManager` with this capture backend
+    pub fn backend(self, backend: Box<dyn CaptureBackend>) -> Self {
+        self.webcam_manager(WebcamManager::with_backend(backend))
+    }
+
+    /// Enable the Shodan tools with this client
+    pub fn shodan_client(mut self, client: ShodanClient) -> Self {
+        self.shodan_client = Some(client);
+        self
+    }
+
+    /// Search this discovery service too, after Shodan. Without a Shodan
+    /// client the remote tools are offered for it alone, webcams being
+    /// fetched by a client without a Shodan key.
+    pub fn discovery_provider(mut self, provider: Arc<dyn WebcamDiscoveryProvider>) -> Self {
+        self.discovery_providers.push(provider);
+        self
+    }
+
+    /// Offer `configure_shodan`, which lets clients enable and disable the
+    /// Shodan tools at runtime (default true)
+    pub fn shodan_configurable(mut self, enabled: bool) -> Self {
+        self.shodan_configurable = enabled;
+        self
+    }
+
+    /// Offer `reload_credentials`, which re-reads the Shodan key from `path`
+    /// so it can be rotated while serving. The file isn't read here: pass
+    /// the key read at startup to `shodan_client`.
+    pub fn shodan_key_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.shodan_key_file = Some(path.into());
+        self
+    }
+
+    /// Allow remote webcam access at all (default true). When false, no
+    /// Shodan client is used, even one given to `shodan_client`, and neither
+    /// the Shodan tools, `capture_remote_image` nor `configure_shodan` are
+    /// offered
+    pub fn remote(mut self, enabled: bool) -> Self {
+        self.remote_enabled = enabled;
+        self
+    }
+
+    /// Offer `scan_network_for_cameras` (default false), which connects to
+    /// every address of the range it is given. Never offered while remote
+    /// access is disabled.
+    pub fn network_scan(mut self, enabled: bool) -> Self {
+        self.network_scan_enabled = enabled;
+        self
+    }
+
+    pub fn capture_history(mut self, history: CaptureHistory) -> Self {
+        self.capture_history = Some(history);
+        self
+    }
+
+    pub fn capture_store(mut self, store: CaptureStore) -> Self {
+        self.capture_store = Some(store);
+        self
+    }
+
+    /// Token the live preview requires instead of one per session
+    pub fn auth_token(mut self, token: AuthToken) -> Self {
+        self.auth_token = Some(token);
+        self
+    }
+
+    /// Offer the live preview tools (default true)
+    pub fn preview(mut self, enabled: bool) -> Self {
+        self.preview_enabled = enabled;
+        self
+    }
+
+    /// Fail calls of `tool` with `TOOL_TIMEOUT` after `timeout`, replacing
+    /// its default
+    pub fn tool_timeout(mut self, tool: impl Into<String>, timeout: Duration) -> Self {
+        self.tool_timeouts.insert(tool.into(), timeout);
+        self
+    }
+
+    /// Fail calls of `tool` with `RATE_LIMITED` beyond `limit`, replacing
+    /// its default
+    pub fn rate_limit(mut self, tool: impl Into<String>, limit: RateLimit) -> Self {
+        self.rate_limits.insert(tool.into(), limit);
+        self
+    }
+
+    /// Fail calls with `RATE_LIMITED` while this many are running, or
+    /// never with `None` (default 4)
+    pub fn max_concurrent_calls(mut self, max: Option<usize>) -> Self {
+        self.max_concurrent_calls = max;
+        self
+    }
+
+    /// Time between snapshots of a camera while its snapshot resource is
+    /// subscribed to (default 5 seconds, at least 1 second)
+    pub fn snapshot_interval(mut self, interval: Duration) -> Self {
+        if interval < MIN_SNAPSHOT_INTERVAL {
+            warn!("Snapshot interval {:?} is below the minimum, using {:?}", interval, MIN_SNAPSHOT_INTERVAL);
+        }
+        self.snapshot_interval = interval.max(MIN_SNAPSHOT_INTERVAL);
+        self
+    }
+
+    /// How long webcams stay in the cache `list_remote_webcams` reads
+    /// after a search last found them (default 24 hours)
+    pub fn webcam_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.webcam_cache_ttl = ttl;
+        self
+    }
+
+    /// Keep the remote webcam cache in this file across restarts (default
+    /// in memory only)
+    pub fn webcam_db(mut self, path: impl Into<PathBuf>) -> Self {
+        self.webcam_db = Some(path.into());
+        self
+    }
+
+    /// Record captures and remote fetches in this audit log (default none)
+    pub fn audit_log(mut self, log: AuditLog) -> Self {
+        self.audit_log = Some(log);
+        self
+    }
+
+    /// Make camera tools wait for the user's consent (default never)
+    pub fn consent(mut self, gate: ConsentGate) -> Self {
+        self.consent = Some(gate);
+        self
+    }
+
+    /// Shodan queries `search_webcams` chooses from (default the built-in
+    /// `DEFAULT_WEBCAM_QUERIES`). An empty list keeps the default.
+    pub fn search_queries(mut self, queries: Vec<String>) -> Self {
+        self.search_queries = Some(queries).filter(|queries| !queries.is_empty());
+        self
+    }
+
+    /// How Shodan requests answered with 429 are retried, by the client
+    /// given to `shodan_client` and any set later (default
+    /// `RetryPolicy::default()`, or the given client's own)
+    pub fn shodan_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.shodan_retry = Some(policy);
+        self
+    }
+
+    /// Connection settings of the Shodan clients `configure_shodan` and
+    /// `reload_credentials` make. The client given to `shodan_client`
+    /// keeps its own.
+    pub fn shodan_client_settings(mut self, settings: ShodanClientBuilder) -> Self {
+        self.shodan_client_settings = Some(settings);
+        self
+    }
+
+    /// Most requests per second sent to the Shodan API by all Shodan
+    /// clients together (default `DEFAULT_SHODAN_REQUESTS_PER_SECOND`).
+    /// Webcam fetches aren't counted. Rates that aren't positive are ignored.
+    pub fn shodan_requests_per_second(mut self, rate: f64) -> Self {
+        if rate.is_finite() && rate > 0.0 {
+            self.shodan_requests_per_second = rate;
+        }
+        self
+    }
+
+    /// Least time between fetches from the same remote webcam host by
+    /// capture, probe and validation (default `DEFAULT_HOST_COOLDOWN`);
+    /// zero disables the cooldown
+    pub fn host_cooldown(mut self, interval: Duration) -> Self {
+        self.host_cooldown = interval;
+        self
+    }
+
+    /// Honeyscore above which a remote webcam host earns a warning
+    /// (default `DEFAULT_HONEYPOT_THRESHOLD`), and whether captures from
+    /// such hosts are refused
+    pub fn honeypot(mut self, threshold: f64, strict: bool) -> Self {
+        self.honeypot_threshold = threshold;
+        self.honeypot_strict = strict;
+        self
+    }
+
+    /// Most Shodan search and host requests per hour and per day (UTC),
+    /// by all Shodan clients together; `None` for no limit (default none)
+    pub fn shodan_budget(mut self, per_hour: Option<u32>, per_day: Option<u32>) -> Self {
+        self.shodan_max_requests_per_hour = per_hour;
+        self.shodan_max_requests_per_day = per_day;
+        self
+    }
+
+    /// Keep the count of Shodan requests against the budget in this file
+    /// across restarts (default in memory only)
+    pub fn shodan_usage_db(mut self, path: impl Into<PathBuf>) -> Self {
+        self.shodan_usage_db = Some(path.into());
+        self
+    }
+
+    pub fn build(self) -> WebcamMcpServer {
+        let capture_history = self.capture_history.unwrap_or_else(CaptureHistory::from_env);
+        info!("Capture history keeps up to {} captures / {} bytes",
+              capture_history.capacity(), capture_history.max_bytes());
+
+        let capture_store = self.capture_store.unwrap_or_else(CaptureStore::from_env);
+        info!("Capture output directory: {}", capture_store.root().display());
+
+        let webcam_manager = self.webcam_manager.unwrap_or_else(WebcamManager::new);
+
+        // Probe for local cameras so a camera-less host (e.g. a container
+        // without /dev/video*) is obvious up front
+        let local_cameras_available = match webcam_manager.list_cameras() {
+            Ok(cameras) if !cameras.is_empty() => {
+                info!("Found {} local camera(s)", cameras.len());
+                true
+            }
+            Ok(_) => {
+                warn!("No local cameras available - local capture tools will fail until a camera is connected");
+                false
+            }
+            Err(e) => {
+                warn!("Local cameras unavailable ({}) - local capture tools will fail", e);
+                false
+            }
+        };
+
+        let shodan_limiter = Arc::new(RequestLimiter::new(self.shodan_requests_per_second));
+        let host_cooldown = Arc::new(HostCooldown::new(self.host_cooldown));
+        let (per_hour, per_day) = (self.shodan_max_requests_per_hour, self.shodan_max_requests_per_day);
+        let credit_budget = Arc::new(match self.shodan_usage_db {
+            Some(path) => CreditBudget::open(path, per_hour, per_day),
+            None => CreditBudget::new(per_hour, per_day),
+        });
+        if credit_budget.is_limited() {
+            info!("Shodan request budget: {} per hour, {} per day",
+                  per_hour.map_or("unlimited".to_string(), |n| n.to_string()),
+                  per_day.map_or("unlimited".to_string(), |n| n.to_string()));
+        }
+        let audit_log = self.audit_log.unwrap_or_else(AuditLog::disabled);
+        let mut shodan_client_settings = self.shodan_client_settings
+            .unwrap_or_default()
+            .request_limiter(Arc::clone(&shodan_limiter))
+            .host_cooldown(Arc::clone(&host_cooldown))
+            .credit_budget(Arc::clone(&credit_budget))
+            .audit_log(audit_log.clone());
+        if let Some(policy) = self.shodan_retry {
+            shodan_client_settings = shodan_client_settings.retry_policy(policy);
+        }
+
+        WebcamMcpServer {
+            name: self.name,
+            version: self.version,
+            webcam_manager: Arc::new(Mutex::new(webcam_manager)),
+            shodan_client: Arc::new(Mutex::new(
+                self.shodan_client
+                    .filter(|_| self.remote_enabled)
+                    .map(|client| match self.shodan_retry {
+                        Some(policy) => client.with_retry_policy(policy),
+                        None => client,
+                    })
+                    .map(|client| client.with_request_limiter(Arc::clone(&shodan_limiter)))
+                    .map(|client| client.with_host_cooldown(Arc::clone(&host_cooldown)))
+                    .map(|client| client.with_credit_budget(Arc::clone(&credit_budget)))
+                    .map(|client| client.with_audit_log(audit_log.clone())),
+            )),
+            discovery_providers: if self.remote_enabled { self.discovery_providers } else { Vec::new() },
+            shodan_configurable: self.shodan_configurable && self.remote_enabled,
+            remote_enabled: self.remote_enabled,
+            network_scan_enabled: self.network_scan_enabled && self.remote_enabled,
+            shodan_key_file: self.shodan_key_file.filter(|_| self.remote_enabled),
+            capture_history: Arc::new(Mutex::new(capture_history)),
+            capture_store,
+            snapshots: Arc::new(Mutex::new(HashMap::new())),
+            snapshot_interval: self.snapshot_interval,
+            preview: Arc::new(Mutex::new(None)),
+            auth_token: self.auth_token,
+            stats: Arc::new(Mutex::new(ServerStats::new())),
+            local_cameras_available,
+            preview_enabled: self.preview_enabled,
+            tool_timeouts: self.tool_timeouts,
+            rate_limits: self.rate_limits,
+            max_concurrent_calls: self.max_concurrent_calls,
+            audit_log,
+            search_queries: Arc::new(self.search_queries.unwrap_or_else(|| {
+                DEFAULT_WEBCAM_QUERIES.iter().map(|query| query.to_string()).collect()
+            })),
+            webcam_cache: Arc::new(Mutex::new(match self.webcam_db {
+                Some(path) => WebcamCache::open(path, self.webcam_cache_ttl),
+                None => WebcamCache::with_ttl(self.webcam_cache_ttl),
+            })),
+            consent: self.consent.unwrap_or_else(ConsentGate::disabled),
+            shodan_client_settings,
+            shodan_limiter,
+            host_cooldown,
+            honeypot: Arc::new(HoneypotGuard::new(self.honeypot_threshold, self.honeypot_strict)),
+            credit_budget,
+        }
+    }
+}
+
+/// Default timeouts enforced by the dispatch layer. Tools not listed
+/// finish quickly or are bounded by their own parameters, and have none.
+fn default_tool_timeouts() -> Vec<(&'static str, Duration)> {
+    vec![
+        ("capture_image", CAPTURE_TOOL_TIMEOUT),
+        ("scan_codes", CAPTURE_TOOL_TIMEOUT),
+        ("compare_captures", CAPTURE_TOOL_TIMEOUT),
+        // Clips and benchmarks run for up to their own limit on top of capturing
+        ("capture_clip", CAPTURE_TOOL_TIMEOUT + Duration::from_millis(MAX_CLIP_DURATION_MS)),
+        ("benchmark_camera", CAPTURE_TOOL_TIMEOUT + Duration::from_secs_f64(MAX_BENCHMARK_SECONDS)),
+        ("search_webcams", SHODAN_SEARCH_TIMEOUT),
+        ("count_webcams", SHODAN_SEARCH_TIMEOUT),
+        ("capture_remote_image", REMOTE_FETCH_TIMEOUT),
+        ("probe_webcam", PROBE_TIMEOUT),
+        ("validate_webcams", VALIDATE_TIMEOUT),
+        ("shodan_account_info", SHODAN_KEY_CHECK_TIMEOUT),
+        ("shodan_host_info", SHODAN_HOST_TIMEOUT),
+        ("check_honeypot", SHODAN_HOST_TIMEOUT),
+        ("request_shodan_scan", SHODAN_HOST_TIMEOUT),
+        ("get_scan_status", SHODAN_HOST_TIMEOUT),
+        ("discover_local_network_cameras", MAX_DISCOVERY_TIMEOUT + LOCAL_DISCOVERY_TIMEOUT),
+        ("scan_network_for_cameras", NETWORK_SCAN_TIMEOUT),
+        ("capture_onvif_snapshot", ONVIF_SNAPSHOT_TIMEOUT),
+    ]
+}
+
+/// Default rate limits enforced by the dispatch layer. Capturing is
+/// limited so a client stuck in a loop can't keep a camera on; Shodan
+/// searches use API credits and are limited tightly.
+fn default_rate_limits() -> Vec<(&'static str, RateLimit)> {
+    let per_minute = Duration::from_secs(60);
+    let per_hour = Duration::from_secs(3600);
+    vec![
+        ("capture_image", RateLimit::new(60, per_minute)),
+        ("scan_codes", RateLimit::new(60, per_minute)),
+        ("capture_clip", RateLimit::new(20, per_minute)),
+        ("search_webcams", RateLimit::new(5, per_hour)),
+        ("count_webcams", RateLimit::new(30, per_hour)),
+        ("capture_remote_image", RateLimit::new(30, per_hour)),
+        ("probe_webcam", RateLimit::new(30, per_hour)),
+        ("validate_webcams", RateLimit::new(10, per_hour)),
+        ("shodan_host_info", RateLimit::new(30, per_hour)),
+        ("check_honeypot", RateLimit::new(30, per_hour)),
+        ("request_shodan_scan", RateLimit::new(5, per_hour)),
+        ("get_scan_status", RateLimit::new(60, per_hour)),
+        ("scan_network_for_cameras", RateLimit::new(5, per_hour)),
+        ("capture_onvif_snapshot", RateLimit::new(30, per_hour)),
+    ]
+}
+
+/// Behaviour hints for every tool. Anything that turns a camera on is not
+/// read-only, even though no data is changed.
+fn tool_annotations() -> Vec<(&'static str, ToolAnnotations)> {
+    vec![
+        ("list_cameras", ToolAnnotations::read_only()),
+        ("capture_image", ToolAnnotations::local_action()),
+        ("scan_codes", ToolAnnotations::local_action()),
+        ("capture_clip", ToolAnnotations::local_action()),
+        ("get_camera_info", ToolAnnotations::read_only()),
+        ("set_camera_defaults", ToolAnnotations::local_action().idempotent()),
+        ("set_default_camera", ToolAnnotations::local_action().idempotent()),
+        ("self_test", ToolAnnotations::local_action()),
+        ("benchmark_camera", ToolAnnotations::local_action()),
+        ("list_captures", ToolAnnotations::read_only()),
+        ("get_capture", ToolAnnotations::read_only()),
+        ("compare_captures", ToolAnnotations::local_action()),
+        ("get_storage_info", ToolAnnotations::read_only()),
+        ("get_audit_log", ToolAnnotations::read_only()),
+        ("get_server_stats", ToolAnnotations::read_only()),
+        ("get_server_info", ToolAnnotations::read_only()),
+        ("health_check", ToolAnnotations::read_only()),
+        ("start_preview", ToolAnnotations::local_action().idempotent()),
+        // Ends the stream for anyone watching
+        ("stop_preview", ToolAnnotations::local_action().destructive().idempotent()),
+        // Checks the key with Shodan
+        ("configure_shodan", ToolAnnotations::local_action().idempotent().open_world()),
+        // Checks the key with Shodan
+        ("reload_credentials", ToolAnnotations::local_action().idempotent().open_world()),
+        ("grant_consent", ToolAnnotations::local_action()),
+        ("search_webcams", ToolAnnotations::read_only().open_world()),
+        ("count_webcams", ToolAnnotations::read_only().open_world()),
+        ("capture_remote_image", ToolAnnotations::read_only().open_world()),
+        ("probe_webcam", ToolAnnotations::read_only().open_world()),
+        ("validate_webcams", ToolAnnotations::read_only().open_world()),
+        ("shodan_account_info", ToolAnnotations::read_only().open_world()),
+        ("shodan_host_info", ToolAnnotations::read_only().open_world()),
+        ("check_honeypot", ToolAnnotations::read_only().open_world()),
+        // Spends scan credits, and has Shodan connect to the hosts
+        ("request_shodan_scan", ToolAnnotations::local_action().open_world()),
+        ("get_scan_status", ToolAnnotations::read_only().open_world()),
+        // Adds what it finds to the webcam cache
+        ("discover_local_network_cameras", ToolAnnotations::local_action().open_world()),
+        // Adds what it finds to the webcam cache
+        ("scan_network_for_cameras", ToolAnnotations::local_action().open_world()),
+        ("capture_onvif_snapshot", ToolAnnotations::read_only().open_world()),
+        ("list_search_queries", ToolAnnotations::read_only()),
+        ("get_target_policy", ToolAnnotations::read_only()),
+        ("list_remote_webcams", ToolAnnotations::read_only()),
+        ("clear_remote_webcams", ToolAnnotations::local_action().destructive().idempotent()),
+        // Replaces a file of the same name in the data directory
+        ("export_webcams", ToolAnnotations::local_action()),
+    ]
+}
+
+/// JSON schema for the optional `crop` parameter
+fn crop_schema() -> Value {
+    json!({
+        "type": "object",
+        "description": "Region of the frame to keep, in pixels (optional)",
+        "properties": {
+            "x": { "type": "number" },
+            "y": { "type": "number" },
+            "width": { "type": "number" },
+            "height": { "type": "number" }
+        },
+        "required": ["x", "y", "width", "height"]
+    })
+}
+
+/// Schema properties for the settings that can also come from a camera profile
+fn insert_profile_properties(props: &mut std::collections::HashMap<String, Value>) {
+    props.insert("width".to_string(), json!({
+        "type": "number",
+        "description": "Requested width in pixels, together with height (optional, defaults to the camera profile or the highest resolution)"
+    }));
+    props.insert("height".to_string(), json!({
+        "type": "number",
+        "description": "Requested height in pixels, together with width (optional)"
+    }));
+    props.insert("rotation".to_string(), json!({
+        "type": "number",
+        "enum": [0, 90, 180, 270],
+        "description": "Clockwise rotation in degrees (optional, defaults to the camera profile or 0)"
+    }));
+    props.insert("quality".to_string(), json!({
+        "type": "number",
+        "description": "JPEG quality 1-100 (optional, defaults to the camera profile or 75)"
+    }));
+    props.insert("warmup_frames".to_string(), json!({
+        "type": "number",
+        "description": "Frames to discard before capturing so exposure can settle (optional, defaults to the camera profile or 0)"
+    }));
+}
+
+/// Parse the processing options shared by the local capture tools
+fn parse_capture_options(params: &Value) -> Result<CaptureOptions, String> {
+    let enhance = match params.get("enhance").and_then(|v| v.as_str()) {
+        Some(value) => Some(EnhanceMode::parse(value).ok_or_else(|| {
+            format!("Invalid enhance value '{}': expected auto, gamma or equalize", value)
+        })?),
+        None => None,
+    };
+
+    let crop = match params.get("crop") {
+        Some(value) => Some(CropRect::from_json(value).ok_or_else(|| {
+            "Invalid crop value: expected an object with x, y, width and height".to_string()
+        })?),
+        None => None,
+    };
+
+    let white_balance = match params.get("white_balance") {
+        Some(value) => Some(WhiteBalance::from_json(value).ok_or_else(|| {
+            format!("Invalid white_balance value {}: expected 'auto' or a Kelvin value between 1000 and 15000", value)
+        })?),
+        None => None,
+    };
+
+    let blur_faces = params.get("blur_faces")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+        .then(|| FaceBlur {
+            strict: params.get("strict").and_then(|v| v.as_bool()).unwrap_or(true),
+        });
+
+    let width = params.get("width").and_then(|v| v.as_u64()).map(|v| v as u32);
+    let height = params.get("height").and_then(|v| v.as_u64()).map(|v| v as u32);
+    let resolution = match (width, height) {
+        (Some(w), Some(h)) if w > 0 && h > 0 => Some((w, h)),
+        (None, None) => None,
+        _ => return Err("width and height must both be positive and given together".to_string()),
+    };
+
+    let rotation = params.get("rotation").and_then(|v| v.as_u64()).map(|v| v as u32);
+    if let Some(rotation) = rotation {
+        if ![0, 90, 180, 270].contains(&rotation) {
+            return Err(format!("Invalid rotation {}: expected 0, 90, 180 or 270", rotation));
+        }
+    }
+
+    let quality = match params.get("quality").and_then(|v| v.as_u64()) {
+        Some(q) if (1..=100).contains(&q) => Some(q as u8),
+        Some(q) => return Err(format!("Invalid quality {}: expected 1-100", q)),
+        None => None,
+    };
+    let warmup_frames = params.get("warmup_frames").and_then(|v| v.as_u64()).map(|v| v.min(100) as u32);
+
+    let embed_exif = params.get("embed_exif").and_then(|v| v.as_bool()).unwrap_or(false);
+    let exif_comment = params.get("comment").and_then(|v| v.as_str()).map(str::to_string);
+    if exif_comment.as_ref().is_some_and(|c| c.len() > exif_writer::MAX_COMMENT_BYTES) {
+        return Err(format!("comment is too long: at most {} bytes", exif_writer::MAX_COMMENT_BYTES));
+    }
+
+    Ok(CaptureOptions {
+        enhance,
+        crop,
+        white_balance,
+        blur_faces,
+        resolution,
+        rotation,
+        quality,
+        warmup_frames,
+        max_width: None,
+        embed_exif,
+        exif_comment,
+    })
+}
+
+/// Input schemas of the image options `parse_remote_image_options` reads
+fn insert_remote_image_option_schemas(props: &mut HashMap<String, Value>) {
+    props.insert("max_width".to_string(), json!({
+        "type": "number",
+        "description": "Downscale images wider than this, keeping the aspect ratio (optional)"
+    }));
+    props.insert("thumbnail".to_string(), json!({
+        "type": "boolean",
+        "description": format!("Downscale to a {} pixel wide thumbnail, for triaging many webcams cheaply (optional, defaults to false)", webcam::THUMBNAIL_WIDTH)
+    }));
+    props.insert("grayscale".to_string(), json!({
+        "type": "boolean",
+        "description": "Convert the image to grayscale (optional, defaults to false)"
+    }));
+    props.insert("format".to_string(), json!({
+        "type": "string",
+        "enum": ["jpeg", "png"],
+        "description": "Encoding of the returned image (optional, defaults to the webcam's JPEG or PNG; other formats become JPEG)"
+    }));
+    props.insert("quality".to_string(), json!({
+        "type": "number",
+        "description": "JPEG quality 1-100; giving one re-encodes a JPEG even if nothing else changes (optional, defaults to 75 when the image is re-encoded)"
+    }));
+    props.insert("stats".to_string(), json!({
+        "type": "boolean",
+        "description": "Report the image's mean luminance, luminance spread and mean colour (optional, defaults to false)"
+    }));
+    props.insert("passthrough".to_string(), json!({
+        "type": "boolean",
+        "description": "Return the image exactly as the webcam sent it, without decoding it; cannot be combined with the processing options (optional, defaults to false)"
+    }));
+}
+
+/// Parse the processing options of `capture_remote_image` and
+/// `capture_onvif_snapshot`
+fn parse_remote_image_options(params: &Value) -> Result<webcam::RemoteImageOptions, String> {
+    let max_width = match params.get("max_width").and_then(|v| v.as_u64()) {
+        Some(0) => return Err("Invalid max_width 0: expected a positive width".to_string()),
+        Some(width) => Some(width.min(u32::MAX as u64) as u32),
+        None => None,
+    };
+    let format = match params.get("format").and_then(|v| v.as_str()) {
+        Some(value) => Some(webcam::OutputFormat::parse(value).ok_or_else(|| {
+            format!("Invalid format '{}': expected jpeg or png", value)
+        })?),
+        None => None,
+    };
+    let quality = match params.get("quality").and_then(|v| v.as_u64()) {
+        Some(q) if (1..=100).contains(&q) => Some(q as u8),
+        Some(q) => return Err(format!("Invalid quality {}: expected 1-100", q)),
+        None => None,
+    };
+    let options = webcam::RemoteImageOptions {
+        max_width,
+        thumbnail: params.get("thumbnail").and_then(|v| v.as_bool()).unwrap_or(false),
+        grayscale: params.get("grayscale").and_then(|v| v.as_bool()).unwrap_or(false),
+        format,
+        quality,
+        stats: params.get("stats").and_then(|v| v.as_bool()).unwrap_or(false),
+        passthrough: params.get("passthrough").and_then(|v| v.as_bool()).unwrap_or(false),
+    };
+    options.validate().map_err(|e| e.to_string())?;
+    Ok(options)
+}
+
+/// Update the usage counters. A poisoned lock is recovered, since counting
+/// must not make a tool call fail.
+fn record(stats: &Mutex<ServerStats>, update: impl FnOnce(&mut ServerStats)) {
+    update(&mut stats.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+}
+
+/// Tool response for a failed capture. A busy camera gets text naming the
+/// application to close; its `CAMERA_BUSY` code tells the caller a retry
+/// can work once the camera is released.
+fn capture_error_response(e: &webcam::WebcamError) -> Value {
+    let text = match e {
+        webcam::WebcamError::DeviceBusy { .. } => e.to_string(),
+        _ => format!("Error capturing image: {}", e),
+    };
+    ToolError::from(e).into_response(text)
+}
+
+/// Tool response for a capture id that isn't in the history
+fn capture_not_found_response(capture_id: &str) -> Value {
+    ToolError::new(ErrorCode::CaptureNotFound, format!("Capture not found: {}", capture_id))
+        .with_details(json!({ "capture_id": capture_id }))
+        .into_response(format!("Capture '{}' not found (it may have been evicted from history)", capture_id))
+}
+
+/// A clip frame after scaling and encoding
+struct EncodedClipFrame {
+    camera_index: u32,
+    dimensions: (u32, u32),
+    data: ClipFrameData,
+}
+
+enum ClipFrameData {
+    /// Quantized frame, written out with the others by `write_gif`
+    Gif(gif::Frame<'static>),
+    /// Complete JPEG, concatenated with the others into an MJPEG stream
+    Jpeg(Vec<u8>),
+}
+
+/// A camera's latest snapshot resource capture
+struct Snapshot {
+    taken: Instant,
+    result: webcam::CaptureResult,
+}
+
+/// Capture a snapshot of the camera with its profile and keep it as the
+/// camera's latest. `source` is the MCP method it is audited under.
+fn take_snapshot(
+    manager: &mut WebcamManager,
+    snapshots: &Mutex<HashMap<u32, Snapshot>>,
+    stats: &Mutex<ServerStats>,
+    audit_log: &AuditLog,
+    source: &str,
+    camera_index: u32,
+) -> Result<webcam::CaptureResult, webcam::WebcamError> {
+    let started = Instant::now();
+    let result = manager.capture_image_with_options(Some(camera_index), &CaptureOptions::default());
+    audit_log.record(AuditEntry {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        tool: source.to_string(),
+        arguments: json!({ "uri": format!("{}{}/snapshot", CAMERA_RESOURCE_PREFIX, camera_index) }),
+        camera_index: Some(camera_index),
+        url: None,
+        result_bytes: result.as_ref().map_or(0, |result| result.image_bytes.len() as u64),
+        success: result.is_ok(),
+        error_code: result.as_ref().err().map(|e| error_code_name(ToolError::from(e).code)),
+        duration_ms: started.elapsed().as_millis() as u64,
+        prev_hash: String::new(),
+    });
+    let result = result?;
+    record(stats, |s| {
+        s.record_capture(camera_index, Some(started.elapsed()));
+        s.record_encoded(result.image_bytes.len());
+    });
+    snapshots.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(camera_index, Snapshot { taken: Instant::now(), result: result.clone() });
+    Ok(result)
+}
+
+/// Audit log entry for a finished tool call. The camera is the one the
+/// result reports, else the one asked for.
+fn audited_call(call: &ToolCall) -> AuditEntry {
+    let (result_bytes, success, error_code, structured) = match call.result {
+        Ok(result) => {
+            let structured = result.get("structuredContent");
+            let error_code = structured
+                .and_then(|s| s.pointer("/error/code"))
+                .and_then(|code| code.as_str())
+                .map(str::to_string);
+            let failed = result["isError"] == true;
+            (result.to_string().len() as u64, !failed, error_code.or_else(|| failed.then(|| "UNKNOWN".to_string())), structured)
         }
+        Err(_) => (0, false, Some("INTERNAL_ERROR".to_string()), None),
+    };
+    let camera_index = structured
+        .and_then(|s| s.pointer("/metadata/camera_index"))
+        .or_else(|| call.arguments.get("camera_index"))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32);
+    AuditEntry {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        tool: call.tool.to_string(),
+        arguments: call.arguments.clone(),
+        camera_index,
+        url: call.arguments.get("url").and_then(|v| v.as_str()).map(str::to_string),
+        result_bytes,
+        success,
+        error_code,
+        duration_ms: call.duration.as_millis() as u64,
+        prev_hash: String::new(),
+    }
+}
+
+/// Stable name of an error code, e.g. `CAMERA_BUSY`
+fn error_code_name(code: ErrorCode) -> String {
+    serde_json::to_value(code).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default()
+}
+
+/// Camera index of a `webcam://camera/{index}` or
+/// `webcam://camera/{index}/snapshot` URI, and whether it is the snapshot
+fn parse_camera_resource(uri: &str) -> Option<(u32, bool)> {
+    let path = uri.strip_prefix(CAMERA_RESOURCE_PREFIX)?;
+    let (index, snapshot) = match path.strip_suffix("/snapshot") {
+        Some(index) => (index, true),
+        None => (path, false),
+    };
+    if !index.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((index.parse().ok()?, snapshot))
+}
+
+/// Capture store file holding the capture resource with this id
+fn resource_file_name(capture_id: &str) -> String {
+    format!("capture_{}.jpg", capture_id)
+}
+
+/// Capture store file an export asked to be saved as `file_name` is
+/// written to: named like the store's other exports, so that retention
+/// applies to it and it can't replace a capture or clip
+fn export_file_name(file_name: &str) -> String {
+    if file_name.starts_with("webcams_") {
+        file_name.to_string()
+    } else {
+        format!("webcams_{}", file_name)
+    }
+}
+
+/// Tool result for arguments that failed to parse
+fn invalid_params_response(message: &str) -> Value {
+    ToolError::invalid_params(message).into_response(message)
+}
+
+/// Webcam at `url`, for tools given a URL rather than a search result. Its
+/// address and port are the `ip` and `port` in `params`, else the URL's.
+fn remote_webcam_at(url: &str, params: &Value) -> RemoteWebcam {
+    let parsed_url = url::Url::parse(url).ok();
+    RemoteWebcam {
+        ip: params.get("ip").and_then(|v| v.as_str()).map(str::to_string)
+            .or_else(|| parsed_url.as_ref().and_then(|url| url.host_str()).map(|host| host.trim_matches(['[', ']']).to_string()))
+            .unwrap_or_else(|| "unknown".to_string()),
+        port: params.get("port").and_then(|v| v.as_u64()).map(|port| port as u16)
+            .or_else(|| parsed_url.as_ref().and_then(|url| url.port_or_known_default()))
+            .unwrap_or(80),
+        url: url.to_string(),
+        hostname: None,
+        location: None,
+        org: None,
+        product: None,
+        last_seen: chrono::Utc::now().to_rfc3339(),
+        access_type: WebcamAccessType::HTTP,
+        classification: None,
+        sources: Vec::new(),
+    }
+}
+
+/// `client`, accepting invalid webcam certificates if the call's
+/// `accept_invalid_certs` argument says so, or as configured without one
+fn accept_invalid_certs_param(client: &ShodanClient, params: &Value) -> ShodanClient {
+    match params.get("accept_invalid_certs").and_then(|v| v.as_bool()) {
+        Some(accept) => client.accepting_invalid_certs(accept),
+        None => client.clone(),
+    }
+}
+
+/// The `vendor` argument, or the response for one naming no known make
+fn vendor_param(params: &Value) -> Result<Option<Vendor>, Value> {
+    match params.get("vendor").and_then(|v| v.as_str()).map(str::trim).filter(|name| !name.is_empty()) {
+        Some(name) => name.parse().map(Some).map_err(|e: String| invalid_params_response(&e)),
+        None => Ok(None),
+    }
+}
+
+/// The `near` point and `radius_km` arguments, or the response for a call
+/// giving only one of them or a point off the map
+fn near_param(params: &Value) -> Result<Option<GeoFilter>, Value> {
+    let near = params.get("near").filter(|near| !near.is_null());
+    let radius_km = params.get("radius_km").and_then(|v| v.as_f64());
+    match (near, radius_km) {
+        (Some(near), Some(radius_km)) => {
+            let coordinate = |name: &str| near.get(name).and_then(|v| v.as_f64());
+            let (Some(latitude), Some(longitude)) = (coordinate("lat"), coordinate("lon")) else {
+                return Err(invalid_params_response("near must have a numeric lat and lon"));
+            };
+            GeoFilter::new(latitude, longitude, radius_km).map(Some).map_err(|e| invalid_params_response(&e))
+        }
+        (None, None) => Ok(None),
+        _ => Err(invalid_params_response("near and radius_km must be given together")),
+    }
+}
+
+/// `entries` as JSON, each with its `distance_km` from `near`'s point when
+/// there is one, to the nearest 10 m
+fn with_distances<T: serde::Serialize>(
+    entries: &[T],
+    near: Option<GeoFilter>,
+    location: impl Fn(&T) -> Option<&ShodanLocation>,
+) -> Value {
+    Value::Array(entries.iter().map(|entry| {
+        let mut value = serde_json::to_value(entry).unwrap_or(Value::Null);
+        if let (Some(near), Value::Object(fields)) = (near, &mut value) {
+            let distance = near.distance_km(location(entry)).map(|km| (km * 100.0).round() / 100.0);
+            fields.insert("distance_km".to_string(), json!(distance));
+        }
+        value
+    }).collect())
+}
+
+/// Look up the honeyscores of `ips` that weren't yet, one Shodan request
+/// each and at most `MAX_SEARCH_HONEYSCORES` of them, for a search's
+/// metadata
+async fn look_up_honeyscores<'a>(client: &ShodanClient, guard: &HoneypotGuard, ips: impl Iterator<Item = &'a str>) -> Value {
+    let mut unscored: Vec<&str> = Vec::new();
+    for ip in ips {
+        if guard.score(ip).is_none() && !unscored.contains(&ip) {
+            unscored.push(ip);
+        }
+    }
+    let skipped = unscored.len().saturating_sub(MAX_SEARCH_HONEYSCORES);
+    let (mut looked_up, mut failed) = (0, 0);
+    for ip in unscored.into_iter().take(MAX_SEARCH_HONEYSCORES) {
+        match client.honeyscore(ip).await {
+            Ok(score) => {
+                guard.record(ip, score);
+                looked_up += 1;
+            }
+            // Shodan has no score for many hosts; they are left without one
+            Err(ShodanError::NotFound(_)) => failed += 1,
+            Err(e) => {
+                warn!("Could not look up the honeyscore of {}: {}", ip, e);
+                failed += 1;
+            }
+        }
+    }
+    json!({
+        "threshold": guard.threshold(),
+        "looked_up": looked_up,
+        "failed": failed,
+        "skipped": skipped
+    })
+}
+
+/// `entries`, each with its remembered `honeyscore` (null if there is
+/// none) and a `honeypot_warning` if it's above the threshold, when
+/// `guard` is given
+fn with_honeyscores(mut entries: Value, guard: Option<&HoneypotGuard>) -> Value {
+    let Some(guard) = guard else {
+        return entries;
+    };
+    if let Value::Array(list) = &mut entries {
+        for fields in list.iter_mut().filter_map(|entry| entry.as_object_mut()) {
+            let score = fields.get("ip").and_then(|ip| ip.as_str()).and_then(|ip| guard.score(ip));
+            fields.insert("honeyscore".to_string(), json!(score));
+            if let Some(warning) = score.and_then(|score| guard.warning(score)) {
+                fields.insert("honeypot_warning".to_string(), json!(warning));
+            }
+        }
+    }
+    entries
+}
+
+/// In strict mode, the error refusing a capture from `ip` if Shodan rates
+/// it a likely honeypot, with the text shown to the model. A score not yet
+/// looked up is, if `client` has a key; hosts given by name and IPs Shodan
+/// has no score for are let through, and a failed lookup refuses.
+async fn honeypot_refusal(client: &ShodanClient, guard: &HoneypotGuard, ip: &str) -> Option<(ToolError, String)> {
+    if !guard.strict() || ip.parse::<std::net::IpAddr>().is_err() {
+        return None;
+    }
+    let score = match guard.score(ip) {
+        Some(score) => score,
+        None if !client.has_api_key() => return None,
+        None => match client.honeyscore(ip).await {
+            Ok(score) => {
+                guard.record(ip, score);
+                score
+            }
+            Err(ShodanError::NotFound(_)) => return None,
+            Err(e) => {
+                return Some((ToolError::from(&e), format!("Could not check whether {} is a honeypot, strict mode refuses it: {}", ip, e)));
+            }
+        },
+    };
+    guard.is_likely_honeypot(score).then(|| {
+        let text = format!("Refusing to capture from {}: Shodan rates it {:.2} on its honeypot scale, above the {:.2} threshold", ip, score, guard.threshold());
+        let error = ToolError::new(ErrorCode::HoneypotSuspected, text.clone())
+            .with_details(json!({ "ip": ip, "honeyscore": score, "threshold": guard.threshold() }));
+        (error, text)
+    })
+}
+
+/// Names `vendor` arguments take, for tool descriptions
+fn vendor_names() -> String {
+    Vendor::ALL.iter().map(|vendor| vendor.name()).collect::<Vec<_>>().join(", ")
+}
+
+/// Webcam credentials from the `username` and `password` arguments, or the
+/// response for a call giving only one of them
+fn credentials_param(params: &Value) -> Result<Option<WebcamCredentials>, Value> {
+    let username = params.get("username").and_then(|v| v.as_str());
+    let password = params.get("password").and_then(|v| v.as_str());
+    match (username, password) {
+        (Some(username), Some(password)) => Ok(Some(WebcamCredentials::new(username, password))),
+        (None, None) => Ok(None),
+        _ => Err(invalid_params_response("'username' and 'password' must be given together")),
+    }
+}
+
+/// Tool result for a call the client cancelled; `what` names the operation
+fn cancelled_response(what: &str) -> Value {
+    info!("{} cancelled by the client", what);
+    ToolError::new(ErrorCode::Cancelled, format!("{} cancelled", what))
+        .into_response(format!("{} was cancelled by the client", what))
+}
+
+/// Run `future` on `rt` until it completes or `cancel` fires; `None` if
+/// it was cancelled, dropping the future and any request it had open
+fn block_on_cancellable<F: std::future::Future>(
+    rt: &tokio::runtime::Runtime,
+    cancel: &CancellationToken,
+    future: F,
+) -> Option<F::Output> {
+    rt.block_on(async {
+        tokio::select! {
+            output = future => Some(output),
+            _ = cancel.cancelled() => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+
+    fn server_with_cameras(cameras: u32) -> WebcamMcpServer {
+        WebcamMcpServer::builder()
+            .webcam_manager(WebcamManager::with_backend(Box::new(MockBackend::new(cameras))))
+            .build()
+    }
+
+    #[test]
+    fn the_startup_probe_flags_camera_tools_on_camera_less_hosts() {
+        let server = server_with_cameras(0);
+        assert!(!server.local_cameras_available);
+        let description = server.local_tool_description("Capture an image from a local camera.").unwrap();
+        assert!(description.starts_with("Capture an image from a local camera. NOTE: no local cameras were detected"), "{}", description);
+        let capture = server.webcam_manager.lock().unwrap().capture_image(None);
+        assert!(matches!(capture, Err(webcam::WebcamError::NoCamerasAvailable)));
+
+        let server = server_with_cameras(1);
+        assert!(server.local_cameras_available);
+        assert_eq!(server.local_tool_description("Capture an image").as_deref(), Some("Capture an image"));
     }
 }
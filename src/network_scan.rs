@@ -0,0 +1,298 @@
+//! Cameras found by scanning a network: a TCP connect to each port of
+//! every address in a CIDR range, then a banner read from each port that
+//! accepts — the answer to `GET /` on HTTP ports, to `OPTIONS` on RTSP
+//! ones — matched against the vendor rules of `classify`. A scan reaches
+//! every host of the range, so the tool running it is only offered when
+//! the server is started with `--allow-network-scan`.
+
+use crate::classify::{classify, Classification};
+use crate::shodan::{RemoteWebcam, WebcamAccessType};
+use regex::Regex;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::{timeout, timeout_at, Instant};
+
+/// Source that tags webcams found by scanning
+pub const SOURCE: &str = "scan";
+/// Recorded in the webcam cache, followed by the range, as the query that
+/// found them
+pub const DISCOVERY_QUERY: &str = "Network scan";
+/// Smallest prefix length scanned, i.e. the largest range: a /22 is 1024
+/// addresses
+pub const MAX_SCAN_PREFIX: u8 = 22;
+/// Ports tried when none are given: HTTP, RTSP and the usual alternative
+/// HTTP ports of cameras and NVRs
+pub const DEFAULT_SCAN_PORTS: [u16; 4] = [80, 554, 8000, 8080];
+/// Most ports one scan tries
+pub const MAX_SCAN_PORTS: usize = 16;
+/// Ports spoken to in RTSP rather than HTTP
+pub const RTSP_PORTS: [u16; 2] = [554, 8554];
+/// Default number of connections open at once
+pub const DEFAULT_SCAN_CONCURRENCY: usize = 64;
+/// Most connections open at once
+pub const MAX_SCAN_CONCURRENCY: usize = 256;
+/// Default time allowed for each connection attempt
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+/// Longest time allowed for each connection attempt
+pub const MAX_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time a port that accepted has to answer the request sent to it
+const BANNER_TIMEOUT: Duration = Duration::from_secs(2);
+/// Most bytes of an answer kept as its banner
+const MAX_BANNER_BYTES: usize = 2048;
+
+/// An IPv4 network of at most `MAX_SCAN_PREFIX`'s size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanRange {
+    network: Ipv4Addr,
+    prefix: u8,
+}
+
+impl ScanRange {
+    pub fn network(&self) -> Ipv4Addr {
+        self.network
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Addresses a scan connects to: all of the range but, in one larger
+    /// than a /31, its network and broadcast addresses
+    pub fn hosts(&self) -> Vec<Ipv4Addr> {
+        let first = u32::from(self.network);
+        let last = first | u32::MAX.checked_shr(u32::from(self.prefix)).unwrap_or(0);
+        let (first, last) = if self.prefix <= 30 { (first + 1, last - 1) } else { (first, last) };
+        (first..=last).map(Ipv4Addr::from).collect()
+    }
+}
+
+impl FromStr for ScanRange {
+    type Err = String;
+
+    /// `192.168.1.0/24`, or a bare address for a range of one; the host
+    /// bits of the address are ignored
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let (address, prefix) = match text.split_once('/') {
+            Some((address, prefix)) => (address, prefix.parse::<u8>().ok().filter(|prefix| *prefix <= 32)),
+            None => (text, Some(32)),
+        };
+        let address: Ipv4Addr = match address.parse() {
+            Ok(address) => address,
+            Err(_) if address.parse::<Ipv6Addr>().is_ok() => {
+                return Err(format!("'{}' is an IPv6 range; only IPv4 networks can be scanned", text));
+            }
+            Err(_) => return Err(format!("'{}' is not a valid IPv4 CIDR range", text)),
+        };
+        let prefix = prefix.ok_or_else(|| format!("'{}' is not a valid IPv4 CIDR range", text))?;
+        if prefix < MAX_SCAN_PREFIX {
+            return Err(format!(
+                "'{}' is too large to scan; the largest range is a /{} ({} addresses)",
+                text, MAX_SCAN_PREFIX, 1u32 << (32 - MAX_SCAN_PREFIX)
+            ));
+        }
+        let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+        Ok(Self { network: Ipv4Addr::from(u32::from(address) & mask), prefix })
+    }
+}
+
+impl fmt::Display for ScanRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+impl Serialize for ScanRange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// How an open port is spoken to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanProtocol {
+    Http,
+    Rtsp,
+}
+
+impl ScanProtocol {
+    /// The protocol tried on `port`
+    pub fn for_port(port: u16) -> Self {
+        if RTSP_PORTS.contains(&port) { ScanProtocol::Rtsp } else { ScanProtocol::Http }
+    }
+}
+
+/// A port that accepted a connection
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OpenPort {
+    pub ip: Ipv4Addr,
+    pub port: u16,
+    pub protocol: ScanProtocol,
+    /// The start of what it answered, status line and headers first;
+    /// empty if it answered nothing in time
+    pub banner: String,
+}
+
+impl OpenPort {
+    /// Whether it answered in the protocol it was spoken to in
+    pub fn answered(&self) -> bool {
+        match self.protocol {
+            ScanProtocol::Http => self.banner.starts_with("HTTP/"),
+            ScanProtocol::Rtsp => self.banner.starts_with("RTSP/"),
+        }
+    }
+
+    /// Its make, as `classify` judges it from the banner
+    pub fn classification(&self) -> Option<Classification> {
+        classify(self.header("server").as_deref(), &self.banner, self.port)
+    }
+
+    /// Whether it looks like a camera, a DVR or an NVR: of a make
+    /// `classify` knows, an RTSP server, or naming itself as one
+    pub fn is_camera(&self) -> bool {
+        static CAMERA_WORDS: OnceLock<Regex> = OnceLock::new();
+        let words = CAMERA_WORDS.get_or_init(|| {
+            Regex::new(r"(?i)\b(ip ?cam(era)?|network camera|webcam|nvr|dvr|network video|surveillance|onvif)\b|multipart/x-mixed-replace").unwrap()
+        });
+        self.answered()
+            && (self.protocol == ScanProtocol::Rtsp || words.is_match(&self.banner) || self.classification().is_some())
+    }
+
+    /// The value of the first header `name`, matched ignoring case
+    pub fn header(&self, name: &str) -> Option<String> {
+        let head = self.banner.split("\r\n\r\n").next().unwrap_or_default();
+        head.lines().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim().eq_ignore_ascii_case(name).then(|| value.trim().to_string())
+        }).filter(|value| !value.is_empty())
+    }
+
+    /// The port as a webcam entry of the remote-webcam cache
+    pub fn to_webcam(&self) -> RemoteWebcam {
+        let (access_type, url) = match self.protocol {
+            ScanProtocol::Rtsp => (WebcamAccessType::RTSP, format!("rtsp://{}:{}/", self.ip, self.port)),
+            ScanProtocol::Http if self.banner.to_ascii_lowercase().contains("multipart/x-mixed-replace") => {
+                (WebcamAccessType::MJPEG, format!("http://{}:{}/", self.ip, self.port))
+            }
+            ScanProtocol::Http => (WebcamAccessType::HTTP, format!("http://{}:{}/", self.ip, self.port)),
+        };
+        RemoteWebcam {
+            ip: self.ip.to_string(),
+            port: self.port,
+            url,
+            hostname: None,
+            location: None,
+            org: None,
+            classification: self.classification(),
+            product: self.header("server"),
+            last_seen: chrono::Utc::now().to_rfc3339(),
+            access_type,
+            sources: vec![SOURCE.to_string()],
+        }
+    }
+}
+
+/// A port that looks like a camera's
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanCandidate {
+    #[serde(flatten)]
+    pub webcam: RemoteWebcam,
+    pub protocol: ScanProtocol,
+    pub banner: String,
+}
+
+/// What a scan found
+#[derive(Debug, Clone, Default)]
+pub struct NetworkScan {
+    /// Addresses connected to
+    pub hosts: usize,
+    /// Connection attempts, one per port of each address
+    pub attempts: usize,
+    /// Every port that accepted, by address then port
+    pub open: Vec<OpenPort>,
+}
+
+impl NetworkScan {
+    /// The open ports that look like cameras', as webcam entries
+    pub fn candidates(&self) -> Vec<ScanCandidate> {
+        self.open
+            .iter()
+            .filter(|open| open.is_camera())
+            .map(|open| ScanCandidate { webcam: open.to_webcam(), protocol: open.protocol, banner: open.banner.clone() })
+            .collect()
+    }
+}
+
+/// Connect to each of `ports` on each of `hosts`, at most `concurrency`
+/// connections at once, each given `connect_timeout`, and read a banner
+/// from every port that accepts
+pub async fn scan(hosts: &[Ipv4Addr], ports: &[u16], concurrency: usize, connect_timeout: Duration) -> NetworkScan {
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut probes = JoinSet::new();
+    for &ip in hosts {
+        for &port in ports {
+            let Ok(permit) = Arc::clone(&permits).acquire_owned().await else {
+                continue;
+            };
+            probes.spawn(async move {
+                let open = probe(ip, port, connect_timeout).await;
+                drop(permit);
+                open
+            });
+        }
+    }
+    let mut open = Vec::new();
+    while let Some(probed) = probes.join_next().await {
+        if let Ok(Some(port)) = probed {
+            open.push(port);
+        }
+    }
+    open.sort_by_key(|port| (port.ip, port.port));
+    NetworkScan { hosts: hosts.len(), attempts: hosts.len() * ports.len(), open }
+}
+
+/// Connect to `port` of `ip` and read its banner, or `None` if it
+/// doesn't accept within `connect_timeout`
+pub async fn probe(ip: Ipv4Addr, port: u16, connect_timeout: Duration) -> Option<OpenPort> {
+    let address = SocketAddr::new(IpAddr::V4(ip), port);
+    let mut stream = timeout(connect_timeout, TcpStream::connect(address)).await.ok()?.ok()?;
+    let protocol = ScanProtocol::for_port(port);
+    let deadline = Instant::now() + BANNER_TIMEOUT;
+    let mut banner = Vec::new();
+    if let Ok(Ok(())) = timeout_at(deadline, stream.write_all(probe_request(protocol, ip, port).as_bytes())).await {
+        let mut buffer = [0u8; 1024];
+        while banner.len() < MAX_BANNER_BYTES {
+            match timeout_at(deadline, stream.read(&mut buffer)).await {
+                Ok(Ok(read)) if read > 0 => banner.extend_from_slice(&buffer[..read]),
+                _ => break,
+            }
+            // An RTSP answer has no body to wait for
+            if protocol == ScanProtocol::Rtsp && banner.windows(4).any(|end| end == b"\r\n\r\n") {
+                break;
+            }
+        }
+    }
+    banner.truncate(MAX_BANNER_BYTES);
+    Some(OpenPort { ip, port, protocol, banner: String::from_utf8_lossy(&banner).into_owned() })
+}
+
+/// What is sent to an open port: `GET /` over HTTP, `OPTIONS` over RTSP
+pub fn probe_request(protocol: ScanProtocol, ip: Ipv4Addr, port: u16) -> String {
+    let user_agent = concat!("mcp-webcam/", env!("CARGO_PKG_VERSION"));
+    match protocol {
+        ScanProtocol::Http => format!(
+            "GET / HTTP/1.0\r\nHost: {}:{}\r\nUser-Agent: {}\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+            ip, port, user_agent
+        ),
+        ScanProtocol::Rtsp => format!("OPTIONS rtsp://{}:{}/ RTSP/1.0\r\nCSeq: 1\r\nUser-Agent: {}\r\n\r\n", ip, port, user_agent),
+    }
+}
@@ -0,0 +1,207 @@
+//! Cameras on the local network found by ONVIF WS-Discovery. Most LAN
+//! cameras never appear in Shodan, but nearly all of them answer a SOAP
+//! `Probe` multicast to 239.255.255.250:3702 with a `ProbeMatch` listing
+//! their device service URLs (`XAddrs`) and scopes, among them the name
+//! and hardware model they were given. The probe is sent from every IPv4
+//! interface, as a host with several (wired, Wi-Fi, a VPN) sends multicast
+//! out of only one of them otherwise, and replies are collected until the
+//! listen window closes.
+
+use crate::classify::classify;
+use crate::local_discovery::{elements, host_for_url, local_interfaces, multicast};
+use crate::shodan::{RemoteWebcam, WebcamAccessType};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Source that tags webcams found by WS-Discovery
+pub const SOURCE: &str = "onvif";
+/// Recorded in the webcam cache as the query that found them
+pub const DISCOVERY_QUERY: &str = "ONVIF WS-Discovery";
+/// Where WS-Discovery probes are multicast
+pub const WS_DISCOVERY_ADDRESS: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 3702);
+
+/// A device that answered the probe
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OnvifDevice {
+    /// Its endpoint reference, usually `urn:uuid:...`; the same whichever
+    /// interface it answered on
+    pub endpoint: String,
+    /// Device service URLs, spoken to with SOAP
+    pub xaddrs: Vec<String>,
+    /// Types it claims, e.g. `dn:NetworkVideoTransmitter`
+    pub types: Vec<String>,
+    pub scopes: Vec<String>,
+    /// From its `onvif://www.onvif.org/name/` scope
+    pub name: Option<String>,
+    /// From its `onvif://www.onvif.org/hardware/` scope
+    pub hardware: Option<String>,
+    /// Address the reply came from
+    pub responder: IpAddr,
+}
+
+impl OnvifDevice {
+    /// The device as a webcam entry of the remote-webcam cache. Its URL is
+    /// the device service on the address it answered from, or the first
+    /// one it lists.
+    pub fn to_webcam(&self) -> RemoteWebcam {
+        let urls: Vec<url::Url> = self.xaddrs.iter().filter_map(|xaddr| url::Url::parse(xaddr).ok()).collect();
+        let responder = self.responder.to_string();
+        let service = urls.iter()
+            .find(|url| url.host_str().map(|host| host.trim_matches(&['[', ']'][..])) == Some(responder.as_str()))
+            .or_else(|| urls.first());
+        let port = service.and_then(|url| url.port_or_known_default()).unwrap_or(80);
+        let url = match service {
+            Some(service) => service.to_string(),
+            None => format!("http://{}/onvif/device_service", host_for_url(self.responder)),
+        };
+        let product = self.hardware.clone().or_else(|| self.name.clone());
+        // Names usually lead with the make, hardware models rarely do
+        let described: Vec<&str> = self.name.iter().chain(&self.hardware).map(String::as_str).collect();
+        let described = described.join(" ");
+        let classification = classify(Some(described.as_str()).filter(|d| !d.is_empty()), &self.scopes.join("\n"), port);
+        RemoteWebcam {
+            ip: responder,
+            port,
+            url,
+            hostname: None,
+            location: None,
+            org: None,
+            classification,
+            product,
+            last_seen: chrono::Utc::now().to_rfc3339(),
+            access_type: WebcamAccessType::ONVIF,
+            sources: vec![SOURCE.to_string()],
+        }
+    }
+}
+
+/// What a discovery found, and where it looked
+#[derive(Debug, Clone, Default)]
+pub struct OnvifDiscovery {
+    pub devices: Vec<OnvifDevice>,
+    /// Local addresses the probe was sent from
+    pub interfaces: Vec<Ipv4Addr>,
+}
+
+/// Probe every IPv4 interface but loopback and listen for `timeout`
+pub async fn discover(timeout: Duration) -> io::Result<OnvifDiscovery> {
+    discover_via(&local_interfaces(), WS_DISCOVERY_ADDRESS, timeout).await
+}
+
+/// Send the probe to `target` from each of `interfaces` and collect the
+/// devices that answer within `timeout`, each listed once however many
+/// interfaces it answered on. Fails only if the probe couldn't be sent
+/// from any of them.
+pub async fn discover_via(interfaces: &[Ipv4Addr], target: SocketAddr, timeout: Duration) -> io::Result<OnvifDiscovery> {
+    let message_id = format!("uuid:{}", uuid::Uuid::new_v4());
+    let probe = probe_message(&message_id);
+    let (interfaces, replies) = multicast(interfaces, target, &[probe], Instant::now() + timeout).await?;
+
+    let mut discovery = OnvifDiscovery { devices: Vec::new(), interfaces };
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for (from, reply) in replies {
+        if relates_to(&reply).is_some_and(|id| id != message_id) {
+            continue;
+        }
+        for device in parse_probe_matches(&reply, from.ip()) {
+            match seen.get(&device.endpoint) {
+                Some(&index) => {
+                    let known = &mut discovery.devices[index];
+                    for xaddr in device.xaddrs {
+                        if !known.xaddrs.contains(&xaddr) {
+                            known.xaddrs.push(xaddr);
+                        }
+                    }
+                }
+                None => {
+                    seen.insert(device.endpoint.clone(), discovery.devices.len());
+                    discovery.devices.push(device);
+                }
+            }
+        }
+    }
+    Ok(discovery)
+}
+
+/// The SOAP `Probe` for network video transmitters, with `message_id`
+/// for replies to refer to
+pub fn probe_message(message_id: &str) -> String {
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope" xmlns:a="http://schemas.xmlsoap.org/ws/2004/08/addressing" "#,
+            r#"xmlns:d="http://schemas.xmlsoap.org/ws/2005/04/discovery" xmlns:dn="http://www.onvif.org/ver10/network/wsdl">"#,
+            r#"<s:Header><a:MessageID>{}</a:MessageID><a:To s:mustUnderstand="true">urn:schemas-xmlsoap-org:ws:2005:04:discovery</a:To>"#,
+            r#"<a:Action s:mustUnderstand="true">http://schemas.xmlsoap.org/ws/2005/04/discovery/Probe</a:Action></s:Header>"#,
+            r#"<s:Body><d:Probe><d:Types>dn:NetworkVideoTransmitter</d:Types></d:Probe></s:Body></s:Envelope>"#,
+        ),
+        message_id
+    )
+}
+
+/// The devices of a `ProbeMatches` reply from `responder`. Elements are
+/// matched by local name, whatever namespace prefix a device uses; a match
+/// without an endpoint reference is skipped.
+pub fn parse_probe_matches(xml: &str, responder: IpAddr) -> Vec<OnvifDevice> {
+    elements(xml, "ProbeMatch")
+        .into_iter()
+        .filter_map(|probe_match| {
+            let endpoint = elements(probe_match, "Address").into_iter().next()?.trim().to_string();
+            if endpoint.is_empty() {
+                return None;
+            }
+            let list = |name: &str| -> Vec<String> {
+                elements(probe_match, name).into_iter().flat_map(str::split_whitespace).map(str::to_string).collect()
+            };
+            let scopes = list("Scopes");
+            Some(OnvifDevice {
+                endpoint,
+                xaddrs: list("XAddrs"),
+                types: list("Types"),
+                name: scope_value(&scopes, "name"),
+                hardware: scope_value(&scopes, "hardware"),
+                scopes,
+                responder,
+            })
+        })
+        .collect()
+}
+
+/// The `MessageID` a reply answers, if it says
+fn relates_to(xml: &str) -> Option<String> {
+    elements(xml, "RelatesTo").into_iter().next().map(|id| id.trim().to_string())
+}
+
+/// The decoded value of the first `onvif://www.onvif.org/{kind}/...`
+/// scope, e.g. `Front Door` of `onvif://www.onvif.org/name/Front%20Door`
+fn scope_value(scopes: &[String], kind: &str) -> Option<String> {
+    let prefix = format!("onvif://www.onvif.org/{}/", kind);
+    scopes.iter()
+        .find_map(|scope| scope.strip_prefix(&prefix))
+        .map(|value| percent_decode(value).trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok()).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,252 @@
+//! Snapshots of ONVIF cameras, through the SOAP services their device
+//! service URL leads to: `GetCapabilities` names the media service,
+//! `GetProfiles` lists its media profiles, and `GetSnapshotUri` gives the
+//! HTTP URL of a profile's still image, fetched like any remote webcam
+//! image. A camera without one is asked for the profile's RTSP stream
+//! with `GetStreamUri` instead. Given credentials, each request carries a
+//! WS-Security `UsernameToken` with a password digest; the snapshot
+//! request answers HTTP Basic or Digest challenges with them.
+
+use crate::http_auth::WebcamCredentials;
+use crate::local_discovery::{element_text, elements};
+use crate::shodan::{sanitize_url, RemoteImage, ShodanClient, ShodanError, WEBCAM_FETCH_TIMEOUT};
+use base64::{engine::general_purpose, Engine as _};
+use regex::Regex;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::time::Duration;
+use tracing::{debug, info};
+
+/// Namespace of the device service's messages
+pub const DEVICE_NAMESPACE: &str = "http://www.onvif.org/ver10/device/wsdl";
+/// Namespace of the media service's messages
+pub const MEDIA_NAMESPACE: &str = "http://www.onvif.org/ver10/media/wsdl";
+/// Namespace of the types inside them, such as a stream setup
+const SCHEMA_NAMESPACE: &str = "http://www.onvif.org/ver10/schema";
+/// Time allowed for each SOAP request
+const SOAP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A media profile: one encoding of one video source
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MediaProfile {
+    /// What requests name it by
+    pub token: String,
+    pub name: Option<String>,
+    /// Video encoding, e.g. `H264` or `JPEG`
+    pub encoding: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// What a camera's services gave, and the snapshot if it has one
+#[derive(Debug, Clone)]
+pub struct OnvifSnapshot {
+    /// Media service spoken to
+    pub media_url: String,
+    pub profiles: Vec<MediaProfile>,
+    /// The profile the snapshot is of
+    pub profile: MediaProfile,
+    pub snapshot_uri: Option<String>,
+    /// The profile's RTSP stream, asked for only when it has no snapshot URI
+    pub stream_uri: Option<String>,
+    /// The image at `snapshot_uri`
+    pub image: Option<RemoteImage>,
+}
+
+impl ShodanClient {
+    /// Resolve the snapshot URI of a profile of the ONVIF camera whose
+    /// device service is at `device_url` — the one whose token is
+    /// `profile`, else the first — and fetch it. A camera that offers no
+    /// snapshot URI has the profile's RTSP stream URI reported instead,
+    /// and no image. Every request goes through the target policy, and
+    /// the camera's host is claimed from the cooldown once for them all.
+    pub async fn onvif_snapshot(
+        &self,
+        device_url: &str,
+        credentials: Option<&WebcamCredentials>,
+        profile: Option<&str>,
+    ) -> Result<OnvifSnapshot, ShodanError> {
+        let host = url::Url::parse(device_url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.trim_matches(['[', ']']).to_string()))
+            .ok_or_else(|| ShodanError::Generic(format!("'{}' is not a valid device service URL", sanitize_url(device_url))))?;
+        self.check_target(device_url).await?;
+        self.claim_host(&host)?;
+
+        let capabilities = self.call_onvif(device_url, DEVICE_NAMESPACE, "GetCapabilities", "<Category>Media</Category>", credentials).await?;
+        let media_url = parse_media_service(&capabilities).unwrap_or_else(|| device_url.to_string());
+        debug!("Media service of {}: {}", sanitize_url(device_url), sanitize_url(&media_url));
+
+        let profiles = parse_profiles(&self.call_onvif(&media_url, MEDIA_NAMESPACE, "GetProfiles", "", credentials).await?);
+        let chosen = match profile {
+            Some(token) => profiles.iter().find(|candidate| candidate.token == token).cloned(),
+            None => profiles.first().cloned(),
+        };
+        let Some(chosen) = chosen else {
+            let reason = match profile {
+                Some(token) => format!("the camera has no profile '{}'", token),
+                None => "the camera has no media profiles".to_string(),
+            };
+            return Err(ShodanError::OnvifFault { action: "GetProfiles".to_string(), reason });
+        };
+
+        let token = format!("<ProfileToken>{}</ProfileToken>", xml_escape(&chosen.token));
+        let snapshot_uri = match self.call_onvif(&media_url, MEDIA_NAMESPACE, "GetSnapshotUri", &token, credentials).await {
+            Ok(response) => parse_media_uri(&response),
+            // Cameras without snapshots fault, most with ActionNotSupported
+            Err(ShodanError::OnvifFault { reason, .. }) => {
+                debug!("{} offers no snapshot URI: {}", sanitize_url(&media_url), reason);
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        if let Some(uri) = snapshot_uri {
+            let image = self.fetch_image(&uri, credentials, WEBCAM_FETCH_TIMEOUT).await?;
+            info!("Fetched the ONVIF snapshot of profile {} from {}", chosen.token, sanitize_url(&uri));
+            return Ok(OnvifSnapshot { media_url, profiles, profile: chosen, snapshot_uri: Some(uri), stream_uri: None, image: Some(image) });
+        }
+
+        let setup = format!(
+            r#"<StreamSetup><Stream xmlns="{0}">RTP-Unicast</Stream><Transport xmlns="{0}"><Protocol>RTSP</Protocol></Transport></StreamSetup>{1}"#,
+            SCHEMA_NAMESPACE, token
+        );
+        let stream_uri = parse_media_uri(&self.call_onvif(&media_url, MEDIA_NAMESPACE, "GetStreamUri", &setup, credentials).await?);
+        Ok(OnvifSnapshot { media_url, profiles, profile: chosen, snapshot_uri: None, stream_uri, image: None })
+    }
+
+    /// Call `action` of the service at `url`, whose messages are in
+    /// `namespace`, with `body` inside the request element. Returns the
+    /// response, or its fault as an error.
+    async fn call_onvif(
+        &self,
+        url: &str,
+        namespace: &str,
+        action: &str,
+        body: &str,
+        credentials: Option<&WebcamCredentials>,
+    ) -> Result<String, ShodanError> {
+        let envelope = soap_envelope(namespace, action, body, credentials);
+        let (status, response) = self.post_soap(url, &format!("{}/{}", namespace, action), envelope, SOAP_TIMEOUT).await?;
+        let fault = parse_fault(&response);
+        let unauthorized = status == reqwest::StatusCode::UNAUTHORIZED || fault.as_ref().is_some_and(|fault| fault.not_authorized);
+        if unauthorized {
+            let scheme = "WS-UsernameToken".to_string();
+            return Err(match credentials {
+                Some(_) => ShodanError::AuthRejected { scheme, realm: None },
+                None => ShodanError::AuthRequired { scheme, realm: None },
+            });
+        }
+        if let Some(fault) = fault {
+            return Err(ShodanError::OnvifFault { action: action.to_string(), reason: fault.reason });
+        }
+        if !status.is_success() || elements(&response, "Body").is_empty() {
+            return Err(ShodanError::OnvifFault { action: action.to_string(), reason: format!("HTTP {} without a SOAP response", status) });
+        }
+        Ok(response)
+    }
+}
+
+/// The SOAP 1.2 request for `action`, with a `UsernameToken` header when
+/// there are credentials
+pub fn soap_envelope(namespace: &str, action: &str, body: &str, credentials: Option<&WebcamCredentials>) -> String {
+    let header = match credentials {
+        Some(credentials) => {
+            let created = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+            format!("<s:Header>{}</s:Header>", username_token(credentials, uuid::Uuid::new_v4().as_bytes(), &created))
+        }
+        None => String::new(),
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope">{}<s:Body><{2} xmlns="{1}">{3}</{2}></s:Body></s:Envelope>"#,
+        header, namespace, action, body
+    )
+}
+
+/// A WS-Security header with `credentials` as a `UsernameToken`, the
+/// password sent as Base64(SHA-1(nonce + created + password))
+pub fn username_token(credentials: &WebcamCredentials, nonce: &[u8], created: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(nonce);
+    hasher.update(created.as_bytes());
+    hasher.update(credentials.password.expose().as_bytes());
+    let digest = general_purpose::STANDARD.encode(hasher.finalize());
+    format!(
+        concat!(
+            r#"<Security s:mustUnderstand="1" xmlns="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-secext-1.0.xsd">"#,
+            r#"<UsernameToken><Username>{}</Username>"#,
+            r#"<Password Type="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-username-token-profile-1.0#PasswordDigest">{}</Password>"#,
+            r#"<Nonce EncodingType="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-soap-message-security-1.0#Base64Binary">{}</Nonce>"#,
+            r#"<Created xmlns="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-utility-1.0.xsd">{}</Created>"#,
+            r#"</UsernameToken></Security>"#,
+        ),
+        xml_escape(&credentials.username),
+        digest,
+        general_purpose::STANDARD.encode(nonce),
+        created
+    )
+}
+
+/// The media service URL of a `GetCapabilitiesResponse`
+pub fn parse_media_service(xml: &str) -> Option<String> {
+    elements(xml, "Media").into_iter().find_map(|media| element_text(media, "XAddr"))
+}
+
+/// The profiles of a `GetProfilesResponse`, in the camera's order. Elements
+/// are matched by local name, whatever namespace prefix a camera uses; a
+/// profile without a token is skipped.
+pub fn parse_profiles(xml: &str) -> Vec<MediaProfile> {
+    let pattern = Regex::new(r#"(?s)<(?:[\w.-]+:)?Profiles\s([^>]*)>(.*?)</(?:[\w.-]+:)?Profiles\s*>"#).unwrap();
+    let token = Regex::new(r#"\btoken\s*=\s*["']([^"']*)["']"#).unwrap();
+    pattern
+        .captures_iter(xml)
+        .filter_map(|profile| {
+            let token = token.captures(profile.get(1)?.as_str())?.get(1)?.as_str().to_string();
+            let body = profile.get(2)?.as_str();
+            let video = elements(body, "VideoEncoderConfiguration").into_iter().next().unwrap_or_default();
+            let resolution = elements(video, "Resolution").into_iter().next().unwrap_or_default();
+            let dimension = |name: &str| element_text(resolution, name).and_then(|value| value.parse().ok());
+            Some(MediaProfile {
+                token,
+                // The profile's own name comes before its configurations'
+                name: element_text(body, "Name"),
+                encoding: element_text(video, "Encoding"),
+                width: dimension("Width"),
+                height: dimension("Height"),
+            })
+        })
+        .collect()
+}
+
+/// The URI of a `GetSnapshotUriResponse` or `GetStreamUriResponse`
+pub fn parse_media_uri(xml: &str) -> Option<String> {
+    elements(xml, "MediaUri").into_iter().find_map(|uri| element_text(uri, "Uri"))
+}
+
+/// A SOAP fault a camera answered with
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoapFault {
+    /// Its reason text, with its subcodes, e.g. `ter:ActionNotSupported`
+    pub reason: String,
+    /// Whether it refused the credentials, or their absence
+    pub not_authorized: bool,
+}
+
+/// The fault of a SOAP 1.2 or 1.1 response, if it is one
+pub fn parse_fault(xml: &str) -> Option<SoapFault> {
+    let fault = elements(xml, "Fault").into_iter().next()?;
+    let codes: Vec<String> = elements(fault, "Value").into_iter().map(|code| code.trim().to_string()).collect();
+    let text = element_text(fault, "Text").or_else(|| element_text(fault, "faultstring"));
+    let reason = match (text, codes.last()) {
+        (Some(text), Some(code)) => format!("{} ({})", text, code),
+        (Some(text), None) => text,
+        (None, Some(code)) => code.clone(),
+        (None, None) => "SOAP fault".to_string(),
+    };
+    let lower = fault.to_ascii_lowercase();
+    let not_authorized = lower.contains("notauthorized") || lower.contains("failedauthentication") || lower.contains("not authorized");
+    Some(SoapFault { reason, not_authorized })
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
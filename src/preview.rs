@@ -0,0 +1,448 @@
+use crate::auth::{self, AuthOutcome, AuthToken, Authenticator};
+use crate::webcam::{CaptureOptions, WebcamManager};
+use serde::Serialize;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+/// Multipart boundary used for the MJPEG stream
+const BOUNDARY: &str = "mcpwebcamframe";
+/// Largest request head accepted from a viewer
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// Settings for a preview session
+#[derive(Debug, Clone)]
+pub struct PreviewConfig {
+    pub camera_index: Option<u32>,
+    /// Address to bind; loopback unless `MCP_WEBCAM_PREVIEW_BIND` says otherwise
+    pub bind_addr: IpAddr,
+    /// 0 picks a random free port
+    pub port: u16,
+    pub fps: u32,
+    pub max_viewers: usize,
+    /// Token viewers must present; `None` generates one for this session
+    pub auth_token: Option<AuthToken>,
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        let bind_addr = std::env::var("MCP_WEBCAM_PREVIEW_BIND")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        Self {
+            camera_index: None,
+            bind_addr,
+            port: 0,
+            fps: 5,
+            max_viewers: 2,
+            auth_token: None,
+        }
+    }
+}
+
+/// Public description of a running preview
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviewInfo {
+    pub url: String,
+    pub stream_url: String,
+    pub address: String,
+    pub camera_index: Option<u32>,
+    pub fps: u32,
+    pub max_viewers: usize,
+    pub viewers: usize,
+    pub started_at: String,
+}
+
+/// A running preview HTTP server. Dropping it without `stop` leaves the
+/// server running; call `stop` to shut it down.
+pub struct PreviewServer {
+    info: PreviewInfo,
+    stop: Arc<AtomicBool>,
+    viewers: Arc<AtomicUsize>,
+    shutdown_tx: watch::Sender<bool>,
+}
+
+struct Shared {
+    auth: Authenticator,
+    max_viewers: usize,
+    viewers: Arc<AtomicUsize>,
+    frames: watch::Receiver<Option<Arc<Vec<u8>>>>,
+    shutdown: watch::Receiver<bool>,
+}
+
+/// Decrements the viewer count when a stream ends
+struct ViewerGuard(Arc<AtomicUsize>);
+
+impl Drop for ViewerGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl PreviewServer {
+    /// Bind the listener and start the frame producer and accept loop.
+    /// Binding happens synchronously so errors are reported to the caller;
+    /// the accept loop runs on `runtime`.
+    pub fn start(
+        webcam_manager: Arc<Mutex<WebcamManager>>,
+        config: PreviewConfig,
+        runtime: &tokio::runtime::Handle,
+    ) -> std::io::Result<Self> {
+        let std_listener = std::net::TcpListener::bind(SocketAddr::new(config.bind_addr, config.port))?;
+        std_listener.set_nonblocking(true)?;
+        let address = std_listener.local_addr()?;
+        let token = config.auth_token.clone().unwrap_or_else(AuthToken::generate);
+        let fps = config.fps.clamp(1, 30);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let viewers = Arc::new(AtomicUsize::new(0));
+        let (frame_tx, frame_rx) = watch::channel(None);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        // Frame producer: grabs frames only while someone is watching, taking
+        // the manager lock per frame so interactive captures can interleave
+        {
+            let stop = Arc::clone(&stop);
+            let viewers = Arc::clone(&viewers);
+            let camera_index = config.camera_index;
+            let interval = Duration::from_millis(1000 / fps as u64);
+            std::thread::spawn(move || {
+                let options = CaptureOptions::default();
+                while !stop.load(Ordering::SeqCst) {
+                    if viewers.load(Ordering::SeqCst) > 0 {
+                        let frame = webcam_manager
+                            .lock()
+                            .ok()
+                            .map(|mut manager| manager.capture_frame(camera_index, &options));
+                        match frame {
+                            Some(Ok(frame)) => match frame.encode_jpeg() {
+                                Ok(bytes) => {
+                                    let _ = frame_tx.send(Some(Arc::new(bytes)));
+                                }
+                                Err(e) => warn!("Preview frame encoding failed: {}", e),
+                            },
+                            Some(Err(e)) => warn!("Preview frame capture failed: {}", e),
+                            None => warn!("Preview could not acquire webcam manager lock"),
+                        }
+                    }
+                    std::thread::sleep(interval);
+                }
+                debug!("Preview frame producer stopped");
+            });
+        }
+
+        let shared = Arc::new(Shared {
+            auth: Authenticator::new(token.clone()),
+            max_viewers: config.max_viewers,
+            viewers: Arc::clone(&viewers),
+            frames: frame_rx,
+            shutdown: shutdown_rx.clone(),
+        });
+
+        let mut accept_shutdown = shutdown_rx;
+        runtime.spawn(async move {
+            let listener = match TcpListener::from_std(std_listener) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    warn!("Preview listener setup failed: {}", e);
+                    return;
+                }
+            };
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, peer)) => {
+                            debug!("Preview connection from {}", peer);
+                            tokio::spawn(handle_connection(stream, peer.ip(), Arc::clone(&shared)));
+                        }
+                        Err(e) => warn!("Preview accept failed: {}", e),
+                    },
+                    _ = accept_shutdown.changed() => break,
+                }
+            }
+            debug!("Preview listener on {} closed", address);
+        });
+
+        let url = format!("http://{}/?token={}", address, token.query_value());
+        let stream_url = format!("http://{}/stream?token={}", address, token.query_value());
+        info!("Preview server listening on {}", address);
+
+        Ok(Self {
+            info: PreviewInfo {
+                url,
+                stream_url,
+                address: address.to_string(),
+                camera_index: config.camera_index,
+                fps,
+                max_viewers: config.max_viewers,
+                viewers: 0,
+                started_at: chrono::Utc::now().to_rfc3339(),
+            },
+            stop,
+            viewers,
+            shutdown_tx,
+        })
+    }
+
+    pub fn info(&self) -> PreviewInfo {
+        PreviewInfo {
+            viewers: self.viewers.load(Ordering::SeqCst),
+            ..self.info.clone()
+        }
+    }
+
+    /// Stop the producer, close the listener and end all open streams
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        let _ = self.shutdown_tx.send(true);
+        info!("Preview server on {} stopped", self.info.address);
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, peer: IpAddr, shared: Arc<Shared>) {
+    let request = match read_request_head(&mut stream).await {
+        Some(request) => request,
+        None => return,
+    };
+
+    let target = request
+        .lines()
+        .next()
+        .and_then(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some("GET"), Some(target)) => Some(target.to_string()),
+                _ => None,
+            }
+        });
+    let target = match target {
+        Some(target) => target,
+        None => {
+            let _ = respond(&mut stream, "405 Method Not Allowed", "text/plain", b"Method not allowed").await;
+            return;
+        }
+    };
+
+    let (path, query) = target.split_once('?').unwrap_or((target.as_str(), ""));
+    match shared.auth.check(peer, auth::presented_token(&request, query).as_deref()) {
+        AuthOutcome::Allowed => {}
+        AuthOutcome::Rejected => {
+            let _ = respond(&mut stream, "401 Unauthorized", "text/plain", b"Missing or invalid token").await;
+            return;
+        }
+        AuthOutcome::LockedOut => {
+            let _ = respond(&mut stream, "429 Too Many Requests", "text/plain", b"Too many failed attempts").await;
+            return;
+        }
+    }
+
+    match path {
+        "/" => {
+            let page = format!(
+                "<!doctype html><html><head><title>mcp-webcam preview</title>\
+                 <link rel=\"icon\" href=\"data:,\"></head>\
+                 <body style=\"margin:0;background:#000\">\
+                 <img src=\"/stream?token={}\" style=\"width:100%\"></body></html>",
+                shared.auth.token().query_value()
+            );
+            let _ = respond(&mut stream, "200 OK", "text/html; charset=utf-8", page.as_bytes()).await;
+        }
+        "/stream" => stream_mjpeg(stream, shared).await,
+        _ => {
+            let _ = respond(&mut stream, "404 Not Found", "text/plain", b"Not found").await;
+        }
+    }
+}
+
+async fn stream_mjpeg(mut stream: TcpStream, shared: Arc<Shared>) {
+    // Reserve a viewer slot
+    let previous = shared.viewers.fetch_add(1, Ordering::SeqCst);
+    let _guard = ViewerGuard(Arc::clone(&shared.viewers));
+    if previous >= shared.max_viewers {
+        let _ = respond(&mut stream, "503 Service Unavailable", "text/plain", b"Too many viewers").await;
+        return;
+    }
+
+    let header = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={}\r\n\
+         Cache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        BOUNDARY
+    );
+    if stream.write_all(header.as_bytes()).await.is_err() {
+        return;
+    }
+
+    let mut frames = shared.frames.clone();
+    let mut shutdown = shared.shutdown.clone();
+    loop {
+        tokio::select! {
+            changed = frames.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+            }
+            _ = shutdown.changed() => break,
+        }
+
+        let frame = frames.borrow_and_update().clone();
+        if let Some(frame) = frame {
+            let part_header = format!(
+                "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+                BOUNDARY,
+                frame.len()
+            );
+            let written = async {
+                stream.write_all(part_header.as_bytes()).await?;
+                stream.write_all(&frame).await?;
+                stream.write_all(b"\r\n").await
+            };
+            if written.await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+async fn read_request_head(stream: &mut TcpStream) -> Option<String> {
+    let mut buffer = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    loop {
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut chunk))
+            .await
+            .ok()?
+            .ok()?;
+        if read == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if buffer.windows(4).any(|w| w == b"\r\n\r\n") {
+            return Some(String::from_utf8_lossy(&buffer).into_owned());
+        }
+        if buffer.len() > MAX_REQUEST_BYTES {
+            return None;
+        }
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> std::io::Result<()> {
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+
+    const TOKEN: &str = "preview-test-token";
+
+    fn start(max_viewers: usize) -> PreviewServer {
+        let backend = MockBackend::new(1).with_frame(image::RgbImage::from_pixel(32, 24, image::Rgb([0, 200, 0])));
+        let manager = Arc::new(Mutex::new(WebcamManager::with_backend(Box::new(backend))));
+        let config = PreviewConfig {
+            bind_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            fps: 30,
+            max_viewers,
+            auth_token: Some(AuthToken::new(TOKEN).unwrap()),
+            ..Default::default()
+        };
+        PreviewServer::start(manager, config, &tokio::runtime::Handle::current()).unwrap()
+    }
+
+    async fn open(address: &str, request_line: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!("{}\r\nHost: {}\r\nAuthorization: Bearer {}\r\n\r\n", request_line, address, TOKEN);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        stream
+    }
+
+    /// Read until `marker` has been seen, or give up after a few seconds
+    async fn read_until(stream: &mut TcpStream, marker: &[u8]) -> Vec<u8> {
+        let mut received = Vec::new();
+        let mut chunk = [0u8; 4096];
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while !received.windows(marker.len()).any(|w| w == marker) {
+            let read = tokio::time::timeout_at(deadline, stream.read(&mut chunk)).await.unwrap().unwrap();
+            if read == 0 {
+                break;
+            }
+            received.extend_from_slice(&chunk[..read]);
+        }
+        received
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn streams_are_multipart_jpeg_frames() {
+        let preview = start(1);
+        let address = preview.info().address;
+
+        let mut stream = open(&address, "GET /stream HTTP/1.1").await;
+        // The response head, then the first part's header
+        let mut received = read_until(&mut stream, b"\r\n\r\n").await;
+        let head_end = received.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        let head = String::from_utf8_lossy(&received[..head_end]).into_owned();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+        assert!(head.contains(&format!("multipart/x-mixed-replace; boundary={}\r\n", BOUNDARY)), "{}", head);
+        received.drain(..head_end);
+        while !received.windows(4).any(|w| w == b"\r\n\r\n") {
+            received.extend(read_until(&mut stream, b"\r\n\r\n").await);
+        }
+
+        let part_end = received.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        let part = String::from_utf8_lossy(&received[..part_end]).into_owned();
+        let length = part
+            .strip_prefix(&format!("--{}\r\nContent-Type: image/jpeg\r\nContent-Length: ", BOUNDARY))
+            .and_then(|rest| rest.trim_end().parse::<usize>().ok())
+            .unwrap_or_else(|| panic!("unexpected part header {:?}", part));
+        let mut jpeg = received[part_end..].to_vec();
+        if jpeg.len() < length {
+            let mut rest = vec![0; length - jpeg.len()];
+            stream.read_exact(&mut rest).await.unwrap();
+            jpeg.extend(rest);
+        }
+        jpeg.truncate(length);
+        assert_eq!(&jpeg[..2], &[0xFF, 0xD8]);
+        let frame = image::load_from_memory(&jpeg).unwrap().into_rgb8();
+        assert_eq!(frame.dimensions(), (32, 24));
+        assert!(frame.get_pixel(16, 12)[1] > 180);
+        assert_eq!(preview.info().viewers, 1);
+
+        // The only viewer slot is taken
+        let mut second = open(&address, "GET /stream HTTP/1.1").await;
+        let refused = read_until(&mut second, b"\r\n").await;
+        assert!(refused.starts_with(b"HTTP/1.1 503 Service Unavailable"), "{}", String::from_utf8_lossy(&refused));
+
+        preview.stop();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn other_paths_and_methods_are_refused() {
+        let preview = start(1);
+        let address = preview.info().address;
+
+        let mut missing = open(&address, "GET /nothing-here HTTP/1.1").await;
+        assert!(read_until(&mut missing, b"\r\n").await.starts_with(b"HTTP/1.1 404 Not Found"));
+        let mut posted = open(&address, "POST /stream HTTP/1.1").await;
+        assert!(read_until(&mut posted, b"\r\n").await.starts_with(b"HTTP/1.1 405 Method Not Allowed"));
+        let mut page = open(&address, "GET / HTTP/1.1").await;
+        let mut html = Vec::new();
+        page.read_to_end(&mut html).await.unwrap();
+        assert!(String::from_utf8_lossy(&html).contains(&format!("/stream?token={}", TOKEN)));
+        assert_eq!(preview.info().viewers, 0);
+
+        preview.stop();
+    }
+}
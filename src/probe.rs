@@ -0,0 +1,144 @@
+//! Finding the snapshot URL of a remote webcam. `construct_webcam_url`
+//! can only guess one; probing tries the paths camera makes are known to
+//! serve stills on, the maker's own first, until one answers with an image.
+
+use crate::classify::{classify, Vendor};
+use crate::http_auth::WebcamCredentials;
+use crate::shodan::{sanitize_url, RemoteImage, RemoteWebcam, ShodanClient, ShodanError};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// Paths tried on any webcam after its maker's, most common first
+const GENERIC_SNAPSHOT_PATHS: &[&str] = &[
+    "/snapshot.jpg",
+    "/image.jpg",
+    "/shot.jpg",
+    "/snap.jpg",
+    "/cgi-bin/snapshot.cgi",
+    "/axis-cgi/jpg/image.cgi",
+    "/ISAPI/Streaming/channels/101/picture",
+    "/image/jpeg.cgi",
+    "/jpg/image.jpg",
+    "/snapshot.cgi",
+    "/out.jpg",
+    "/",
+];
+
+/// Most requests a probe may send to one host
+pub const MAX_PROBE_REQUESTS: usize = 30;
+/// Most requests a probe may have in flight at once
+pub const MAX_PROBE_CONCURRENCY: usize = 8;
+
+/// Limits of one probe
+#[derive(Debug, Clone, Copy)]
+pub struct ProbeOptions {
+    /// Requests in flight at once
+    pub concurrency: usize,
+    /// Paths tried at most
+    pub max_requests: usize,
+    /// Time allowed for each request
+    pub timeout: Duration,
+}
+
+impl Default for ProbeOptions {
+    fn default() -> Self {
+        Self { concurrency: 4, max_requests: 12, timeout: Duration::from_secs(3) }
+    }
+}
+
+/// The snapshot URL a probe found
+#[derive(Debug, Clone)]
+pub struct ProbeResult {
+    pub url: String,
+    /// Time the request for `url` took
+    pub latency: Duration,
+    pub image: RemoteImage,
+    /// Requests finished before the image was found, including its own
+    pub attempts: usize,
+}
+
+/// Snapshot paths to try for a webcam whose product is `product`: the
+/// maker's first, then the common ones
+pub fn snapshot_paths(product: Option<&str>) -> Vec<&'static str> {
+    vendor_snapshot_paths(classify(product, "", 0).map(|classification| classification.vendor))
+}
+
+/// Snapshot paths to try for a webcam of `vendor`: its own first, then
+/// the common ones
+pub fn vendor_snapshot_paths(vendor: Option<Vendor>) -> Vec<&'static str> {
+    let mut paths: Vec<&'static str> = vendor.map(|vendor| vendor.snapshot_paths().to_vec()).unwrap_or_default();
+    for path in GENERIC_SNAPSHOT_PATHS {
+        if !paths.contains(path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// Base URL of a webcam: HTTPS on the usual TLS ports, else HTTP
+fn base_url(webcam: &RemoteWebcam) -> String {
+    let host = if webcam.ip.contains(':') { format!("[{}]", webcam.ip) } else { webcam.ip.clone() };
+    let scheme = if matches!(webcam.port, 443 | 8443) { "https" } else { "http" };
+    format!("{}://{}:{}", scheme, host, webcam.port)
+}
+
+impl ShodanClient {
+    /// Find a URL on `webcam`'s address and port that serves an image, by
+    /// trying known snapshot paths, those of its make first: as classified
+    /// when it was found, else as its `product` names it. Returns
+    /// as soon as one does. If none does and one wanted a login, that
+    /// error is returned, so the caller knows to ask for credentials. The
+    /// probe claims the host once against the cooldown, however many
+    /// paths it tries.
+    pub async fn probe_webcam(
+        &self,
+        webcam: &RemoteWebcam,
+        credentials: Option<&WebcamCredentials>,
+        options: &ProbeOptions,
+    ) -> Result<ProbeResult, ShodanError> {
+        let base = base_url(webcam);
+        self.check_target(&base).await?;
+        self.claim_host(&webcam.ip)?;
+        let paths = match &webcam.classification {
+            Some(classification) => vendor_snapshot_paths(Some(classification.vendor)),
+            None => snapshot_paths(webcam.product.as_deref()),
+        };
+        let urls: Vec<String> = paths
+            .iter()
+            .take(options.max_requests.clamp(1, MAX_PROBE_REQUESTS))
+            .map(|path| format!("{}{}", base, path))
+            .collect();
+        debug!("Probing {} snapshot paths on {}", urls.len(), sanitize_url(&base));
+
+        let mut attempts = 0;
+        let mut auth_error = None;
+        for batch in urls.chunks(options.concurrency.clamp(1, MAX_PROBE_CONCURRENCY)) {
+            // Dropping the set on return aborts the requests still running
+            let mut requests = tokio::task::JoinSet::new();
+            for url in batch {
+                let client = self.clone();
+                let credentials = credentials.cloned();
+                let url = url.clone();
+                let timeout = options.timeout;
+                requests.spawn(async move {
+                    let started = Instant::now();
+                    let result = client.fetch_image(&url, credentials.as_ref(), timeout).await;
+                    (url, result, started.elapsed())
+                });
+            }
+            while let Some(joined) = requests.join_next().await {
+                let Ok((url, result, latency)) = joined else { continue };
+                attempts += 1;
+                match result {
+                    Ok(image) => {
+                        info!("Found a snapshot URL for {} after {} request(s): {}", sanitize_url(&base), attempts, sanitize_url(&url));
+                        return Ok(ProbeResult { url, latency, image, attempts });
+                    }
+                    Err(e @ (ShodanError::AuthRequired { .. } | ShodanError::AuthRejected { .. })) => auth_error = Some(e),
+                    Err(e) => debug!("{}: {}", sanitize_url(&url), e),
+                }
+            }
+        }
+        Err(auth_error.unwrap_or(ShodanError::NoSnapshotUrl { tried: attempts }))
+    }
+}
@@ -0,0 +1,1279 @@
+//! MCP JSON-RPC dispatch over a `Transport`, stdio by default.
+//!
+//! mcpr's `Server` only routes `initialize`, `tools/list` and `tools/call`,
+//! so the server runs its own loop. Tool handlers keep mcpr's signature and
+//! tool definitions still use mcpr's schema types. Handler results are
+//! shaped into spec `CallToolResult`s by `call_tool_result`.
+//!
+//! Every tool call gets a `CancellationToken`, cancelled when the client
+//! sends `notifications/cancelled` for its request id. Handlers registered
+//! with `register_cancellable_tool_handler` receive it; the rest run to
+//! completion.
+//!
+//! Arguments are checked against the tool's `inputSchema` before its
+//! handler runs, so a misspelled or mistyped argument fails with an
+//! `INVALID_PARAMS` result naming it instead of being ignored.
+//!
+//! Tools given a timeout with `ServerConfig::with_timeout` run on a worker
+//! thread; when the timeout passes, the call is cancelled and the client
+//! gets a `TOOL_TIMEOUT` error instead of waiting on a wedged handler.
+//!
+//! `serve` runs each `tools/call` on a thread of its own, so calls
+//! overlap. Tools given a `RateLimit` with `ServerConfig::with_rate_limit`
+//! draw a token from a bucket per call, and `with_max_concurrent_calls`
+//! caps the handlers running at once, counting ones still running after
+//! their call timed out. Calls over either limit fail with `RATE_LIMITED`
+//! and a `retry_after_seconds` hint, without reaching their handler.
+//!
+//! Resources can be subscribed to with `resources/subscribe`; the
+//! subscription handler pushes `notifications/resources/updated` through a
+//! `Notifier`. Subscriptions still open when the client goes away are
+//! unsubscribed.
+//!
+//! `logging/setLevel` starts forwarding log events to the client, see
+//! `client_log`.
+//!
+//! A `tools/call` whose `_meta` carries a `progressToken` can be told of
+//! progress: handlers registered with `add_reporting_tool` get a
+//! `Progress` that queues `notifications/progress` for that token.
+//! Notifications are sent while the call still runs, and those it queued
+//! go out before its response.
+
+use base64::{engine::general_purpose, Engine as _};
+use crate::client_log::ClientLog;
+use crate::tool_error::{ErrorCode, ToolError};
+use crate::tool_schemas;
+use mcpr::error::MCPError;
+use mcpr::schema::common::Tool;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Protocol version reported when the client doesn't ask for one
+pub const DEFAULT_PROTOCOL_VERSION: &str = "2024-11-05";
+/// First protocol version with `structuredContent` in tool results
+pub const STRUCTURED_CONTENT_VERSION: &str = "2025-06-18";
+/// Fields a `CallToolResult` may have; handlers' other fields are moved
+/// into `structuredContent`
+const CALL_TOOL_RESULT_FIELDS: &[&str] = &["content", "isError", "structuredContent", "_meta"];
+
+// JSON-RPC error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+/// MCP error code for a resource URI that doesn't exist (or no longer exists)
+pub const RESOURCE_NOT_FOUND: i64 = -32002;
+/// How often `serve` sends queued notifications, also while a call runs
+const NOTIFICATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Arguments whose values are left out of logs
+const SECRET_ARGUMENTS: &[&str] = &["api_key", "token", "code", "username", "password"];
+
+type ToolHandler = Arc<dyn Fn(Value, &CancellationToken, &Progress) -> Result<Value, MCPError> + Send + Sync>;
+type RejectedCallHook = Box<dyn Fn(&str) + Send + Sync>;
+type ToolCallHook = Box<dyn Fn(&ToolCall) + Send + Sync>;
+type CallGate = Box<dyn Fn(&str) -> Result<(), ToolError> + Send + Sync>;
+type ResourceHandler = Box<dyn Fn(&str) -> Result<ResourceContents, RpcError> + Send + Sync>;
+type ResourceLister = Box<dyn Fn() -> Vec<Resource> + Send + Sync>;
+type SubscriptionHandler = Box<dyn Fn(&str, bool) -> Result<(), RpcError> + Send + Sync>;
+
+/// Carries JSON-RPC messages between the server and one client. `serve`
+/// waits for the next message on one thread while responses are sent from
+/// another, so a cancellation can arrive while a tool call runs.
+pub trait Transport: Send + Sync + 'static {
+    /// Next message from the client, or `None` once the client has gone
+    fn receive(&self) -> Result<Option<String>, MCPError>;
+
+    fn send(&self, message: &Value) -> Result<(), MCPError>;
+}
+
+/// Newline-delimited JSON-RPC over stdin and stdout
+pub struct StdioTransport {
+    stdin: std::io::Stdin,
+    stdout: std::io::Stdout,
+}
+
+impl StdioTransport {
+    pub fn new() -> Self {
+        Self {
+            stdin: std::io::stdin(),
+            stdout: std::io::stdout(),
+        }
+    }
+}
+
+impl Default for StdioTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for StdioTransport {
+    fn receive(&self) -> Result<Option<String>, MCPError> {
+        let mut line = String::new();
+        match self.stdin.read_line(&mut line) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(line.trim_end_matches(['\r', '\n']).to_string())),
+            Err(e) => Err(MCPError::Protocol(format!("Failed to read from stdin: {}", e))),
+        }
+    }
+
+    fn send(&self, message: &Value) -> Result<(), MCPError> {
+        let mut stdout = self.stdout.lock();
+        writeln!(stdout, "{}", message)
+            .and_then(|_| stdout.flush())
+            .map_err(|e| MCPError::Protocol(format!("Failed to write to stdout: {}", e)))
+    }
+}
+
+/// A JSON-RPC error returned to the client
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{message} ({code})")]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({ "code": self.code, "message": self.message })
+    }
+}
+
+/// Binary contents of a resource, returned by `resources/read`
+#[derive(Debug, Clone)]
+pub struct ResourceContents {
+    pub mime_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// A finished tool call, as passed to `Server::on_tool_call`
+pub struct ToolCall<'a> {
+    pub tool: &'a str,
+    /// Arguments with secrets such as API keys replaced
+    pub arguments: &'a Value,
+    /// The `CallToolResult`, or the error the handler failed with
+    pub result: Result<&'a Value, &'a MCPError>,
+    pub duration: Duration,
+}
+
+/// A concrete resource listed by `resources/list`
+#[derive(Debug, Clone)]
+pub struct Resource {
+    pub uri: String,
+    pub name: String,
+    pub description: String,
+    pub mime_type: String,
+}
+
+/// A URI template advertised by `resources/templates/list`
+#[derive(Debug, Clone)]
+pub struct ResourceTemplate {
+    pub uri_template: String,
+    pub name: String,
+    pub description: String,
+    pub mime_type: String,
+}
+
+/// Server name, version and tool definitions
+#[derive(Default)]
+pub struct ServerConfig {
+    name: String,
+    version: String,
+    tools: Vec<Tool>,
+    /// `outputSchema` per tool name; mcpr's `Tool` has no field for it
+    output_schemas: HashMap<String, Value>,
+    /// `annotations` per tool name, likewise missing from mcpr's `Tool`
+    annotations: HashMap<String, ToolAnnotations>,
+    /// Longest a call of each tool may run; tools without one have no limit
+    timeouts: HashMap<String, Duration>,
+    /// Calls allowed per tool over time; tools without one have no limit
+    rate_limits: HashMap<String, RateLimit>,
+    /// Most tool handlers running at once; unlimited when unset
+    max_concurrent_calls: Option<usize>,
+}
+
+impl ServerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn with_version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    pub fn with_tool(mut self, tool: Tool) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    /// Declare the schema of a tool's `structuredContent`
+    pub fn with_output_schema(mut self, tool: &str, schema: Value) -> Self {
+        self.output_schemas.insert(tool.to_string(), schema);
+        self
+    }
+
+    /// Declare hints about a tool's behaviour
+    pub fn with_annotations(mut self, tool: &str, annotations: ToolAnnotations) -> Self {
+        self.annotations.insert(tool.to_string(), annotations);
+        self
+    }
+
+    /// Fail calls of `tool` with `TOOL_TIMEOUT` once they run longer than `timeout`
+    pub fn with_timeout(mut self, tool: &str, timeout: Duration) -> Self {
+        self.timeouts.insert(tool.to_string(), timeout);
+        self
+    }
+
+    /// Fail calls of `tool` with `RATE_LIMITED` beyond `limit`
+    pub fn with_rate_limit(mut self, tool: &str, limit: RateLimit) -> Self {
+        self.rate_limits.insert(tool.to_string(), limit);
+        self
+    }
+
+    /// Fail calls with `RATE_LIMITED` while `max` handlers are running
+    pub fn with_max_concurrent_calls(mut self, max: usize) -> Self {
+        self.max_concurrent_calls = Some(max);
+        self
+    }
+}
+
+/// At most `calls` calls of a tool per `per_seconds`. Unused calls
+/// accumulate up to `calls`, so a burst of that many is allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub calls: u32,
+    pub per_seconds: u64,
+}
+
+impl RateLimit {
+    pub fn new(calls: u32, per: Duration) -> Self {
+        Self { calls, per_seconds: per.as_secs() }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.calls == 0 || self.per_seconds == 0 {
+            return Err("calls and per_seconds must both be positive".to_string());
+        }
+        Ok(())
+    }
+
+    /// Calls regained per second
+    fn refill_rate(&self) -> f64 {
+        self.calls as f64 / self.per_seconds as f64
+    }
+}
+
+/// Calls a tool has left under its `RateLimit`
+struct TokenBucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn full(limit: &RateLimit) -> Self {
+        Self { tokens: limit.calls as f64, refilled: Instant::now() }
+    }
+
+    /// Take a call, or say how many seconds until one is available
+    fn take(&mut self, limit: &RateLimit) -> Result<(), u64> {
+        let now = Instant::now();
+        let regained = now.duration_since(self.refilled).as_secs_f64() * limit.refill_rate();
+        self.tokens = (self.tokens + regained).min(limit.calls as f64);
+        self.refilled = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err((((1.0 - self.tokens) / limit.refill_rate()).ceil() as u64).max(1))
+        }
+    }
+}
+
+/// A running tool handler, counted until it returns
+struct RunningCall(Arc<AtomicUsize>);
+
+impl Drop for RunningCall {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Hints clients use to decide, e.g., whether a tool call needs approval.
+/// Hints left unset take the spec's defaults: not read-only, destructive,
+/// not idempotent and open-world.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>,
+}
+
+impl ToolAnnotations {
+    /// Only reads state of this server or host
+    pub fn read_only() -> Self {
+        Self { read_only_hint: Some(true), open_world_hint: Some(false), ..Self::default() }
+    }
+
+    /// Acts on this host, e.g. turns a camera on, without destroying anything
+    pub fn local_action() -> Self {
+        Self {
+            read_only_hint: Some(false),
+            destructive_hint: Some(false),
+            idempotent_hint: Some(false),
+            open_world_hint: Some(false),
+        }
+    }
+
+    pub fn idempotent(mut self) -> Self {
+        self.idempotent_hint = Some(true);
+        self
+    }
+
+    pub fn destructive(mut self) -> Self {
+        self.destructive_hint = Some(true);
+        self
+    }
+
+    /// Reaches hosts outside this machine, e.g. on the public internet
+    pub fn open_world(mut self) -> Self {
+        self.open_world_hint = Some(true);
+        self
+    }
+}
+
+/// Queues notifications for the client. Clones share the queue; `serve`
+/// sends what is queued every so often, and before each response.
+#[derive(Clone, Default)]
+pub struct Notifier {
+    queue: Arc<Mutex<Vec<Value>>>,
+}
+
+impl Notifier {
+    pub fn notify(&self, method: &str, params: Option<Value>) {
+        let mut notification = json!({ "jsonrpc": "2.0", "method": method });
+        if let Some(params) = params {
+            notification["params"] = params;
+        }
+        self.queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(notification);
+    }
+
+    /// Tell a subscribed client that `uri` has changed
+    pub fn resource_updated(&self, uri: &str) {
+        self.notify("notifications/resources/updated", Some(json!({ "uri": uri })));
+    }
+
+    fn take(&self) -> Vec<Value> {
+        std::mem::take(&mut *self.queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+}
+
+/// Progress of one tool call, for the client that asked for it with a
+/// `progressToken`. Without one, reports are dropped.
+#[derive(Clone, Default)]
+pub struct Progress {
+    notifier: Notifier,
+    token: Option<Value>,
+    /// Reports so far; each notification's `progress` counts up
+    reported: Arc<AtomicUsize>,
+}
+
+impl Progress {
+    pub fn new(notifier: Notifier, token: Option<Value>) -> Self {
+        Self { notifier, token, reported: Arc::new(AtomicUsize::new(0)) }
+    }
+
+    /// Whether the client asked to be told of progress
+    pub fn is_requested(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// Queue a `notifications/progress` with `message` and the fields of
+    /// `data`, an object, alongside `progressToken` and `progress`
+    pub fn report(&self, message: impl Into<String>, data: Value) {
+        let Some(token) = &self.token else {
+            return;
+        };
+        let progress = self.reported.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut params = json!({ "progressToken": token, "progress": progress, "message": message.into() });
+        if let (Value::Object(params), Value::Object(data)) = (&mut params, data) {
+            params.extend(data);
+        }
+        self.notifier.notify("notifications/progress", Some(params));
+    }
+}
+
+/// Tools a server offers and their handlers. Clones share the same tools,
+/// so a handler holding one can add or remove tools while the server runs.
+/// Once the client has initialized, each change queues a
+/// `notifications/tools/list_changed` for it.
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Arc<RwLock<RegisteredTools>>,
+    notifications: Notifier,
+    /// Whether changes are announced; set by `initialize`
+    notify: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct RegisteredTools {
+    /// Definitions in the order `tools/list` returns them
+    definitions: Vec<Tool>,
+    handlers: HashMap<String, ToolHandler>,
+}
+
+impl ToolRegistry {
+    /// Offer `tool`, replacing any tool with the same name
+    pub fn add_tool<F>(&self, tool: Tool, handler: F)
+    where
+        F: Fn(Value) -> Result<Value, MCPError> + Send + Sync + 'static,
+    {
+        self.add_cancellable_tool(tool, move |params, _: &CancellationToken| handler(params));
+    }
+
+    /// Offer `tool` with a handler that stops early when its token is cancelled
+    pub fn add_cancellable_tool<F>(&self, tool: Tool, handler: F)
+    where
+        F: Fn(Value, &CancellationToken) -> Result<Value, MCPError> + Send + Sync + 'static,
+    {
+        self.add_reporting_tool(tool, move |params, cancel: &CancellationToken, _: &Progress| handler(params, cancel));
+    }
+
+    /// Offer `tool` with a cancellable handler that reports its progress
+    pub fn add_reporting_tool<F>(&self, tool: Tool, handler: F)
+    where
+        F: Fn(Value, &CancellationToken, &Progress) -> Result<Value, MCPError> + Send + Sync + 'static,
+    {
+        {
+            let mut tools = self.tools.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+            tools.handlers.insert(tool.name.clone(), Arc::new(handler));
+            match tools.definitions.iter().position(|t| t.name == tool.name) {
+                Some(i) => tools.definitions[i] = tool,
+                None => tools.definitions.push(tool),
+            }
+        }
+        self.changed();
+    }
+
+    /// Stop offering the tool `name`; returns whether it was offered
+    pub fn remove_tool(&self, name: &str) -> bool {
+        let removed = {
+            let mut tools = self.tools.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+            tools.handlers.remove(name);
+            let before = tools.definitions.len();
+            tools.definitions.retain(|t| t.name != name);
+            tools.definitions.len() != before
+        };
+        if removed {
+            self.changed();
+        }
+        removed
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tools.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+            .definitions.iter().any(|t| t.name == name)
+    }
+
+    /// Check `arguments` against the declared input schema of tool `name`
+    fn check_arguments(&self, name: &str, arguments: &Value) -> Result<(), ToolError> {
+        let tools = self.tools.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match tools.definitions.iter().find(|t| t.name == name) {
+            Some(tool) => check_arguments(tool, arguments),
+            None => Ok(()),
+        }
+    }
+
+    fn handler(&self, name: &str) -> Option<ToolHandler> {
+        self.tools.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+            .handlers.get(name).cloned()
+    }
+
+    fn changed(&self) {
+        if self.notify.load(Ordering::SeqCst) {
+            debug!("Tool list changed, notifying the client");
+            self.notifications.notify("notifications/tools/list_changed", None);
+        }
+    }
+}
+
+pub struct Server {
+    config: ServerConfig,
+    tools: ToolRegistry,
+    /// Resource handlers keyed by URI prefix, e.g. `webcam://capture/`
+    resource_handlers: Vec<(String, ResourceHandler)>,
+    resource_templates: Vec<ResourceTemplate>,
+    /// Called on each `resources/list`, since resources come and go
+    resource_listers: Vec<ResourceLister>,
+    /// Subscription handlers keyed by URI prefix
+    subscription_handlers: Vec<(String, SubscriptionHandler)>,
+    /// URIs the client is subscribed to
+    subscriptions: Mutex<BTreeSet<String>>,
+    /// Version agreed in `initialize`
+    protocol_version: Mutex<String>,
+    /// Tokens of running tool calls, keyed by their request id as JSON
+    in_flight: Mutex<HashMap<String, CancellationToken>>,
+    /// Rate limit buckets, keyed by tool name
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+    /// Tool handlers running, including ones whose call timed out
+    running: Arc<AtomicUsize>,
+    /// Told the tool name of each call rejected for its arguments
+    on_invalid_arguments: Option<RejectedCallHook>,
+    /// Told the tool name of each call rejected by a rate limit
+    on_rate_limited: Option<RejectedCallHook>,
+    /// Told about every call that reached its handler
+    on_tool_call: Option<ToolCallHook>,
+    /// Asked before each call whether it may run
+    call_gate: Option<CallGate>,
+}
+
+impl Server {
+    pub fn new(mut config: ServerConfig) -> Self {
+        let tools = ToolRegistry::default();
+        tools.tools.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+            .definitions = std::mem::take(&mut config.tools);
+        Self {
+            config,
+            tools,
+            resource_handlers: Vec::new(),
+            resource_templates: Vec::new(),
+            resource_listers: Vec::new(),
+            subscription_handlers: Vec::new(),
+            subscriptions: Mutex::new(BTreeSet::new()),
+            protocol_version: Mutex::new(DEFAULT_PROTOCOL_VERSION.to_string()),
+            in_flight: Mutex::new(HashMap::new()),
+            buckets: Mutex::new(HashMap::new()),
+            running: Arc::new(AtomicUsize::new(0)),
+            on_invalid_arguments: None,
+            on_rate_limited: None,
+            on_tool_call: None,
+            call_gate: None,
+        }
+    }
+
+    pub fn register_tool_handler<F>(&mut self, name: &str, handler: F) -> Result<(), MCPError>
+    where
+        F: Fn(Value) -> Result<Value, MCPError> + Send + Sync + 'static,
+    {
+        self.register_cancellable_tool_handler(name, move |params, _: &CancellationToken| handler(params))
+    }
+
+    /// Register a handler that gets the call's `CancellationToken`. Long
+    /// handlers should check it and return early once it is cancelled.
+    pub fn register_cancellable_tool_handler<F>(&mut self, name: &str, handler: F) -> Result<(), MCPError>
+    where
+        F: Fn(Value, &CancellationToken) -> Result<Value, MCPError> + Send + Sync + 'static,
+    {
+        if !self.tools.contains(name) {
+            return Err(MCPError::Protocol(format!("Tool '{}' is not defined in the server config", name)));
+        }
+        self.tools.tools.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+            .handlers.insert(name.to_string(), Arc::new(move |params, cancel: &CancellationToken, _: &Progress| handler(params, cancel)));
+        Ok(())
+    }
+
+    /// Call `hook` with the tool name whenever a call is rejected because
+    /// its arguments don't match the tool's input schema, e.g. to count it
+    pub fn on_invalid_arguments<F>(&mut self, hook: F)
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.on_invalid_arguments = Some(Box::new(hook));
+    }
+
+    /// Call `hook` with the tool name whenever a call is rejected with
+    /// `RATE_LIMITED`
+    pub fn on_rate_limited<F>(&mut self, hook: F)
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.on_rate_limited = Some(Box::new(hook));
+    }
+
+    /// Ask `gate` with the tool name before each call with valid arguments
+    /// runs; the call fails with the error it returns, e.g. until the user
+    /// has consented
+    pub fn gate_tool_calls<F>(&mut self, gate: F)
+    where
+        F: Fn(&str) -> Result<(), ToolError> + Send + Sync + 'static,
+    {
+        self.call_gate = Some(Box::new(gate));
+    }
+
+    /// Call `hook` after every tool call that reached its handler, whether
+    /// it succeeded or not, e.g. to audit it
+    pub fn on_tool_call<F>(&mut self, hook: F)
+    where
+        F: Fn(&ToolCall) + Send + Sync + 'static,
+    {
+        self.on_tool_call = Some(Box::new(hook));
+    }
+
+    /// Handle on the server's tools, for adding and removing tools at runtime
+    pub fn tool_registry(&self) -> ToolRegistry {
+        self.tools.clone()
+    }
+
+    /// Handle for queueing notifications to the client
+    pub fn notifier(&self) -> Notifier {
+        self.tools.notifications.clone()
+    }
+
+    /// Notifications queued for the client since the last call, e.g.
+    /// `notifications/tools/list_changed`. `serve` sends them itself;
+    /// callers of `handle_message` send them themselves.
+    pub fn take_notifications(&self) -> Vec<Value> {
+        self.tools.notifications.take()
+    }
+
+    /// Handle `resources/read` for every URI starting with `template`'s
+    /// text before the first `{`. The handler gets the full URI.
+    pub fn register_resource_handler<F>(&mut self, template: ResourceTemplate, handler: F)
+    where
+        F: Fn(&str) -> Result<ResourceContents, RpcError> + Send + Sync + 'static,
+    {
+        let prefix = template.uri_template.split('{').next().unwrap_or_default().to_string();
+        self.resource_handlers.push((prefix, Box::new(handler)));
+        self.resource_templates.push(template);
+    }
+
+    /// Add the resources `lister` returns to every `resources/list`
+    pub fn register_resource_lister<F>(&mut self, lister: F)
+    where
+        F: Fn() -> Vec<Resource> + Send + Sync + 'static,
+    {
+        self.resource_listers.push(Box::new(lister));
+    }
+
+    /// Handle `resources/subscribe` and `resources/unsubscribe` for every
+    /// URI starting with `prefix`. The handler gets the URI and whether to
+    /// subscribe; it is called once per change, and to unsubscribe whatever
+    /// is left when the client goes away.
+    pub fn register_subscription_handler<F>(&mut self, prefix: &str, handler: F)
+    where
+        F: Fn(&str, bool) -> Result<(), RpcError> + Send + Sync + 'static,
+    {
+        self.subscription_handlers.push((prefix.to_string(), Box::new(handler)));
+    }
+
+    /// Serve requests from stdin until it is closed. Blocks the calling thread.
+    pub fn start(&self) -> Result<(), MCPError> {
+        self.serve(StdioTransport::new())
+    }
+
+    /// Serve requests from `transport` until the client goes away. Blocks
+    /// the calling thread.
+    ///
+    /// Each `tools/call` runs on a thread of its own, so calls overlap and
+    /// a slow one doesn't hold up the next. Other requests are handled in
+    /// order once the calls received before them have answered. A reader
+    /// thread keeps receiving meanwhile so that `notifications/cancelled`
+    /// reaches the tool call it is about; when the client goes away,
+    /// running calls are cancelled and resource subscriptions dropped.
+    /// Notifications are sent before each response, and while idle as they
+    /// are queued.
+    pub fn serve<T: Transport>(&self, transport: T) -> Result<(), MCPError> {
+        let transport = &transport;
+        let (queue, messages) = std::sync::mpsc::channel::<String>();
+        // Notifications queued while a call runs, e.g. its progress, are
+        // sent as they come; the lock keeps them and responses whole
+        let sending = Mutex::new(());
+        let flush = || -> Result<(), MCPError> {
+            for notification in self.take_notifications() {
+                transport.send(&notification)?;
+            }
+            Ok(())
+        };
+        let respond = |response: Option<Value>| -> Result<(), MCPError> {
+            let _sending = sending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            // A call's notifications come before its response
+            flush()?;
+            if let Some(response) = response {
+                transport.send(&response)?;
+            }
+            Ok(())
+        };
+        let serving = AtomicBool::new(true);
+
+        std::thread::scope(|scope| {
+            let reader = scope.spawn(move || -> Result<(), MCPError> {
+                let received = loop {
+                    let message = match transport.receive() {
+                        Ok(Some(message)) => message,
+                        Ok(None) => break Ok(()),
+                        Err(e) => break Err(e),
+                    };
+                    if message.trim().is_empty() {
+                        continue;
+                    }
+                    if is_cancellation(&message) {
+                        self.handle_message(&message);
+                    } else if queue.send(message).is_err() {
+                        // The serving loop stopped after a failed send
+                        break Ok(());
+                    }
+                };
+                self.cancel_all();
+                received
+            });
+
+            let pump = scope.spawn(|| -> Result<(), MCPError> {
+                while serving.load(Ordering::SeqCst) {
+                    std::thread::sleep(NOTIFICATION_POLL_INTERVAL);
+                    let _sending = sending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                    flush()?;
+                }
+                Ok(())
+            });
+
+            let served = (|| -> Result<(), MCPError> {
+                let mut calls = Vec::new();
+                loop {
+                    let response = match messages.recv_timeout(NOTIFICATION_POLL_INTERVAL) {
+                        Ok(message) if is_tool_call(&message) => {
+                            calls.push(scope.spawn(move || respond(self.handle_message(&message))));
+                            None
+                        }
+                        Ok(message) => {
+                            join_calls(&mut calls, true)?;
+                            self.handle_message(&message)
+                        }
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => return join_calls(&mut calls, true),
+                    };
+                    join_calls(&mut calls, false)?;
+                    respond(response)?;
+                }
+            })();
+            serving.store(false, Ordering::SeqCst);
+            let pumped = pump.join()
+                .unwrap_or_else(|_| Err(MCPError::Protocol("Notification sender panicked".to_string())));
+            let served = served.and(pumped);
+            drop(messages);
+            // After the last queued request, which may have subscribed
+            self.unsubscribe_all();
+            ClientLog::global().disable();
+
+            let received = reader.join()
+                .unwrap_or_else(|_| Err(MCPError::Protocol("Transport reader panicked".to_string())));
+            served.and(received)
+        })?;
+
+        info!("Client disconnected, shutting down");
+        Ok(())
+    }
+
+    /// Handle one JSON-RPC message; returns the response to send, if any
+    pub fn handle_message(&self, message: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(message) {
+            Ok(request) => request,
+            Err(e) => {
+                warn!("Failed to parse message: {}", e);
+                return Some(error_response(Value::Null, &RpcError::new(PARSE_ERROR, e.to_string())));
+            }
+        };
+
+        let method = match request.get("method").and_then(|m| m.as_str()) {
+            Some(method) => method,
+            None => {
+                // Responses to server-initiated requests aren't used
+                let id = request.get("id").cloned()?;
+                return Some(error_response(id, &RpcError::new(INVALID_REQUEST, "Missing method")));
+            }
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+        // Notifications get no response
+        let id = match request.get("id") {
+            Some(id) => id.clone(),
+            None => {
+                debug!("Received notification {}", method);
+                if method == "notifications/cancelled" {
+                    self.cancel_request(&params);
+                }
+                return None;
+            }
+        };
+
+        debug!("Handling {} request", method);
+        let result = match method {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => self.list_tools(),
+            "tools/call" => self.call_tool(&id, &params),
+            "resources/list" => Ok(self.list_resources()),
+            "resources/templates/list" => Ok(self.list_resource_templates()),
+            "resources/read" => self.read_resource(&params),
+            "resources/subscribe" => self.subscribe(&params, true),
+            "resources/unsubscribe" => self.subscribe(&params, false),
+            "logging/setLevel" => self.set_log_level(&params),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+        };
+
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => {
+                debug!("{} failed: {}", method, e);
+                error_response(id, &e)
+            }
+        })
+    }
+
+    fn initialize(&self, params: &Value) -> Value {
+        let protocol_version = params.get("protocolVersion")
+            .and_then(|v| v.as_str())
+            .unwrap_or(DEFAULT_PROTOCOL_VERSION);
+        *self.protocol_version.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = protocol_version.to_string();
+        self.tools.notify.store(true, Ordering::SeqCst);
+
+        json!({
+            "protocolVersion": protocol_version,
+            "capabilities": {
+                "tools": { "listChanged": true },
+                "resources": { "subscribe": !self.subscription_handlers.is_empty() },
+                "logging": {}
+            },
+            "serverInfo": {
+                "name": self.config.name,
+                "version": self.config.version
+            }
+        })
+    }
+
+    fn list_tools(&self) -> Result<Value, RpcError> {
+        let registered = self.tools.tools.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut tools = Vec::with_capacity(registered.definitions.len());
+        for tool in &registered.definitions {
+            let mut definition = serde_json::to_value(tool)
+                .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+            if let Some(schema) = self.config.output_schemas.get(&tool.name) {
+                definition["outputSchema"] = schema.clone();
+            }
+            if let Some(annotations) = self.config.annotations.get(&tool.name) {
+                definition["annotations"] = json!(annotations);
+            }
+            tools.push(definition);
+        }
+        Ok(json!({ "tools": tools }))
+    }
+
+    /// Cancel the tool call named by a `notifications/cancelled`. Requests
+    /// that already finished or are unknown are ignored, as the spec asks.
+    fn cancel_request(&self, params: &Value) {
+        let Some(id) = params.get("requestId") else {
+            warn!("Ignoring cancellation without a requestId");
+            return;
+        };
+        let reason = params.get("reason").and_then(|r| r.as_str()).unwrap_or("no reason given");
+        match self.in_flight.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).get(&id.to_string()) {
+            Some(token) => {
+                info!("Client cancelled request {}: {}", id, reason);
+                token.cancel();
+            }
+            None => debug!("Cancellation for request {} that is not running", id),
+        }
+    }
+
+    /// Subscribe to or unsubscribe from a resource. Repeating either is a
+    /// no-op, so the handler only sees changes.
+    fn subscribe(&self, params: &Value, subscribe: bool) -> Result<Value, RpcError> {
+        let uri = params.get("uri")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing resource uri"))?;
+        let handler = self.subscription_handlers.iter()
+            .find(|(prefix, _)| uri.starts_with(prefix.as_str()))
+            .map(|(_, handler)| handler)
+            .ok_or_else(|| RpcError::new(RESOURCE_NOT_FOUND, format!("Resource not found: {}", uri)))?;
+
+        let mut subscriptions = self.subscriptions.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if subscriptions.contains(uri) != subscribe {
+            handler(uri, subscribe)?;
+            if subscribe {
+                info!("Client subscribed to {}", uri);
+                subscriptions.insert(uri.to_string());
+            } else {
+                info!("Client unsubscribed from {}", uri);
+                subscriptions.remove(uri);
+            }
+        }
+        Ok(json!({}))
+    }
+
+    /// Forward server log events at the requested level and above
+    fn set_log_level(&self, params: &Value) -> Result<Value, RpcError> {
+        let level = params.get("level")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing log level"))?;
+        ClientLog::global().set_level(level, self.notifier())
+            .map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+        info!("Forwarding {} and more severe log events to the client", level);
+        Ok(json!({}))
+    }
+
+    /// Drop every resource subscription, e.g. once the client has gone
+    fn unsubscribe_all(&self) {
+        let subscriptions: Vec<String> = self.subscriptions.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .cloned()
+            .collect();
+        for uri in subscriptions {
+            if let Err(e) = self.subscribe(&json!({ "uri": uri }), false) {
+                warn!("Failed to unsubscribe from {}: {}", uri, e);
+            }
+        }
+    }
+
+    /// Count a call of `tool` as running, unless the cap on concurrent
+    /// calls or the tool's rate limit has been reached
+    fn admit(&self, tool: &str) -> Result<RunningCall, ToolError> {
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        let call = RunningCall(Arc::clone(&self.running));
+        if let Some(max) = self.config.max_concurrent_calls {
+            if running > max {
+                warn!("Rejected {} call: {} tool calls already running", tool, max);
+                return Err(ToolError::new(ErrorCode::RateLimited, format!("{} tool calls are already running", max))
+                    .with_details(json!({ "tool": tool, "retry_after_seconds": 1, "max_concurrent_calls": max })));
+            }
+        }
+
+        if let Some(limit) = self.config.rate_limits.get(tool) {
+            let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let bucket = buckets.entry(tool.to_string()).or_insert_with(|| TokenBucket::full(limit));
+            if let Err(retry_after) = bucket.take(limit) {
+                warn!("Rejected {} call: rate limit of {} per {} s reached", tool, limit.calls, limit.per_seconds);
+                return Err(ToolError::new(
+                    ErrorCode::RateLimited,
+                    format!("{} is limited to {} call(s) per {} s; retry in {} s", tool, limit.calls, limit.per_seconds, retry_after),
+                )
+                .with_details(json!({
+                    "tool": tool,
+                    "retry_after_seconds": retry_after,
+                    "calls": limit.calls,
+                    "per_seconds": limit.per_seconds
+                })));
+            }
+        }
+        Ok(call)
+    }
+
+    /// Cancel every running tool call, e.g. once the client has gone
+    fn cancel_all(&self) {
+        for token in self.in_flight.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).values() {
+            token.cancel();
+        }
+    }
+
+    fn call_tool(&self, id: &Value, params: &Value) -> Result<Value, RpcError> {
+        let name = params.get("name")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing tool name"))?;
+        // Cloned out of the registry so the handler may change the tools
+        let handler = self.tools.handler(name)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Unknown tool: {}", name)))?;
+
+        let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        if let Err(e) = self.tools.check_arguments(name, &arguments) {
+            debug!("Rejected {} call: {}", name, e.message);
+            if let Some(hook) = &self.on_invalid_arguments {
+                hook(name);
+            }
+            let message = e.message.clone();
+            return Ok(call_tool_result(e.into_response(message), &self.protocol_version()));
+        }
+
+        if let Some(Err(e)) = self.call_gate.as_ref().map(|gate| gate(name)) {
+            debug!("Gated {} call: {}", name, e.message);
+            let message = e.message.clone();
+            return Ok(call_tool_result(e.into_response(message), &self.protocol_version()));
+        }
+
+        let running = match self.admit(name) {
+            Ok(running) => running,
+            Err(e) => {
+                if let Some(hook) = &self.on_rate_limited {
+                    hook(name);
+                }
+                let message = e.message.clone();
+                return Ok(call_tool_result(e.into_response(message), &self.protocol_version()));
+            }
+        };
+
+        let cancel = CancellationToken::new();
+        let progress = Progress::new(self.notifier(), params.pointer("/_meta/progressToken").cloned());
+        let key = id.to_string();
+        let logged_arguments = self.on_tool_call.as_ref().map(|_| redacted(&arguments));
+        let started = Instant::now();
+        self.in_flight.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(key.clone(), cancel.clone());
+        let result = match self.config.timeouts.get(name) {
+            Some(&timeout) => call_with_timeout(name, handler, arguments, &cancel, &progress, timeout, running),
+            None => handler(arguments, &cancel, &progress),
+        };
+        self.in_flight.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(&key);
+        let result = result.map(|result| call_tool_result(result, &self.protocol_version()));
+
+        if let (Some(hook), Some(arguments)) = (&self.on_tool_call, &logged_arguments) {
+            hook(&ToolCall { tool: name, arguments, result: result.as_ref(), duration: started.elapsed() });
+        }
+        let result = result.map_err(|e| {
+            error!("Tool {} failed: {}", name, e);
+            RpcError::new(INTERNAL_ERROR, e.to_string())
+        })?;
+
+        // Catch handlers drifting from their declared schema during development
+        if cfg!(debug_assertions) && result["isError"] != true {
+            if let Some(schema) = self.config.output_schemas.get(name) {
+                if let Err(e) = tool_schemas::validate(schema, &result["structuredContent"]) {
+                    error!("Tool {} result does not match its output schema: {}", name, e);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn protocol_version(&self) -> String {
+        self.protocol_version.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    fn list_resources(&self) -> Value {
+        let resources: Vec<Value> = self.resource_listers.iter().flat_map(|lister| lister()).map(|r| json!({
+            "uri": r.uri,
+            "name": r.name,
+            "description": r.description,
+            "mimeType": r.mime_type
+        })).collect();
+        json!({ "resources": resources })
+    }
+
+    fn list_resource_templates(&self) -> Value {
+        let templates: Vec<Value> = self.resource_templates.iter().map(|t| json!({
+            "uriTemplate": t.uri_template,
+            "name": t.name,
+            "description": t.description,
+            "mimeType": t.mime_type
+        })).collect();
+        json!({ "resourceTemplates": templates })
+    }
+
+    fn read_resource(&self, params: &Value) -> Result<Value, RpcError> {
+        let uri = params.get("uri")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing resource uri"))?;
+        let handler = self.resource_handlers.iter()
+            .find(|(prefix, _)| uri.starts_with(prefix.as_str()))
+            .map(|(_, handler)| handler)
+            .ok_or_else(|| RpcError::new(RESOURCE_NOT_FOUND, format!("Resource not found: {}", uri)))?;
+
+        let contents = handler(uri)?;
+        // JSON and text go out as `text`, anything else base64-encoded as `blob`
+        let textual = contents.mime_type == "application/json" || contents.mime_type.starts_with("text/");
+        let text = if textual { std::str::from_utf8(&contents.bytes).ok() } else { None };
+        let body = match text {
+            Some(text) => json!({ "uri": uri, "mimeType": contents.mime_type, "text": text }),
+            None => json!({ "uri": uri, "mimeType": contents.mime_type, "blob": general_purpose::STANDARD.encode(&contents.bytes) }),
+        };
+        Ok(json!({ "contents": [body] }))
+    }
+}
+
+/// Shape a tool handler's result as an MCP `CallToolResult`. Fields other
+/// than the standard ones are moved into `structuredContent`. Clients on
+/// protocol versions without structured content also get it as a JSON text
+/// block, since they only read `content`.
+pub fn call_tool_result(result: Value, protocol_version: &str) -> Value {
+    let mut fields = match result {
+        Value::Object(fields) => fields,
+        other => Map::from_iter([("structuredContent".to_string(), json!({ "result": other }))]),
+    };
+
+    let mut structured = match fields.remove("structuredContent") {
+        Some(Value::Object(structured)) => structured,
+        Some(other) => Map::from_iter([("result".to_string(), other)]),
+        None => Map::new(),
+    };
+    let extra: Vec<String> = fields.keys()
+        .filter(|key| !CALL_TOOL_RESULT_FIELDS.contains(&key.as_str()))
+        .cloned()
+        .collect();
+    for key in extra {
+        if let Some(value) = fields.remove(&key) {
+            structured.insert(key, value);
+        }
+    }
+
+    let mut content = match fields.remove("content") {
+        Some(Value::Array(content)) => content,
+        _ => Vec::new(),
+    };
+    if !structured.is_empty() {
+        let structured = Value::Object(structured);
+        if protocol_version < STRUCTURED_CONTENT_VERSION {
+            content.push(json!({ "type": "text", "text": structured.to_string() }));
+        }
+        fields.insert("structuredContent".to_string(), structured);
+    }
+    fields.insert("content".to_string(), Value::Array(content));
+
+    Value::Object(fields)
+}
+
+/// Check tool call arguments against the tool's `inputSchema`: required
+/// arguments are present, every argument is declared and each value has
+/// the declared type. Optional arguments may be null, which handlers treat
+/// as absent.
+fn check_arguments(tool: &Tool, arguments: &Value) -> Result<(), ToolError> {
+    let fields = arguments.as_object().ok_or_else(|| {
+        ToolError::invalid_params(format!("Arguments of {} must be an object, got {}", tool.name, arguments))
+    })?;
+    let no_properties = HashMap::new();
+    let properties = tool.input_schema.properties.as_ref().unwrap_or(&no_properties);
+    let required = tool.input_schema.required.as_deref().unwrap_or_default();
+
+    for name in required {
+        if fields.get(name).map_or(true, Value::is_null) {
+            return Err(ToolError::invalid_params(format!("Missing required argument '{}' of {}", name, tool.name))
+                .with_details(json!({ "field": name, "expected": properties.get(name) })));
+        }
+    }
+
+    for (name, value) in fields {
+        let Some(schema) = properties.get(name) else {
+            let mut known: Vec<&str> = properties.keys().map(String::as_str).collect();
+            known.sort_unstable();
+            let suggestion = known.iter().copied().find(|known| normalized(known) == normalized(name));
+            let hint = match suggestion {
+                Some(suggestion) => format!("did you mean '{}'?", suggestion),
+                None if known.is_empty() => "it takes no arguments".to_string(),
+                None => format!("expected one of: {}", known.join(", ")),
+            };
+            return Err(ToolError::invalid_params(format!("Unknown argument '{}' for {}; {}", name, tool.name, hint))
+                .with_details(json!({ "field": name, "suggestion": suggestion, "expected": { "properties": known } })));
+        };
+        if value.is_null() && !required.contains(name) {
+            continue;
+        }
+        tool_schemas::validate_at(schema, value, name).map_err(|e| {
+            ToolError::invalid_params(format!("Invalid argument for {}: {}", tool.name, e))
+                .with_details(json!({ "field": name, "expected": schema }))
+        })?;
+    }
+    Ok(())
+}
+
+/// Argument name ignoring case, `_` and `-`, to spot e.g. `cameraIndex`
+/// meaning `camera_index`
+fn normalized(name: &str) -> String {
+    name.chars().filter(|c| *c != '_' && *c != '-').flat_map(char::to_lowercase).collect()
+}
+
+/// Run `handler` on a worker thread and stop waiting for it after
+/// `timeout`. A call that times out is cancelled; a handler that ignores
+/// its token keeps running in the background until it returns, and counts
+/// as `running` until then.
+fn call_with_timeout(
+    name: &str,
+    handler: ToolHandler,
+    arguments: Value,
+    cancel: &CancellationToken,
+    progress: &Progress,
+    timeout: Duration,
+    running: RunningCall,
+) -> Result<Value, MCPError> {
+    let logged_arguments = redacted(&arguments);
+    let worker_cancel = cancel.clone();
+    let progress = progress.clone();
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::Builder::new()
+        .name(format!("tool-{}", name))
+        .spawn(move || {
+            let _running = running;
+            let _ = tx.send(handler(arguments, &worker_cancel, &progress));
+        })
+        .map_err(|e| MCPError::Protocol(format!("Failed to start tool {}: {}", name, e)))?;
+
+    match rx.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => {
+            error!("Tool {} timed out after {:?} with arguments {}", name, timeout, logged_arguments);
+            cancel.cancel();
+            let timeout_ms = timeout.as_millis() as u64;
+            Ok(ToolError::new(ErrorCode::ToolTimeout, format!("{} did not finish within {} ms", name, timeout_ms))
+                .with_details(json!({ "tool": name, "timeout_ms": timeout_ms }))
+                .into_response(format!("Tool {} timed out after {:.1} s", name, timeout.as_secs_f64())))
+        }
+        Err(RecvTimeoutError::Disconnected) => Err(MCPError::Protocol(format!("Tool {} panicked", name))),
+    }
+}
+
+/// Tool arguments for logging, with secrets such as API keys replaced
+fn redacted(arguments: &Value) -> Value {
+    let mut arguments = arguments.clone();
+    if let Some(fields) = arguments.as_object_mut() {
+        for (name, value) in fields.iter_mut() {
+            if SECRET_ARGUMENTS.contains(&name.as_str()) {
+                *value = json!("<redacted>");
+            }
+        }
+    }
+    arguments
+}
+
+/// Join the tool calls `serve` started that have answered, or with `all`
+/// wait for every one; gives the first failure to send a response
+fn join_calls(calls: &mut Vec<std::thread::ScopedJoinHandle<'_, Result<(), MCPError>>>, all: bool) -> Result<(), MCPError> {
+    let mut joined = Ok(());
+    let mut i = 0;
+    while i < calls.len() {
+        if all || calls[i].is_finished() {
+            let call = calls.swap_remove(i).join()
+                .unwrap_or_else(|_| Err(MCPError::Protocol("Tool call panicked".to_string())));
+            joined = joined.and(call);
+        } else {
+            i += 1;
+        }
+    }
+    joined
+}
+
+/// Whether `message` is a `tools/call` request, which `serve` runs
+/// alongside other calls
+fn is_tool_call(message: &str) -> bool {
+    serde_json::from_str::<Value>(message)
+        .is_ok_and(|message| message["method"] == "tools/call" && message.get("id").is_some())
+}
+
+/// Whether `message` is a `notifications/cancelled`, which must not wait
+/// behind the request it cancels
+fn is_cancellation(message: &str) -> bool {
+    serde_json::from_str::<Value>(message)
+        .is_ok_and(|message| message["method"] == "notifications/cancelled" && message.get("id").is_none())
+}
+
+fn error_response(id: Value, error: &RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": error.to_json() })
+}
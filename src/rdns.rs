@@ -0,0 +1,154 @@
+//! Hostnames of discovered webcams from reverse DNS. Shodan often has no
+//! hostname for a result, while the PTR record of its address usually
+//! tells a home connection (`host-81-2-69-1.broadband.example.net`) from a
+//! business. Lookups run concurrently after a search, within a time budget
+//! so they never hold up its response by more than that; a lookup that
+//! fails or doesn't finish in time leaves the hostname unset.
+
+use crate::shodan::RemoteWebcam;
+use crate::target_policy::restricted_range;
+use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+use hickory_resolver::TokioAsyncResolver;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Longest reverse DNS may hold up a search by default
+pub const DEFAULT_RDNS_BUDGET: Duration = Duration::from_secs(2);
+/// Time allowed for one lookup, if the budget leaves that much
+const RDNS_LOOKUP_TIMEOUT: Duration = Duration::from_secs(1);
+/// Lookups in flight at once
+const RDNS_CONCURRENCY: usize = 16;
+
+/// What filling in hostnames did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct HostnameFill {
+    /// Addresses looked up, successfully or not
+    pub looked_up: usize,
+    /// Webcams given a hostname
+    pub resolved: usize,
+    /// Addresses the budget ran out before
+    pub unfinished: usize,
+}
+
+/// Reverse lookups through the system's resolvers, as `/etc/resolv.conf`
+/// lists them
+#[derive(Clone)]
+pub struct ReverseDns {
+    resolver: TokioAsyncResolver,
+}
+
+impl ReverseDns {
+    /// Resolver giving each lookup at most `timeout`, or `None` if the
+    /// system configuration can't be read. The resolver belongs to the
+    /// runtime it is first used on, so make one per search.
+    pub fn system(timeout: Duration) -> Option<Self> {
+        let (config, mut options): (ResolverConfig, ResolverOpts) = match hickory_resolver::system_conf::read_system_conf() {
+            Ok(conf) => conf,
+            Err(e) => {
+                warn!("Reverse DNS disabled: could not read the system resolver configuration: {}", e);
+                return None;
+            }
+        };
+        options.timeout = timeout;
+        options.attempts = 1;
+        Some(Self { resolver: TokioAsyncResolver::tokio(config, options) })
+    }
+
+    /// First name of `ip`'s PTR record, without the trailing dot
+    pub async fn lookup(&self, ip: IpAddr) -> Option<String> {
+        match self.resolver.reverse_lookup(ip).await {
+            Ok(names) => names.iter().next().map(|name| name.to_string().trim_end_matches('.').to_string()).filter(|name| !name.is_empty()),
+            Err(e) => {
+                debug!("No reverse DNS for {}: {}", ip, e);
+                None
+            }
+        }
+    }
+}
+
+/// Whether `ip` can have a PTR record in public DNS: not a private or
+/// other restricted address, nor one reserved for documentation
+fn has_public_reverse_zone(ip: IpAddr) -> bool {
+    let documentation = match ip {
+        IpAddr::V4(ip) => matches!(ip.octets(), [192, 0, 2, _] | [198, 51, 100, _] | [203, 0, 113, _]),
+        IpAddr::V6(ip) => ip.segments()[..2] == [0x2001, 0x0db8],
+    };
+    !documentation && restricted_range(ip).is_none()
+}
+
+/// Give the webcams without a hostname the one `lookup` finds for their
+/// address. Each address is looked up once, at most `concurrency` at a
+/// time; whatever hasn't finished when `budget` is up is abandoned.
+pub async fn fill_hostnames<F, Fut>(webcams: &mut [RemoteWebcam], budget: Duration, concurrency: usize, lookup: F) -> HostnameFill
+where
+    F: Fn(IpAddr) -> Fut,
+    Fut: Future<Output = Option<String>> + Send + 'static,
+{
+    let mut seen = HashSet::new();
+    let addresses: Vec<IpAddr> = webcams
+        .iter()
+        .filter(|webcam| webcam.hostname.is_none())
+        .filter_map(|webcam| webcam.ip.parse().ok())
+        .filter(|ip| has_public_reverse_zone(*ip) && seen.insert(*ip))
+        .collect();
+    let mut fill = HostnameFill::default();
+    if addresses.is_empty() || budget.is_zero() {
+        return fill;
+    }
+
+    let deadline = Instant::now() + budget;
+    let mut pending = addresses.into_iter();
+    let mut names: HashMap<IpAddr, String> = HashMap::new();
+    // Dropping the set when the budget is up aborts the lookups still running
+    let mut running = tokio::task::JoinSet::new();
+    loop {
+        while running.len() < concurrency.max(1) {
+            let Some(ip) = pending.next() else { break };
+            let found = lookup(ip);
+            running.spawn(async move { (ip, found.await) });
+        }
+        match tokio::time::timeout_at(deadline, running.join_next()).await {
+            Ok(Some(Ok((ip, name)))) => {
+                fill.looked_up += 1;
+                if let Some(name) = name {
+                    names.insert(ip, name);
+                }
+            }
+            Ok(Some(Err(_))) => fill.looked_up += 1,
+            Ok(None) => break,
+            Err(_) => {
+                fill.unfinished = running.len() + pending.len();
+                debug!("Reverse DNS budget of {:?} used up with {} lookup(s) unfinished", budget, fill.unfinished);
+                break;
+            }
+        }
+    }
+
+    for webcam in webcams.iter_mut().filter(|webcam| webcam.hostname.is_none()) {
+        if let Some(name) = webcam.ip.parse().ok().and_then(|ip: IpAddr| names.get(&ip)) {
+            webcam.hostname = Some(name.clone());
+            fill.resolved += 1;
+        }
+    }
+    fill
+}
+
+/// `fill_hostnames` through the system's resolvers
+pub async fn fill_hostnames_from_dns(webcams: &mut [RemoteWebcam], budget: Duration) -> HostnameFill {
+    if budget.is_zero() {
+        return HostnameFill::default();
+    }
+    let Some(dns) = ReverseDns::system(RDNS_LOOKUP_TIMEOUT.min(budget)) else {
+        return HostnameFill::default();
+    };
+    fill_hostnames(webcams, budget, RDNS_CONCURRENCY, move |ip| {
+        let dns = dns.clone();
+        async move { dns.lookup(ip).await }
+    })
+    .await
+}
@@ -0,0 +1,175 @@
+//! Snapshots of RTSP webcams, which `capture_remote_image` can't fetch
+//! with an HTTP GET. Needs the `rtsp` feature and `ffmpeg` installed.
+
+use crate::shodan::ShodanError;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Command run for RTSP snapshots unless another is configured
+pub const DEFAULT_FFMPEG: &str = "ffmpeg";
+/// Default time allowed for an RTSP snapshot, connecting included
+pub const DEFAULT_RTSP_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Words in ffmpeg's errors that mean the stream couldn't be opened, as
+/// opposed to opened but not decoded
+const CONNECTION_ERRORS: &[&str] = &[
+    "connection",
+    "timed out",
+    "no route to host",
+    "network is unreachable",
+    "name or service not known",
+    "failed to resolve",
+    "server returned",
+    "method describe failed",
+    "method setup failed",
+    "unauthorized",
+    "invalid data found when processing input",
+];
+
+/// Protocols ffmpeg may open for a snapshot: the input script, then the
+/// stream it names
+#[cfg(feature = "rtsp")]
+const PROTOCOL_WHITELIST: &str = "file,rtsp,rtsps,rtp,tcp,udp,tls";
+
+/// An ffconcat script naming the stream to snapshot, readable by this user
+/// only and removed when dropped. ffmpeg opens the stream over TCP, as the
+/// script asks.
+#[cfg(feature = "rtsp")]
+struct InputScript {
+    path: PathBuf,
+}
+
+#[cfg(feature = "rtsp")]
+impl InputScript {
+    fn write(url: &str) -> std::io::Result<Self> {
+        use std::io::Write;
+
+        let path = std::env::temp_dir().join(format!("mcp-webcam-rtsp-{}.ffconcat", uuid::Uuid::new_v4().simple()));
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&path)?;
+        let script = Self { path };
+        write!(
+            file,
+            "ffconcat version 1.0\nfile '{}'\noption rtsp_transport tcp\n",
+            url.replace('\'', r"'\''")
+        )?;
+        Ok(script)
+    }
+}
+
+#[cfg(feature = "rtsp")]
+impl Drop for InputScript {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Whether a URL is served over RTSP rather than HTTP
+pub fn is_rtsp_url(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    url.starts_with("rtsp://") || url.starts_with("rtsps://")
+}
+
+/// Takes single frames from RTSP streams by running `ffmpeg`, which pulls
+/// the first frame over TCP and encodes it as JPEG. The stream's URL is
+/// handed over in a private file, never on the command line. ffmpeg is killed when
+/// the timeout passes or the snapshot is cancelled, since RTSP servers
+/// often accept a connection and then never send a frame.
+#[derive(Debug, Clone)]
+pub struct RtspSnapshotter {
+    ffmpeg: PathBuf,
+    timeout: Duration,
+}
+
+impl Default for RtspSnapshotter {
+    fn default() -> Self {
+        Self::new(DEFAULT_FFMPEG, DEFAULT_RTSP_TIMEOUT)
+    }
+}
+
+impl RtspSnapshotter {
+    pub fn new(ffmpeg: impl Into<PathBuf>, timeout: Duration) -> Self {
+        Self { ffmpeg: ffmpeg.into(), timeout }
+    }
+
+    /// One frame of the stream at `url`, as JPEG
+    pub async fn snapshot(&self, url: &str) -> Result<Vec<u8>, ShodanError> {
+        #[cfg(feature = "rtsp")]
+        {
+            use crate::shodan::sanitize_url;
+            use std::process::Stdio;
+            use tracing::debug;
+
+            // Any local user can read a command line, so the URL and the
+            // credentials in it reach ffmpeg through a script of its own
+            let script = InputScript::write(url).map_err(|e| {
+                ShodanError::RtspNotSupported(format!("failed to write ffmpeg's input script: {}", e))
+            })?;
+            let mut command = tokio::process::Command::new(&self.ffmpeg);
+            command
+                .args(["-nostdin", "-hide_banner", "-loglevel", "error"])
+                .args(["-protocol_whitelist", PROTOCOL_WHITELIST, "-f", "concat", "-safe", "0", "-i"])
+                .arg(&script.path)
+                .args(["-frames:v", "1", "-f", "image2pipe", "-c:v", "mjpeg", "-"])
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true);
+            debug!("Taking an RTSP snapshot of {} with {}", sanitize_url(url), self.ffmpeg.display());
+
+            let child = command.spawn().map_err(|e| {
+                ShodanError::RtspNotSupported(format!("failed to run {}: {}", self.ffmpeg.display(), e))
+            })?;
+            // Dropping the child on timeout kills ffmpeg
+            let output = match tokio::time::timeout(self.timeout, child.wait_with_output()).await {
+                Ok(output) => output.map_err(|e| ShodanError::RtspConnection(format!("ffmpeg failed: {}", e)))?,
+                Err(_) => {
+                    return Err(ShodanError::RtspConnection(format!(
+                        "no frame from {} within {} s",
+                        sanitize_url(url),
+                        self.timeout.as_secs_f64()
+                    )))
+                }
+            };
+
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr).replace(url, &sanitize_url(url));
+                return Err(ffmpeg_error(&stderr));
+            }
+            if !output.stdout.starts_with(&[0xFF, 0xD8, 0xFF]) {
+                return Err(ShodanError::RtspDecode("ffmpeg produced no JPEG frame".to_string()));
+            }
+            Ok(output.stdout)
+        }
+
+        #[cfg(not(feature = "rtsp"))]
+        {
+            let _ = (url, &self.ffmpeg, self.timeout);
+            Err(ShodanError::RtspNotSupported("built without the 'rtsp' feature".to_string()))
+        }
+    }
+}
+
+/// The error for ffmpeg's `stderr` when it failed: a connection error if
+/// the stream couldn't be opened, else a decode error
+pub fn ffmpeg_error(stderr: &str) -> ShodanError {
+    let message = stderr
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("ffmpeg gave no reason")
+        .trim()
+        .to_string();
+    let lower = stderr.to_ascii_lowercase();
+    if CONNECTION_ERRORS.iter().any(|error| lower.contains(error)) {
+        ShodanError::RtspConnection(message)
+    } else {
+        ShodanError::RtspDecode(message)
+    }
+}
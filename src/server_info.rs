@@ -0,0 +1,51 @@
+//! Build details of the running server, reported by `get_server_info`.
+
+use crate::consent::ConsentStatus;
+use crate::protocol::RateLimit;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Commit the server was built from, or `unknown` outside a git checkout
+pub const GIT_COMMIT: &str = env!("MCP_WEBCAM_GIT_COMMIT");
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerInfo {
+    pub name: String,
+    pub version: String,
+    pub git_commit: String,
+    /// Cargo features the binary was built with
+    pub features: Vec<&'static str>,
+    /// Backend used for local cameras without a per-camera override
+    pub capture_backend: String,
+    pub shodan_enabled: bool,
+    /// Remote webcam access turned off by the administrator (`--no-remote`
+    /// or `MCP_WEBCAM_DISABLE_REMOTE`), whatever Shodan key is set
+    pub remote_disabled: bool,
+    /// Timeout of each tool that has one, in milliseconds
+    pub tool_timeouts_ms: BTreeMap<String, u64>,
+    /// Rate limit of each tool that has one
+    pub rate_limits: BTreeMap<String, RateLimit>,
+    /// Most tool calls running at once; `None` for no limit
+    pub max_concurrent_calls: Option<usize>,
+    /// Which tools wait for the user's consent, and until when it is given
+    pub consent: ConsentStatus,
+}
+
+/// Cargo features compiled into this build
+pub fn enabled_features() -> Vec<&'static str> {
+    [
+        ("local_cameras", cfg!(feature = "local_cameras")),
+        ("opencv_backend", cfg!(feature = "opencv_backend")),
+        ("gstreamer_backend", cfg!(feature = "gstreamer_backend")),
+        ("libcamera", cfg!(feature = "libcamera")),
+        ("turbojpeg", cfg!(feature = "turbojpeg")),
+        ("rtsp", cfg!(feature = "rtsp")),
+        ("censys", cfg!(feature = "censys")),
+        ("barcodes", cfg!(feature = "barcodes")),
+        ("face_blur", cfg!(feature = "face_blur")),
+        ("test_camera", cfg!(feature = "test_camera")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
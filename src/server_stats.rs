@@ -0,0 +1,124 @@
+//! Usage counters kept by the MCP server for the `get_server_stats` tool.
+//! Updating and reading them never touches camera hardware.
+
+use crate::credit_budget::BudgetStatus;
+use crate::host_cooldown::CooldownStats;
+use crate::shodan::LimiterStats;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Key under which captures from remote webcams are counted
+pub const REMOTE_CAMERA_KEY: &str = "remote";
+
+#[derive(Debug)]
+pub struct ServerStats {
+    started: Instant,
+    /// Successful captures by camera index (or `remote`)
+    captures: BTreeMap<String, u64>,
+    /// Failed tool calls by error type
+    failures: BTreeMap<String, u64>,
+    bytes_encoded: u64,
+    /// Captures with a measured latency, and their total latency
+    timed_captures: u64,
+    total_latency: Duration,
+    shodan_queries: u64,
+    /// When the Shodan key was last checked, and whether it was accepted
+    shodan_key_check: Option<(Instant, bool)>,
+}
+
+impl Default for ServerStats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            captures: BTreeMap::new(),
+            failures: BTreeMap::new(),
+            bytes_encoded: 0,
+            timed_captures: 0,
+            total_latency: Duration::ZERO,
+            shodan_queries: 0,
+            shodan_key_check: None,
+        }
+    }
+}
+
+impl ServerStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a successful capture from `camera`. `latency` is left out for
+    /// clips, whose duration is mostly the requested frame interval.
+    pub fn record_capture(&mut self, camera: impl ToString, latency: Option<Duration>) {
+        *self.captures.entry(camera.to_string()).or_default() += 1;
+        if let Some(latency) = latency {
+            self.timed_captures += 1;
+            self.total_latency += latency;
+        }
+    }
+
+    /// Count bytes of images and clips encoded for responses or files
+    pub fn record_encoded(&mut self, bytes: usize) {
+        self.bytes_encoded += bytes as u64;
+    }
+
+    /// Count a failed tool call; `kind` is a short snake_case error type
+    pub fn record_failure(&mut self, kind: &str) {
+        *self.failures.entry(kind.to_string()).or_default() += 1;
+    }
+
+    pub fn record_shodan_query(&mut self) {
+        self.shodan_queries += 1;
+    }
+
+    /// Record whether Shodan accepted the API key
+    pub fn record_shodan_key_check(&mut self, valid: bool) {
+        self.shodan_key_check = Some((Instant::now(), valid));
+    }
+
+    /// Time since the last Shodan key check and its result
+    pub fn shodan_key_check(&self) -> Option<(Duration, bool)> {
+        self.shodan_key_check.map(|(at, valid)| (at.elapsed(), valid))
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            uptime_secs: self.started.elapsed().as_secs(),
+            captures_total: self.captures.values().sum(),
+            captures_per_camera: self.captures.clone(),
+            failures_total: self.failures.values().sum(),
+            failures_by_type: self.failures.clone(),
+            bytes_encoded: self.bytes_encoded,
+            average_capture_ms: (self.timed_captures > 0)
+                .then(|| self.total_latency.as_secs_f64() * 1000.0 / self.timed_captures as f64),
+            shodan_queries: self.shodan_queries,
+            shodan_requests: None,
+            host_cooldown: None,
+            shodan_budget: None,
+        }
+    }
+}
+
+/// Counters as reported by `get_server_stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsSnapshot {
+    pub uptime_secs: u64,
+    pub captures_total: u64,
+    pub captures_per_camera: BTreeMap<String, u64>,
+    pub failures_total: u64,
+    pub failures_by_type: BTreeMap<String, u64>,
+    pub bytes_encoded: u64,
+    /// Mean time to capture a single frame, including encoding where the
+    /// tool encodes it; `None` before the first one
+    pub average_capture_ms: Option<f64>,
+    pub shodan_queries: u64,
+    /// The Shodan request limiter's queue and waits; `None` when remote
+    /// access is disabled
+    pub shodan_requests: Option<LimiterStats>,
+    /// Remote webcam hosts cooling down between fetches; `None` when
+    /// remote access is disabled
+    pub host_cooldown: Option<CooldownStats>,
+    /// Shodan search and host requests made and left this hour and today;
+    /// `None` when remote access is disabled
+    pub shodan_budget: Option<BudgetStatus>,
+}
@@ -0,0 +1,361 @@
+//! Startup settings for the MCP server, filled from the command line by
+//! the `mcp-webcam` binary. Anything left unset falls back to the
+//! environment variables documented in the README.
+
+use crate::auth::AuthToken;
+use crate::consent::DEFAULT_CONSENT_DURATION;
+use crate::honeypot::DEFAULT_HONEYPOT_THRESHOLD;
+use crate::host_cooldown::DEFAULT_HOST_COOLDOWN;
+use crate::config::ConfigFile;
+use crate::logging::{LogFormat, DEFAULT_LOG_MAX_BYTES};
+use crate::mcp_server::{DEFAULT_SNAPSHOT_INTERVAL, MIN_SNAPSHOT_INTERVAL};
+use crate::webcam::{CameraProfile, CaptureStore, RetentionPolicy, WebcamManager};
+use crate::shodan::{read_key_file, ShodanClientBuilder};
+use crate::target_policy::TargetRule;
+use crate::webcam_cache::DEFAULT_WEBCAM_CACHE_TTL;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// Log filter used when neither `--log-level` nor `RUST_LOG` is given
+pub const DEFAULT_LOG_LEVEL: &str = "info";
+/// Environment variable that turns off remote webcam access, like `--no-remote`
+pub const DISABLE_REMOTE_ENV: &str = "MCP_WEBCAM_DISABLE_REMOTE";
+/// Environment variable naming a file that holds the Shodan API key
+pub const SHODAN_KEY_FILE_ENV: &str = "SHODAN_API_KEY_FILE";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerSettings {
+    /// Camera captured from when a tool call names none; `None` falls back
+    /// to the configuration file, then camera 0
+    pub default_camera: Option<u32>,
+    /// Capture settings for cameras whose profile leaves them unset
+    pub capture_defaults: CameraProfile,
+    /// Downscale every frame wider than this
+    pub max_width: Option<u32>,
+    /// Whether the Shodan tools are offered at all
+    pub shodan_enabled: bool,
+    /// Remote webcam access turned off by the administrator: no Shodan
+    /// client, search or remote capture, whatever keys are configured.
+    /// `MCP_WEBCAM_DISABLE_REMOTE` does the same.
+    pub remote_disabled: bool,
+    /// Shodan API key; `None` falls back to `SHODAN_API_KEY`
+    pub shodan_api_key: Option<String>,
+    /// File holding the Shodan API key, re-read by `reload_credentials`;
+    /// `None` falls back to `SHODAN_API_KEY_FILE`
+    pub shodan_key_file: Option<PathBuf>,
+    /// Time allowed to connect to Shodan or a webcam; `None` keeps the
+    /// default
+    pub shodan_connect_timeout: Option<Duration>,
+    /// Time allowed for a whole Shodan API request; `None` keeps the default
+    pub shodan_request_timeout: Option<Duration>,
+    /// `User-Agent` of Shodan and webcam requests; `None` keeps
+    /// `mcp-webcam/<version>`
+    pub shodan_user_agent: Option<String>,
+    /// Proxy for Shodan and webcam requests; `None` falls back to
+    /// `HTTPS_PROXY` and the like
+    pub shodan_proxy: Option<String>,
+    /// Accept invalid TLS certificates from remote webcams, never from Shodan
+    pub remote_accept_invalid_certs: bool,
+    /// Fetch from remote webcams on loopback, private and link-local
+    /// addresses too
+    pub allow_private_targets: bool,
+    /// Networks and hosts remote webcams may be fetched from, in addition
+    /// to the configuration file's; none allows every public host
+    pub allowed_targets: Vec<TargetRule>,
+    /// Networks and hosts remote webcams are never fetched from, in
+    /// addition to the configuration file's
+    pub blocked_targets: Vec<TargetRule>,
+    /// Offer `scan_network_for_cameras`, which connects to every address
+    /// of a range; off unless remote access is enabled too
+    pub allow_network_scan: bool,
+    /// `ffmpeg` taking RTSP snapshots; `None` runs `ffmpeg` from the `PATH`
+    pub ffmpeg_path: Option<PathBuf>,
+    /// Time allowed for an RTSP snapshot; `None` keeps the default
+    pub rtsp_timeout: Option<Duration>,
+    /// Most bytes read from a remote webcam for one image; `None` keeps
+    /// the default
+    pub remote_max_download_bytes: Option<usize>,
+    /// Wall-clock limit on one remote webcam fetch; `None` keeps the default
+    pub remote_fetch_deadline: Option<Duration>,
+    /// Longest reverse DNS may add to a search; zero turns it off, `None`
+    /// keeps the default
+    pub remote_rdns_budget: Option<Duration>,
+    /// Token required by network listeners; `None` falls back to
+    /// `MCP_WEBCAM_AUTH_TOKEN`, then the configuration file
+    pub auth_token: Option<String>,
+    /// Capture output directory; `None` falls back to `MCP_WEBCAM_DATA_DIR`
+    pub data_dir: Option<PathBuf>,
+    /// `tracing` filter directive, e.g. `debug` or `mcp_webcam=trace`
+    pub log_level: String,
+    /// File to log to instead of stderr
+    pub log_file: Option<PathBuf>,
+    pub log_format: LogFormat,
+    /// Size at which `log_file` is rotated
+    pub log_max_bytes: u64,
+    /// Time between snapshots of a camera whose snapshot resource is
+    /// subscribed to
+    pub snapshot_interval: Duration,
+    /// How long discovered webcams stay cached after a search last found them
+    pub webcam_cache_ttl: Duration,
+    /// Least time between fetches from one remote webcam host; zero turns
+    /// the cooldown off
+    pub host_cooldown: Duration,
+    /// Shodan honeyscore above which a remote webcam host is taken for a
+    /// honeypot
+    pub honeypot_threshold: f64,
+    /// Refuse captures from hosts scoring above `honeypot_threshold`
+    pub honeypot_strict: bool,
+    /// Most Shodan search and host requests per hour; `None` for no limit
+    pub shodan_max_requests_per_hour: Option<u32>,
+    /// Most Shodan search and host requests per day; `None` for no limit
+    pub shodan_max_requests_per_day: Option<u32>,
+    /// Whether local camera tools wait for the user's consent
+    pub require_consent: bool,
+    /// Whether remote webcam captures wait for the user's consent
+    pub require_remote_consent: bool,
+    /// How long consent lasts once given
+    pub consent_duration: Duration,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            default_camera: None,
+            capture_defaults: CameraProfile::default(),
+            max_width: None,
+            shodan_enabled: true,
+            remote_disabled: false,
+            shodan_api_key: None,
+            shodan_key_file: None,
+            shodan_connect_timeout: None,
+            shodan_request_timeout: None,
+            shodan_user_agent: None,
+            shodan_proxy: None,
+            remote_accept_invalid_certs: false,
+            allow_private_targets: false,
+            allowed_targets: Vec::new(),
+            blocked_targets: Vec::new(),
+            allow_network_scan: false,
+            ffmpeg_path: None,
+            rtsp_timeout: None,
+            remote_max_download_bytes: None,
+            remote_fetch_deadline: None,
+            remote_rdns_budget: None,
+            auth_token: None,
+            data_dir: None,
+            log_level: DEFAULT_LOG_LEVEL.to_string(),
+            log_file: None,
+            log_format: LogFormat::Text,
+            log_max_bytes: DEFAULT_LOG_MAX_BYTES,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            webcam_cache_ttl: DEFAULT_WEBCAM_CACHE_TTL,
+            host_cooldown: DEFAULT_HOST_COOLDOWN,
+            honeypot_threshold: DEFAULT_HONEYPOT_THRESHOLD,
+            honeypot_strict: false,
+            shodan_max_requests_per_hour: None,
+            shodan_max_requests_per_day: None,
+            require_consent: false,
+            require_remote_consent: false,
+            consent_duration: DEFAULT_CONSENT_DURATION,
+        }
+    }
+}
+
+impl ServerSettings {
+    /// Reject contradictory or out-of-range settings before the server starts
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.shodan_enabled && self.shodan_api_key.is_some() {
+            return Err("a Shodan API key was given but Shodan is disabled".to_string());
+        }
+        if self.remote_disabled && self.shodan_api_key.is_some() {
+            return Err("a Shodan API key was given but remote access is disabled".to_string());
+        }
+        if self.remote_disabled && self.allow_network_scan {
+            return Err("network scanning was allowed but remote access is disabled".to_string());
+        }
+        if self.shodan_key_file.is_some() {
+            if self.shodan_api_key.is_some() {
+                return Err("give either a Shodan API key or a key file, not both".to_string());
+            }
+            if !self.shodan_enabled || self.remote_disabled {
+                return Err("a Shodan key file was given but Shodan is disabled".to_string());
+            }
+        }
+        if let Ok(config) = ConfigFile::load() {
+            config.target_rules()?;
+        }
+        self.shodan_client_builder().validate().map_err(|e| e.to_string())?;
+        if let Some(token) = &self.auth_token {
+            AuthToken::new(token.as_str())?;
+        }
+        if self.log_max_bytes == 0 {
+            return Err("log file size limit must be positive".to_string());
+        }
+        if self.snapshot_interval < MIN_SNAPSHOT_INTERVAL {
+            return Err(format!("snapshot interval must be at least {} second", MIN_SNAPSHOT_INTERVAL.as_secs()));
+        }
+        if self.webcam_cache_ttl < Duration::from_secs(1) {
+            return Err("webcam cache TTL must be at least 1 second".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.honeypot_threshold) {
+            return Err("honeypot threshold must be between 0 and 1".to_string());
+        }
+        if self.consent_duration < Duration::from_secs(1) {
+            return Err("consent duration must be at least 1 second".to_string());
+        }
+        if self.max_width == Some(0) {
+            return Err("max width must be positive".to_string());
+        }
+        if self.capture_defaults.backend.is_some() {
+            return Err("the capture backend can only be set per camera".to_string());
+        }
+        self.capture_defaults
+            .validate()
+            .map_err(|e| format!("capture defaults: {}", e))
+    }
+
+    /// Whether remote webcam access is allowed at all, i.e. neither
+    /// `--no-remote` nor `MCP_WEBCAM_DISABLE_REMOTE` turned it off
+    pub fn remote_enabled(&self) -> bool {
+        let disabled_by_env = std::env::var(DISABLE_REMOTE_ENV).is_ok_and(|value| {
+            !matches!(value.trim().to_ascii_lowercase().as_str(), "" | "0" | "false" | "no" | "off")
+        });
+        !self.remote_disabled && !disabled_by_env
+    }
+
+    /// Shodan API key to use, or `None` when Shodan or remote access is
+    /// disabled or no key is configured. A key given directly wins over a
+    /// key file, which wins over `SHODAN_API_KEY`; a key file that can't be
+    /// read leaves Shodan disabled.
+    pub fn resolved_shodan_key(&self) -> Option<String> {
+        if !self.shodan_enabled || !self.remote_enabled() {
+            return None;
+        }
+        if let Some(key) = &self.shodan_api_key {
+            return Some(key.clone());
+        }
+        if let Some(path) = self.resolved_shodan_key_file() {
+            return match read_key_file(&path) {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    error!("{} - Shodan features disabled until the file is fixed and reload_credentials is called", e);
+                    None
+                }
+            };
+        }
+        std::env::var("SHODAN_API_KEY").ok()
+    }
+
+    /// File the Shodan key is read from, if one is configured and Shodan
+    /// is enabled and not overridden by a key given directly
+    pub fn resolved_shodan_key_file(&self) -> Option<PathBuf> {
+        if !self.shodan_enabled || !self.remote_enabled() || self.shodan_api_key.is_some() {
+            return None;
+        }
+        self.shodan_key_file
+            .clone()
+            .or_else(|| std::env::var_os(SHODAN_KEY_FILE_ENV).map(PathBuf::from))
+    }
+
+    /// Connection settings of the Shodan clients
+    pub fn shodan_client_builder(&self) -> ShodanClientBuilder {
+        let mut builder = ShodanClientBuilder::new()
+            .accept_invalid_webcam_certs(self.remote_accept_invalid_certs)
+            .allow_private_targets(self.allow_private_targets);
+        let (allow, block) = self.resolved_target_rules();
+        if !allow.is_empty() || !block.is_empty() {
+            builder = builder.target_rules(allow, block);
+        }
+        if let Some(timeout) = self.shodan_connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.shodan_request_timeout {
+            builder = builder.request_timeout(timeout);
+        }
+        if let Some(user_agent) = &self.shodan_user_agent {
+            builder = builder.user_agent(user_agent.as_str());
+        }
+        if let Some(proxy) = &self.shodan_proxy {
+            builder = builder.proxy(proxy.as_str());
+        }
+        if let Some(ffmpeg) = &self.ffmpeg_path {
+            builder = builder.ffmpeg(ffmpeg);
+        }
+        if let Some(timeout) = self.rtsp_timeout {
+            builder = builder.rtsp_timeout(timeout);
+        }
+        if let Some(bytes) = self.remote_max_download_bytes {
+            builder = builder.max_download_bytes(bytes);
+        }
+        if let Some(deadline) = self.remote_fetch_deadline {
+            builder = builder.fetch_deadline(deadline);
+        }
+        if let Some(budget) = self.remote_rdns_budget {
+            builder = builder.reverse_dns_budget(budget);
+        }
+        builder
+    }
+
+    /// Allowlist and blocklist of remote webcam targets: the ones given
+    /// here followed by the configuration file's
+    pub fn resolved_target_rules(&self) -> (Vec<TargetRule>, Vec<TargetRule>) {
+        let (mut allow, mut block) = (self.allowed_targets.clone(), self.blocked_targets.clone());
+        match ConfigFile::load().and_then(|config| config.target_rules()) {
+            Ok((config_allow, config_block)) => {
+                allow.extend(config_allow);
+                block.extend(config_block);
+            }
+            Err(e) => warn!("Ignoring [target_policy] in the configuration file: {}", e),
+        }
+        (allow, block)
+    }
+
+    /// Camera captured from when a tool call names none
+    pub fn resolved_default_camera(&self) -> u32 {
+        self.default_camera
+            .or_else(|| match ConfigFile::load() {
+                Ok(config) => config.default_camera,
+                Err(e) => {
+                    warn!("Ignoring default_camera in the configuration file: {}", e);
+                    None
+                }
+            })
+            .unwrap_or(0)
+    }
+
+    /// Camera manager with the default camera, width limit and capture
+    /// defaults of these settings; invalid capture defaults are ignored
+    pub fn webcam_manager(&self) -> WebcamManager {
+        let mut manager = WebcamManager::new();
+        manager.set_default_camera(self.resolved_default_camera());
+        manager.set_max_width(self.max_width);
+        if let Err(e) = manager.set_capture_defaults(self.capture_defaults.clone()) {
+            warn!("Ignoring invalid capture defaults: {}", e);
+        }
+        manager
+    }
+
+    /// Store of captures and server state, under `data_dir` or else where
+    /// the environment says
+    pub fn capture_store(&self) -> CaptureStore {
+        match &self.data_dir {
+            Some(dir) => CaptureStore::new(dir.clone(), RetentionPolicy::from_env()),
+            None => CaptureStore::from_env(),
+        }
+    }
+
+    /// Token network listeners require, or `None` to generate one per
+    /// listener. The stdio transport is never authenticated.
+    pub fn resolved_auth_token(&self) -> Option<AuthToken> {
+        match &self.auth_token {
+            Some(token) => AuthToken::new(token.as_str()).ok(),
+            None => AuthToken::from_env().or_else(|| {
+                let token = ConfigFile::load().ok()?.auth_token?;
+                AuthToken::new(token)
+                    .map_err(|e| warn!("Ignoring auth_token in the configuration file: {}", e))
+                    .ok()
+            }),
+        }
+    }
+}
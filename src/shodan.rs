@@ -1,24 +1,172 @@
+use crate::audit::{AuditEntry, AuditLog};
+use crate::certificate::{certificate_names, CertificateNames};
+use crate::classify::{classify, Classification, Vendor};
+use crate::client_log::ClientLog;
+use crate::credit_budget::{BudgetExceeded, CreditBudget};
+use crate::host_cooldown::HostCooldown;
+use crate::http_auth::{self, WebcamCredentials};
+use crate::image_processing;
+use crate::rdns::{fill_hostnames_from_dns, HostnameFill, DEFAULT_RDNS_BUDGET};
+use crate::rtsp::{self, RtspSnapshotter};
+use crate::target_policy::{restricted_range, HostLookup, PolicyResolver, SystemLookup, TargetPolicy, TargetRule, AUDIT_TOOL, MAX_REDIRECTS};
 use anyhow::Result;
+use futures::stream::{Stream, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
+/// Tag of the webcams Shodan finds, in `RemoteWebcam::sources`
+pub const SOURCE: &str = "shodan";
+/// Environment variable holding the webcam search queries, one per line
+pub const SEARCH_QUERIES_ENV: &str = "MCP_WEBCAM_SEARCH_QUERIES";
+/// Built-in webcam search queries, most productive first
+pub const DEFAULT_WEBCAM_QUERIES: &[&str] = &[
+    "\"200 OK\" \"Content-Type: multipart/x-mixed-replace\"",
+    "\"Server: IP Webcam Server\"",
+    "port:8080 \"mjpeg\"",
+    "port:8081 \"mjpeg\"",
+    "port:554 \"rtsp\"",
+    "\"live view axis\"",
+    "\"axis video server\"",
+    "inurl:\"view/view.shtml\"",
+    "inurl:\"ViewerFrame?Mode=\"",
+    "inurl:\"MultiCameraFrame?Mode=\"",
+    "Server: SQ-WEBCAM",
+    "Server: yawcam",
+    "Server: webcamXP",
+];
+/// Queries planned for a search that doesn't choose, to spare query credits
+pub const DEFAULT_MAX_QUERIES: usize = 3;
+/// Webcams returned by a search that doesn't give a limit
+pub const DEFAULT_SEARCH_LIMIT: u32 = 20;
+
+/// Query parameters whose values `sanitize_url` hides
+const SECRET_QUERY_PARAMS: &[&str] = &["key", "api_key", "token", "auth_token", "password"];
+const MASK: &str = "***";
+
+/// API key that formats as `***`, so it can't end up in a log line or
+/// error message by accident. `expose` is the only way to the key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// `text` with every occurrence of the secret masked
+    pub fn scrub(&self, text: &str) -> String {
+        if self.0.is_empty() {
+            text.to_string()
+        } else {
+            text.replace(&self.0, MASK)
+        }
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(MASK)
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(MASK)
+    }
+}
+
+/// Shodan API key stored in `path`, e.g. a systemd credential or Docker
+/// secret, without surrounding whitespace
+pub fn read_key_file(path: &Path) -> Result<String, String> {
+    let key = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read Shodan key file {}: {}", path.display(), e))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("Shodan key file {} is empty", path.display()));
+    }
+    Ok(key.to_string())
+}
+
+/// `url` fit for logs and error messages: credentials and secret query
+/// parameters such as `key=` are masked. Text that doesn't parse as a URL
+/// loses everything from its query on.
+pub fn sanitize_url(url: &str) -> String {
+    let Ok(mut parsed) = url::Url::parse(url) else {
+        return url.split(['?', '#']).next().unwrap_or_default().to_string();
+    };
+    if !parsed.username().is_empty() {
+        let _ = parsed.set_username(MASK);
+    }
+    if parsed.password().is_some() {
+        let _ = parsed.set_password(Some(MASK));
+    }
+    if parsed.query().is_some() {
+        let pairs: Vec<(String, String)> = parsed.query_pairs()
+            .map(|(name, value)| {
+                let secret = SECRET_QUERY_PARAMS.iter().any(|p| name.eq_ignore_ascii_case(p));
+                (name.into_owned(), if secret { MASK.to_string() } else { value.into_owned() })
+            })
+            .collect();
+        parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    parsed.to_string()
+}
+
+/// One banner from `/shodan/host/search`. Only the fields used are
+/// declared; the rest of Shodan's banner is ignored.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShodanResult {
-    pub ip: String,
+    /// The address as text, IPv4 or IPv6; the canonical form
+    #[serde(default)]
+    pub ip_str: Option<String>,
+    /// IPv4 address as an integer, used when `ip_str` is missing
+    #[serde(default)]
+    pub ip: Option<u64>,
     pub port: u16,
-    pub hostname: Option<String>,
+    #[serde(default)]
+    pub hostnames: Vec<String>,
     pub location: Option<ShodanLocation>,
     pub org: Option<String>,
     pub data: String,
     pub timestamp: String,
-    pub transport: String,
+    #[serde(default)]
+    pub transport: Option<String>,
+    #[serde(default)]
     pub product: Option<String>,
 }
 
+impl ShodanResult {
+    /// `ip_str`, or else the integer `ip` in dotted form
+    pub fn address(&self) -> Option<String> {
+        self.ip_str.clone().or_else(|| {
+            self.ip
+                .and_then(|ip| u32::try_from(ip).ok())
+                .map(|ip| Ipv4Addr::from(ip).to_string())
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShodanLocation {
+    /// ISO 3166-1 alpha-2 code, e.g. `GB`
+    #[serde(default)]
+    pub country_code: Option<String>,
     pub country_name: Option<String>,
     pub city: Option<String>,
     pub region_code: Option<String>,
@@ -33,12 +181,211 @@ pub struct ShodanSearchResponse {
     pub facets: Option<HashMap<String, Vec<ShodanFacet>>>,
 }
 
+/// One service Shodan saw on a host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShodanService {
+    pub port: u16,
+    #[serde(default)]
+    pub transport: Option<String>,
+    #[serde(default)]
+    pub product: Option<String>,
+    /// When the banner was collected
+    pub timestamp: String,
+}
+
+/// `/shodan/host/{ip}` as sent; location fields are at the top level
+#[derive(Deserialize)]
+struct RawShodanHost {
+    ip_str: String,
+    #[serde(default)]
+    ports: Vec<u16>,
+    #[serde(default)]
+    hostnames: Vec<String>,
+    org: Option<String>,
+    isp: Option<String>,
+    os: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    vulns: Vec<String>,
+    last_update: Option<String>,
+    country_code: Option<String>,
+    country_name: Option<String>,
+    city: Option<String>,
+    region_code: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    #[serde(default)]
+    data: Vec<ShodanService>,
+}
+
+/// Everything Shodan knows about one host, from `/shodan/host/{ip}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "RawShodanHost")]
+pub struct ShodanHost {
+    pub ip: String,
+    /// Open ports, ascending
+    pub ports: Vec<u16>,
+    pub hostnames: Vec<String>,
+    pub org: Option<String>,
+    pub isp: Option<String>,
+    pub os: Option<String>,
+    pub location: ShodanLocation,
+    /// Shodan's labels, e.g. `iot` or `self-signed`
+    pub tags: Vec<String>,
+    /// CVE identifiers, on plans that include them
+    pub vulns: Vec<String>,
+    pub last_update: Option<String>,
+    /// One per banner; with history, older banners too
+    pub services: Vec<ShodanService>,
+}
+
+impl From<RawShodanHost> for ShodanHost {
+    fn from(raw: RawShodanHost) -> Self {
+        let mut ports = raw.ports;
+        ports.sort_unstable();
+        ports.dedup();
+        Self {
+            ip: raw.ip_str,
+            ports,
+            hostnames: raw.hostnames,
+            org: raw.org,
+            isp: raw.isp,
+            os: raw.os,
+            location: ShodanLocation {
+                country_code: raw.country_code,
+                country_name: raw.country_name,
+                city: raw.city,
+                region_code: raw.region_code,
+                latitude: raw.latitude,
+                longitude: raw.longitude,
+            },
+            tags: raw.tags,
+            vulns: raw.vulns,
+            last_update: raw.last_update,
+            services: raw.data,
+        }
+    }
+}
+
+/// Plan and remaining credits of the account a key belongs to, from
+/// Shodan's `/api-info`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShodanAccountInfo {
+    #[serde(default)]
+    pub plan: Option<String>,
+    /// Search queries left this month
+    pub query_credits: i64,
+    /// On-demand scans left this month
+    pub scan_credits: i64,
+}
+
+/// Most addresses one on-demand scan may ask for; each costs a scan credit
+pub const MAX_SCAN_TARGETS: usize = 16;
+
+/// An on-demand scan Shodan accepted, from `/shodan/scan`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShodanScan {
+    pub id: String,
+    /// Addresses to be scanned
+    pub count: u64,
+    /// Scan credits the account has left
+    pub credits_left: i64,
+}
+
+/// Where an on-demand scan is, from Shodan's `SUBMITTING`, `QUEUE`,
+/// `PROCESSING` and `DONE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanStatus {
+    Submitting,
+    Queued,
+    Processing,
+    Done,
+    /// A status this client doesn't know
+    Unknown,
+}
+
+impl ScanStatus {
+    pub fn parse(status: &str) -> Self {
+        match status.trim().to_ascii_uppercase().as_str() {
+            "SUBMITTING" => Self::Submitting,
+            "QUEUE" | "QUEUED" => Self::Queued,
+            "PROCESSING" => Self::Processing,
+            "DONE" => Self::Done,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn is_done(self) -> bool {
+        self == Self::Done
+    }
+}
+
+#[derive(Deserialize)]
+struct RawScanStatus {
+    id: String,
+    #[serde(default)]
+    count: u64,
+    status: String,
+    #[serde(default)]
+    created: Option<String>,
+}
+
+/// Progress of an on-demand scan, from `/shodan/scan/{id}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "RawScanStatus")]
+pub struct ShodanScanStatus {
+    pub id: String,
+    pub count: u64,
+    pub status: ScanStatus,
+    /// The status as Shodan wrote it
+    pub shodan_status: String,
+    /// When the scan was requested
+    pub created: Option<String>,
+}
+
+impl From<RawScanStatus> for ShodanScanStatus {
+    fn from(raw: RawScanStatus) -> Self {
+        Self {
+            id: raw.id,
+            count: raw.count,
+            status: ScanStatus::parse(&raw.status),
+            shodan_status: raw.status,
+            created: raw.created,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShodanFacet {
     pub count: u64,
     pub value: String,
 }
 
+/// Facets a webcam count is broken down by, in Shodan's `name:size` form
+pub const COUNT_FACETS: &[&str] = &["country:5", "org:5"];
+
+/// How many results a query has and how they break down, from
+/// `/shodan/host/count`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShodanCount {
+    pub total: u64,
+    /// Top values of each facet asked for, most common first
+    #[serde(default)]
+    pub facets: HashMap<String, Vec<ShodanFacet>>,
+}
+
+/// Result count of one webcam query, with the countries and
+/// organizations most of its results are in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryCount {
+    pub query: String,
+    pub total: u64,
+    pub top_countries: Vec<ShodanFacet>,
+    pub top_orgs: Vec<ShodanFacet>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RemoteWebcam {
     pub ip: String,
@@ -50,133 +397,1844 @@ pub struct RemoteWebcam {
     pub product: Option<String>,
     pub last_seen: String,
     pub access_type: WebcamAccessType,
+    /// Its make, as `classify` judged it from the Shodan result
+    #[serde(default)]
+    pub classification: Option<Classification>,
+    /// Discovery services that found it, e.g. `shodan` and `censys`
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+impl RemoteWebcam {
+    /// Fill in the details this entry lacks from another sighting of the
+    /// same endpoint, found by a different query or service. Of two
+    /// classifications the more certain is kept; the sources of both are.
+    pub fn merge(&mut self, other: RemoteWebcam) {
+        self.hostname = self.hostname.take().or(other.hostname);
+        self.location = self.location.take().or(other.location);
+        self.org = self.org.take().or(other.org);
+        self.product = self.product.take().or(other.product);
+        let more_certain = match (&self.classification, &other.classification) {
+            (Some(ours), Some(theirs)) => theirs.confidence > ours.confidence,
+            (None, Some(_)) => true,
+            _ => false,
+        };
+        if more_certain {
+            self.classification = other.classification;
+        }
+        if self.last_seen.is_empty() {
+            self.last_seen = other.last_seen;
+        }
+        for source in other.sources {
+            if !self.sources.contains(&source) {
+                self.sources.push(source);
+            }
+        }
+    }
+}
+
+/// One endpoint of a webcam host, as listed by `group_by_host`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebcamEndpoint {
+    pub port: u16,
+    pub url: String,
+    pub product: Option<String>,
+    pub last_seen: String,
+    pub access_type: WebcamAccessType,
+    pub classification: Option<Classification>,
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+/// Every endpoint found on one IP address
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebcamHost {
+    pub ip: String,
+    pub hostname: Option<String>,
+    pub location: Option<ShodanLocation>,
+    pub org: Option<String>,
+    pub endpoints: Vec<WebcamEndpoint>,
+}
+
+/// One entry per IP, in the order each was first found, with its
+/// endpoints in the order they were found. Host details come from the
+/// first endpoint that has them.
+pub fn group_by_host(webcams: Vec<RemoteWebcam>) -> Vec<WebcamHost> {
+    let mut hosts: Vec<WebcamHost> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for webcam in webcams {
+        let endpoint = WebcamEndpoint {
+            port: webcam.port,
+            url: webcam.url,
+            product: webcam.product,
+            last_seen: webcam.last_seen,
+            access_type: webcam.access_type,
+            classification: webcam.classification,
+            sources: webcam.sources,
+        };
+        match index.entry(webcam.ip.clone()) {
+            Entry::Occupied(entry) => {
+                let host = &mut hosts[*entry.get()];
+                host.hostname = host.hostname.take().or(webcam.hostname);
+                host.location = host.location.take().or(webcam.location);
+                host.org = host.org.take().or(webcam.org);
+                host.endpoints.push(endpoint);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(hosts.len());
+                hosts.push(WebcamHost {
+                    ip: webcam.ip,
+                    hostname: webcam.hostname,
+                    location: webcam.location,
+                    org: webcam.org,
+                    endpoints: vec![endpoint],
+                });
+            }
+        }
+    }
+    hosts
+}
+
+/// Mean radius of the Earth, for great-circle distances
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+/// Largest search radius, half the Earth's circumference
+pub const MAX_RADIUS_KM: f64 = 20_015.0;
+
+/// Great-circle distance in km between two points given in degrees, by
+/// the haversine formula
+pub fn haversine_km(latitude1: f64, longitude1: f64, latitude2: f64, longitude2: f64) -> f64 {
+    let (phi1, phi2) = (latitude1.to_radians(), latitude2.to_radians());
+    let delta_phi = (latitude2 - latitude1).to_radians();
+    let delta_lambda = (longitude2 - longitude1).to_radians();
+    let a = (delta_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (delta_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+}
+
+/// Webcams within `radius_km` of a point
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoFilter {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius_km: f64,
+}
+
+impl GeoFilter {
+    /// Checked like the other filters, before anything is sent
+    pub fn new(latitude: f64, longitude: f64, radius_km: f64) -> Result<Self, String> {
+        if !(-90.0..=90.0).contains(&latitude) {
+            return Err(format!("latitude must be between -90 and 90, got {}", latitude));
+        }
+        if !(-180.0..=180.0).contains(&longitude) {
+            return Err(format!("longitude must be between -180 and 180, got {}", longitude));
+        }
+        if radius_km.is_nan() || radius_km <= 0.0 || radius_km > MAX_RADIUS_KM {
+            return Err(format!("radius_km must be above 0 and at most {}, got {}", MAX_RADIUS_KM, radius_km));
+        }
+        Ok(Self { latitude, longitude, radius_km })
+    }
+
+    /// Distance in km from the point to `location`, if it has coordinates
+    pub fn distance_km(&self, location: Option<&ShodanLocation>) -> Option<f64> {
+        let location = location?;
+        Some(haversine_km(self.latitude, self.longitude, location.latitude?, location.longitude?))
+    }
+
+    /// Whether `location` has coordinates within the radius
+    pub fn contains(&self, location: Option<&ShodanLocation>) -> bool {
+        self.distance_km(location).is_some_and(|distance| distance <= self.radius_km)
+    }
+}
+
+/// Restricts a webcam search by place, organization and make. The place
+/// and organization are added to every Shodan query and checked again
+/// against each result, since Shodan's own matching is loose; Shodan knows
+/// nothing of makes, so the vendor is only checked against results.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchFilters {
+    /// ISO 3166-1 alpha-2 country code, e.g. `GB`
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub org: Option<String>,
+    #[serde(default)]
+    pub vendor: Option<Vendor>,
+    /// Only webcams within a distance of a point, nearest first
+    #[serde(default)]
+    pub near: Option<GeoFilter>,
+}
+
+impl SearchFilters {
+    /// Filters with values of a usable shape; the country code is
+    /// upper-cased. Checked before anything is sent, so a malformed
+    /// filter costs no query credits.
+    pub fn new(country: Option<&str>, city: Option<&str>, org: Option<&str>) -> Result<Self, String> {
+        let country = match country.map(str::trim) {
+            Some(code) if code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic()) => Some(code.to_ascii_uppercase()),
+            Some(code) => {
+                return Err(format!("country must be a two-letter ISO code such as GB, got '{}'", code));
+            }
+            None => None,
+        };
+        Ok(Self { country, city: filter_value("city", city)?, org: filter_value("org", org)?, vendor: None, near: None })
+    }
+
+    /// These filters, keeping only webcams within `near`'s radius
+    pub fn with_near(mut self, near: Option<GeoFilter>) -> Self {
+        self.near = near;
+        self
+    }
+
+    /// These filters, keeping only webcams classified as `vendor`'s
+    pub fn with_vendor(mut self, vendor: Option<Vendor>) -> Self {
+        self.vendor = vendor;
+        self
+    }
+
+    /// Shodan filter syntax appended to each query, e.g. ` country:"GB"`.
+    /// A radius becomes Shodan's `geo:"latitude,longitude,km"`, so fewer
+    /// far-away results are paid for.
+    fn query_suffix(&self) -> String {
+        let geo = self.near.map(|near| format!("{},{},{}", near.latitude, near.longitude, near.radius_km));
+        [("country", &self.country), ("city", &self.city), ("org", &self.org), ("geo", &geo)]
+            .into_iter()
+            .filter_map(|(name, value)| value.as_ref().map(|value| format!(" {}:\"{}\"", name, value)))
+            .collect()
+    }
+
+    /// Whether `webcam` is within the radius, if one is set. A webcam
+    /// without coordinates isn't.
+    pub fn within_radius(&self, webcam: &RemoteWebcam) -> bool {
+        self.near.is_none_or(|near| near.contains(webcam.location.as_ref()))
+    }
+
+    /// Whether `webcam` really is where the filters say. A result without
+    /// the field a filter needs doesn't match.
+    pub fn matches(&self, webcam: &RemoteWebcam) -> bool {
+        let location = webcam.location.as_ref();
+        if let Some(country) = &self.country {
+            if !location.and_then(|l| l.country_code.as_deref()).is_some_and(|code| code.eq_ignore_ascii_case(country)) {
+                return false;
+            }
+        }
+        if let Some(city) = &self.city {
+            if !location.and_then(|l| l.city.as_deref()).is_some_and(|c| c.eq_ignore_ascii_case(city)) {
+                return false;
+            }
+        }
+        if let Some(org) = &self.org {
+            if !webcam.org.as_deref().is_some_and(|o| o.to_lowercase().contains(&org.to_lowercase())) {
+                return false;
+            }
+        }
+        if let Some(vendor) = self.vendor {
+            if !webcam.classification.as_ref().is_some_and(|c| c.vendor == vendor) {
+                return false;
+            }
+        }
+        self.within_radius(webcam)
+    }
+}
+
+/// The addresses of an on-demand scan, each once: public IPv4 or IPv6
+/// addresses only, since Shodan scans neither ranges nor private
+/// networks, and at most `MAX_SCAN_TARGETS` of them
+pub fn parse_scan_targets<S: AsRef<str>>(targets: &[S]) -> Result<Vec<IpAddr>, String> {
+    let mut ips: Vec<IpAddr> = Vec::new();
+    for target in targets {
+        let target = target.as_ref().trim();
+        let ip: IpAddr = target.parse().map_err(|_| format!("{} is not an IP address", target))?;
+        if let Some(range) = restricted_range(ip) {
+            return Err(format!("{} is a {} address, which Shodan can't scan", ip, range));
+        }
+        if !ips.contains(&ip) {
+            ips.push(ip);
+        }
+    }
+    if ips.is_empty() {
+        return Err("Name at least one IP address to scan".to_string());
+    }
+    if ips.len() > MAX_SCAN_TARGETS {
+        return Err(format!("At most {} addresses can be scanned per call, not {}", MAX_SCAN_TARGETS, ips.len()));
+    }
+    Ok(ips)
+}
+
+/// Queries in `text`, one per line; blank lines are skipped
+pub fn parse_query_list(text: &str) -> Vec<String> {
+    text.lines().map(str::trim).filter(|line| !line.is_empty()).map(str::to_string).collect()
+}
+
+/// Queries a search may run, in order. The first `planned` share the
+/// result limit; the rest only run while results are still short of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPlan {
+    pub queries: Vec<String>,
+    pub planned: usize,
+}
+
+/// The queries a search may run: those at `indices` in order, or else the
+/// whole list, at most `max` of them. With neither, the first
+/// `DEFAULT_MAX_QUERIES` are planned and the rest are extras.
+pub fn select_queries(queries: &[String], indices: Option<&[usize]>, max: Option<usize>) -> Result<QueryPlan, String> {
+    if max == Some(0) {
+        return Err("max_queries must be at least 1".to_string());
+    }
+    let selected: Vec<String> = match indices {
+        Some([]) => return Err("query_indices must name at least one query".to_string()),
+        Some(indices) => {
+            let mut chosen: Vec<usize> = Vec::new();
+            for &index in indices {
+                if index >= queries.len() {
+                    return Err(format!("query index {} is out of range; there are {} queries (see list_search_queries)", index, queries.len()));
+                }
+                if !chosen.contains(&index) {
+                    chosen.push(index);
+                }
+            }
+            chosen.into_iter().take(max.unwrap_or(usize::MAX)).map(|index| queries[index].clone()).collect()
+        }
+        None => queries.iter().take(max.unwrap_or(usize::MAX)).cloned().collect(),
+    };
+    let planned = if indices.is_none() && max.is_none() {
+        DEFAULT_MAX_QUERIES.min(selected.len())
+    } else {
+        selected.len()
+    };
+    Ok(QueryPlan { queries: selected, planned })
+}
+
+/// Webcams found by a search and the queries run to find them
+#[derive(Debug, Clone, Default)]
+pub struct WebcamSearch {
+    pub webcams: Vec<RemoteWebcam>,
+    pub queries: Vec<String>,
+    /// Results dropped for lying outside the radius or having no
+    /// coordinates
+    pub outside_radius: usize,
+    /// Hostnames filled in from reverse DNS
+    pub reverse_dns: HostnameFill,
+}
+
+/// Run the plan's queries with `search` until `limit` distinct endpoints
+/// (IP and port) matching `filters` are found or the queries run out, then cut the list
+/// to `limit`. Each planned query asks for its share of what is still
+/// missing; an extra query asks for all of it. An endpoint found again
+/// is merged into its first sighting rather than listed twice. With a
+/// radius, the webcams are then sorted nearest first. A failed query is logged
+/// and skipped; if every query fails, the last error is returned.
+pub async fn collect_webcams<F, Fut>(
+    plan: &QueryPlan,
+    limit: u32,
+    filters: &SearchFilters,
+    search: F,
+) -> Result<WebcamSearch, ShodanError>
+where
+    F: FnMut(String, u32) -> Fut,
+    Fut: Future<Output = Result<Vec<RemoteWebcam>, ShodanError>>,
+{
+    collect_webcams_with(plan, limit, filters, &filters.query_suffix(), search).await
+}
+
+/// `collect_webcams` for a service with its own query syntax: `suffix`,
+/// the filters in that syntax, is appended to each query
+pub async fn collect_webcams_with<F, Fut>(
+    plan: &QueryPlan,
+    limit: u32,
+    filters: &SearchFilters,
+    suffix: &str,
+    mut search: F,
+) -> Result<WebcamSearch, ShodanError>
+where
+    F: FnMut(String, u32) -> Fut,
+    Fut: Future<Output = Result<Vec<RemoteWebcam>, ShodanError>>,
+{
+    let mut collector = WebcamCollector::new(plan, limit, filters, suffix);
+    while let Some((query, share)) = collector.next_query() {
+        let results = search(query.clone(), share).await;
+        collector.add(&query, results);
+    }
+    collector.finish()
+}
+
+/// The state of a search `collect_webcams_with` runs, one query at a
+/// time, for callers that want each query's webcams as they arrive: ask
+/// for the `next_query`, `add` its results, and `finish` once there are
+/// no more queries to run.
+#[derive(Debug)]
+pub struct WebcamCollector {
+    plan: QueryPlan,
+    limit: usize,
+    filters: SearchFilters,
+    suffix: String,
+    run: usize,
+    found: WebcamSearch,
+    seen: HashMap<(String, u16), usize>,
+    last_error: Option<ShodanError>,
+    any_succeeded: bool,
+    /// Set when a query was refused by the request budget, which the
+    /// rest would be too
+    out_of_budget: bool,
+}
+
+impl WebcamCollector {
+    pub fn new(plan: &QueryPlan, limit: u32, filters: &SearchFilters, suffix: &str) -> Self {
+        Self {
+            plan: plan.clone(),
+            limit: limit as usize,
+            filters: filters.clone(),
+            suffix: suffix.to_string(),
+            run: 0,
+            found: WebcamSearch::default(),
+            seen: HashMap::new(),
+            last_error: None,
+            any_succeeded: false,
+            out_of_budget: false,
+        }
+    }
+
+    /// The next query to run, with the suffix, and how many results to
+    /// ask for; `None` once `limit` webcams are found, the queries run out
+    /// or the request budget is used up
+    pub fn next_query(&mut self) -> Option<(String, u32)> {
+        if self.out_of_budget {
+            return None;
+        }
+        let query = self.plan.queries.get(self.run)?;
+        let missing = self.limit.saturating_sub(self.found.webcams.len());
+        if missing == 0 {
+            return None;
+        }
+        let share = if self.run < self.plan.planned { missing.div_ceil(self.plan.planned - self.run) } else { missing };
+        self.found.queries.push(query.clone());
+        self.run += 1;
+        Some((format!("{}{}", query, self.suffix), share as u32))
+    }
+
+    /// Merge in the results of `query`; returns the endpoints it added,
+    /// as far as `limit` allows
+    pub fn add(&mut self, query: &str, results: Result<Vec<RemoteWebcam>, ShodanError>) -> Vec<RemoteWebcam> {
+        let webcams = match results {
+            Ok(webcams) => webcams,
+            Err(e) => {
+                warn!("Failed to search with query '{}': {}", query, e);
+                self.out_of_budget = matches!(e, ShodanError::BudgetExceeded(_));
+                self.last_error = Some(e);
+                return Vec::new();
+            }
+        };
+        self.any_succeeded = true;
+        let before = self.found.webcams.len();
+        // Results arrive in Shodan's order; keep the first sighting of
+        // each endpoint and fill in what it lacks from the rest
+        for webcam in webcams {
+            if !self.filters.within_radius(&webcam) {
+                self.found.outside_radius += 1;
+                continue;
+            }
+            if !self.filters.matches(&webcam) {
+                continue;
+            }
+            match self.seen.entry((webcam.ip.clone(), webcam.port)) {
+                Entry::Occupied(entry) => self.found.webcams[*entry.get()].merge(webcam),
+                Entry::Vacant(entry) => {
+                    entry.insert(self.found.webcams.len());
+                    self.found.webcams.push(webcam);
+                }
+            }
+        }
+        let added = self.found.webcams.len().min(self.limit);
+        self.found.webcams[before.min(added)..added].to_vec()
+    }
+
+    /// The webcams found, with a radius sorted nearest first, then cut to
+    /// `limit`. If every query failed, the last error instead.
+    pub fn finish(&mut self) -> Result<WebcamSearch, ShodanError> {
+        match self.last_error.take() {
+            Some(e) if !self.any_succeeded => Err(e),
+            _ => {
+                let mut found = std::mem::take(&mut self.found);
+                if let Some(near) = self.filters.near {
+                    // Every webcam left has coordinates. Sorted before the
+                    // cut, so the nearest are kept whatever order they came in
+                    let distance = |webcam: &RemoteWebcam| near.distance_km(webcam.location.as_ref()).unwrap_or(f64::INFINITY);
+                    found.webcams.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+                }
+                found.webcams.truncate(self.limit);
+                Ok(found)
+            }
+        }
+    }
+}
+
+/// A Shodan webcam search as it runs: each item is the webcams one query
+/// added, as soon as it answers. `finish` runs whatever queries are left
+/// and returns the whole search, as `ShodanClient::search_webcams` does.
+pub struct WebcamStream<'a> {
+    client: &'a ShodanClient,
+    collector: Arc<Mutex<WebcamCollector>>,
+    batches: Pin<Box<dyn Stream<Item = Vec<RemoteWebcam>> + Send + 'a>>,
+}
+
+impl Stream for WebcamStream<'_> {
+    type Item = Vec<RemoteWebcam>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.batches.as_mut().poll_next(cx)
+    }
+}
+
+impl WebcamStream<'_> {
+    /// Run the queries left, then return every webcam found, with
+    /// hostnames filled in from reverse DNS
+    pub async fn finish(mut self) -> Result<WebcamSearch, ShodanError> {
+        while self.next().await.is_some() {}
+        let finished = self.collector.lock().unwrap_or_else(|e| e.into_inner()).finish();
+        let mut found = finished?;
+        found.reverse_dns = fill_hostnames_from_dns(&mut found.webcams, self.client.reverse_dns_budget).await;
+        if found.reverse_dns.resolved > 0 {
+            debug!("Reverse DNS named {} webcam(s)", found.reverse_dns.resolved);
+        }
+
+        info!("Found {} unique webcams with {} queries", found.webcams.len(), found.queries.len());
+        Ok(found)
+    }
+}
+
+/// Longest city or org filter accepted
+const MAX_FILTER_LENGTH: usize = 100;
+
+fn filter_value(name: &str, value: Option<&str>) -> Result<Option<String>, String> {
+    let Some(value) = value.map(str::trim) else {
+        return Ok(None);
+    };
+    if value.is_empty() || value.len() > MAX_FILTER_LENGTH {
+        return Err(format!("{} must be 1 to {} characters", name, MAX_FILTER_LENGTH));
+    }
+    if value.chars().any(|c| c == '"' || c.is_control()) {
+        return Err(format!("{} must not contain quotes or control characters", name));
+    }
+    Ok(Some(value.to_string()))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WebcamAccessType {
+    MJPEG,
+    RTSP,
+    HTTP,
+    /// An ONVIF device on the local network; its URL is the device
+    /// service, spoken to with SOAP rather than serving images itself
+    ONVIF,
+    Unknown,
+}
+
+/// Messages never contain the API key: request errors lose their URL, which
+/// carries the key, and response text is scrubbed of it
+#[derive(Debug, thiserror::Error)]
+pub enum ShodanError {
+    #[error("HTTP request failed: {0}")]
+    HttpError(reqwest::Error),
+    #[error("API key not provided")]
+    NoApiKey,
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+    /// Shodan kept answering 429; `retry_after` is its suggested wait, or
+    /// the next backoff step when it gave none
+    #[error("Rate limit exceeded{}", .retry_after.map(|wait| format!(", retry in {} s", wait.as_secs().max(1))).unwrap_or_default())]
+    RateLimitExceeded { retry_after: Option<Duration> },
+    #[error("Unauthorized - check API key")]
+    Unauthorized,
+    #[error("No information available for {0}")]
+    NotFound(String),
+    #[error("Invalid Shodan client settings: {0}")]
+    InvalidSettings(String),
+    /// A webcam answered with something other than an image, such as a
+    /// login page; `preview` is the start of the body
+    #[error("Response is not an image ({}): {preview}", .content_type.as_deref().unwrap_or("no Content-Type"))]
+    NotAnImage { content_type: Option<String>, preview: String },
+    /// A webcam answered 401 and no credentials were given
+    #[error("Webcam requires {scheme} authentication{}", .realm.as_ref().map(|realm| format!(" (realm \"{}\")", realm)).unwrap_or_default())]
+    AuthRequired { scheme: String, realm: Option<String> },
+    /// A webcam answered 401 to the credentials given
+    #[error("Webcam rejected the credentials for {scheme} authentication{}", .realm.as_ref().map(|realm| format!(" (realm \"{}\")", realm)).unwrap_or_default())]
+    AuthRejected { scheme: String, realm: Option<String> },
+    /// Probing found no snapshot URL serving an image
+    #[error("No snapshot URL answered with an image ({tried} tried)")]
+    NoSnapshotUrl { tried: usize },
+    #[error("RTSP snapshots unavailable: {0}")]
+    RtspNotSupported(String),
+    /// The RTSP stream couldn't be opened, or sent no frame in time
+    #[error("RTSP connection failed: {0}")]
+    RtspConnection(String),
+    #[error("RTSP frame could not be decoded: {0}")]
+    RtspDecode(String),
+    /// A webcam sent more than the download limit; the connection was
+    /// closed after `read` bytes
+    #[error("Response is larger than the {limit} byte download limit ({read} bytes read)")]
+    ResponseTooLarge { limit: usize, read: usize },
+    /// A webcam fetch was still running at its wall-clock deadline
+    #[error("Fetch did not finish within {} s", .deadline.as_secs_f64())]
+    FetchDeadline { deadline: Duration },
+    /// The target policy refused a webcam URL or a redirect; `reason`
+    /// names the rule
+    #[error("Blocked request to {target}: {reason}")]
+    TargetBlocked { target: String, reason: String },
+    /// A webcam's TLS certificate could not be verified; `certificate`
+    /// names its subject and issuer when it could be read
+    #[error("Webcam's TLS certificate could not be verified: {reason}{}", .certificate.as_ref().map(|names| format!(" (subject \"{}\", issuer \"{}\")", names.subject, names.issuer)).unwrap_or_default())]
+    TlsError { reason: String, certificate: Option<CertificateNames> },
+    /// `host` was fetched from less than the cooldown interval ago
+    #[error("{host} was fetched from moments ago, retry in {} s", .retry_after.as_secs_f64().ceil())]
+    HostCooldown { host: String, retry_after: Duration },
+    /// An ONVIF camera answered `action` with a SOAP fault, or with
+    /// something other than a SOAP response
+    #[error("ONVIF {action} failed: {reason}")]
+    OnvifFault { action: String, reason: String },
+    /// The hourly or daily budget of Shodan search and host requests is
+    /// used up; no request was sent
+    #[error("Request refused: {0}")]
+    BudgetExceeded(BudgetExceeded),
+    #[error("Generic error: {0}")]
+    Generic(String),
+}
+
+impl From<reqwest::Error> for ShodanError {
+    fn from(e: reqwest::Error) -> Self {
+        ShodanError::HttpError(e.without_url())
+    }
+}
+
+/// Default number of times a request Shodan answers with 429 is retried
+pub const DEFAULT_SHODAN_RETRIES: u32 = 3;
+
+/// How requests Shodan answers with 429 Too Many Requests are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 fails on the first 429
+    pub max_retries: u32,
+    /// Wait before the first retry when Shodan sends no `Retry-After`,
+    /// doubled for each retry after it
+    pub base_delay: Duration,
+    /// Longest wait before a retry. A longer `Retry-After` isn't waited
+    /// out: the request fails at once and passes the wait on.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_SHODAN_RETRIES,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The default policy with `max_retries` retries
+    pub fn with_max_retries(max_retries: u32) -> Self {
+        Self { max_retries, ..Self::default() }
+    }
+
+    /// Wait before retry number `retry` (from 0): Shodan's `retry_after`
+    /// if it sent one, else exponential backoff up to `max_delay`
+    pub fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        retry_after.unwrap_or_else(|| {
+            self.base_delay.saturating_mul(2u32.saturating_pow(retry)).min(self.max_delay)
+        })
+    }
+}
+
+/// Shortest pause between the queries of a search while Shodan is
+/// rate limiting
+const PACING_STEP: Duration = Duration::from_millis(500);
+/// Longest pause between the queries of a search
+const MAX_PACING: Duration = Duration::from_secs(10);
+
+/// Pause between consecutive queries of a search or count. It doubles
+/// after a query Shodan answered 429 to and halves after one it didn't,
+/// down to no pause at all.
+#[derive(Debug, Default)]
+struct Pacing {
+    delay: Duration,
+    throttled: bool,
+}
+
+impl Pacing {
+    fn record_throttled(&mut self) {
+        self.throttled = true;
+    }
+
+    /// The pause before the next query
+    fn next_delay(&mut self) -> Duration {
+        self.delay = if std::mem::take(&mut self.throttled) {
+            (self.delay * 2).clamp(PACING_STEP, MAX_PACING)
+        } else if self.delay > PACING_STEP {
+            self.delay / 2
+        } else {
+            Duration::ZERO
+        };
+        self.delay
+    }
+}
+
+/// Requests per second sent to Shodan by default, the limit of most plans
+pub const DEFAULT_SHODAN_REQUESTS_PER_SECOND: f64 = 1.0;
+
+/// Spaces out requests to the Shodan API so they stay within the plan's
+/// rate, however many tool calls make them at once. Requests wait their
+/// turn in the order they arrive.
+#[derive(Debug)]
+pub struct RequestLimiter {
+    interval: Duration,
+    /// Earliest time the next request may be sent
+    next_slot: tokio::sync::Mutex<Option<Instant>>,
+    counters: Mutex<LimiterCounters>,
+}
+
+#[derive(Debug, Default)]
+struct LimiterCounters {
+    queued: usize,
+    max_queued: usize,
+    requests: u64,
+    waited_requests: u64,
+    total_wait: Duration,
+    max_wait: Duration,
+}
+
+/// Rate limiter state as reported by `get_server_stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct LimiterStats {
+    pub requests_per_second: f64,
+    /// Requests waiting for their turn right now
+    pub queue_depth: usize,
+    pub max_queue_depth: usize,
+    /// Requests sent since startup
+    pub requests: u64,
+    /// Requests that had to wait, and for how long in total and at most
+    pub waited_requests: u64,
+    pub total_wait_ms: u64,
+    pub max_wait_ms: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum WebcamAccessType {
-    MJPEG,
-    RTSP,
-    HTTP,
-    Unknown,
-}
+impl RequestLimiter {
+    /// Limiter allowing `requests_per_second`, which must be positive
+    pub fn new(requests_per_second: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / requests_per_second),
+            next_slot: tokio::sync::Mutex::new(None),
+            counters: Mutex::new(LimiterCounters::default()),
+        }
+    }
+
+    /// Wait until a request may be sent, and count it
+    pub async fn acquire(&self) {
+        let arrived = Instant::now();
+        self.update(|c| {
+            c.queued += 1;
+            c.max_queued = c.max_queued.max(c.queued);
+        });
+
+        let mut next_slot = self.next_slot.lock().await;
+        if let Some(at) = *next_slot {
+            tokio::time::sleep_until(at.into()).await;
+        }
+        *next_slot = Some(Instant::now() + self.interval);
+        drop(next_slot);
+
+        let waited = arrived.elapsed();
+        self.update(|c| {
+            c.queued -= 1;
+            c.requests += 1;
+            // Waits shorter than this are taking the lock, not rate limiting
+            if waited >= Duration::from_millis(1) {
+                c.waited_requests += 1;
+                c.total_wait += waited;
+                c.max_wait = c.max_wait.max(waited);
+            }
+        });
+    }
+
+    pub fn stats(&self) -> LimiterStats {
+        let c = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        LimiterStats {
+            requests_per_second: 1.0 / self.interval.as_secs_f64(),
+            queue_depth: c.queued,
+            max_queue_depth: c.max_queued,
+            requests: c.requests,
+            waited_requests: c.waited_requests,
+            total_wait_ms: c.total_wait.as_millis() as u64,
+            max_wait_ms: c.max_wait.as_millis() as u64,
+        }
+    }
+
+    fn update(&self, change: impl FnOnce(&mut LimiterCounters)) {
+        change(&mut self.counters.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+}
+
+impl Default for RequestLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_SHODAN_REQUESTS_PER_SECOND)
+    }
+}
+
+/// Suggested wait in a 429 response's `Retry-After`, in seconds
+pub(crate) fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response.headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Default time allowed to connect to Shodan or a webcam
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default time allowed for a whole Shodan API request
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Time allowed for a whole webcam image fetch
+pub(crate) const WEBCAM_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+/// Most bytes read of an ONVIF camera's SOAP response
+const MAX_SOAP_RESPONSE_BYTES: usize = 256 * 1024;
+/// Default most bytes read from a remote webcam, for one image or up to
+/// the first frame of an MJPEG stream
+pub const DEFAULT_MAX_DOWNLOAD_BYTES: usize = 10 * 1024 * 1024;
+/// Default wall-clock limit on fetching one image from a webcam, however
+/// steadily it sends
+pub const DEFAULT_FETCH_DEADLINE: Duration = Duration::from_secs(30);
+/// Characters of a body kept in `ShodanError::NotAnImage`, or in the
+/// error for an API answer that isn't the JSON expected
+const BODY_PREVIEW_CHARS: usize = 200;
+
+/// An image fetched from a remote webcam
+#[derive(Debug, Clone)]
+pub struct RemoteImage {
+    pub bytes: Vec<u8>,
+    /// Detected from the bytes, or taken from the response's `Content-Type`
+    pub mime_type: String,
+    /// Whether it came over HTTPS with a certificate that failed
+    /// verification and was accepted anyway
+    pub tls_unverified: bool,
+}
+
+/// How much of a webcam's image a fetch reads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FetchExtent {
+    /// The whole image, up to the download limit
+    #[default]
+    Whole,
+    /// Only as far as the image's header, enough for its type and
+    /// dimensions. Images over the download limit can be read this way.
+    Header,
+}
+
+/// What a metadata-only fetch found out about a webcam's image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RemoteImageInfo {
+    /// Detected from the bytes, or taken from the response's `Content-Type`
+    pub mime_type: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Size of the whole image: the webcam's `Content-Length`, or what was
+    /// read when the image ended before its header was found. `None` when
+    /// the webcam didn't say.
+    pub size_bytes: Option<u64>,
+    /// Bytes downloaded
+    pub bytes_read: usize,
+    pub tls_unverified: bool,
+}
+
+impl RemoteImageInfo {
+    /// Information about an image fetched whole
+    pub fn of(image: &RemoteImage) -> Self {
+        let dimensions = image_processing::image_dimensions(&image.bytes);
+        Self {
+            mime_type: image.mime_type.clone(),
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+            size_bytes: Some(image.bytes.len() as u64),
+            bytes_read: image.bytes.len(),
+            tls_unverified: image.tls_unverified,
+        }
+    }
+}
+
+/// Body of a webcam's answer, read as far as a `FetchExtent` asks
+#[derive(Debug, Default)]
+pub(crate) struct ImageBody {
+    pub bytes: Vec<u8>,
+    /// `Content-Type` of the answer, or of a stream's first part
+    pub content_type: Option<String>,
+    /// Size of the whole body or frame, when known
+    pub size_bytes: Option<u64>,
+}
+
+/// MIME type of an image from its magic bytes, for JPEG, PNG, GIF and BMP
+pub fn sniff_image_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.starts_with(b"BM") && bytes.len() > 14 {
+        Some("image/bmp")
+    } else {
+        None
+    }
+}
+
+/// Whether a Content-Type is a multipart stream such as MJPEG's
+/// `multipart/x-mixed-replace`
+fn is_multipart(content_type: &str) -> bool {
+    content_type.trim_start().to_ascii_lowercase().starts_with("multipart/")
+}
+
+/// The `boundary` parameter of a multipart Content-Type, unquoted
+fn boundary_param(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        let value = value.trim().trim_matches('"');
+        (name.trim().eq_ignore_ascii_case("boundary") && !value.is_empty()).then(|| value.to_string())
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack.get(from..)?.windows(needle.len()).position(|window| window == needle).map(|at| at + from)
+}
+
+/// Index just past the line break of the line containing `from`
+fn line_end(buffer: &[u8], from: usize) -> Option<usize> {
+    find(buffer, b"\n", from).map(|at| at + 1)
+}
+
+/// The first non-empty part of a multipart body
+#[derive(Debug, PartialEq)]
+struct MultipartPart {
+    content_type: Option<String>,
+    body: std::ops::Range<usize>,
+}
+
+/// The first non-empty part in `buffer`, once all of it has arrived. The
+/// part ends after its `Content-Length`, or else at the next delimiter.
+/// Cameras disagree on whether `boundary` includes the leading dashes, and
+/// some send none; then the first line starting with `--` is the delimiter.
+fn first_part(buffer: &[u8], boundary: Option<&str>) -> Option<MultipartPart> {
+    let name = match boundary {
+        Some(boundary) => boundary.to_string(),
+        None => {
+            let start = find(buffer, b"--", 0)?;
+            let end = line_end(buffer, start)?;
+            String::from_utf8_lossy(&buffer[start..end]).trim().to_string()
+        }
+    };
+    let delimiter = format!("--{}", name.trim_start_matches('-')).into_bytes();
+
+    let mut at = find(buffer, &delimiter, 0)?;
+    loop {
+        let mut content_type = None;
+        let mut length = None;
+        let mut line_start = line_end(buffer, at)?;
+        let body_start = loop {
+            let end = line_end(buffer, line_start)?;
+            let line = String::from_utf8_lossy(&buffer[line_start..end]);
+            let line = line.trim();
+            if line.is_empty() {
+                break end;
+            }
+            if let Some((header, value)) = line.split_once(':') {
+                match header.trim().to_ascii_lowercase().as_str() {
+                    "content-type" => content_type = Some(value.trim().to_string()),
+                    "content-length" => length = value.trim().parse::<usize>().ok(),
+                    _ => {}
+                }
+            }
+            line_start = end;
+        };
+        let body_end = match length {
+            Some(length) if buffer.len() >= body_start + length => body_start + length,
+            Some(_) => return None,
+            None => {
+                // Drop the line break and any extra dashes before the delimiter
+                let mut end = find(buffer, &delimiter, body_start)?;
+                while end > body_start && buffer[end - 1] == b'-' {
+                    end -= 1;
+                }
+                if end > body_start && buffer[end - 1] == b'\n' {
+                    end -= 1;
+                }
+                if end > body_start && buffer[end - 1] == b'\r' {
+                    end -= 1;
+                }
+                end
+            }
+        };
+        if body_end > body_start {
+            return Some(MultipartPart { content_type, body: body_start..body_end });
+        }
+        at = find(buffer, &delimiter, body_end)?;
+    }
+}
+
+/// Path and query of `url`, as a request line carries them
+fn request_uri(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(parsed) => parsed[url::Position::BeforePath..url::Position::AfterQuery].to_string(),
+        Err(_) => "/".to_string(),
+    }
+}
+
+/// Whether `a` and `b` share scheme, host and port, so credentials
+/// given for one may be sent to the other
+fn same_origin(a: &str, b: &str) -> bool {
+    match (url::Url::parse(a), url::Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin().is_tuple() && a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+/// `url` logging in as `credentials`, unless it names a user already
+fn with_credentials(url: &str, credentials: &WebcamCredentials) -> String {
+    match url::Url::parse(url) {
+        Ok(mut parsed) if parsed.username().is_empty() => {
+            let _ = parsed.set_username(&credentials.username);
+            let _ = parsed.set_password(Some(credentials.password.expose()));
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// A webcam's answer to a request, after any redirects
+pub(crate) struct WebcamResponse {
+    /// URL that answered
+    pub url: String,
+    pub response: reqwest::Response,
+    /// Whether its certificate failed verification and was accepted
+    pub tls_unverified: bool,
+}
+
+/// Why the certificate a request got was rejected, if that is how it failed
+fn certificate_error(error: &reqwest::Error) -> Option<String> {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(e) = source {
+        let message = e.to_string();
+        if message.to_ascii_lowercase().contains("certificate") {
+            return Some(message);
+        }
+        source = e.source();
+    }
+    None
+}
+
+/// Why the target policy refused the addresses a request's host resolved
+/// to when connecting, if that is why it failed
+fn resolver_refusal(error: &reqwest::Error) -> Option<String> {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(e) = source {
+        if let Some(ShodanError::TargetBlocked { reason, .. }) = e.downcast_ref::<ShodanError>() {
+            return Some(reason.clone());
+        }
+        source = e.source();
+    }
+    None
+}
+
+/// The whole body, up to `limit` bytes. The body is streamed, so a larger
+/// one is never buffered: dropping the response closes the connection.
+async fn read_capped(mut response: reqwest::Response, limit: usize) -> Result<Vec<u8>, ShodanError> {
+    if let Some(length) = response.content_length().filter(|length| *length > limit as u64) {
+        debug!("Content-Length {} is over the download limit", length);
+        return Err(ShodanError::ResponseTooLarge { limit, read: 0 });
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() > limit {
+            return Err(ShodanError::ResponseTooLarge { limit, read: body.len() });
+        }
+    }
+    Ok(body)
+}
+
+/// The body up to the point `done` says enough has been read, and whether
+/// reading got to its end. Like `read_capped`, no more than `limit` bytes
+/// are read, but a longer Content-Length isn't refused up front.
+async fn read_until(
+    mut response: reqwest::Response,
+    limit: usize,
+    done: impl Fn(&[u8]) -> bool,
+) -> Result<(Vec<u8>, bool), ShodanError> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if done(&body) {
+            return Ok((body, false));
+        }
+        if body.len() > limit {
+            return Err(ShodanError::ResponseTooLarge { limit, read: body.len() });
+        }
+    }
+    Ok((body, true))
+}
+
+/// The first part of a multipart stream and its Content-Type. The stream is
+/// read only that far: dropping the response closes the connection.
+async fn read_first_part(
+    mut response: reqwest::Response,
+    boundary: Option<String>,
+    limit: usize,
+) -> Result<(Vec<u8>, Option<String>), ShodanError> {
+    let mut buffer = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        buffer.extend_from_slice(&chunk);
+        if let Some(part) = first_part(&buffer, boundary.as_deref()) {
+            buffer.truncate(part.body.end);
+            buffer.drain(..part.body.start);
+            return Ok((buffer, part.content_type));
+        }
+        if buffer.len() > limit {
+            return Err(ShodanError::ResponseTooLarge { limit, read: buffer.len() });
+        }
+    }
+    Err(ShodanError::Generic("Stream ended before its first frame".to_string()))
+}
+
+/// MIME type of a fetched body: its magic bytes, else an `image/*`
+/// `content_type` unless the body is markup. Anything else, such as a
+/// login page, is `ShodanError::NotAnImage`.
+pub fn detect_image_type(bytes: &[u8], content_type: Option<&str>) -> Result<String, ShodanError> {
+    if let Some(mime_type) = sniff_image_type(bytes) {
+        return Ok(mime_type.to_string());
+    }
+    let essence = content_type.map(|value| value.split(';').next().unwrap_or_default().trim().to_ascii_lowercase());
+    let text = String::from_utf8_lossy(&bytes[..bytes.len().min(BODY_PREVIEW_CHARS * 4)]);
+    let markup = text.trim_start().starts_with('<');
+    match essence {
+        Some(mime_type) if mime_type.starts_with("image/") && !markup && !bytes.is_empty() => Ok(mime_type),
+        _ => Err(ShodanError::NotAnImage {
+            content_type: content_type.map(str::to_string),
+            preview: text.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(BODY_PREVIEW_CHARS).collect(),
+        }),
+    }
+}
+
+/// Environment variables naming the proxies the HTTP clients use when no
+/// proxy is configured
+const PROXY_ENV_VARS: &[&str] = &["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"];
+
+/// Connection settings of Shodan clients, reusable for every key, e.g.
+/// when the key is reloaded
+///
+/// Without a proxy, the `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY`
+/// environment variables are honored.
+#[derive(Debug, Clone)]
+pub struct ShodanClientBuilder {
+    base_url: String,
+    connect_timeout: Duration,
+    request_timeout: Duration,
+    user_agent: String,
+    proxy: Option<String>,
+    accept_invalid_webcam_certs: bool,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RequestLimiter>>,
+    ffmpeg: PathBuf,
+    rtsp_timeout: Duration,
+    max_download_bytes: usize,
+    fetch_deadline: Duration,
+    target_policy: TargetPolicy,
+    host_lookup: Arc<dyn HostLookup>,
+    reverse_dns_budget: Duration,
+    host_cooldown: Option<Arc<HostCooldown>>,
+    credit_budget: Option<Arc<CreditBudget>>,
+    audit_log: AuditLog,
+}
+
+impl Default for ShodanClientBuilder {
+    fn default() -> Self {
+        Self {
+            base_url: "https://api.shodan.io".to_string(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            user_agent: concat!("mcp-webcam/", env!("CARGO_PKG_VERSION")).to_string(),
+            proxy: None,
+            accept_invalid_webcam_certs: false,
+            retry: RetryPolicy::default(),
+            limiter: None,
+            ffmpeg: PathBuf::from(rtsp::DEFAULT_FFMPEG),
+            rtsp_timeout: rtsp::DEFAULT_RTSP_TIMEOUT,
+            max_download_bytes: DEFAULT_MAX_DOWNLOAD_BYTES,
+            fetch_deadline: DEFAULT_FETCH_DEADLINE,
+            target_policy: TargetPolicy::default(),
+            host_lookup: Arc::new(SystemLookup),
+            reverse_dns_budget: DEFAULT_RDNS_BUDGET,
+            host_cooldown: None,
+            credit_budget: None,
+            audit_log: AuditLog::disabled(),
+        }
+    }
+}
+
+impl ShodanClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// See `ShodanClient::with_base_url`
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Time allowed to connect, to Shodan or to a webcam
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Time allowed for a whole Shodan API request. Webcam fetches have
+    /// their own, shorter limit.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// `User-Agent` of every request (default `mcp-webcam/<version>`)
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Send every request, to Shodan and to webcams, through the proxy at
+    /// `url`, e.g. `http://proxy.example.com:3128`
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Accept invalid TLS certificates from webcams, many of which are
+    /// self-signed, unless a call says otherwise (see
+    /// `ShodanClient::accepting_invalid_certs`). Certificates are always
+    /// checked first, so accepted ones can be reported. Requests to Shodan
+    /// are always verified.
+    pub fn accept_invalid_webcam_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_webcam_certs = accept;
+        self
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Share `limiter` between the clients built (default one each)
+    pub fn request_limiter(mut self, limiter: Arc<RequestLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// `ffmpeg` command taking RTSP snapshots (default `ffmpeg` on the `PATH`)
+    pub fn ffmpeg(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ffmpeg = path.into();
+        self
+    }
+
+    /// Time allowed for an RTSP snapshot, connecting included
+    pub fn rtsp_timeout(mut self, timeout: Duration) -> Self {
+        self.rtsp_timeout = timeout;
+        self
+    }
+
+    /// Most bytes read from a webcam for one image (default 10 MiB); a
+    /// webcam sending more fails with `ShodanError::ResponseTooLarge`
+    pub fn max_download_bytes(mut self, bytes: usize) -> Self {
+        self.max_download_bytes = bytes;
+        self
+    }
+
+    /// Wall-clock limit on fetching one image from a webcam, logging in
+    /// included (default 30 seconds). Unlike the per-request timeouts it
+    /// also stops a webcam that keeps sending a few bytes at a time.
+    pub fn fetch_deadline(mut self, deadline: Duration) -> Self {
+        self.fetch_deadline = deadline;
+        self
+    }
+
+    /// Fetch from webcams on loopback, private, link-local and other
+    /// non-public addresses too (default only public ones)
+    pub fn allow_private_targets(mut self, allow: bool) -> Self {
+        self.target_policy.allow_private = allow;
+        self
+    }
+
+    /// Fetch only from the networks and hosts in `allow`, if it isn't
+    /// empty, and never from those in `block` (default neither)
+    pub fn target_rules(mut self, allow: Vec<TargetRule>, block: Vec<TargetRule>) -> Self {
+        self.target_policy.allow = allow;
+        self.target_policy.block = block;
+        self
+    }
+
+    /// Resolve webcam host names with `lookup`, for the target policy and
+    /// for connecting alike (default the system resolver)
+    pub fn host_lookup(mut self, lookup: Arc<dyn HostLookup>) -> Self {
+        self.host_lookup = lookup;
+        self
+    }
+
+    /// Record each target policy decision in `log` (default none)
+    pub fn audit_log(mut self, log: AuditLog) -> Self {
+        self.audit_log = log;
+        self
+    }
+
+    /// Which webcam URLs clients built from these settings fetch
+    pub fn target_policy(&self) -> &TargetPolicy {
+        &self.target_policy
+    }
+
+    /// Longest reverse DNS lookups of webcams without a hostname may add
+    /// to a search (default 2 seconds); zero turns them off
+    pub fn reverse_dns_budget(mut self, budget: Duration) -> Self {
+        self.reverse_dns_budget = budget;
+        self
+    }
+
+    /// Space fetches from each webcam host by `cooldown`, shared between
+    /// the clients built (default no cooldown)
+    pub fn host_cooldown(mut self, cooldown: Arc<HostCooldown>) -> Self {
+        self.host_cooldown = Some(cooldown);
+        self
+    }
+
+    /// Count search and host requests against `budget`, shared between
+    /// the clients built (default no limit)
+    pub fn credit_budget(mut self, budget: Arc<CreditBudget>) -> Self {
+        self.credit_budget = Some(budget);
+        self
+    }
+
+    /// Check the settings without building a client
+    pub fn validate(&self) -> Result<(), ShodanError> {
+        if self.connect_timeout.is_zero() || self.request_timeout.is_zero() || self.rtsp_timeout.is_zero() || self.fetch_deadline.is_zero() {
+            return Err(ShodanError::InvalidSettings("timeouts must be positive".to_string()));
+        }
+        if self.max_download_bytes == 0 {
+            return Err(ShodanError::InvalidSettings("the download limit must be positive".to_string()));
+        }
+        if reqwest::header::HeaderValue::from_str(&self.user_agent).is_err() {
+            return Err(ShodanError::InvalidSettings("the user agent must be printable ASCII".to_string()));
+        }
+        self.proxy().map(|_| ())
+    }
+
+    /// Client for `api_key` with these settings
+    pub fn build(&self, api_key: impl Into<String>) -> Result<ShodanClient, ShodanError> {
+        self.validate()?;
+        let api_key = api_key.into();
+        // Kept out of logs forwarded to the client
+        ClientLog::global().redact(&api_key);
+
+        let client = self.http_client(false)?.timeout(self.request_timeout).build()?;
+        // Redirects from webcams are followed by hand, checking each target,
+        // and connections only go to addresses the policy allows
+        let target_policy = Arc::new(self.target_policy.clone());
+        let resolver = Arc::new(
+            PolicyResolver::new(Arc::clone(&target_policy), Arc::clone(&self.host_lookup)).with_proxy_hosts(self.proxy_hosts()),
+        );
+        let fetch_client = self.http_client(false)?
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(resolver.clone())
+            .build()?;
+        // Used only after a certificate failed verification, for a call
+        // that accepts that, or to read the certificate for the error
+        let insecure_fetch_client = self.http_client(true)?
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(resolver)
+            .tls_info(true)
+            .build()?;
+        Ok(ShodanClient {
+            client,
+            fetch_client,
+            insecure_fetch_client,
+            accept_invalid_certs: self.accept_invalid_webcam_certs,
+            api_key: SecretString::new(api_key),
+            base_url: self.base_url.clone(),
+            retry: self.retry,
+            pacing: Arc::new(Mutex::new(Pacing::default())),
+            limiter: self.limiter.clone().unwrap_or_default(),
+            rtsp: RtspSnapshotter::new(&self.ffmpeg, self.rtsp_timeout),
+            max_download_bytes: self.max_download_bytes,
+            fetch_deadline: self.fetch_deadline,
+            target_policy,
+            host_lookup: Arc::clone(&self.host_lookup),
+            audit_log: self.audit_log.clone(),
+            reverse_dns_budget: self.reverse_dns_budget,
+            host_cooldown: self.host_cooldown.clone().unwrap_or_else(|| Arc::new(HostCooldown::new(Duration::ZERO))),
+            credit_budget: self.credit_budget.clone().unwrap_or_default(),
+        })
+    }
+
+    /// HTTP client for another discovery service's API, with these
+    /// connection settings
+    pub(crate) fn api_client(&self) -> Result<Client, ShodanError> {
+        self.validate()?;
+        Ok(self.http_client(false)?.timeout(self.request_timeout).build()?)
+    }
+
+    fn proxy(&self) -> Result<Option<reqwest::Proxy>, ShodanError> {
+        self.proxy
+            .as_deref()
+            .map(|url| {
+                reqwest::Proxy::all(url)
+                    .map_err(|_| ShodanError::InvalidSettings(format!("invalid proxy URL '{}'", sanitize_url(url))))
+            })
+            .transpose()
+    }
 
-#[derive(Debug, thiserror::Error)]
-pub enum ShodanError {
-    #[error("HTTP request failed: {0}")]
-    HttpError(#[from] reqwest::Error),
-    #[error("API key not provided")]
-    NoApiKey,
-    #[error("Invalid query: {0}")]
-    InvalidQuery(String),
-    #[error("Rate limit exceeded")]
-    RateLimitExceeded,
-    #[error("Unauthorized - check API key")]
-    Unauthorized,
-    #[error("Generic error: {0}")]
-    Generic(String),
+    /// Host names of the proxies requests go through: the one configured,
+    /// or else those of the proxy environment variables
+    fn proxy_hosts(&self) -> Vec<String> {
+        let urls: Vec<String> = match &self.proxy {
+            Some(url) => vec![url.clone()],
+            None => PROXY_ENV_VARS.iter().filter_map(|name| std::env::var(name).ok()).collect(),
+        };
+        urls.iter()
+            .filter_map(|url| {
+                let url = url.trim();
+                // The variables may leave out the scheme
+                url::Url::parse(url).or_else(|_| url::Url::parse(&format!("http://{}", url))).ok()
+            })
+            .filter_map(|url| match url.host() {
+                Some(url::Host::Domain(host)) => Some(host.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn http_client(&self, accept_invalid_certs: bool) -> Result<reqwest::ClientBuilder, ShodanError> {
+        let mut builder = Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .user_agent(self.user_agent.clone())
+            .danger_accept_invalid_certs(accept_invalid_certs);
+        if let Some(proxy) = self.proxy()? {
+            builder = builder.proxy(proxy);
+        }
+        Ok(builder)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ShodanClient {
+    /// Requests to the Shodan API
     client: Client,
-    api_key: String,
+    /// Requests to webcams
+    fetch_client: Client,
+    /// Requests to webcams whose certificates failed verification
+    insecure_fetch_client: Client,
+    /// Whether webcam certificates that fail verification are accepted
+    accept_invalid_certs: bool,
+    api_key: SecretString,
     base_url: String,
+    retry: RetryPolicy,
+    /// Shared by clones, so every search paces itself by what all saw
+    pacing: Arc<Mutex<Pacing>>,
+    /// Shared by clones; applies to API requests, not webcam fetches
+    limiter: Arc<RequestLimiter>,
+    rtsp: RtspSnapshotter,
+    /// Most bytes read from a webcam for one image
+    max_download_bytes: usize,
+    /// Wall-clock limit on one webcam image fetch
+    fetch_deadline: Duration,
+    /// Which webcam URLs may be fetched
+    target_policy: Arc<TargetPolicy>,
+    /// Resolves webcam host names for the target policy
+    host_lookup: Arc<dyn HostLookup>,
+    /// Where target policy decisions are recorded
+    audit_log: AuditLog,
+    /// Longest reverse DNS may add to a search
+    reverse_dns_budget: Duration,
+    /// Shared by clones; last fetch from each webcam host
+    host_cooldown: Arc<HostCooldown>,
+    /// Shared by clones; search and host requests made and allowed
+    credit_budget: Arc<CreditBudget>,
 }
 
 impl ShodanClient {
+    /// Client with the default settings
     pub fn new(api_key: String) -> Self {
-        Self {
-            client: Client::new(),
-            api_key,
-            base_url: "https://api.shodan.io".to_string(),
-        }
+        ShodanClientBuilder::default()
+            .build(api_key)
+            .expect("the default Shodan client settings are valid")
     }
 
-    /// Search for webcams using various common queries
-    pub async fn search_webcams(&self, limit: Option<u32>) -> Result<Vec<RemoteWebcam>, ShodanError> {
-        info!("Searching for webcams via Shodan");
-
-        // Common webcam search queries
-        let queries = vec![
-            "Server: SQ-WEBCAM",
-            "Server: yawcam",
-            "Server: webcamXP",
-            "\"Server: IP Webcam Server\"",
-            "\"200 OK\" \"Content-Type: multipart/x-mixed-replace\"",
-            "port:8080 \"mjpeg\"",
-            "port:8081 \"mjpeg\"",
-            "port:554 \"rtsp\"",
-            "\"axis video server\"",
-            "\"live view axis\"",
-            "inurl:\"view/view.shtml\"",
-            "inurl:\"ViewerFrame?Mode=\"",
-            "inurl:\"MultiCameraFrame?Mode=\"",
-        ];
-
-        let mut all_webcams = Vec::new();
-        let limit_per_query = limit.map(|l| l / queries.len() as u32).unwrap_or(10);
-
-        for query in queries.iter().take(3) { // Limit to first 3 queries to avoid rate limits
-            match self.search(query, Some(limit_per_query)).await {
-                Ok(results) => {
-                    let webcams = self.process_search_results(results);
-                    all_webcams.extend(webcams);
-                    
-                    // Add small delay to avoid rate limiting
-                    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-                }
+    /// Settings for clients other than the default
+    pub fn builder() -> ShodanClientBuilder {
+        ShodanClientBuilder::default()
+    }
+
+    /// Send API requests through `limiter`, e.g. one shared with the
+    /// clients of other keys on the same plan
+    pub fn with_request_limiter(mut self, limiter: Arc<RequestLimiter>) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    /// Space fetches from each webcam host through `cooldown`, e.g. one
+    /// shared with the clients of other keys
+    pub fn with_host_cooldown(mut self, cooldown: Arc<HostCooldown>) -> Self {
+        self.host_cooldown = cooldown;
+        self
+    }
+
+    /// Count search and host requests against `budget`, e.g. one shared
+    /// with the clients of other keys
+    pub fn with_credit_budget(mut self, budget: Arc<CreditBudget>) -> Self {
+        self.credit_budget = budget;
+        self
+    }
+
+    /// The budget search and host requests are counted against
+    pub fn credit_budget(&self) -> &Arc<CreditBudget> {
+        &self.credit_budget
+    }
+
+    /// Count a search or host request against the budget, or fail with
+    /// `ShodanError::BudgetExceeded` without sending it
+    fn claim_budget(&self) -> Result<(), ShodanError> {
+        self.credit_budget.claim().map_err(|exceeded| {
+            warn!("Refused a Shodan request: {}", exceeded);
+            ShodanError::BudgetExceeded(exceeded)
+        })
+    }
+
+    /// Record a fetch from webcam host `host`, or fail with
+    /// `ShodanError::HostCooldown` if the last was less than the cooldown
+    /// interval ago. Capturing, probing and validating each claim a host
+    /// once, however many requests they then send it.
+    pub fn claim_host(&self, host: &str) -> Result<(), ShodanError> {
+        self.host_cooldown.claim(host).map_err(|retry_after| {
+            debug!("Refused to fetch from {} for another {:?}", host, retry_after);
+            ShodanError::HostCooldown { host: host.to_string(), retry_after }
+        })
+    }
+
+    /// Retry requests Shodan answers with 429 according to `policy`
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Send API requests to `base_url` instead of `https://api.shodan.io`,
+    /// e.g. a proxy or a test server
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Search for up to `limit` webcams (default `DEFAULT_SEARCH_LIMIT`)
+    /// with the queries of `plan`, e.g. made by `select_queries`. Webcams
+    /// Shodan has no hostname for get their reverse DNS name, as far as
+    /// the budget allows.
+    pub async fn search_webcams(&self, limit: Option<u32>, filters: &SearchFilters, plan: &QueryPlan) -> Result<WebcamSearch, ShodanError> {
+        self.search_webcams_stream(limit, filters, plan).finish().await
+    }
+
+    /// `search_webcams` as a stream of the webcams each query adds, as it
+    /// answers; `WebcamStream::finish` returns the whole search
+    pub fn search_webcams_stream(&self, limit: Option<u32>, filters: &SearchFilters, plan: &QueryPlan) -> WebcamStream<'_> {
+        let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+        info!("Searching for up to {} webcams via Shodan with {} of {} queries planned",
+              limit, plan.planned, plan.queries.len());
+        debug!("Search filters: {:?}", filters);
+
+        let collector = Arc::new(Mutex::new(WebcamCollector::new(plan, limit, filters, &filters.query_suffix())));
+        let batches = futures::stream::unfold(Arc::clone(&collector), move |collector| async move {
+            let next = collector.lock().unwrap_or_else(|e| e.into_inner()).next_query();
+            let (query, share) = next?;
+            let results = self.search(&query, Some(share)).await;
+            self.pause().await;
+            let results = results.map(|response| self.process_search_results(response));
+            let added = collector.lock().unwrap_or_else(|e| e.into_inner()).add(&query, results);
+            Some((added, collector))
+        });
+        WebcamStream { client: self, collector, batches: Box::pin(batches) }
+    }
+
+    /// Count the results of each of `queries`, narrowed by `filters`,
+    /// without fetching any. A failed query is logged and skipped; if
+    /// every query fails, the last error is returned.
+    pub async fn count_webcams(&self, queries: &[String], filters: &SearchFilters) -> Result<Vec<QueryCount>, ShodanError> {
+        info!("Counting webcams via Shodan with {} queries", queries.len());
+        let suffix = filters.query_suffix();
+        let mut counts = Vec::new();
+        let mut last_error = None;
+
+        for query in queries {
+            match self.count(&format!("{}{}", query, suffix), COUNT_FACETS).await {
+                Ok(mut count) => counts.push(QueryCount {
+                    query: query.clone(),
+                    total: count.total,
+                    top_countries: count.facets.remove("country").unwrap_or_default(),
+                    top_orgs: count.facets.remove("org").unwrap_or_default(),
+                }),
                 Err(e) => {
-                    warn!("Failed to search with query '{}': {}", query, e);
+                    warn!("Failed to count with query '{}': {}", query, e);
+                    last_error = Some(e);
                 }
             }
+            self.pause().await;
+        }
+
+        match last_error {
+            Some(e) if counts.is_empty() => Err(e),
+            _ => Ok(counts),
+        }
+    }
+
+    /// Number of results of `query` and its top `facets` (`name:size`),
+    /// without the results themselves; costs no query credits on most plans
+    pub async fn count(&self, query: &str, facets: &[&str]) -> Result<ShodanCount, ShodanError> {
+        debug!("Executing Shodan count: {}", query);
+
+        let facets = facets.join(",");
+        let mut params = vec![("query", query)];
+        if !facets.is_empty() {
+            params.push(("facets", &facets));
+        }
+        let response = self.get("/shodan/host/count", &params).await?;
+
+        match response.status() {
+            reqwest::StatusCode::OK => self.read_json(response).await,
+            reqwest::StatusCode::UNAUTHORIZED => Err(ShodanError::Unauthorized),
+            status => {
+                let error_text = self.api_key.scrub(&response.text().await.unwrap_or_default());
+                error!("Shodan API error {}: {}", status, error_text);
+                Err(ShodanError::Generic(format!("HTTP {}: {}", status, error_text)))
+            }
+        }
+    }
+
+    /// Whether the client has a key for the Shodan API. One without only
+    /// fetches webcams, for searches run by other discovery services.
+    pub fn has_api_key(&self) -> bool {
+        !self.api_key.expose().is_empty()
+    }
+
+    /// Check the API key against Shodan's account endpoint, which costs no
+    /// query credits
+    pub async fn validate_key(&self) -> Result<(), ShodanError> {
+        self.api_info().await.map(|_| ())
+    }
+
+    /// Plan and remaining credits of the key's account; costs no credits
+    pub async fn api_info(&self) -> Result<ShodanAccountInfo, ShodanError> {
+        let response = self.get("/api-info", &[]).await?;
+
+        match response.status() {
+            reqwest::StatusCode::OK => self.read_json(response).await,
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Err(ShodanError::Unauthorized),
+            status => Err(ShodanError::Generic(format!("HTTP {}", status))),
+        }
+    }
+
+    /// Everything Shodan knows about `ip`; with `history`, banners it
+    /// no longer sees are included. An `ip` that isn't an IPv4 or IPv6
+    /// address is refused before any request is made.
+    pub async fn host(&self, ip: &str, history: bool) -> Result<ShodanHost, ShodanError> {
+        let ip: IpAddr = ip.trim().parse()
+            .map_err(|_| ShodanError::InvalidQuery(format!("{} is not an IP address", ip)))?;
+        debug!("Looking up Shodan host {}", ip);
+
+        let params: &[(&str, &str)] = if history { &[("history", "true")] } else { &[] };
+        self.claim_budget()?;
+        let response = self.get(&format!("/shodan/host/{}", ip), params).await?;
+
+        match response.status() {
+            reqwest::StatusCode::OK => self.read_json(response).await,
+            reqwest::StatusCode::NOT_FOUND => Err(ShodanError::NotFound(ip.to_string())),
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Err(ShodanError::Unauthorized),
+            status => {
+                let error_text = self.api_key.scrub(&response.text().await.unwrap_or_default());
+                error!("Shodan API error {}: {}", status, error_text);
+                Err(ShodanError::Generic(format!("HTTP {}: {}", status, error_text)))
+            }
+        }
+    }
+
+    /// Shodan Labs' honeypot score of `ip`, from 0.0 to 1.0; one request,
+    /// waiting its turn with the request limiter like any other. An `ip`
+    /// that isn't an IPv4 or IPv6 address is refused before any request
+    /// is made.
+    pub async fn honeyscore(&self, ip: &str) -> Result<f64, ShodanError> {
+        let ip: IpAddr = ip.trim().trim_matches(['[', ']']).parse()
+            .map_err(|_| ShodanError::InvalidQuery(format!("{} is not an IP address", ip)))?;
+        debug!("Looking up the honeyscore of {}", ip);
+
+        let response = self.get(&format!("/labs/honeyscore/{}", ip), &[]).await?;
+
+        match response.status() {
+            reqwest::StatusCode::OK => {
+                let text = response.text().await?;
+                text.trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|score| (0.0..=1.0).contains(score))
+                    .ok_or_else(|| ShodanError::Generic(format!("Unexpected honeyscore '{}'", text.trim().chars().take(BODY_PREVIEW_CHARS).collect::<String>())))
+            }
+            reqwest::StatusCode::NOT_FOUND => Err(ShodanError::NotFound(ip.to_string())),
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Err(ShodanError::Unauthorized),
+            status => {
+                let error_text = self.api_key.scrub(&response.text().await.unwrap_or_default());
+                error!("Shodan API error {}: {}", status, error_text);
+                Err(ShodanError::Generic(format!("HTTP {}: {}", status, error_text)))
+            }
+        }
+    }
+
+    /// Ask Shodan to scan `ips` now, rather than waiting for its crawlers;
+    /// costs a scan credit per address. The addresses are checked with
+    /// `parse_scan_targets` before any request is made.
+    pub async fn scan<S: AsRef<str>>(&self, ips: &[S]) -> Result<ShodanScan, ShodanError> {
+        let ips = parse_scan_targets(ips).map_err(ShodanError::InvalidQuery)?;
+        let ips = ips.iter().map(|ip| ip.to_string()).collect::<Vec<_>>().join(",");
+        info!("Requesting a Shodan scan of {}", ips);
+
+        let response = self.post_form("/shodan/scan", &[("ips", &ips)]).await?;
+
+        match response.status() {
+            reqwest::StatusCode::OK | reqwest::StatusCode::CREATED => self.read_json(response).await,
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Err(ShodanError::Unauthorized),
+            status => {
+                let error_text = self.api_key.scrub(&response.text().await.unwrap_or_default());
+                error!("Shodan API error {}: {}", status, error_text);
+                Err(ShodanError::Generic(format!("HTTP {}: {}", status, error_text)))
+            }
+        }
+    }
+
+    /// Progress of the on-demand scan `id`; costs no credits
+    pub async fn scan_status(&self, id: &str) -> Result<ShodanScanStatus, ShodanError> {
+        let id = id.trim();
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(ShodanError::InvalidQuery(format!("'{}' is not a scan id", id)));
         }
+        debug!("Checking Shodan scan {}", id);
 
-        // Remove duplicates based on IP
-        all_webcams.sort_by(|a, b| a.ip.cmp(&b.ip));
-        all_webcams.dedup_by(|a, b| a.ip == b.ip);
+        let response = self.get(&format!("/shodan/scan/{}", id), &[]).await?;
 
-        info!("Found {} unique webcams", all_webcams.len());
-        Ok(all_webcams)
+        match response.status() {
+            reqwest::StatusCode::OK => self.read_json(response).await,
+            reqwest::StatusCode::NOT_FOUND => Err(ShodanError::NotFound(format!("scan {}", id))),
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Err(ShodanError::Unauthorized),
+            status => {
+                let error_text = self.api_key.scrub(&response.text().await.unwrap_or_default());
+                error!("Shodan API error {}: {}", status, error_text);
+                Err(ShodanError::Generic(format!("HTTP {}: {}", status, error_text)))
+            }
+        }
     }
 
     /// Generic search function
     pub async fn search(&self, query: &str, limit: Option<u32>) -> Result<ShodanSearchResponse, ShodanError> {
         debug!("Executing Shodan search: {}", query);
 
-        let url = format!("{}/shodan/host/search", self.base_url);
-        let mut params = vec![
-            ("key", self.api_key.as_str()),
-            ("query", query),
-        ];
-
+        let mut params = vec![("query", query)];
         let limit_str;
         if let Some(limit) = limit {
             limit_str = limit.to_string();
             params.push(("limit", &limit_str));
         }
-
-        let response = self.client
-            .get(&url)
-            .query(&params)
-            .send()
-            .await?;
+        self.claim_budget()?;
+        let response = self.get("/shodan/host/search", &params).await?;
 
         match response.status() {
             reqwest::StatusCode::OK => {
-                let search_response: ShodanSearchResponse = response.json().await?;
+                let search_response: ShodanSearchResponse = self.read_json(response).await?;
                 debug!("Search returned {} results", search_response.matches.len());
                 Ok(search_response)
             }
             reqwest::StatusCode::UNAUTHORIZED => Err(ShodanError::Unauthorized),
-            reqwest::StatusCode::TOO_MANY_REQUESTS => Err(ShodanError::RateLimitExceeded),
             status => {
-                let error_text = response.text().await.unwrap_or_default();
+                let error_text = self.api_key.scrub(&response.text().await.unwrap_or_default());
                 error!("Shodan API error {}: {}", status, error_text);
                 Err(ShodanError::Generic(format!("HTTP {}: {}", status, error_text)))
             }
         }
     }
 
+    /// GET `path` of the API with the key and `params`
+    async fn get(&self, path: &str, params: &[(&str, &str)]) -> Result<reqwest::Response, ShodanError> {
+        self.send(reqwest::Method::GET, path, params, &[]).await
+    }
+
+    /// POST `form` to `path` of the API, with the key
+    async fn post_form(&self, path: &str, form: &[(&str, &str)]) -> Result<reqwest::Response, ShodanError> {
+        self.send(reqwest::Method::POST, path, &[], form).await
+    }
+
+    /// Send a request to `path` of the API with the key, `params` and, if
+    /// any, `form` as its body, each attempt waiting its turn with the
+    /// request limiter. A 429 answer is retried as the retry policy allows
+    /// and, once it doesn't, becomes `RateLimitExceeded`; any other answer
+    /// is the caller's to read. A client without a key, used only to fetch
+    /// webcams, sends nothing.
+    async fn send(&self, method: reqwest::Method, path: &str, params: &[(&str, &str)], form: &[(&str, &str)]) -> Result<reqwest::Response, ShodanError> {
+        if !self.has_api_key() {
+            return Err(ShodanError::NoApiKey);
+        }
+        let url = format!("{}{}", self.base_url, path);
+        let mut retry = 0;
+        loop {
+            self.limiter.acquire().await;
+            let mut request = self.client
+                .request(method.clone(), &url)
+                .query(&[("key", self.api_key.expose())])
+                .query(params);
+            if !form.is_empty() {
+                request = request.form(form);
+            }
+            let response = request.send().await?;
+            if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response);
+            }
+
+            if let Ok(mut pacing) = self.pacing.lock() {
+                pacing.record_throttled();
+            }
+            let suggested = retry_after(&response);
+            let wait = self.retry.delay(retry, suggested);
+            if retry >= self.retry.max_retries || wait > self.retry.max_delay {
+                warn!("Shodan is rate limiting {}; giving up after {} retries", path, retry);
+                return Err(ShodanError::RateLimitExceeded { retry_after: Some(wait) });
+            }
+            debug!("Shodan is rate limiting {}; retrying in {:?}", path, wait);
+            tokio::time::sleep(wait).await;
+            retry += 1;
+        }
+    }
+
+    /// Body of a successful API answer parsed as `T`. One that doesn't
+    /// parse, e.g. a proxy's HTML error page or a truncated body, is a
+    /// `Generic` error quoting its start rather than a failed request, so
+    /// it isn't taken for a network problem worth retrying.
+    async fn read_json<T: serde::de::DeserializeOwned>(&self, response: reqwest::Response) -> Result<T, ShodanError> {
+        let text = response.text().await?;
+        serde_json::from_str(&text).map_err(|e| {
+            let preview: String = self.api_key.scrub(&text).split_whitespace().collect::<Vec<_>>().join(" ").chars().take(BODY_PREVIEW_CHARS).collect();
+            error!("Malformed Shodan API response: {}", e);
+            ShodanError::Generic(format!("Malformed response from Shodan ({}): {}", e, preview))
+        })
+    }
+
+    /// Wait between the queries of a search, longer while Shodan is rate
+    /// limiting
+    async fn pause(&self) {
+        let delay = self.pacing.lock().map(|mut pacing| pacing.next_delay()).unwrap_or(PACING_STEP);
+        if !delay.is_zero() {
+            debug!("Pausing {:?} before the next Shodan query", delay);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
     /// Process search results and extract webcam information
     fn process_search_results(&self, response: ShodanSearchResponse) -> Vec<RemoteWebcam> {
         response.matches
@@ -187,19 +2245,28 @@ impl ShodanClient {
 
     /// Extract webcam information from a Shodan result
     fn extract_webcam_info(&self, result: ShodanResult) -> Option<RemoteWebcam> {
+        let Some(ip) = result.address() else {
+            debug!("Skipping Shodan result on port {} without an address", result.port);
+            return None;
+        };
         let access_type = self.determine_access_type(&result);
-        let url = self.construct_webcam_url(&result, &access_type)?;
+        // IPv6 addresses are bracketed in URLs
+        let host = if ip.contains(':') { format!("[{}]", ip) } else { ip.clone() };
+        let url = self.construct_webcam_url(&result, &host, &access_type)?;
+        let classification = classify(result.product.as_deref(), &result.data, result.port);
 
         Some(RemoteWebcam {
-            ip: result.ip,
+            ip,
             port: result.port,
             url,
-            hostname: result.hostname,
+            hostname: result.hostnames.into_iter().next(),
             location: result.location,
             org: result.org,
             product: result.product,
             last_seen: result.timestamp,
             access_type,
+            classification,
+            sources: vec![SOURCE.to_string()],
         })
     }
 
@@ -219,7 +2286,7 @@ impl ShodanClient {
     }
 
     /// Construct a webcam URL based on the result and access type
-    fn construct_webcam_url(&self, result: &ShodanResult, access_type: &WebcamAccessType) -> Option<String> {
+    fn construct_webcam_url(&self, result: &ShodanResult, host: &str, access_type: &WebcamAccessType) -> Option<String> {
         match access_type {
             WebcamAccessType::MJPEG => {
                 // Common MJPEG endpoints
@@ -234,43 +2301,652 @@ impl ShodanClient {
                 
                 for endpoint in endpoints {
                     if result.data.contains(endpoint) {
-                        return Some(format!("http://{}:{}{}", result.ip, result.port, endpoint));
+                        return Some(format!("http://{}:{}{}", host, result.port, endpoint));
                     }
                 }
                 
                 // Default MJPEG endpoint
-                Some(format!("http://{}:{}/mjpeg", result.ip, result.port))
+                Some(format!("http://{}:{}/mjpeg", host, result.port))
             }
             WebcamAccessType::RTSP => {
-                Some(format!("rtsp://{}:{}/", result.ip, result.port))
+                Some(format!("rtsp://{}:{}/", host, result.port))
             }
             WebcamAccessType::HTTP => {
-                Some(format!("http://{}:{}/", result.ip, result.port))
+                Some(format!("http://{}:{}/", host, result.port))
             }
-            WebcamAccessType::Unknown => {
-                Some(format!("http://{}:{}/", result.ip, result.port))
+            WebcamAccessType::ONVIF | WebcamAccessType::Unknown => {
+                Some(format!("http://{}:{}/", host, result.port))
             }
         }
     }
 
-    /// Attempt to fetch an image from a remote webcam
-    pub async fn fetch_webcam_image(&self, webcam: &RemoteWebcam) -> Result<Vec<u8>, ShodanError> {
-        debug!("Fetching image from webcam: {}", webcam.url);
+    /// Attempt to fetch an image from a remote webcam; of an MJPEG or
+    /// RTSP stream, its first frame
+    pub async fn fetch_webcam_image(&self, webcam: &RemoteWebcam) -> Result<RemoteImage, ShodanError> {
+        self.fetch_webcam_image_with_credentials(webcam, None).await
+    }
 
-        let response = self.client
-            .get(&webcam.url)
-            .timeout(std::time::Duration::from_secs(10))
-            .send()
-            .await?;
+    /// `fetch_webcam_image`, logging in with `credentials` if the webcam
+    /// asks for HTTP Basic or Digest authentication. RTSP webcams get them
+    /// in the URL.
+    pub async fn fetch_webcam_image_with_credentials(
+        &self,
+        webcam: &RemoteWebcam,
+        credentials: Option<&WebcamCredentials>,
+    ) -> Result<RemoteImage, ShodanError> {
+        debug!("Fetching image from webcam: {}", sanitize_url(&webcam.url));
+        self.claim_host(&webcam.ip)?;
+        self.fetch_claimed_webcam_image(webcam, credentials).await
+    }
+
+    /// `fetch_webcam_image_with_credentials` of a webcam whose host the
+    /// caller has already claimed against the cooldown
+    pub(crate) async fn fetch_claimed_webcam_image(
+        &self,
+        webcam: &RemoteWebcam,
+        credentials: Option<&WebcamCredentials>,
+    ) -> Result<RemoteImage, ShodanError> {
+        if rtsp::is_rtsp_url(&webcam.url) {
+            let pinned = self.pinned_rtsp_url(&webcam.url).await?;
+            let url = match credentials {
+                Some(credentials) => with_credentials(&pinned, credentials),
+                None => pinned,
+            };
+            let bytes = self.rtsp.snapshot(&url).await?;
+            info!("Took a {} byte RTSP snapshot of {}", bytes.len(), sanitize_url(&webcam.url));
+            return Ok(RemoteImage { bytes, mime_type: "image/jpeg".to_string(), tls_unverified: false });
+        }
+
+        self.fetch_image(&webcam.url, credentials, WEBCAM_FETCH_TIMEOUT).await
+    }
+
+    /// Type, dimensions and size of a remote webcam's image, downloading
+    /// only as far as its header. An RTSP webcam's snapshot is taken whole.
+    pub async fn fetch_webcam_image_info(
+        &self,
+        webcam: &RemoteWebcam,
+        credentials: Option<&WebcamCredentials>,
+    ) -> Result<RemoteImageInfo, ShodanError> {
+        if rtsp::is_rtsp_url(&webcam.url) {
+            let image = self.fetch_webcam_image_with_credentials(webcam, credentials).await?;
+            return Ok(RemoteImageInfo::of(&image));
+        }
+        debug!("Fetching the image header of webcam: {}", sanitize_url(&webcam.url));
+        self.claim_host(&webcam.ip)?;
+        self.fetch_image_info(&webcam.url, credentials, WEBCAM_FETCH_TIMEOUT).await
+    }
+
+    /// Which webcam URLs this client fetches
+    pub fn target_policy(&self) -> &TargetPolicy {
+        &self.target_policy
+    }
+
+    /// Record target policy decisions in `log`
+    pub fn with_audit_log(mut self, log: AuditLog) -> Self {
+        self.audit_log = log;
+        self
+    }
+
+    /// Check `url` against the target policy, before it is requested,
+    /// and audit the decision
+    pub async fn check_target(&self, url: &str) -> Result<(), ShodanError> {
+        let started = Instant::now();
+        let decision = self.target_policy.check_with(url, self.host_lookup.as_ref()).await;
+        self.audit_target(url, &decision, started);
+        decision
+    }
+
+    /// `url` checked against the target policy, with its host name
+    /// replaced by the address it resolved to, checked too. ffmpeg would
+    /// otherwise look the name up again after the check, and could get
+    /// another answer.
+    async fn pinned_rtsp_url(&self, url: &str) -> Result<String, ShodanError> {
+        self.check_target(url).await?;
+        let mut parsed = url::Url::parse(url).map_err(|e| ShodanError::Generic(format!("Invalid URL {}: {}", sanitize_url(url), e)))?;
+        let Some(url::Host::Domain(host)) = parsed.host().map(|host| host.to_owned()) else {
+            return Ok(url.to_string());
+        };
+        let addresses = self.host_lookup.lookup(&host).await.unwrap_or_default();
+        let ip = addresses.first().copied().ok_or_else(|| ShodanError::RtspConnection(format!("{} did not resolve", host)))?;
+        self.check_target_ip(url, ip)?;
+        let _ = parsed.set_ip_host(ip);
+        debug!("Connecting to {} at {}", host, ip);
+        Ok(parsed.to_string())
+    }
+
+    /// `check_target` for an address `url`'s host is reached at directly
+    pub fn check_target_ip(&self, url: &str, ip: IpAddr) -> Result<(), ShodanError> {
+        let started = Instant::now();
+        let decision = self.target_policy.check_ip(url, ip);
+        self.audit_target(url, &decision, started);
+        decision
+    }
+
+    fn audit_target(&self, url: &str, decision: &Result<(), ShodanError>, started: Instant) {
+        let arguments = match decision {
+            Ok(()) => json!({ "decision": "allowed" }),
+            Err(ShodanError::TargetBlocked { reason, .. }) => json!({ "decision": "denied", "reason": reason }),
+            Err(e) => json!({ "decision": "denied", "reason": e.to_string() }),
+        };
+        self.audit_log.record(AuditEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            tool: AUDIT_TOOL.to_string(),
+            arguments,
+            camera_index: None,
+            url: Some(sanitize_url(url)),
+            result_bytes: 0,
+            success: decision.is_ok(),
+            error_code: decision.is_err().then(|| "TARGET_BLOCKED".to_string()),
+            duration_ms: started.elapsed().as_millis() as u64,
+            prev_hash: String::new(),
+        });
+    }
+
+    /// This client accepting webcam certificates that fail verification,
+    /// or not, e.g. as one call asks
+    pub fn accepting_invalid_certs(&self, accept: bool) -> Self {
+        Self { accept_invalid_certs: accept, ..self.clone() }
+    }
+
+    pub fn accepts_invalid_certs(&self) -> bool {
+        self.accept_invalid_certs
+    }
+
+    /// GET `url` from a webcam. A certificate that fails verification is
+    /// accepted if the client accepts invalid certificates, and the
+    /// response marked as unverified; otherwise it fails with
+    /// `ShodanError::TlsError`.
+    async fn send_to_webcam(&self, url: &str, timeout: Duration) -> Result<(reqwest::Response, bool), ShodanError> {
+        self.send_to_webcam_with(url, timeout, |client| client.get(url)).await
+    }
+
+    /// `send_to_webcam` for the request `request` makes with a client
+    async fn send_to_webcam_with(
+        &self,
+        url: &str,
+        timeout: Duration,
+        request: impl Fn(&Client) -> reqwest::RequestBuilder,
+    ) -> Result<(reqwest::Response, bool), ShodanError> {
+        let error = match request(&self.fetch_client).timeout(timeout).send().await {
+            Ok(response) => return Ok((response, false)),
+            Err(error) => error,
+        };
+        if let Some(reason) = resolver_refusal(&error) {
+            return Err(ShodanError::TargetBlocked { target: sanitize_url(url), reason });
+        }
+        let Some(reason) = certificate_error(&error) else {
+            return Err(error.into());
+        };
+        if self.accept_invalid_certs {
+            warn!("Accepting the invalid TLS certificate of {} as asked: {}", sanitize_url(url), reason);
+            let response = request(&self.insecure_fetch_client).timeout(timeout).send().await?;
+            return Ok((response, true));
+        }
+        // Connect again without verifying, only to read the certificate
+        let certificate = match self.insecure_fetch_client.get(url).timeout(timeout).send().await {
+            Ok(response) => response
+                .extensions()
+                .get::<reqwest::tls::TlsInfo>()
+                .and_then(|info| info.peer_certificate())
+                .and_then(certificate_names),
+            Err(_) => None,
+        };
+        warn!("TLS certificate of {} failed verification: {}", sanitize_url(url), reason);
+        Err(ShodanError::TlsError { reason, certificate })
+    }
+
+    /// Client for a webcam request, `tls_unverified` if its certificate
+    /// was accepted unverified
+    fn webcam_client(&self, tls_unverified: bool) -> &Client {
+        if tls_unverified {
+            &self.insecure_fetch_client
+        } else {
+            &self.fetch_client
+        }
+    }
+
+    /// GET `url` from a webcam, following up to `MAX_REDIRECTS` redirects,
+    /// with the URL and every redirect target checked against the target
+    /// policy. Returns the URL that answered, and its response.
+    pub(crate) async fn get_checked(&self, url: &str, timeout: Duration) -> Result<WebcamResponse, ShodanError> {
+        let mut target = url.to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            self.check_target(&target).await?;
+            let (response, tls_unverified) = self.send_to_webcam(&target, timeout).await?;
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .filter(|_| response.status().is_redirection());
+            let Some(location) = location else {
+                return Ok(WebcamResponse { url: target, response, tls_unverified });
+            };
+            let next = url::Url::parse(&target)
+                .and_then(|base| base.join(location))
+                .map_err(|_| ShodanError::Generic(format!("Invalid redirect from {}", sanitize_url(&target))))?;
+            debug!("{} redirects to {}", sanitize_url(&target), sanitize_url(next.as_str()));
+            target = next.to_string();
+        }
+        Err(ShodanError::TargetBlocked {
+            target: sanitize_url(url),
+            reason: format!("more than {} redirects", MAX_REDIRECTS),
+        })
+    }
+
+    /// POST the SOAP `envelope` of `action` to the ONVIF service at `url`,
+    /// checked against the target policy like any webcam request. Returns
+    /// the status and the body, faults included, up to
+    /// `MAX_SOAP_RESPONSE_BYTES`.
+    pub(crate) async fn post_soap(
+        &self,
+        url: &str,
+        action: &str,
+        envelope: String,
+        timeout: Duration,
+    ) -> Result<(reqwest::StatusCode, String), ShodanError> {
+        self.check_target(url).await?;
+        let content_type = format!("application/soap+xml; charset=utf-8; action=\"{}\"", action);
+        let (response, _) = self.send_to_webcam_with(url, timeout, |client| {
+            client.post(url).header(reqwest::header::CONTENT_TYPE, content_type.as_str()).body(envelope.clone())
+        }).await?;
+        let status = response.status();
+        let body = read_capped(response, MAX_SOAP_RESPONSE_BYTES).await?;
+        Ok((status, String::from_utf8_lossy(&body).into_owned()))
+    }
+
+    /// Image at the HTTP `url`, each request allowed `timeout` and the
+    /// whole fetch the client's deadline
+    pub(crate) async fn fetch_image(
+        &self,
+        url: &str,
+        credentials: Option<&WebcamCredentials>,
+        timeout: Duration,
+    ) -> Result<RemoteImage, ShodanError> {
+        match tokio::time::timeout(self.fetch_deadline, self.fetch_image_unbounded(url, credentials, timeout, FetchExtent::Whole)).await {
+            Ok(fetched) => fetched.map(|(image, _)| image),
+            Err(_) => {
+                warn!("Gave up on {} after {:?}", sanitize_url(url), self.fetch_deadline);
+                Err(ShodanError::FetchDeadline { deadline: self.fetch_deadline })
+            }
+        }
+    }
+
+    /// What the image at the HTTP `url` is, reading only as far as its
+    /// header
+    pub(crate) async fn fetch_image_info(
+        &self,
+        url: &str,
+        credentials: Option<&WebcamCredentials>,
+        timeout: Duration,
+    ) -> Result<RemoteImageInfo, ShodanError> {
+        let fetch = self.fetch_image_unbounded(url, credentials, timeout, FetchExtent::Header);
+        let (image, size_bytes) = match tokio::time::timeout(self.fetch_deadline, fetch).await {
+            Ok(fetched) => fetched?,
+            Err(_) => {
+                warn!("Gave up on {} after {:?}", sanitize_url(url), self.fetch_deadline);
+                return Err(ShodanError::FetchDeadline { deadline: self.fetch_deadline });
+            }
+        };
+        Ok(RemoteImageInfo { size_bytes, ..RemoteImageInfo::of(&image) })
+    }
+
+    /// The answer to a successful webcam request, read as far as `extent`
+    /// asks and never past the download limit. Of an MJPEG stream, which
+    /// never ends, the first frame is read.
+    pub(crate) async fn read_image_body(&self, response: reqwest::Response, extent: FetchExtent) -> Result<ImageBody, ShodanError> {
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        match content_type {
+            Some(content_type) if is_multipart(&content_type) => {
+                debug!("Reading the first frame of the {} stream", content_type);
+                let (bytes, content_type) = read_first_part(response, boundary_param(&content_type), self.max_download_bytes).await?;
+                Ok(ImageBody { size_bytes: Some(bytes.len() as u64), bytes, content_type })
+            }
+            content_type => {
+                let length = response.content_length();
+                let (bytes, ended) = match extent {
+                    FetchExtent::Whole => (read_capped(response, self.max_download_bytes).await?, true),
+                    FetchExtent::Header => {
+                        read_until(response, self.max_download_bytes, |body| image_processing::image_dimensions(body).is_some()).await?
+                    }
+                };
+                let size_bytes = length.or_else(|| ended.then_some(bytes.len() as u64));
+                Ok(ImageBody { bytes, content_type, size_bytes })
+            }
+        }
+    }
+
+    /// The image at `url` as far as `extent` asks, and the size of the
+    /// whole image when known
+    async fn fetch_image_unbounded(
+        &self,
+        url: &str,
+        credentials: Option<&WebcamCredentials>,
+        timeout: Duration,
+        extent: FetchExtent,
+    ) -> Result<(RemoteImage, Option<u64>), ShodanError> {
+        let WebcamResponse { url: answered, mut response, tls_unverified } = self.get_checked(url, timeout).await?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            // Credentials belong to the webcam they were given for, not to
+            // whichever host a redirect pointed at
+            let credentials = match credentials {
+                Some(_) if !same_origin(url, &answered) => {
+                    warn!("Not sending credentials for {} to {}", sanitize_url(url), sanitize_url(&answered));
+                    None
+                }
+                credentials => credentials,
+            };
+            response = self.authenticate(&answered, response, credentials, timeout, tls_unverified).await?;
+        }
+        let url = answered.as_str();
 
         if response.status().is_success() {
-            let bytes = response.bytes().await?;
-            info!("Successfully fetched {} bytes from {}", bytes.len(), webcam.url);
-            Ok(bytes.to_vec())
+            let ImageBody { bytes, content_type, size_bytes } = self.read_image_body(response, extent).await?;
+            let mime_type = match detect_image_type(&bytes, content_type.as_deref()) {
+                Ok(mime_type) => mime_type,
+                Err(e) => {
+                    warn!("{} did not return an image: {}", sanitize_url(url), e);
+                    return Err(e);
+                }
+            };
+            info!("Successfully fetched {} bytes of {} from {}", bytes.len(), mime_type, sanitize_url(url));
+            Ok((RemoteImage { bytes, mime_type, tls_unverified }, size_bytes))
         } else {
-            warn!("Failed to fetch image from {}: {}", webcam.url, response.status());
+            warn!("Failed to fetch image from {}: {}", sanitize_url(url), response.status());
             Err(ShodanError::Generic(format!("HTTP {}", response.status())))
         }
     }
+
+    /// Answer the challenge of a webcam's 401 `response` with
+    /// `credentials`, giving the response to the authenticated request
+    async fn authenticate(
+        &self,
+        url: &str,
+        response: reqwest::Response,
+        credentials: Option<&WebcamCredentials>,
+        timeout: Duration,
+        tls_unverified: bool,
+    ) -> Result<reqwest::Response, ShodanError> {
+        let challenges: Vec<http_auth::Challenge> = response
+            .headers()
+            .get_all(reqwest::header::WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(http_auth::parse_challenges)
+            .collect();
+        let challenge = http_auth::preferred_challenge(&challenges).or(challenges.first());
+        let scheme = challenge.map_or_else(|| "HTTP".to_string(), |challenge| challenge.scheme.clone());
+        let realm = challenge.and_then(|challenge| challenge.realm()).map(str::to_string);
+        debug!("{} asks for {} authentication", sanitize_url(url), scheme);
+
+        let Some(credentials) = credentials else {
+            return Err(ShodanError::AuthRequired { scheme, realm });
+        };
+        let authorization = challenge
+            .and_then(|challenge| http_auth::authorization(challenge, "GET", &request_uri(url), credentials));
+        let Some(authorization) = authorization else {
+            warn!("{} asks for unsupported {} authentication", sanitize_url(url), scheme);
+            return Err(ShodanError::AuthRequired { scheme, realm });
+        };
+        let response = self.webcam_client(tls_unverified)
+            .get(url)
+            .timeout(timeout)
+            .header(reqwest::header::AUTHORIZATION, authorization)
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(ShodanError::AuthRejected { scheme, realm });
+        }
+        Ok(response)
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// `count` webcams with distinct addresses starting at `first`
+    fn webcams(first: usize, count: usize) -> Vec<RemoteWebcam> {
+        (first..first + count)
+            .map(|n| RemoteWebcam {
+                ip: format!("192.0.2.{}", n),
+                port: 80,
+                url: format!("http://192.0.2.{}/", n),
+                hostname: None,
+                location: None,
+                org: None,
+                product: None,
+                last_seen: String::new(),
+                access_type: WebcamAccessType::HTTP,
+                classification: None,
+                sources: Vec::new(),
+            })
+            .collect()
+    }
+
+    fn part(buffer: &[u8], boundary: Option<&str>) -> Option<(Option<String>, Vec<u8>)> {
+        first_part(buffer, boundary).map(|part| (part.content_type, buffer[part.body].to_vec()))
+    }
+
+    #[test]
+    fn multipart_parts_end_at_their_length_or_the_next_delimiter() {
+        let with_length = b"--frame\r\nContent-Type: image/jpeg\r\nContent-Length: 4\r\n\r\nabcd\r\n--frame\r\n";
+        assert_eq!(part(with_length, Some("frame")), Some((Some("image/jpeg".to_string()), b"abcd".to_vec())));
+        // Until the whole part has arrived there is nothing to return
+        assert_eq!(part(&with_length[..58], Some("frame")), None);
+        assert_eq!(part(&with_length[..20], Some("frame")), None);
+
+        let delimited = b"--frame\r\nContent-Type: image/jpeg\r\n\r\nabcd\r\n--frame\r\n";
+        assert_eq!(part(delimited, Some("frame")), Some((Some("image/jpeg".to_string()), b"abcd".to_vec())));
+        assert_eq!(part(&delimited[..48], Some("frame")), None);
+        // The parameter with its dashes, bare LF line breaks and no parameter at all
+        assert_eq!(part(delimited, Some("--frame")).unwrap().1, b"abcd");
+        assert_eq!(part(b"--frame\ncontent-type: image/jpeg\n\nabcd\n--frame\n", None).unwrap().1, b"abcd");
+        assert_eq!(part(b"----frame\r\n\r\nabcd\r\n----frame\r\n", Some("--frame")).unwrap(), (None, b"abcd".to_vec()));
+        // An empty part before the first frame is skipped
+        let empty_first = b"--frame\r\n\r\n\r\n--frame\r\nContent-Length: 2\r\n\r\nok";
+        assert_eq!(part(empty_first, Some("frame")).unwrap().1, b"ok");
+    }
+
+    #[test]
+    fn boundaries_are_read_from_the_content_type() {
+        assert!(is_multipart("multipart/x-mixed-replace; boundary=frame"));
+        assert!(!is_multipart("image/jpeg"));
+        assert_eq!(boundary_param("multipart/x-mixed-replace; boundary=frame").as_deref(), Some("frame"));
+        assert_eq!(boundary_param("multipart/x-mixed-replace;Boundary=\"--myboundary\"").as_deref(), Some("--myboundary"));
+        assert_eq!(boundary_param("multipart/x-mixed-replace"), None);
+    }
+
+    fn plan(queries: usize, planned: usize) -> QueryPlan {
+        QueryPlan { queries: (0..queries).map(|q| q.to_string()).collect(), planned }
+    }
+
+    /// Search where query `q` has `available[q]` results, the first
+    /// `overlap` of them shared with every other query. Returns what was
+    /// found and the share each query asked for.
+    async fn run(plan: &QueryPlan, limit: u32, available: &[usize], overlap: usize) -> (WebcamSearch, Vec<u32>) {
+        let shares = Mutex::new(Vec::new());
+        let found = collect_webcams(plan, limit, &SearchFilters::default(), |query, share| {
+            shares.lock().unwrap().push(share);
+            let q: usize = query.parse().unwrap();
+            let shared = overlap.min(available[q]);
+            let mut results = webcams(0, shared);
+            results.extend(webcams(100 * (q + 1), available[q] - shared));
+            results.truncate(share as usize);
+            async move { Ok(results) }
+        })
+        .await
+        .unwrap();
+        (found, shares.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn limit_is_shared_between_the_planned_queries() {
+        // Fewer wanted than available: three planned queries split 20
+        let (found, shares) = run(&plan(13, 3), 20, &[50; 13], 0).await;
+        assert_eq!(shares, [7, 7, 6]);
+        assert_eq!(found.webcams.len(), 20);
+        assert_eq!(found.queries, ["0", "1", "2"]);
+    }
+
+    #[tokio::test]
+    async fn extra_queries_run_until_the_limit_is_met() {
+        // Exactly as many available as wanted, spread over four queries
+        let (found, shares) = run(&plan(6, 2), 8, &[2, 2, 2, 2, 2, 2], 0).await;
+        assert_eq!(found.webcams.len(), 8);
+        assert_eq!(found.queries, ["0", "1", "2", "3"]);
+        // Planned queries ask for their share, extras for all that's missing
+        assert_eq!(shares, [4, 6, 4, 2]);
+    }
+
+    #[tokio::test]
+    async fn duplicates_are_dropped_and_the_list_cut_to_the_limit() {
+        // The first two queries find the same three webcams; the last
+        // ignores its share, as Shodan returns whole pages
+        let found = collect_webcams(&plan(3, 3), 5, &SearchFilters::default(), |query, _| {
+            let results = if query == "2" { webcams(0, 10) } else { webcams(0, 3) };
+            async move { Ok(results) }
+        })
+        .await
+        .unwrap();
+        let ips: Vec<&str> = found.webcams.iter().map(|w| w.ip.as_str()).collect();
+        assert_eq!(ips, ["192.0.2.0", "192.0.2.1", "192.0.2.2", "192.0.2.3", "192.0.2.4"]);
+        assert_eq!(found.queries.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn endpoints_on_one_host_are_kept_and_duplicates_merged() {
+        // Query 0 finds two ports on one host; query 1 finds one of them
+        // again with the details query 0 lacked
+        let found = collect_webcams(&plan(2, 2), 10, &SearchFilters::default(), |query, _| {
+            let mut results = webcams(0, 1);
+            if query == "0" {
+                let mut rtsp = results[0].clone();
+                rtsp.port = 554;
+                results.push(rtsp);
+            } else {
+                results[0].hostname = Some("cam.example.org".to_string());
+                results[0].product = Some("Axis".to_string());
+            }
+            async move { Ok(results) }
+        })
+        .await
+        .unwrap();
+        let endpoints: Vec<(&str, u16)> = found.webcams.iter().map(|w| (w.ip.as_str(), w.port)).collect();
+        assert_eq!(endpoints, [("192.0.2.0", 80), ("192.0.2.0", 554)]);
+        assert_eq!(found.webcams[0].hostname.as_deref(), Some("cam.example.org"));
+        assert_eq!(found.webcams[0].product.as_deref(), Some("Axis"));
+        assert_eq!(found.webcams[1].hostname, None);
+
+        let hosts = group_by_host(found.webcams);
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].hostname.as_deref(), Some("cam.example.org"));
+        let ports: Vec<u16> = hosts[0].endpoints.iter().map(|e| e.port).collect();
+        assert_eq!(ports, [80, 554]);
+    }
+
+    #[tokio::test]
+    async fn a_radius_keeps_nearby_webcams_nearest_first() {
+        // Around London: Paris, Reading, somewhere unknown and New York
+        let near = GeoFilter::new(51.5074, -0.1278, 400.0).unwrap();
+        let filters = SearchFilters::default().with_near(Some(near));
+        let places = [Some((48.8566, 2.3522)), Some((51.4543, -0.9781)), None, Some((40.7128, -74.0060))];
+        let queries = Mutex::new(Vec::new());
+        let found = collect_webcams(&plan(1, 1), 10, &filters, |query, _| {
+            queries.lock().unwrap().push(query);
+            let mut results = webcams(0, places.len());
+            for (webcam, place) in results.iter_mut().zip(places) {
+                webcam.location = place.map(|(latitude, longitude)| ShodanLocation {
+                    country_code: None,
+                    country_name: None,
+                    city: None,
+                    region_code: None,
+                    latitude: Some(latitude),
+                    longitude: Some(longitude),
+                });
+            }
+            async move { Ok(results) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(queries.into_inner().unwrap(), ["0 geo:\"51.5074,-0.1278,400\""]);
+        let ips: Vec<&str> = found.webcams.iter().map(|w| w.ip.as_str()).collect();
+        assert_eq!(ips, ["192.0.2.1", "192.0.2.0"]);
+        assert_eq!(found.outside_radius, 2);
+    }
+
+    #[tokio::test]
+    async fn a_radius_keeps_the_nearest_webcams_past_the_limit() {
+        // Around London, limited to one: Paris comes first, Reading after it
+        let near = GeoFilter::new(51.5074, -0.1278, 400.0).unwrap();
+        let filters = SearchFilters::default().with_near(Some(near));
+        let places = [(48.8566, 2.3522), (51.4543, -0.9781)];
+        let found = collect_webcams(&plan(1, 1), 1, &filters, |_, _| {
+            let mut results = webcams(0, places.len());
+            for (webcam, (latitude, longitude)) in results.iter_mut().zip(places) {
+                webcam.location = Some(ShodanLocation {
+                    country_code: None,
+                    country_name: None,
+                    city: None,
+                    region_code: None,
+                    latitude: Some(latitude),
+                    longitude: Some(longitude),
+                });
+            }
+            async move { Ok(results) }
+        })
+        .await
+        .unwrap();
+        let ips: Vec<&str> = found.webcams.iter().map(|w| w.ip.as_str()).collect();
+        assert_eq!(ips, ["192.0.2.1"]);
+    }
+
+    #[tokio::test]
+    async fn more_wanted_than_available_returns_everything_found() {
+        let (found, _) = run(&plan(3, 1), 50, &[4, 3, 0], 1).await;
+        // 4 + 3 results, one shared
+        assert_eq!(found.webcams.len(), 6);
+        assert_eq!(found.queries, ["0", "1", "2"]);
+    }
+
+    #[test]
+    fn retries_back_off_unless_shodan_says_how_long() {
+        let policy = RetryPolicy::default();
+        let delays: Vec<Duration> = (0..5).map(|retry| policy.delay(retry, None)).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 10].map(Duration::from_secs));
+        // Retry-After is used as given, even past the cap
+        assert_eq!(policy.delay(0, Some(Duration::from_secs(30))), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn pacing_follows_rate_limiting() {
+        let mut pacing = Pacing::default();
+        assert_eq!(pacing.next_delay(), Duration::ZERO);
+        pacing.record_throttled();
+        assert_eq!(pacing.next_delay(), PACING_STEP);
+        pacing.record_throttled();
+        assert_eq!(pacing.next_delay(), PACING_STEP * 2);
+        // Calms down once Shodan stops answering 429
+        assert_eq!(pacing.next_delay(), PACING_STEP);
+        assert_eq!(pacing.next_delay(), Duration::ZERO);
+        for _ in 0..10 {
+            pacing.record_throttled();
+            pacing.next_delay();
+        }
+        assert_eq!(pacing.delay, MAX_PACING);
+    }
+
+    #[tokio::test]
+    async fn every_query_failing_is_an_error() {
+        let failed = collect_webcams(&plan(2, 2), 10, &SearchFilters::default(), |_, _| async {
+            Err::<Vec<RemoteWebcam>, _>(ShodanError::Unauthorized)
+        })
+        .await;
+        assert!(matches!(failed, Err(ShodanError::Unauthorized)));
+    }
+}
@@ -0,0 +1,302 @@
+//! Cameras on the local network found by SSDP, UPnP's discovery protocol.
+//! Many consumer cameras and DVRs answer an `M-SEARCH` multicast to
+//! 239.255.255.250:1900 with the URL of an XML device description, which
+//! names the device (`friendlyName`), its `manufacturer` and model and
+//! the web page it serves (`presentationURL`). Searches are for UPnP's
+//! camera device type and for the basic device type most cameras use;
+//! the latter also finds printers and NAS boxes, so only devices whose
+//! description looks like a camera's are kept.
+
+use crate::classify::classify;
+use crate::local_discovery::{element_text, host_for_url, local_interfaces, multicast};
+use crate::shodan::{RemoteWebcam, WebcamAccessType};
+use regex::Regex;
+use serde::Serialize;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tracing::debug;
+
+/// Source that tags webcams found by SSDP
+pub const SOURCE: &str = "ssdp";
+/// Recorded in the webcam cache as the query that found them
+pub const DISCOVERY_QUERY: &str = "SSDP M-SEARCH";
+/// Where SSDP searches are multicast
+pub const SSDP_ADDRESS: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 1900);
+/// UPnP's device type for cameras
+pub const CAMERA_DEVICE_TYPE: &str = "urn:schemas-upnp-org:device:DigitalSecurityCamera:1";
+/// Search targets of the `M-SEARCH`es sent: the camera device type, and
+/// the basic device most IP cameras and DVRs announce themselves as
+pub const CAMERA_SEARCH_TARGETS: [&str; 2] = [CAMERA_DEVICE_TYPE, "urn:schemas-upnp-org:device:Basic:1"];
+/// Longest a device description may take to fetch
+const DESCRIPTION_TIMEOUT: Duration = Duration::from_secs(3);
+/// Largest device description read
+const MAX_DESCRIPTION_BYTES: usize = 256 * 1024;
+/// Longest a device may wait before answering, as `MX` asks
+const MAX_RESPONSE_DELAY_SECS: u64 = 5;
+
+/// A device that answered the search, with its description
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SsdpDevice {
+    /// URL of its device description
+    pub location: String,
+    /// Its unique service name, e.g. `uuid:...::upnp:rootdevice`
+    pub usn: Option<String>,
+    /// The search target it answered
+    pub search_target: Option<String>,
+    /// Its `SERVER` header, usually OS and UPnP stack
+    pub server: Option<String>,
+    pub device_type: Option<String>,
+    pub friendly_name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model_name: Option<String>,
+    pub model_number: Option<String>,
+    /// Its web page, made absolute
+    pub presentation_url: Option<String>,
+    /// Address the answer came from
+    pub responder: IpAddr,
+}
+
+impl SsdpDevice {
+    /// The device as a webcam entry of the remote-webcam cache. Its URL is
+    /// its web page, or the root of the server its description is on.
+    pub fn to_webcam(&self) -> RemoteWebcam {
+        let page = self.presentation_url.as_deref().unwrap_or(&self.location);
+        let port = url::Url::parse(page).ok().and_then(|url| url.port_or_known_default()).unwrap_or(80);
+        let url = match &self.presentation_url {
+            Some(page) => page.clone(),
+            None => format!("http://{}:{}/", host_for_url(self.responder), port),
+        };
+        let product = match (&self.model_name, &self.model_number) {
+            (Some(name), Some(number)) if !name.contains(number.as_str()) => Some(format!("{} {}", name, number)),
+            (Some(name), _) => Some(name.clone()),
+            (None, number) => number.clone(),
+        };
+        let described = self.described();
+        let banner = [self.server.as_deref(), self.device_type.as_deref()].into_iter().flatten().collect::<Vec<_>>().join("\n");
+        RemoteWebcam {
+            ip: self.responder.to_string(),
+            port,
+            url,
+            hostname: None,
+            location: None,
+            org: None,
+            classification: classify(Some(described.as_str()).filter(|d| !d.is_empty()), &banner, port),
+            product,
+            last_seen: chrono::Utc::now().to_rfc3339(),
+            access_type: WebcamAccessType::HTTP,
+            sources: vec![SOURCE.to_string()],
+        }
+    }
+
+    /// Whether its description looks like a camera's, a DVR's or an NVR's:
+    /// of the camera device type, of a make `classify` knows, or named as
+    /// one
+    pub fn is_camera(&self) -> bool {
+        static CAMERA_WORDS: OnceLock<Regex> = OnceLock::new();
+        let words = CAMERA_WORDS.get_or_init(|| {
+            Regex::new(r"(?i)\b(ip ?cam(era)?|cam|camera|webcam|ipc|nvr|dvr|network video|surveillance)\b").unwrap()
+        });
+        let described = self.described();
+        self.device_type.as_deref().is_some_and(|device_type| device_type.contains("DigitalSecurityCamera"))
+            || words.is_match(&described)
+            || classify(Some(described.as_str()).filter(|d| !d.is_empty()), "", 0).is_some()
+    }
+
+    /// Its name, make and model, for matching against
+    fn described(&self) -> String {
+        [&self.friendly_name, &self.manufacturer, &self.model_name, &self.model_number]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// What a discovery found, and where it looked
+#[derive(Debug, Clone, Default)]
+pub struct SsdpDiscovery {
+    pub devices: Vec<SsdpDevice>,
+    /// Local addresses the searches were sent from
+    pub interfaces: Vec<Ipv4Addr>,
+}
+
+/// Search from every IPv4 interface but loopback and listen for `timeout`
+pub async fn discover(timeout: Duration) -> io::Result<SsdpDiscovery> {
+    discover_via(&local_interfaces(), SSDP_ADDRESS, timeout).await
+}
+
+/// Send the searches to `target` from each of `interfaces`, collect the
+/// answers received within `timeout` and fetch the description each
+/// points to, once per description URL. Answers whose description is on
+/// another host than the one answering are ignored. A device whose
+/// description can't be fetched is kept only if it answered as a camera.
+pub async fn discover_via(interfaces: &[Ipv4Addr], target: SocketAddr, timeout: Duration) -> io::Result<SsdpDiscovery> {
+    let delay = timeout.as_secs().clamp(1, MAX_RESPONSE_DELAY_SECS);
+    let searches: Vec<String> = CAMERA_SEARCH_TARGETS.iter().map(|st| search_message(st, delay)).collect();
+    let (interfaces, replies) = multicast(interfaces, target, &searches, Instant::now() + timeout).await?;
+
+    let mut answers: Vec<SearchResponse> = Vec::new();
+    for (from, reply) in replies {
+        let Some(answer) = parse_search_response(&reply, from.ip()) else {
+            continue;
+        };
+        let location_host = url::Url::parse(&answer.location).ok().and_then(|url| url.host_str().map(|host| host.trim_matches(&['[', ']'][..]).to_string()));
+        if location_host.as_deref() != Some(answer.responder.to_string().as_str()) {
+            debug!("Ignoring SSDP answer from {} describing {}", answer.responder, answer.location);
+            continue;
+        }
+        if !answers.iter().any(|known| known.location == answer.location) {
+            answers.push(answer);
+        }
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(DESCRIPTION_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy()
+        .user_agent(concat!("mcp-webcam/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(io::Error::other)?;
+    let mut fetching = JoinSet::new();
+    for answer in answers {
+        let client = client.clone();
+        fetching.spawn(async move {
+            let description = fetch_description(&client, &answer.location).await;
+            (answer, description)
+        });
+    }
+
+    let mut devices = Vec::new();
+    while let Some(fetched) = fetching.join_next().await {
+        let Ok((answer, description)) = fetched else {
+            continue;
+        };
+        let device = match description {
+            Ok(xml) => parse_description(&xml, answer),
+            Err(e) => {
+                debug!("Could not fetch the SSDP device description {}: {}", answer.location, e);
+                if answer.search_target.as_deref() != Some(CAMERA_DEVICE_TYPE) {
+                    continue;
+                }
+                answer.into_device()
+            }
+        };
+        if device.is_camera() {
+            devices.push(device);
+        }
+    }
+    devices.sort_by(|a, b| (a.responder, &a.location).cmp(&(b.responder, &b.location)));
+    Ok(SsdpDiscovery { devices, interfaces })
+}
+
+/// An `M-SEARCH` for `search_target`, answered within `delay` seconds
+pub fn search_message(search_target: &str, delay: u64) -> String {
+    format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: {}\r\nST: {}\r\nUSER-AGENT: mcp-webcam/{} UPnP/1.1\r\n\r\n",
+        delay,
+        search_target,
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// The headers of an answer to a search
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResponse {
+    pub location: String,
+    pub usn: Option<String>,
+    pub search_target: Option<String>,
+    pub server: Option<String>,
+    pub responder: IpAddr,
+}
+
+impl SearchResponse {
+    /// A device known only from its answer
+    fn into_device(self) -> SsdpDevice {
+        SsdpDevice {
+            device_type: self.search_target.clone(),
+            location: self.location,
+            usn: self.usn,
+            search_target: self.search_target,
+            server: self.server,
+            friendly_name: None,
+            manufacturer: None,
+            model_name: None,
+            model_number: None,
+            presentation_url: None,
+            responder: self.responder,
+        }
+    }
+}
+
+/// The answer to a search in `reply`, from `responder`: a `200 OK` with a
+/// `LOCATION`. Other traffic on the port, such as `NOTIFY`s and other
+/// hosts' searches, gives `None`.
+pub fn parse_search_response(reply: &str, responder: IpAddr) -> Option<SearchResponse> {
+    let mut lines = reply.lines();
+    let status = lines.next()?;
+    if !status.starts_with("HTTP/1.") || status.split_whitespace().nth(1) != Some("200") {
+        return None;
+    }
+    let mut response = SearchResponse { location: String::new(), usn: None, search_target: None, server: None, responder };
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = Some(value.trim().to_string()).filter(|value| !value.is_empty());
+        match name.trim().to_ascii_uppercase().as_str() {
+            "LOCATION" => response.location = value.unwrap_or_default(),
+            "USN" => response.usn = value,
+            "ST" => response.search_target = value,
+            "SERVER" => response.server = value,
+            _ => {}
+        }
+    }
+    Some(response).filter(|response| !response.location.is_empty())
+}
+
+/// The device an answer's description `xml` describes. Of an embedded
+/// device list, the root device's details are the ones taken.
+pub fn parse_description(xml: &str, answer: SearchResponse) -> SsdpDevice {
+    let base = element_text(xml, "URLBase")
+        .and_then(|base| url::Url::parse(&base).ok())
+        .or_else(|| url::Url::parse(&answer.location).ok());
+    let presentation_url = element_text(xml, "presentationURL")
+        .and_then(|page| match &base {
+            Some(base) => base.join(&page).ok(),
+            None => url::Url::parse(&page).ok(),
+        })
+        .filter(|page| matches!(page.scheme(), "http" | "https"))
+        .map(|page| page.to_string());
+    let mut device = answer.into_device();
+    let answered_as = device.device_type.take();
+    SsdpDevice {
+        device_type: element_text(xml, "deviceType").or(answered_as),
+        friendly_name: element_text(xml, "friendlyName"),
+        manufacturer: element_text(xml, "manufacturer"),
+        model_name: element_text(xml, "modelName"),
+        model_number: element_text(xml, "modelNumber"),
+        presentation_url,
+        ..device
+    }
+}
+
+/// The description at `location`, up to `MAX_DESCRIPTION_BYTES`
+async fn fetch_description(client: &reqwest::Client, location: &str) -> Result<String, String> {
+    let mut response = client.get(location).send().await.map_err(|e| e.without_url().to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.without_url().to_string())? {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_DESCRIPTION_BYTES {
+            return Err(format!("description is larger than {} bytes", MAX_DESCRIPTION_BYTES));
+        }
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
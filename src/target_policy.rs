@@ -0,0 +1,355 @@
+//! Which remote webcam URLs the server may fetch. The model chooses the
+//! URLs, so without a policy `capture_remote_image` would fetch cloud
+//! metadata endpoints or hosts on the server's own network for it. Every
+//! request to a webcam, and every redirect it answers with, is checked:
+//! the scheme must be one webcams use and, unless private targets are
+//! allowed, no address the host resolves to may be in a private range.
+//!
+//! Operators can narrow this further with rules: a blocklist of networks
+//! and host names that are always refused, and an allowlist that, when it
+//! has any rules, is the only thing fetched from. Host names are checked
+//! both as written and by the addresses they resolve to.
+//!
+//! A name is checked again when the HTTP client connects: `PolicyResolver`
+//! resolves it for the client and hands over only addresses the policy
+//! allows. Otherwise a host could answer the check with a public address
+//! and the connection, moments later, with a private one. Through a proxy,
+//! the proxy is what the client connects to and looks up, so it isn't held
+//! to the policy; the webcam's host is resolved by the proxy, and only the
+//! check before each request applies to it. Programs that resolve names
+//! themselves, such as ffmpeg for RTSP, are given the checked address
+//! instead of the name.
+
+use crate::shodan::{sanitize_url, ShodanError};
+use futures::future::BoxFuture;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Schemes a webcam URL may have
+pub const ALLOWED_SCHEMES: [&str; 4] = ["http", "https", "rtsp", "rtsps"];
+/// Most redirects followed for one webcam request
+pub const MAX_REDIRECTS: usize = 3;
+/// `tool` of the audit log entries recording policy decisions
+pub const AUDIT_TOOL: &str = "target_policy";
+
+/// One allowlist or blocklist entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetRule {
+    /// Addresses in a CIDR range; a bare address is a range of one
+    Network { address: IpAddr, prefix: u8 },
+    /// A host name exactly, or with a leading `*.` every name below a
+    /// domain
+    Host(String),
+}
+
+impl TargetRule {
+    /// Whether `ip` is in this rule's range; host name rules match no
+    /// address
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let TargetRule::Network { address, prefix } = self else {
+            return false;
+        };
+        match (canonical(*address), canonical(ip)) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(*prefix)).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(*prefix)).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether host name `name` is matched by this rule
+    pub fn matches_name(&self, name: &str) -> bool {
+        let TargetRule::Host(rule) = self else {
+            return false;
+        };
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        match rule.strip_prefix("*.") {
+            Some(domain) => name.strip_suffix(domain).is_some_and(|rest| rest.ends_with('.') && rest.len() > 1),
+            None => name == *rule,
+        }
+    }
+}
+
+/// IPv4-mapped IPv6 addresses as the IPv4 address they stand for
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+impl FromStr for TargetRule {
+    type Err = String;
+
+    /// `203.0.113.0/24`, `2001:db8::/32`, `198.51.100.7`, `cam.example.com`
+    /// or `*.example.com`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if let Some((address, prefix)) = text.split_once('/') {
+            let address: IpAddr = address.parse().map_err(|_| format!("'{}' is not a valid CIDR range", text))?;
+            let max = if address.is_ipv4() { 32 } else { 128 };
+            let prefix = prefix.parse::<u8>().ok().filter(|prefix| *prefix <= max)
+                .ok_or_else(|| format!("'{}' has an invalid prefix length, at most {} for this address", text, max))?;
+            return Ok(TargetRule::Network { address, prefix });
+        }
+        if let Ok(address) = text.trim_matches(['[', ']']).parse::<IpAddr>() {
+            let prefix = if address.is_ipv4() { 32 } else { 128 };
+            return Ok(TargetRule::Network { address, prefix });
+        }
+        let name = text.trim_end_matches('.').to_ascii_lowercase();
+        let labels = name.strip_prefix("*.").unwrap_or(&name);
+        let valid = !labels.is_empty()
+            && labels.split('.').all(|label| {
+                !label.is_empty() && label.len() <= 63 && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            });
+        if !valid {
+            return Err(format!("'{}' is neither an address, a CIDR range nor a host name", text));
+        }
+        Ok(TargetRule::Host(name))
+    }
+}
+
+impl fmt::Display for TargetRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetRule::Network { address, prefix } => write!(f, "{}/{}", address, prefix),
+            TargetRule::Host(name) => f.write_str(name),
+        }
+    }
+}
+
+impl Serialize for TargetRule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Rules webcam URLs are checked against
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TargetPolicy {
+    /// Allow loopback, private, link-local and other non-public addresses,
+    /// e.g. for cameras on the local network
+    pub allow_private: bool,
+    /// When not empty, the only networks and hosts fetched from. An
+    /// allowed range lets its private addresses through as well.
+    pub allow: Vec<TargetRule>,
+    /// Networks and hosts never fetched from, whatever else allows them
+    pub block: Vec<TargetRule>,
+}
+
+impl TargetPolicy {
+    /// Policy allowing private addresses as well as public ones
+    pub fn allowing_private() -> Self {
+        Self { allow_private: true, ..Self::default() }
+    }
+
+    /// Whether the policy has an allowlist or a blocklist
+    pub fn has_rules(&self) -> bool {
+        !self.allow.is_empty() || !self.block.is_empty()
+    }
+
+    /// Check `url` before it is requested, resolving its host name. A
+    /// name that doesn't resolve is let through for the request to fail
+    /// on as it would have, unless only an allowlist could let it through.
+    pub async fn check(&self, url: &str) -> Result<(), ShodanError> {
+        self.check_with(url, &SystemLookup).await
+    }
+
+    /// `check`, resolving host names with `lookup`
+    pub async fn check_with(&self, url: &str, lookup: &dyn HostLookup) -> Result<(), ShodanError> {
+        let parsed = url::Url::parse(url).map_err(|e| blocked(url, format!("not a valid URL ({})", e)))?;
+        if !ALLOWED_SCHEMES.contains(&parsed.scheme()) {
+            return Err(blocked(url, format!("scheme '{}' is not allowed, only {}", parsed.scheme(), ALLOWED_SCHEMES.join(", "))));
+        }
+        if self.allow_private && !self.has_rules() {
+            return Ok(());
+        }
+        let (name, addresses): (Option<&str>, Vec<IpAddr>) = match parsed.host() {
+            Some(url::Host::Ipv4(ip)) => (None, vec![IpAddr::V4(ip)]),
+            Some(url::Host::Ipv6(ip)) => (None, vec![IpAddr::V6(ip)]),
+            Some(url::Host::Domain(host)) => {
+                self.check_name(url, host)?;
+                (Some(host), lookup.lookup(host).await.unwrap_or_default())
+            }
+            None => return Err(blocked(url, "the URL has no host".to_string())),
+        };
+        if addresses.is_empty() && !self.allow.is_empty() && !name.is_some_and(|name| self.name_allowed(name)) {
+            return Err(denied(url, format!("{} did not resolve, so it can't be matched against the allowlist", name.unwrap_or("the host"))));
+        }
+        addresses.into_iter().try_for_each(|ip| self.check_address(url, name, ip))
+    }
+
+    /// Check an address `target` connects to directly, such as the port
+    /// of an RTSP webcam. The host name of `target`, if it has one, is
+    /// checked too.
+    pub fn check_ip(&self, target: &str, ip: IpAddr) -> Result<(), ShodanError> {
+        let parsed = url::Url::parse(target).ok();
+        let name = parsed.as_ref().and_then(|url| url.host_str()).filter(|host| host.parse::<IpAddr>().is_err() && !host.starts_with('['));
+        if let Some(name) = name {
+            self.check_name(target, name)?;
+        }
+        self.check_address(target, name, ip)
+    }
+
+    /// Refuse a host name on the blocklist
+    fn check_name(&self, target: &str, name: &str) -> Result<(), ShodanError> {
+        match self.block.iter().find(|rule| rule.matches_name(name)) {
+            Some(rule) => Err(denied(target, format!("{} is on the blocklist ({})", name, rule))),
+            None => Ok(()),
+        }
+    }
+
+    fn name_allowed(&self, name: &str) -> bool {
+        self.allow.iter().any(|rule| rule.matches_name(name))
+    }
+
+    /// Check one address `target` reaches, of host name `name` if it has
+    /// one
+    fn check_address(&self, target: &str, name: Option<&str>, ip: IpAddr) -> Result<(), ShodanError> {
+        if let Some(rule) = self.block.iter().find(|rule| rule.contains(ip)) {
+            return Err(denied(target, format!("{} is on the blocklist ({})", ip, rule)));
+        }
+        let in_allowed_range = self.allow.iter().any(|rule| rule.contains(ip));
+        if !self.allow.is_empty() && !in_allowed_range && !name.is_some_and(|name| self.name_allowed(name)) {
+            return Err(denied(target, format!("{} is not on the allowlist", name.map_or_else(|| ip.to_string(), |name| format!("{} ({})", name, ip)))));
+        }
+        match restricted_range(ip).filter(|_| !self.allow_private && !in_allowed_range) {
+            Some(range) => {
+                warn!("Blocked a request to {}: {} is {}", sanitize_url(target), ip, range);
+                Err(blocked(target, format!("{} is a {} address; pass --allow-private-targets to allow private targets", ip, range)))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Resolves host names to addresses
+pub trait HostLookup: Send + Sync + fmt::Debug {
+    fn lookup(&self, host: &str) -> BoxFuture<'static, std::io::Result<Vec<IpAddr>>>;
+}
+
+/// Lookups through the operating system's resolver
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemLookup;
+
+impl HostLookup for SystemLookup {
+    fn lookup(&self, host: &str) -> BoxFuture<'static, std::io::Result<Vec<IpAddr>>> {
+        let host = host.to_string();
+        Box::pin(async move {
+            Ok(tokio::net::lookup_host((host.as_str(), 0)).await?.map(|address| address.ip()).collect())
+        })
+    }
+}
+
+/// DNS resolver of the webcam HTTP clients. Each connection goes to the
+/// addresses this lookup returned, and it returns none unless the policy
+/// allows every one the name resolved to, so what was checked is what is
+/// connected to.
+#[derive(Debug, Clone)]
+pub struct PolicyResolver {
+    policy: Arc<TargetPolicy>,
+    lookup: Arc<dyn HostLookup>,
+    /// Host names of the proxies requests go through, resolved without
+    /// the policy
+    proxy_hosts: Arc<[String]>,
+}
+
+impl PolicyResolver {
+    pub fn new(policy: Arc<TargetPolicy>, lookup: Arc<dyn HostLookup>) -> Self {
+        Self { policy, lookup, proxy_hosts: Arc::new([]) }
+    }
+
+    /// Let the client connect to the proxies named `hosts` wherever they
+    /// are, e.g. on the local network
+    pub fn with_proxy_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.proxy_hosts = hosts.into_iter().map(|host| host.to_ascii_lowercase()).collect();
+        self
+    }
+}
+
+impl reqwest::dns::Resolve for PolicyResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let (policy, lookup) = (Arc::clone(&self.policy), Arc::clone(&self.lookup));
+        let host = name.as_str().to_string();
+        let proxy = self.proxy_hosts.contains(&host.to_ascii_lowercase());
+        Box::pin(async move {
+            let addresses = lookup.lookup(&host).await?;
+            debug!("{} resolved to {:?} for connecting", host, addresses);
+            if !proxy && (!policy.allow_private || policy.has_rules()) {
+                policy.check_name(&host, &host)?;
+                for &ip in &addresses {
+                    policy.check_address(&host, Some(&host), ip)?;
+                }
+            }
+            // The HTTP client fills in the port
+            let addresses: reqwest::dns::Addrs = Box::new(addresses.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addresses)
+        })
+    }
+}
+
+/// Refusal by an allowlist or blocklist rule
+fn denied(url: &str, reason: String) -> ShodanError {
+    warn!("Blocked a request to {}: {}", sanitize_url(url), reason);
+    blocked(url, reason)
+}
+
+fn blocked(url: &str, reason: String) -> ShodanError {
+    ShodanError::TargetBlocked { target: sanitize_url(url), reason }
+}
+
+/// Name of the non-public range `ip` is in, if any
+pub fn restricted_range(ip: IpAddr) -> Option<&'static str> {
+    match ip {
+        IpAddr::V4(ip) => restricted_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => restricted_v4(mapped),
+            None => restricted_v6(ip),
+        },
+    }
+}
+
+fn restricted_v4(ip: Ipv4Addr) -> Option<&'static str> {
+    let [a, b, ..] = ip.octets();
+    if ip.is_loopback() {
+        Some("loopback")
+    } else if ip.is_private() {
+        Some("private")
+    } else if ip.is_link_local() {
+        Some("link-local")
+    } else if ip.is_unspecified() || a == 0 {
+        Some("unspecified")
+    } else if a == 100 && (64..128).contains(&b) {
+        Some("shared (carrier-grade NAT)")
+    } else if ip.is_broadcast() || ip.is_multicast() || a >= 240 {
+        Some("broadcast, multicast or reserved")
+    } else {
+        None
+    }
+}
+
+fn restricted_v6(ip: Ipv6Addr) -> Option<&'static str> {
+    let first = ip.segments()[0];
+    if ip.is_loopback() {
+        Some("loopback")
+    } else if ip.is_unspecified() {
+        Some("unspecified")
+    } else if first & 0xfe00 == 0xfc00 {
+        Some("private (unique local)")
+    } else if first & 0xffc0 == 0xfe80 {
+        Some("link-local")
+    } else if ip.is_multicast() {
+        Some("broadcast, multicast or reserved")
+    } else {
+        None
+    }
+}
@@ -0,0 +1,464 @@
+//! Machine-readable errors returned by MCP tools. A failed tool call is
+//! still a successful JSON-RPC response: the result has `isError: true`
+//! and, in `structuredContent`, an `error` object with a stable `code`, so
+//! clients can tell failures apart without matching on message text.
+//!
+//! Every error also says whether it is `retryable`: whether the same call
+//! can succeed later, e.g. once a rate limit or cooldown has passed, as
+//! against needing different arguments, credentials or consent first. The
+//! conversions below match every error variant and every code by name, so
+//! a new variant can't build until it is given a code and a retry policy.
+//! Failures of the server itself, such as a poisoned lock or a panicked
+//! handler, are JSON-RPC errors instead, not tool results.
+
+use crate::barcode::BarcodeError;
+use crate::shodan::ShodanError;
+use crate::webcam::WebcamError;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Stable error codes, serialized as e.g. `CAMERA_BUSY`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    InvalidParams,
+    CameraNotFound,
+    NoCamerasAvailable,
+    CameraBusy,
+    CameraDisconnected,
+    CameraNotOpen,
+    /// The capture backend failed to open or read the camera
+    CameraError,
+    LocalCamerasNotSupported,
+    CaptureTimeout,
+    PrivacyMaskInvalid,
+    ImageProcessingFailed,
+    DecodeFailed,
+    FaceDetectionFailed,
+    StorageError,
+    CaptureNotFound,
+    BarcodeNotSupported,
+    BarcodeFailed,
+    PreviewFailed,
+    ShodanRateLimit,
+    ShodanUnauthorized,
+    ShodanInvalidQuery,
+    /// Shodan has no information about the host looked up
+    ShodanHostNotFound,
+    /// The server's hourly or daily budget of Shodan requests is used up
+    ShodanBudgetExceeded,
+    ShodanError,
+    /// A remote webcam answered with something other than an image
+    NotAnImage,
+    /// A remote webcam wants a username and password
+    WebcamAuthRequired,
+    /// A remote webcam refused the username and password given
+    WebcamAuthRejected,
+    /// None of the snapshot URLs probed served an image
+    NoSnapshotUrl,
+    /// RTSP snapshots need the `rtsp` feature and ffmpeg
+    RtspNotSupported,
+    RtspConnectionFailed,
+    RtspDecodeFailed,
+    /// A remote webcam sent more than the download limit
+    ResponseTooLarge,
+    /// A remote webcam fetch ran past its wall-clock deadline
+    FetchTimeout,
+    /// The target policy refused a webcam URL or one it redirected to
+    TargetBlocked,
+    /// A remote webcam's TLS certificate failed verification
+    TlsError,
+    /// The host was fetched from less than the cooldown interval ago
+    HostCooldown,
+    /// An ONVIF camera answered with a SOAP fault
+    OnvifError,
+    /// Shodan rates the host a likely honeypot, and strict mode refuses it
+    HoneypotSuspected,
+    NetworkError,
+    /// The client cancelled the call with `notifications/cancelled`
+    Cancelled,
+    /// The call ran longer than the tool's timeout
+    ToolTimeout,
+    /// The tool's rate limit or the cap on concurrent calls was reached
+    RateLimited,
+    /// The tool turns a camera on and the user hasn't consented yet
+    ConsentRequired,
+}
+
+impl ErrorCode {
+    /// Whether the same call may succeed if made again later, unchanged;
+    /// `details.retry_after_seconds` says when, where it is known
+    pub fn is_retryable(self) -> bool {
+        match self {
+            Self::CameraBusy
+            | Self::CameraDisconnected
+            | Self::CaptureTimeout
+            | Self::ShodanRateLimit
+            | Self::ShodanBudgetExceeded
+            | Self::RtspConnectionFailed
+            | Self::FetchTimeout
+            | Self::HostCooldown
+            | Self::NetworkError
+            | Self::ToolTimeout
+            | Self::RateLimited => true,
+            Self::InvalidParams
+            | Self::CameraNotFound
+            | Self::NoCamerasAvailable
+            | Self::CameraNotOpen
+            | Self::CameraError
+            | Self::LocalCamerasNotSupported
+            | Self::PrivacyMaskInvalid
+            | Self::ImageProcessingFailed
+            | Self::DecodeFailed
+            | Self::FaceDetectionFailed
+            | Self::StorageError
+            | Self::CaptureNotFound
+            | Self::BarcodeNotSupported
+            | Self::BarcodeFailed
+            | Self::PreviewFailed
+            | Self::ShodanUnauthorized
+            | Self::ShodanInvalidQuery
+            | Self::ShodanHostNotFound
+            | Self::ShodanError
+            | Self::NotAnImage
+            | Self::WebcamAuthRequired
+            | Self::WebcamAuthRejected
+            | Self::NoSnapshotUrl
+            | Self::RtspNotSupported
+            | Self::RtspDecodeFailed
+            | Self::ResponseTooLarge
+            | Self::TargetBlocked
+            | Self::TlsError
+            | Self::OnvifError
+            | Self::HoneypotSuspected
+            | Self::Cancelled
+            | Self::ConsentRequired => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Whether retrying the same call can succeed, from `code`
+    pub retryable: bool,
+    /// Structured context, e.g. the camera index or the process holding a busy camera
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl ToolError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), retryable: code.is_retryable(), details: None }
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidParams, message)
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Tool result reporting this error, with `text` as the content shown to the model
+    pub fn into_response(self, text: impl Into<String>) -> Value {
+        json!({
+            "content": [{
+                "type": "text",
+                "text": text.into()
+            }],
+            "isError": true,
+            "structuredContent": {
+                "error": self
+            }
+        })
+    }
+}
+
+impl From<&WebcamError> for ToolError {
+    fn from(e: &WebcamError) -> Self {
+        let code = match e {
+            WebcamError::CameraNotFound { .. } => ErrorCode::CameraNotFound,
+            #[cfg(feature = "local_cameras")]
+            WebcamError::CameraOpen(_) => ErrorCode::CameraError,
+            #[cfg(feature = "opencv_backend")]
+            WebcamError::OpenCv(_) => ErrorCode::CameraError,
+            WebcamError::GStreamer(_) | WebcamError::GStreamerPipeline { .. } | WebcamError::Libcamera(_) => {
+                ErrorCode::CameraError
+            }
+            WebcamError::ImageProcessing(_) => ErrorCode::ImageProcessingFailed,
+            WebcamError::NoCamerasAvailable => ErrorCode::NoCamerasAvailable,
+            WebcamError::LocalCamerasNotSupported => ErrorCode::LocalCamerasNotSupported,
+            WebcamError::Storage(_) => ErrorCode::StorageError,
+            WebcamError::InvalidOptions(_) => ErrorCode::InvalidParams,
+            WebcamError::Decode(_) | WebcamError::UndecodableImage { .. } => ErrorCode::DecodeFailed,
+            WebcamError::PrivacyMaskConfig(_) => ErrorCode::PrivacyMaskInvalid,
+            WebcamError::FaceDetection(_) => ErrorCode::FaceDetectionFailed,
+            WebcamError::CameraNotOpen => ErrorCode::CameraNotOpen,
+            WebcamError::CameraDisconnected { .. } => ErrorCode::CameraDisconnected,
+            WebcamError::Timeout { .. } => ErrorCode::CaptureTimeout,
+            WebcamError::DeviceBusy { .. } => ErrorCode::CameraBusy,
+            WebcamError::Cancelled => ErrorCode::Cancelled,
+        };
+        let error = Self::new(code, e.to_string());
+
+        match e {
+            WebcamError::CameraNotFound { index }
+            | WebcamError::CameraDisconnected { index }
+            | WebcamError::GStreamerPipeline { index, .. } => error.with_details(json!({ "camera_index": index })),
+            WebcamError::DeviceBusy { index, holder } => {
+                error.with_details(json!({ "camera_index": index, "holder": holder }))
+            }
+            WebcamError::Timeout { seconds } => error.with_details(json!({ "timeout_seconds": seconds })),
+            WebcamError::UndecodableImage { content_type, declared_type, reason } => error.with_details(json!({
+                "content_type": content_type,
+                "declared_type": declared_type,
+                "reason": reason
+            })),
+            #[cfg(feature = "local_cameras")]
+            WebcamError::CameraOpen(_) => error,
+            #[cfg(feature = "opencv_backend")]
+            WebcamError::OpenCv(_) => error,
+            WebcamError::ImageProcessing(_)
+            | WebcamError::NoCamerasAvailable
+            | WebcamError::LocalCamerasNotSupported
+            | WebcamError::Storage(_)
+            | WebcamError::InvalidOptions(_)
+            | WebcamError::Decode(_)
+            | WebcamError::PrivacyMaskConfig(_)
+            | WebcamError::FaceDetection(_)
+            | WebcamError::CameraNotOpen
+            | WebcamError::Cancelled
+            | WebcamError::GStreamer(_)
+            | WebcamError::Libcamera(_) => error,
+        }
+    }
+}
+
+impl From<&ShodanError> for ToolError {
+    fn from(e: &ShodanError) -> Self {
+        let code = match e {
+            ShodanError::HttpError(_) => ErrorCode::NetworkError,
+            ShodanError::NoApiKey | ShodanError::Unauthorized => ErrorCode::ShodanUnauthorized,
+            ShodanError::InvalidQuery(_) => ErrorCode::ShodanInvalidQuery,
+            ShodanError::NotFound(_) => ErrorCode::ShodanHostNotFound,
+            ShodanError::RateLimitExceeded { .. } => ErrorCode::ShodanRateLimit,
+            ShodanError::Generic(_) | ShodanError::InvalidSettings(_) => ErrorCode::ShodanError,
+            ShodanError::NotAnImage { .. } => ErrorCode::NotAnImage,
+            ShodanError::AuthRequired { .. } => ErrorCode::WebcamAuthRequired,
+            ShodanError::AuthRejected { .. } => ErrorCode::WebcamAuthRejected,
+            ShodanError::NoSnapshotUrl { .. } => ErrorCode::NoSnapshotUrl,
+            ShodanError::RtspNotSupported(_) => ErrorCode::RtspNotSupported,
+            ShodanError::RtspConnection(_) => ErrorCode::RtspConnectionFailed,
+            ShodanError::RtspDecode(_) => ErrorCode::RtspDecodeFailed,
+            ShodanError::ResponseTooLarge { .. } => ErrorCode::ResponseTooLarge,
+            ShodanError::FetchDeadline { .. } => ErrorCode::FetchTimeout,
+            ShodanError::TargetBlocked { .. } => ErrorCode::TargetBlocked,
+            ShodanError::TlsError { .. } => ErrorCode::TlsError,
+            ShodanError::HostCooldown { .. } => ErrorCode::HostCooldown,
+            ShodanError::OnvifFault { .. } => ErrorCode::OnvifError,
+            ShodanError::BudgetExceeded(_) => ErrorCode::ShodanBudgetExceeded,
+        };
+        let error = Self::new(code, e.to_string());
+        match e {
+            ShodanError::RateLimitExceeded { retry_after: Some(wait) } => {
+                error.with_details(json!({ "retry_after_seconds": wait.as_secs().max(1) }))
+            }
+            ShodanError::AuthRequired { scheme, realm } | ShodanError::AuthRejected { scheme, realm } => {
+                error.with_details(json!({ "scheme": scheme, "realm": realm }))
+            }
+            ShodanError::NoSnapshotUrl { tried } => error.with_details(json!({ "tried": tried })),
+            ShodanError::ResponseTooLarge { limit, read } => {
+                error.with_details(json!({ "limit_bytes": limit, "bytes_read": read }))
+            }
+            ShodanError::FetchDeadline { deadline } => {
+                error.with_details(json!({ "timeout_seconds": deadline.as_secs_f64() }))
+            }
+            ShodanError::TargetBlocked { target, reason } => {
+                error.with_details(json!({ "target": target, "reason": reason }))
+            }
+            ShodanError::TlsError { reason, certificate } => error.with_details(json!({
+                "reason": reason,
+                "subject": certificate.as_ref().map(|names| &names.subject),
+                "issuer": certificate.as_ref().map(|names| &names.issuer),
+                "self_signed": certificate.as_ref().map(|names| names.self_signed())
+            })),
+            ShodanError::HostCooldown { host, retry_after } => error.with_details(json!({
+                "host": host,
+                "retry_after_seconds": retry_after.as_secs_f64().ceil() as u64
+            })),
+            ShodanError::OnvifFault { action, reason } => error.with_details(json!({ "action": action, "reason": reason })),
+            ShodanError::BudgetExceeded(exceeded) => error.with_details(json!({
+                "window": exceeded.window,
+                "limit": exceeded.limit,
+                "resets_at": exceeded.resets_at,
+                "retry_after_seconds": exceeded.retry_after(chrono::Utc::now()).as_secs_f64().ceil() as u64
+            })),
+            ShodanError::NotAnImage { content_type, preview } => {
+                error.with_details(json!({ "content_type": content_type, "body_preview": preview }))
+            }
+            ShodanError::RateLimitExceeded { retry_after: None }
+            | ShodanError::HttpError(_)
+            | ShodanError::NoApiKey
+            | ShodanError::Unauthorized
+            | ShodanError::InvalidQuery(_)
+            | ShodanError::NotFound(_)
+            | ShodanError::Generic(_)
+            | ShodanError::InvalidSettings(_)
+            | ShodanError::RtspNotSupported(_)
+            | ShodanError::RtspConnection(_)
+            | ShodanError::RtspDecode(_) => error,
+        }
+    }
+}
+
+impl From<&BarcodeError> for ToolError {
+    fn from(e: &BarcodeError) -> Self {
+        let code = match e {
+            BarcodeError::NotSupported => ErrorCode::BarcodeNotSupported,
+            BarcodeError::Decode(_) => ErrorCode::BarcodeFailed,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certificate::CertificateNames;
+    use crate::credit_budget::{BudgetExceeded, BudgetWindow};
+    use std::time::Duration;
+
+    const README: &str = include_str!("../README.md");
+
+    /// Check an error's serialized code, retry flag and tool result, and that
+    /// the code is listed in the README
+    fn check(error: ToolError, code: &str, retryable: bool) {
+        let response = error.clone().into_response("failed");
+        assert_eq!(response["isError"], true, "{}", code);
+        assert_eq!(response["content"][0], json!({ "type": "text", "text": "failed" }));
+        let json = &response["structuredContent"]["error"];
+        assert_eq!(json["code"], code);
+        assert_eq!(json["retryable"], retryable, "{}", code);
+        assert_eq!(json["message"], error.message);
+        assert!(README.contains(&format!("`{}`", code)), "{} is not documented", code);
+    }
+
+    fn io_error() -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied")
+    }
+
+    #[test]
+    fn webcam_errors_map_to_their_codes() {
+        // Backend errors that can't be built without a device are left out
+        let cases = [
+            (WebcamError::CameraNotFound { index: 3 }, "CAMERA_NOT_FOUND", false),
+            (WebcamError::GStreamer("no element".into()), "CAMERA_ERROR", false),
+            (WebcamError::GStreamerPipeline { index: 7, message: "eos".into() }, "CAMERA_ERROR", false),
+            (WebcamError::Libcamera("timeout".into()), "CAMERA_ERROR", false),
+            (
+                WebcamError::ImageProcessing(image::ImageError::Limits(image::error::LimitError::from_kind(
+                    image::error::LimitErrorKind::DimensionError,
+                ))),
+                "IMAGE_PROCESSING_FAILED",
+                false,
+            ),
+            (WebcamError::NoCamerasAvailable, "NO_CAMERAS_AVAILABLE", false),
+            (WebcamError::LocalCamerasNotSupported, "LOCAL_CAMERAS_NOT_SUPPORTED", false),
+            (WebcamError::Storage(io_error()), "STORAGE_ERROR", false),
+            (WebcamError::InvalidOptions("rotation".into()), "INVALID_PARAMS", false),
+            (WebcamError::Decode("truncated".into()), "DECODE_FAILED", false),
+            (
+                WebcamError::UndecodableImage {
+                    content_type: "text/html".into(),
+                    declared_type: "image/jpeg".into(),
+                    reason: "not an image".into(),
+                },
+                "DECODE_FAILED",
+                false,
+            ),
+            (WebcamError::PrivacyMaskConfig("bad json".into()), "PRIVACY_MASK_INVALID", false),
+            (WebcamError::FaceDetection("no model".into()), "FACE_DETECTION_FAILED", false),
+            (WebcamError::CameraNotOpen, "CAMERA_NOT_OPEN", false),
+            (WebcamError::CameraDisconnected { index: 1 }, "CAMERA_DISCONNECTED", true),
+            (WebcamError::Timeout { seconds: 10 }, "CAPTURE_TIMEOUT", true),
+            (WebcamError::Cancelled, "CANCELLED", false),
+            (WebcamError::DeviceBusy { index: 0, holder: None }, "CAMERA_BUSY", true),
+        ];
+        for (error, code, retryable) in &cases {
+            let tool_error = ToolError::from(error);
+            assert_eq!(tool_error.message, error.to_string());
+            check(tool_error, code, *retryable);
+        }
+
+        let pipeline = ToolError::from(&WebcamError::GStreamerPipeline { index: 7, message: "eos".into() });
+        assert_eq!(pipeline.details, Some(json!({ "camera_index": 7 })));
+        let timeout = ToolError::from(&WebcamError::Timeout { seconds: 10 });
+        assert_eq!(timeout.details, Some(json!({ "timeout_seconds": 10 })));
+        assert!(ToolError::from(&WebcamError::Cancelled).details.is_none());
+    }
+
+    #[test]
+    fn shodan_errors_map_to_their_codes() {
+        let http = reqwest::Client::new().get("not a url").build().unwrap_err();
+        let budget = BudgetExceeded {
+            window: BudgetWindow::Hour,
+            limit: 100,
+            resets_at: chrono::Utc::now() + chrono::Duration::minutes(30),
+        };
+        let certificate = CertificateNames { subject: "CN=cam".into(), issuer: "CN=cam".into() };
+        let cases = [
+            (ShodanError::HttpError(http), "NETWORK_ERROR", true),
+            (ShodanError::NoApiKey, "SHODAN_UNAUTHORIZED", false),
+            (ShodanError::Unauthorized, "SHODAN_UNAUTHORIZED", false),
+            (ShodanError::InvalidQuery("port:".into()), "SHODAN_INVALID_QUERY", false),
+            (ShodanError::NotFound("192.0.2.1".into()), "SHODAN_HOST_NOT_FOUND", false),
+            (ShodanError::RateLimitExceeded { retry_after: None }, "SHODAN_RATE_LIMIT", true),
+            (ShodanError::Generic("bad JSON".into()), "SHODAN_ERROR", false),
+            (ShodanError::InvalidSettings("proxy".into()), "SHODAN_ERROR", false),
+            (ShodanError::NotAnImage { content_type: None, preview: "<html>".into() }, "NOT_AN_IMAGE", false),
+            (ShodanError::AuthRequired { scheme: "Basic".into(), realm: None }, "WEBCAM_AUTH_REQUIRED", false),
+            (ShodanError::AuthRejected { scheme: "Digest".into(), realm: None }, "WEBCAM_AUTH_REJECTED", false),
+            (ShodanError::NoSnapshotUrl { tried: 4 }, "NO_SNAPSHOT_URL", false),
+            (ShodanError::RtspNotSupported("no ffmpeg".into()), "RTSP_NOT_SUPPORTED", false),
+            (ShodanError::RtspConnection("refused".into()), "RTSP_CONNECTION_FAILED", true),
+            (ShodanError::RtspDecode("no frame".into()), "RTSP_DECODE_FAILED", false),
+            (ShodanError::ResponseTooLarge { limit: 10, read: 11 }, "RESPONSE_TOO_LARGE", false),
+            (ShodanError::FetchDeadline { deadline: Duration::from_secs(5) }, "FETCH_TIMEOUT", true),
+            (ShodanError::TargetBlocked { target: "10.0.0.1".into(), reason: "private".into() }, "TARGET_BLOCKED", false),
+            (ShodanError::TlsError { reason: "expired".into(), certificate: Some(certificate) }, "TLS_ERROR", false),
+            (ShodanError::HostCooldown { host: "192.0.2.1".into(), retry_after: Duration::from_millis(1500) }, "HOST_COOLDOWN", true),
+            (ShodanError::OnvifFault { action: "GetSnapshotUri".into(), reason: "fault".into() }, "ONVIF_ERROR", false),
+            (ShodanError::BudgetExceeded(budget), "SHODAN_BUDGET_EXCEEDED", true),
+        ];
+        for (error, code, retryable) in &cases {
+            let tool_error = ToolError::from(error);
+            assert_eq!(tool_error.message, error.to_string());
+            check(tool_error, code, *retryable);
+        }
+
+        let rate_limited = ToolError::from(&ShodanError::RateLimitExceeded { retry_after: Some(Duration::from_millis(200)) });
+        assert_eq!(rate_limited.details, Some(json!({ "retry_after_seconds": 1 })));
+        let cooldown = ToolError::from(&ShodanError::HostCooldown { host: "192.0.2.1".into(), retry_after: Duration::from_millis(1500) });
+        assert_eq!(cooldown.details, Some(json!({ "host": "192.0.2.1", "retry_after_seconds": 2 })));
+    }
+
+    #[test]
+    fn other_errors_map_to_their_codes() {
+        check(ToolError::from(&BarcodeError::NotSupported), "BARCODE_NOT_SUPPORTED", false);
+        check(ToolError::from(&BarcodeError::Decode("crc".into())), "BARCODE_FAILED", false);
+        check(ToolError::invalid_params("missing url"), "INVALID_PARAMS", false);
+        for (code, name, retryable) in [
+            (ErrorCode::CaptureNotFound, "CAPTURE_NOT_FOUND", false),
+            (ErrorCode::PreviewFailed, "PREVIEW_FAILED", false),
+            (ErrorCode::HoneypotSuspected, "HONEYPOT_SUSPECTED", false),
+            (ErrorCode::ToolTimeout, "TOOL_TIMEOUT", true),
+            (ErrorCode::RateLimited, "RATE_LIMITED", true),
+            (ErrorCode::ConsentRequired, "CONSENT_REQUIRED", false),
+        ] {
+            check(ToolError::new(code, "failed"), name, retryable);
+        }
+    }
+}
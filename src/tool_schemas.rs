@@ -0,0 +1,1121 @@
+//! Output schemas for the tools' `structuredContent`, advertised in
+//! `tools/list` as `outputSchema` and checked against handler results in
+//! debug builds and tests by `validate`.
+//!
+//! Only the subset of JSON Schema used here is supported: `type` (a name
+//! or a list of names), `properties`, `required`, `additionalProperties`
+//! (as a schema), `items` and `enum`. Objects may have properties beyond
+//! the declared ones.
+
+use crate::classify::Vendor;
+use serde_json::{json, Value};
+
+/// Output schema of every tool that returns structured content
+pub fn output_schemas() -> Vec<(&'static str, Value)> {
+    // A Shodan facet value and how many results have it
+    let facet = object(json!({ "value": string(), "count": integer() }), &["value", "count"]);
+    vec![
+        ("list_cameras", object(json!({ "cameras": array(camera_info()) }), &["cameras"])),
+        ("capture_image", object(json!({ "metadata": capture_metadata() }), &["metadata"])),
+        ("scan_codes", object(
+            json!({
+                "codes": array(object(
+                    json!({
+                        "payload": string(),
+                        "symbology": string(),
+                        "bounding_box": rect()
+                    }),
+                    &["payload", "symbology", "bounding_box"],
+                )),
+                "found": boolean(),
+                "metadata": object(
+                    json!({
+                        "camera_index": integer(),
+                        "width": integer(),
+                        "height": integer(),
+                        "timestamp": string()
+                    }),
+                    &["camera_index", "width", "height", "timestamp"],
+                )
+            }),
+            &["codes", "found", "metadata"],
+        )),
+        ("capture_clip", object(
+            json!({
+                "metadata": object(
+                    json!({
+                        "camera_index": integer(),
+                        "frames": integer(),
+                        "interval_ms": integer(),
+                        "width": integer(),
+                        "height": integer(),
+                        "format": { "type": "string", "enum": ["gif", "mjpeg"] },
+                        "mime_type": string(),
+                        "size_bytes": integer(),
+                        "timestamp": string()
+                    }),
+                    &["camera_index", "frames", "width", "height", "format", "mime_type", "size_bytes"],
+                ),
+                "path": string()
+            }),
+            &["metadata"],
+        )),
+        ("set_camera_defaults", object(
+            json!({ "camera_index": integer(), "profile": effective_profile() }),
+            &["camera_index", "profile"],
+        )),
+        ("set_default_camera", object(
+            json!({ "default_camera": integer(), "previous_default_camera": integer() }),
+            &["default_camera", "previous_default_camera"],
+        )),
+        ("self_test", object(
+            json!({
+                "results": array(object(
+                    json!({
+                        "index": integer(),
+                        "name": string(),
+                        "passed": boolean(),
+                        "open_ms": nullable("integer"),
+                        "frame_ms": nullable("integer"),
+                        "width": nullable("integer"),
+                        "height": nullable("integer"),
+                        "mean_luminance": nullable("number"),
+                        "error": nullable("string")
+                    }),
+                    &["index", "name", "passed"],
+                )),
+                "passed": integer(),
+                "total": integer()
+            }),
+            &["results", "passed", "total"],
+        )),
+        ("benchmark_camera", object(
+            json!({
+                "benchmark": object(
+                    json!({
+                        "camera_index": integer(),
+                        "duration_ms": integer(),
+                        "frames": integer(),
+                        "fps": number(),
+                        "min_interval_ms": nullable("number"),
+                        "avg_interval_ms": nullable("number"),
+                        "max_interval_ms": nullable("number"),
+                        "dropped_frames": integer(),
+                        "width": integer(),
+                        "height": integer(),
+                        "pixel_format": string(),
+                        "nominal_fps": integer()
+                    }),
+                    &["camera_index", "duration_ms", "frames", "fps", "dropped_frames"],
+                )
+            }),
+            &["benchmark"],
+        )),
+        ("get_camera_info", object(
+            json!({
+                "camera_info": object(
+                    json!({
+                        "available_cameras": array(camera_info()),
+                        "current_camera": {
+                            "type": ["object", "null"],
+                            "properties": {
+                                "index": integer(),
+                                "name": string(),
+                                "resolution": { "type": ["array", "null"], "items": integer() },
+                                "frame_format": nullable("string"),
+                                "opened_at": string(),
+                                "frames_captured": integer()
+                            },
+                            "required": ["index", "name", "opened_at", "frames_captured"]
+                        },
+                        "default_camera": integer(),
+                        "total_cameras": integer(),
+                        "profiles": { "type": "object", "additionalProperties": effective_profile() }
+                    }),
+                    &["available_cameras", "current_camera", "default_camera", "total_cameras", "profiles"],
+                )
+            }),
+            &["camera_info"],
+        )),
+        ("list_captures", object(
+            json!({
+                "captures": array(object(
+                    json!({
+                        "capture_id": string(),
+                        "timestamp": string(),
+                        "camera_index": integer(),
+                        "width": integer(),
+                        "height": integer(),
+                        "mime_type": string(),
+                        "size_bytes": integer()
+                    }),
+                    &["capture_id", "timestamp", "camera_index", "width", "height"],
+                )),
+                "total": integer()
+            }),
+            &["captures", "total"],
+        )),
+        ("get_capture", object(json!({ "metadata": capture_metadata() }), &["metadata"])),
+        ("compare_captures", object(
+            json!({
+                "comparison": object(
+                    json!({
+                        "capture_id": string(),
+                        "other_capture_id": string(),
+                        "threshold": integer(),
+                        "rescaled": boolean(),
+                        "original_dimensions": { "type": "object", "additionalProperties": array(integer()) },
+                        "report": object(
+                            json!({
+                                "changed_fraction": number(),
+                                "mean_difference": number(),
+                                "changed_pixels": integer(),
+                                "changed_region": { "type": ["object", "null"] },
+                                "width": integer(),
+                                "height": integer()
+                            }),
+                            &["changed_fraction", "mean_difference", "changed_pixels"],
+                        )
+                    }),
+                    &["capture_id", "other_capture_id", "report"],
+                )
+            }),
+            &["comparison"],
+        )),
+        ("get_audit_log", object(
+            json!({
+                "audit": object(
+                    json!({
+                        "enabled": boolean(),
+                        "path": nullable("string"),
+                        "entries": array(object(
+                            json!({
+                                "timestamp": string(),
+                                "tool": string(),
+                                "arguments": { "type": "object" },
+                                "camera_index": nullable("integer"),
+                                "url": nullable("string"),
+                                "result_bytes": integer(),
+                                "success": boolean(),
+                                "error_code": nullable("string"),
+                                "duration_ms": integer(),
+                                "prev_hash": string()
+                            }),
+                            &["timestamp", "tool", "arguments", "result_bytes", "success", "duration_ms", "prev_hash"],
+                        )),
+                        "matched": integer(),
+                        "chain_intact": boolean()
+                    }),
+                    &["enabled", "entries", "matched", "chain_intact"],
+                )
+            }),
+            &["audit"],
+        )),
+        ("get_storage_info", object(
+            json!({
+                "storage": object(
+                    json!({
+                        "directory": string(),
+                        "usage": object(
+                            json!({
+                                "file_count": integer(),
+                                "total_bytes": integer(),
+                                "oldest_file": nullable("string"),
+                                "newest_file": nullable("string")
+                            }),
+                            &["file_count", "total_bytes"],
+                        ),
+                        "retention": object(
+                            json!({
+                                "max_files": integer(),
+                                "max_age_secs": integer(),
+                                "max_total_bytes": integer(),
+                                "cleanup_interval_secs": integer()
+                            }),
+                            &[],
+                        )
+                    }),
+                    &["directory", "usage", "retention"],
+                )
+            }),
+            &["storage"],
+        )),
+        ("get_server_stats", object(
+            json!({
+                "stats": object(
+                    json!({
+                        "uptime_secs": integer(),
+                        "captures_total": integer(),
+                        "captures_per_camera": { "type": "object", "additionalProperties": integer() },
+                        "failures_total": integer(),
+                        "failures_by_type": { "type": "object", "additionalProperties": integer() },
+                        "bytes_encoded": integer(),
+                        "average_capture_ms": nullable("number"),
+                        "shodan_queries": integer(),
+                        "shodan_requests": {
+                            "type": ["object", "null"],
+                            "properties": {
+                                "requests_per_second": { "type": "number" },
+                                "queue_depth": integer(),
+                                "max_queue_depth": integer(),
+                                "requests": integer(),
+                                "waited_requests": integer(),
+                                "total_wait_ms": integer(),
+                                "max_wait_ms": integer()
+                            },
+                            "required": ["requests_per_second", "queue_depth", "requests", "total_wait_ms"]
+                        },
+                        "host_cooldown": {
+                            "type": ["object", "null"],
+                            "properties": {
+                                "interval_ms": integer(),
+                                "capacity": integer(),
+                                "tracked": integer(),
+                                "refused": integer(),
+                                "evicted": integer(),
+                                "cooling": array(object(json!({ "host": string(), "remaining_ms": integer() }), &["host", "remaining_ms"]))
+                            },
+                            "required": ["interval_ms", "tracked", "refused", "cooling"]
+                        },
+                        "shodan_budget": or_null(budget_status())
+                    }),
+                    &["uptime_secs", "captures_total", "failures_total", "bytes_encoded", "shodan_queries"],
+                )
+            }),
+            &["stats"],
+        )),
+        ("health_check", object(
+            json!({
+                "health": object(
+                    json!({
+                        "status": { "type": "string", "enum": ["ok", "degraded"] },
+                        "uptime_secs": integer(),
+                        "cameras_enumerable": nullable("boolean"),
+                        "camera_count": nullable("integer"),
+                        "camera_error": nullable("string"),
+                        "shodan": object(
+                            json!({
+                                "enabled": boolean(),
+                                "key_valid": nullable("boolean"),
+                                "checked_secs_ago": nullable("integer")
+                            }),
+                            &["enabled"],
+                        ),
+                        "failures_total": integer(),
+                        "failures_by_type": { "type": "object", "additionalProperties": integer() }
+                    }),
+                    &["status", "uptime_secs", "shodan", "failures_total"],
+                )
+            }),
+            &["health"],
+        )),
+        ("get_server_info", object(
+            json!({
+                "server_info": object(
+                    json!({
+                        "name": string(),
+                        "version": string(),
+                        "git_commit": string(),
+                        "features": array(string()),
+                        "capture_backend": string(),
+                        "shodan_enabled": boolean(),
+                        "remote_disabled": boolean(),
+                        "tool_timeouts_ms": { "type": "object", "additionalProperties": integer() },
+                        "rate_limits": {
+                            "type": "object",
+                            "additionalProperties": object(json!({ "calls": integer(), "per_seconds": integer() }), &["calls", "per_seconds"])
+                        },
+                        "max_concurrent_calls": nullable("integer"),
+                        "consent": object(
+                            json!({
+                                "duration_seconds": integer(),
+                                "local": consent_scope(),
+                                "remote": consent_scope()
+                            }),
+                            &["duration_seconds", "local", "remote"],
+                        )
+                    }),
+                    &["name", "version", "git_commit", "features", "capture_backend", "shodan_enabled", "remote_disabled", "tool_timeouts_ms", "rate_limits", "max_concurrent_calls", "consent"],
+                )
+            }),
+            &["server_info"],
+        )),
+        ("grant_consent", object(
+            json!({
+                "consent": object(
+                    json!({
+                        "scope": { "type": "string", "enum": ["local", "remote"] },
+                        "expires_at": string(),
+                        "duration_seconds": integer()
+                    }),
+                    &["scope", "expires_at", "duration_seconds"],
+                )
+            }),
+            &["consent"],
+        )),
+        ("start_preview", object(
+            json!({
+                "preview": object(
+                    json!({
+                        "url": string(),
+                        "stream_url": string(),
+                        "address": string(),
+                        "camera_index": nullable("integer"),
+                        "fps": integer(),
+                        "max_viewers": integer(),
+                        "viewers": integer(),
+                        "started_at": string()
+                    }),
+                    &["url", "stream_url", "address"],
+                )
+            }),
+            &["preview"],
+        )),
+        ("configure_shodan", object(
+            json!({
+                "shodan_enabled": boolean(),
+                "tools": array(string())
+            }),
+            &["shodan_enabled", "tools"],
+        )),
+        ("reload_credentials", object(
+            json!({
+                "shodan_enabled": boolean(),
+                "tools": array(string())
+            }),
+            &["shodan_enabled", "tools"],
+        )),
+        ("list_search_queries", object(
+            json!({
+                "queries": array(object(json!({ "index": integer(), "query": string() }), &["index", "query"])),
+                "default_max_queries": integer()
+            }),
+            &["queries", "default_max_queries"],
+        )),
+        ("get_target_policy", object(
+            json!({
+                "target_policy": object(
+                    json!({
+                        "allowlist": array(string()),
+                        "blocklist": array(string()),
+                        "allow_private": boolean(),
+                        "schemes": array(string()),
+                        "max_redirects": integer()
+                    }),
+                    &["allowlist", "blocklist", "allow_private", "schemes", "max_redirects"],
+                )
+            }),
+            &["target_policy"],
+        )),
+        ("shodan_account_info", object(
+            json!({
+                "account": provider_credits(),
+                "accounts": array(provider_credits()),
+                "failed_providers": array(provider_failure()),
+                "budget": budget_status()
+            }),
+            &["account", "accounts", "budget"],
+        )),
+        ("check_honeypot", object(
+            json!({
+                "ip": string(),
+                "honeyscore": number(),
+                "threshold": number(),
+                "likely_honeypot": boolean(),
+                "warning": nullable("string"),
+                "cached": boolean(),
+                "strict": boolean()
+            }),
+            &["ip", "honeyscore", "threshold", "likely_honeypot", "cached", "strict"],
+        )),
+        ("request_shodan_scan", object(
+            json!({
+                "scan": object(
+                    json!({ "id": string(), "count": integer(), "credits_left": integer() }),
+                    &["id", "count", "credits_left"],
+                ),
+                "ips": array(string())
+            }),
+            &["scan", "ips"],
+        )),
+        ("get_scan_status", object(
+            json!({
+                "scan": object(
+                    json!({
+                        "id": string(),
+                        "count": integer(),
+                        "status": { "type": "string", "enum": ["submitting", "queued", "processing", "done", "unknown"] },
+                        "shodan_status": string(),
+                        "created": nullable("string")
+                    }),
+                    &["id", "count", "status", "shodan_status"],
+                ),
+                "done": boolean()
+            }),
+            &["scan", "done"],
+        )),
+        ("count_webcams", object(
+            json!({
+                "counts": array(object(
+                    json!({
+                        "query": string(),
+                        "total": integer(),
+                        "top_countries": array(facet.clone()),
+                        "top_orgs": array(facet)
+                    }),
+                    &["query", "total", "top_countries", "top_orgs"],
+                )),
+                "metadata": object(
+                    json!({
+                        "filters": object(
+                            json!({
+                                "country": nullable("string"),
+                                "city": nullable("string"),
+                                "org": nullable("string")
+                            }),
+                            &["country", "city", "org"],
+                        )
+                    }),
+                    &["filters"],
+                )
+            }),
+            &["counts", "metadata"],
+        )),
+        ("shodan_host_info", object(
+            json!({
+                "host": object(
+                    json!({
+                        "ip": string(),
+                        "ports": array(integer()),
+                        "hostnames": array(string()),
+                        "org": nullable("string"),
+                        "isp": nullable("string"),
+                        "os": nullable("string"),
+                        "location": { "type": "object" },
+                        "tags": array(string()),
+                        "vulns": array(string()),
+                        "last_update": nullable("string"),
+                        "services": array(object(
+                            json!({
+                                "port": integer(),
+                                "transport": nullable("string"),
+                                "product": nullable("string"),
+                                "timestamp": string()
+                            }),
+                            &["port", "timestamp"],
+                        ))
+                    }),
+                    &["ip", "ports", "hostnames", "location", "tags", "vulns", "services"],
+                ),
+                "source": string()
+            }),
+            &["host", "source"],
+        )),
+        ("probe_webcam", object(
+            json!({
+                "probe": object(
+                    json!({
+                        "url": string(),
+                        "latency_ms": integer(),
+                        "size_bytes": integer(),
+                        "mime_type": string(),
+                        "attempts": integer(),
+                        "tls_unverified": boolean()
+                    }),
+                    &["url", "latency_ms", "size_bytes", "mime_type", "attempts"],
+                )
+            }),
+            &["probe"],
+        )),
+        ("list_remote_webcams", object(
+            json!({
+                "webcams": array(object(
+                    json!({
+                        "ip": string(),
+                        "port": integer(),
+                        "url": string(),
+                        "hostname": nullable("string"),
+                        "location": { "type": ["object", "null"] },
+                        "org": nullable("string"),
+                        "product": nullable("string"),
+                        "last_seen": string(),
+                        "access_type": access_type(),
+                        "classification": classification(),
+                        "sources": array(string()),
+                        "queries": array(string()),
+                        "discovered_at": string(),
+                        "last_found_at": string(),
+                        "validation": { "type": ["object", "null"] },
+                        "distance_km": nullable("number")
+                    }),
+                    &["ip", "port", "url", "access_type", "queries", "discovered_at", "last_found_at"],
+                )),
+                "total": integer(),
+                "outside_radius": integer(),
+                "offset": integer(),
+                "next_offset": nullable("integer")
+            }),
+            &["webcams", "total", "offset"],
+        )),
+        ("clear_remote_webcams", object(json!({ "cleared": integer() }), &["cleared"])),
+        ("export_webcams", object(
+            json!({
+                "format": { "type": "string", "enum": ["geojson", "csv", "json"] },
+                "mime_type": string(),
+                "exported": integer(),
+                "size_bytes": integer(),
+                "document": nullable("string"),
+                "path": nullable("string"),
+                "without_coordinates": array(json!({ "type": "object" }))
+            }),
+            &["format", "exported", "size_bytes", "without_coordinates"],
+        )),
+        ("validate_webcams", object(
+            json!({
+                "results": array(object(
+                    json!({
+                        "ip": string(),
+                        "port": integer(),
+                        "url": string(),
+                        "cached": boolean(),
+                        "validation": or_null(webcam_validation()),
+                        "thumbnail": boolean(),
+                        "error": { "type": "object" }
+                    }),
+                    &["ip", "port", "url", "cached", "validation"],
+                )),
+                "summary": object(
+                    json!({
+                        "total": integer(),
+                        "reachable": integer(),
+                        "image_served": integer(),
+                        "cooling_down": integer()
+                    }),
+                    &["total", "reachable", "image_served", "cooling_down"],
+                )
+            }),
+            &["results", "summary"],
+        )),
+        ("discover_local_network_cameras", object(
+            json!({
+                "cameras": array(object(
+                    json!({
+                        "ip": string(),
+                        "port": integer(),
+                        "url": string(),
+                        "product": nullable("string"),
+                        "last_seen": string(),
+                        "access_type": access_type(),
+                        "classification": classification(),
+                        "sources": array(string()),
+                        "name": nullable("string"),
+                        "onvif": or_null(object(
+                            json!({
+                                "endpoint": string(),
+                                "xaddrs": array(string()),
+                                "types": array(string()),
+                                "scopes": array(string()),
+                                "name": nullable("string"),
+                                "hardware": nullable("string"),
+                                "responder": string()
+                            }),
+                            &["endpoint", "xaddrs", "scopes", "responder"],
+                        )),
+                        "ssdp": or_null(object(
+                            json!({
+                                "location": string(),
+                                "usn": nullable("string"),
+                                "search_target": nullable("string"),
+                                "server": nullable("string"),
+                                "device_type": nullable("string"),
+                                "friendly_name": nullable("string"),
+                                "manufacturer": nullable("string"),
+                                "model_name": nullable("string"),
+                                "model_number": nullable("string"),
+                                "presentation_url": nullable("string"),
+                                "responder": string()
+                            }),
+                            &["location", "responder"],
+                        ))
+                    }),
+                    &["ip", "port", "url", "access_type", "sources", "onvif", "ssdp"],
+                )),
+                "total": integer(),
+                "metadata": object(
+                    json!({
+                        "protocols": array(json!({ "type": "string", "enum": ["onvif", "ssdp"] })),
+                        "failed_protocols": array(object(
+                            json!({ "protocol": string(), "error": string() }),
+                            &["protocol", "error"],
+                        )),
+                        "timeout_ms": integer(),
+                        "interfaces": array(string())
+                    }),
+                    &["protocols", "failed_protocols", "timeout_ms", "interfaces"],
+                )
+            }),
+            &["cameras", "total", "metadata"],
+        )),
+        ("scan_network_for_cameras", object(
+            json!({
+                "candidates": array(object(
+                    json!({
+                        "ip": string(),
+                        "port": integer(),
+                        "url": string(),
+                        "product": nullable("string"),
+                        "last_seen": string(),
+                        "access_type": access_type(),
+                        "classification": classification(),
+                        "sources": array(string()),
+                        "protocol": json!({ "type": "string", "enum": ["http", "rtsp"] }),
+                        "banner": string()
+                    }),
+                    &["ip", "port", "url", "access_type", "sources", "protocol", "banner"],
+                )),
+                "total": integer(),
+                "metadata": object(
+                    json!({
+                        "cidr": string(),
+                        "hosts": integer(),
+                        "blocked_hosts": integer(),
+                        "ports": array(integer()),
+                        "concurrency": integer(),
+                        "connect_timeout_ms": integer(),
+                        "open_ports": integer()
+                    }),
+                    &["cidr", "hosts", "blocked_hosts", "ports", "concurrency", "connect_timeout_ms", "open_ports"],
+                )
+            }),
+            &["candidates", "total", "metadata"],
+        )),
+        ("capture_onvif_snapshot", object(
+            json!({
+                "metadata": object(
+                    json!({
+                        "source": string(),
+                        "device_url": string(),
+                        "media_url": string(),
+                        "profile": media_profile(),
+                        "profiles": array(media_profile()),
+                        "snapshot_uri": nullable("string"),
+                        "stream_uri": nullable("string"),
+                        "captured": boolean(),
+                        "url": string(),
+                        "tls_unverified": boolean(),
+                        "mime_type": string(),
+                        "size_bytes": integer(),
+                        "width": nullable("integer"),
+                        "height": nullable("integer"),
+                        "source_mime_type": string(),
+                        "source_size_bytes": integer(),
+                        "source_width": nullable("integer"),
+                        "source_height": nullable("integer"),
+                        "reencoded": boolean(),
+                        "passthrough": boolean(),
+                        "stats": or_null(image_stats()),
+                        "timestamp": string()
+                    }),
+                    &["source", "device_url", "media_url", "profile", "profiles", "snapshot_uri", "stream_uri", "captured", "timestamp"],
+                )
+            }),
+            &["metadata"],
+        )),
+        ("search_webcams", object(
+            json!({
+                "webcams": array(object(
+                    json!({
+                        "ip": string(),
+                        "port": integer(),
+                        "url": string(),
+                        "hostname": nullable("string"),
+                        "location": { "type": ["object", "null"] },
+                        "org": nullable("string"),
+                        "product": nullable("string"),
+                        "last_seen": string(),
+                        "access_type": access_type(),
+                        "classification": classification(),
+                        "sources": array(string()),
+                        "distance_km": nullable("number"),
+                        "honeyscore": nullable("number"),
+                        "honeypot_warning": string()
+                    }),
+                    &["ip", "port", "url", "access_type"],
+                )),
+                "hosts": array(object(
+                    json!({
+                        "ip": string(),
+                        "hostname": nullable("string"),
+                        "location": { "type": ["object", "null"] },
+                        "org": nullable("string"),
+                        "endpoints": array(object(
+                            json!({
+                                "port": integer(),
+                                "url": string(),
+                                "product": nullable("string"),
+                                "last_seen": string(),
+                                "access_type": access_type(),
+                                "classification": classification(),
+                                "sources": array(string())
+                            }),
+                            &["port", "url", "access_type"],
+                        )),
+                        "distance_km": nullable("number"),
+                        "honeyscore": nullable("number"),
+                        "honeypot_warning": string()
+                    }),
+                    &["ip", "endpoints"],
+                )),
+                "total": integer(),
+                "outside_radius": integer(),
+                "metadata": object(
+                    json!({
+                        "filters": object(
+                            json!({
+                                "country": nullable("string"),
+                                "city": nullable("string"),
+                                "org": nullable("string"),
+                                "vendor": nullable("string"),
+                                "near": {
+                                    "type": ["object", "null"],
+                                    "properties": {
+                                        "latitude": number(),
+                                        "longitude": number(),
+                                        "radius_km": number()
+                                    },
+                                    "required": ["latitude", "longitude", "radius_km"]
+                                }
+                            }),
+                            &["country", "city", "org"],
+                        ),
+                        "queries": array(string()),
+                        "group_by_host": boolean(),
+                        "reverse_dns": object(
+                            json!({ "looked_up": integer(), "resolved": integer(), "unfinished": integer() }),
+                            &["looked_up", "resolved", "unfinished"],
+                        ),
+                        "providers": array(string()),
+                        "failed_providers": array(provider_failure()),
+                        "honeyscore": object(
+                            json!({ "threshold": number(), "looked_up": integer(), "failed": integer(), "skipped": integer() }),
+                            &["threshold", "looked_up", "failed", "skipped"],
+                        )
+                    }),
+                    &["filters", "queries", "group_by_host", "providers"],
+                )
+            }),
+            &["total", "metadata"],
+        )),
+        ("capture_remote_image", object(
+            json!({
+                "metadata": object(
+                    json!({
+                        "source": string(),
+                        "url": string(),
+                        "probed": boolean(),
+                        "tls_unverified": boolean(),
+                        "mime_type": string(),
+                        "size_bytes": nullable("integer"),
+                        "bytes_read": integer(),
+                        "metadata_only": boolean(),
+                        "width": nullable("integer"),
+                        "height": nullable("integer"),
+                        "source_mime_type": string(),
+                        "source_size_bytes": integer(),
+                        "source_width": nullable("integer"),
+                        "source_height": nullable("integer"),
+                        "reencoded": boolean(),
+                        "passthrough": boolean(),
+                        "stats": or_null(image_stats()),
+                        "frames": integer(),
+                        "interval_ms": nullable("integer"),
+                        "images": { "type": "string", "enum": ["all", "first_only"] },
+                        "timestamp": string()
+                    }),
+                    &["source", "url", "mime_type", "size_bytes", "reencoded", "timestamp"],
+                ),
+                "liveness": object(
+                    json!({
+                        "verdict": { "type": "string", "enum": ["live", "static", "unknown"] },
+                        "scores": array(nullable("number")),
+                        "threshold": number()
+                    }),
+                    &["verdict", "scores", "threshold"],
+                )
+            }),
+            &["metadata"],
+        )),
+    ]
+}
+
+/// Check `value` against `schema`; the error names the first offending path
+pub fn validate(schema: &Value, value: &Value) -> Result<(), String> {
+    validate_at(schema, value, "$")
+}
+
+/// `validate` with errors naming paths from `path`, e.g. a tool argument
+pub(crate) fn validate_at(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    if let Some(types) = schema.get("type") {
+        let allowed: Vec<&str> = match types {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.iter().any(|name| has_type(value, name)) {
+            return Err(format!("{}: expected {}, got {}", path, allowed.join(" or "), value));
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            return Err(format!("{}: {} is not one of {}", path, value, Value::Array(options.clone())));
+        }
+    }
+
+    if let Value::Object(fields) = value {
+        for name in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+            let name = name.as_str().unwrap_or_default();
+            if !fields.contains_key(name) {
+                return Err(format!("{}: missing required property '{}'", path, name));
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (name, field) in fields {
+            let field_path = format!("{}.{}", path, name);
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(field_schema) => validate_at(field_schema, field, &field_path)?,
+                None => {
+                    if let Some(extra) = schema.get("additionalProperties").filter(|s| s.is_object()) {
+                        validate_at(extra, field, &field_path)?;
+                    }
+                }
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            validate_at(item_schema, item, &format!("{}[{}]", path, i))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => false,
+    }
+}
+
+fn object(properties: Value, required: &[&str]) -> Value {
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn nullable(name: &str) -> Value {
+    json!({ "type": [name, "null"] })
+}
+
+/// `schema` for an object that may be null instead
+fn or_null(mut schema: Value) -> Value {
+    schema["type"] = json!(["object", "null"]);
+    schema
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer" })
+}
+
+fn number() -> Value {
+    json!({ "type": "number" })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+fn rect() -> Value {
+    object(
+        json!({ "x": integer(), "y": integer(), "width": integer(), "height": integer() }),
+        &["x", "y", "width", "height"],
+    )
+}
+
+fn image_stats() -> Value {
+    object(
+        json!({
+            "mean_luminance": number(),
+            "luminance_stddev": number(),
+            "mean_rgb": array(number())
+        }),
+        &["mean_luminance", "luminance_stddev", "mean_rgb"],
+    )
+}
+
+fn camera_info() -> Value {
+    object(
+        json!({
+            "index": integer(),
+            "name": string(),
+            "description": string(),
+            "available": boolean(),
+            "fake": boolean(),
+            "backend": string(),
+            "device_id": nullable("string")
+        }),
+        &["index", "name", "description", "available"],
+    )
+}
+
+fn capture_metadata() -> Value {
+    object(
+        json!({
+            "capture_id": string(),
+            "width": integer(),
+            "height": integer(),
+            "camera_index": integer(),
+            "timestamp": string(),
+            "mime_type": string(),
+            "enhancement": { "type": ["object", "null"] },
+            "white_balance": { "type": ["object", "null"] },
+            "privacy_masked_regions": integer(),
+            "face_blur": { "type": ["object", "null"] },
+            "rotation": integer(),
+            "quality": integer(),
+            "camera_name": nullable("string"),
+            "backend": nullable("string"),
+            "encoder": string(),
+            "encode_ms": number(),
+            "saved_path": nullable("string")
+        }),
+        &["capture_id", "width", "height", "camera_index", "timestamp", "mime_type"],
+    )
+}
+
+/// Consent state of one scope in `get_server_info`
+fn consent_scope() -> Value {
+    object(
+        json!({
+            "required": boolean(),
+            "granted": boolean(),
+            "expires_at": nullable("string"),
+            "gated_tools": array(string())
+        }),
+        &["required", "granted", "gated_tools"],
+    )
+}
+
+fn effective_profile() -> Value {
+    object(
+        json!({
+            "resolution": { "type": ["array", "null"], "items": integer() },
+            "rotation": integer(),
+            "quality": integer(),
+            "warmup_frames": integer(),
+            "backend": nullable("string")
+        }),
+        &["rotation", "quality", "warmup_frames"],
+    )
+}
+
+/// Plan and credits left of a discovery service's account
+fn provider_credits() -> Value {
+    object(
+        json!({
+            "provider": string(),
+            "plan": nullable("string"),
+            "query_credits": integer(),
+            "scan_credits": nullable("integer"),
+            "resets_at": nullable("string")
+        }),
+        &["provider", "plan", "query_credits", "scan_credits"],
+    )
+}
+
+/// The server's budget of Shodan requests and what is left of it
+fn budget_status() -> Value {
+    let window = object(
+        json!({
+            "limit": nullable("integer"),
+            "used": integer(),
+            "remaining": nullable("integer"),
+            "resets_at": string()
+        }),
+        &["limit", "used", "remaining", "resets_at"],
+    );
+    object(
+        json!({ "limited": boolean(), "hour": window.clone(), "day": window, "refused": integer() }),
+        &["limited", "hour", "day", "refused"],
+    )
+}
+
+fn provider_failure() -> Value {
+    object(json!({ "provider": string(), "error": string() }), &["provider", "error"])
+}
+
+/// How a discovered webcam is reached
+fn access_type() -> Value {
+    json!({ "type": "string", "enum": ["MJPEG", "RTSP", "HTTP", "ONVIF", "Unknown"] })
+}
+
+/// Make of a discovered webcam, or null if it wasn't recognized
+fn classification() -> Value {
+    let mut schema = object(
+        json!({
+            "vendor": { "type": "string", "enum": Vendor::ALL.map(Vendor::name) },
+            "confidence": number(),
+            "snapshot_path": nullable("string")
+        }),
+        &["vendor", "confidence"],
+    );
+    schema["type"] = json!(["object", "null"]);
+    schema
+}
+
+/// What `validate_webcams` found for one webcam
+fn webcam_validation() -> Value {
+    object(
+        json!({
+            "checked_at": string(),
+            "reachable": boolean(),
+            "image_served": boolean(),
+            "http_status": nullable("integer"),
+            "latency_ms": nullable("integer"),
+            "content_type": nullable("string"),
+            "error": nullable("string"),
+            "width": nullable("integer"),
+            "height": nullable("integer"),
+            "size_bytes": nullable("integer")
+        }),
+        &["checked_at", "reachable", "image_served"],
+    )
+}
+
+/// A media profile of an ONVIF camera
+fn media_profile() -> Value {
+    object(
+        json!({
+            "token": string(),
+            "name": nullable("string"),
+            "encoding": nullable("string"),
+            "width": nullable("integer"),
+            "height": nullable("integer")
+        }),
+        &["token"],
+    )
+}
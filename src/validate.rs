@@ -0,0 +1,260 @@
+//! Checking many discovered webcams at once. Most of what a search finds
+//! is offline or firewalled; a validation says which entries answer and
+//! which of those serve an image, without downloading the whole image.
+//! Asked for more, it reads as far as each image's header for its
+//! dimensions, or the whole image for a thumbnail.
+
+use crate::rtsp;
+use crate::image_processing;
+use crate::shodan::{detect_image_type, sanitize_url, FetchExtent, ImageBody, RemoteWebcam, ShodanClient, ShodanError};
+use crate::webcam::{self, OutputFormat, RemoteImageOptions};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// Most webcams one validation may check
+pub const MAX_VALIDATE_WEBCAMS: usize = 100;
+/// Most checks a validation may have in flight at once
+pub const MAX_VALIDATE_CONCURRENCY: usize = 32;
+/// Longest a single check may be allowed
+pub const MAX_VALIDATE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Bytes read from an answering webcam to tell what it serves
+const SNIFF_BYTES: usize = 64;
+
+/// How much of each webcam's image a validation reads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValidateDepth {
+    /// The first bytes, enough to tell an image from anything else
+    #[default]
+    Sniff,
+    /// As far as the image's header, for its dimensions and size
+    Metadata,
+    /// The whole image, up to the download limit, to make a thumbnail of
+    Thumbnail,
+}
+
+/// Limits of one validation
+#[derive(Debug, Clone, Copy)]
+pub struct ValidateOptions {
+    /// Checks in flight at once
+    pub concurrency: usize,
+    /// Time allowed for each webcam, connecting included
+    pub timeout: Duration,
+    /// How much of each image to read; RTSP webcams are only connected to
+    /// whatever it is
+    pub depth: ValidateDepth,
+}
+
+impl Default for ValidateOptions {
+    fn default() -> Self {
+        Self { concurrency: 8, timeout: Duration::from_secs(5), depth: ValidateDepth::Sniff }
+    }
+}
+
+/// What a check of one webcam found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebcamValidation {
+    /// When the check ran, RFC 3339
+    pub checked_at: String,
+    /// Whether the webcam answered at all, whatever the status
+    pub reachable: bool,
+    /// Whether it answered with an image or an MJPEG stream
+    pub image_served: bool,
+    /// Status of the answer; `None` for RTSP, which is only connected to
+    pub http_status: Option<u16>,
+    /// Time until the answer, or the connection for RTSP
+    pub latency_ms: Option<u64>,
+    /// Type of what was served: sniffed from its first bytes, else the
+    /// webcam's `Content-Type`
+    pub content_type: Option<String>,
+    /// Why the webcam could not be reached
+    pub error: Option<String>,
+    /// Dimensions of the image, when its header was read
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// Size of the whole image, when its header was read and the webcam
+    /// said or the image was read to its end
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+    /// JPEG thumbnail of the image, when one was asked for. Never cached.
+    #[serde(skip)]
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+impl WebcamValidation {
+    fn unreachable(error: String) -> Self {
+        Self {
+            checked_at: chrono::Utc::now().to_rfc3339(),
+            reachable: false,
+            image_served: false,
+            http_status: None,
+            latency_ms: None,
+            content_type: None,
+            error: Some(error),
+            width: None,
+            height: None,
+            size_bytes: None,
+            thumbnail: None,
+        }
+    }
+}
+
+impl ShodanClient {
+    /// Check every webcam in `webcams`, `options.concurrency` at a time.
+    /// The validations come back in the order of `webcams`. Each host is
+    /// claimed against the cooldown once, however many of its ports are
+    /// checked; the webcams of a host still cooling down are not checked
+    /// and get `ShodanError::HostCooldown` instead.
+    pub async fn validate_webcams(&self, webcams: &[RemoteWebcam], options: &ValidateOptions) -> Vec<Result<WebcamValidation, ShodanError>> {
+        let permits = Arc::new(tokio::sync::Semaphore::new(options.concurrency.clamp(1, MAX_VALIDATE_CONCURRENCY)));
+        let timeout = options.timeout.min(MAX_VALIDATE_TIMEOUT);
+        let depth = options.depth;
+        // Time left to wait for each host claimed, zero once claimed
+        let mut claims: HashMap<&str, Duration> = HashMap::new();
+        let mut validations: Vec<Option<Result<WebcamValidation, ShodanError>>> = webcams.iter().map(|_| None).collect();
+        // Dropping the set, e.g. when the call is cancelled, aborts the checks
+        let mut checks = tokio::task::JoinSet::new();
+        for (index, webcam) in webcams.iter().enumerate() {
+            let retry_after = *claims.entry(webcam.ip.as_str()).or_insert_with(|| match self.claim_host(&webcam.ip) {
+                Err(ShodanError::HostCooldown { retry_after, .. }) => retry_after,
+                _ => Duration::ZERO,
+            });
+            if !retry_after.is_zero() {
+                validations[index] = Some(Err(ShodanError::HostCooldown { host: webcam.ip.clone(), retry_after }));
+                continue;
+            }
+            let client = self.clone();
+            let webcam = webcam.clone();
+            let permits = Arc::clone(&permits);
+            checks.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                (index, client.validate_webcam_to(&webcam, timeout, depth).await)
+            });
+        }
+
+        while let Some(joined) = checks.join_next().await {
+            if let Ok((index, validation)) = joined {
+                validations[index] = Some(Ok(validation));
+            }
+        }
+        let validations: Vec<Result<WebcamValidation, ShodanError>> = validations
+            .into_iter()
+            .map(|validation| validation.unwrap_or_else(|| Ok(WebcamValidation::unreachable("check failed".to_string()))))
+            .collect();
+        let checked: Vec<&WebcamValidation> = validations.iter().filter_map(|validation| validation.as_ref().ok()).collect();
+        info!(
+            "Validated {} webcam(s): {} reachable, {} serving an image, {} left for the cooldown",
+            checked.len(),
+            checked.iter().filter(|v| v.reachable).count(),
+            checked.iter().filter(|v| v.image_served).count(),
+            validations.len() - checked.len()
+        );
+        validations
+    }
+
+    /// Check one webcam: request its URL and look at the first bytes of
+    /// the answer, or for RTSP, connect to its port
+    pub async fn validate_webcam(&self, webcam: &RemoteWebcam, timeout: Duration) -> WebcamValidation {
+        self.validate_webcam_to(webcam, timeout, ValidateDepth::Sniff).await
+    }
+
+    /// `validate_webcam`, reading as much of the image as `depth` asks
+    pub async fn validate_webcam_to(&self, webcam: &RemoteWebcam, timeout: Duration, depth: ValidateDepth) -> WebcamValidation {
+        debug!("Validating {}", sanitize_url(&webcam.url));
+        let started = Instant::now();
+        if rtsp::is_rtsp_url(&webcam.url) {
+            let host = webcam.ip.trim_matches(['[', ']']).to_string();
+            let allowed = match host.parse() {
+                Ok(ip) => self.check_target_ip(&webcam.url, ip),
+                Err(_) => self.check_target(&webcam.url).await,
+            };
+            if let Err(e) = allowed {
+                return WebcamValidation::unreachable(e.to_string());
+            }
+            return match tokio::time::timeout(timeout, tokio::net::TcpStream::connect((host, webcam.port))).await {
+                Ok(Ok(_)) => WebcamValidation {
+                    reachable: true,
+                    latency_ms: Some(started.elapsed().as_millis() as u64),
+                    error: None,
+                    ..WebcamValidation::unreachable(String::new())
+                },
+                Ok(Err(e)) => WebcamValidation::unreachable(e.to_string()),
+                Err(_) => WebcamValidation::unreachable(format!("no connection within {} s", timeout.as_secs_f64())),
+            };
+        }
+
+        let request = async {
+            let mut response = self.get_checked(&webcam.url, timeout).await?.response;
+            let latency = started.elapsed();
+            let status = response.status();
+            let content_type = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.split(';').next().unwrap_or_default().trim().to_ascii_lowercase());
+            let mut body = ImageBody::default();
+            if status.is_success() {
+                match depth {
+                    ValidateDepth::Sniff => {
+                        while body.bytes.len() < SNIFF_BYTES {
+                            match response.chunk().await? {
+                                Some(chunk) => body.bytes.extend_from_slice(&chunk),
+                                None => break,
+                            }
+                        }
+                    }
+                    ValidateDepth::Metadata => body = self.read_image_body(response, FetchExtent::Header).await?,
+                    ValidateDepth::Thumbnail => body = self.read_image_body(response, FetchExtent::Whole).await?,
+                }
+            }
+            Ok::<_, ShodanError>((status, latency, content_type, body))
+        };
+        match tokio::time::timeout(timeout, request).await {
+            Ok(Ok((status, latency, content_type, body))) => {
+                let streams = content_type.as_deref().is_some_and(|t| t.starts_with("multipart/x-mixed-replace"));
+                // Only the first bytes of a stream are its boundary; read
+                // further, they are its first frame
+                let sniffed = depth == ValidateDepth::Sniff && streams;
+                let detected = detect_image_type(&body.bytes, body.content_type.as_deref().or(content_type.as_deref()))
+                    .ok()
+                    .filter(|_| !sniffed);
+                let dimensions = detected.as_ref().filter(|_| depth != ValidateDepth::Sniff).and_then(|_| image_processing::image_dimensions(&body.bytes));
+                let thumbnail = match &detected {
+                    Some(mime_type) if depth == ValidateDepth::Thumbnail => make_thumbnail(&webcam.url, body.bytes, mime_type),
+                    _ => None,
+                };
+                WebcamValidation {
+                    checked_at: chrono::Utc::now().to_rfc3339(),
+                    reachable: true,
+                    image_served: status.is_success() && (detected.is_some() || streams),
+                    http_status: Some(status.as_u16()),
+                    latency_ms: Some(latency.as_millis() as u64),
+                    size_bytes: body.size_bytes.filter(|_| detected.is_some()),
+                    content_type: detected.or(content_type),
+                    error: None,
+                    width: dimensions.map(|(width, _)| width),
+                    height: dimensions.map(|(_, height)| height),
+                    thumbnail,
+                }
+            }
+            Ok(Err(e)) => WebcamValidation::unreachable(e.to_string()),
+            Err(_) => WebcamValidation::unreachable(format!("no answer within {} s", timeout.as_secs_f64())),
+        }
+    }
+}
+
+/// JPEG thumbnail of an image `url` served, if it decodes
+fn make_thumbnail(url: &str, bytes: Vec<u8>, mime_type: &str) -> Option<Vec<u8>> {
+    let options = RemoteImageOptions { thumbnail: true, format: Some(OutputFormat::Jpeg), ..RemoteImageOptions::default() };
+    match webcam::process_remote_image(bytes, mime_type, &options) {
+        Ok(processed) => Some(processed.bytes),
+        Err(e) => {
+            debug!("No thumbnail of {}: {}", sanitize_url(url), e);
+            None
+        }
+    }
+}
@@ -1,20 +1,20 @@
+use crate::backend::{self, CameraControl, CaptureBackend};
+use crate::config::ConfigFile;
+use crate::exif_writer::ExifMetadata;
+use crate::face;
+use crate::image_processing::{self, CropRect, EnhanceMode, EnhancementInfo, WhiteBalance, WhiteBalanceInfo};
 use anyhow::Result;
+use base64::{engine::general_purpose, Engine as _};
+use bytes::Bytes;
+use image::RgbImage;
 use serde::{Deserialize, Serialize};
-use tracing::{error, warn};
-
-#[cfg(feature = "local_cameras")]
-use {
-    anyhow::Context,
-    base64::{engine::general_purpose, Engine as _},
-    image::{ImageFormat},
-    std::io::Cursor,
-    tracing::{debug, info},
-    nokhwa::{
-        pixel_format::RgbFormat,
-        utils::{CameraIndex, RequestedFormat, RequestedFormatType, Resolution},
-        Camera, CallbackCamera,
-    },
-};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CameraInfo {
@@ -22,16 +22,620 @@ pub struct CameraInfo {
     pub name: String,
     pub description: String,
     pub available: bool,
+    /// True for cameras that aren't real devices (synthetic or file-backed)
+    #[serde(default)]
+    pub fake: bool,
+    /// Capture backend serving this camera, e.g. `nokhwa` or `opencv`
+    #[serde(default)]
+    pub backend: String,
+    /// Identifier that stays the same when devices are renumbered, e.g.
+    /// after the camera is unplugged and plugged back in
+    #[serde(default)]
+    pub device_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CaptureResult {
-    pub image_data: String, // Base64 encoded
+    /// Encoded image. Shared rather than copied when the result is cloned;
+    /// base64 is only produced for MCP responses, see `image_base64`.
+    #[serde(rename = "image_data", with = "base64_bytes")]
+    pub image_bytes: Bytes,
     pub mime_type: String,
     pub width: u32,
     pub height: u32,
     pub timestamp: String,
     pub camera_index: u32,
+    /// Present only when enhancement was requested
+    pub enhancement: Option<EnhancementInfo>,
+    /// Present only when white balance was requested
+    pub white_balance: Option<WhiteBalanceInfo>,
+    /// Number of server-configured privacy mask regions blacked out
+    pub privacy_masked_regions: usize,
+    /// Present only when face blurring was requested
+    pub face_blur: Option<FaceBlurInfo>,
+    /// Clockwise rotation applied, in degrees
+    pub rotation: u32,
+    /// JPEG quality used for encoding
+    pub quality: u8,
+    /// Name reported by the camera, when known
+    pub camera_name: Option<String>,
+    /// Capture backend the frame came from, when known
+    pub backend: Option<String>,
+    /// JPEG encoder used: `turbojpeg` or `image`
+    #[serde(default)]
+    pub encoder: String,
+    /// Time spent encoding the JPEG, in milliseconds
+    #[serde(default)]
+    pub encode_ms: f64,
+}
+
+/// Face blurring requested for a capture
+#[derive(Debug, Clone, Copy)]
+pub struct FaceBlur {
+    /// Fail the capture if detection fails, instead of returning the
+    /// unblurred frame
+    pub strict: bool,
+}
+
+/// What face blurring did to a capture
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaceBlurInfo {
+    pub faces_blurred: usize,
+    /// Blurred regions (detected faces plus a margin)
+    pub regions: Vec<CropRect>,
+    /// Set when detection failed and the frame was returned unblurred
+    pub detection_error: Option<String>,
+}
+
+/// JPEG quality used when neither the call nor the camera profile sets one
+pub const DEFAULT_JPEG_QUALITY: u8 = 75;
+
+/// Per-camera default settings. Every field is optional; per-call options
+/// take precedence over the profile.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraProfile {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Clockwise rotation in degrees: 0, 90, 180 or 270
+    pub rotation: Option<u32>,
+    /// JPEG quality, 1-100
+    pub quality: Option<u8>,
+    /// Frames to grab and discard before capturing, to let exposure settle
+    pub warmup_frames: Option<u32>,
+    /// Capture backend to open this camera with instead of the default
+    pub backend: Option<String>,
+}
+
+impl CameraProfile {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width.is_some() != self.height.is_some() {
+            return Err("width and height must be set together".to_string());
+        }
+        if matches!(self.width, Some(0)) || matches!(self.height, Some(0)) {
+            return Err("width and height must be positive".to_string());
+        }
+        if let Some(rotation) = self.rotation {
+            if ![0, 90, 180, 270].contains(&rotation) {
+                return Err(format!("rotation must be 0, 90, 180 or 270, got {}", rotation));
+            }
+        }
+        if let Some(quality) = self.quality {
+            if !(1..=100).contains(&quality) {
+                return Err(format!("quality must be between 1 and 100, got {}", quality));
+            }
+        }
+        if let Some(backend) = &self.backend {
+            if !backend::BACKEND_NAMES.contains(&backend.as_str()) {
+                return Err(format!("backend must be one of {}, got '{}'", backend::BACKEND_NAMES.join(", "), backend));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the `width=1280,height=720,rotation=180` form used by the
+    /// `MCP_WEBCAM_CAMERA_<index>` environment variables
+    pub fn parse_env(value: &str) -> Result<Self, String> {
+        let mut profile = Self::default();
+        for pair in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, val) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got '{}'", pair))?;
+            let parse = |v: &str| v.trim().parse::<u32>().map_err(|_| format!("invalid value for {}: '{}'", key, v));
+            match key.trim() {
+                "width" => profile.width = Some(parse(val)?),
+                "height" => profile.height = Some(parse(val)?),
+                "rotation" => profile.rotation = Some(parse(val)?),
+                "quality" => profile.quality = Some(parse(val)?.min(255) as u8),
+                "warmup_frames" => profile.warmup_frames = Some(parse(val)?),
+                "backend" => profile.backend = Some(val.trim().to_string()),
+                other => return Err(format!("unknown setting '{}'", other)),
+            }
+        }
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// This profile with unset capture settings taken from `defaults`. The
+    /// backend is never inherited, since it only makes sense per camera.
+    pub fn with_fallback(&self, defaults: &CameraProfile) -> CameraProfile {
+        let resolution = self.width.zip(self.height).or(defaults.width.zip(defaults.height));
+        CameraProfile {
+            width: resolution.map(|(w, _)| w),
+            height: resolution.map(|(_, h)| h),
+            rotation: self.rotation.or(defaults.rotation),
+            quality: self.quality.or(defaults.quality),
+            warmup_frames: self.warmup_frames.or(defaults.warmup_frames),
+            backend: self.backend.clone(),
+        }
+    }
+
+    /// Profile values with built-in defaults filled in
+    pub fn effective(&self) -> EffectiveProfile {
+        EffectiveProfile {
+            resolution: self.width.zip(self.height).map(|(w, h)| [w, h]),
+            rotation: self.rotation.unwrap_or(0),
+            quality: self.quality.unwrap_or(DEFAULT_JPEG_QUALITY),
+            warmup_frames: self.warmup_frames.unwrap_or(0),
+            backend: self.backend.clone(),
+        }
+    }
+}
+
+/// Settings a capture will use when no per-call overrides are given
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveProfile {
+    /// Requested resolution; `None` uses the camera's highest resolution
+    pub resolution: Option<[u32; 2]>,
+    pub rotation: u32,
+    pub quality: u8,
+    pub warmup_frames: u32,
+    /// Capture backend override; `None` uses the default backend
+    pub backend: Option<String>,
+}
+
+impl CaptureResult {
+    /// Decode the stored image back into an RGB buffer
+    pub fn decode_image(&self) -> Result<RgbImage, WebcamError> {
+        Ok(image::load_from_memory(&self.image_bytes)?.into_rgb8())
+    }
+
+    /// The encoded image as base64, for an MCP image content block. The
+    /// string is allocated once at its final size.
+    pub fn image_base64(&self) -> String {
+        let len = base64::encoded_len(self.image_bytes.len(), true).unwrap_or_default();
+        let mut encoded = String::with_capacity(len);
+        general_purpose::STANDARD.encode_string(&self.image_bytes, &mut encoded);
+        encoded
+    }
+}
+
+/// Serde for encoded images as base64 strings, matching MCP responses
+mod base64_bytes {
+    use super::*;
+
+    pub fn serialize<S: serde::Serializer>(bytes: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&general_purpose::STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        general_purpose::STANDARD
+            .decode(encoded)
+            .map(Bytes::from)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Per-call capture options
+#[derive(Debug, Clone, Default)]
+pub struct CaptureOptions {
+    /// Low-light enhancement; `None` leaves the frame untouched
+    pub enhance: Option<EnhanceMode>,
+    /// Region of the frame to keep; `None` keeps the full frame
+    pub crop: Option<CropRect>,
+    /// White balance; `None` leaves the camera's own setting untouched
+    pub white_balance: Option<WhiteBalance>,
+    /// Blur detected faces before encoding
+    pub blur_faces: Option<FaceBlur>,
+    /// Requested resolution (width, height)
+    pub resolution: Option<(u32, u32)>,
+    /// Clockwise rotation in degrees: 0, 90, 180 or 270
+    pub rotation: Option<u32>,
+    /// JPEG quality, 1-100
+    pub quality: Option<u8>,
+    /// Frames to grab and discard before capturing
+    pub warmup_frames: Option<u32>,
+    /// Downscale frames wider than this, after any resolution change
+    pub max_width: Option<u32>,
+    /// Embed EXIF metadata in the encoded JPEG
+    pub embed_exif: bool,
+    /// EXIF user comment, written when EXIF is embedded
+    pub exif_comment: Option<String>,
+}
+
+impl CaptureOptions {
+    /// Fill options not set on this call from the camera's profile
+    pub fn with_profile(&self, profile: &CameraProfile) -> CaptureOptions {
+        CaptureOptions {
+            resolution: self.resolution.or(profile.width.zip(profile.height)),
+            rotation: self.rotation.or(profile.rotation),
+            quality: self.quality.or(profile.quality),
+            warmup_frames: self.warmup_frames.or(profile.warmup_frames),
+            ..self.clone()
+        }
+    }
+
+    /// Apply the per-call processing steps to a freshly grabbed frame.
+    /// `hardware_white_balance` is true when the requested white balance was
+    /// already set on the camera before the grab.
+    pub fn process(
+        &self,
+        image: RgbImage,
+        camera_index: u32,
+        hardware_white_balance: bool,
+    ) -> Result<CapturedFrame, WebcamError> {
+        // Scale down to the requested resolution if the camera delivered more
+        let image = match self.resolution {
+            Some((width, height)) if image.dimensions() != (width, height) => {
+                image::DynamicImage::ImageRgb8(image)
+                    .resize(width, height, image::imageops::FilterType::Triangle)
+                    .into_rgb8()
+            }
+            _ => image,
+        };
+        let image = match self.max_width {
+            Some(max_width) => image_processing::limit_width(&image, max_width).unwrap_or(image),
+            None => image,
+        };
+
+        let rotation = self.rotation.unwrap_or(0);
+        let image = match rotation {
+            0 => image,
+            90 => image::imageops::rotate90(&image),
+            180 => image::imageops::rotate180(&image),
+            270 => image::imageops::rotate270(&image),
+            other => {
+                return Err(WebcamError::InvalidOptions(format!(
+                    "rotation must be 0, 90, 180 or 270, got {}", other
+                )))
+            }
+        };
+
+        let mut image = match &self.crop {
+            Some(rect) => rect.apply(&image).ok_or_else(|| {
+                WebcamError::InvalidOptions(format!(
+                    "Crop region {}x{}+{}+{} lies outside the {}x{} frame",
+                    rect.width, rect.height, rect.x, rect.y, image.width(), image.height()
+                ))
+            })?,
+            None => image,
+        };
+
+        // White balance: software gray-world unless the camera handled it
+        let white_balance = self.white_balance.map(|requested| {
+            if hardware_white_balance {
+                WhiteBalanceInfo { requested, path: "hardware".to_string(), gains: None }
+            } else {
+                let gains = image_processing::gray_world(&mut image);
+                WhiteBalanceInfo { requested, path: "software_gray_world".to_string(), gains: Some(gains) }
+            }
+        });
+
+        // Optional low-light enhancement, applied before encoding
+        let enhancement = self
+            .enhance
+            .map(|mode| image_processing::enhance(&mut image, mode));
+        if let Some(info) = &enhancement {
+            debug!("Enhancement {:?}: applied={} mean_luminance={:.1}",
+                   info.requested, info.applied, info.mean_luminance);
+        }
+
+        // Face blurring runs last so detection sees the final frame
+        let face_blur = match self.blur_faces {
+            Some(FaceBlur { strict }) => match face::detect_faces(&image) {
+                Ok(faces) => {
+                    let regions: Vec<CropRect> = faces
+                        .iter()
+                        .map(|rect| image_processing::expand_rect(rect, 0.2, image.width(), image.height()))
+                        .collect();
+                    for rect in &regions {
+                        image_processing::blur_rect(&mut image, rect);
+                    }
+                    debug!("Blurred {} face(s)", regions.len());
+                    Some(FaceBlurInfo { faces_blurred: regions.len(), regions, detection_error: None })
+                }
+                Err(e) if strict => return Err(WebcamError::FaceDetection(e.to_string())),
+                Err(e) => {
+                    warn!("Face detection failed, returning unblurred frame: {}", e);
+                    Some(FaceBlurInfo { faces_blurred: 0, regions: Vec::new(), detection_error: Some(e.to_string()) })
+                }
+            },
+            None => None,
+        };
+
+        Ok(CapturedFrame {
+            image,
+            camera_index,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            enhancement,
+            white_balance,
+            privacy_masked_regions: 0,
+            face_blur,
+            rotation,
+            quality: self.quality.unwrap_or(DEFAULT_JPEG_QUALITY),
+            camera_name: None,
+            backend: None,
+        })
+    }
+}
+
+/// A decoded frame after per-call processing, before encoding
+#[derive(Debug, Clone)]
+pub struct CapturedFrame {
+    pub image: RgbImage,
+    pub camera_index: u32,
+    pub timestamp: String,
+    pub enhancement: Option<EnhancementInfo>,
+    pub white_balance: Option<WhiteBalanceInfo>,
+    pub privacy_masked_regions: usize,
+    pub face_blur: Option<FaceBlurInfo>,
+    pub rotation: u32,
+    /// JPEG quality used by `encode`
+    pub quality: u8,
+    pub camera_name: Option<String>,
+    pub backend: Option<String>,
+}
+
+impl CapturedFrame {
+    /// Wrap an image produced outside the capture path (e.g. a diff image)
+    pub fn new(image: RgbImage, camera_index: u32) -> Self {
+        Self {
+            image,
+            camera_index,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            enhancement: None,
+            white_balance: None,
+            privacy_masked_regions: 0,
+            face_blur: None,
+            rotation: 0,
+            quality: DEFAULT_JPEG_QUALITY,
+            camera_name: None,
+            backend: None,
+        }
+    }
+
+    /// EXIF metadata describing this frame
+    pub fn exif_metadata(&self, comment: Option<String>) -> ExifMetadata {
+        ExifMetadata {
+            timestamp: Some(self.timestamp.clone()),
+            camera_name: self.camera_name.clone(),
+            comment,
+        }
+    }
+
+    /// Encode the frame as raw JPEG bytes
+    pub fn encode_jpeg(&self) -> Result<Vec<u8>, WebcamError> {
+        let (bytes, _) = image_processing::encode_jpeg(&self.image, self.quality)?;
+        Ok(bytes)
+    }
+
+    /// Encode the frame as base64 JPEG
+    pub fn encode(self) -> Result<CaptureResult, WebcamError> {
+        self.encode_with_exif(None)
+    }
+
+    /// Encode the frame as base64 JPEG, embedding `exif` if given
+    pub fn encode_with_exif(self, exif: Option<&ExifMetadata>) -> Result<CaptureResult, WebcamError> {
+        let width = self.image.width();
+        let height = self.image.height();
+
+        // Encode as JPEG
+        let started = std::time::Instant::now();
+        let (image_bytes, encoder) = image_processing::encode_jpeg(&self.image, self.quality)?;
+        let encode_ms = started.elapsed().as_secs_f64() * 1000.0;
+        let image_bytes = match exif {
+            Some(exif) => exif.embed(&image_bytes),
+            None => image_bytes,
+        };
+
+        Ok(CaptureResult {
+            image_bytes: Bytes::from(image_bytes),
+            mime_type: "image/jpeg".to_string(),
+            width,
+            height,
+            timestamp: self.timestamp,
+            camera_index: self.camera_index,
+            enhancement: self.enhancement,
+            white_balance: self.white_balance,
+            privacy_masked_regions: self.privacy_masked_regions,
+            face_blur: self.face_blur,
+            rotation: self.rotation,
+            quality: self.quality,
+            camera_name: self.camera_name,
+            backend: self.backend,
+            encoder: encoder.to_string(),
+            encode_ms,
+        })
+    }
+}
+
+/// Encoding of a processed remote image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "jpeg" | "jpg" => Some(Self::Jpeg),
+            "png" => Some(Self::Png),
+            _ => None,
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+        }
+    }
+
+    fn of(format: image::ImageFormat) -> Option<Self> {
+        match format {
+            image::ImageFormat::Jpeg => Some(Self::Jpeg),
+            image::ImageFormat::Png => Some(Self::Png),
+            _ => None,
+        }
+    }
+}
+
+/// Width remote images are scaled down to for `thumbnail`
+pub const THUMBNAIL_WIDTH: u32 = 320;
+
+/// How an image fetched from a remote webcam is processed before it is
+/// returned, the remote counterpart of `CaptureOptions`
+#[derive(Debug, Clone, Default)]
+pub struct RemoteImageOptions {
+    /// Downscale images wider than this
+    pub max_width: Option<u32>,
+    /// Downscale to at most `THUMBNAIL_WIDTH`, or `max_width` if smaller
+    pub thumbnail: bool,
+    /// Convert to grayscale
+    pub grayscale: bool,
+    /// Encoding of the result; `None` keeps JPEG and PNG as they are and
+    /// re-encodes other formats as JPEG
+    pub format: Option<OutputFormat>,
+    /// JPEG quality, 1-100; giving one re-encodes a JPEG even if nothing
+    /// else changed
+    pub quality: Option<u8>,
+    /// Report brightness and colour statistics
+    pub stats: bool,
+    /// Return the fetched bytes without decoding them. No other option may
+    /// be set with it.
+    pub passthrough: bool,
+}
+
+impl RemoteImageOptions {
+    /// Whether any option other than `passthrough` is set
+    pub fn processes(&self) -> bool {
+        self.max_width.is_some() || self.thumbnail || self.grayscale || self.format.is_some() || self.quality.is_some() || self.stats
+    }
+
+    /// Check the options make sense together, before anything is fetched
+    pub fn validate(&self) -> Result<(), WebcamError> {
+        if self.passthrough && self.processes() {
+            return Err(WebcamError::InvalidOptions(
+                "passthrough returns the image as fetched, so it can't be combined with max_width, thumbnail, grayscale, format, quality or stats".to_string(),
+            ));
+        }
+        match self.quality {
+            Some(quality) if !(1..=100).contains(&quality) => {
+                Err(WebcamError::InvalidOptions(format!("quality must be between 1 and 100, got {}", quality)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Width images are scaled down to, if any
+    fn width_limit(&self) -> Option<u32> {
+        match (self.max_width, self.thumbnail) {
+            (Some(max_width), true) => Some(max_width.min(THUMBNAIL_WIDTH)),
+            (None, true) => Some(THUMBNAIL_WIDTH),
+            (max_width, false) => max_width,
+        }
+    }
+}
+
+/// A remote image after `process_remote_image`
+#[derive(Debug, Clone)]
+pub struct ProcessedImage {
+    pub bytes: Vec<u8>,
+    pub mime_type: String,
+    /// Width and height returned; `None` when passed through undecoded
+    pub dimensions: Option<(u32, u32)>,
+    /// Width and height as fetched; `None` when passed through undecoded
+    pub source_dimensions: Option<(u32, u32)>,
+    /// Whether the image was re-encoded rather than returned as fetched
+    pub reencoded: bool,
+    pub stats: Option<image_processing::ImageStats>,
+}
+
+/// Decode an image fetched from a remote webcam and apply `options` to it.
+/// The fetched bytes are returned unchanged when no option changes them,
+/// so an already-small JPEG isn't recompressed.
+pub fn process_remote_image(
+    bytes: Vec<u8>,
+    content_type: &str,
+    options: &RemoteImageOptions,
+) -> Result<ProcessedImage, WebcamError> {
+    options.validate()?;
+    if options.passthrough {
+        return Ok(ProcessedImage {
+            bytes,
+            mime_type: content_type.to_string(),
+            dimensions: None,
+            source_dimensions: None,
+            reencoded: false,
+            stats: None,
+        });
+    }
+
+    let sniffed = image::guess_format(&bytes).ok();
+    let decoded = image::load_from_memory(&bytes).map_err(|e| WebcamError::UndecodableImage {
+        content_type: sniffed.map_or("unrecognised data", |format| format.to_mime_type()).to_string(),
+        declared_type: content_type.to_string(),
+        reason: e.to_string(),
+    })?;
+    let source_dimensions = (decoded.width(), decoded.height());
+    let source_format = sniffed.and_then(OutputFormat::of);
+
+    let mut image = decoded.into_rgb8();
+    let resized = match options.width_limit().and_then(|max_width| image_processing::limit_width(&image, max_width)) {
+        Some(smaller) => {
+            image = smaller;
+            true
+        }
+        None => false,
+    };
+    if options.grayscale {
+        let gray = image::imageops::grayscale(&image);
+        image = image::DynamicImage::ImageLuma8(gray).into_rgb8();
+    }
+    let stats = options.stats.then(|| image_processing::image_stats(&image));
+
+    let format = options.format.or(source_format).unwrap_or(OutputFormat::Jpeg);
+    let requantize = format == OutputFormat::Jpeg && options.quality.is_some();
+    if !resized && !options.grayscale && !requantize && source_format == Some(format) {
+        return Ok(ProcessedImage {
+            bytes,
+            mime_type: format.mime_type().to_string(),
+            dimensions: Some(source_dimensions),
+            source_dimensions: Some(source_dimensions),
+            reencoded: false,
+            stats,
+        });
+    }
+
+    let bytes = match format {
+        OutputFormat::Jpeg => image_processing::encode_jpeg(&image, options.quality.unwrap_or(DEFAULT_JPEG_QUALITY))?.0,
+        OutputFormat::Png => {
+            let mut buffer = std::io::Cursor::new(Vec::new());
+            image.write_to(&mut buffer, image::ImageFormat::Png)?;
+            buffer.into_inner()
+        }
+    };
+    Ok(ProcessedImage {
+        bytes,
+        mime_type: format.mime_type().to_string(),
+        dimensions: Some(image.dimensions()),
+        source_dimensions: Some(source_dimensions),
+        reencoded: true,
+        stats,
+    })
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -41,171 +645,1253 @@ pub enum WebcamError {
     #[cfg(feature = "local_cameras")]
     #[error("Failed to open camera: {0}")]
     CameraOpen(#[from] nokhwa::NokhwaError),
+    #[cfg(feature = "opencv_backend")]
+    #[error("OpenCV error: {0}")]
+    OpenCv(#[from] opencv::Error),
     #[error("Image processing error: {0}")]
     ImageProcessing(#[from] image::ImageError),
-    #[error("No cameras available")]
+    #[error("No cameras available: no local video devices were found. Connect a camera, or pass one through to the container (e.g. --device /dev/video0)")]
     NoCamerasAvailable,
     #[error("Local camera support not compiled in")]
     LocalCamerasNotSupported,
+    #[error("Storage error: {0}")]
+    Storage(#[from] std::io::Error),
+    #[error("Invalid capture options: {0}")]
+    InvalidOptions(String),
+    #[error("Failed to decode image: {0}")]
+    Decode(String),
+    #[error("The remote webcam sent {content_type} (as {declared_type}), which could not be decoded as an image: {reason}")]
+    UndecodableImage {
+        /// Type sniffed from the bytes, or "unrecognised data"
+        content_type: String,
+        /// Type the webcam declared
+        declared_type: String,
+        reason: String,
+    },
+    #[error("Privacy mask configuration is invalid, captures are disabled: {0}")]
+    PrivacyMaskConfig(String),
+    #[error("Face detection failed: {0}")]
+    FaceDetection(String),
+    #[error("No camera is open")]
+    CameraNotOpen,
+    #[error("Camera {index} was disconnected")]
+    CameraDisconnected { index: u32 },
+    #[error("Camera did not respond within {seconds} seconds")]
+    Timeout { seconds: u64 },
+    #[error("Capture was cancelled")]
+    Cancelled,
+    #[error("GStreamer error: {0}")]
+    GStreamer(String),
+    #[error("GStreamer pipeline for camera {index} failed: {message}")]
+    GStreamerPipeline { index: u32, message: String },
+    #[error("libcamera capture failed: {0}")]
+    Libcamera(String),
+    #[error("Camera {index} is busy: it is being used by {}. Close that application and try again", .holder.as_deref().unwrap_or("another application"))]
+    DeviceBusy {
+        index: u32,
+        /// Process holding the device, e.g. `zoom (pid 4242)`, if it could be found
+        holder: Option<String>,
+    },
+}
+
+impl WebcamError {
+    /// Short snake_case name of the error, for grouping failures in statistics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WebcamError::CameraNotFound { .. } => "camera_not_found",
+            #[cfg(feature = "local_cameras")]
+            WebcamError::CameraOpen(_) => "camera_open",
+            #[cfg(feature = "opencv_backend")]
+            WebcamError::OpenCv(_) => "opencv",
+            WebcamError::ImageProcessing(_) => "image_processing",
+            WebcamError::NoCamerasAvailable => "no_cameras_available",
+            WebcamError::LocalCamerasNotSupported => "local_cameras_not_supported",
+            WebcamError::Storage(_) => "storage",
+            WebcamError::InvalidOptions(_) => "invalid_options",
+            WebcamError::Decode(_) | WebcamError::UndecodableImage { .. } => "decode",
+            WebcamError::PrivacyMaskConfig(_) => "privacy_mask_config",
+            WebcamError::FaceDetection(_) => "face_detection",
+            WebcamError::CameraNotOpen => "camera_not_open",
+            WebcamError::CameraDisconnected { .. } => "camera_disconnected",
+            WebcamError::Timeout { .. } => "timeout",
+            WebcamError::Cancelled => "cancelled",
+            WebcamError::GStreamer(_) | WebcamError::GStreamerPipeline { .. } => "gstreamer",
+            WebcamError::Libcamera(_) => "libcamera",
+            WebcamError::DeviceBusy { .. } => "device_busy",
+        }
+    }
+}
+
+/// Server-side privacy masks: rectangles per camera index that are always
+/// blacked out, in full-frame coordinates, before any other processing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PrivacyMasks {
+    masks: HashMap<u32, Vec<CropRect>>,
+}
+
+impl PrivacyMasks {
+    /// Load masks from the JSON in `MCP_WEBCAM_PRIVACY_MASKS`, or from the
+    /// file named by `MCP_WEBCAM_PRIVACY_MASKS_FILE`. The format is
+    /// `{"0": [{"x": 0, "y": 0, "width": 100, "height": 50}]}`.
+    pub fn from_env() -> Result<Self, String> {
+        let json = match (
+            std::env::var("MCP_WEBCAM_PRIVACY_MASKS"),
+            std::env::var("MCP_WEBCAM_PRIVACY_MASKS_FILE"),
+        ) {
+            (Ok(json), _) => json,
+            (Err(_), Ok(path)) => std::fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read {}: {}", path, e))?,
+            _ => return Ok(Self::default()),
+        };
+        serde_json::from_str(&json).map_err(|e| format!("failed to parse privacy masks: {}", e))
+    }
+
+    pub fn for_camera(&self, index: u32) -> &[CropRect] {
+        self.masks.get(&index).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Black out every mask region for the camera; returns how many were applied
+    pub fn apply(&self, index: u32, img: &mut RgbImage) -> usize {
+        let regions = self.for_camera(index);
+        for rect in regions {
+            image_processing::fill_rect(img, rect, [0, 0, 0]);
+        }
+        regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.masks.values().all(Vec::is_empty)
+    }
 }
 
 pub struct WebcamManager {
-    #[cfg(feature = "local_cameras")]
-    current_camera: Option<Camera>,
-    #[cfg(not(feature = "local_cameras"))]
-    current_camera: Option<()>, // Placeholder when local cameras not supported
+    /// Access to real camera devices
+    backend: Box<dyn CaptureBackend>,
+    /// Other backends, by name, created when a camera profile selects them
+    alternate_backends: HashMap<String, Box<dyn CaptureBackend>>,
+    /// Backends defining cameras of their own, e.g. GStreamer pipelines
+    extra_backends: Vec<Box<dyn CaptureBackend>>,
     current_index: Option<u32>,
+    /// Resolution requested when the current camera was opened
+    current_resolution: Option<(u32, u32)>,
+    /// Stable id of the current camera, to find it again if it is renumbered
+    current_device_id: Option<String>,
+    /// When the current camera was opened (RFC 3339)
+    current_opened_at: Option<String>,
+    /// Frames captured from the current camera since it was opened
+    current_frames: u64,
+    /// Cameras found by the default backend and when they were enumerated,
+    /// since enumeration can take hundreds of milliseconds
+    device_cache: Mutex<Option<(Instant, Vec<CameraInfo>)>>,
+    /// How long `device_cache` stays valid; zero disables caching
+    device_cache_ttl: Duration,
+    /// Masks can only be configured at startup; an invalid configuration
+    /// disables captures rather than silently capturing unmasked frames
+    privacy_masks: Result<PrivacyMasks, String>,
+    /// Default settings per camera index
+    profiles: HashMap<u32, CameraProfile>,
+    /// Settings for cameras whose profile leaves them unset
+    capture_defaults: CameraProfile,
+    /// Camera used when a call names none
+    default_camera: u32,
+    /// Width above which every frame is downscaled; `None` keeps full size
+    max_width: Option<u32>,
+    /// Synthetic camera, present when built with the `test_camera` feature
+    synthetic: Option<SyntheticCamera>,
+    /// Camera replaying image files, present when `MCP_WEBCAM_FAKE_SOURCE` is set
+    file_camera: Option<FileCamera>,
 }
 
 impl WebcamManager {
     pub fn new() -> Self {
+        Self::with_backend(backend::default_backend())
+    }
+
+    /// Manager using the given capture backend instead of the platform one
+    pub fn with_backend(backend: Box<dyn CaptureBackend>) -> Self {
+        let privacy_masks = PrivacyMasks::from_env();
+        match &privacy_masks {
+            Ok(masks) if !masks.is_empty() => {
+                let total: usize = masks.masks.values().map(Vec::len).sum();
+                info!("Loaded {} privacy mask region(s) for {} camera(s)", total, masks.masks.len());
+            }
+            Ok(_) => {}
+            Err(e) => error!("Privacy mask configuration is invalid, captures are disabled: {}", e),
+        }
+
         Self {
-            current_camera: None,
+            backend,
+            alternate_backends: HashMap::new(),
+            extra_backends: backend::extra_backends(),
             current_index: None,
+            current_resolution: None,
+            current_device_id: None,
+            current_opened_at: None,
+            current_frames: 0,
+            device_cache: Mutex::new(None),
+            device_cache_ttl: device_cache_ttl(),
+            privacy_masks,
+            profiles: load_camera_profiles(),
+            capture_defaults: CameraProfile::default(),
+            default_camera: 0,
+            max_width: None,
+            synthetic: cfg!(feature = "test_camera").then(SyntheticCamera::default),
+            file_camera: FileCamera::from_env(),
         }
     }
 
+    /// Whether `index` is a camera that isn't backed by a real device
+    fn is_fake(&self, index: u32) -> bool {
+        (self.synthetic.is_some() && index == SYNTHETIC_CAMERA_INDEX)
+            || (self.file_camera.is_some() && index == FILE_CAMERA_INDEX)
+    }
+
+    /// Name of the backend serving cameras without a profile override
+    pub fn default_backend_name(&self) -> &str {
+        self.backend.name()
+    }
+
+    /// Name of the backend serving camera `index`
+    fn backend_name(&self, index: u32) -> &str {
+        self.profiles.get(&index)
+            .and_then(|profile| profile.backend.as_deref())
+            .unwrap_or_else(|| self.backend.name())
+    }
+
+    /// Backend serving camera `index`: the backend that defines it, the one
+    /// its profile selects, or the default
+    fn backend_for(&mut self, index: u32) -> Result<&mut dyn CaptureBackend, WebcamError> {
+        if let Some(position) = self.extra_backends.iter().position(|b| b.owns(index)) {
+            return Ok(self.extra_backends[position].as_mut());
+        }
+
+        let name = self.backend_name(index).to_string();
+        if name == self.backend.name() {
+            return Ok(self.backend.as_mut());
+        }
+
+        let backend = match self.alternate_backends.entry(name) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let backend = backend::create_backend(entry.key()).ok_or_else(|| {
+                    WebcamError::InvalidOptions(format!("Capture backend '{}' is not available in this build", entry.key()))
+                })?;
+                info!("Using {} capture backend for camera {}", backend.name(), index);
+                entry.insert(backend)
+            }
+        };
+        Ok(backend.as_mut())
+    }
+
+    /// Privacy masks in effect, if the configuration is valid
+    pub fn privacy_masks(&self) -> Option<&PrivacyMasks> {
+        self.privacy_masks.as_ref().ok()
+    }
+
+    /// Default settings profile for a camera (empty if none configured)
+    pub fn camera_profile(&self, index: u32) -> CameraProfile {
+        self.profiles.get(&index).cloned().unwrap_or_default()
+    }
+
+    /// Replace the default settings profile for a camera
+    pub fn set_camera_profile(&mut self, index: u32, profile: CameraProfile) -> Result<(), WebcamError> {
+        profile.validate().map_err(WebcamError::InvalidOptions)?;
+        info!("Updated default settings for camera {}: {:?}", index, profile);
+        if profile == CameraProfile::default() {
+            self.profiles.remove(&index);
+        } else {
+            self.profiles.insert(index, profile);
+        }
+        Ok(())
+    }
+
+    /// Settings a capture from this camera uses when no overrides are given
+    pub fn effective_profile(&self, index: u32) -> EffectiveProfile {
+        self.resolved_profile(index).effective()
+    }
+
+    /// The camera's profile with server-wide capture defaults filled in
+    fn resolved_profile(&self, index: u32) -> CameraProfile {
+        self.camera_profile(index).with_fallback(&self.capture_defaults)
+    }
+
+    /// Replace the settings used by cameras whose profile leaves them unset
+    pub fn set_capture_defaults(&mut self, defaults: CameraProfile) -> Result<(), WebcamError> {
+        defaults.validate().map_err(WebcamError::InvalidOptions)?;
+        if defaults.backend.is_some() {
+            return Err(WebcamError::InvalidOptions("the backend can only be set per camera".to_string()));
+        }
+        self.capture_defaults = defaults;
+        Ok(())
+    }
+
+    /// Camera used by captures that don't name one
+    pub fn default_camera(&self) -> u32 {
+        self.default_camera
+    }
+
+    pub fn set_default_camera(&mut self, index: u32) {
+        self.default_camera = index;
+    }
+
+    /// Downscale every frame wider than `max_width`; `None` keeps full size
+    pub fn set_max_width(&mut self, max_width: Option<u32>) {
+        self.max_width = max_width.filter(|&w| w > 0);
+    }
+
     /// List all available cameras
     pub fn list_cameras(&self) -> Result<Vec<CameraInfo>, WebcamError> {
-        #[cfg(feature = "local_cameras")]
-        {
-            info!("Listing available cameras");
-            
-            match nokhwa::query_devices() {
-                Ok(devices) => {
-                    let cameras: Vec<CameraInfo> = devices
-                        .into_iter()
-                        .enumerate()
-                        .map(|(index, device)| CameraInfo {
-                            index: index as u32,
-                            name: device.human_name().to_string(),
-                            description: device.description().to_string(),
-                            available: true,
-                        })
-                        .collect();
+        let has_other_cameras = self.synthetic.is_some()
+            || self.file_camera.is_some()
+            || !self.extra_backends.is_empty();
+        let mut cameras = match self.list_local_cameras() {
+            Ok(cameras) => cameras,
+            Err(WebcamError::LocalCamerasNotSupported) if has_other_cameras => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        for backend in &self.extra_backends {
+            match backend.list_devices() {
+                Ok(devices) => cameras.extend(devices),
+                Err(e) => warn!("Failed to list {} cameras: {}", backend.name(), e),
+            }
+        }
+        if self.synthetic.is_some() {
+            cameras.push(SyntheticCamera::info());
+        }
+        if let Some(file_camera) = &self.file_camera {
+            cameras.push(file_camera.info());
+        }
+        Ok(cameras)
+    }
 
-                    info!("Found {} cameras", cameras.len());
-                    Ok(cameras)
-                }
-                Err(e) => {
-                    warn!("Failed to query devices: {}", e);
-                    Ok(vec![]) // Return empty list instead of error
+    /// List all available cameras, re-enumerating devices even if a recent
+    /// enumeration is cached
+    pub fn refresh_cameras(&self) -> Result<Vec<CameraInfo>, WebcamError> {
+        self.invalidate_device_cache();
+        self.list_cameras()
+    }
+
+    /// Forget cached devices so the next listing re-enumerates, e.g. after
+    /// a camera disappeared
+    pub fn invalidate_device_cache(&self) {
+        if let Ok(mut cache) = self.device_cache.lock() {
+            *cache = None;
+        }
+    }
+
+    /// Devices from the default backend, enumerated at most once per
+    /// `device_cache_ttl`
+    fn cached_devices(&self) -> Result<Vec<CameraInfo>, WebcamError> {
+        let mut cache = match self.device_cache.lock() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some((enumerated, devices)) = cache.as_ref() {
+            if enumerated.elapsed() < self.device_cache_ttl {
+                return Ok(devices.clone());
+            }
+        }
+
+        let devices = self.backend.list_devices()?;
+        if !self.device_cache_ttl.is_zero() {
+            *cache = Some((Instant::now(), devices.clone()));
+        }
+        Ok(devices)
+    }
+
+    /// Cameras found by the capture backend, labelled with the backend
+    /// each will actually be opened with
+    fn list_local_cameras(&self) -> Result<Vec<CameraInfo>, WebcamError> {
+        let mut cameras = self.cached_devices()?;
+        for camera in &mut cameras {
+            camera.backend = self.backend_name(camera.index).to_string();
+        }
+        Ok(cameras)
+    }
+
+    /// Open a specific camera by index, at the resolution from its profile
+    pub fn open_camera(&mut self, index: u32) -> Result<(), WebcamError> {
+        let profile = self.resolved_profile(index);
+        self.open_camera_at(index, profile.width.zip(profile.height))
+    }
+
+    /// Open a camera, requesting a resolution if given. If the camera
+    /// rejects the resolution it is opened at its highest resolution and
+    /// frames are scaled in software.
+    fn open_camera_at(&mut self, index: u32, resolution: Option<(u32, u32)>) -> Result<(), WebcamError> {
+        // Fake cameras have nothing to open
+        if self.is_fake(index) {
+            self.close_camera();
+            self.current_index = Some(index);
+            self.current_resolution = resolution;
+            self.current_opened_at = Some(chrono::Utc::now().to_rfc3339());
+            return Ok(());
+        }
+
+        info!("Opening camera {}", index);
+        self.close_camera();
+
+        match self.backend_for(index)?.open(index, resolution) {
+            Ok(()) => {
+                info!("Successfully opened camera {}", index);
+                self.current_index = Some(index);
+                self.current_resolution = resolution;
+                self.current_device_id = self.backend_for(index)?.device_id();
+                self.current_opened_at = Some(chrono::Utc::now().to_rfc3339());
+                Ok(())
+            }
+            Err(e) if is_busy_error(&e.to_string()) => {
+                warn!("Camera {} is busy: {}", index, e);
+                Err(WebcamError::DeviceBusy { index, holder: device_holder(index) })
+            }
+            Err(e) => {
+                // Report a missing device rather than a backend-specific error
+                if matches!(self.refresh_cameras(), Ok(cameras) if cameras.is_empty()) {
+                    return Err(WebcamError::NoCamerasAvailable);
                 }
+                Err(e)
             }
         }
-        
-        #[cfg(not(feature = "local_cameras"))]
-        {
-            warn!("Local camera support not compiled in");
-            Err(WebcamError::LocalCamerasNotSupported)
+    }
+
+    /// Capture an image from the current or specified camera
+    pub fn capture_image(&mut self, camera_index: Option<u32>) -> Result<CaptureResult, WebcamError> {
+        self.capture_image_with_options(camera_index, &CaptureOptions::default())
+    }
+
+    /// Capture an image, applying the given processing options before encoding
+    pub fn capture_image_with_options(
+        &mut self,
+        camera_index: Option<u32>,
+        options: &CaptureOptions,
+    ) -> Result<CaptureResult, WebcamError> {
+        let frame = self.capture_frame(camera_index, options)?;
+        let (width, height, index) = (frame.image.width(), frame.image.height(), frame.camera_index);
+        let exif = options.embed_exif.then(|| frame.exif_metadata(options.exif_comment.clone()));
+        let result = frame.encode_with_exif(exif.as_ref())?;
+
+        debug!("Encoded {}x{} frame from camera {} ({} JPEG bytes)",
+               width, height, index, result.image_bytes.len());
+        Ok(result)
+    }
+
+    /// Capture a decoded frame with processing options applied, without encoding it
+    pub fn capture_frame(
+        &mut self,
+        camera_index: Option<u32>,
+        options: &CaptureOptions,
+    ) -> Result<CapturedFrame, WebcamError> {
+        let target_index = camera_index.unwrap_or(self.default_camera);
+        let options = &CaptureOptions {
+            max_width: options.max_width.or(self.max_width),
+            ..options.with_profile(&self.resolved_profile(target_index))
+        };
+
+        if let Err(e) = &self.privacy_masks {
+            return Err(WebcamError::PrivacyMaskConfig(e.clone()));
         }
+
+        let mut raw = if let Some(synthetic) = self.synthetic.as_mut().filter(|_| target_index == SYNTHETIC_CAMERA_INDEX) {
+            synthetic.grab(options)
+        } else if let Some(file_camera) = self.file_camera.as_mut().filter(|_| target_index == FILE_CAMERA_INDEX) {
+            file_camera.grab()?
+        } else {
+            self.grab_local_frame(target_index, options)?
+        };
+        debug!("Captured frame: {}x{}", raw.image.width(), raw.image.height());
+
+        // Privacy masks come first so nothing downstream sees the masked areas
+        let masked_regions = self.privacy_masks.as_ref()
+            .map(|masks| masks.apply(target_index, &mut raw.image))
+            .unwrap_or(0);
+
+        let mut captured = options.process(raw.image, target_index, raw.hardware_white_balance)?;
+        captured.privacy_masked_regions = masked_regions;
+        captured.camera_name = Some(raw.camera_name);
+        captured.backend = Some(raw.backend);
+        if self.current_index == Some(target_index) {
+            self.current_frames += 1;
+        }
+
+        info!("Successfully captured image: {}x{} from camera {}",
+                       captured.image.width(), captured.image.height(), target_index);
+        Ok(captured)
     }
 
-    /// Open a specific camera by index
-    pub fn open_camera(&mut self, _index: u32) -> Result<(), WebcamError> {
-        #[cfg(feature = "local_cameras")]
-        {
-            info!("Opening camera {}", index);
-
-            // Close current camera if open
-            if self.current_camera.is_some() {
-                debug!("Closing current camera");
-                self.current_camera = None;
-                self.current_index = None;
-            }
-
-            // Try to open the requested camera
-            let camera_index = CameraIndex::Index(index);
-            let requested_format = RequestedFormat::new::<RgbFormat>(
-                RequestedFormatType::AbsoluteHighestResolution,
-            );
-
-            match Camera::new(camera_index, requested_format) {
-                Ok(camera) => {
-                    info!("Successfully opened camera {}", index);
-                    self.current_camera = Some(camera);
-                    self.current_index = Some(index);
-                    Ok(())
+    /// Grab and decode one frame from a backend camera, opening it first if
+    /// needed. If the open camera fails it is checked for having been
+    /// unplugged, and the grab is retried once if it has reappeared.
+    fn grab_local_frame(&mut self, target_index: u32, options: &CaptureOptions) -> Result<RawFrame, WebcamError> {
+        self.ensure_open(target_index, options.resolution)?;
+        match self.grab_open_frame(target_index, options) {
+            Ok(raw) => Ok(raw),
+            Err(e) => {
+                let index = self.recover_camera(target_index, e)?;
+                self.ensure_open(index, options.resolution)?;
+                self.grab_open_frame(index, options)
+            }
+        }
+    }
+
+    /// After a grab from the open camera fails, drop the handle and
+    /// re-enumerate. Returns the index to retry at if the same device is
+    /// still (or again) connected, possibly renumbered; otherwise the error
+    /// to report.
+    fn recover_camera(&mut self, index: u32, error: WebcamError) -> Result<u32, WebcamError> {
+        let device_id = self.current_device_id.clone();
+        warn!("Camera {} failed ({}), checking whether it is still connected", index, error);
+        self.close_camera();
+
+        let cameras = self.refresh_cameras().unwrap_or_default();
+        match device_id {
+            Some(id) => match cameras.iter().find(|c| c.device_id.as_deref() == Some(id.as_str())) {
+                Some(camera) => {
+                    info!("Camera {} is connected as camera {}, reopening", index, camera.index);
+                    Ok(camera.index)
                 }
-                Err(e) => {
-                    error!("Failed to open camera {}: {}", index, e);
-                    Err(WebcamError::CameraOpen(e))
+                None => Err(WebcamError::CameraDisconnected { index }),
+            },
+            // Without a stable id the device can't be recognised again
+            None if cameras.iter().any(|c| c.index == index) => Err(error),
+            None => Err(WebcamError::CameraDisconnected { index }),
+        }
+    }
+
+    /// Grab and decode one frame from the open backend camera
+    fn grab_open_frame(&mut self, target_index: u32, options: &CaptureOptions) -> Result<RawFrame, WebcamError> {
+        let backend = self.backend_for(target_index)?;
+
+        info!("Capturing frame from camera {} via {}", target_index, backend.name());
+
+        // Set a requested colour temperature on the camera; fall back to
+        // software correction if the control isn't supported
+        let hardware_white_balance = match options.white_balance {
+            Some(WhiteBalance::Kelvin(kelvin)) => {
+                match backend.set_control(CameraControl::WhiteBalance, kelvin as i64) {
+                    Ok(()) => {
+                        debug!("Set white balance to {}K on camera {}", kelvin, target_index);
+                        true
+                    }
+                    Err(e) => {
+                        debug!("White balance control unsupported on camera {}: {}", target_index, e);
+                        false
+                    }
                 }
             }
+            _ => false,
+        };
+
+        // Let exposure settle before the real capture
+        for _ in 0..options.warmup_frames.unwrap_or(0) {
+            backend.skip_frame()?;
         }
-        
-        #[cfg(not(feature = "local_cameras"))]
+
+        let image = backend.grab_frame()?;
+        let camera_name = backend.camera_name().unwrap_or_else(|| format!("Camera {}", target_index));
+
+        Ok(RawFrame { image, camera_name, backend: backend.name().to_string(), hardware_white_balance })
+    }
+
+    /// Open a backend camera unless it is already open at this resolution
+    fn ensure_open(&mut self, index: u32, resolution: Option<(u32, u32)>) -> Result<(), WebcamError> {
+        if !self.backend_for(index)?.is_open()
+            || self.current_index != Some(index)
+            || self.current_resolution != resolution
         {
-            error!("Local camera support not compiled in");
-            Err(WebcamError::LocalCamerasNotSupported)
+            self.open_camera_at(index, resolution)?;
         }
+        Ok(())
     }
 
-    /// Capture an image from the current or specified camera
-    pub fn capture_image(&mut self, _camera_index: Option<u32>) -> Result<CaptureResult, WebcamError> {
-        #[cfg(feature = "local_cameras")]
-        {
-            let target_index = camera_index.unwrap_or(0);
+    /// Capture `frames` frames, `interval` apart, from one camera. The camera is
+    /// opened at most once and reused for every frame.
+    pub fn capture_sequence(
+        &mut self,
+        camera_index: Option<u32>,
+        options: &CaptureOptions,
+        frames: u32,
+        interval: Duration,
+    ) -> Result<Vec<CapturedFrame>, WebcamError> {
+        let mut captured = Vec::with_capacity(frames as usize);
+        for i in 0..frames {
+            if i > 0 {
+                std::thread::sleep(interval);
+            }
+            captured.push(self.capture_frame(camera_index, options)?);
+        }
+        Ok(captured)
+    }
+
+    /// Capture a sequence like `capture_sequence`, running `encode` on each
+    /// frame on the rayon pool while the following frames are grabbed.
+    /// Results are in capture order. At most one frame per pool thread (plus
+    /// one) waits for encoding at a time, so long sequences of large frames
+    /// don't pile up in memory.
+    pub fn capture_sequence_with<T, F>(
+        &mut self,
+        camera_index: Option<u32>,
+        options: &CaptureOptions,
+        frames: u32,
+        interval: Duration,
+        encode: F,
+    ) -> Result<Vec<T>, WebcamError>
+    where
+        T: Send + 'static,
+        F: Fn(CapturedFrame) -> Result<T, WebcamError> + Send + Sync + 'static,
+    {
+        self.capture_sequence_cancellable(camera_index, options, frames, interval, &CancellationToken::new(), encode)
+    }
+
+    /// `capture_sequence_with` that stops within `CANCEL_POLL_INTERVAL` of
+    /// `cancel` firing, closes the camera and returns `Cancelled`
+    pub fn capture_sequence_cancellable<T, F>(
+        &mut self,
+        camera_index: Option<u32>,
+        options: &CaptureOptions,
+        frames: u32,
+        interval: Duration,
+        cancel: &CancellationToken,
+        encode: F,
+    ) -> Result<Vec<T>, WebcamError>
+    where
+        T: Send + 'static,
+        F: Fn(CapturedFrame) -> Result<T, WebcamError> + Send + Sync + 'static,
+    {
+        let result = encode_sequence(frames, interval, cancel, encode, || self.capture_frame(camera_index, options));
+        if matches!(result, Err(WebcamError::Cancelled)) {
+            self.close_camera();
+        }
+        result
+    }
+
+    /// `capture_sequence_cancellable` against the shared manager, locking it
+    /// for each frame only, so other tools can use the cameras during the
+    /// wait between frames
+    pub fn capture_sequence_shared<T, F>(
+        manager: &Mutex<WebcamManager>,
+        camera_index: Option<u32>,
+        options: &CaptureOptions,
+        frames: u32,
+        interval: Duration,
+        cancel: &CancellationToken,
+        encode: F,
+    ) -> Result<Vec<T>, WebcamError>
+    where
+        T: Send + 'static,
+        F: Fn(CapturedFrame) -> Result<T, WebcamError> + Send + Sync + 'static,
+    {
+        let lock = || manager.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let result = encode_sequence(frames, interval, cancel, encode, || lock().capture_frame(camera_index, options));
+        if matches!(result, Err(WebcamError::Cancelled)) {
+            lock().close_camera();
+        }
+        result
+    }
 
-            // Open camera if not already open or if different camera requested
-            if self.current_camera.is_none() || self.current_index != Some(target_index) {
-                self.open_camera(target_index)?;
+    /// Open each camera (or just `camera_index`), grab one frame, check it
+    /// isn't blank and time each step. Every camera opened here is closed
+    /// again, including the one that was open before the test.
+    pub fn self_test(&mut self, camera_index: Option<u32>) -> Result<Vec<SelfTestResult>, WebcamError> {
+        let cameras = self.list_cameras()?;
+        let targets: Vec<CameraInfo> = match camera_index {
+            Some(index) => {
+                let camera = cameras
+                    .into_iter()
+                    .find(|c| c.index == index)
+                    .ok_or(WebcamError::CameraNotFound { index })?;
+                vec![camera]
             }
+            None => cameras,
+        };
 
-            let camera = self.current_camera.as_mut()
-                .ok_or(WebcamError::CameraNotFound { index: target_index })?;
+        // Release the current handle so the device can be opened for testing
+        self.close_camera();
 
-            info!("Capturing frame from camera {}", target_index);
+        Ok(targets
+            .into_iter()
+            .map(|camera| self.test_camera(camera))
+            .collect())
+    }
+
+    fn test_camera(&mut self, camera: CameraInfo) -> SelfTestResult {
+        let mut result = SelfTestResult {
+            index: camera.index,
+            name: camera.name,
+            passed: false,
+            open_ms: None,
+            frame_ms: None,
+            width: None,
+            height: None,
+            mean_luminance: None,
+            error: None,
+        };
+
+        let started = std::time::Instant::now();
+        if let Err(e) = self.open_camera(camera.index) {
+            result.error = Some(e.to_string());
+            return result;
+        }
+        result.open_ms = Some(started.elapsed().as_millis() as u64);
+
+        let started = std::time::Instant::now();
+        let frame = self.capture_frame(Some(camera.index), &CaptureOptions::default());
+        result.frame_ms = Some(started.elapsed().as_millis() as u64);
+        self.close_camera();
+
+        match frame {
+            Ok(frame) => {
+                let mean = image_processing::mean_luminance(&frame.image);
+                result.width = Some(frame.image.width());
+                result.height = Some(frame.image.height());
+                result.mean_luminance = Some(mean);
+                if mean < SELF_TEST_BLACK_THRESHOLD {
+                    result.error = Some("Frame is all black".to_string());
+                } else if mean > SELF_TEST_WHITE_THRESHOLD {
+                    result.error = Some("Frame is all white".to_string());
+                } else {
+                    result.passed = true;
+                }
+            }
+            Err(e) => result.error = Some(e.to_string()),
+        }
+
+        debug!("Self-test camera {}: passed={} error={:?}", result.index, result.passed, result.error);
+        result
+    }
 
-            // Capture frame
-            let frame = camera.frame()
-                .context("Failed to capture frame")
-                .map_err(|e| WebcamError::CameraOpen(nokhwa::NokhwaError::GeneralError(e.to_string())))?;
+    /// Grab frames from one camera as fast as it delivers them for
+    /// `duration` and report the achieved rate. Frames are not decoded.
+    pub fn benchmark(&mut self, camera_index: Option<u32>, duration: Duration) -> Result<BenchmarkReport, WebcamError> {
+        self.benchmark_cancellable(camera_index, duration, &CancellationToken::new())
+    }
 
-            let width = frame.width();
-            let height = frame.height();
+    /// `benchmark` that stops once `cancel` fires, closes the camera and
+    /// returns `Cancelled`
+    pub fn benchmark_cancellable(
+        &mut self,
+        camera_index: Option<u32>,
+        duration: Duration,
+        cancel: &CancellationToken,
+    ) -> Result<BenchmarkReport, WebcamError> {
+        let target_index = camera_index.unwrap_or(self.default_camera);
+        if self.is_fake(target_index) {
+            return Err(WebcamError::InvalidOptions(format!("Camera {} is not a real device", target_index)));
+        }
+        let profile = self.resolved_profile(target_index);
+        self.ensure_open(target_index, profile.width.zip(profile.height))?;
 
-            debug!("Captured frame: {}x{}", width, height);
+        let backend = self.backend_for(target_index)?;
+        let format = backend.format().ok_or(WebcamError::CameraNotOpen)?;
+        info!("Benchmarking camera {} for {:?} at {:?}", target_index, duration, format);
 
-            // Convert to RGB image
-            let rgb_data = frame.into_rgb8();
-            let img = image::ImageBuffer::from_raw(width, height, rgb_data.into_raw())
-                .ok_or_else(|| WebcamError::ImageProcessing(image::ImageError::Parameter(
-                    image::error::ParameterError::from_kind(image::error::ParameterErrorKind::DimensionMismatch)
-                )))?;
+        let started = std::time::Instant::now();
+        let mut grabbed = Vec::new();
+        while started.elapsed() < duration {
+            if cancel.is_cancelled() {
+                info!("Benchmark of camera {} cancelled after {} frame(s)", target_index, grabbed.len());
+                self.close_camera();
+                return Err(WebcamError::Cancelled);
+            }
+            backend.skip_frame()?;
+            grabbed.push(started.elapsed());
+        }
 
-            // Encode as JPEG
-            let mut buffer = Cursor::new(Vec::new());
-            img.write_to(&mut buffer, ImageFormat::Jpeg)?;
-            let image_bytes = buffer.into_inner();
+        let intervals: Vec<f64> = grabbed
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).as_secs_f64() * 1000.0)
+            .collect();
+        let nominal_ms = match format.frame_rate {
+            0 => None,
+            fps => Some(1000.0 / fps as f64),
+        };
+        // An interval that spans several nominal frame periods means the
+        // frames in between were dropped
+        let dropped_frames = nominal_ms.map_or(0, |nominal| {
+            intervals
+                .iter()
+                .map(|interval| ((interval / nominal).round() as u64).saturating_sub(1))
+                .sum()
+        });
+        let span = match (grabbed.first(), grabbed.last()) {
+            (Some(first), Some(last)) => (*last - *first).as_secs_f64(),
+            _ => 0.0,
+        };
 
-            // Encode as base64
-            let image_data = general_purpose::STANDARD.encode(&image_bytes);
+        Ok(BenchmarkReport {
+            camera_index: target_index,
+            duration_ms: started.elapsed().as_millis() as u64,
+            frames: grabbed.len() as u32,
+            fps: if span > 0.0 { intervals.len() as f64 / span } else { 0.0 },
+            min_interval_ms: intervals.iter().cloned().reduce(f64::min),
+            avg_interval_ms: (!intervals.is_empty())
+                .then(|| intervals.iter().sum::<f64>() / intervals.len() as f64),
+            max_interval_ms: intervals.iter().cloned().reduce(f64::max),
+            dropped_frames,
+            width: format.width,
+            height: format.height,
+            pixel_format: format.pixel_format,
+            nominal_fps: format.frame_rate,
+        })
+    }
 
-            let result = CaptureResult {
-                image_data,
-                mime_type: "image/jpeg".to_string(),
-                width,
-                height,
-                timestamp: chrono::Utc::now().to_rfc3339(),
-                camera_index: target_index,
+    /// Run `f` against the shared manager on a worker thread and give up
+    /// after `timeout`, so a camera that stops responding can't block the
+    /// caller. The worker keeps the lock until the camera call returns.
+    pub fn with_timeout<T, F>(manager: &Arc<Mutex<WebcamManager>>, timeout: Duration, f: F) -> Result<T, WebcamError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut WebcamManager) -> Result<T, WebcamError> + Send + 'static,
+    {
+        let manager = Arc::clone(manager);
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = match manager.lock() {
+                Ok(mut manager) => f(&mut manager),
+                Err(poisoned) => f(&mut poisoned.into_inner()),
             };
+            let _ = tx.send(result);
+        });
+
+        rx.recv_timeout(timeout).map_err(|_| {
+            warn!("Camera operation timed out after {:?}", timeout);
+            WebcamError::Timeout { seconds: timeout.as_secs() }
+        })?
+    }
 
-            info!("Successfully captured image: {}x{} from camera {}", width, height, target_index);
-            Ok(result)
+    /// Release the currently open camera, if any
+    pub fn close_camera(&mut self) {
+        let backends = std::iter::once(&mut self.backend)
+            .chain(self.alternate_backends.values_mut())
+            .chain(self.extra_backends.iter_mut());
+        for backend in backends {
+            if backend.is_open() {
+                debug!("Closing camera {:?} ({})", self.current_index, backend.name());
+            }
+            backend.close();
         }
-        
-        #[cfg(not(feature = "local_cameras"))]
-        {
-            error!("Local camera support not compiled in");
-            Err(WebcamError::LocalCamerasNotSupported)
+        self.current_index = None;
+        self.current_resolution = None;
+        self.current_device_id = None;
+        self.current_opened_at = None;
+        self.current_frames = 0;
+    }
+
+    /// Details of the currently open camera, or `None` if no camera is open
+    pub fn get_current_camera_info(&self) -> Option<CurrentCameraInfo> {
+        let index = self.current_index?;
+        let opened_at = self.current_opened_at.clone().unwrap_or_default();
+
+        let (name, format) = if self.synthetic.is_some() && index == SYNTHETIC_CAMERA_INDEX {
+            (SYNTHETIC_CAMERA_NAME.to_string(), None)
+        } else if let Some(file_camera) = self.file_camera.as_ref().filter(|_| index == FILE_CAMERA_INDEX) {
+            (file_camera.info().name, None)
+        } else {
+            let backend = std::iter::once(&self.backend)
+                .chain(self.alternate_backends.values())
+                .chain(self.extra_backends.iter())
+                .find(|backend| backend.is_open())?;
+            let name = backend.camera_name().unwrap_or_else(|| format!("Camera {}", index));
+            (name, backend.format())
+        };
+
+        Some(CurrentCameraInfo {
+            index,
+            name,
+            resolution: format.as_ref().map(|f| (f.width, f.height)).or(self.current_resolution),
+            frame_format: format.map(|f| f.pixel_format),
+            opened_at,
+            frames_captured: self.current_frames,
+        })
+    }
+}
+
+/// The camera `WebcamManager` currently has open
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CurrentCameraInfo {
+    pub index: u32,
+    pub name: String,
+    /// Negotiated resolution, or the requested one if the camera doesn't
+    /// report it; `None` if neither is known
+    pub resolution: Option<(u32, u32)>,
+    /// Pixel format negotiated with the camera, e.g. `MJPEG`
+    pub frame_format: Option<String>,
+    /// When the camera was opened (RFC 3339)
+    pub opened_at: String,
+    /// Frames captured since the camera was opened
+    pub frames_captured: u64,
+}
+
+/// A frame straight from a camera, before masks and processing
+struct RawFrame {
+    image: RgbImage,
+    camera_name: String,
+    backend: String,
+    /// Whether a requested white balance was set on the camera itself
+    hardware_white_balance: bool,
+}
+
+/// Index the synthetic test camera is listed under, clear of real devices
+pub const SYNTHETIC_CAMERA_INDEX: u32 = 100;
+/// Name the synthetic test camera is listed under
+pub const SYNTHETIC_CAMERA_NAME: &str = "Synthetic Test Camera";
+/// Resolution of synthetic frames when none is requested
+pub const SYNTHETIC_DEFAULT_RESOLUTION: (u32, u32) = (640, 480);
+/// Frame rate the synthetic timestamps advance at
+const SYNTHETIC_FPS: u64 = 30;
+
+/// Fake camera for tests and CI, enabled by the `test_camera` feature.
+/// Frames are colour bars with the frame counter and a timestamp derived
+/// from it burned in, so the same sequence of captures always produces the
+/// same frames.
+#[derive(Debug, Default)]
+pub struct SyntheticCamera {
+    frames: u64,
+}
+
+impl SyntheticCamera {
+    pub fn info() -> CameraInfo {
+        CameraInfo {
+            index: SYNTHETIC_CAMERA_INDEX,
+            name: SYNTHETIC_CAMERA_NAME.to_string(),
+            description: "Deterministic colour bars with frame counter (test_camera feature)".to_string(),
+            available: true,
+            fake: true,
+            device_id: None,
+            backend: "synthetic".to_string(),
+        }
+    }
+
+    /// Render the next frame at `width` x `height`
+    pub fn next_frame(&mut self, width: u32, height: u32) -> RgbImage {
+        self.frames += 1;
+        let mut image = image_processing::color_bars(width, height);
+
+        // Frame counter and elapsed time on a black strip along the bottom
+        let elapsed_ms = (self.frames - 1) * 1000 / SYNTHETIC_FPS;
+        let text = format!(
+            "#{:06} {:02}:{:02}:{:02}.{:03}",
+            self.frames,
+            elapsed_ms / 3_600_000,
+            elapsed_ms / 60_000 % 60,
+            elapsed_ms / 1000 % 60,
+            elapsed_ms % 1000
+        );
+        let scale = (height / 96).max(1);
+        let strip_height = 7 * scale;
+        let strip_y = height.saturating_sub(strip_height);
+        image_processing::fill_rect(
+            &mut image,
+            &CropRect { x: 0, y: strip_y, width, height: strip_height },
+            [0, 0, 0],
+        );
+        image_processing::draw_text(&mut image, scale, strip_y + scale, scale, &text, [255, 255, 255]);
+        image
+    }
+
+    fn grab(&mut self, options: &CaptureOptions) -> RawFrame {
+        let (width, height) = options.resolution.unwrap_or(SYNTHETIC_DEFAULT_RESOLUTION);
+        // Warmup frames are skipped, as they would be on a real camera
+        self.frames += options.warmup_frames.unwrap_or(0) as u64;
+        RawFrame {
+            image: self.next_frame(width, height),
+            camera_name: SYNTHETIC_CAMERA_NAME.to_string(),
+            backend: "synthetic".to_string(),
+            hardware_white_balance: false,
+        }
+    }
+}
+
+/// Index the file-backed fake camera is listed under
+pub const FILE_CAMERA_INDEX: u32 = 101;
+
+/// Fake camera that replays image files from `MCP_WEBCAM_FAKE_SOURCE`,
+/// either a single image or a directory of images. Each capture returns
+/// the next file (in name order, wrapping around), decoded and then
+/// processed like a real frame.
+#[derive(Debug)]
+pub struct FileCamera {
+    source: PathBuf,
+    files: Vec<PathBuf>,
+    next: usize,
+}
+
+impl FileCamera {
+    /// Load the source named by `MCP_WEBCAM_FAKE_SOURCE`. An invalid or
+    /// empty source is logged and ignored.
+    pub fn from_env() -> Option<Self> {
+        let source = std::env::var_os("MCP_WEBCAM_FAKE_SOURCE")?;
+        match Self::new(&source) {
+            Ok(camera) => {
+                info!("Fake camera {} replays {} image(s) from {}",
+                               FILE_CAMERA_INDEX, camera.files.len(), camera.source.display());
+                Some(camera)
+            }
+            Err(e) => {
+                error!("Ignoring MCP_WEBCAM_FAKE_SOURCE: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn new(source: impl AsRef<Path>) -> Result<Self, String> {
+        let source = source.as_ref().to_path_buf();
+        let files = if source.is_dir() {
+            let mut files: Vec<PathBuf> = std::fs::read_dir(&source)
+                .map_err(|e| format!("{}: {}", source.display(), e))?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.is_file() && image::ImageFormat::from_path(path).is_ok())
+                .collect();
+            files.sort();
+            files
+        } else if source.is_file() {
+            vec![source.clone()]
+        } else {
+            return Err(format!("{} does not exist", source.display()));
+        };
+
+        if files.is_empty() {
+            return Err(format!("no images found in {}", source.display()));
+        }
+        Ok(Self { source, files, next: 0 })
+    }
+
+    pub fn info(&self) -> CameraInfo {
+        CameraInfo {
+            index: FILE_CAMERA_INDEX,
+            name: format!("Fake Camera ({})", self.source.display()),
+            description: format!("FAKE: replays {} image file(s) from MCP_WEBCAM_FAKE_SOURCE", self.files.len()),
+            available: true,
+            fake: true,
+            device_id: None,
+            backend: "file".to_string(),
+        }
+    }
+
+    fn grab(&mut self) -> Result<RawFrame, WebcamError> {
+        let path = &self.files[self.next];
+        self.next = (self.next + 1) % self.files.len();
+        debug!("Fake camera reading {}", path.display());
+
+        Ok(RawFrame {
+            image: image::open(path)?.into_rgb8(),
+            camera_name: self.info().name,
+            backend: "file".to_string(),
+            hardware_white_balance: false,
+        })
+    }
+}
+
+/// Outcome of testing one camera with `WebcamManager::self_test`
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestResult {
+    pub index: u32,
+    pub name: String,
+    pub passed: bool,
+    pub open_ms: Option<u64>,
+    pub frame_ms: Option<u64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub mean_luminance: Option<f32>,
+    pub error: Option<String>,
+}
+
+/// Frame rate measured by `WebcamManager::benchmark`
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub camera_index: u32,
+    pub duration_ms: u64,
+    pub frames: u32,
+    pub fps: f64,
+    pub min_interval_ms: Option<f64>,
+    pub avg_interval_ms: Option<f64>,
+    pub max_interval_ms: Option<f64>,
+    pub dropped_frames: u64,
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: String,
+    pub nominal_fps: u32,
+}
+
+/// How often waits in cancellable captures check their token
+pub const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Grab `frames` frames with `grab`, `interval` apart, running `encode` on
+/// each on the rayon pool while the following frames are grabbed. Results
+/// are in capture order. At most one frame per pool thread (plus one) waits
+/// for encoding at a time, so long sequences of large frames don't pile up
+/// in memory. Returns `Cancelled` if `cancel` fires before the last grab.
+fn encode_sequence<T, F>(
+    frames: u32,
+    interval: Duration,
+    cancel: &CancellationToken,
+    encode: F,
+    mut grab: impl FnMut() -> Result<CapturedFrame, WebcamError>,
+) -> Result<Vec<T>, WebcamError>
+where
+    T: Send + 'static,
+    F: Fn(CapturedFrame) -> Result<T, WebcamError> + Send + Sync + 'static,
+{
+    let encode = Arc::new(encode);
+    let max_in_flight = rayon::current_num_threads() + 1;
+    let (tx, rx) = mpsc::channel();
+    let mut results: Vec<Option<T>> = (0..frames).map(|_| None).collect();
+    let mut in_flight = 0;
+
+    let receive = |results: &mut Vec<Option<T>>| -> Result<(), WebcamError> {
+        let (i, encoded): (usize, Result<T, WebcamError>) = rx
+            .recv()
+            .map_err(|_| WebcamError::InvalidOptions("Frame encoder stopped".to_string()))?;
+        results[i] = Some(encoded?);
+        Ok(())
+    };
+
+    for i in 0..frames as usize {
+        let cancelled = if i > 0 { sleep_unless_cancelled(interval, cancel) } else { cancel.is_cancelled() };
+        if cancelled {
+            info!("Sequence cancelled after {} of {} frame(s)", i, frames);
+            return Err(WebcamError::Cancelled);
+        }
+        while in_flight >= max_in_flight {
+            receive(&mut results)?;
+            in_flight -= 1;
+        }
+
+        let frame = grab()?;
+        let (tx, encode) = (tx.clone(), Arc::clone(&encode));
+        rayon::spawn(move || {
+            let _ = tx.send((i, encode(frame)));
+        });
+        in_flight += 1;
+    }
+    while in_flight > 0 {
+        receive(&mut results)?;
+        in_flight -= 1;
+    }
+
+    Ok(results.into_iter().flatten().collect())
+}
+
+/// Sleep for `duration` in short steps; returns true, early, if `cancel` fires
+pub(crate) fn sleep_unless_cancelled(duration: Duration, cancel: &CancellationToken) -> bool {
+    let deadline = Instant::now() + duration;
+    loop {
+        if cancel.is_cancelled() {
+            return true;
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return false;
+        }
+        std::thread::sleep(remaining.min(CANCEL_POLL_INTERVAL));
+    }
+}
+
+/// Default time a device enumeration is reused for
+pub const DEFAULT_DEVICE_CACHE_TTL_SECS: u64 = 5;
+
+/// Device cache lifetime from `MCP_WEBCAM_DEVICE_CACHE_SECS`, or the default
+fn device_cache_ttl() -> Duration {
+    let seconds = std::env::var("MCP_WEBCAM_DEVICE_CACHE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_DEVICE_CACHE_TTL_SECS);
+    Duration::from_secs(seconds)
+}
+
+/// Default time allowed for a camera operation before it is abandoned
+pub const DEFAULT_CAPTURE_TIMEOUT_SECS: u64 = 30;
+
+/// Capture timeout from `MCP_WEBCAM_CAPTURE_TIMEOUT_SECS`, or the default
+pub fn capture_timeout() -> Duration {
+    let seconds = std::env::var("MCP_WEBCAM_CAPTURE_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_CAPTURE_TIMEOUT_SECS);
+    Duration::from_secs(seconds)
+}
+
+/// Mean luminance below which a test frame counts as all black
+const SELF_TEST_BLACK_THRESHOLD: f32 = 5.0;
+/// Mean luminance above which a test frame counts as all white
+const SELF_TEST_WHITE_THRESHOLD: f32 = 250.0;
+
+/// Whether a backend error means another application has the camera open.
+/// Backends only report this as text, which varies by platform and language.
+fn is_busy_error(message: &str) -> bool {
+    const PATTERNS: &[&str] = &[
+        // EBUSY on Linux and macOS
+        "busy",
+        "in use",
+        "being used",
+        // Media Foundation's "hardware in use" on Windows
+        "0xc00d3704",
+        // Localised Windows messages
+        "使用中",
+        "正在使用",
+    ];
+    let message = message.to_lowercase();
+    PATTERNS.iter().any(|pattern| message.contains(pattern))
+}
+
+/// Name and pid of a process holding `/dev/video<index>` open, found by
+/// scanning `/proc/*/fd`. Processes we may not inspect are skipped.
+#[cfg(target_os = "linux")]
+fn device_holder(index: u32) -> Option<String> {
+    let device = PathBuf::from(format!("/dev/video{}", index));
+    let own_pid = std::process::id().to_string();
+
+    std::fs::read_dir("/proc").ok()?.flatten().find_map(|entry| {
+        let pid = entry.file_name().into_string().ok()?;
+        if !pid.bytes().all(|b| b.is_ascii_digit()) || pid == own_pid {
+            return None;
+        }
+        let holds_device = std::fs::read_dir(entry.path().join("fd"))
+            .ok()?
+            .flatten()
+            .any(|fd| std::fs::read_link(fd.path()).is_ok_and(|target| target == device));
+        if !holds_device {
+            return None;
+        }
+        let name = std::fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+        Some(format!("{} (pid {})", name.trim(), pid))
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn device_holder(_index: u32) -> Option<String> {
+    None
+}
+
+/// Camera profiles from the config file, overridden per camera by
+/// `MCP_WEBCAM_CAMERA_<index>` environment variables. Invalid entries are
+/// logged and skipped.
+fn load_camera_profiles() -> HashMap<u32, CameraProfile> {
+    let mut profiles = match ConfigFile::load().and_then(|config| config.camera_profiles()) {
+        Ok(profiles) => profiles,
+        Err(e) => {
+            error!("Ignoring camera profiles from config file: {}", e);
+            HashMap::new()
+        }
+    };
+
+    for (key, value) in std::env::vars() {
+        let index = match key.strip_prefix("MCP_WEBCAM_CAMERA_").and_then(|i| i.parse::<u32>().ok()) {
+            Some(index) => index,
+            None => continue,
+        };
+        match CameraProfile::parse_env(&value) {
+            Ok(profile) => {
+                profiles.insert(index, profile);
+            }
+            Err(e) => error!("Ignoring {}: {}", key, e),
         }
     }
 
-    /// Get information about the currently open camera
-    pub fn get_current_camera_info(&self) -> Option<u32> {
-        self.current_index
+    if !profiles.is_empty() {
+        info!("Loaded default settings for {} camera(s)", profiles.len());
     }
+    profiles
 }
 
 impl Default for WebcamManager {
@@ -214,4 +1900,426 @@ impl Default for WebcamManager {
     }
 }
 
+/// Limits enforced on the capture output directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub max_files: usize,
+    pub max_age_secs: u64,
+    pub max_total_bytes: u64,
+    pub cleanup_interval_secs: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_files: 500,
+            max_age_secs: 7 * 24 * 60 * 60,
+            max_total_bytes: 1024 * 1024 * 1024,
+            cleanup_interval_secs: 300,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Read overrides from `MCP_WEBCAM_RETENTION_MAX_FILES`,
+    /// `MCP_WEBCAM_RETENTION_MAX_AGE_SECS`, `MCP_WEBCAM_RETENTION_MAX_BYTES`
+    /// and `MCP_WEBCAM_CLEANUP_INTERVAL_SECS`
+    pub fn from_env() -> Self {
+        fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        }
+
+        let defaults = Self::default();
+        Self {
+            max_files: env_or("MCP_WEBCAM_RETENTION_MAX_FILES", defaults.max_files),
+            max_age_secs: env_or("MCP_WEBCAM_RETENTION_MAX_AGE_SECS", defaults.max_age_secs),
+            max_total_bytes: env_or("MCP_WEBCAM_RETENTION_MAX_BYTES", defaults.max_total_bytes),
+            cleanup_interval_secs: env_or("MCP_WEBCAM_CLEANUP_INTERVAL_SECS", defaults.cleanup_interval_secs),
+        }
+    }
+}
+
+/// Current usage of the capture output directory
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageUsage {
+    pub file_count: usize,
+    pub total_bytes: u64,
+    pub oldest_file: Option<String>,
+    pub newest_file: Option<String>,
+}
+
+/// Files removed by a cleanup pass
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CleanupReport {
+    pub removed_files: usize,
+    pub removed_bytes: u64,
+}
+
+/// Prefixes of the file names the store's writers use: captures and
+/// capture resources, clips and webcam exports. `usage` and `cleanup` only
+/// see files named with one of these, so anything else in the directory is
+/// left alone.
+pub const STORE_FILE_PREFIXES: &[&str] = &["capture_", "clip_", "webcams_"];
+
+/// Directory that every file-writing feature saves into, with a retention
+/// policy enforced by `cleanup` (periodically via `spawn_cleanup_task`)
+#[derive(Debug, Clone)]
+pub struct CaptureStore {
+    root: PathBuf,
+    policy: RetentionPolicy,
+}
+
+impl CaptureStore {
+    pub fn new(root: impl Into<PathBuf>, policy: RetentionPolicy) -> Self {
+        Self {
+            root: root.into(),
+            policy,
+        }
+    }
+
+    /// Root the store at `MCP_WEBCAM_DATA_DIR`, defaulting to
+    /// `<temp dir>/mcp-webcam`
+    pub fn from_env() -> Self {
+        let root = std::env::var("MCP_WEBCAM_DATA_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("mcp-webcam"));
+        Self::new(root, RetentionPolicy::from_env())
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Directory for state kept across restarts, such as the remote webcam
+    /// database. `cleanup` only removes files directly in the root, so
+    /// nothing in here is subject to the retention policy.
+    pub fn state_dir(&self) -> PathBuf {
+        self.root.join("state")
+    }
+
+    pub fn policy(&self) -> &RetentionPolicy {
+        &self.policy
+    }
+
+    /// Write `bytes` to a new file named `<prefix>_<timestamp>_<id>.<extension>`.
+    /// `prefix` should be one of `STORE_FILE_PREFIXES` without its `_`, or
+    /// retention won't apply to the file.
+    pub fn save(&self, prefix: &str, extension: &str, bytes: &[u8]) -> Result<PathBuf, WebcamError> {
+        let file_name = format!(
+            "{}_{}_{}.{}",
+            prefix,
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8],
+            extension
+        );
+        self.save_as(&file_name, bytes)
+    }
+
+    /// Write `bytes` to a file with the given name in the store, replacing
+    /// any existing file of that name
+    pub fn save_as(&self, file_name: &str, bytes: &[u8]) -> Result<PathBuf, WebcamError> {
+        let path = self.path_of(file_name)?;
+        std::fs::create_dir_all(&self.root)?;
+        std::fs::write(&path, bytes)?;
+
+        debug!("Saved {} bytes to {}", bytes.len(), path.display());
+        Ok(path)
+    }
+
+    /// Write `bytes` to a new file with the given name in the store,
+    /// refusing a name that's already taken
+    pub fn save_new(&self, file_name: &str, bytes: &[u8]) -> Result<PathBuf, WebcamError> {
+        let path = self.path_of(file_name)?;
+        std::fs::create_dir_all(&self.root)?;
+        let mut file = match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(WebcamError::InvalidOptions(format!("A file named '{}' already exists", file_name)));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        std::io::Write::write_all(&mut file, bytes)?;
+
+        debug!("Saved {} bytes to {}", bytes.len(), path.display());
+        Ok(path)
+    }
+
+    /// Read a file previously written to the store. Returns `None` if it
+    /// doesn't exist, e.g. because retention cleanup removed it.
+    pub fn load(&self, file_name: &str) -> Result<Option<Vec<u8>>, WebcamError> {
+        match std::fs::read(self.path_of(file_name)?) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Path of a file in the store; names that could escape the store
+    /// directory are rejected
+    fn path_of(&self, file_name: &str) -> Result<PathBuf, WebcamError> {
+        let valid = !file_name.is_empty()
+            && !file_name.starts_with('.')
+            && file_name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if !valid {
+            return Err(WebcamError::InvalidOptions(format!("Invalid file name '{}'", file_name)));
+        }
+        Ok(self.root.join(file_name))
+    }
+
+    /// Whether `file_name` is named like a file the store wrote
+    fn owns(file_name: &str) -> bool {
+        STORE_FILE_PREFIXES.iter().any(|prefix| file_name.starts_with(prefix))
+    }
+
+    /// Regular files in the store that it wrote, oldest first
+    fn files(&self) -> Result<Vec<(PathBuf, u64, SystemTime)>, WebcamError> {
+        let mut files = Vec::new();
+        let entries = match std::fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(files),
+            Err(e) => return Err(e.into()),
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_name().to_str().is_some_and(Self::owns) {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                files.push((entry.path(), metadata.len(), modified));
+            }
+        }
+
+        files.sort_by_key(|(_, _, modified)| *modified);
+        Ok(files)
+    }
+
+    pub fn usage(&self) -> Result<StorageUsage, WebcamError> {
+        let files = self.files()?;
+        let name = |path: &PathBuf| path.file_name().map(|n| n.to_string_lossy().into_owned());
+
+        Ok(StorageUsage {
+            file_count: files.len(),
+            total_bytes: files.iter().map(|(_, size, _)| size).sum(),
+            oldest_file: files.first().and_then(|(path, _, _)| name(path)),
+            newest_file: files.last().and_then(|(path, _, _)| name(path)),
+        })
+    }
+
+    /// Remove files that are too old, then the oldest files until both the
+    /// file-count and total-size limits are satisfied
+    pub fn cleanup(&self) -> Result<CleanupReport, WebcamError> {
+        let mut report = CleanupReport::default();
+        let max_age = Duration::from_secs(self.policy.max_age_secs);
+        let now = SystemTime::now();
+
+        let mut remaining = Vec::new();
+        for (path, size, modified) in self.files()? {
+            let expired = now.duration_since(modified).map(|age| age > max_age).unwrap_or(false);
+            if expired {
+                Self::remove(&path, size, &mut report);
+            } else {
+                remaining.push((path, size));
+            }
+        }
+
+        let mut total: u64 = remaining.iter().map(|(_, size)| size).sum();
+        let mut count = remaining.len();
+        for (path, size) in remaining {
+            if count <= self.policy.max_files && total <= self.policy.max_total_bytes {
+                break;
+            }
+            Self::remove(&path, size, &mut report);
+            count -= 1;
+            total = total.saturating_sub(size);
+        }
+
+        if report.removed_files > 0 {
+            info!("Storage cleanup removed {} file(s), {} bytes",
+                           report.removed_files, report.removed_bytes);
+        }
+        Ok(report)
+    }
+
+    fn remove(path: &Path, size: u64, report: &mut CleanupReport) {
+        match std::fs::remove_file(path) {
+            Ok(()) => {
+                report.removed_files += 1;
+                report.removed_bytes += size;
+            }
+            Err(e) => warn!("Failed to remove {}: {}", path.display(), e),
+        }
+    }
+
+    /// Run `cleanup` on the policy's interval for the lifetime of the runtime
+    pub fn spawn_cleanup_task(&self) -> tokio::task::JoinHandle<()> {
+        let store = self.clone();
+        let interval = Duration::from_secs(store.policy.cleanup_interval_secs.max(1));
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let store = store.clone();
+                match tokio::task::spawn_blocking(move || store.cleanup()).await {
+                    Ok(Err(e)) => warn!("Storage cleanup failed: {}", e),
+                    Err(e) => warn!("Storage cleanup task panicked: {}", e),
+                    Ok(Ok(_)) => {}
+                }
+            }
+        })
+    }
+}
+
 // Add chrono dependency for timestamps
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(name: &str, policy: RetentionPolicy) -> CaptureStore {
+        let root = std::env::temp_dir().join(format!("mcp-webcam-store-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        CaptureStore::new(root, policy)
+    }
+
+    #[test]
+    fn cleanup_only_removes_files_the_store_wrote() {
+        let store = store("owned", RetentionPolicy { max_files: 1, ..RetentionPolicy::default() });
+        let first = store.save("capture", "jpg", b"first").unwrap();
+        let second = store.save("clip", "gif", b"second").unwrap();
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        std::fs::File::options().write(true).open(&first).unwrap().set_modified(an_hour_ago).unwrap();
+        std::fs::write(store.root().join("notes.txt"), b"not a capture").unwrap();
+        std::fs::create_dir_all(store.state_dir()).unwrap();
+
+        let usage = store.usage().unwrap();
+        assert_eq!((usage.file_count, usage.total_bytes), (2, 11));
+
+        let report = store.cleanup().unwrap();
+        assert_eq!((report.removed_files, report.removed_bytes), (1, 5));
+        assert!(!first.exists());
+        assert!(second.exists());
+        assert_eq!(std::fs::read(store.root().join("notes.txt")).unwrap(), b"not a capture");
+        assert!(store.state_dir().is_dir());
+        let _ = std::fs::remove_dir_all(store.root());
+    }
+
+    #[test]
+    fn cleanup_removes_expired_files_then_the_oldest_over_the_size_limit() {
+        let policy = RetentionPolicy { max_age_secs: 600, max_total_bytes: 10, ..RetentionPolicy::default() };
+        let store = store("age-size", policy);
+        let backdate = |path: &Path, secs: u64| {
+            let modified = SystemTime::now() - Duration::from_secs(secs);
+            std::fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+        };
+        let expired = store.save("capture", "jpg", b"aaaa").unwrap();
+        backdate(&expired, 3600);
+        let oldest = store.save("clip", "gif", b"bbbbbb").unwrap();
+        backdate(&oldest, 120);
+        let newest = store.save("webcams", "csv", b"cccccc").unwrap();
+
+        let report = store.cleanup().unwrap();
+        assert_eq!((report.removed_files, report.removed_bytes), (2, 10));
+        assert!(!expired.exists() && !oldest.exists());
+        assert_eq!(store.load(newest.file_name().unwrap().to_str().unwrap()).unwrap().unwrap(), b"cccccc");
+        assert_eq!(store.cleanup().unwrap().removed_files, 0);
+        let _ = std::fs::remove_dir_all(store.root());
+    }
+
+    #[test]
+    fn file_names_cannot_leave_the_store() {
+        let store = store("names", RetentionPolicy::default());
+        for name in ["../capture_escape.jpg", "", ".hidden", "capture/x.jpg"] {
+            assert!(matches!(store.save_as(name, b"x"), Err(WebcamError::InvalidOptions(_))), "{:?}", name);
+        }
+        assert!(store.load("capture_missing.jpg").unwrap().is_none());
+        let _ = std::fs::remove_dir_all(store.root());
+    }
+
+    #[test]
+    fn new_files_never_replace_existing_ones() {
+        let store = store("new-files", RetentionPolicy::default());
+        store.save_as("webcams_a.csv", b"first").unwrap();
+        assert!(matches!(store.save_new("webcams_a.csv", b"second"), Err(WebcamError::InvalidOptions(_))));
+        assert_eq!(store.load("webcams_a.csv").unwrap().unwrap(), b"first");
+        store.save_new("webcams_b.csv", b"second").unwrap();
+        assert_eq!(store.load("webcams_b.csv").unwrap().unwrap(), b"second");
+        assert!(matches!(store.save_new("../webcams_c.csv", b"x"), Err(WebcamError::InvalidOptions(_))));
+        let _ = std::fs::remove_dir_all(store.root());
+    }
+
+    #[test]
+    fn profiles_parse_from_the_environment_form() {
+        let profile = CameraProfile::parse_env(" width=1280, height=720,rotation=180,quality=90,backend=opencv,").unwrap();
+        assert_eq!(
+            profile,
+            CameraProfile {
+                width: Some(1280),
+                height: Some(720),
+                rotation: Some(180),
+                quality: Some(90),
+                warmup_frames: None,
+                backend: Some("opencv".to_string()),
+            }
+        );
+        assert_eq!(CameraProfile::parse_env("").unwrap(), CameraProfile::default());
+
+        for (value, error) in [
+            ("width", "expected key=value"),
+            ("width=wide,height=720", "invalid value for width"),
+            ("zoom=2", "unknown setting 'zoom'"),
+            ("width=1280", "width and height must be set together"),
+            ("width=0,height=720", "must be positive"),
+            ("rotation=45", "rotation must be 0, 90, 180 or 270"),
+            ("quality=0", "quality must be between 1 and 100"),
+            ("quality=300", "quality must be between 1 and 100"),
+            ("backend=v4l", "backend must be one of"),
+        ] {
+            let message = CameraProfile::parse_env(value).unwrap_err();
+            assert!(message.contains(error), "{}: {}", value, message);
+        }
+    }
+
+    #[test]
+    fn profiles_fall_back_to_defaults_except_for_the_backend() {
+        let defaults = CameraProfile {
+            width: Some(640),
+            height: Some(480),
+            rotation: Some(90),
+            quality: Some(60),
+            warmup_frames: Some(3),
+            backend: Some("nokhwa".to_string()),
+        };
+        let camera = CameraProfile { rotation: Some(0), ..Default::default() };
+
+        let resolved = camera.with_fallback(&defaults);
+        assert_eq!((resolved.width, resolved.height), (Some(640), Some(480)));
+        assert_eq!(resolved.rotation, Some(0));
+        assert_eq!((resolved.quality, resolved.warmup_frames), (Some(60), Some(3)));
+        assert_eq!(resolved.backend, None);
+
+        let effective = CameraProfile::default().effective();
+        assert_eq!(effective.resolution, None);
+        assert_eq!((effective.rotation, effective.quality, effective.warmup_frames), (0, DEFAULT_JPEG_QUALITY, 0));
+        assert_eq!(resolved.effective().resolution, Some([640, 480]));
+    }
+
+    #[test]
+    fn call_options_take_precedence_over_the_profile() {
+        let profile = CameraProfile::parse_env("width=1280,height=720,rotation=180,quality=90").unwrap();
+        let call = CaptureOptions { rotation: Some(90), max_width: Some(320), ..Default::default() };
+
+        let options = call.with_profile(&profile);
+        assert_eq!(options.resolution, Some((1280, 720)));
+        assert_eq!(options.rotation, Some(90));
+        assert_eq!(options.quality, Some(90));
+        assert_eq!(options.warmup_frames, None);
+        assert_eq!(options.max_width, Some(320));
+    }
+}
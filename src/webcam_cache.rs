@@ -0,0 +1,376 @@
+//! Webcams found by `search_webcams`, kept so that later calls can list and
+//! refer to them without searching Shodan again, with what validating them
+//! found. Entries no search has found for the cache's TTL are dropped.
+//!
+//! A cache opened on a file survives restarts: it is loaded when the server
+//! starts and saved, by writing a temporary file and renaming it over the
+//! old one, after changes and at shutdown.
+
+use crate::shodan::{RemoteWebcam, SearchFilters};
+use crate::validate::WebcamValidation;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// How long a webcam stays cached after a search last found it
+pub const DEFAULT_WEBCAM_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Webcams `list_remote_webcams` returns per page unless asked otherwise
+pub const DEFAULT_LIST_LIMIT: usize = 50;
+/// Most webcams `list_remote_webcams` returns per page
+pub const MAX_LIST_LIMIT: usize = 500;
+/// Version of the webcam database file written by this release. Version 0
+/// is a bare array of webcams, as `search_webcams` lists them.
+pub const WEBCAM_DB_VERSION: u64 = 1;
+/// File name of the webcam database in the state directory
+pub const WEBCAM_DB_FILE: &str = "remote_webcams.json";
+
+#[derive(Error, Debug)]
+pub enum WebcamDbError {
+    #[error("Failed to read or write the webcam database: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Webcam database is corrupted: {0}")]
+    Corrupt(String),
+
+    #[error("Webcam database version {0} is newer than this release supports ({WEBCAM_DB_VERSION})")]
+    UnsupportedVersion(u64),
+}
+
+/// A webcam in the cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedWebcam {
+    #[serde(flatten)]
+    pub webcam: RemoteWebcam,
+    /// Shodan queries of the searches that found it
+    pub queries: Vec<String>,
+    /// When a search first found it
+    pub discovered_at: DateTime<Utc>,
+    /// When a search last found it; the TTL runs from here
+    pub last_found_at: DateTime<Utc>,
+    /// The latest validation, if it has been validated
+    pub validation: Option<WebcamValidation>,
+}
+
+/// Which cached webcams `WebcamCache::list` returns
+#[derive(Debug, Clone, Default)]
+pub struct CacheFilter {
+    /// Country, city and organization, as for searches
+    pub filters: SearchFilters,
+    /// Only webcams whose latest validation reached them
+    pub reachable_only: bool,
+    /// Only webcams whose product contains this, ignoring case
+    pub product: Option<String>,
+}
+
+impl CacheFilter {
+    pub fn matches(&self, cached: &CachedWebcam) -> bool {
+        if !self.filters.matches(&cached.webcam) {
+            return false;
+        }
+        if self.reachable_only && !cached.validation.as_ref().is_some_and(|v| v.reachable) {
+            return false;
+        }
+        if let Some(product) = &self.product {
+            let wanted = product.to_lowercase();
+            if !cached.webcam.product.as_deref().is_some_and(|p| p.to_lowercase().contains(&wanted)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// One page of `WebcamCache::list`
+#[derive(Debug, Clone)]
+pub struct CachePage {
+    pub webcams: Vec<CachedWebcam>,
+    /// Cached webcams matching the filter, on every page
+    pub total: usize,
+    /// Webcams matching the rest of the filter that lie outside its
+    /// radius or have no coordinates
+    pub outside_radius: usize,
+}
+
+/// Discovered webcams by `ip:port`
+#[derive(Debug)]
+pub struct WebcamCache {
+    entries: HashMap<String, CachedWebcam>,
+    /// Keys of the latest search's webcams, in its order
+    last_search: Vec<String>,
+    ttl: Duration,
+    /// Database file the cache is saved to, if any
+    path: Option<PathBuf>,
+    /// Whether there are changes `flush` has not saved yet
+    dirty: bool,
+}
+
+impl Default for WebcamCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Key of a webcam in the cache
+pub fn cache_key(ip: &str, port: u16) -> String {
+    format!("{}:{}", ip, port)
+}
+
+impl WebcamCache {
+    pub fn new() -> Self {
+        Self::with_ttl(DEFAULT_WEBCAM_CACHE_TTL)
+    }
+
+    /// Cache dropping webcams no search has found for `ttl`
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self { entries: HashMap::new(), last_search: Vec::new(), ttl, path: None, dirty: false }
+    }
+
+    /// Cache saved to the database at `path`, starting with the webcams
+    /// saved there. A corrupted file, or one from a newer release, is
+    /// moved aside and the cache starts empty; a file that can't be read
+    /// at all is left alone and the cache is not saved.
+    pub fn open(path: impl Into<PathBuf>, ttl: Duration) -> Self {
+        let path = path.into();
+        let mut cache = Self::with_ttl(ttl);
+        match read_db(&path) {
+            Ok(webcams) => {
+                for cached in webcams {
+                    cache.entries.insert(cache_key(&cached.webcam.ip, cached.webcam.port), cached);
+                }
+                let expired = cache.prune_expired();
+                info!("Loaded {} remote webcam(s) from {} ({} expired)", cache.len(), path.display(), expired);
+            }
+            Err(WebcamDbError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("No webcam database at {} yet", path.display());
+            }
+            Err(WebcamDbError::Io(e)) => {
+                warn!("Could not read {}, remote webcams will not be saved: {}", path.display(), e);
+                return cache;
+            }
+            Err(e) => {
+                let reason = match &e {
+                    WebcamDbError::UnsupportedVersion(version) => format!("v{}", version),
+                    _ => "corrupt".to_string(),
+                };
+                let backup = backup_path(&path, &reason);
+                match std::fs::rename(&path, &backup) {
+                    Ok(()) => warn!("{}; moved it to {} and starting with no remote webcams", e, backup.display()),
+                    Err(rename_error) => {
+                        warn!("{}, and it could not be moved aside ({}); remote webcams will not be saved", e, rename_error);
+                        return cache;
+                    }
+                }
+            }
+        }
+        cache.path = Some(path);
+        cache
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Database file the cache is saved to
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Save the cache to its database if it changed since the last save.
+    /// A failure is logged and the save tried again at the next flush.
+    pub fn flush(&mut self) {
+        let Some(path) = &self.path else { return };
+        if !self.dirty {
+            return;
+        }
+        match write_db(path, self.entries.values()) {
+            Ok(()) => {
+                debug!("Saved {} remote webcam(s) to {}", self.entries.len(), path.display());
+                self.dirty = false;
+            }
+            Err(e) => warn!("Could not save remote webcams to {}: {}", path.display(), e),
+        }
+    }
+
+    /// Add the webcams a search found with `queries`. Webcams already
+    /// cached are refreshed, keeping when they were discovered and their
+    /// validation.
+    pub fn record_search(&mut self, webcams: &[RemoteWebcam], queries: &[String]) {
+        let now = Utc::now();
+        self.last_search.clear();
+        for webcam in webcams {
+            let key = cache_key(&webcam.ip, webcam.port);
+            match self.entries.get_mut(&key) {
+                Some(cached) => {
+                    let previous = std::mem::replace(&mut cached.webcam, webcam.clone());
+                    cached.webcam.merge(previous);
+                    cached.last_found_at = now;
+                    for query in queries {
+                        if !cached.queries.contains(query) {
+                            cached.queries.push(query.clone());
+                        }
+                    }
+                }
+                None => {
+                    self.entries.insert(key.clone(), CachedWebcam {
+                        webcam: webcam.clone(),
+                        queries: queries.to_vec(),
+                        discovered_at: now,
+                        last_found_at: now,
+                        validation: None,
+                    });
+                }
+            }
+            if !self.last_search.contains(&key) {
+                self.last_search.push(key);
+            }
+        }
+        self.dirty |= !webcams.is_empty();
+        self.prune_expired();
+    }
+
+    /// Webcams of the latest search still cached, in its order
+    pub fn last_search(&self) -> Vec<RemoteWebcam> {
+        self.last_search
+            .iter()
+            .filter_map(|key| self.entries.get(key))
+            .map(|cached| cached.webcam.clone())
+            .collect()
+    }
+
+    /// Keep `validation` with the cached webcam at `ip:port`. Returns
+    /// whether it is cached; validations of other webcams are dropped.
+    pub fn record_validation(&mut self, ip: &str, port: u16, validation: WebcamValidation) -> bool {
+        match self.entries.get_mut(&cache_key(ip, port)) {
+            Some(cached) => {
+                cached.validation = Some(validation);
+                self.dirty = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cached webcams matching `filter`, from the `offset`th on and at most
+    /// `limit` of them: nearest first if the filter has a radius, else most
+    /// recently found first
+    pub fn list(&mut self, filter: &CacheFilter, offset: usize, limit: usize) -> CachePage {
+        self.prune_expired();
+        let anywhere = CacheFilter { filters: filter.filters.clone().with_near(None), ..filter.clone() };
+        let (mut matching, outside): (Vec<&CachedWebcam>, Vec<&CachedWebcam>) = self.entries
+            .values()
+            .filter(|cached| anywhere.matches(cached))
+            .partition(|cached| filter.filters.within_radius(&cached.webcam));
+        let key = |cached: &CachedWebcam| cache_key(&cached.webcam.ip, cached.webcam.port);
+        match filter.filters.near {
+            Some(near) => {
+                let distance = |cached: &CachedWebcam| near.distance_km(cached.webcam.location.as_ref()).unwrap_or(f64::INFINITY);
+                matching.sort_by(|a, b| distance(a).total_cmp(&distance(b)).then_with(|| key(a).cmp(&key(b))));
+            }
+            None => matching.sort_by(|a, b| b.last_found_at.cmp(&a.last_found_at).then_with(|| key(a).cmp(&key(b)))),
+        }
+        CachePage {
+            total: matching.len(),
+            outside_radius: outside.len(),
+            webcams: matching.into_iter().skip(offset).take(limit).cloned().collect(),
+        }
+    }
+
+    pub fn get(&self, ip: &str, port: u16) -> Option<&CachedWebcam> {
+        self.entries.get(&cache_key(ip, port))
+    }
+
+    /// Forget every webcam, returning how many there were
+    pub fn clear(&mut self) -> usize {
+        let cleared = self.entries.len();
+        self.entries.clear();
+        self.last_search.clear();
+        self.dirty |= cleared > 0;
+        cleared
+    }
+
+    /// Drop webcams no search has found for the TTL
+    pub fn prune_expired(&mut self) -> usize {
+        self.prune_expired_at(Utc::now())
+    }
+
+    /// `prune_expired` as of `now`
+    pub fn prune_expired_at(&mut self, now: DateTime<Utc>) -> usize {
+        // A TTL too long for chrono never runs out
+        let Ok(ttl) = chrono::Duration::from_std(self.ttl) else { return 0 };
+        let before = self.entries.len();
+        self.entries.retain(|_, cached| now.signed_duration_since(cached.last_found_at) < ttl);
+        let pruned = before - self.entries.len();
+        if pruned > 0 {
+            self.dirty = true;
+            debug!("Dropped {} webcam(s) no search found in {:?}", pruned, self.ttl);
+        }
+        pruned
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Webcams saved in the database at `path`, migrating older versions
+pub fn read_db(path: &Path) -> Result<Vec<CachedWebcam>, WebcamDbError> {
+    let text = std::fs::read_to_string(path)?;
+    let value: Value = serde_json::from_str(&text).map_err(|e| WebcamDbError::Corrupt(e.to_string()))?;
+    let corrupt = |e: serde_json::Error| WebcamDbError::Corrupt(e.to_string());
+    match value {
+        // Version 0: the webcams alone, with no record of when they were found
+        Value::Array(_) => {
+            let webcams: Vec<RemoteWebcam> = serde_json::from_value(value).map_err(corrupt)?;
+            let now = Utc::now();
+            Ok(webcams
+                .into_iter()
+                .map(|webcam| CachedWebcam { webcam, queries: Vec::new(), discovered_at: now, last_found_at: now, validation: None })
+                .collect())
+        }
+        Value::Object(mut db) => match db.get("version").and_then(Value::as_u64) {
+            Some(WEBCAM_DB_VERSION) => {
+                let webcams = db.remove("webcams").unwrap_or(Value::Array(Vec::new()));
+                serde_json::from_value(webcams).map_err(corrupt)
+            }
+            Some(version) if version > WEBCAM_DB_VERSION => Err(WebcamDbError::UnsupportedVersion(version)),
+            _ => Err(WebcamDbError::Corrupt("no supported version number".to_string())),
+        },
+        _ => Err(WebcamDbError::Corrupt("not a webcam list".to_string())),
+    }
+}
+
+/// Write `webcams` to the database at `path`, replacing it only once the
+/// new file is complete
+pub fn write_db<'a>(path: &Path, webcams: impl Iterator<Item = &'a CachedWebcam>) -> Result<(), WebcamDbError> {
+    let mut webcams: Vec<&CachedWebcam> = webcams.collect();
+    webcams.sort_by_key(|cached| cache_key(&cached.webcam.ip, cached.webcam.port));
+    let db = json!({ "version": WEBCAM_DB_VERSION, "saved_at": Utc::now(), "webcams": webcams });
+    let bytes = serde_json::to_vec_pretty(&db).map_err(|e| WebcamDbError::Corrupt(e.to_string()))?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    std::fs::write(&temporary, bytes)?;
+    std::fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Where a database that can't be used is moved, e.g.
+/// `remote_webcams.json.corrupt-20240102T030405Z`
+fn backup_path(path: &Path, reason: &str) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".{}-{}", reason, Utc::now().format("%Y%m%dT%H%M%SZ")));
+    PathBuf::from(backup)
+}
@@ -0,0 +1,88 @@
+//! The audit log of captures and remote fetches, written by a server built
+//! around the mock backend and read back with `get_audit_log`.
+
+mod common;
+
+use common::{call, request};
+use mcp_webcam::protocol::Server;
+use mcp_webcam::{AuditLog, CaptureStore, MockBackend, RetentionPolicy, WebcamMcpServer};
+use serde_json::{json, Value};
+use std::path::Path;
+
+fn server_auditing_to(dir: &Path, audit_log: AuditLog) -> Server {
+    WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(2)))
+        .capture_store(CaptureStore::new(dir.join("captures"), RetentionPolicy::default()))
+        .audit_log(audit_log)
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn captures_are_audited_in_a_hash_chain() {
+    let dir = std::env::temp_dir().join(format!("mcp-webcam-audit-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let log_path = dir.join("logs").join("audit.jsonl");
+    let server = server_auditing_to(&dir, AuditLog::open(&log_path));
+
+    assert_eq!(call(&server, "capture_image", json!({ "camera_index": 1, "comment": "front door" }))["isError"], false);
+    assert_eq!(call(&server, "capture_image", json!({ "camera_index": 9 }))["isError"], true);
+    // Tools that don't touch a camera aren't audited
+    call(&server, "list_cameras", json!({}));
+    request(&server, "resources/read", json!({ "uri": "webcam://camera/0/snapshot" }));
+
+    let audit = &call(&server, "get_audit_log", json!({}))["structuredContent"]["audit"];
+    assert_eq!(audit["enabled"], true);
+    assert_eq!(audit["matched"], 3);
+    assert_eq!(audit["chain_intact"], true);
+    let entries = audit["entries"].as_array().unwrap();
+    let summary: Vec<(&str, &Value, bool)> = entries.iter()
+        .map(|e| (e["tool"].as_str().unwrap(), &e["camera_index"], e["success"].as_bool().unwrap()))
+        .collect();
+    assert_eq!(summary, [
+        ("capture_image", &json!(1), true),
+        ("capture_image", &json!(9), false),
+        ("resources/read", &json!(0), true),
+    ]);
+    assert_eq!(entries[0]["arguments"]["comment"], "front door");
+    assert!(entries[0]["result_bytes"].as_u64().unwrap() > 0);
+    assert_eq!(entries[1]["error_code"], "CAMERA_NOT_FOUND");
+
+    // The cap keeps the newest entries; `since` filters by time
+    let newest = &call(&server, "get_audit_log", json!({ "limit": 1 }))["structuredContent"]["audit"];
+    assert_eq!(newest["entries"][0]["tool"], "resources/read");
+    assert_eq!(newest["matched"], 3);
+    let future = &call(&server, "get_audit_log", json!({ "since": "2999-01-01T00:00:00Z" }))["structuredContent"]["audit"];
+    assert_eq!(future["matched"], 0);
+    assert_eq!(call(&server, "get_audit_log", json!({ "since": "yesterday" }))["isError"], true);
+
+    // Editing a line breaks the chain
+    let text = std::fs::read_to_string(&log_path).unwrap();
+    std::fs::write(&log_path, text.replacen("\"camera_index\":9", "\"camera_index\":8", 1)).unwrap();
+    let tampered = &call(&server, "get_audit_log", json!({}))["structuredContent"]["audit"];
+    assert_eq!(tampered["chain_intact"], false);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn captures_succeed_when_the_audit_log_cannot_be_written() {
+    let dir = std::env::temp_dir().join(format!("mcp-webcam-audit-unwritable-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    // A file stands where the log's directory should be
+    std::fs::write(dir.join("not-a-dir"), b"").unwrap();
+    let server = server_auditing_to(&dir, AuditLog::open(dir.join("not-a-dir").join("audit.jsonl")));
+
+    assert_eq!(call(&server, "capture_image", json!({ "camera_index": 0 }))["isError"], false);
+    let unreadable = call(&server, "get_audit_log", json!({}));
+    assert_eq!(unreadable["structuredContent"]["error"]["code"], "STORAGE_ERROR");
+
+    let disabled = server_auditing_to(&dir, AuditLog::disabled());
+    assert_eq!(call(&disabled, "capture_image", json!({ "camera_index": 0 }))["isError"], false);
+    assert_eq!(call(&disabled, "get_audit_log", json!({}))["structuredContent"]["audit"]["enabled"], false);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
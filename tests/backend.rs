@@ -0,0 +1,359 @@
+//! `WebcamManager` capture logic exercised through the mock backend.
+
+use mcp_webcam::image_processing::WhiteBalance;
+use mcp_webcam::tool_error::ToolError;
+use mcp_webcam::{CameraProfile, CaptureOptions, FaceBlur, MockBackend, WebcamError, WebcamManager};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+fn manager_with(backend: MockBackend) -> WebcamManager {
+    WebcamManager::with_backend(Box::new(backend))
+}
+
+#[test]
+fn lists_backend_devices() {
+    let cameras = manager_with(MockBackend::new(2)).list_cameras().unwrap();
+    assert_eq!(cameras.iter().map(|c| c.index).collect::<Vec<_>>(), vec![0, 1]);
+    assert_eq!(cameras[1].name, "Mock Camera 1");
+    assert_eq!(cameras[1].backend, "mock");
+}
+
+#[test]
+fn device_list_is_cached_until_refreshed() {
+    let backend = MockBackend::new(1);
+    let stats = backend.stats();
+    let unplugged = backend.unplug_switch();
+    let manager = manager_with(backend);
+
+    manager.list_cameras().unwrap();
+    manager.list_cameras().unwrap();
+    assert_eq!(stats.enumerations.load(Ordering::Relaxed), 1);
+
+    unplugged.store(true, Ordering::Relaxed);
+    assert_eq!(manager.list_cameras().unwrap().len(), 1);
+    assert!(manager.refresh_cameras().unwrap().is_empty());
+    assert_eq!(stats.enumerations.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn capture_processes_backend_frames() {
+    let mut manager = manager_with(MockBackend::new(1));
+    let result = manager
+        .capture_image_with_options(Some(0), &CaptureOptions { rotation: Some(90), quality: Some(60), ..Default::default() })
+        .unwrap();
+
+    assert_eq!((result.width, result.height), (240, 320));
+    assert_eq!(result.quality, 60);
+    assert_eq!(result.camera_name.as_deref(), Some("Mock Camera 0"));
+    assert_eq!(result.backend.as_deref(), Some("mock"));
+    let encoder = if cfg!(feature = "turbojpeg") { "turbojpeg" } else { "image" };
+    assert_eq!(result.encoder, encoder);
+    let current = manager.get_current_camera_info().unwrap();
+    assert_eq!(current.index, 0);
+    assert_eq!(current.name, "Mock Camera 0");
+    assert_eq!(current.resolution, Some((320, 240)));
+    assert_eq!(current.frames_captured, 1);
+    assert_eq!(current.frame_format.as_deref(), Some("RGB"));
+}
+
+#[test]
+fn current_camera_frame_count_restarts_when_another_camera_opens() {
+    let mut manager = manager_with(MockBackend::new(2));
+    assert!(manager.get_current_camera_info().is_none());
+
+    manager.capture_frame(Some(0), &CaptureOptions::default()).unwrap();
+    manager.capture_frame(Some(0), &CaptureOptions::default()).unwrap();
+    let first = manager.get_current_camera_info().unwrap();
+    assert_eq!((first.index, first.frames_captured), (0, 2));
+
+    manager.capture_frame(Some(1), &CaptureOptions::default()).unwrap();
+    let second = manager.get_current_camera_info().unwrap();
+    assert_eq!((second.index, second.name.as_str(), second.frames_captured), (1, "Mock Camera 1", 1));
+    assert!(second.opened_at >= first.opened_at);
+
+    manager.close_camera();
+    assert!(manager.get_current_camera_info().is_none());
+}
+
+#[test]
+fn camera_is_reopened_only_when_needed() {
+    let backend = MockBackend::new(2);
+    let stats = backend.stats();
+    let mut manager = manager_with(backend);
+    let options = CaptureOptions::default();
+
+    manager.capture_frame(Some(0), &options).unwrap();
+    manager.capture_frame(Some(0), &options).unwrap();
+    assert_eq!(stats.opens.load(Ordering::Relaxed), 1);
+
+    manager.capture_frame(Some(1), &options).unwrap();
+    assert_eq!(stats.opens.load(Ordering::Relaxed), 2);
+
+    let resized = CaptureOptions { resolution: Some((160, 120)), ..Default::default() };
+    let frame = manager.capture_frame(Some(1), &resized).unwrap();
+    assert_eq!(stats.opens.load(Ordering::Relaxed), 3);
+    assert_eq!(frame.image.dimensions(), (160, 120));
+}
+
+#[test]
+fn warmup_frames_are_grabbed_and_discarded() {
+    let backend = MockBackend::new(1);
+    let stats = backend.stats();
+    let mut manager = manager_with(backend);
+
+    manager.capture_frame(Some(0), &CaptureOptions { warmup_frames: Some(3), ..Default::default() }).unwrap();
+    assert_eq!(stats.frames_grabbed.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn server_defaults_apply_when_call_names_none() {
+    let mut manager = manager_with(MockBackend::new(2));
+    manager.set_default_camera(1);
+    manager.set_max_width(Some(160));
+    manager.set_capture_defaults(CameraProfile { rotation: Some(90), ..Default::default() }).unwrap();
+
+    let frame = manager.capture_frame(None, &CaptureOptions::default()).unwrap();
+    assert_eq!(frame.camera_index, 1);
+    assert_eq!(frame.image.dimensions(), (120, 160));
+
+    // Per-camera profiles and per-call options still win
+    manager.set_camera_profile(0, CameraProfile { rotation: Some(0), ..Default::default() }).unwrap();
+    let frame = manager.capture_frame(Some(0), &CaptureOptions::default()).unwrap();
+    assert_eq!(frame.image.dimensions(), (160, 120));
+    assert_eq!(manager.effective_profile(1).rotation, 90);
+
+    let backend_default = CameraProfile { backend: Some("mock".to_string()), ..Default::default() };
+    assert!(manager.set_capture_defaults(backend_default).is_err());
+}
+
+#[test]
+fn missing_camera_is_reported() {
+    let mut manager = manager_with(MockBackend::new(1));
+    match manager.capture_image(Some(5)) {
+        Err(WebcamError::CameraNotFound { index }) => assert_eq!(index, 5),
+        other => panic!("unexpected result {:?}", other.map(|r| r.camera_index)),
+    }
+}
+
+#[test]
+fn no_devices_reports_no_cameras_available() {
+    let mut manager = manager_with(MockBackend::new(0));
+    assert!(matches!(manager.capture_image(None), Err(WebcamError::NoCamerasAvailable)));
+}
+
+#[test]
+fn busy_camera_is_reported_and_retried() {
+    let mut manager = manager_with(MockBackend::new(1).busy());
+    for _ in 0..2 {
+        match manager.capture_image(Some(0)) {
+            Err(WebcamError::DeviceBusy { index, .. }) => assert_eq!(index, 0),
+            other => panic!("unexpected result {:?}", other.map(|r| r.camera_index)),
+        }
+    }
+    // Still listed, so the agent can try again once the camera is free
+    assert_eq!(manager.list_cameras().unwrap().len(), 1);
+    assert!(manager.get_current_camera_info().is_none());
+}
+
+#[test]
+fn busy_camera_error_names_code_and_holder() {
+    let error = ToolError::from(&WebcamError::DeviceBusy { index: 2, holder: Some("zoom (pid 42)".to_string()) });
+    let json = serde_json::to_value(&error).unwrap();
+    assert_eq!(json["code"], "CAMERA_BUSY");
+    assert_eq!(json["details"]["camera_index"], 2);
+    assert_eq!(json["details"]["holder"], "zoom (pid 42)");
+    // Worth trying again once the camera is free, unlike a missing camera
+    assert_eq!(json["retryable"], true);
+    assert!(!ToolError::from(&WebcamError::CameraNotFound { index: 2 }).retryable);
+}
+
+#[test]
+fn unplugged_camera_is_reported_and_picked_up_again() {
+    let backend = MockBackend::new(1);
+    let unplugged = backend.unplug_switch();
+    let stats = backend.stats();
+    let mut manager = manager_with(backend);
+    let options = CaptureOptions::default();
+
+    manager.capture_frame(Some(0), &options).unwrap();
+
+    unplugged.store(true, Ordering::Relaxed);
+    match manager.capture_frame(Some(0), &options) {
+        Err(WebcamError::CameraDisconnected { index }) => assert_eq!(index, 0),
+        other => panic!("unexpected result {:?}", other.map(|f| f.camera_index)),
+    }
+    assert!(manager.get_current_camera_info().is_none());
+    assert!(manager.list_cameras().unwrap().is_empty());
+
+    // Plugged back in: the stale handle was dropped, so the camera is reopened
+    unplugged.store(false, Ordering::Relaxed);
+    manager.capture_frame(Some(0), &options).unwrap();
+    assert_eq!(stats.opens.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn self_test_flags_blank_frames() {
+    let black = image::RgbImage::new(64, 48);
+    let mut manager = manager_with(MockBackend::new(1).with_frame(black));
+
+    let results = manager.self_test(None).unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(!results[0].passed);
+    assert_eq!(results[0].error.as_deref(), Some("Frame is all black"));
+    assert!(manager.get_current_camera_info().is_none());
+
+    let mut manager = manager_with(MockBackend::new(1));
+    assert!(manager.self_test(Some(0)).unwrap()[0].passed);
+}
+
+#[test]
+fn self_test_checks_every_camera_and_releases_them() {
+    let backend = MockBackend::new(2);
+    let stats = backend.stats();
+    let mut manager = manager_with(backend);
+    manager.capture_frame(Some(1), &CaptureOptions::default()).unwrap();
+
+    let results = manager.self_test(None).unwrap();
+    assert_eq!(results.iter().map(|r| (r.index, r.passed)).collect::<Vec<_>>(), vec![(0, true), (1, true)]);
+    for result in &results {
+        assert!(result.open_ms.is_some() && result.frame_ms.is_some());
+        assert_eq!((result.width, result.height), (Some(320), Some(240)));
+        assert!(result.error.is_none());
+    }
+    // The camera open before the test was released too
+    assert!(manager.get_current_camera_info().is_none());
+    assert_eq!(stats.opens.load(Ordering::Relaxed), 3);
+
+    assert!(matches!(manager.self_test(Some(5)), Err(WebcamError::CameraNotFound { index: 5 })));
+}
+
+#[test]
+fn self_test_reports_white_frames_and_busy_cameras() {
+    let white = image::RgbImage::from_pixel(64, 48, image::Rgb([255, 255, 255]));
+    let mut manager = manager_with(MockBackend::new(1).with_frame(white));
+    let result = &manager.self_test(Some(0)).unwrap()[0];
+    assert!(!result.passed);
+    assert_eq!(result.error.as_deref(), Some("Frame is all white"));
+
+    let mut manager = manager_with(MockBackend::new(1).busy());
+    let result = &manager.self_test(None).unwrap()[0];
+    assert!(!result.passed);
+    assert!(result.open_ms.is_none() && result.frame_ms.is_none());
+    assert!(result.error.as_deref().unwrap().contains("busy"), "{:?}", result.error);
+}
+
+#[test]
+fn benchmark_reports_backend_format() {
+    let mut manager = manager_with(MockBackend::new(1));
+    let report = manager.benchmark(Some(0), Duration::from_millis(50)).unwrap();
+
+    assert!(report.frames > 0);
+    assert_eq!((report.width, report.height), (320, 240));
+    assert_eq!(report.nominal_fps, 30);
+}
+
+#[test]
+fn benchmark_measures_every_grabbed_frame() {
+    let backend = MockBackend::new(1);
+    let stats = backend.stats();
+    let mut manager = manager_with(backend);
+    let report = manager.benchmark(Some(0), Duration::from_millis(50)).unwrap();
+
+    assert_eq!(report.frames as u64, stats.frames_grabbed.load(Ordering::Relaxed));
+    assert!(report.duration_ms >= 50);
+    assert!(report.fps > 0.0);
+    let (min, avg, max) = (report.min_interval_ms.unwrap(), report.avg_interval_ms.unwrap(), report.max_interval_ms.unwrap());
+    assert!(min <= avg && avg <= max, "{} {} {}", min, avg, max);
+    // Mock frames arrive far faster than the nominal 30 fps
+    assert_eq!(report.dropped_frames, 0);
+    assert_eq!(report.pixel_format, "RGB");
+}
+
+#[test]
+fn benchmark_stops_when_cancelled_or_timed_out() {
+    let mut manager = manager_with(MockBackend::new(1));
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    let cancelled = manager.benchmark_cancellable(Some(0), Duration::from_secs(5), &cancel);
+    assert!(matches!(cancelled, Err(WebcamError::Cancelled)));
+    assert!(manager.get_current_camera_info().is_none());
+    assert!(matches!(manager.benchmark(Some(3), Duration::from_millis(10)), Err(WebcamError::CameraNotFound { index: 3 })));
+
+    // A camera that takes longer than the timeout doesn't hold up the caller
+    let manager = Arc::new(Mutex::new(manager));
+    let started = std::time::Instant::now();
+    let result = WebcamManager::with_timeout(&manager, Duration::from_millis(50), |manager| {
+        manager.benchmark(Some(0), Duration::from_secs(2))
+    });
+    assert!(matches!(result, Err(WebcamError::Timeout { .. })));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn white_balance_uses_the_camera_control_when_supported() {
+    let orange = image::RgbImage::from_pixel(64, 48, image::Rgb([200, 120, 40]));
+    let backend = MockBackend::new(1).with_frame(orange.clone()).with_white_balance_control();
+    let stats = backend.stats();
+    let mut manager = manager_with(backend);
+
+    let options = CaptureOptions { white_balance: Some(WhiteBalance::Kelvin(3200)), ..Default::default() };
+    let frame = manager.capture_frame(Some(0), &options).unwrap();
+    let info = frame.white_balance.unwrap();
+    assert_eq!((info.requested, info.path.as_str(), info.gains), (WhiteBalance::Kelvin(3200), "hardware", None));
+    assert_eq!(stats.white_balance.load(Ordering::Relaxed), 3200);
+    // The camera did the correction, so the frame is left as grabbed
+    assert_eq!(frame.image, orange);
+}
+
+#[test]
+fn white_balance_falls_back_to_gray_world() {
+    let orange = image::RgbImage::from_pixel(64, 48, image::Rgb([200, 120, 40]));
+    let mut manager = manager_with(MockBackend::new(1).with_frame(orange));
+
+    for requested in [WhiteBalance::Kelvin(3200), WhiteBalance::Auto] {
+        let options = CaptureOptions { white_balance: Some(requested), ..Default::default() };
+        let frame = manager.capture_frame(Some(0), &options).unwrap();
+        let info = frame.white_balance.unwrap();
+        assert_eq!((info.requested, info.path.as_str()), (requested, "software_gray_world"));
+        let gains = info.gains.unwrap();
+        assert!(gains[0] < 1.0 && gains[2] > 1.0, "{:?}", gains);
+        assert_eq!(frame.image.get_pixel(0, 0).0, [120, 120, 120]);
+    }
+
+    let frame = manager.capture_frame(Some(0), &CaptureOptions::default()).unwrap();
+    assert!(frame.white_balance.is_none());
+}
+
+/// Detection fails: either face_blur isn't compiled in or the model is missing
+fn without_face_model() {
+    let missing = std::env::temp_dir().join(format!("mcp-webcam-no-face-model-{}.bin", std::process::id()));
+    std::env::set_var("MCP_WEBCAM_FACE_MODEL", missing);
+}
+
+#[test]
+fn failed_face_detection_refuses_strict_captures() {
+    without_face_model();
+    let mut manager = manager_with(MockBackend::new(1));
+
+    let options = CaptureOptions { blur_faces: Some(FaceBlur { strict: true }), ..Default::default() };
+    let err = manager.capture_image_with_options(Some(0), &options).unwrap_err();
+    assert!(matches!(err, WebcamError::FaceDetection(_)), "{:?}", err);
+    let json = serde_json::to_value(ToolError::from(&err)).unwrap();
+    assert_eq!(json["code"], "FACE_DETECTION_FAILED");
+}
+
+#[test]
+fn failed_face_detection_returns_the_unblurred_frame_when_not_strict() {
+    without_face_model();
+    let mut manager = manager_with(MockBackend::new(1));
+
+    let options = CaptureOptions { blur_faces: Some(FaceBlur { strict: false }), ..Default::default() };
+    let frame = manager.capture_frame(Some(0), &options).unwrap();
+    let info = frame.face_blur.unwrap();
+    assert_eq!(info.faces_blurred, 0);
+    assert!(info.regions.is_empty());
+    assert!(info.detection_error.is_some());
+    assert_eq!(frame.image, image::RgbImage::from_pixel(320, 240, image::Rgb([128, 128, 128])));
+}
@@ -0,0 +1,107 @@
+//! The Censys client against a stand-in for its API: search hits become
+//! webcams tagged `censys`, host lookups and account quotas take the
+//! shapes the Shodan tools report, and the secret never leaves the
+//! Authorization header.
+
+#![cfg(feature = "censys")]
+
+mod common;
+
+use common::{serve, HttpResponse, Requests};
+use mcp_webcam::censys::SOURCE;
+use mcp_webcam::shodan::select_queries;
+use mcp_webcam::{CensysClient, SearchFilters, ShodanClient, ShodanError, WebcamAccessType, WebcamDiscoveryProvider};
+
+const API_ID: &str = "censys-test-id";
+const SECRET: &str = "censys-not-a-real-secret";
+
+/// Answer every request with `status` and a JSON `body`; returns the
+/// base URL and the requests received
+fn serve_json(status: &'static str, body: &'static str) -> (String, Requests) {
+    serve(move |_| HttpResponse::new(status, "application/json", body))
+}
+
+fn client(base_url: &str) -> CensysClient {
+    CensysClient::new(API_ID, SECRET, &ShodanClient::builder()).unwrap().with_base_url(base_url)
+}
+
+#[tokio::test]
+async fn search_hits_become_webcams_tagged_censys() {
+    let (base_url, requests) = serve_json("200 OK", include_str!("fixtures/censys_host_search.json"));
+    let queries: Vec<String> = vec!["shodan query".to_string()];
+    let plan = select_queries(&queries, None, None).unwrap();
+    let filters = SearchFilters::new(Some("gb"), None, None).unwrap();
+
+    let found = client(&base_url).search_webcams(Some(10), &filters, &plan).await.unwrap();
+
+    let request = &requests.all()[0];
+    assert_eq!((request.method.as_str(), request.path.as_str()), ("GET", "/v2/hosts/search"));
+    assert!(request.query.contains("location.country_code"), "{}", request.query);
+    assert!(request.header("authorization").unwrap().starts_with("Basic "), "{}", request.head);
+    assert!(!request.query.contains(SECRET) && !request.head.contains(SECRET), "{:?}", request);
+    assert_eq!(found.queries.len(), 1);
+
+    // Of the first host, only the service the query matched; of the
+    // second, every HTTP and RTSP service
+    let endpoints: Vec<(&str, u16, &str)> = found.webcams.iter().map(|w| (w.ip.as_str(), w.port, w.url.as_str())).collect();
+    assert_eq!(endpoints, vec![
+        ("192.0.2.10", 8080, "http://192.0.2.10:8080/"),
+        ("2001:db8::5", 443, "https://[2001:db8::5]:443/"),
+        ("2001:db8::5", 554, "rtsp://[2001:db8::5]:554/"),
+    ]);
+    let first = &found.webcams[0];
+    assert_eq!(first.sources, vec![SOURCE.to_string()]);
+    assert_eq!(first.hostname.as_deref(), Some("host-192-0-2-10.broadband.example.net"));
+    assert_eq!(first.org.as_deref(), Some("EXAMPLE-BROADBAND"));
+    assert_eq!(first.location.as_ref().and_then(|l| l.city.as_deref()), Some("London"));
+    assert_eq!(first.last_seen, "2026-09-30T08:12:44.000Z");
+    assert!(matches!(found.webcams[2].access_type, WebcamAccessType::RTSP));
+}
+
+#[tokio::test]
+async fn host_lookups_take_the_shape_of_shodans() {
+    let (base_url, requests) = serve_json("200 OK", include_str!("fixtures/censys_host.json"));
+    let host = client(&base_url).host("192.0.2.10").await.unwrap();
+    assert_eq!(requests.paths(), vec!["/v2/hosts/192.0.2.10"]);
+
+    assert_eq!(host.ports, vec![22, 8080]);
+    assert_eq!(host.hostnames, vec!["camera.example.net", "host-192-0-2-10.broadband.example.net"]);
+    assert_eq!(host.os.as_deref(), Some("Linux"));
+    assert_eq!(host.tags, vec!["camera", "remote-access"]);
+    assert_eq!(host.services[0].product.as_deref(), Some("IP Camera"));
+    assert_eq!(host.services[0].transport.as_deref(), Some("tcp"));
+
+    let (base_url, _) = serve_json("404 Not Found", r#"{"code": 404, "status": "Not Found", "error": "host not found"}"#);
+    assert!(matches!(client(&base_url).host("192.0.2.11").await, Err(ShodanError::NotFound(ip)) if ip == "192.0.2.11"));
+    let bad = client("http://127.0.0.1:1").host("not an address").await;
+    assert!(matches!(bad, Err(ShodanError::InvalidQuery(_))));
+}
+
+#[tokio::test]
+async fn account_quota_becomes_credits_left() {
+    let (base_url, _) = serve_json(
+        "200 OK",
+        r#"{"email": "someone@example.com", "login": "someone", "quota": {"used": 40, "allowance": 250, "resets_at": "2026-11-01 00:00:00"}}"#,
+    );
+    let credits = client(&base_url).credits().await.unwrap();
+    assert_eq!(credits.provider, "censys");
+    assert_eq!(credits.query_credits, 210);
+    assert_eq!(credits.scan_credits, None);
+    assert_eq!(credits.resets_at.as_deref(), Some("2026-11-01 00:00:00"));
+
+    let (base_url, _) = serve_json("401 Unauthorized", r#"{"code": 401, "status": "Unauthorized"}"#);
+    assert!(matches!(client(&base_url).credits().await, Err(ShodanError::Unauthorized)));
+    let (base_url, _) = serve_json("429 Too Many Requests", r#"{"code": 429, "status": "Too Many Requests"}"#);
+    assert!(matches!(client(&base_url).credits().await, Err(ShodanError::RateLimitExceeded { .. })));
+}
+
+#[test]
+fn credentials_come_from_both_variables() {
+    std::env::remove_var("CENSYS_API_ID");
+    std::env::set_var("CENSYS_API_SECRET", SECRET);
+    assert!(CensysClient::from_env(&ShodanClient::builder()).unwrap().is_none());
+    std::env::set_var("CENSYS_API_ID", API_ID);
+    assert!(CensysClient::from_env(&ShodanClient::builder()).unwrap().is_some());
+    std::env::remove_var("CENSYS_API_ID");
+    std::env::remove_var("CENSYS_API_SECRET");
+}
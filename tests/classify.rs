@@ -0,0 +1,96 @@
+//! Classification of discovered webcams by make, from representative
+//! Shodan products and banners.
+
+use mcp_webcam::classify::{classify, Vendor};
+use mcp_webcam::probe::vendor_snapshot_paths;
+use mcp_webcam::{RemoteWebcam, SearchFilters, WebcamAccessType};
+
+fn vendor(product: Option<&str>, banner: &str, port: u16) -> Option<Vendor> {
+    classify(product, banner, port).map(|classification| classification.vendor)
+}
+
+#[test]
+fn makes_are_recognized_from_their_banners() {
+    let banners = [
+        ("HTTP/1.1 200 OK\r\nServer: Hikvision-Webs\r\nContent-Type: text/html\r\n\r\n", 80, Vendor::Hikvision),
+        ("HTTP/1.1 401 Unauthorized\r\nServer: App-webs/\r\nWWW-Authenticate: Digest realm=\"IP Camera\"\r\n\r\n", 80, Vendor::Hikvision),
+        ("HTTP/1.1 200 OK\r\nServer: DNVRS-Webs\r\n\r\n", 8000, Vendor::Hikvision),
+        ("HTTP/1.1 200 OK\r\nServer: DHWebs\r\n\r\n", 80, Vendor::Dahua),
+        ("HTTP/1.1 401 Unauthorized\r\nServer: Netwave IP Camera\r\nWWW-Authenticate: Basic realm=\"index.html\"\r\n\r\n", 81, Vendor::Foscam),
+        ("HTTP/1.0 200 OK\r\nServer: Boa/0.94.13\r\n\r\n<title>Live View / - AXIS 211 Network Camera</title>", 80, Vendor::Axis),
+        ("HTTP/1.1 200 OK\r\nServer: MOBOTIX\r\n\r\n", 80, Vendor::Mobotix),
+        ("HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"DCS-932L\"\r\n\r\n", 80, Vendor::Dlink),
+        ("HTTP/1.1 200 OK\r\n\r\n<frame src=\"CgiStart?page=Single&Language=0\"><a href=\"ViewerFrame?Mode=Motion\">", 80, Vendor::Panasonic),
+        ("HTTP/1.0 200 OK\r\nServer: gen5th/1.0 (SNC-CH140)\r\n\r\n", 80, Vendor::Sony),
+        ("HTTP/1.1 200 OK\r\nServer: Vivotek Network Camera\r\n\r\n", 80, Vendor::Vivotek),
+        ("HTTP/1.1 200 OK\r\nServer: IP Webcam Server 0.4\r\n\r\n", 8080, Vendor::IpWebcam),
+        ("HTTP/1.0 200 OK\r\nServer: yawcam\r\nContent-Type: text/html\r\n\r\n", 8081, Vendor::Yawcam),
+        ("HTTP/1.1 200 OK\r\nServer: webcamXP 5\r\n\r\n", 8080, Vendor::WebcamXp),
+        ("HTTP/1.0 200 OK\r\nContent-Type: multipart/x-mixed-replace;boundary=frame\r\n\r\n", 8080, Vendor::GenericMjpeg),
+    ];
+    for (banner, port, expected) in banners {
+        assert_eq!(vendor(None, banner, port), Some(expected), "{}", banner);
+    }
+    assert_eq!(vendor(None, "HTTP/1.1 200 OK\r\nServer: nginx\r\n\r\n", 80), None);
+    assert_eq!(vendor(None, "", 80), None);
+}
+
+#[test]
+fn the_product_outweighs_a_banner_hint() {
+    // An Axis camera streaming MJPEG is an Axis, not a generic stream
+    let banner = "HTTP/1.0 200 OK\r\nContent-Type: multipart/x-mixed-replace;boundary=myboundary\r\n\r\n";
+    let axis = classify(Some("Axis M1065-L Network Camera"), banner, 80).unwrap();
+    assert_eq!(axis.vendor, Vendor::Axis);
+    assert_eq!(axis.confidence, 0.95);
+    assert_eq!(axis.snapshot_path.as_deref(), Some("/axis-cgi/jpg/image.cgi"));
+    // "Axis" must be a word of its own
+    assert_eq!(vendor(Some("Taxis dispatch"), "", 80), None);
+
+    let generic = classify(None, banner, 8080).unwrap();
+    assert_eq!(generic.vendor, Vendor::GenericMjpeg);
+    assert_eq!(generic.confidence, 0.45);
+    assert_eq!(generic.snapshot_path, None);
+    // Off its usual ports a rule is a little less certain
+    assert_eq!(classify(None, banner, 9000).unwrap().confidence, 0.4);
+    assert_eq!(classify(None, "HTTP/1.1 200 OK\r\nServer: webcamXP 5\r\n\r\n", 8080).unwrap().confidence, 0.95);
+}
+
+#[test]
+fn vendor_names_parse_loosely() {
+    assert_eq!("hikvision".parse::<Vendor>(), Ok(Vendor::Hikvision));
+    assert_eq!("D-Link".parse::<Vendor>(), Ok(Vendor::Dlink));
+    assert_eq!("IP Webcam".parse::<Vendor>(), Ok(Vendor::IpWebcam));
+    assert_eq!("generic_mjpeg".parse::<Vendor>(), Ok(Vendor::GenericMjpeg));
+    let error = "Acme".parse::<Vendor>().unwrap_err();
+    assert!(error.contains("hikvision"), "{}", error);
+    for vendor in Vendor::ALL {
+        assert_eq!(vendor.name().parse::<Vendor>(), Ok(vendor));
+        assert_eq!(serde_json::to_value(vendor).unwrap(), vendor.name());
+    }
+}
+
+#[test]
+fn the_vendor_filter_needs_a_matching_classification() {
+    let mut webcam = RemoteWebcam {
+        ip: "192.0.2.1".to_string(),
+        port: 80,
+        url: "http://192.0.2.1:80/".to_string(),
+        hostname: None,
+        location: None,
+        org: None,
+        product: None,
+        last_seen: String::new(),
+        access_type: WebcamAccessType::HTTP,
+        classification: None,
+        sources: Vec::new(),
+    };
+    let dahua = SearchFilters::default().with_vendor(Some(Vendor::Dahua));
+    assert!(!dahua.matches(&webcam));
+    webcam.classification = classify(None, "HTTP/1.1 200 OK\r\nServer: DHWebs\r\n\r\n", 80);
+    assert!(dahua.matches(&webcam));
+    assert!(!SearchFilters::default().with_vendor(Some(Vendor::Axis)).matches(&webcam));
+
+    // Probing tries the make's paths first
+    assert_eq!(vendor_snapshot_paths(Some(Vendor::Dahua))[0], "/cgi-bin/snapshot.cgi");
+    assert_eq!(vendor_snapshot_paths(Some(Vendor::GenericMjpeg)), vendor_snapshot_paths(None));
+}
@@ -0,0 +1,147 @@
+//! The binary's one-shot subcommands, without the MCP transport: capture
+//! and list-cameras against the file-backed fake camera, and the shodan
+//! subcommands' key check and fetches from a stand-in webcam on loopback.
+
+mod common;
+
+use common::{serve, temp_dir, HttpResponse};
+use mcp_webcam::webcam::FILE_CAMERA_INDEX;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Run the binary with `args` and a fake camera replaying `source`
+fn run(source: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mcp-webcam"))
+        .args(["--log-level", "warn", "--no-shodan"])
+        .args(args)
+        .env("MCP_WEBCAM_FAKE_SOURCE", source)
+        .env_remove("MCP_WEBCAM_LOG_FILE")
+        .output()
+        .expect("run binary")
+}
+
+fn fake_source(name: &str) -> PathBuf {
+    let dir = temp_dir(name);
+    image::RgbImage::from_pixel(64, 48, image::Rgb([0, 128, 255])).save(dir.join("frame.png")).unwrap();
+    dir
+}
+
+#[test]
+fn capture_writes_a_jpeg_to_a_file_or_stdout() {
+    let source = fake_source("cli-capture");
+    let camera = FILE_CAMERA_INDEX.to_string();
+    let out = source.join("capture.jpg");
+
+    let output = run(&source, &["capture", "--camera", &camera, "--width", "32", "--height", "24", "--out", out.to_str().unwrap()]);
+    assert!(output.status.success(), "capture failed: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.stdout.is_empty());
+    let image = image::open(&out).unwrap();
+    assert_eq!((image.width(), image.height()), (32, 24));
+
+    let output = run(&source, &["capture", "--camera", &camera, "--rotation", "90"]);
+    assert!(output.status.success(), "capture failed: {}", String::from_utf8_lossy(&output.stderr));
+    let image = image::load_from_memory(&output.stdout).unwrap();
+    assert_eq!((image.width(), image.height()), (48, 64));
+    let _ = std::fs::remove_dir_all(&source);
+}
+
+#[test]
+fn capture_failures_exit_non_zero_with_the_error() {
+    let source = fake_source("cli-capture-error");
+    let camera = FILE_CAMERA_INDEX.to_string();
+
+    let output = run(&source, &["capture", "--camera", &camera, "--crop", "1000,1000,10,10"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("lies outside the 64x48 frame"));
+
+    // Flags are checked before any camera is opened
+    let output = run(&source, &["capture", "--crop", "1,2,3"]);
+    assert_eq!(output.status.code(), Some(2));
+    for threshold in ["1.5", "NaN", "inf", "-0.5"] {
+        let output = run(&source, &[&format!("--honeypot-threshold={}", threshold), "list-cameras"]);
+        assert_eq!(output.status.code(), Some(2), "{}", threshold);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("expected a score from 0 to 1"), "{}", threshold);
+    }
+    assert!(run(&source, &["--honeypot-threshold", "0.8", "list-cameras"]).status.success());
+    let _ = std::fs::remove_dir_all(&source);
+}
+
+#[test]
+fn list_cameras_prints_a_table_or_json() {
+    let source = fake_source("cli-list");
+
+    let output = run(&source, &["list-cameras", "--json"]);
+    assert!(output.status.success(), "list-cameras failed: {}", String::from_utf8_lossy(&output.stderr));
+    let cameras: Vec<Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let fake = cameras.iter().find(|camera| camera["index"] == FILE_CAMERA_INDEX).expect("fake camera listed");
+    assert_eq!(fake["fake"], true);
+
+    let output = run(&source, &["list-cameras"]);
+    assert!(output.status.success());
+    let table = String::from_utf8(output.stdout).unwrap();
+    assert!(table.starts_with("INDEX"));
+    assert!(table.lines().any(|line| line.starts_with(&FILE_CAMERA_INDEX.to_string()) && line.contains("yes (fake)")));
+    let _ = std::fs::remove_dir_all(&source);
+}
+
+/// A webcam on loopback answering every request with `body` as a PNG;
+/// returns its snapshot URL
+fn serve_png(body: Vec<u8>) -> String {
+    let (base, _) = serve(move |_| HttpResponse::new("200 OK", "image/png", body.clone()));
+    format!("{}/snapshot.png", base)
+}
+
+/// Run the binary's shodan subcommand with `args` and no Shodan key
+fn run_shodan(data_dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mcp-webcam"))
+        .args(["--log-level", "warn"])
+        .arg("--data-dir")
+        .arg(data_dir)
+        .args(args)
+        .env_remove("SHODAN_API_KEY")
+        .env_remove("SHODAN_API_KEY_FILE")
+        .env_remove("MCP_WEBCAM_DISABLE_REMOTE")
+        .env_remove("MCP_WEBCAM_LOG_FILE")
+        .output()
+        .expect("run binary")
+}
+
+#[test]
+fn shodan_search_without_a_key_exits_as_unauthorized() {
+    let data_dir = temp_dir("cli-shodan-key");
+    let output = run_shodan(&data_dir, &["shodan", "search", "--query", "webcam", "--json"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no Shodan API key"));
+
+    let output = run_shodan(&data_dir, &["--no-remote", "shodan", "search", "--query", "webcam"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("remote webcam access is turned off"));
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn shodan_fetch_writes_the_image_within_the_target_policy() {
+    let data_dir = temp_dir("cli-shodan-fetch");
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::RgbImage::from_pixel(16, 12, image::Rgb([10, 20, 30])).write_to(&mut png, image::ImageFormat::Png).unwrap();
+    let png = png.into_inner();
+    let url = serve_png(png.clone());
+
+    // Loopback is refused unless private targets are allowed
+    let output = run_shodan(&data_dir, &["shodan", "fetch", "--url", &url]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Blocked request"));
+
+    let out = data_dir.join("fetched.png");
+    let output = run_shodan(&data_dir, &["--allow-private-targets", "shodan", "fetch", "--url", &url, "--out", out.to_str().unwrap()]);
+    assert!(output.status.success(), "fetch failed: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(&out).unwrap(), png);
+
+    let output = run_shodan(&data_dir, &["--allow-private-targets", "shodan", "fetch", "--url", &url]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, png);
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
@@ -0,0 +1,46 @@
+//! Log events forwarded to the client after `logging/setLevel`. The
+//! forwarding state is per process, so this file holds a single test.
+
+mod common;
+
+use common::request;
+use mcp_webcam::protocol::INVALID_PARAMS;
+use mcp_webcam::{ClientLog, MockBackend, ShodanClient, WebcamMcpServer};
+use serde_json::json;
+use tracing_subscriber::layer::SubscriberExt;
+
+#[tokio::test]
+async fn events_at_the_requested_level_are_forwarded_without_secrets() {
+    let subscriber = tracing_subscriber::registry().with(ClientLog::global().layer());
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(ShodanClient::new("shodan-key-1234".to_string()))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+    let initialized = request(&server, "initialize", json!({ "protocolVersion": "2025-06-18" }));
+    assert_eq!(initialized["result"]["capabilities"]["logging"], json!({}));
+
+    // Nothing is forwarded before the client asks
+    tracing::warn!("before setLevel");
+    assert!(server.take_notifications().is_empty());
+
+    let invalid = request(&server, "logging/setLevel", json!({ "level": "verbose" }));
+    assert_eq!(invalid["error"]["code"], INVALID_PARAMS);
+    assert_eq!(request(&server, "logging/setLevel", json!({ "level": "warning" }))["result"], json!({}));
+    server.take_notifications();
+
+    tracing::info!("below the level");
+    tracing::warn!(api_key = "anything", "Shodan rejected key shodan-key-1234");
+    let messages = server.take_notifications();
+    assert_eq!(messages.len(), 1, "{:?}", messages);
+    assert_eq!(messages[0]["method"], "notifications/message");
+    let params = &messages[0]["params"];
+    assert_eq!(params["level"], "warning");
+    assert_eq!(params["logger"], module_path!());
+    assert_eq!(params["data"]["message"], "Shodan rejected key [REDACTED]");
+    assert_eq!(params["data"]["api_key"], "[REDACTED]");
+}
@@ -0,0 +1,268 @@
+//! Helpers shared by the integration tests: a stand-in HTTP server on
+//! loopback, remote webcam and image fixtures, and servers built around
+//! the mock backend with a Shodan client pointed at a stand-in.
+
+// Each test binary uses only some of these
+#![allow(dead_code)]
+
+use image::{ImageFormat, Rgb, RgbImage};
+use mcp_webcam::protocol::Server;
+use mcp_webcam::shodan::{RemoteWebcam, ShodanLocation, WebcamAccessType};
+use mcp_webcam::{MockBackend, ShodanClient, WebcamMcpServer, WebcamMcpServerBuilder};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A Shodan host search finding three webcams: 203.0.113.7:8080 over
+/// HTTP, 198.51.100.23:554 over RTSP and one on IPv6
+pub const HOST_SEARCH: &str = include_str!("../fixtures/shodan_host_search.json");
+pub const ONVIF_CAPABILITIES: &str = include_str!("../fixtures/onvif_get_capabilities.xml");
+pub const ONVIF_PROFILES: &str = include_str!("../fixtures/onvif_get_profiles.xml");
+
+/// Key given to Shodan clients talking to a stand-in
+pub const SHODAN_KEY: &str = "sk-not-a-real-shodan-key";
+
+/// A request a stand-in received
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    /// Path without the query string
+    pub path: String,
+    /// Query string without the `?`, empty if there was none
+    pub query: String,
+    /// Header lines as received, after the request line
+    pub head: String,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// Value of the first header called `name`, in any case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+
+    /// URL the stand-in was reached at, from the Host header
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.header("host").unwrap_or_default())
+    }
+
+    /// SOAP action named in the Content-Type, without its namespace
+    pub fn soap_action(&self) -> Option<&str> {
+        let content_type = self.header("content-type")?;
+        let start = content_type.find("action=\"")? + 8;
+        content_type[start..].split('"').next()?.rsplit('/').next()
+    }
+}
+
+/// An answer for a stand-in to send
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: &'static str,
+    /// Header lines, each ending in CRLF; Content-Length and Connection
+    /// are added when sending
+    pub headers: String,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(status: &'static str, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: format!("Content-Type: {}\r\n", content_type),
+            body: body.into(),
+        }
+    }
+
+    /// `200 OK` with a JSON body
+    pub fn json(body: impl Into<Vec<u8>>) -> Self {
+        Self::new("200 OK", "application/json", body)
+    }
+
+    /// No body, only a status and `headers`
+    pub fn empty(status: &'static str) -> Self {
+        Self {
+            status,
+            headers: String::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push_str(&format!("{}: {}\r\n", name, value));
+        self
+    }
+}
+
+/// Requests a stand-in received, in the order they were read
+#[derive(Debug, Clone, Default)]
+pub struct Requests(Arc<Mutex<Vec<HttpRequest>>>);
+
+impl Requests {
+    pub fn all(&self) -> Vec<HttpRequest> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        self.0.lock().unwrap().iter().map(|request| request.path.clone()).collect()
+    }
+
+    /// Requests whose path starts with `prefix`
+    pub fn count(&self, prefix: &str) -> usize {
+        self.0.lock().unwrap().iter().filter(|request| request.path.starts_with(prefix)).count()
+    }
+}
+
+/// Serve HTTP on loopback, answering every request with `answer` on a
+/// thread of its own; returns the base URL and the requests received.
+/// A request is recorded before it is answered.
+pub fn serve(answer: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static) -> (String, Requests) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Requests::default();
+    let recorded = requests.clone();
+    let answer = Arc::new(answer);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { break };
+            let (recorded, answer) = (recorded.clone(), Arc::clone(&answer));
+            std::thread::spawn(move || {
+                let mut reader = BufReader::new(stream);
+                let Some(request) = read_request(&mut reader) else { return };
+                recorded.0.lock().unwrap().push(request.clone());
+
+                let response = answer(&request);
+                let mut bytes = format!(
+                    "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    response.status,
+                    response.headers,
+                    response.body.len()
+                )
+                .into_bytes();
+                bytes.extend_from_slice(&response.body);
+                let _ = reader.get_mut().write_all(&bytes);
+            });
+        }
+    });
+    (base, requests)
+}
+
+/// Read a request line, headers and a Content-Length body; `None` if the
+/// client went away first
+fn read_request(reader: &mut impl BufRead) -> Option<HttpRequest> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).ok()?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let (path, query) = (path.to_string(), query.to_string());
+
+    let mut head = String::new();
+    let mut line = String::new();
+    while reader.read_line(&mut line).ok()? > 2 {
+        head.push_str(&line);
+        line.clear();
+    }
+    let mut request = HttpRequest { method, path, query, head, body: Vec::new() };
+    let length = request.header("content-length").and_then(|value| value.parse().ok()).unwrap_or(0);
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body).ok()?;
+    Some(request)
+}
+
+/// Shodan client for a stand-in at `base_url`, allowed to fetch from
+/// loopback and never waiting on reverse DNS
+pub fn shodan_client(base_url: &str) -> ShodanClient {
+    ShodanClient::builder()
+        .base_url(base_url)
+        .allow_private_targets(true)
+        .reverse_dns_budget(Duration::ZERO)
+        .build(SHODAN_KEY)
+        .unwrap()
+}
+
+/// Server with one mock camera and a Shodan client for a stand-in at
+/// `base_url`, with no request pacing or host cooldown to wait on and no
+/// preview
+pub fn server_builder(base_url: &str) -> WebcamMcpServerBuilder {
+    WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(shodan_client(base_url))
+        .shodan_requests_per_second(100.0)
+        .host_cooldown(Duration::ZERO)
+        .preview(false)
+}
+
+/// Send a JSON-RPC request and return the whole response
+pub fn request(server: &Server, method: &str, params: Value) -> Value {
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    server.handle_message(&request.to_string()).unwrap()
+}
+
+/// Call a tool and return the `tools/call` result
+pub fn call(server: &Server, name: &str, arguments: Value) -> Value {
+    request(server, "tools/call", json!({ "name": name, "arguments": arguments }))["result"].clone()
+}
+
+/// Call a tool and return the result's structured content
+pub fn call_structured(server: &Server, name: &str, arguments: Value) -> Value {
+    call(server, name, arguments)["structuredContent"].clone()
+}
+
+/// An HTTP webcam at `ip:port` with nothing else known about it
+pub fn webcam(ip: &str, port: u16) -> RemoteWebcam {
+    RemoteWebcam {
+        ip: ip.to_string(),
+        port,
+        url: format!("http://{}:{}/", ip, port),
+        hostname: None,
+        location: None,
+        org: None,
+        product: None,
+        last_seen: String::new(),
+        access_type: WebcamAccessType::HTTP,
+        classification: None,
+        sources: Vec::new(),
+    }
+}
+
+/// Somewhere in `country_code`, with nothing else known
+pub fn location(country_code: &str) -> ShodanLocation {
+    ShodanLocation {
+        country_code: Some(country_code.to_string()),
+        country_name: None,
+        city: None,
+        region_code: None,
+        latitude: None,
+        longitude: None,
+    }
+}
+
+/// A 320x240 green JPEG
+pub fn jpeg() -> Vec<u8> {
+    let mut buffer = Cursor::new(Vec::new());
+    RgbImage::from_pixel(320, 240, Rgb([20, 200, 20])).write_to(&mut buffer, ImageFormat::Jpeg).unwrap();
+    buffer.into_inner()
+}
+
+/// An ONVIF GetSnapshotUri or GetStreamUri answer giving `uri`
+pub fn media_uri(uri: &str) -> String {
+    format!(
+        r#"<env:Envelope xmlns:env="http://www.w3.org/2003/05/soap-envelope" xmlns:trt="http://www.onvif.org/ver10/media/wsdl" xmlns:tt="http://www.onvif.org/ver10/schema"><env:Body><trt:GetSnapshotUriResponse><trt:MediaUri><tt:Uri>{}</tt:Uri><tt:InvalidAfterConnect>false</tt:InvalidAfterConnect></trt:MediaUri></trt:GetSnapshotUriResponse></env:Body></env:Envelope>"#,
+        uri
+    )
+}
+
+/// An empty directory for `name`, separate for each test run
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mcp-webcam-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,124 @@
+//! Consent mode: camera tools refused until `grant_consent` is given the
+//! one-time code shown to the user.
+
+mod common;
+
+use common::{call, request};
+use mcp_webcam::protocol::INVALID_REQUEST;
+use mcp_webcam::{ConsentGate, ConsentScope, MockBackend, ShodanClient, WebcamMcpServer};
+use serde_json::json;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Codes shown to the user, by scope
+type ShownCodes = Arc<Mutex<Vec<(ConsentScope, String)>>>;
+
+/// Gate that hands its codes to the test instead of the console
+fn gate(local: bool, remote: bool, duration: Duration) -> (ConsentGate, ShownCodes) {
+    let codes = Arc::new(Mutex::new(Vec::new()));
+    let shown = Arc::clone(&codes);
+    let gate = ConsentGate::new(local, remote, duration)
+        .on_code(move |scope, code| shown.lock().unwrap().push((scope, code.to_string())));
+    (gate, codes)
+}
+
+#[tokio::test]
+async fn captures_wait_for_the_one_time_code() {
+    let (gate, codes) = gate(true, false, Duration::from_secs(60));
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .consent(gate)
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let refused = call(&server, "capture_image", json!({ "camera_index": 0 }));
+    assert_eq!(refused["structuredContent"]["error"]["code"], "CONSENT_REQUIRED");
+    assert_eq!(refused["structuredContent"]["error"]["details"]["scope"], "local");
+    let code = codes.lock().unwrap()[0].1.clone();
+    assert_eq!(code.len(), 6);
+    // The client never sees the code
+    assert!(!refused.to_string().contains(&code));
+
+    // Refusing again doesn't issue another code; tools without a camera run
+    call(&server, "capture_image", json!({ "camera_index": 0 }));
+    assert_eq!(codes.lock().unwrap().len(), 1);
+    assert_eq!(call(&server, "list_cameras", json!({}))["isError"], false);
+    let snapshot = request(&server, "resources/read", json!({ "uri": "webcam://camera/0/snapshot" }));
+    assert_eq!(snapshot["error"]["code"], INVALID_REQUEST);
+
+    let info = &call(&server, "get_server_info", json!({}))["structuredContent"]["server_info"]["consent"];
+    assert_eq!(info["local"]["required"], true);
+    assert_eq!(info["local"]["granted"], false);
+    assert!(info["local"]["gated_tools"].as_array().unwrap().contains(&json!("start_preview")));
+    assert_eq!(info["remote"]["required"], false);
+
+    // A wrong code throws the outstanding one away
+    assert_eq!(call(&server, "grant_consent", json!({ "code": "not it" }))["isError"], true);
+    assert_eq!(call(&server, "grant_consent", json!({ "code": code }))["isError"], true);
+    call(&server, "capture_image", json!({ "camera_index": 0 }));
+    let code = codes.lock().unwrap()[1].1.clone();
+
+    let granted = call(&server, "grant_consent", json!({ "code": code }));
+    assert_eq!(granted["structuredContent"]["consent"]["scope"], "local");
+    assert_eq!(granted["structuredContent"]["consent"]["duration_seconds"], 60);
+    assert_eq!(call(&server, "capture_image", json!({ "camera_index": 0 }))["isError"], false);
+    let info = &call(&server, "get_server_info", json!({}))["structuredContent"]["server_info"]["consent"];
+    assert_eq!(info["local"]["granted"], true);
+    assert!(info["local"]["expires_at"].is_string());
+    // Codes are single use
+    assert_eq!(call(&server, "grant_consent", json!({ "code": code }))["isError"], true);
+}
+
+#[tokio::test]
+async fn remote_captures_are_gated_separately_and_consent_expires() {
+    let (gate, codes) = gate(false, true, Duration::from_secs(1));
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(ShodanClient::new("test-key".to_string()))
+        .consent(gate.clone())
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    assert_eq!(call(&server, "capture_image", json!({ "camera_index": 0 }))["isError"], false);
+    // Refused before anything is fetched
+    let refused = call(&server, "capture_remote_image", json!({ "url": "http://192.0.2.1/snapshot.jpg" }));
+    assert_eq!(refused["structuredContent"]["error"]["details"]["scope"], "remote");
+    assert_eq!(codes.lock().unwrap()[0].0, ConsentScope::Remote);
+
+    let code = codes.lock().unwrap()[0].1.clone();
+    assert!(gate.grant(&code).is_ok());
+    assert!(gate.is_granted(ConsentScope::Remote));
+    std::thread::sleep(Duration::from_millis(1100));
+    assert!(!gate.is_granted(ConsentScope::Remote));
+    assert!(gate.check("capture_remote_image").is_err());
+}
+
+#[tokio::test]
+async fn onvif_snapshots_wait_for_remote_consent() {
+    let (gate, codes) = gate(false, true, Duration::from_secs(60));
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client_settings(ShodanClient::builder().allow_private_targets(true))
+        .consent(gate)
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+    // Nothing listens there, so an approved call fails on connecting
+    let closed = format!("http://{}/onvif/device_service", TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap());
+
+    let refused = call(&server, "capture_onvif_snapshot", json!({ "url": closed }));
+    assert_eq!(refused["structuredContent"]["error"]["code"], "CONSENT_REQUIRED");
+    assert_eq!(refused["structuredContent"]["error"]["details"]["scope"], "remote");
+
+    let code = codes.lock().unwrap()[0].1.clone();
+    assert_eq!(call(&server, "grant_consent", json!({ "code": code }))["isError"], false);
+    let attempted = call(&server, "capture_onvif_snapshot", json!({ "url": closed }));
+    assert_eq!(attempted["isError"], true);
+    assert_ne!(attempted["structuredContent"]["error"]["code"], "CONSENT_REQUIRED");
+}
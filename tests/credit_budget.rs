@@ -0,0 +1,128 @@
+//! The Shodan request budget: hourly and daily windows, usage kept across
+//! restarts, and refusals in tools against a stand-in for Shodan's API.
+
+mod common;
+
+use chrono::{DateTime, TimeZone, Utc};
+use common::{call_structured as call, serve, server_builder, shodan_client, HttpResponse, Requests, HOST_SEARCH};
+use mcp_webcam::{BudgetWindow, CreditBudget, ShodanError};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 10, 16, hour, minute, 0).unwrap()
+}
+
+/// Answer searches from the fixture, host lookups with an empty host and
+/// account lookups on loopback; returns the base URL and the requests
+/// received
+fn serve_shodan() -> (String, Requests) {
+    serve(|request| match request.path.as_str() {
+        "/shodan/host/search" => HttpResponse::json(HOST_SEARCH),
+        "/api-info" => HttpResponse::json(r#"{"plan": "dev", "query_credits": 100, "scan_credits": 5}"#),
+        _ => HttpResponse::json(r#"{"ip_str": "203.0.113.7", "ports": [8080], "data": []}"#),
+    })
+}
+
+#[test]
+fn requests_are_refused_until_the_window_resets() {
+    let budget = CreditBudget::new(Some(2), Some(3));
+    assert!(budget.claim_at(at(10, 5)).is_ok());
+    assert!(budget.claim_at(at(10, 30)).is_ok());
+
+    let refused = budget.claim_at(at(10, 45)).unwrap_err();
+    assert_eq!(refused.window, BudgetWindow::Hour);
+    assert_eq!(refused.limit, 2);
+    assert_eq!(refused.resets_at, at(11, 0));
+    assert_eq!(refused.retry_after(at(10, 45)), Duration::from_secs(15 * 60));
+
+    // A new hour, but the day's third request is its last
+    assert!(budget.claim_at(at(11, 10)).is_ok());
+    let refused = budget.claim_at(at(11, 20)).unwrap_err();
+    assert_eq!(refused.window, BudgetWindow::Day);
+    assert_eq!(refused.resets_at, Utc.with_ymd_and_hms(2026, 10, 17, 0, 0, 0).unwrap());
+
+    let status = budget.status_at(at(11, 30));
+    assert!(status.limited);
+    assert_eq!((status.hour.used, status.hour.remaining), (1, Some(1)));
+    assert_eq!((status.day.used, status.day.remaining), (3, Some(0)));
+    assert_eq!(status.refused, 2);
+
+    let tomorrow = Utc.with_ymd_and_hms(2026, 10, 17, 0, 1, 0).unwrap();
+    assert!(budget.claim_at(tomorrow).is_ok());
+}
+
+#[test]
+fn an_unlimited_budget_still_counts() {
+    let budget = CreditBudget::unlimited();
+    for minute in 0..50 {
+        assert!(budget.claim_at(at(9, minute)).is_ok());
+    }
+    let status = budget.status_at(at(9, 59));
+    assert!(!status.limited);
+    assert_eq!((status.hour.used, status.hour.remaining), (50, None));
+}
+
+#[test]
+fn usage_survives_a_restart() {
+    let dir = std::env::temp_dir().join(format!("mcp-webcam-budget-{}", std::process::id()));
+    let path = dir.join("state").join("shodan_usage.json");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let budget = CreditBudget::open(&path, Some(5), None);
+    budget.claim().unwrap();
+    budget.claim().unwrap();
+    drop(budget);
+
+    let reopened = CreditBudget::open(&path, Some(5), None);
+    assert_eq!(reopened.status().hour.used, 2);
+    assert_eq!(reopened.status().hour.remaining, Some(3));
+
+    std::fs::write(&path, "not json").unwrap();
+    assert_eq!(CreditBudget::open(&path, Some(5), None).status().hour.used, 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn refused_requests_are_never_sent() {
+    let (base_url, requests) = serve_shodan();
+    let client = shodan_client(&base_url).with_credit_budget(Arc::new(CreditBudget::new(Some(1), None)));
+    client.host("203.0.113.7", false).await.unwrap();
+    let refused = client.search("webcam", None).await.unwrap_err();
+    assert!(matches!(refused, ShodanError::BudgetExceeded(ref exceeded) if exceeded.window == BudgetWindow::Hour));
+    assert!(refused.to_string().contains("1 request(s) per hour"));
+
+    // Counts and account lookups cost no query credits and aren't counted
+    client.api_info().await.unwrap();
+    assert_eq!(requests.paths(), ["/shodan/host/203.0.113.7", "/api-info"]);
+}
+
+#[test]
+fn tools_report_and_enforce_the_budget() {
+    let (base_url, requests) = serve_shodan();
+    let server = server_builder(&base_url).shodan_budget(Some(2), None).build().build_server().unwrap();
+
+    // A search counts one request per query, not per result
+    let found = call(&server, "search_webcams", json!({ "limit": 10, "max_queries": 1 }));
+    assert_eq!(found["total"], 3);
+    let account = call(&server, "shodan_account_info", json!({}));
+    assert_eq!(account["budget"]["hour"]["used"], 1);
+    assert_eq!(account["budget"]["hour"]["remaining"], 1);
+    assert_eq!(account["budget"]["day"]["limit"], Value::Null);
+
+    call(&server, "shodan_host_info", json!({ "ip": "203.0.113.7" }));
+    let refused = call(&server, "search_webcams", json!({ "limit": 10, "max_queries": 1 }));
+    let error = &refused["error"];
+    assert_eq!(error["code"], "SHODAN_BUDGET_EXCEEDED");
+    assert_eq!(error["details"]["window"], "hour");
+    assert_eq!(error["details"]["limit"], 2);
+    assert!(error["details"]["resets_at"].as_str().unwrap().ends_with(":00:00Z"));
+    assert!(error["details"]["retry_after_seconds"].as_u64().unwrap() <= 3600);
+    assert_eq!(requests.count("/shodan/host/"), 2);
+
+    let stats = &call(&server, "get_server_stats", json!({}))["stats"]["shodan_budget"];
+    assert_eq!(stats["hour"]["used"], 2);
+    assert_eq!(stats["hour"]["remaining"], 0);
+    assert_eq!(stats["refused"], 1);
+}
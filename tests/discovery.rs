@@ -0,0 +1,134 @@
+//! Searches routed to several discovery services: results merged
+//! alternately, endpoints found twice listed once with both sources, a
+//! failing service left out, and the remote tools offered for a service
+//! other than Shodan alone.
+
+mod common;
+
+use async_trait::async_trait;
+use common::call;
+use mcp_webcam::shodan::{select_queries, QueryPlan, WebcamSearch};
+use mcp_webcam::{
+    Discovery, MockBackend, ProviderCredits, RemoteWebcam, SearchFilters, ShodanClient, ShodanError, ShodanHost,
+    WebcamDiscoveryProvider, WebcamMcpServer,
+};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A discovery service that finds `webcams`, or fails with `Unauthorized`
+/// when it has none
+struct Stub {
+    name: &'static str,
+    webcams: Vec<RemoteWebcam>,
+}
+
+#[async_trait]
+impl WebcamDiscoveryProvider for Stub {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn search_webcams(&self, _limit: Option<u32>, _filters: &SearchFilters, _plan: &QueryPlan) -> Result<WebcamSearch, ShodanError> {
+        if self.webcams.is_empty() {
+            return Err(ShodanError::Unauthorized);
+        }
+        Ok(WebcamSearch { webcams: self.webcams.clone(), queries: vec![format!("{} query", self.name)], ..Default::default() })
+    }
+
+    async fn host(&self, ip: &str, _history: bool) -> Result<ShodanHost, ShodanError> {
+        if self.webcams.is_empty() {
+            return Err(ShodanError::NotFound(ip.to_string()));
+        }
+        Ok(serde_json::from_str(include_str!("fixtures/shodan_host.json")).unwrap())
+    }
+
+    async fn credits(&self) -> Result<ProviderCredits, ShodanError> {
+        Ok(ProviderCredits { provider: self.name.to_string(), plan: None, query_credits: 42, scan_credits: None, resets_at: None })
+    }
+}
+
+fn webcam(last_octet: u8, hostname: Option<&str>) -> RemoteWebcam {
+    RemoteWebcam { hostname: hostname.map(str::to_string), ..common::webcam(&format!("192.0.2.{}", last_octet), 80) }
+}
+
+fn stub(name: &'static str, webcams: Vec<RemoteWebcam>) -> Arc<dyn WebcamDiscoveryProvider> {
+    Arc::new(Stub { name, webcams })
+}
+
+fn plan() -> QueryPlan {
+    select_queries(&["webcam".to_string()], None, None).unwrap()
+}
+
+#[tokio::test]
+async fn results_are_taken_alternately_and_merged_once() {
+    let discovery = Discovery::new(vec![
+        stub("first", vec![webcam(1, None), webcam(2, None), webcam(3, None)]),
+        stub("second", vec![webcam(2, Some("cam.example.net")), webcam(4, None)]),
+    ]);
+    let merged = discovery.search_webcams(Some(3), &SearchFilters::default(), &plan()).await.unwrap();
+
+    let ips: Vec<&str> = merged.found.webcams.iter().map(|webcam| webcam.ip.as_str()).collect();
+    assert_eq!(ips, vec!["192.0.2.1", "192.0.2.2", "192.0.2.4"]);
+    assert_eq!(merged.found.webcams[0].sources, vec!["first"]);
+    assert_eq!(merged.found.webcams[1].sources, vec!["second", "first"]);
+    assert_eq!(merged.found.webcams[1].hostname.as_deref(), Some("cam.example.net"));
+    assert_eq!(merged.found.queries, vec!["first query", "second query"]);
+    assert_eq!(merged.providers, vec!["first", "second"]);
+    assert!(merged.failures.is_empty());
+}
+
+#[tokio::test]
+async fn a_failing_service_is_reported_unless_all_fail() {
+    let discovery = Discovery::new(vec![stub("broken", Vec::new()), stub("working", vec![webcam(1, None)])]);
+    let merged = discovery.search_webcams(None, &SearchFilters::default(), &plan()).await.unwrap();
+    assert_eq!(merged.providers, vec!["working"]);
+    assert_eq!(merged.failures[0].provider, "broken");
+    assert_eq!(merged.found.webcams.len(), 1);
+
+    let (source, host) = discovery.host("192.0.2.1", false).await.unwrap();
+    assert_eq!(source, "working");
+    assert!(!host.ports.is_empty());
+
+    let all_broken = Discovery::new(vec![stub("broken", Vec::new()), stub("also broken", Vec::new())]);
+    let failed = all_broken.search_webcams(None, &SearchFilters::default(), &plan()).await;
+    assert!(matches!(failed, Err(ShodanError::Unauthorized)));
+}
+
+#[tokio::test]
+async fn a_client_without_a_key_sends_nothing_to_shodan() {
+    let client = ShodanClient::builder().build("").unwrap();
+    assert!(!client.has_api_key());
+    assert!(matches!(client.api_info().await, Err(ShodanError::NoApiKey)));
+}
+
+#[test]
+fn remote_tools_are_offered_for_another_service_alone() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .discovery_provider(stub("stub", vec![webcam(1, None), webcam(2, None)]))
+        .host_cooldown(Duration::ZERO)
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let found = call(&server, "search_webcams", json!({ "limit": 5 }));
+    assert_eq!(found["structuredContent"]["total"], 2);
+    assert_eq!(found["structuredContent"]["webcams"][0]["sources"], json!(["stub"]));
+    assert_eq!(found["structuredContent"]["metadata"]["providers"], json!(["stub"]));
+
+    let grouped = call(&server, "search_webcams", json!({ "group_by_host": true }));
+    assert_eq!(grouped["structuredContent"]["hosts"][0]["endpoints"][0]["sources"], json!(["stub"]));
+
+    let host = call(&server, "shodan_host_info", json!({ "ip": "192.0.2.1" }));
+    assert_eq!(host["structuredContent"]["source"], "stub");
+
+    let account = call(&server, "shodan_account_info", json!({}));
+    assert_eq!(account["structuredContent"]["account"]["provider"], "stub");
+    assert_eq!(account["structuredContent"]["accounts"].as_array().unwrap().len(), 1);
+
+    // Counting is Shodan's alone, and there's no key
+    let counted = call(&server, "count_webcams", json!({}));
+    assert_eq!(counted["structuredContent"]["error"]["code"], "SHODAN_UNAUTHORIZED");
+}
@@ -0,0 +1,136 @@
+//! Exporting discovered webcams as GeoJSON, CSV and JSON, and the
+//! `export_webcams` tool writing them inline or to the data directory.
+
+mod common;
+
+use mcp_webcam::export::export_webcams;
+use mcp_webcam::shodan::ShodanLocation;
+use mcp_webcam::{CaptureStore, ExportFormat, MockBackend, RemoteWebcam, RetentionPolicy, ShodanClient, WebcamAccessType, WebcamMcpServer};
+use serde_json::{json, Value};
+
+fn webcam(ip: &str, place: Option<(f64, f64)>, product: &str) -> RemoteWebcam {
+    RemoteWebcam {
+        url: format!("http://{}:8080/mjpeg", ip),
+        location: Some(ShodanLocation {
+            city: Some("London".to_string()),
+            latitude: place.map(|(latitude, _)| latitude),
+            longitude: place.map(|(_, longitude)| longitude),
+            ..common::location("GB")
+        }),
+        product: Some(product.to_string()),
+        last_seen: "2026-01-01T00:00:00".to_string(),
+        access_type: WebcamAccessType::MJPEG,
+        ..common::webcam(ip, 8080)
+    }
+}
+
+fn webcams() -> Vec<RemoteWebcam> {
+    vec![webcam("192.0.2.1", Some((51.5074, -0.1278)), "webcamXP"), webcam("192.0.2.2", None, "Camera, \"outdoor\"")]
+}
+
+#[test]
+fn geojson_places_webcams_with_coordinates_and_hands_back_the_rest() {
+    let export = export_webcams(&webcams(), ExportFormat::GeoJson);
+    assert_eq!(export.exported, 1);
+    assert_eq!(export.without_coordinates.len(), 1);
+    assert_eq!(export.without_coordinates[0].ip, "192.0.2.2");
+
+    let document: Value = serde_json::from_str(&export.document).unwrap();
+    assert_eq!(document["type"], "FeatureCollection");
+    let feature = &document["features"][0];
+    // Longitude first
+    assert_eq!(feature["geometry"], json!({ "type": "Point", "coordinates": [-0.1278, 51.5074] }));
+    assert_eq!(feature["properties"]["ip"], "192.0.2.1");
+    assert_eq!(feature["properties"]["port"], 8080);
+    assert_eq!(feature["properties"]["product"], "webcamXP");
+    assert_eq!(feature["properties"]["last_seen"], "2026-01-01T00:00:00");
+}
+
+#[test]
+fn csv_and_json_keep_every_webcam() {
+    let csv = export_webcams(&webcams(), ExportFormat::Csv);
+    assert!(csv.without_coordinates.is_empty());
+    let lines: Vec<&str> = csv.document.lines().collect();
+    assert_eq!(lines[0], "ip,port,url,product,last_seen,hostname,org,country_code,city,vendor,latitude,longitude");
+    assert_eq!(lines[1], "192.0.2.1,8080,http://192.0.2.1:8080/mjpeg,webcamXP,2026-01-01T00:00:00,,,GB,London,,51.5074,-0.1278");
+    assert_eq!(lines[2], "192.0.2.2,8080,http://192.0.2.2:8080/mjpeg,\"Camera, \"\"outdoor\"\"\",2026-01-01T00:00:00,,,GB,London,,,");
+
+    let json = export_webcams(&webcams(), ExportFormat::Json);
+    let document: Value = serde_json::from_str(&json.document).unwrap();
+    assert_eq!(document.as_array().unwrap().len(), 2);
+    assert_eq!(json.exported, 2);
+
+    assert_eq!("GeoJSON".parse::<ExportFormat>(), Ok(ExportFormat::GeoJson));
+    assert!("kml".parse::<ExportFormat>().unwrap_err().contains("geojson, csv, json"));
+}
+
+fn call(server: &mcp_webcam::protocol::Server, arguments: Value) -> Value {
+    common::call_structured(server, "export_webcams", arguments)
+}
+
+#[test]
+fn exports_are_returned_inline_or_saved_under_the_name_given() {
+    let dir = std::env::temp_dir().join(format!("mcp-webcam-export-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = dir.join("state").join("remote_webcams.json");
+    std::fs::create_dir_all(db.parent().unwrap()).unwrap();
+    std::fs::write(&db, serde_json::to_string(&webcams()).unwrap()).unwrap();
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(ShodanClient::new("test-key".to_string()))
+        .capture_store(CaptureStore::new(&dir, RetentionPolicy::default()))
+        .webcam_db(&db)
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let inline = call(&server, json!({ "format": "geojson" }));
+    assert_eq!(inline["exported"], 1);
+    assert_eq!(inline["path"], Value::Null);
+    assert_eq!(inline["without_coordinates"][0]["ip"], "192.0.2.2");
+    let document: Value = serde_json::from_str(inline["document"].as_str().unwrap()).unwrap();
+    assert_eq!(document["features"].as_array().unwrap().len(), 1);
+
+    // Names are given the prefix retention looks for
+    let saved = call(&server, json!({ "format": "csv", "file_name": "cameras.csv" }));
+    assert_eq!(saved["document"], Value::Null);
+    assert_eq!(saved["path"], dir.join("webcams_cameras.csv").display().to_string());
+    assert_eq!(std::fs::read_to_string(dir.join("webcams_cameras.csv")).unwrap().lines().count(), 3);
+    let saved = call(&server, json!({ "format": "json", "file_name": "webcams_all.json" }));
+    assert_eq!(saved["path"], dir.join("webcams_all.json").display().to_string());
+
+    let escaping = call(&server, json!({ "format": "csv", "file_name": "../webcams.csv" }));
+    assert_eq!(escaping["error"]["code"], "INVALID_PARAMS");
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn exports_never_replace_a_file() {
+    let dir = common::temp_dir("export-existing");
+    let db = dir.join("state").join("remote_webcams.json");
+    std::fs::create_dir_all(db.parent().unwrap()).unwrap();
+    std::fs::write(&db, serde_json::to_string(&webcams()).unwrap()).unwrap();
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(ShodanClient::new("test-key".to_string()))
+        .capture_store(CaptureStore::new(&dir, RetentionPolicy::default()))
+        .webcam_db(&db)
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    // A capture resource's file keeps its image: the export lands beside it
+    std::fs::write(dir.join("capture_1234.jpg"), b"jpeg").unwrap();
+    let beside = call(&server, json!({ "format": "csv", "file_name": "capture_1234.jpg" }));
+    assert_eq!(beside["path"], dir.join("webcams_capture_1234.jpg").display().to_string());
+    assert_eq!(std::fs::read(dir.join("capture_1234.jpg")).unwrap(), b"jpeg");
+
+    // An earlier export isn't overwritten either
+    let again = call(&server, json!({ "format": "geojson", "file_name": "capture_1234.jpg" }));
+    assert_eq!(again["error"]["code"], "INVALID_PARAMS");
+    assert!(again["error"]["message"].as_str().unwrap().contains("already exists"));
+    assert_eq!(std::fs::read_to_string(dir.join("webcams_capture_1234.jpg")).unwrap().lines().count(), 3);
+    let _ = std::fs::remove_dir_all(dir);
+}
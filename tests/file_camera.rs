@@ -0,0 +1,38 @@
+//! The file-backed fake camera replays images through the capture pipeline.
+
+use mcp_webcam::webcam::FILE_CAMERA_INDEX;
+use mcp_webcam::{CaptureOptions, WebcamManager};
+
+#[test]
+fn replays_directory_through_pipeline() {
+    let dir = std::env::temp_dir().join(format!("mcp-webcam-fake-source-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    image::RgbImage::from_pixel(64, 48, image::Rgb([255, 0, 0])).save(dir.join("a.png")).unwrap();
+    image::RgbImage::from_pixel(64, 48, image::Rgb([0, 0, 255])).save(dir.join("b.png")).unwrap();
+    std::fs::write(dir.join("notes.txt"), "not an image").unwrap();
+
+    std::env::set_var("MCP_WEBCAM_FAKE_SOURCE", &dir);
+    let mut manager = WebcamManager::new();
+
+    let cameras = manager.list_cameras().unwrap();
+    let camera = cameras.iter().find(|c| c.index == FILE_CAMERA_INDEX).expect("fake camera listed");
+    assert!(camera.fake);
+
+    let options = CaptureOptions { rotation: Some(90), ..Default::default() };
+    let colours: Vec<[u8; 3]> = (0..3)
+        .map(|_| {
+            let frame = manager.capture_frame(Some(FILE_CAMERA_INDEX), &options).unwrap();
+            assert_eq!(frame.image.dimensions(), (48, 64));
+            frame.image.get_pixel(0, 0).0
+        })
+        .collect();
+    // Files are returned in name order and the sequence wraps around
+    assert_eq!(colours, vec![[255, 0, 0], [0, 0, 255], [255, 0, 0]]);
+
+    let resized = manager
+        .capture_image_with_options(Some(FILE_CAMERA_INDEX), &CaptureOptions { resolution: Some((32, 24)), ..Default::default() })
+        .unwrap();
+    assert_eq!((resized.width, resized.height), (32, 24));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
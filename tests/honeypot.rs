@@ -0,0 +1,158 @@
+//! Honeypot scores against a stand-in for Shodan's API: looked up once
+//! and remembered, warned of above the threshold in search results, and
+//! refusing captures in strict mode.
+
+mod common;
+
+use common::{call, serve, server_builder, shodan_client, HttpResponse, Requests, HOST_SEARCH};
+use mcp_webcam::{HoneypotGuard, MockBackend, ShodanClient, ShodanError, WebcamMcpServer};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Serve searches from the fixture and honeyscores of a few addresses on
+/// loopback; returns the base URL and the requests received
+fn serve_shodan() -> (String, Requests) {
+    serve(|request| match request.path.strip_prefix("/labs/honeyscore/") {
+        Some("203.0.113.7" | "127.0.0.1") => HttpResponse::json("0.9"),
+        Some("198.51.100.23") => HttpResponse::json("0.1"),
+        Some("192.0.2.99") => HttpResponse::json("not a score"),
+        Some(_) => HttpResponse::new("404 Not Found", "application/json", r#"{"error": "No information available for that IP."}"#),
+        None if request.path == "/shodan/host/search" => HttpResponse::json(HOST_SEARCH),
+        None => HttpResponse::new("404 Not Found", "application/json", "{}"),
+    })
+}
+
+fn server(base_url: &str, strict: bool) -> mcp_webcam::protocol::Server {
+    server_builder(base_url).honeypot(0.5, strict).build().build_server().unwrap()
+}
+
+fn honeyscore_lookups(requests: &Requests) -> usize {
+    requests.count("/labs/honeyscore/")
+}
+
+#[test]
+fn scores_above_the_threshold_are_warned_of() {
+    let guard = HoneypotGuard::new(0.5, false);
+    assert!(guard.warning(0.5).is_none());
+    assert!(guard.warning(0.51).unwrap().contains("likely a honeypot"));
+
+    guard.record("[2001:DB8::2A]", 0.7);
+    let check = guard.check("2001:db8::2a", guard.score("2001:db8::2a").unwrap());
+    assert_eq!(check.ip, "2001:db8::2a");
+    assert!(check.likely_honeypot);
+}
+
+#[test]
+fn the_oldest_score_is_forgotten_when_full() {
+    let guard = HoneypotGuard::with_capacity(0.5, false, 2);
+    guard.record("192.0.2.1", 0.1);
+    std::thread::sleep(Duration::from_millis(2));
+    guard.record("192.0.2.2", 0.2);
+    guard.record("192.0.2.3", 0.3);
+    assert_eq!(guard.score("192.0.2.1"), None);
+    assert_eq!(guard.score("192.0.2.2"), Some(0.2));
+    assert_eq!(guard.score("192.0.2.3"), Some(0.3));
+}
+
+#[tokio::test]
+async fn honeyscores_are_parsed_and_checked() {
+    let (base_url, requests) = serve_shodan();
+    let client = shodan_client(&base_url);
+    assert_eq!(client.honeyscore("198.51.100.23").await.unwrap(), 0.1);
+    assert!(matches!(client.honeyscore("192.0.2.1").await, Err(ShodanError::NotFound(_))));
+    assert!(matches!(client.honeyscore("192.0.2.99").await, Err(ShodanError::Generic(_))));
+
+    // Refused before any request
+    assert!(matches!(client.honeyscore("cam.example.net").await, Err(ShodanError::InvalidQuery(_))));
+    assert_eq!(honeyscore_lookups(&requests), 3);
+
+    let keyless = ShodanClient::builder().base_url(&base_url).build("").unwrap();
+    assert!(matches!(keyless.honeyscore("198.51.100.23").await, Err(ShodanError::NoApiKey)));
+}
+
+#[test]
+fn check_honeypot_looks_a_host_up_once() {
+    let (base_url, requests) = serve_shodan();
+    let server = server(&base_url, false);
+
+    let first = call(&server, "check_honeypot", json!({ "ip": "203.0.113.7" }));
+    assert_eq!(first["isError"], false);
+    let check = &first["structuredContent"];
+    assert_eq!(check["honeyscore"], 0.9);
+    assert_eq!(check["likely_honeypot"], true);
+    assert_eq!(check["cached"], false);
+    assert!(check["warning"].as_str().unwrap().contains("0.90"));
+
+    let again = call(&server, "check_honeypot", json!({ "ip": "203.0.113.7" }));
+    assert_eq!(again["structuredContent"]["cached"], true);
+    assert_eq!(honeyscore_lookups(&requests), 1);
+
+    let unknown = call(&server, "check_honeypot", json!({ "ip": "192.0.2.1" }));
+    assert_eq!(unknown["structuredContent"]["error"]["code"], "SHODAN_HOST_NOT_FOUND");
+    let invalid = call(&server, "check_honeypot", json!({ "ip": "not-an-ip" }));
+    assert_eq!(invalid["structuredContent"]["error"]["code"], "INVALID_PARAMS");
+    assert_eq!(honeyscore_lookups(&requests), 2);
+}
+
+#[test]
+fn searches_can_carry_honeyscores() {
+    let (base_url, requests) = serve_shodan();
+    let server = server(&base_url, false);
+
+    let plain = call(&server, "search_webcams", json!({ "limit": 10, "max_queries": 1 }));
+    assert_eq!(plain["isError"], false);
+    assert!(plain["structuredContent"]["webcams"][0].get("honeyscore").is_none());
+    assert_eq!(honeyscore_lookups(&requests), 0);
+
+    let scored = call(&server, "search_webcams", json!({ "limit": 10, "max_queries": 1, "with_honeyscore": true }));
+    assert_eq!(scored["isError"], false);
+    let webcams = scored["structuredContent"]["webcams"].as_array().unwrap();
+    let by_ip = |ip: &str| webcams.iter().find(|webcam| webcam["ip"] == ip).unwrap().clone();
+    assert_eq!(by_ip("203.0.113.7")["honeyscore"], 0.9);
+    assert!(by_ip("203.0.113.7")["honeypot_warning"].is_string());
+    assert_eq!(by_ip("198.51.100.23")["honeyscore"], 0.1);
+    assert!(by_ip("198.51.100.23").get("honeypot_warning").is_none());
+    assert_eq!(by_ip("2001:db8::2a")["honeyscore"], Value::Null);
+    assert_eq!(
+        scored["structuredContent"]["metadata"]["honeyscore"],
+        json!({ "threshold": 0.5, "looked_up": 2, "failed": 1, "skipped": 0 })
+    );
+    assert_eq!(honeyscore_lookups(&requests), 3);
+
+    // Scores found are remembered; only the host without one is asked again
+    let grouped = call(&server, "search_webcams", json!({ "limit": 10, "max_queries": 1, "with_honeyscore": true, "group_by_host": true }));
+    assert_eq!(grouped["structuredContent"]["hosts"][0]["honeyscore"], 0.9);
+    assert_eq!(honeyscore_lookups(&requests), 4);
+}
+
+#[test]
+fn searching_with_honeyscores_needs_a_shodan_key() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .discovery_provider(Arc::new(shodan_client("http://127.0.0.1:1")))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+    let result = call(&server, "search_webcams", json!({ "with_honeyscore": true }));
+    assert_eq!(result["structuredContent"]["error"]["code"], "INVALID_PARAMS");
+}
+
+#[test]
+fn strict_mode_refuses_likely_honeypots() {
+    let (base_url, requests) = serve_shodan();
+    let strict = server(&base_url, true);
+    let refused = call(&strict, "capture_remote_image", json!({ "url": "http://127.0.0.1:9/snapshot.jpg" }));
+    assert_eq!(refused["isError"], true);
+    let error = &refused["structuredContent"]["error"];
+    assert_eq!(error["code"], "HONEYPOT_SUSPECTED");
+    assert_eq!(error["details"], json!({ "ip": "127.0.0.1", "honeyscore": 0.9, "threshold": 0.5 }));
+    assert_eq!(honeyscore_lookups(&requests), 1);
+
+    // Without strict mode the capture is attempted, and fails on the closed port
+    let lenient = server(&base_url, false);
+    let attempted = call(&lenient, "capture_remote_image", json!({ "url": "http://127.0.0.1:9/snapshot.jpg" }));
+    assert_ne!(attempted["structuredContent"]["error"]["code"], "HONEYPOT_SUSPECTED");
+    assert_eq!(honeyscore_lookups(&requests), 1);
+}
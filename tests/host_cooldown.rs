@@ -0,0 +1,110 @@
+//! The per-host cooldown between remote webcam fetches: refusals and the
+//! time left, the bounded host table, and how tools and stats report it.
+
+mod common;
+
+use common::call_structured as call;
+use mcp_webcam::host_cooldown::HostCooldown;
+use mcp_webcam::tool_error::{ErrorCode, ToolError};
+use mcp_webcam::{MockBackend, ShodanClient, ShodanError, WebcamMcpServer};
+use serde_json::{json, Value};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[test]
+fn a_host_is_refused_until_its_interval_is_up() {
+    let cooldown = HostCooldown::new(Duration::from_secs(10));
+    let start = Instant::now();
+    assert_eq!(cooldown.claim_at("192.0.2.1", start), Ok(()));
+    assert_eq!(cooldown.claim_at("192.0.2.1", start + Duration::from_secs(4)), Err(Duration::from_secs(6)));
+    // Other hosts aren't held up, and IPv6 is one host with or without brackets
+    assert_eq!(cooldown.claim_at("192.0.2.2", start + Duration::from_secs(4)), Ok(()));
+    assert_eq!(cooldown.claim_at("[2001:DB8::1]", start), Ok(()));
+    assert!(cooldown.claim_at("2001:db8::1", start + Duration::from_secs(1)).is_err());
+    // A refusal doesn't restart the interval
+    assert_eq!(cooldown.claim_at("192.0.2.1", start + Duration::from_secs(10)), Ok(()));
+
+    let stats = cooldown.stats_at(start + Duration::from_secs(12));
+    assert_eq!(stats.interval_ms, 10_000);
+    assert_eq!(stats.tracked, 3);
+    assert_eq!(stats.refused, 2);
+    let cooling: Vec<(&str, u64)> = stats.cooling.iter().map(|host| (host.host.as_str(), host.remaining_ms)).collect();
+    assert_eq!(cooling, [("192.0.2.1", 8_000), ("192.0.2.2", 2_000)]);
+
+    let disabled = HostCooldown::new(Duration::ZERO);
+    assert!((0..3).all(|_| disabled.claim_at("192.0.2.1", start).is_ok()));
+    assert_eq!(disabled.stats_at(start).tracked, 0);
+}
+
+#[test]
+fn a_full_table_forgets_the_host_fetched_from_longest_ago() {
+    let cooldown = HostCooldown::with_capacity(Duration::from_secs(10), 2);
+    let start = Instant::now();
+    cooldown.claim_at("192.0.2.1", start).unwrap();
+    cooldown.claim_at("192.0.2.2", start + Duration::from_secs(1)).unwrap();
+    cooldown.claim_at("192.0.2.3", start + Duration::from_secs(2)).unwrap();
+
+    let stats = cooldown.stats_at(start + Duration::from_secs(2));
+    assert_eq!((stats.tracked, stats.evicted), (2, 1));
+    // Forgotten, so no longer cooling down
+    assert!(cooldown.claim_at("192.0.2.1", start + Duration::from_secs(3)).is_ok());
+    assert!(cooldown.claim_at("192.0.2.3", start + Duration::from_secs(3)).is_err());
+
+    // Hosts past their interval make room before any still cooling down
+    let later = start + Duration::from_secs(12);
+    cooldown.claim_at("192.0.2.4", later).unwrap();
+    assert!(cooldown.claim_at("192.0.2.1", later).is_err());
+    assert_eq!(cooldown.stats_at(later).evicted, 2);
+}
+
+#[test]
+fn clients_sharing_a_cooldown_refuse_with_the_time_left() {
+    let cooldown = Arc::new(HostCooldown::new(Duration::from_millis(2500)));
+    let first = ShodanClient::builder().host_cooldown(Arc::clone(&cooldown)).build("key").unwrap();
+    let second = ShodanClient::new("other-key".to_string()).with_host_cooldown(Arc::clone(&cooldown));
+
+    first.claim_host("192.0.2.1").unwrap();
+    let error = second.claim_host("192.0.2.1").unwrap_err();
+    assert!(matches!(error, ShodanError::HostCooldown { .. }), "{:?}", error);
+    let error = ToolError::from(&error);
+    assert_eq!(error.code, ErrorCode::HostCooldown);
+    assert_eq!(error.details.as_ref().unwrap()["host"], "192.0.2.1");
+    assert_eq!(error.details.as_ref().unwrap()["retry_after_seconds"], 3);
+}
+
+#[test]
+fn tools_share_the_cooldown_and_stats_list_hosts_cooling_down() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(ShodanClient::builder().allow_private_targets(true).build("test-key").unwrap())
+        .host_cooldown(Duration::from_secs(60))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+    // Ports nothing listens on, both on 127.0.0.1
+    let closed = || format!("http://{}/snapshot.jpg", TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap());
+
+    // One batch claims each host once, however many of its webcams it checks
+    let webcams = json!({ "webcams": [{ "url": closed() }, { "url": closed() }], "timeout_seconds": 1 });
+    let validated = call(&server, "validate_webcams", webcams.clone());
+    assert_eq!(validated["summary"]["cooling_down"], 0);
+    assert_eq!(validated["results"][1]["validation"]["reachable"], false);
+
+    let again = call(&server, "validate_webcams", webcams);
+    assert_eq!(again["summary"]["total"], 2);
+    assert_eq!(again["summary"]["cooling_down"], 2);
+    assert_eq!(again["results"][0]["validation"], Value::Null);
+    assert_eq!(again["results"][0]["error"]["code"], "HOST_COOLDOWN");
+
+    let captured = call(&server, "capture_remote_image", json!({ "url": closed() }));
+    assert_eq!(captured["error"]["code"], "HOST_COOLDOWN");
+    assert!(captured["error"]["details"]["retry_after_seconds"].as_u64().unwrap() <= 60);
+
+    let stats = &call(&server, "get_server_stats", json!({}))["stats"]["host_cooldown"];
+    assert_eq!(stats["interval_ms"], 60_000);
+    assert_eq!(stats["tracked"], 1);
+    assert_eq!(stats["refused"], 2);
+    assert_eq!(stats["cooling"][0]["host"], "127.0.0.1");
+}
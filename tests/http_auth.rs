@@ -0,0 +1,67 @@
+use mcp_webcam::http_auth::{authorization, digest_authorization, parse_challenges, preferred_challenge, Challenge};
+use mcp_webcam::WebcamCredentials;
+
+/// The example challenge of RFC 7616 section 3.9.1
+fn rfc_challenge(algorithm: &str) -> Challenge {
+    let header = format!(
+        r#"Digest realm="http-auth@example.org", qop="auth, auth-int", algorithm={}, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#,
+        algorithm
+    );
+    parse_challenges(&header).remove(0)
+}
+
+fn response_of(authorization: &str) -> &str {
+    let start = authorization.find("response=\"").unwrap() + "response=\"".len();
+    &authorization[start..start + authorization[start..].find('"').unwrap()]
+}
+
+#[test]
+fn digest_responses_match_the_rfc_examples() {
+    let credentials = WebcamCredentials::new("Mufasa", "Circle of Life");
+    let cnonce = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+
+    let md5 = digest_authorization(&rfc_challenge("MD5"), "GET", "/dir/index.html", &credentials, cnonce, 1).unwrap();
+    assert_eq!(response_of(&md5), "8ca523f5e9506fed4657c9700eebdbec");
+    let sha256 = digest_authorization(&rfc_challenge("SHA-256"), "GET", "/dir/index.html", &credentials, cnonce, 1).unwrap();
+    assert_eq!(response_of(&sha256), "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1");
+
+    for field in [
+        r#"username="Mufasa""#,
+        r#"uri="/dir/index.html""#,
+        "algorithm=SHA-256",
+        "qop=auth,",
+        "nc=00000001",
+        r#"opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#,
+    ] {
+        assert!(sha256.contains(field), "{} lacks {}", sha256, field);
+    }
+    assert!(!sha256.contains("Circle of Life"));
+
+    // Only qop=auth-int, or an algorithm that isn't implemented, can't be answered
+    let mut auth_int = rfc_challenge("MD5");
+    auth_int.params.insert("qop".to_string(), "auth-int".to_string());
+    assert_eq!(digest_authorization(&auth_int, "GET", "/", &credentials, cnonce, 1), None);
+    assert_eq!(digest_authorization(&rfc_challenge("SHA-512-256"), "GET", "/", &credentials, cnonce, 1), None);
+}
+
+#[test]
+fn challenges_are_parsed_and_the_strongest_is_answered() {
+    let challenges = parse_challenges(
+        r#"Basic realm="IP Camera", Digest realm="a \"quoted\", realm", nonce=abc, algorithm=MD5-sess, Digest realm="cam", nonce="def", algorithm=SHA-256"#,
+    );
+    assert_eq!(challenges.len(), 3);
+    assert_eq!(challenges[0].scheme, "Basic");
+    assert_eq!(challenges[0].realm(), Some("IP Camera"));
+    assert_eq!(challenges[1].realm(), Some(r#"a "quoted", realm"#));
+    assert_eq!(challenges[1].params["nonce"], "abc");
+    assert_eq!(challenges[1].params["algorithm"], "MD5-sess");
+
+    assert_eq!(preferred_challenge(&challenges), Some(&challenges[2]));
+    assert_eq!(preferred_challenge(&challenges[..2]), Some(&challenges[1]));
+    assert_eq!(preferred_challenge(&parse_challenges("Negotiate, NTLM")), None);
+
+    let credentials = WebcamCredentials::new("admin", "12345");
+    let basic = authorization(&challenges[0], "GET", "/", &credentials).unwrap();
+    assert_eq!(basic, "Basic YWRtaW46MTIzNDU=");
+    assert!(format!("{:?}", credentials).contains("admin") && !format!("{:?}", credentials).contains("12345"));
+}
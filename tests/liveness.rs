@@ -0,0 +1,141 @@
+//! Several frames of a remote webcam: difference scores and the liveness
+//! verdict drawn from them, the frame limits, and capture_remote_image
+//! fetching the frames from a stand-in webcam on loopback.
+
+mod common;
+
+use common::HttpResponse;
+use image::{ImageFormat, Rgb, RgbImage};
+use mcp_webcam::liveness::{assess, check_frames, difference_score, LIVENESS_THRESHOLD};
+use mcp_webcam::{Liveness, MockBackend, ShodanClient, WebcamMcpServer};
+use serde_json::{json, Value};
+use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A 64x48 PNG, grey at `level` with a white square at `offset`
+fn frame(level: u8, offset: u32) -> Vec<u8> {
+    let image = RgbImage::from_fn(64, 48, |x, y| {
+        if (offset..offset + 8).contains(&x) && (8..16).contains(&y) {
+            Rgb([255, 255, 255])
+        } else {
+            Rgb([level, level, level])
+        }
+    });
+    let mut buffer = Cursor::new(Vec::new());
+    image.write_to(&mut buffer, ImageFormat::Png).unwrap();
+    buffer.into_inner()
+}
+
+/// A webcam serving `frames` in turn, the last one over again once they
+/// run out; returns its snapshot URL and the requests it answered
+fn serve(frames: Vec<Vec<u8>>) -> (String, Arc<AtomicUsize>) {
+    let served = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&served);
+    let (base, _) = common::serve(move |_| {
+        let index = counter.fetch_add(1, Ordering::SeqCst).min(frames.len() - 1);
+        HttpResponse::new("200 OK", "image/png", frames[index].clone())
+    });
+    (format!("{}/snapshot.png", base), served)
+}
+
+fn server(cooldown: Duration) -> mcp_webcam::protocol::Server {
+    let client = ShodanClient::builder().allow_private_targets(true).build("test-key").unwrap();
+    WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(client)
+        .host_cooldown(cooldown)
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap()
+}
+
+fn call(server: &mcp_webcam::protocol::Server, arguments: Value) -> Value {
+    common::call(server, "capture_remote_image", arguments)
+}
+
+fn images(result: &Value) -> usize {
+    result["content"].as_array().unwrap().iter().filter(|item| item["type"] == "image").count()
+}
+
+#[test]
+fn frames_are_scored_against_the_one_before() {
+    let still = frame(100, 0);
+    assert_eq!(difference_score(&still, &still), Some(0.0));
+    assert!(difference_score(&still, &frame(100, 40)).unwrap() > LIVENESS_THRESHOLD);
+    assert_eq!(difference_score(&still, b"not an image"), None);
+
+    let moving = assess(&[&frame(100, 0), &frame(100, 20), &frame(100, 40)]);
+    assert_eq!(moving.verdict, Liveness::Live);
+    assert_eq!(moving.scores.len(), 2);
+
+    assert_eq!(assess(&[&still, &still, &still]).verdict, Liveness::Static);
+    // One level of 255 on every pixel is below the threshold
+    assert_eq!(assess(&[&frame(100, 0), &frame(101, 0)]).verdict, Liveness::Static);
+    assert_eq!(assess(&[&still, b"not an image"]).verdict, Liveness::Unknown);
+    assert_eq!(assess(&[&still]).verdict, Liveness::Unknown);
+}
+
+#[test]
+fn frames_must_fit_in_the_span() {
+    assert!(check_frames(1, Duration::from_millis(100)).is_ok());
+    assert!(check_frames(10, Duration::from_millis(1000)).is_ok());
+    assert!(check_frames(0, Duration::from_millis(1000)).unwrap_err().contains("1-10"));
+    assert!(check_frames(11, Duration::from_millis(100)).is_err());
+    assert!(check_frames(3, Duration::from_millis(50)).unwrap_err().contains("at least 100"));
+    assert!(check_frames(5, Duration::from_secs(3)).unwrap_err().contains("longer than 10 s"));
+}
+
+#[test]
+fn a_frozen_webcam_is_static() {
+    let (url, served) = serve(vec![frame(100, 0)]);
+    let result = call(&server(Duration::ZERO), json!({ "url": url, "frames": 3, "interval_ms": 100 }));
+    assert_eq!(result["isError"], false, "{}", result);
+    let liveness = &result["structuredContent"]["liveness"];
+    assert_eq!(liveness["verdict"], "static");
+    assert_eq!(liveness["scores"], json!([0.0, 0.0]));
+    assert_eq!(images(&result), 3);
+    assert_eq!(result["structuredContent"]["metadata"]["frames"], 3);
+    assert_eq!(result["structuredContent"]["metadata"]["interval_ms"], 100);
+    assert_eq!(served.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn a_moving_webcam_is_live_and_can_return_only_its_first_frame() {
+    let (url, _) = serve(vec![frame(100, 0), frame(100, 20), frame(100, 40)]);
+    let result = call(&server(Duration::ZERO), json!({ "url": url, "frames": 3, "interval_ms": 100, "images": "first_only" }));
+    assert_eq!(result["isError"], false, "{}", result);
+    assert_eq!(result["structuredContent"]["liveness"]["verdict"], "live");
+    assert_eq!(images(&result), 1);
+    assert_eq!(result["structuredContent"]["metadata"]["images"], "first_only");
+    assert!(result["content"][1]["text"].as_str().unwrap().contains("live"));
+}
+
+#[test]
+fn the_cooldown_applies_to_the_first_frame_only() {
+    let (url, served) = serve(vec![frame(100, 0)]);
+    let server = server(Duration::from_secs(60));
+    let result = call(&server, json!({ "url": url, "frames": 2, "interval_ms": 100 }));
+    assert_eq!(result["isError"], false, "{}", result);
+    assert_eq!(served.load(Ordering::SeqCst), 2);
+
+    let again = call(&server, json!({ "url": url, "frames": 2, "interval_ms": 100 }));
+    assert_eq!(again["structuredContent"]["error"]["code"], "HOST_COOLDOWN");
+    assert_eq!(served.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn a_single_frame_has_no_liveness() {
+    let (url, _) = serve(vec![frame(100, 0)]);
+    let server = server(Duration::ZERO);
+    let result = call(&server, json!({ "url": url }));
+    assert!(result["structuredContent"].get("liveness").is_none());
+    assert_eq!(result["structuredContent"]["metadata"]["frames"], 1);
+
+    let refused = call(&server, json!({ "url": url, "frames": 2, "metadata_only": true }));
+    assert_eq!(refused["structuredContent"]["error"]["code"], "INVALID_PARAMS");
+    let refused = call(&server, json!({ "url": url, "frames": 2, "images": "some" }));
+    assert_eq!(refused["structuredContent"]["error"]["code"], "INVALID_PARAMS");
+}
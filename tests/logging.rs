@@ -0,0 +1,82 @@
+//! Log routing: stdout of the binary carries only JSON-RPC, and log files
+//! rotate by size.
+
+mod common;
+
+use common::temp_dir;
+use mcp_webcam::logging::{LogFormat, RotatingFile, LOG_FILE_BACKUPS};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[test]
+fn stdout_carries_only_json_rpc_frames() {
+    let data_dir = temp_dir("stdio-data");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mcp-webcam"))
+        .args(["--log-level", "debug", "--no-shodan"])
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .env_remove("MCP_WEBCAM_LOG_FILE")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("start server binary");
+
+    let messages = [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+        json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }),
+        json!({ "jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": { "name": "get_server_stats", "arguments": {} } }),
+    ];
+    {
+        let mut stdin = child.stdin.take().unwrap();
+        for message in &messages {
+            writeln!(stdin, "{}", message).unwrap();
+        }
+        // Dropping stdin ends the session
+    }
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "server failed: {}", String::from_utf8_lossy(&output.stderr));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let frames: Vec<Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|_| panic!("non JSON-RPC line on stdout: {}", line)))
+        .collect();
+    assert_eq!(frames.len(), 3);
+    assert!(frames.iter().all(|frame| frame["jsonrpc"] == "2.0"));
+
+    // The logs went to stderr instead
+    assert!(String::from_utf8_lossy(&output.stderr).contains("MCP Webcam Server starting"));
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn log_file_rotates_by_size() {
+    let dir = temp_dir("log-rotation");
+    let path = dir.join("server.log");
+    let mut file = RotatingFile::open(&path, 100).unwrap();
+
+    for i in 0..20 {
+        // One write per line, as the subscriber writes one per event
+        file.write_all(format!("{:02} {}\n", i, "x".repeat(40)).as_bytes()).unwrap();
+    }
+    file.flush().unwrap();
+
+    assert!(std::fs::metadata(&path).unwrap().len() <= 100);
+    for n in 1..=LOG_FILE_BACKUPS {
+        assert!(RotatingFile::backup_path(&path, n).exists(), "missing backup {}", n);
+    }
+    assert!(!RotatingFile::backup_path(&path, LOG_FILE_BACKUPS + 1).exists());
+    // The newest lines are in the current file
+    assert!(std::fs::read_to_string(&path).unwrap().contains("19 "));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn log_format_parses_known_names() {
+    assert_eq!(LogFormat::parse("json"), Ok(LogFormat::Json));
+    assert_eq!(LogFormat::parse("text"), Ok(LogFormat::Text));
+    assert!(LogFormat::parse("xml").is_err());
+}
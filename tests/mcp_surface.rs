@@ -0,0 +1,294 @@
+//! The whole JSON-RPC surface driven by a client on the other end of an
+//! in-memory connection: initialize, tools/list and a call of every listed
+//! tool, against the mock backend and one loopback stand-in serving as
+//! Shodan's API, a webcam and an ONVIF camera. No camera or network needed.
+
+mod common;
+
+use base64::{engine::general_purpose, Engine as _};
+use common::{jpeg, media_uri, serve, server_builder, HttpResponse, HOST_SEARCH, ONVIF_CAPABILITIES, ONVIF_PROFILES};
+use image::ImageFormat;
+use mcp_webcam::protocol::Transport;
+use mcp_webcam::tool_schemas::validate;
+use mcp_webcam::{ShodanClient, WebcamMcpServer};
+use mcpr::error::MCPError;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const HOST: &str = include_str!("fixtures/shodan_host.json");
+const SCAN_ID: &str = "R2XRT5HH6X67PFAB";
+
+/// Longest a client waits for one response
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The server's end of the connection
+struct ServerEnd {
+    incoming: Mutex<Receiver<String>>,
+    outgoing: Sender<Value>,
+}
+
+impl Transport for ServerEnd {
+    fn receive(&self) -> Result<Option<String>, MCPError> {
+        // `None` once the client has hung up
+        Ok(self.incoming.lock().unwrap().recv().ok())
+    }
+
+    fn send(&self, message: &Value) -> Result<(), MCPError> {
+        // A client that gave up on a response no longer reads them
+        let _ = self.outgoing.send(message.clone());
+        Ok(())
+    }
+}
+
+/// The client's end: requests sent one at a time, each waiting for its
+/// response. Dropping it hangs up.
+struct Client {
+    outgoing: Sender<String>,
+    incoming: Receiver<Value>,
+    next_id: u64,
+}
+
+impl Client {
+    /// Send a request and return its response, skipping notifications
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.outgoing
+            .send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string())
+            .unwrap();
+        loop {
+            let message = self.incoming.recv_timeout(RESPONSE_TIMEOUT)
+                .unwrap_or_else(|_| panic!("no response to {} {}", method, params));
+            if message["id"] == id {
+                assert_eq!(message["jsonrpc"], "2.0", "{}", message);
+                return message;
+            }
+        }
+    }
+
+    fn notify(&self, method: &str) {
+        self.outgoing.send(json!({ "jsonrpc": "2.0", "method": method }).to_string()).unwrap();
+    }
+
+    fn call_tool(&mut self, name: &str, arguments: &Value) -> Value {
+        let response = self.request("tools/call", json!({ "name": name, "arguments": arguments }));
+        response.get("result").cloned()
+            .unwrap_or_else(|| panic!("{} {}: no result in {}", name, arguments, response))
+    }
+}
+
+/// Both ends of an in-memory connection
+fn connection() -> (ServerEnd, Client) {
+    let (to_server, from_client) = channel();
+    let (to_client, from_server) = channel();
+    let server = ServerEnd { incoming: Mutex::new(from_client), outgoing: to_client };
+    let client = Client { outgoing: to_server, incoming: from_server, next_id: 0 };
+    (server, client)
+}
+
+/// Serve Shodan's API from the fixtures, a JPEG on every other path and
+/// ONVIF replies to SOAP requests, on loopback; returns the base URL
+fn serve_everything() -> String {
+    let (base, _) = serve(|request| {
+        let soap = |body: String| HttpResponse::new("200 OK", "application/soap+xml; charset=utf-8", body);
+        match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/shodan/scan") => HttpResponse::json(format!(r#"{{"count": 1, "id": "{}", "credits_left": 98}}"#, SCAN_ID)),
+            ("POST", _) => match request.soap_action().unwrap_or_default() {
+                "GetCapabilities" => soap(ONVIF_CAPABILITIES.replace("http://192.0.2.64", &request.base_url())),
+                "GetProfiles" => soap(ONVIF_PROFILES.to_string()),
+                "GetSnapshotUri" => soap(media_uri(&format!("{}/snapshot.jpg", request.base_url()))),
+                _ => HttpResponse::new("400 Bad Request", "text/plain", "unknown action"),
+            },
+            (_, "/shodan/host/search") => HttpResponse::json(HOST_SEARCH),
+            (_, "/shodan/host/count") => HttpResponse::json(r#"{"total": 42, "facets": {}}"#),
+            (_, "/api-info") => HttpResponse::json(r#"{"plan": "dev", "query_credits": 100, "scan_credits": 5}"#),
+            (_, path) if path.starts_with("/shodan/host/") => HttpResponse::json(HOST),
+            (_, path) if path.starts_with("/shodan/scan/") => HttpResponse::json(format!(
+                r#"{{"count": 1, "status": "PROCESSING", "id": "{}", "created": "2026-10-16T08:17:43.794000"}}"#, SCAN_ID
+            )),
+            (_, path) if path.starts_with("/labs/honeyscore/") => HttpResponse::new("200 OK", "text/plain", "0.1"),
+            _ => HttpResponse::new("200 OK", "image/jpeg", jpeg()),
+        }
+    });
+    base
+}
+
+fn server(base_url: &str) -> WebcamMcpServer {
+    server_builder(base_url)
+        .shodan_client_settings(ShodanClient::builder().allow_private_targets(true).reverse_dns_budget(Duration::ZERO))
+        .network_scan(true)
+        .preview(true)
+        .build()
+}
+
+/// How a call must end
+#[derive(Debug, Clone, Copy)]
+enum Outcome {
+    Succeeds,
+    /// With this error code
+    Fails(&'static str),
+    /// Either, depending on the build or host
+    Either,
+}
+
+/// Check a `tools/call` result against the MCP `CallToolResult` schema,
+/// and that every image in it decodes
+fn assert_call_tool_result(tool: &str, result: &Value) {
+    let fields = result.as_object().unwrap_or_else(|| panic!("{}: result is not an object", tool));
+    for key in fields.keys() {
+        assert!(
+            ["content", "isError", "structuredContent", "_meta"].contains(&key.as_str()),
+            "{}: unexpected field '{}'", tool, key
+        );
+    }
+
+    let content = result["content"].as_array().unwrap_or_else(|| panic!("{}: content is not an array", tool));
+    assert!(!content.is_empty(), "{}: no content", tool);
+    for block in content {
+        match block["type"].as_str() {
+            Some("text") => assert!(block["text"].is_string(), "{}: invalid text block {}", tool, block),
+            Some("image") => {
+                let data = general_purpose::STANDARD.decode(block["data"].as_str().unwrap_or_default())
+                    .unwrap_or_else(|e| panic!("{}: image data is not base64: {}", tool, e));
+                let mime_type = block["mimeType"].as_str().unwrap_or_else(|| panic!("{}: image without a mimeType", tool));
+                let format = ImageFormat::from_mime_type(mime_type).unwrap_or_else(|| panic!("{}: unknown image type {}", tool, mime_type));
+                if let Err(e) = image::load_from_memory_with_format(&data, format) {
+                    panic!("{}: {} image does not decode: {}", tool, mime_type, e);
+                }
+            }
+            Some("resource_link") => assert!(block["uri"].is_string() && block["name"].is_string(), "{}: invalid resource link {}", tool, block),
+            _ => panic!("{}: invalid content block {}", tool, block),
+        }
+    }
+    if let Some(is_error) = result.get("isError") {
+        assert!(is_error.is_boolean(), "{}: isError is not a boolean", tool);
+    }
+}
+
+#[tokio::test]
+async fn every_listed_tool_answers_over_the_wire() {
+    let base_url = serve_everything();
+    let host_port = base_url.rsplit(':').next().unwrap().parse::<u16>().unwrap();
+    let snapshot_url = format!("{}/snapshot.jpg", base_url);
+    let device_url = format!("{}/onvif/device_service", base_url);
+    let (transport, mut client) = connection();
+
+    let session = std::thread::spawn(move || {
+        let initialized = client.request("initialize", json!({
+            "protocolVersion": "2025-06-18",
+            "capabilities": {},
+            "clientInfo": { "name": "mcp-surface-test", "version": "0" }
+        }));
+        let result = &initialized["result"];
+        assert_eq!(result["protocolVersion"], "2025-06-18");
+        assert!(result["capabilities"]["tools"].is_object());
+        assert_eq!(result["serverInfo"]["name"], env!("CARGO_PKG_NAME"));
+        client.notify("notifications/initialized");
+
+        let listed = client.request("tools/list", json!({}));
+        let tools = listed["result"]["tools"].as_array().unwrap().clone();
+        for tool in &tools {
+            assert!(tool["name"].is_string(), "{}", tool);
+            assert!(tool["description"].is_string(), "{} has no description", tool["name"]);
+            assert_eq!(tool["inputSchema"]["type"], "object", "{}", tool["name"]);
+        }
+        let output_schemas: HashMap<String, Value> = tools.iter()
+            .filter(|tool| !tool["outputSchema"].is_null())
+            .map(|tool| (tool["name"].as_str().unwrap().to_string(), tool["outputSchema"].clone()))
+            .collect();
+
+        let captured = client.call_tool("capture_image", &json!({}));
+        let capture_id = captured["structuredContent"]["metadata"]["capture_id"].as_str().unwrap().to_string();
+
+        use Outcome::*;
+        // In order: captures before the tools reading them, searches before
+        // the cache is listed, exported and cleared
+        let calls = [
+            ("list_cameras", json!({}), Succeeds),
+            ("capture_image", json!({ "camera_index": 0, "quality": 70 }), Succeeds),
+            ("capture_image", json!({ "camera_index": 7 }), Fails("CAMERA_NOT_FOUND")),
+            ("scan_codes", json!({ "include_image": true }), Either),
+            ("capture_clip", json!({ "frames": 2, "interval_ms": 0 }), Succeeds),
+            ("get_camera_info", json!({}), Either),
+            ("set_camera_defaults", json!({ "camera_index": 0, "quality": 80 }), Succeeds),
+            ("set_default_camera", json!({ "camera_index": 0 }), Succeeds),
+            ("set_default_camera", json!({ "camera_index": 4242 }), Fails("CAMERA_NOT_FOUND")),
+            ("self_test", json!({}), Succeeds),
+            ("benchmark_camera", json!({ "duration_seconds": 0.1 }), Either),
+            ("list_captures", json!({}), Succeeds),
+            ("get_capture", json!({ "capture_id": capture_id }), Succeeds),
+            ("get_capture", json!({ "capture_id": "nope" }), Fails("CAPTURE_NOT_FOUND")),
+            ("compare_captures", json!({ "capture_id": capture_id, "capture_new": true, "include_diff_image": true }), Succeeds),
+            ("start_preview", json!({}), Succeeds),
+            ("stop_preview", json!({}), Succeeds),
+            ("get_storage_info", json!({}), Succeeds),
+            ("get_audit_log", json!({ "limit": 10 }), Succeeds),
+            ("get_server_stats", json!({}), Succeeds),
+            ("health_check", json!({}), Either),
+            ("get_server_info", json!({}), Succeeds),
+            ("configure_shodan", json!({}), Fails("INVALID_PARAMS")),
+            ("discover_local_network_cameras", json!({ "timeout_ms": 200 }), Either),
+            ("capture_onvif_snapshot", json!({ "url": device_url }), Succeeds),
+            ("scan_network_for_cameras", json!({ "cidr": "127.0.0.1/32", "ports": [host_port], "connect_timeout_ms": 500 }), Succeeds),
+            ("search_webcams", json!({ "limit": 10, "max_queries": 1 }), Succeeds),
+            ("count_webcams", json!({ "query": "webcam" }), Succeeds),
+            ("list_search_queries", json!({}), Succeeds),
+            ("get_target_policy", json!({}), Succeeds),
+            ("shodan_account_info", json!({}), Succeeds),
+            ("shodan_host_info", json!({ "ip": "203.0.113.7" }), Succeeds),
+            ("check_honeypot", json!({ "ip": "203.0.113.7" }), Succeeds),
+            ("request_shodan_scan", json!({ "ips": ["203.0.113.7"] }), Succeeds),
+            ("request_shodan_scan", json!({ "ips": ["192.168.1.20"] }), Fails("INVALID_PARAMS")),
+            ("get_scan_status", json!({ "id": SCAN_ID }), Succeeds),
+            ("probe_webcam", json!({ "ip": "127.0.0.1", "port": host_port }), Succeeds),
+            ("capture_remote_image", json!({ "url": snapshot_url, "frames": 2, "interval_ms": 100 }), Succeeds),
+            ("capture_remote_image", json!({ "url": snapshot_url, "frames": 11 }), Fails("INVALID_PARAMS")),
+            ("validate_webcams", json!({ "webcams": [{ "url": snapshot_url }], "timeout_seconds": 2 }), Succeeds),
+            ("list_remote_webcams", json!({}), Succeeds),
+            ("export_webcams", json!({ "format": "geojson" }), Succeeds),
+            ("clear_remote_webcams", json!({}), Succeeds),
+        ];
+
+        // A tool added without a call here fails the test
+        let listed: BTreeSet<&str> = tools.iter().map(|tool| tool["name"].as_str().unwrap()).collect();
+        let called: BTreeSet<&str> = calls.iter().map(|(tool, _, _)| *tool).collect();
+        assert_eq!(listed, called, "tools listed and tools called differ");
+
+        for (tool, arguments, outcome) in &calls {
+            let result = client.call_tool(tool, arguments);
+            assert_call_tool_result(tool, &result);
+            let failed = result["isError"] == true;
+            match outcome {
+                Succeeds => assert!(!failed, "{} {}: {}", tool, arguments, result),
+                Fails(code) => {
+                    assert!(failed, "{} {} should fail: {}", tool, arguments, result);
+                    assert_eq!(result["structuredContent"]["error"]["code"], *code, "{} {}", tool, arguments);
+                }
+                Either => {}
+            }
+            if failed {
+                let error = &result["structuredContent"]["error"];
+                assert!(error["code"].is_string() && error["message"].is_string(), "{}: {}", tool, result);
+                assert!(error["retryable"].is_boolean(), "{}: {}", tool, result);
+            } else if let Some(schema) = output_schemas.get(*tool) {
+                if let Err(e) = validate(schema, &result["structuredContent"]) {
+                    panic!("{} {}: {}", tool, arguments, e);
+                }
+            } else {
+                assert!(result.get("structuredContent").is_none(), "{} has no output schema", tool);
+            }
+        }
+
+        // Tools not listed are JSON-RPC errors, not tool results
+        let unknown = client.request("tools/call", json!({ "name": "no_such_tool", "arguments": {} }));
+        assert_eq!(unknown["error"]["code"], -32602);
+    });
+
+    server(&base_url).run_with_transport(transport).await.unwrap();
+    if let Err(panic) = session.join() {
+        std::panic::resume_unwind(panic);
+    }
+}
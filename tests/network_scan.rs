@@ -0,0 +1,156 @@
+//! Network scans against stand-in servers on loopback: ranges parsed and
+//! capped, banners read from the ports that accept, only the ones that
+//! look like cameras listed, and the tool only offered when scanning is
+//! allowed.
+
+mod common;
+
+use mcp_webcam::network_scan::{probe_request, scan, OpenPort, ScanProtocol, ScanRange, SOURCE};
+use mcp_webcam::{MockBackend, WebcamAccessType, WebcamMcpServer, WebcamMcpServerBuilder};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener};
+use std::time::Duration;
+
+const CAMERA_ANSWER: &str = "HTTP/1.1 200 OK\r\nServer: Hikvision-Webs\r\nContent-Type: text/html\r\nConnection: close\r\n\r\n<html><head><title>Login</title></head></html>";
+const ROUTER_ANSWER: &str = "HTTP/1.1 200 OK\r\nServer: nginx\r\nContent-Type: text/html\r\nConnection: close\r\n\r\n<html><head><title>Router</title></head></html>";
+
+/// Answer every request on a loopback port with `answer`; returns the port
+fn serve(answer: &'static str) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 2 {
+                line.clear();
+            }
+            let _ = reader.get_mut().write_all(answer.as_bytes());
+        }
+    });
+    port
+}
+
+/// A loopback port nothing listens on
+fn closed_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+fn scanning_server() -> WebcamMcpServerBuilder {
+    WebcamMcpServer::builder().backend(Box::new(MockBackend::new(1))).preview(false)
+}
+
+fn call(builder: WebcamMcpServerBuilder, arguments: Value) -> Value {
+    common::call_structured(&builder.build().build_server().unwrap(), "scan_network_for_cameras", arguments)
+}
+
+fn tool_names(builder: WebcamMcpServerBuilder) -> Vec<String> {
+    let listed = common::request(&builder.build().build_server().unwrap(), "tools/list", json!({}));
+    listed["result"]["tools"].as_array().unwrap().iter().filter_map(|tool| tool["name"].as_str()).map(str::to_string).collect()
+}
+
+#[test]
+fn ranges_are_parsed_and_capped() {
+    let range: ScanRange = "192.168.1.77/24".parse().unwrap();
+    assert_eq!(range.to_string(), "192.168.1.0/24");
+    let hosts = range.hosts();
+    assert_eq!(hosts.len(), 254);
+    assert_eq!(hosts[0], Ipv4Addr::new(192, 168, 1, 1));
+    assert_eq!(hosts[253], Ipv4Addr::new(192, 168, 1, 254));
+
+    assert_eq!("10.0.0.0/22".parse::<ScanRange>().unwrap().hosts().len(), 1022);
+    assert_eq!("10.0.0.4/31".parse::<ScanRange>().unwrap().hosts(), vec![Ipv4Addr::new(10, 0, 0, 4), Ipv4Addr::new(10, 0, 0, 5)]);
+    assert_eq!("10.0.0.9".parse::<ScanRange>().unwrap().hosts(), vec![Ipv4Addr::new(10, 0, 0, 9)]);
+
+    assert!("10.0.0.0/21".parse::<ScanRange>().unwrap_err().contains("too large"));
+    assert!("2001:db8::/120".parse::<ScanRange>().unwrap_err().contains("IPv6"));
+    assert!("10.0.0.0/33".parse::<ScanRange>().is_err());
+    assert!("cameras".parse::<ScanRange>().is_err());
+}
+
+#[test]
+fn banners_decide_what_is_a_camera() {
+    assert!(probe_request(ScanProtocol::Http, Ipv4Addr::LOCALHOST, 8080).starts_with("GET / HTTP/1.0\r\nHost: 127.0.0.1:8080\r\n"));
+    assert!(probe_request(ScanProtocol::Rtsp, Ipv4Addr::LOCALHOST, 554).starts_with("OPTIONS rtsp://127.0.0.1:554/ RTSP/1.0\r\nCSeq: 1\r\n"));
+    assert_eq!(ScanProtocol::for_port(554), ScanProtocol::Rtsp);
+    assert_eq!(ScanProtocol::for_port(8000), ScanProtocol::Http);
+
+    let open = |port: u16, banner: &str| OpenPort {
+        ip: Ipv4Addr::new(192, 0, 2, 10),
+        port,
+        protocol: ScanProtocol::for_port(port),
+        banner: banner.to_string(),
+    };
+    let rtsp = open(554, "RTSP/1.0 200 OK\r\nCSeq: 1\r\nPublic: OPTIONS, DESCRIBE, SETUP, PLAY, TEARDOWN\r\n\r\n");
+    assert!(rtsp.is_camera());
+    let webcam = rtsp.to_webcam();
+    assert_eq!(webcam.url, "rtsp://192.0.2.10:554/");
+    assert!(matches!(webcam.access_type, WebcamAccessType::RTSP));
+    assert_eq!(webcam.sources, vec![SOURCE.to_string()]);
+
+    let mjpeg = open(8081, "HTTP/1.0 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary=frame\r\n\r\n");
+    assert!(mjpeg.is_camera());
+    assert!(matches!(mjpeg.to_webcam().access_type, WebcamAccessType::MJPEG));
+
+    let camera = open(80, CAMERA_ANSWER);
+    assert_eq!(camera.header("SERVER").as_deref(), Some("Hikvision-Webs"));
+    assert_eq!(camera.to_webcam().classification.map(|c| c.vendor.name()), Some("hikvision"));
+
+    // Neither a router, nor a port speaking something else
+    assert!(!open(80, ROUTER_ANSWER).is_camera());
+    assert!(!open(554, "SSH-2.0-OpenSSH_9.6\r\n").is_camera());
+    assert!(!open(80, "").is_camera());
+}
+
+#[tokio::test]
+async fn open_ports_are_found_and_read() {
+    let camera = serve(CAMERA_ANSWER);
+    let router = serve(ROUTER_ANSWER);
+    let closed = closed_port();
+
+    let scan = scan(&[Ipv4Addr::LOCALHOST], &[camera, router, closed], 2, Duration::from_millis(500)).await;
+    assert_eq!(scan.hosts, 1);
+    assert_eq!(scan.attempts, 3);
+    let mut open: Vec<u16> = scan.open.iter().map(|port| port.port).collect();
+    open.sort_unstable();
+    let mut expected = vec![camera, router];
+    expected.sort_unstable();
+    assert_eq!(open, expected);
+
+    let candidates = scan.candidates();
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].webcam.url, format!("http://127.0.0.1:{}/", camera));
+    assert_eq!(candidates[0].webcam.product.as_deref(), Some("Hikvision-Webs"));
+    assert!(candidates[0].banner.contains("<title>Login</title>"));
+}
+
+#[test]
+fn the_tool_needs_the_flag() {
+    assert!(!tool_names(scanning_server()).contains(&"scan_network_for_cameras".to_string()));
+    assert!(!tool_names(scanning_server().network_scan(true).remote(false)).contains(&"scan_network_for_cameras".to_string()));
+    assert!(tool_names(scanning_server().network_scan(true)).contains(&"scan_network_for_cameras".to_string()));
+}
+
+#[test]
+fn the_tool_lists_candidates_and_refuses_large_ranges() {
+    let camera = serve(CAMERA_ANSWER);
+    let result = call(scanning_server().network_scan(true), json!({ "cidr": "127.0.0.1/32", "ports": [camera, closed_port()] }));
+    assert_eq!(result["total"], 1);
+    assert_eq!(result["candidates"][0]["port"], camera);
+    assert_eq!(result["candidates"][0]["protocol"], "http");
+    assert_eq!(result["candidates"][0]["sources"], json!(["scan"]));
+    assert_eq!(result["metadata"]["cidr"], "127.0.0.1/32");
+    assert_eq!(result["metadata"]["open_ports"], 1);
+
+    for arguments in [
+        json!({ "cidr": "10.0.0.0/16" }),
+        json!({ "cidr": "10.0.0.0/24", "ports": [] }),
+        json!({ "cidr": "10.0.0.0/24", "concurrency": 1000 }),
+        json!({ "cidr": "10.0.0.0/24", "connect_timeout_ms": 60_000 }),
+        json!({}),
+    ] {
+        let result = call(scanning_server().network_scan(true), arguments.clone());
+        assert_eq!(result["error"]["code"], "INVALID_PARAMS", "{}", arguments);
+    }
+}
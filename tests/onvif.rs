@@ -0,0 +1,93 @@
+//! ONVIF WS-Discovery against a stand-in camera answering on loopback:
+//! the probe it receives, the ProbeMatch parsed into a device and a
+//! webcam entry, a device answering twice listed once, and replies to
+//! someone else's probe ignored.
+
+use mcp_webcam::onvif::{discover_via, parse_probe_matches, probe_message, SOURCE};
+use mcp_webcam::{MockBackend, WebcamAccessType, WebcamMcpServer};
+use serde_json::json;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::time::Duration;
+
+const PROBE_MATCHES: &str = include_str!("fixtures/onvif_probe_matches.xml");
+const LOOPBACK: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+#[test]
+fn probe_matches_become_devices_and_webcams() {
+    let devices = parse_probe_matches(PROBE_MATCHES, LOOPBACK);
+    assert_eq!(devices.len(), 1);
+    let device = &devices[0];
+    assert_eq!(device.endpoint, "urn:uuid:4419a0c2-55e3-11b2-8d4c-bcbac2a1b2c3");
+    assert_eq!(device.xaddrs, vec![
+        "http://[fe80::bacb:acff:fea1:b2c3]/onvif/device_service",
+        "http://127.0.0.1:8000/onvif/device_service",
+    ]);
+    assert_eq!(device.types, vec!["dn:NetworkVideoTransmitter", "tds:Device"]);
+    assert_eq!(device.scopes.len(), 5);
+    assert_eq!(device.name.as_deref(), Some("HIKVISION Front Door"));
+    assert_eq!(device.hardware.as_deref(), Some("DS-2CD2042WD-I"));
+
+    // The device service on the address that answered
+    let webcam = device.to_webcam();
+    assert_eq!(webcam.ip, "127.0.0.1");
+    assert_eq!(webcam.port, 8000);
+    assert_eq!(webcam.url, "http://127.0.0.1:8000/onvif/device_service");
+    assert_eq!(webcam.product.as_deref(), Some("DS-2CD2042WD-I"));
+    assert_eq!(webcam.sources, vec![SOURCE.to_string()]);
+    assert!(matches!(webcam.access_type, WebcamAccessType::ONVIF));
+    assert_eq!(webcam.classification.map(|c| c.vendor.name()), Some("hikvision"));
+
+    // Any prefix will do; a match without an endpoint reference is skipped
+    let unprefixed = r#"<ProbeMatches><ProbeMatch><XAddrs>http://192.0.2.7/onvif/device_service</XAddrs></ProbeMatch>
+        <ProbeMatch><EndpointReference><Address>urn:uuid:7</Address></EndpointReference><Scopes/></ProbeMatch></ProbeMatches>"#;
+    let devices = parse_probe_matches(unprefixed, "192.0.2.7".parse().unwrap());
+    assert_eq!(devices.len(), 1);
+    assert!(devices[0].xaddrs.is_empty());
+    assert_eq!(devices[0].to_webcam().url, "http://192.0.2.7/onvif/device_service");
+}
+
+#[test]
+fn the_probe_asks_for_video_transmitters() {
+    let probe = probe_message("uuid:1234");
+    assert!(probe.contains("<a:MessageID>uuid:1234</a:MessageID>"));
+    assert!(probe.contains("http://schemas.xmlsoap.org/ws/2005/04/discovery/Probe"));
+    assert!(probe.contains("<d:Types>dn:NetworkVideoTransmitter</d:Types>"));
+}
+
+#[tokio::test]
+async fn a_camera_answering_twice_is_listed_once() {
+    let camera = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = camera.local_addr().unwrap();
+    let answering = std::thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        let (length, from) = camera.recv_from(&mut buffer).unwrap();
+        let probe = String::from_utf8_lossy(&buffer[..length]).into_owned();
+        let message_id = probe.split("<a:MessageID>").nth(1).and_then(|rest| rest.split('<').next()).unwrap().to_string();
+        let reply = PROBE_MATCHES.replace("uuid:RELATES-TO", &message_id);
+        camera.send_to(reply.as_bytes(), from).unwrap();
+        camera.send_to(reply.as_bytes(), from).unwrap();
+        camera.send_to(PROBE_MATCHES.replace("uuid:RELATES-TO", "uuid:someone-else").as_bytes(), from).unwrap();
+        probe
+    });
+
+    let discovery = discover_via(&[Ipv4Addr::LOCALHOST], address, Duration::from_millis(500)).await.unwrap();
+    let probe = answering.join().unwrap();
+    assert!(probe.contains("NetworkVideoTransmitter"));
+    assert_eq!(discovery.interfaces, vec![Ipv4Addr::LOCALHOST]);
+    assert_eq!(discovery.devices.len(), 1);
+    assert_eq!(discovery.devices[0].responder, LOOPBACK);
+    assert_eq!(discovery.devices[0].name.as_deref(), Some("HIKVISION Front Door"));
+}
+
+#[test]
+fn the_listen_window_is_bounded() {
+    let server = WebcamMcpServer::builder().backend(Box::new(MockBackend::new(1))).preview(false).build().build_server().unwrap();
+    for timeout_ms in [json!(0), json!(60_000), json!("soon")] {
+        let request = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": { "name": "discover_local_network_cameras", "arguments": { "timeout_ms": timeout_ms } }
+        });
+        let response = server.handle_message(&request.to_string()).unwrap();
+        assert_eq!(response["result"]["structuredContent"]["error"]["code"], "INVALID_PARAMS", "{}", timeout_ms);
+    }
+}
@@ -0,0 +1,188 @@
+//! ONVIF snapshots from a stand-in camera on loopback: the media service
+//! found through the device service, profiles parsed and chosen, the
+//! snapshot fetched and processed, the RTSP stream reported by a camera
+//! without snapshots, faults and refused credentials, and the
+//! WS-UsernameToken digest.
+
+mod common;
+
+use common::{jpeg, media_uri, serve, HttpResponse, Requests, ONVIF_CAPABILITIES as CAPABILITIES, ONVIF_PROFILES as PROFILES};
+use mcp_webcam::onvif_snapshot::{parse_fault, parse_media_service, parse_media_uri, parse_profiles, soap_envelope, username_token, MEDIA_NAMESPACE};
+use mcp_webcam::{MockBackend, ShodanClient, ShodanError, WebcamCredentials, WebcamMcpServer};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const NOT_SUPPORTED: &str = r#"<env:Envelope xmlns:env="http://www.w3.org/2003/05/soap-envelope" xmlns:ter="http://www.onvif.org/ver10/error"><env:Body><env:Fault><env:Code><env:Value>env:Receiver</env:Value><env:Subcode><env:Value>ter:ActionNotSupported</env:Value></env:Subcode></env:Code><env:Reason><env:Text xml:lang="en">Snapshots are not supported</env:Text></env:Reason></env:Fault></env:Body></env:Envelope>"#;
+const NOT_AUTHORIZED: &str = r#"<env:Envelope xmlns:env="http://www.w3.org/2003/05/soap-envelope" xmlns:ter="http://www.onvif.org/ver10/error"><env:Body><env:Fault><env:Code><env:Value>env:Sender</env:Value><env:Subcode><env:Value>ter:NotAuthorized</env:Value></env:Subcode></env:Code><env:Reason><env:Text xml:lang="en">Sender not Authorized</env:Text></env:Reason></env:Fault></env:Body></env:Envelope>"#;
+
+/// A stand-in camera
+#[derive(Clone, Copy)]
+struct Camera {
+    /// Whether it answers GetSnapshotUri
+    snapshots: bool,
+    /// Whether it faults requests without a UsernameToken
+    requires_token: bool,
+}
+
+/// Serve `camera` on loopback; returns its device service URL and the
+/// requests it received
+fn serve_camera(camera: Camera) -> (String, Requests) {
+    let (base, requests) = serve(move |request| {
+        if request.method == "GET" && request.path == "/snapshot.jpg" {
+            return HttpResponse::new("200 OK", "image/jpeg", jpeg());
+        }
+        let body = String::from_utf8_lossy(&request.body);
+        let soap = |status, xml: String| HttpResponse::new(status, "application/soap+xml; charset=utf-8", xml);
+        match request.soap_action().unwrap_or_default() {
+            _ if camera.requires_token && !body.contains("UsernameToken") => soap("400 Bad Request", NOT_AUTHORIZED.to_string()),
+            "GetCapabilities" => soap("200 OK", CAPABILITIES.replace("http://192.0.2.64", &request.base_url())),
+            "GetProfiles" => soap("200 OK", PROFILES.to_string()),
+            "GetSnapshotUri" if camera.snapshots => soap("200 OK", media_uri(&format!("{}/snapshot.jpg", request.base_url()))),
+            "GetSnapshotUri" => soap("500 Internal Server Error", NOT_SUPPORTED.to_string()),
+            "GetStreamUri" => soap("200 OK", media_uri("rtsp://127.0.0.1:554/Streaming/Channels/102")),
+            _ => soap("400 Bad Request", NOT_SUPPORTED.to_string()),
+        }
+    });
+    (format!("{}/onvif/device_service", base), requests)
+}
+
+fn client() -> ShodanClient {
+    ShodanClient::builder().allow_private_targets(true).build("").unwrap()
+}
+
+/// SOAP actions the camera was asked for, each with the request's body
+fn soap_requests(requests: &Requests) -> Vec<(String, String)> {
+    requests
+        .all()
+        .iter()
+        .filter(|request| request.method == "POST")
+        .map(|request| (request.soap_action().unwrap_or_default().to_string(), String::from_utf8_lossy(&request.body).into_owned()))
+        .collect()
+}
+
+fn actions(requests: &Requests) -> Vec<String> {
+    soap_requests(requests).into_iter().map(|(action, _)| action).collect()
+}
+
+fn call(arguments: Value) -> Value {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client_settings(ShodanClient::builder().allow_private_targets(true))
+        .host_cooldown(Duration::ZERO)
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+    common::call(&server, "capture_onvif_snapshot", arguments)
+}
+
+#[test]
+fn responses_are_parsed() {
+    assert_eq!(parse_media_service(CAPABILITIES).as_deref(), Some("http://192.0.2.64/onvif/Media"));
+
+    let profiles = parse_profiles(PROFILES);
+    assert_eq!(profiles.len(), 2);
+    assert_eq!(profiles[0].token, "Profile_1");
+    assert_eq!(profiles[0].name.as_deref(), Some("mainStream"));
+    assert_eq!(profiles[0].encoding.as_deref(), Some("H264"));
+    assert_eq!((profiles[0].width, profiles[0].height), (Some(2560), Some(1440)));
+    assert_eq!(profiles[1].token, "Profile_2");
+    assert_eq!(profiles[1].encoding.as_deref(), Some("JPEG"));
+
+    assert_eq!(parse_media_uri(&media_uri("http://192.0.2.64/snapshot.jpg?a=1&amp;b=2")).as_deref(), Some("http://192.0.2.64/snapshot.jpg?a=1&b=2"));
+
+    let fault = parse_fault(NOT_SUPPORTED).unwrap();
+    assert_eq!(fault.reason, "Snapshots are not supported (ter:ActionNotSupported)");
+    assert!(!fault.not_authorized);
+    assert!(parse_fault(NOT_AUTHORIZED).unwrap().not_authorized);
+    assert!(parse_fault(PROFILES).is_none());
+}
+
+#[test]
+fn the_username_token_carries_a_password_digest() {
+    // Base64(SHA-1(nonce + created + password)), as ONVIF cameras check it
+    let credentials = WebcamCredentials::new("admin<1>", "12345");
+    let token = username_token(&credentials, b"0123456789abcdef", "2026-01-01T00:00:00.000Z");
+    assert!(token.contains("<Username>admin&lt;1&gt;</Username>"));
+    assert!(token.contains(">EacIrKOD6XcH4k/nCyP5TegFO1g=</Password>"));
+    assert!(token.contains(">MDEyMzQ1Njc4OWFiY2RlZg==</Nonce>"));
+    assert!(token.contains(">2026-01-01T00:00:00.000Z</Created>"));
+    assert!(!token.contains("12345"));
+
+    let envelope = soap_envelope(MEDIA_NAMESPACE, "GetProfiles", "", Some(&credentials));
+    assert!(envelope.contains("<s:Header><Security"));
+    assert!(envelope.contains(r#"<s:Body><GetProfiles xmlns="http://www.onvif.org/ver10/media/wsdl"></GetProfiles></s:Body>"#));
+    assert!(!soap_envelope(MEDIA_NAMESPACE, "GetProfiles", "", None).contains("Header"));
+}
+
+#[tokio::test]
+async fn the_snapshot_is_fetched_from_the_media_service() {
+    let (device_url, requests) = serve_camera(Camera { snapshots: true, requires_token: true });
+    let credentials = WebcamCredentials::new("admin", "12345");
+    let snapshot = client().onvif_snapshot(&device_url, Some(&credentials), Some("Profile_2")).await.unwrap();
+    assert_eq!(snapshot.media_url, device_url.replace("/onvif/device_service", "/onvif/Media"));
+    assert_eq!(snapshot.profiles.len(), 2);
+    assert_eq!(snapshot.profile.token, "Profile_2");
+    assert!(snapshot.snapshot_uri.unwrap().ends_with("/snapshot.jpg"));
+    assert!(snapshot.stream_uri.is_none());
+    let image = snapshot.image.unwrap();
+    assert_eq!(image.mime_type, "image/jpeg");
+
+    assert_eq!(actions(&requests), vec!["GetCapabilities", "GetProfiles", "GetSnapshotUri"]);
+    let requested = soap_requests(&requests)[2].1.clone();
+    assert!(requested.contains("<ProfileToken>Profile_2</ProfileToken>"));
+}
+
+#[tokio::test]
+async fn a_camera_without_snapshots_reports_its_stream() {
+    let (device_url, requests) = serve_camera(Camera { snapshots: false, requires_token: false });
+    let snapshot = client().onvif_snapshot(&device_url, None, None).await.unwrap();
+    assert_eq!(snapshot.profile.token, "Profile_1");
+    assert!(snapshot.snapshot_uri.is_none() && snapshot.image.is_none());
+    assert_eq!(snapshot.stream_uri.as_deref(), Some("rtsp://127.0.0.1:554/Streaming/Channels/102"));
+    assert_eq!(actions(&requests), vec!["GetCapabilities", "GetProfiles", "GetSnapshotUri", "GetStreamUri"]);
+    assert!(soap_requests(&requests)[3].1.contains("<Protocol>RTSP</Protocol>"));
+}
+
+#[tokio::test]
+async fn faults_and_missing_credentials_are_reported() {
+    let (device_url, _) = serve_camera(Camera { snapshots: true, requires_token: true });
+    let error = client().onvif_snapshot(&device_url, None, None).await.unwrap_err();
+    assert!(matches!(error, ShodanError::AuthRequired { ref scheme, .. } if scheme == "WS-UsernameToken"), "{:?}", error);
+
+    let (device_url, _) = serve_camera(Camera { snapshots: true, requires_token: false });
+    let error = client().onvif_snapshot(&device_url, None, Some("Profile_9")).await.unwrap_err();
+    assert!(matches!(error, ShodanError::OnvifFault { ref action, ref reason } if action == "GetProfiles" && reason.contains("Profile_9")), "{:?}", error);
+
+    // Loopback is refused unless private targets are allowed
+    let error = ShodanClient::builder().build("").unwrap().onvif_snapshot(&device_url, None, None).await.unwrap_err();
+    assert!(matches!(error, ShodanError::TargetBlocked { .. }), "{:?}", error);
+}
+
+#[test]
+fn the_tool_returns_the_image_or_the_stream() {
+    let (device_url, _) = serve_camera(Camera { snapshots: true, requires_token: true });
+    let result = call(json!({ "url": device_url, "username": "admin", "password": "12345", "max_width": 160, "stats": true }));
+    assert_eq!(result["content"][0]["type"], "image");
+    let metadata = &result["structuredContent"]["metadata"];
+    assert_eq!(metadata["source"], "onvif");
+    assert_eq!(metadata["captured"], true);
+    assert_eq!(metadata["profile"]["token"], "Profile_1");
+    assert_eq!((metadata["width"].as_u64(), metadata["height"].as_u64()), (Some(160), Some(120)));
+    assert_eq!(metadata["source_width"], 320);
+    assert!(metadata["stats"]["mean_rgb"].is_array());
+
+    let (device_url, _) = serve_camera(Camera { snapshots: false, requires_token: false });
+    let result = call(json!({ "url": device_url }));
+    assert_eq!(result["isError"], false);
+    let metadata = &result["structuredContent"]["metadata"];
+    assert_eq!(metadata["captured"], false);
+    assert_eq!(metadata["snapshot_uri"], json!(null));
+    assert_eq!(metadata["stream_uri"], "rtsp://127.0.0.1:554/Streaming/Channels/102");
+    assert!(result["content"][0]["text"].as_str().unwrap().contains("capture_remote_image"));
+
+    let (device_url, _) = serve_camera(Camera { snapshots: true, requires_token: true });
+    let result = call(json!({ "url": device_url }));
+    assert_eq!(result["structuredContent"]["error"]["code"], "WEBCAM_AUTH_REQUIRED");
+    assert_eq!(call(json!({}))["structuredContent"]["error"]["code"], "INVALID_PARAMS");
+}
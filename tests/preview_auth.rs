@@ -0,0 +1,139 @@
+//! Token authentication of the live preview HTTP server.
+
+use mcp_webcam::auth::{self, AuthOutcome, Authenticator, MAX_AUTH_FAILURES};
+use mcp_webcam::{AuthToken, MockBackend, PreviewConfig, PreviewServer, WebcamManager};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const TOKEN: &str = "s3cret-preview-token";
+
+fn start_preview() -> PreviewServer {
+    let manager = Arc::new(Mutex::new(WebcamManager::with_backend(Box::new(MockBackend::new(1)))));
+    let config = PreviewConfig {
+        bind_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        auth_token: Some(AuthToken::new(TOKEN).unwrap()),
+        ..Default::default()
+    };
+    PreviewServer::start(manager, config, &tokio::runtime::Handle::current()).unwrap()
+}
+
+/// Status line of the response to a GET of `target`
+async fn get(address: &str, target: &str, bearer: Option<&str>) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let authorization = bearer
+        .map(|token| format!("Authorization: Bearer {}\r\n", token))
+        .unwrap_or_default();
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n{}\r\n", target, address, authorization);
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    String::from_utf8_lossy(&response).lines().next().unwrap_or_default().to_string()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn preview_requires_the_configured_token() {
+    let preview = start_preview();
+    let address = preview.info().address;
+    assert!(preview.info().url.ends_with(&format!("?token={}", TOKEN)));
+
+    assert_eq!(get(&address, "/", None).await, "HTTP/1.1 401 Unauthorized");
+    assert_eq!(get(&address, "/", Some("wrong")).await, "HTTP/1.1 401 Unauthorized");
+    assert_eq!(get(&address, "/?token=wrong", None).await, "HTTP/1.1 401 Unauthorized");
+
+    assert_eq!(get(&address, "/", Some(TOKEN)).await, "HTTP/1.1 200 OK");
+    assert_eq!(get(&address, &format!("/?token={}", TOKEN), None).await, "HTTP/1.1 200 OK");
+
+    preview.stop();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn repeated_failures_lock_the_address_out() {
+    let preview = start_preview();
+    let address = preview.info().address;
+
+    for _ in 0..MAX_AUTH_FAILURES {
+        assert_eq!(get(&address, "/", Some("wrong")).await, "HTTP/1.1 401 Unauthorized");
+    }
+    // Even the right token is refused until the lockout ends
+    assert_eq!(get(&address, "/", Some(TOKEN)).await, "HTTP/1.1 429 Too Many Requests");
+
+    preview.stop();
+}
+
+#[test]
+fn lockout_expires_and_success_resets_failures() {
+    let peer = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let auth = Authenticator::with_limits(AuthToken::new(TOKEN).unwrap(), 2, Duration::from_millis(50));
+
+    assert_eq!(auth.check(peer, Some("wrong")), AuthOutcome::Rejected);
+    assert_eq!(auth.check(peer, Some(TOKEN)), AuthOutcome::Allowed);
+    assert_eq!(auth.check(peer, None), AuthOutcome::Rejected);
+    assert_eq!(auth.check(peer, Some("wrong")), AuthOutcome::Rejected);
+    assert_eq!(auth.check(peer, Some(TOKEN)), AuthOutcome::LockedOut);
+
+    // Other addresses are unaffected
+    assert_eq!(auth.check(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), Some(TOKEN)), AuthOutcome::Allowed);
+
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(auth.check(peer, Some(TOKEN)), AuthOutcome::Allowed);
+}
+
+#[test]
+fn token_is_read_from_bearer_header_before_query() {
+    let head = "GET /stream?token=from-query HTTP/1.1\r\nauthorization: bearer from-header\r\n\r\n";
+    assert_eq!(auth::presented_token(head, "token=from-query").as_deref(), Some("from-header"));
+
+    let head = "GET /stream?token=from-query HTTP/1.1\r\nHost: x\r\n\r\n";
+    assert_eq!(auth::presented_token(head, "fps=5&token=from-query").as_deref(), Some("from-query"));
+    assert_eq!(auth::presented_token(head, "").as_deref(), None);
+}
+
+#[test]
+fn query_tokens_are_percent_decoded() {
+    let head = "GET /stream HTTP/1.1\r\nHost: x\r\n\r\n";
+    assert_eq!(auth::presented_token(head, "token=a%2Bb%2Fc%25d%26e").as_deref(), Some("a+b/c%d&e"));
+    assert_eq!(auth::presented_token(head, "fps=5&tok%65n=abc").as_deref(), Some("abc"));
+    // As in form data, a literal `+` is a space
+    assert_eq!(auth::presented_token(head, "token=a+b").as_deref(), Some("a b"));
+
+    let token = AuthToken::new("a+b/c%d&e").unwrap();
+    assert_eq!(token.query_value(), "a%2Bb%2Fc%25d%26e");
+    assert_eq!(AuthToken::new("plain-token_1.0").unwrap().query_value(), "plain-token_1.0");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tokens_with_reserved_characters_work_in_the_query() {
+    let token = "p+ss/w%rd&x=1";
+    let manager = Arc::new(Mutex::new(WebcamManager::with_backend(Box::new(MockBackend::new(1)))));
+    let config = PreviewConfig {
+        bind_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        auth_token: Some(AuthToken::new(token).unwrap()),
+        ..Default::default()
+    };
+    let preview = PreviewServer::start(manager, config, &tokio::runtime::Handle::current()).unwrap();
+    let address = preview.info().address;
+
+    // The URLs handed out carry the token encoded, and work as given
+    let encoded = "p%2Bss%2Fw%25rd%26x%3D1";
+    assert!(preview.info().url.ends_with(&format!("/?token={}", encoded)));
+    assert_eq!(get(&address, &format!("/?token={}", encoded), None).await, "HTTP/1.1 200 OK");
+    assert_eq!(get(&address, &format!("/?token={}", token), None).await, "HTTP/1.1 401 Unauthorized");
+    assert_eq!(get(&address, "/", Some(token)).await, "HTTP/1.1 200 OK");
+
+    preview.stop();
+}
+
+#[test]
+fn tokens_compare_by_content_and_are_not_logged() {
+    let token = AuthToken::new(TOKEN).unwrap();
+    assert!(token.matches(TOKEN));
+    assert!(!token.matches("s3cret-preview-tokeN"));
+    assert!(!token.matches("s3cret"));
+    assert!(!format!("{:?}", token).contains(TOKEN));
+    assert!(AuthToken::new("has space").is_err());
+    assert!(AuthToken::new("").is_err());
+}
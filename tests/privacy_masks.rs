@@ -0,0 +1,160 @@
+//! Server-configured privacy masks black out every captured frame, and a bad
+//! mask configuration disables capture instead of being ignored.
+
+use mcp_webcam::{AuthToken, CaptureOptions, MockBackend, PreviewConfig, PreviewServer, WebcamError, WebcamManager};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_util::sync::CancellationToken;
+
+/// Masks the top-left quarter of camera 0's 320x240 mock frames
+const TOP_LEFT_MASK: &str = r#"{"0": [{"x": 0, "y": 0, "width": 160, "height": 120}]}"#;
+const TOKEN: &str = "privacy-mask-preview-token";
+
+/// Masks are read from the environment when the manager is built, so tests
+/// setting the variables take turns
+static ENV: Mutex<()> = Mutex::new(());
+
+fn manager_with_masks(masks: &str) -> WebcamManager {
+    let _guard = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    std::env::set_var("MCP_WEBCAM_PRIVACY_MASKS", masks);
+    let manager = WebcamManager::with_backend(Box::new(MockBackend::new(1)));
+    std::env::remove_var("MCP_WEBCAM_PRIVACY_MASKS");
+    manager
+}
+
+/// The masked quarter is black and the rest still shows the mid-grey frame
+fn assert_top_left_masked(image: &image::RgbImage) {
+    assert_eq!(image.dimensions(), (320, 240));
+    for (x, y) in [(0, 0), (80, 60), (150, 110)] {
+        assert!(image.get_pixel(x, y).0.iter().all(|&c| c < 16), "({}, {}) not masked: {:?}", x, y, image.get_pixel(x, y));
+    }
+    for (x, y) in [(240, 60), (80, 180), (300, 220)] {
+        assert!(image.get_pixel(x, y).0.iter().all(|&c| c.abs_diff(128) < 16), "({}, {}) masked: {:?}", x, y, image.get_pixel(x, y));
+    }
+}
+
+#[test]
+fn captured_images_are_masked() {
+    let mut manager = manager_with_masks(TOP_LEFT_MASK);
+
+    let result = manager.capture_image(Some(0)).unwrap();
+    assert_eq!(result.privacy_masked_regions, 1);
+    assert_top_left_masked(&image::load_from_memory(&result.image_bytes).unwrap().into_rgb8());
+
+    // Masks are in full-frame coordinates, applied before any processing
+    let rotated = manager
+        .capture_image_with_options(Some(0), &CaptureOptions { rotation: Some(180), ..Default::default() })
+        .unwrap();
+    let rotated = image::load_from_memory(&rotated.image_bytes).unwrap().into_rgb8();
+    assert!(rotated.get_pixel(300, 220).0.iter().all(|&c| c < 16));
+    assert!(rotated.get_pixel(20, 20).0.iter().all(|&c| c.abs_diff(128) < 16));
+}
+
+#[test]
+fn clip_frames_are_masked() {
+    let manager = Mutex::new(manager_with_masks(TOP_LEFT_MASK));
+
+    let frames = WebcamManager::capture_sequence_shared(
+        &manager,
+        Some(0),
+        &CaptureOptions::default(),
+        3,
+        Duration::from_millis(10),
+        &CancellationToken::new(),
+        Ok,
+    )
+    .unwrap();
+
+    assert_eq!(frames.len(), 3);
+    for frame in &frames {
+        assert_eq!(frame.privacy_masked_regions, 1);
+        assert_top_left_masked(&frame.image);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn preview_frames_are_masked() {
+    let manager = Arc::new(Mutex::new(manager_with_masks(TOP_LEFT_MASK)));
+    let config = PreviewConfig {
+        bind_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        fps: 30,
+        auth_token: Some(AuthToken::new(TOKEN).unwrap()),
+        ..Default::default()
+    };
+    let preview = PreviewServer::start(manager, config, &tokio::runtime::Handle::current()).unwrap();
+    let address = preview.info().address;
+
+    let mut stream = TcpStream::connect(&address).await.unwrap();
+    let request = format!("GET /stream HTTP/1.1\r\nHost: {}\r\nAuthorization: Bearer {}\r\n\r\n", address, TOKEN);
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    // Read up to the first part's body: the response head, then the part header
+    let mut received = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let (length, body_start) = loop {
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut chunk)).await.unwrap().unwrap();
+        assert!(read > 0, "preview closed the stream");
+        received.extend_from_slice(&chunk[..read]);
+        let text = String::from_utf8_lossy(&received).into_owned();
+        let part_header = text
+            .find("Content-Length: ")
+            .and_then(|start| text[start..].find("\r\n\r\n").map(|end| (start, start + end)));
+        if let Some((start, end)) = part_header {
+            break (text[start + "Content-Length: ".len()..end].parse::<usize>().unwrap(), end + 4);
+        }
+    };
+    let mut jpeg = received[body_start..].to_vec();
+    if jpeg.len() < length {
+        let mut rest = vec![0; length - jpeg.len()];
+        stream.read_exact(&mut rest).await.unwrap();
+        jpeg.extend(rest);
+    }
+    jpeg.truncate(length);
+
+    assert_top_left_masked(&image::load_from_memory(&jpeg).unwrap().into_rgb8());
+    preview.stop();
+}
+
+#[test]
+fn invalid_mask_configuration_refuses_capture() {
+    for masks in [r#"{"0": [{"x": 0, "y": 0}]}"#, "not json", r#"{"front": []}"#] {
+        let mut manager = manager_with_masks(masks);
+        assert!(manager.privacy_masks().is_none(), "{} accepted", masks);
+
+        let err = manager.capture_image(Some(0)).unwrap_err();
+        assert!(matches!(err, WebcamError::PrivacyMaskConfig(_)), "{}: {:?}", masks, err);
+        let err = manager.capture_frame(Some(0), &CaptureOptions::default()).unwrap_err();
+        assert!(matches!(err, WebcamError::PrivacyMaskConfig(_)), "{}: {:?}", masks, err);
+
+        let manager = Mutex::new(manager);
+        let err = WebcamManager::capture_sequence_shared(
+            &manager,
+            Some(0),
+            &CaptureOptions::default(),
+            2,
+            Duration::from_millis(10),
+            &CancellationToken::new(),
+            Ok,
+        )
+        .unwrap_err();
+        assert!(matches!(err, WebcamError::PrivacyMaskConfig(_)), "{}: {:?}", masks, err);
+    }
+}
+
+#[test]
+fn unreadable_mask_file_refuses_capture() {
+    let mut manager = {
+        let _guard = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let missing = std::env::temp_dir().join(format!("mcp-webcam-no-masks-{}.json", std::process::id()));
+        std::env::set_var("MCP_WEBCAM_PRIVACY_MASKS_FILE", &missing);
+        let manager = WebcamManager::with_backend(Box::new(MockBackend::new(1)));
+        std::env::remove_var("MCP_WEBCAM_PRIVACY_MASKS_FILE");
+        manager
+    };
+
+    let err = manager.capture_image(Some(0)).unwrap_err();
+    assert!(matches!(err, WebcamError::PrivacyMaskConfig(ref message) if message.contains("failed to read")), "{:?}", err);
+}
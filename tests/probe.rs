@@ -0,0 +1,85 @@
+//! Probing a stand-in webcam for its snapshot URL: the paths tried, the
+//! first image found, and what comes back when no path serves one.
+
+mod common;
+
+use common::{serve, HttpResponse, Requests};
+use mcp_webcam::probe::snapshot_paths;
+use mcp_webcam::tool_error::{ErrorCode, ToolError};
+use mcp_webcam::{ProbeOptions, RemoteWebcam, ShodanClient, ShodanError};
+
+const JPEG: &[u8] = b"\xFF\xD8\xFF\xE0probe\xFF\xD9";
+
+/// A webcam answering every request with a 404, except a JPEG on
+/// `snapshot_path` and a login challenge on `login_path`. Returns its port
+/// and the requests received, in order.
+fn serve_webcam(snapshot_path: Option<&'static str>, login_path: Option<&'static str>) -> (u16, Requests) {
+    let (base, requests) = serve(move |request| {
+        if Some(request.path.as_str()) == snapshot_path {
+            HttpResponse::new("200 OK", "image/jpeg", JPEG)
+        } else if Some(request.path.as_str()) == login_path {
+            HttpResponse::empty("401 Unauthorized").header("WWW-Authenticate", "Basic realm=\"cam\"")
+        } else {
+            HttpResponse::new("404 Not Found", "text/html", "not found")
+        }
+    });
+    (base.rsplit(':').next().unwrap().parse().unwrap(), requests)
+}
+
+fn webcam(port: u16, product: Option<&str>) -> RemoteWebcam {
+    RemoteWebcam { url: String::new(), product: product.map(str::to_string), ..common::webcam("127.0.0.1", port) }
+}
+
+#[test]
+fn the_makers_paths_come_first_and_none_twice() {
+    let generic = snapshot_paths(None);
+    assert_eq!(generic[0], "/snapshot.jpg");
+    assert_eq!(generic.last(), Some(&"/"));
+
+    let hikvision = snapshot_paths(Some("Hikvision IP Camera"));
+    assert_eq!(&hikvision[..2], ["/ISAPI/Streaming/channels/101/picture", "/Streaming/channels/1/picture"]);
+    let mut deduplicated = hikvision.clone();
+    deduplicated.sort();
+    deduplicated.dedup();
+    assert_eq!(deduplicated.len(), hikvision.len());
+    assert_eq!(snapshot_paths(Some("")), generic);
+}
+
+#[tokio::test]
+async fn probes_stop_at_the_first_image() {
+    let (port, requested) = serve_webcam(Some("/axis-cgi/jpg/image.cgi"), None);
+    let client = ShodanClient::builder().allow_private_targets(true).build("key").unwrap();
+    let options = ProbeOptions { concurrency: 1, ..ProbeOptions::default() };
+
+    // Axis paths go first, so its make finds the image at once
+    let found = client.probe_webcam(&webcam(port, Some("AXIS M1065")), None, &options).await.unwrap();
+    assert_eq!(found.url, format!("http://127.0.0.1:{}/axis-cgi/jpg/image.cgi", port));
+    assert_eq!(found.attempts, 1);
+    assert_eq!(found.image.mime_type, "image/jpeg");
+    assert_eq!(found.image.bytes, JPEG);
+
+    // Without it, the generic paths before it get a 404 first
+    let before = requested.all().len();
+    let found = client.probe_webcam(&webcam(port, None), None, &options).await.unwrap();
+    let position = snapshot_paths(None).iter().position(|path| *path == "/axis-cgi/jpg/image.cgi").unwrap();
+    assert_eq!(found.attempts, position + 1);
+    assert_eq!(requested.all().len() - before, position + 1);
+}
+
+#[tokio::test]
+async fn failed_probes_say_how_many_paths_were_tried_or_that_a_login_is_needed() {
+    let client = ShodanClient::builder().allow_private_targets(true).build("key").unwrap();
+    let options = ProbeOptions { max_requests: 5, concurrency: 2, ..ProbeOptions::default() };
+
+    let (port, requested) = serve_webcam(None, None);
+    let error = client.probe_webcam(&webcam(port, None), None, &options).await.unwrap_err();
+    assert!(matches!(error, ShodanError::NoSnapshotUrl { tried: 5 }), "{:?}", error);
+    assert_eq!(requested.all().len(), 5);
+    let tool_error = ToolError::from(&error);
+    assert_eq!(tool_error.code, ErrorCode::NoSnapshotUrl);
+    assert_eq!(tool_error.details.unwrap()["tried"], 5);
+
+    let (port, _) = serve_webcam(None, Some("/image.jpg"));
+    let error = client.probe_webcam(&webcam(port, None), None, &options).await.unwrap_err();
+    assert!(matches!(error, ShodanError::AuthRequired { .. }), "{:?}", error);
+}
@@ -0,0 +1,163 @@
+//! Per-tool rate limits and the cap on concurrent calls, enforced by the
+//! dispatch layer.
+
+mod common;
+
+use common::call;
+use mcp_webcam::protocol::{RateLimit, Server, ServerConfig, Transport};
+use mcp_webcam::{ConfigFile, MockBackend, WebcamMcpServer};
+use mcpr::error::MCPError;
+use mcpr::schema::common::{Tool, ToolInputSchema};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn tool(name: &str) -> Tool {
+    Tool {
+        name: name.to_string(),
+        description: None,
+        input_schema: ToolInputSchema { r#type: "object".to_string(), properties: None, required: None },
+    }
+}
+
+#[test]
+fn calls_beyond_the_rate_limit_are_rejected_with_a_retry_hint() {
+    let config = ServerConfig::new()
+        .with_tool(tool("ping"))
+        .with_tool(tool("pong"))
+        .with_rate_limit("ping", RateLimit::new(2, Duration::from_secs(60)));
+    let mut server = Server::new(config);
+    server.register_tool_handler("ping", |_| Ok(json!({ "content": [] }))).unwrap();
+    server.register_tool_handler("pong", |_| Ok(json!({ "content": [] }))).unwrap();
+
+    assert_eq!(call(&server, "ping", json!({}))["isError"], false);
+    assert_eq!(call(&server, "ping", json!({}))["isError"], false);
+    let limited = call(&server, "ping", json!({}));
+    assert_eq!(limited["isError"], true);
+    let error = &limited["structuredContent"]["error"];
+    assert_eq!(error["code"], "RATE_LIMITED");
+    assert_eq!(error["details"]["calls"], 2);
+    assert_eq!(error["details"]["per_seconds"], 60);
+    // One call comes back every 30 s
+    let retry_after = error["details"]["retry_after_seconds"].as_u64().unwrap();
+    assert!((29..=30).contains(&retry_after), "{}", retry_after);
+
+    // Other tools have their own limits, here none
+    for _ in 0..5 {
+        assert_eq!(call(&server, "pong", json!({}))["isError"], false);
+    }
+}
+
+#[test]
+fn handlers_still_running_after_a_timeout_count_towards_the_cap() {
+    let config = ServerConfig::new()
+        .with_tool(tool("stuck"))
+        .with_timeout("stuck", Duration::from_millis(50))
+        .with_max_concurrent_calls(1);
+    let mut server = Server::new(config);
+    // Ignores its cancellation token
+    server
+        .register_tool_handler("stuck", |_| {
+            std::thread::sleep(Duration::from_millis(400));
+            Ok(json!({ "content": [] }))
+        })
+        .unwrap();
+
+    let timed_out = call(&server, "stuck", json!({}));
+    assert_eq!(timed_out["structuredContent"]["error"]["code"], "TOOL_TIMEOUT");
+    let rejected = call(&server, "stuck", json!({}));
+    assert_eq!(rejected["structuredContent"]["error"]["code"], "RATE_LIMITED");
+    assert_eq!(rejected["structuredContent"]["error"]["details"]["max_concurrent_calls"], 1);
+
+    // Once the first handler returns there is room again
+    std::thread::sleep(Duration::from_millis(500));
+    let again = call(&server, "stuck", json!({}));
+    assert_eq!(again["structuredContent"]["error"]["code"], "TOOL_TIMEOUT");
+}
+
+/// Sends its messages `pause` apart, then reports the client as gone
+struct PacedTransport {
+    incoming: Mutex<VecDeque<String>>,
+    sent: Arc<Mutex<Vec<Value>>>,
+    pause: Duration,
+}
+
+impl Transport for PacedTransport {
+    fn receive(&self) -> Result<Option<String>, MCPError> {
+        std::thread::sleep(self.pause);
+        Ok(self.incoming.lock().unwrap().pop_front())
+    }
+
+    fn send(&self, message: &Value) -> Result<(), MCPError> {
+        self.sent.lock().unwrap().push(message.clone());
+        Ok(())
+    }
+}
+
+#[test]
+fn overlapping_calls_beyond_the_cap_are_rejected() {
+    let config = ServerConfig::new().with_tool(tool("slow")).with_max_concurrent_calls(1);
+    let mut server = Server::new(config);
+    server
+        .register_tool_handler("slow", |_| {
+            std::thread::sleep(Duration::from_millis(400));
+            Ok(json!({ "content": [] }))
+        })
+        .unwrap();
+
+    // The second call arrives while the first is still running
+    let calls = (1..=2).map(|id| {
+        json!({ "jsonrpc": "2.0", "id": id, "method": "tools/call", "params": { "name": "slow", "arguments": {} } }).to_string()
+    });
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let transport = PacedTransport { incoming: Mutex::new(calls.collect()), sent: Arc::clone(&sent), pause: Duration::from_millis(100) };
+    server.serve(transport).unwrap();
+
+    let sent = sent.lock().unwrap();
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0]["id"], 2);
+    let rejected = &sent[0]["result"]["structuredContent"]["error"];
+    assert_eq!(rejected["code"], "RATE_LIMITED");
+    assert_eq!(rejected["details"]["max_concurrent_calls"], 1);
+    assert_eq!(sent[1]["id"], 1);
+    assert_eq!(sent[1]["result"]["isError"], false);
+}
+
+#[tokio::test]
+async fn limits_are_configurable_reported_and_counted() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .rate_limit("capture_image", RateLimit::new(1, Duration::from_secs(60)))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    assert_eq!(call(&server, "capture_image", json!({ "camera_index": 0 }))["isError"], false);
+    let limited = call(&server, "capture_image", json!({ "camera_index": 0 }));
+    assert_eq!(limited["structuredContent"]["error"]["code"], "RATE_LIMITED");
+
+    let info = &call(&server, "get_server_info", json!({}))["structuredContent"]["server_info"];
+    assert_eq!(info["rate_limits"]["capture_image"], json!({ "calls": 1, "per_seconds": 60 }));
+    // Shodan searches use API credits, so they are limited by default
+    assert_eq!(info["rate_limits"]["search_webcams"], json!({ "calls": 5, "per_seconds": 3600 }));
+    assert_eq!(info["max_concurrent_calls"], 4);
+
+    let stats = &call(&server, "get_server_stats", json!({}))["structuredContent"]["stats"];
+    assert_eq!(stats["failures_by_type"]["rate_limited"], 1);
+}
+
+#[test]
+fn config_file_rate_limits_must_be_positive() {
+    let config: ConfigFile = toml::from_str(
+        "max_concurrent_calls = 2\n[rate_limits]\ncapture_image = { calls = 10, per_seconds = 60 }\n",
+    )
+    .unwrap();
+    assert_eq!(config.max_concurrent_calls, Some(2));
+    assert_eq!(config.rate_limits().unwrap()["capture_image"], RateLimit::new(10, Duration::from_secs(60)));
+
+    let config: ConfigFile = toml::from_str("[rate_limits]\nsearch_webcams = { calls = 0, per_seconds = 60 }\n").unwrap();
+    assert!(config.rate_limits().unwrap_err().contains("search_webcams"));
+    assert!(toml::from_str::<ConfigFile>("[rate_limits]\nsearch_webcams = { calls = 1, per = 60 }\n").is_err());
+}
@@ -0,0 +1,94 @@
+//! Hostnames filled in from reverse DNS: which webcams are looked up,
+//! concurrency and the time budget, with stand-in lookups.
+
+mod common;
+
+use mcp_webcam::rdns::{fill_hostnames, HostnameFill};
+use mcp_webcam::RemoteWebcam;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+fn webcam(ip: &str, port: u16, hostname: Option<&str>) -> RemoteWebcam {
+    RemoteWebcam { hostname: hostname.map(str::to_string), ..common::webcam(ip, port) }
+}
+
+#[tokio::test]
+async fn webcams_without_a_hostname_get_their_ptr_name() {
+    let mut webcams = vec![
+        webcam("81.2.69.1", 80, None),
+        webcam("81.2.69.1", 8080, None),
+        webcam("81.2.69.2", 80, Some("shodan.example.com")),
+        webcam("81.2.69.3", 80, None),
+        // Private and documentation addresses have no public PTR records
+        webcam("10.0.0.1", 80, None),
+        webcam("192.0.2.1", 80, None),
+    ];
+    let asked = Arc::new(Mutex::new(Vec::new()));
+    let asked_by_lookup = Arc::clone(&asked);
+    let fill = fill_hostnames(&mut webcams, Duration::from_secs(5), 4, move |ip: IpAddr| {
+        asked_by_lookup.lock().unwrap().push(ip.to_string());
+        async move { (ip.to_string() == "81.2.69.1").then(|| "host-81-2-69-1.broadband.example.net".to_string()) }
+    })
+    .await;
+
+    let mut asked = asked.lock().unwrap().clone();
+    asked.sort();
+    assert_eq!(asked, ["81.2.69.1", "81.2.69.3"], "each address once, and only those without a hostname");
+    assert_eq!(fill, HostnameFill { looked_up: 2, resolved: 2, unfinished: 0 });
+    let hostnames: Vec<Option<&str>> = webcams.iter().map(|webcam| webcam.hostname.as_deref()).collect();
+    assert_eq!(
+        hostnames,
+        [
+            Some("host-81-2-69-1.broadband.example.net"),
+            Some("host-81-2-69-1.broadband.example.net"),
+            Some("shodan.example.com"),
+            None,
+            None,
+            None
+        ]
+    );
+}
+
+#[tokio::test]
+async fn lookups_never_outlast_the_budget() {
+    let mut webcams: Vec<RemoteWebcam> = (1..=20).map(|n| webcam(&format!("81.2.69.{}", n), 80, None)).collect();
+    let running = Arc::new(AtomicUsize::new(0));
+    let most_running = Arc::new(AtomicUsize::new(0));
+    let (running_by_lookup, most_by_lookup) = (Arc::clone(&running), Arc::clone(&most_running));
+    let started = Instant::now();
+    let fill = fill_hostnames(&mut webcams, Duration::from_millis(300), 4, move |ip: IpAddr| {
+        let running = Arc::clone(&running_by_lookup);
+        let most_running = Arc::clone(&most_by_lookup);
+        async move {
+            most_running.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+            // Every other address answers quickly, the rest hang
+            let [.., last] = match ip {
+                IpAddr::V4(ip) => ip.octets(),
+                IpAddr::V6(_) => unreachable!(),
+            };
+            let delay = if last % 2 == 1 { Duration::from_millis(10) } else { Duration::from_secs(60) };
+            tokio::time::sleep(delay).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+            Some(format!("host-{}.example.net", last))
+        }
+    })
+    .await;
+
+    assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+    assert!(most_running.load(Ordering::SeqCst) <= 4);
+    assert!(fill.unfinished > 0, "{:?}", fill);
+    assert_eq!(fill.looked_up + fill.unfinished, 20);
+    assert_eq!(webcams.iter().filter(|webcam| webcam.hostname.is_some()).count(), fill.resolved);
+    // Failed and unfinished lookups leave the hostname unset
+    assert!(webcams.iter().filter(|webcam| webcam.ip.ends_with(['0', '2', '4', '6', '8'])).all(|webcam| webcam.hostname.is_none()));
+}
+
+#[tokio::test]
+async fn a_zero_budget_looks_nothing_up() {
+    let mut webcams = vec![webcam("81.2.69.1", 80, None)];
+    let fill = fill_hostnames(&mut webcams, Duration::ZERO, 4, |_ip: IpAddr| async { Some("never.example.net".to_string()) }).await;
+    assert_eq!(fill, HostnameFill::default());
+    assert_eq!(webcams[0].hostname, None);
+}
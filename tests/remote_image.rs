@@ -0,0 +1,209 @@
+//! Remote webcam images through the processing pipeline: bytes kept as
+//! fetched when nothing changes them, resizing, grayscale, format and
+//! statistics, passthrough, and images that don't decode. Thumbnails and
+//! metadata-only fetches, of single captures and validations.
+
+mod common;
+
+use common::HttpResponse;
+use image::{ImageFormat, Rgb, RgbImage};
+use mcp_webcam::tool_error::{ErrorCode, ToolError};
+use mcp_webcam::webcam::process_remote_image;
+use mcp_webcam::{MockBackend, OutputFormat, RemoteImageOptions, ShodanClient, WebcamError, WebcamMcpServer};
+use serde_json::{json, Value};
+use std::io::Cursor;
+use std::time::Duration;
+
+/// A 640x480 image, red on the left and blue on the right
+fn encoded(format: ImageFormat) -> Vec<u8> {
+    let image = RgbImage::from_fn(640, 480, |x, _| if x < 320 { Rgb([200, 20, 20]) } else { Rgb([20, 20, 200]) });
+    let mut buffer = Cursor::new(Vec::new());
+    image.write_to(&mut buffer, format).unwrap();
+    buffer.into_inner()
+}
+
+#[test]
+fn an_image_nothing_changes_is_returned_as_fetched() {
+    let jpeg = encoded(ImageFormat::Jpeg);
+    let processed = process_remote_image(jpeg.clone(), "image/jpeg", &RemoteImageOptions { max_width: Some(1024), ..Default::default() }).unwrap();
+    assert_eq!(processed.bytes, jpeg);
+    assert!(!processed.reencoded);
+    assert_eq!(processed.dimensions, Some((640, 480)));
+
+    // Asking for a quality re-encodes, as does a format webcams rarely send
+    let requantized = process_remote_image(jpeg, "image/jpeg", &RemoteImageOptions { quality: Some(30), ..Default::default() }).unwrap();
+    assert!(requantized.reencoded);
+    let bmp = process_remote_image(encoded(ImageFormat::Bmp), "image/bmp", &RemoteImageOptions::default()).unwrap();
+    assert!(bmp.reencoded);
+    assert_eq!(bmp.mime_type, "image/jpeg");
+    assert_eq!(image::guess_format(&bmp.bytes).unwrap(), ImageFormat::Jpeg);
+}
+
+#[test]
+fn images_are_resized_converted_and_measured() {
+    let options = RemoteImageOptions {
+        max_width: Some(320),
+        grayscale: true,
+        format: Some(OutputFormat::Png),
+        stats: true,
+        ..Default::default()
+    };
+    let processed = process_remote_image(encoded(ImageFormat::Jpeg), "image/jpeg", &options).unwrap();
+    assert!(processed.reencoded);
+    assert_eq!(processed.mime_type, "image/png");
+    assert_eq!(processed.source_dimensions, Some((640, 480)));
+    assert_eq!(processed.dimensions, Some((320, 240)));
+    let decoded = image::load_from_memory(&processed.bytes).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (320, 240));
+
+    let stats = processed.stats.unwrap();
+    let [r, g, b] = stats.mean_rgb;
+    assert!((r - g).abs() < 1.0 && (g - b).abs() < 1.0, "{:?}", stats.mean_rgb);
+    // Two flat halves of different brightness
+    assert!(stats.luminance_stddev > 5.0, "{:?}", stats);
+}
+
+#[test]
+fn passthrough_skips_decoding_and_refuses_processing_options() {
+    let not_really = b"\xFF\xD8\xFF\xE0truncated".to_vec();
+    let processed = process_remote_image(not_really.clone(), "image/jpeg", &RemoteImageOptions { passthrough: true, ..Default::default() }).unwrap();
+    assert_eq!(processed.bytes, not_really);
+    assert_eq!(processed.dimensions, None);
+
+    let options = RemoteImageOptions { passthrough: true, grayscale: true, ..Default::default() };
+    let error = process_remote_image(not_really, "image/jpeg", &options).unwrap_err();
+    assert_eq!(ToolError::from(&error).code, ErrorCode::InvalidParams);
+}
+
+#[test]
+fn an_undecodable_image_names_the_sniffed_type() {
+    let error = process_remote_image(b"\xFF\xD8\xFF\xE0truncated".to_vec(), "image/png", &RemoteImageOptions::default()).unwrap_err();
+    assert!(matches!(&error, WebcamError::UndecodableImage { content_type, .. } if content_type == "image/jpeg"), "{:?}", error);
+    let error = ToolError::from(&error);
+    assert_eq!(error.code, ErrorCode::DecodeFailed);
+    let details = error.details.unwrap();
+    assert_eq!(details["content_type"], "image/jpeg");
+    assert_eq!(details["declared_type"], "image/png");
+
+    let error = process_remote_image(b"GIF89a but not really".to_vec(), "image/gif", &RemoteImageOptions::default()).unwrap_err();
+    assert_eq!(ToolError::from(&error).details.unwrap()["content_type"], "image/gif");
+}
+
+/// A 400x300 PNG of noise, which compresses badly
+fn noisy_png() -> Vec<u8> {
+    let mut state = 0x2545_f491u32;
+    let image = RgbImage::from_fn(400, 300, |_, _| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let [r, g, b, _] = state.to_le_bytes();
+        Rgb([r, g, b])
+    });
+    let mut buffer = Cursor::new(Vec::new());
+    image.write_to(&mut buffer, ImageFormat::Png).unwrap();
+    buffer.into_inner()
+}
+
+/// A webcam serving `body` as `content_type` to every request
+fn serve(content_type: &'static str, body: Vec<u8>) -> String {
+    let (base, _) = common::serve(move |_| HttpResponse::new("200 OK", content_type, body.clone()));
+    format!("{}/snapshot.jpg", base)
+}
+
+fn call(server: &mcp_webcam::protocol::Server, arguments: Value) -> Value {
+    common::call(server, "capture_remote_image", arguments)
+}
+
+fn server_downloading_at_most(max_download_bytes: usize) -> mcp_webcam::protocol::Server {
+    let client = ShodanClient::builder()
+        .allow_private_targets(true)
+        .max_download_bytes(max_download_bytes)
+        .build("test-key")
+        .unwrap();
+    WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(client)
+        .host_cooldown(Duration::ZERO)
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap()
+}
+
+#[test]
+fn capture_remote_image_processes_what_it_fetched() {
+    let server = server_downloading_at_most(10 * 1024 * 1024);
+
+    let url = serve("image/png", encoded(ImageFormat::Png));
+    let result = call(&server, json!({ "url": url, "max_width": 160, "format": "jpeg", "quality": 60, "stats": true }));
+    assert_eq!(result["content"][0]["mimeType"], "image/jpeg");
+    let metadata = &result["structuredContent"]["metadata"];
+    assert_eq!((metadata["width"].as_u64(), metadata["height"].as_u64()), (Some(160), Some(120)));
+    assert_eq!(metadata["source_mime_type"], "image/png");
+    assert_eq!(metadata["source_width"], 640);
+    assert_eq!(metadata["reencoded"], true);
+    assert!(metadata["stats"]["mean_luminance"].as_f64().unwrap() > 0.0);
+
+    let result = call(&server, json!({ "url": url, "passthrough": true, "max_width": 160 }));
+    assert_eq!(result["structuredContent"]["error"]["code"], "INVALID_PARAMS");
+
+    let broken = serve("image/jpeg", b"\xFF\xD8\xFF\xE0cut short".to_vec());
+    let result = call(&server, json!({ "url": broken }));
+    assert_eq!(result["isError"], true);
+    assert_eq!(result["structuredContent"]["error"]["code"], "DECODE_FAILED");
+    assert_eq!(result["structuredContent"]["error"]["details"]["content_type"], "image/jpeg");
+
+    let passed = call(&server, json!({ "url": broken, "passthrough": true }));
+    assert_eq!(passed["structuredContent"]["metadata"]["reencoded"], false);
+    assert_eq!(passed["structuredContent"]["metadata"]["width"], Value::Null);
+}
+
+#[test]
+fn metadata_only_reads_the_header_of_an_image_too_large_to_download() {
+    let server = server_downloading_at_most(64 * 1024);
+    let png = noisy_png();
+    assert!(png.len() > 256 * 1024);
+    let url = serve("image/png", png.clone());
+
+    let whole = call(&server, json!({ "url": url }));
+    assert_eq!(whole["structuredContent"]["error"]["code"], "RESPONSE_TOO_LARGE");
+
+    let result = call(&server, json!({ "url": url, "metadata_only": true }));
+    assert_eq!(result["content"].as_array().unwrap().len(), 1);
+    assert_eq!(result["content"][0]["type"], "text");
+    let metadata = &result["structuredContent"]["metadata"];
+    assert_eq!(metadata["metadata_only"], true);
+    assert_eq!(metadata["mime_type"], "image/png");
+    assert_eq!((metadata["width"].as_u64(), metadata["height"].as_u64()), (Some(400), Some(300)));
+    assert_eq!(metadata["size_bytes"], png.len());
+    assert!(metadata["bytes_read"].as_u64().unwrap() <= 64 * 1024);
+
+    let result = call(&server, json!({ "url": url, "metadata_only": true, "thumbnail": true }));
+    assert_eq!(result["structuredContent"]["error"]["code"], "INVALID_PARAMS");
+}
+
+#[test]
+fn thumbnails_of_captures_and_validations_are_small_jpegs() {
+    let server = server_downloading_at_most(10 * 1024 * 1024);
+    let url = serve("image/png", encoded(ImageFormat::Png));
+
+    let result = call(&server, json!({ "url": url, "thumbnail": true, "max_width": 1000 }));
+    assert_eq!(result["structuredContent"]["metadata"]["width"], 320);
+
+    let not_an_image = serve("text/html", b"<html>login</html>".to_vec());
+    let validated = common::call(&server, "validate_webcams", json!({ "webcams": [{ "url": url }, { "url": not_an_image }], "thumbnail": true }));
+    let results = &validated["structuredContent"]["results"];
+    assert_eq!(results[0]["thumbnail"], true);
+    assert_eq!(results[0]["validation"]["width"], 640);
+    assert_eq!(results[1]["thumbnail"], false);
+    let content = validated["content"].as_array().unwrap();
+    assert_eq!(content.len(), 3);
+    assert_eq!(content[2]["mimeType"], "image/jpeg");
+
+    // Read as far as the header, so dimensions but no thumbnail
+    let validated = common::call(&server, "validate_webcams", json!({ "webcams": [{ "url": url }], "metadata_only": true }));
+    let validation = &validated["structuredContent"]["results"][0]["validation"];
+    assert_eq!((validation["width"].as_u64(), validation["height"].as_u64()), (Some(640), Some(480)));
+    assert_eq!(validated["structuredContent"]["results"][0]["thumbnail"], false);
+    assert_eq!(validated["content"].as_array().unwrap().len(), 1);
+}
@@ -0,0 +1,124 @@
+//! Cameras and their snapshots as MCP resources, and subscriptions to the
+//! snapshots, served by a server built around the mock backend.
+
+mod common;
+
+use base64::{engine::general_purpose, Engine as _};
+use common::{call, request};
+use mcp_webcam::protocol::{INVALID_PARAMS, RESOURCE_NOT_FOUND};
+use mcp_webcam::{CaptureStore, MockBackend, RetentionPolicy, WebcamMcpServer};
+use serde_json::{json, Value};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime};
+
+#[tokio::test]
+async fn cameras_are_listed_and_read_as_resources() {
+    let backend = MockBackend::new(2);
+    let stats = backend.stats();
+    let server = WebcamMcpServer::builder().backend(Box::new(backend)).preview(false).build().build_server().unwrap();
+
+    let listed = request(&server, "resources/list", json!({}));
+    let resources: Vec<(&str, &str)> = listed["result"]["resources"].as_array().unwrap().iter()
+        .map(|r| (r["uri"].as_str().unwrap(), r["mimeType"].as_str().unwrap()))
+        .collect();
+    // Builds with the synthetic camera list it after the mock's cameras
+    assert_eq!(resources[..4], [
+        ("webcam://camera/0", "application/json"),
+        ("webcam://camera/0/snapshot", "image/jpeg"),
+        ("webcam://camera/1", "application/json"),
+        ("webcam://camera/1/snapshot", "image/jpeg"),
+    ]);
+
+    let templates = request(&server, "resources/templates/list", json!({}));
+    assert!(templates["result"]["resourceTemplates"].as_array().unwrap().iter()
+        .any(|t| t["uriTemplate"] == "webcam://camera/{camera_index}/snapshot"));
+
+    let camera = request(&server, "resources/read", json!({ "uri": "webcam://camera/1" }));
+    let contents = &camera["result"]["contents"][0];
+    assert_eq!(contents["mimeType"], "application/json");
+    let info: Value = serde_json::from_str(contents["text"].as_str().unwrap()).unwrap();
+    assert_eq!(info["index"], 1);
+
+    let snapshot = request(&server, "resources/read", json!({ "uri": "webcam://camera/1/snapshot" }));
+    let contents = &snapshot["result"]["contents"][0];
+    assert_eq!(contents["mimeType"], "image/jpeg");
+    let jpeg = general_purpose::STANDARD.decode(contents["blob"].as_str().unwrap()).unwrap();
+    assert_eq!(&jpeg[..2], &[0xFF, 0xD8]);
+
+    // A second read straight after reuses the capture
+    let grabbed = stats.frames_grabbed.load(Ordering::Relaxed);
+    let again = request(&server, "resources/read", json!({ "uri": "webcam://camera/1/snapshot" }));
+    assert_eq!(again["result"]["contents"][0]["blob"], contents["blob"]);
+    assert_eq!(stats.frames_grabbed.load(Ordering::Relaxed), grabbed);
+}
+
+#[tokio::test]
+async fn unknown_cameras_are_not_found() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    for uri in ["webcam://camera/7", "webcam://camera/7/snapshot", "webcam://camera/x", "webcam://camera/+0"] {
+        let response = request(&server, "resources/read", json!({ "uri": uri }));
+        assert_eq!(response["error"]["code"], RESOURCE_NOT_FOUND, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn only_existing_snapshots_can_be_subscribed_to() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let camera = request(&server, "resources/subscribe", json!({ "uri": "webcam://camera/0" }));
+    assert_eq!(camera["error"]["code"], INVALID_PARAMS);
+    let missing = request(&server, "resources/subscribe", json!({ "uri": "webcam://camera/7/snapshot" }));
+    assert_eq!(missing["error"]["code"], RESOURCE_NOT_FOUND);
+    let capture = request(&server, "resources/subscribe", json!({ "uri": "webcam://capture/abc" }));
+    assert_eq!(capture["error"]["code"], RESOURCE_NOT_FOUND);
+
+    let snapshot = json!({ "uri": "webcam://camera/0/snapshot" });
+    assert_eq!(request(&server, "resources/subscribe", snapshot.clone())["result"], json!({}));
+    assert_eq!(request(&server, "resources/unsubscribe", snapshot.clone())["result"], json!({}));
+    // Unsubscribing again is harmless
+    assert_eq!(request(&server, "resources/unsubscribe", snapshot)["result"], json!({}));
+}
+
+#[tokio::test]
+async fn captures_expired_by_retention_are_not_found() {
+    let dir = common::temp_dir("expired-resources");
+    let policy = RetentionPolicy { max_age_secs: 600, ..RetentionPolicy::default() };
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .capture_store(CaptureStore::new(&dir, policy.clone()))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let result = call(&server, "capture_image", json!({ "camera_index": 0, "as_resource": true }));
+    let link = &result["content"][0];
+    assert_eq!(link["type"], "resource_link");
+    let uri = link["uri"].as_str().unwrap();
+    let read = request(&server, "resources/read", json!({ "uri": uri }));
+    assert_eq!(read["result"]["contents"][0]["mimeType"], "image/jpeg");
+
+    // Age the stored capture past the policy and let the periodic cleanup run
+    let file = dir.join(link["name"].as_str().unwrap());
+    let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+    std::fs::File::options().write(true).open(&file).unwrap().set_modified(an_hour_ago).unwrap();
+    assert_eq!(CaptureStore::new(&dir, policy).cleanup().unwrap().removed_files, 1);
+
+    let expired = request(&server, "resources/read", json!({ "uri": uri }));
+    assert_eq!(expired["error"]["code"], RESOURCE_NOT_FOUND, "{}", expired);
+    assert!(expired["error"]["message"].as_str().unwrap().contains(uri));
+    assert!(expired.get("result").is_none(), "{}", expired);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,88 @@
+//! Webcam searches reported query by query against a stand-in for
+//! Shodan's API: the stream of batches, and the progress notifications
+//! search_webcams sends a client that asks for them.
+
+mod common;
+
+use common::{request, serve, server_builder, shodan_client, HttpResponse, HOST_SEARCH};
+use futures::StreamExt;
+use mcp_webcam::shodan::select_queries;
+use mcp_webcam::{RequestLimiter, SearchFilters, ShodanClient};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+const NO_MATCHES: &str = r#"{"matches": [], "total": 0}"#;
+
+/// Answer the first search with the fixture's three webcams and every
+/// later one with none, on loopback; returns the base URL
+fn serve_shodan() -> String {
+    let searched = AtomicBool::new(false);
+    let (base, _) = serve(move |request| {
+        let search = request.path == "/shodan/host/search";
+        HttpResponse::json(if search && !searched.swap(true, Ordering::SeqCst) { HOST_SEARCH } else { NO_MATCHES })
+    });
+    base
+}
+
+fn client(base_url: &str) -> ShodanClient {
+    shodan_client(base_url).with_request_limiter(Arc::new(RequestLimiter::new(100.0)))
+}
+
+fn search(meta: Option<Value>) -> (Value, Vec<Value>) {
+    let server = server_builder(&serve_shodan()).build().build_server().unwrap();
+    let mut params = json!({ "name": "search_webcams", "arguments": { "limit": 10, "max_queries": 2 } });
+    if let Some(meta) = meta {
+        params["_meta"] = meta;
+    }
+    let result = request(&server, "tools/call", params)["result"].clone();
+    let progress = server.take_notifications().into_iter()
+        .filter(|notification| notification["method"] == "notifications/progress")
+        .collect();
+    (result, progress)
+}
+
+#[tokio::test]
+async fn each_query_yields_the_webcams_it_added() {
+    let client = client(&serve_shodan());
+    let plan = select_queries(&["webcam".to_string(), "mjpeg".to_string()], None, None).unwrap();
+    let batches: Vec<_> = client.search_webcams_stream(Some(10), &SearchFilters::default(), &plan).collect().await;
+    assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), [3, 0]);
+    assert_eq!(batches[0][0].ip, "203.0.113.7");
+}
+
+#[tokio::test]
+async fn finishing_a_stream_returns_the_whole_search() {
+    let client = client(&serve_shodan());
+    let plan = select_queries(&["webcam".to_string(), "mjpeg".to_string()], None, None).unwrap();
+    let mut stream = client.search_webcams_stream(Some(10), &SearchFilters::default(), &plan);
+    assert_eq!(stream.next().await.unwrap().len(), 3);
+    let found = stream.finish().await.unwrap();
+    assert_eq!(found.webcams.len(), 3);
+    assert_eq!(found.queries, ["webcam", "mjpeg"]);
+}
+
+#[test]
+fn progress_is_reported_per_query_to_a_client_asking_for_it() {
+    let (result, progress) = search(Some(json!({ "progressToken": "search-1" })));
+    assert_eq!(result["isError"], false);
+    assert_eq!(result["structuredContent"]["total"], 3);
+
+    assert_eq!(progress.len(), 2);
+    let first = &progress[0]["params"];
+    assert_eq!(first["progressToken"], "search-1");
+    assert_eq!(first["progress"], 1);
+    assert_eq!(first["provider"], "shodan");
+    assert_eq!(first["webcams"].as_array().unwrap().len(), 3);
+    assert_eq!(first["webcams"][0]["ip"], "203.0.113.7");
+    assert!(first["message"].as_str().unwrap().contains("3 webcam(s)"));
+    assert_eq!(progress[1]["params"]["progress"], 2);
+    assert_eq!(progress[1]["params"]["webcams"], json!([]));
+}
+
+#[test]
+fn no_progress_is_reported_without_a_token() {
+    let (result, progress) = search(None);
+    assert_eq!(result["structuredContent"]["total"], 3);
+    assert!(progress.is_empty());
+}
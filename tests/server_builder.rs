@@ -0,0 +1,335 @@
+//! MCP handlers of a server assembled with `WebcamMcpServer::builder`
+//! around the mock backend, without cameras or environment variables.
+
+mod common;
+
+use common::{call, call_structured, request};
+use mcp_webcam::protocol::INVALID_PARAMS;
+use mcp_webcam::{CaptureHistory, CaptureStore, MockBackend, RetentionPolicy, ShodanClient, WebcamMcpServer};
+use serde_json::json;
+
+#[tokio::test]
+async fn handlers_use_injected_backend_and_settings() {
+    let data_dir = std::env::temp_dir().join(format!("mcp-webcam-builder-{}", std::process::id()));
+    let server = WebcamMcpServer::builder()
+        .name("embedded-webcam")
+        .version("9.9.9")
+        .backend(Box::new(MockBackend::new(2)))
+        .capture_history(CaptureHistory::new(4, 1024 * 1024))
+        .capture_store(CaptureStore::new(&data_dir, RetentionPolicy::default()))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let initialized = request(&server, "initialize", json!({}));
+    assert_eq!(initialized["result"]["serverInfo"]["name"], "embedded-webcam");
+    assert_eq!(initialized["result"]["serverInfo"]["version"], "9.9.9");
+
+    let listed = request(&server, "tools/list", json!({}));
+    let tools: Vec<&str> = listed["result"]["tools"].as_array().unwrap().iter()
+        .filter_map(|tool| tool["name"].as_str())
+        .collect();
+    assert!(tools.contains(&"capture_image"));
+    assert!(!tools.contains(&"start_preview"));
+    assert!(!tools.contains(&"search_webcams"));
+
+    let cameras = request(&server, "tools/call", json!({ "name": "list_cameras", "arguments": {} }));
+    assert_eq!(cameras["result"]["structuredContent"]["cameras"].as_array().unwrap().len(), 2);
+
+    let captured = request(&server, "tools/call", json!({
+        "name": "capture_image",
+        "arguments": { "camera_index": 1, "save": true }
+    }));
+    let metadata = &captured["result"]["structuredContent"]["metadata"];
+    assert_eq!(metadata["camera_index"], 1);
+    assert_eq!(metadata["width"], 320);
+    assert!(metadata["saved_path"].as_str().unwrap().starts_with(data_dir.to_str().unwrap()));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[tokio::test]
+async fn health_check_reports_cameras_without_opening_them() {
+    let health_check = |server: &mcp_webcam::protocol::Server| call_structured(server, "health_check", json!({}))["health"].clone();
+
+    let backend = MockBackend::new(2);
+    let stats = backend.stats();
+    let server = WebcamMcpServer::builder().backend(Box::new(backend)).preview(false).build().build_server().unwrap();
+    let health = health_check(&server);
+    assert_eq!(health["status"], "ok");
+    assert_eq!(health["cameras_enumerable"], true);
+    assert_eq!(health["camera_count"], 2);
+    assert_eq!(health["shodan"]["enabled"], false);
+    assert_eq!(health["failures_total"], 0);
+    assert_eq!(stats.opens.load(std::sync::atomic::Ordering::Relaxed), 0);
+
+    let server = WebcamMcpServer::builder().backend(Box::new(MockBackend::new(0))).preview(false).build().build_server().unwrap();
+    let health = health_check(&server);
+    assert_eq!(health["status"], "degraded");
+    assert_eq!(health["cameras_enumerable"], false);
+}
+
+#[tokio::test]
+async fn arguments_are_checked_against_the_input_schema() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let misspelled = call(&server, "capture_image", json!({ "cameraIndex": 0 }));
+    assert_eq!(misspelled["isError"], true);
+    let error = &misspelled["structuredContent"]["error"];
+    assert_eq!(error["code"], "INVALID_PARAMS");
+    assert_eq!(error["details"]["field"], "cameraIndex");
+    assert_eq!(error["details"]["suggestion"], "camera_index");
+    assert!(error["message"].as_str().unwrap().contains("did you mean 'camera_index'?"));
+
+    let mistyped = call(&server, "capture_image", json!({ "camera_index": "0" }));
+    let error = &mistyped["structuredContent"]["error"];
+    assert_eq!(error["code"], "INVALID_PARAMS");
+    assert_eq!(error["details"]["field"], "camera_index");
+    assert!(error["message"].as_str().unwrap().contains("camera_index: expected number"));
+
+    let missing = call(&server, "get_capture", json!({}));
+    let error = &missing["structuredContent"]["error"];
+    assert_eq!(error["code"], "INVALID_PARAMS");
+    assert_eq!(error["details"]["field"], "capture_id");
+
+    let none_expected = call(&server, "get_server_info", json!({ "verbose": true }));
+    assert!(none_expected["structuredContent"]["error"]["message"].as_str().unwrap().contains("it takes no arguments"));
+
+    // Null optional arguments are treated as absent
+    let captured = call(&server, "capture_image", json!({ "camera_index": 0, "quality": null }));
+    assert!(captured.get("isError").is_none());
+
+    let stats = &call(&server, "get_server_stats", json!({}))["structuredContent"]["stats"];
+    assert_eq!(stats["failures_by_type"]["invalid_params"], 4);
+}
+
+#[tokio::test]
+async fn default_camera_can_be_changed_for_the_session() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(2)))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    assert_eq!(call_structured(&server, "get_camera_info", json!({}))["camera_info"]["default_camera"], 0);
+
+    let missing = call_structured(&server, "set_default_camera", json!({ "camera_index": 5 }));
+    assert_eq!(missing["error"]["code"], "CAMERA_NOT_FOUND");
+    // Builds with the synthetic camera list it after these
+    assert_eq!(missing["error"]["details"]["available_indices"].as_array().unwrap()[..2], [json!(0), json!(1)]);
+    assert!(missing["error"]["message"].as_str().unwrap().contains("available cameras: 0, 1"));
+
+    let changed = call_structured(&server, "set_default_camera", json!({ "camera_index": 1 }));
+    assert_eq!(changed, json!({ "default_camera": 1, "previous_default_camera": 0 }));
+    assert_eq!(call_structured(&server, "get_camera_info", json!({}))["camera_info"]["default_camera"], 1);
+    assert_eq!(call_structured(&server, "capture_image", json!({}))["metadata"]["camera_index"], 1);
+}
+
+#[tokio::test]
+async fn self_test_reports_without_image_data() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(2)))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let tested = call(&server, "self_test", json!({ "camera_index": 1 }));
+    assert_ne!(tested["isError"], true);
+    assert_eq!((&tested["structuredContent"]["passed"], &tested["structuredContent"]["total"]), (&json!(1), &json!(1)));
+    assert_eq!(tested["structuredContent"]["results"][0]["index"], 1);
+    assert!(tested["content"].as_array().unwrap().iter().all(|block| block["type"] == "text"), "{}", tested);
+
+    let missing = call(&server, "self_test", json!({ "camera_index": 5 }));
+    assert_eq!(missing["isError"], true);
+    assert_eq!(missing["structuredContent"]["error"]["code"], "CAMERA_NOT_FOUND");
+}
+
+#[tokio::test]
+async fn benchmark_duration_is_capped() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let too_long = call(&server, "benchmark_camera", json!({ "camera_index": 0, "duration_seconds": 11 }));
+    assert_eq!(too_long["isError"], true);
+    assert_eq!(too_long["structuredContent"]["error"]["code"], "INVALID_PARAMS");
+
+    let report = &call_structured(&server, "benchmark_camera", json!({ "camera_index": 0, "duration_seconds": 0.1 }))["benchmark"];
+    assert_eq!(report["camera_index"], 0);
+    assert_eq!((&report["width"], &report["height"], &report["pixel_format"]), (&json!(320), &json!(240), &json!("RGB")));
+    assert!(report["frames"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn camera_info_details_the_open_camera() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(2)))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let closed = call(&server, "get_camera_info", json!({}));
+    assert!(closed["structuredContent"]["camera_info"]["current_camera"].is_null());
+    assert!(closed["content"][0]["text"].as_str().unwrap().ends_with("current: no camera is currently open"), "{}", closed);
+
+    call(&server, "capture_image", json!({ "camera_index": 1 }));
+    call(&server, "capture_image", json!({ "camera_index": 1 }));
+    let open = call(&server, "get_camera_info", json!({}));
+    let current = &open["structuredContent"]["camera_info"]["current_camera"];
+    assert_eq!(current["index"], 1);
+    assert_eq!(current["name"], "Mock Camera 1");
+    assert_eq!(current["resolution"], json!([320, 240]));
+    assert_eq!(current["frame_format"], "RGB");
+    assert_eq!(current["frames_captured"], 2);
+    assert!(chrono::DateTime::parse_from_rfc3339(current["opened_at"].as_str().unwrap()).is_ok());
+    assert!(open["content"][0]["text"].as_str().unwrap().contains("current: camera 1 (Mock Camera 1), 2 frame(s) captured since"));
+}
+
+#[tokio::test]
+async fn disabling_remote_access_drops_the_shodan_client() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(ShodanClient::new("test-key".to_string()))
+        .remote(false)
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let listed = request(&server, "tools/list", json!({}));
+    let tools: Vec<&str> = listed["result"]["tools"].as_array().unwrap().iter()
+        .filter_map(|tool| tool["name"].as_str())
+        .collect();
+    for remote_tool in ["search_webcams", "capture_remote_image", "count_webcams", "shodan_account_info", "shodan_host_info", "probe_webcam", "validate_webcams", "list_remote_webcams", "clear_remote_webcams", "export_webcams", "get_target_policy", "configure_shodan"] {
+        assert!(!tools.contains(&remote_tool), "{}", remote_tool);
+    }
+
+    let fetch = request(&server, "tools/call", json!({
+        "name": "capture_remote_image",
+        "arguments": { "url": "http://192.0.2.1/snapshot.jpg" }
+    }));
+    assert_eq!(fetch["error"]["code"], INVALID_PARAMS);
+
+    let info = request(&server, "tools/call", json!({ "name": "get_server_info", "arguments": {} }));
+    let text = info["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("administratively disabled"), "{}", text);
+    let info = &info["result"]["structuredContent"]["server_info"];
+    assert_eq!(info["remote_disabled"], true);
+    assert_eq!(info["shodan_enabled"], false);
+}
+
+#[tokio::test]
+async fn unreadable_key_file_keeps_the_current_key() {
+    let key_file = std::env::temp_dir().join(format!("mcp-webcam-missing-key-{}", std::process::id()));
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(ShodanClient::new("startup-key".to_string()))
+        .shodan_key_file(&key_file)
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let listed = request(&server, "tools/list", json!({}));
+    let tools: Vec<&str> = listed["result"]["tools"].as_array().unwrap().iter()
+        .filter_map(|tool| tool["name"].as_str())
+        .collect();
+    assert!(tools.contains(&"reload_credentials"));
+
+    let reloaded = request(&server, "tools/call", json!({ "name": "reload_credentials", "arguments": {} }));
+    assert_eq!(reloaded["result"]["structuredContent"]["error"]["code"], "STORAGE_ERROR");
+    let info = request(&server, "tools/call", json!({ "name": "get_server_info", "arguments": {} }));
+    assert_eq!(info["result"]["structuredContent"]["server_info"]["shodan_enabled"], true);
+}
+
+#[tokio::test]
+async fn search_queries_are_listed_and_chosen_by_index() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(ShodanClient::new("test-key".to_string()))
+        .search_queries(vec!["port:8080 \"mjpeg\"".to_string(), "\"live view axis\"".to_string()])
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let listed = request(&server, "tools/call", json!({ "name": "list_search_queries", "arguments": {} }));
+    let listed = &listed["result"]["structuredContent"];
+    assert_eq!(listed["queries"][1], json!({ "index": 1, "query": "\"live view axis\"" }));
+    assert_eq!(listed["default_max_queries"], 3);
+
+    // Rejected before anything is sent to Shodan
+    let searched = request(&server, "tools/call", json!({
+        "name": "search_webcams",
+        "arguments": { "query_indices": [2] }
+    }));
+    assert_eq!(searched["result"]["structuredContent"]["error"]["code"], "INVALID_PARAMS");
+    for arguments in [json!({ "query_indices": [2] }), json!({ "query": "webcam", "query_indices": [0] }), json!({ "query": " " })] {
+        let counted = request(&server, "tools/call", json!({
+            "name": "count_webcams",
+            "arguments": arguments
+        }));
+        assert_eq!(counted["result"]["structuredContent"]["error"]["code"], "INVALID_PARAMS", "{}", arguments);
+    }
+}
+
+#[tokio::test]
+async fn host_lookups_need_an_ip_address() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(ShodanClient::new("test-key".to_string()))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    // Rejected before anything is sent to Shodan
+    for arguments in [json!({ "ip": "203.0.113.300" }), json!({ "ip": "cam.example.org" }), json!({})] {
+        let looked_up = request(&server, "tools/call", json!({
+            "name": "shodan_host_info",
+            "arguments": arguments
+        }));
+        assert_eq!(looked_up["result"]["structuredContent"]["error"]["code"], "INVALID_PARAMS", "{}", arguments);
+
+        let probed = request(&server, "tools/call", json!({
+            "name": "probe_webcam",
+            "arguments": arguments
+        }));
+        assert_eq!(probed["result"]["structuredContent"]["error"]["code"], "INVALID_PARAMS", "{}", arguments);
+    }
+}
+
+#[tokio::test]
+async fn shodan_request_limiter_is_reported_in_stats() {
+    let stats = |remote: bool| {
+        let server = WebcamMcpServer::builder()
+            .backend(Box::new(MockBackend::new(1)))
+            .shodan_client(ShodanClient::new("test-key".to_string()))
+            .shodan_requests_per_second(0.5)
+            .remote(remote)
+            .preview(false)
+            .build()
+            .build_server()
+            .unwrap();
+        let stats = request(&server, "tools/call", json!({ "name": "get_server_stats", "arguments": {} }));
+        stats["result"]["structuredContent"]["stats"]["shodan_requests"].clone()
+    };
+
+    let limiter = stats(true);
+    assert_eq!(limiter["requests_per_second"], 0.5);
+    assert_eq!(limiter["queue_depth"], 0);
+    assert_eq!(limiter["requests"], 0);
+    assert!(stats(false).is_null());
+}
@@ -0,0 +1,137 @@
+//! Validation of the startup settings built from command-line flags.
+
+use mcp_webcam::{CameraProfile, ServerSettings};
+use std::time::Duration;
+
+#[test]
+fn defaults_are_valid() {
+    let settings = ServerSettings::default();
+    assert!(settings.validate().is_ok());
+    assert!(settings.shodan_enabled);
+    assert_eq!(settings.log_level, "info");
+}
+
+#[test]
+fn shodan_key_conflicts_with_disabled_shodan() {
+    let settings = ServerSettings {
+        shodan_enabled: false,
+        shodan_api_key: Some("key".to_string()),
+        ..Default::default()
+    };
+    assert!(settings.validate().unwrap_err().contains("Shodan is disabled"));
+}
+
+#[test]
+fn explicit_shodan_key_is_used_and_disabling_drops_it() {
+    let settings = ServerSettings { shodan_api_key: Some("key".to_string()), ..Default::default() };
+    assert_eq!(settings.resolved_shodan_key().as_deref(), Some("key"));
+
+    let disabled = ServerSettings { shodan_enabled: false, ..Default::default() };
+    assert_eq!(disabled.resolved_shodan_key(), None);
+}
+
+#[test]
+fn disabling_remote_access_ignores_the_shodan_key() {
+    let settings = ServerSettings { remote_disabled: true, ..Default::default() };
+    assert!(!settings.remote_enabled());
+    assert_eq!(settings.resolved_shodan_key(), None);
+
+    let with_key = ServerSettings { shodan_api_key: Some("key".to_string()), ..settings };
+    assert!(with_key.validate().unwrap_err().contains("remote access is disabled"));
+
+    let scanning = ServerSettings { remote_disabled: true, allow_network_scan: true, ..Default::default() };
+    assert!(scanning.validate().unwrap_err().contains("remote access is disabled"));
+}
+
+#[test]
+fn shodan_key_is_read_from_a_file() {
+    let path = std::env::temp_dir().join(format!("mcp-webcam-shodan-key-{}", std::process::id()));
+    std::fs::write(&path, "  file-key\n").unwrap();
+    let settings = ServerSettings { shodan_key_file: Some(path.clone()), ..Default::default() };
+    assert!(settings.validate().is_ok());
+    assert_eq!(settings.resolved_shodan_key().as_deref(), Some("file-key"));
+    assert_eq!(settings.resolved_shodan_key_file(), Some(path.clone()));
+
+    // An unreadable or empty file leaves Shodan disabled rather than failing
+    std::fs::write(&path, "\n").unwrap();
+    assert_eq!(settings.resolved_shodan_key(), None);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(settings.resolved_shodan_key(), None);
+
+    let both = ServerSettings { shodan_api_key: Some("key".to_string()), ..settings.clone() };
+    assert!(both.validate().unwrap_err().contains("not both"));
+    let disabled = ServerSettings { shodan_enabled: false, ..settings };
+    assert!(disabled.validate().is_err());
+    assert_eq!(disabled.resolved_shodan_key_file(), None);
+}
+
+#[test]
+fn shodan_connection_settings_are_checked() {
+    let settings = ServerSettings {
+        shodan_proxy: Some("http://proxy.example.com:3128".to_string()),
+        shodan_request_timeout: Some(Duration::from_secs(5)),
+        remote_accept_invalid_certs: true,
+        ..Default::default()
+    };
+    assert!(settings.validate().is_ok());
+
+    let bad_proxy = ServerSettings { shodan_proxy: Some("not a proxy".to_string()), ..Default::default() };
+    assert!(bad_proxy.validate().unwrap_err().contains("invalid proxy URL"));
+    let bad_agent = ServerSettings { shodan_user_agent: Some("line\nbreak".to_string()), ..Default::default() };
+    assert!(bad_agent.validate().unwrap_err().contains("user agent"));
+    let no_time = ServerSettings { shodan_connect_timeout: Some(Duration::ZERO), ..Default::default() };
+    assert!(no_time.validate().is_err());
+    let no_download = ServerSettings { remote_max_download_bytes: Some(0), ..Default::default() };
+    assert!(no_download.validate().unwrap_err().contains("download limit"));
+}
+
+#[test]
+fn invalid_capture_defaults_are_rejected() {
+    let rotated = ServerSettings {
+        capture_defaults: CameraProfile { rotation: Some(45), ..Default::default() },
+        ..Default::default()
+    };
+    assert!(rotated.validate().unwrap_err().starts_with("capture defaults:"));
+
+    let half_resolution = ServerSettings {
+        capture_defaults: CameraProfile { width: Some(640), ..Default::default() },
+        ..Default::default()
+    };
+    assert!(half_resolution.validate().is_err());
+
+    let zero_width = ServerSettings { max_width: Some(0), ..Default::default() };
+    assert!(zero_width.validate().is_err());
+}
+
+#[test]
+fn auth_token_must_be_usable_in_a_header() {
+    let spaced = ServerSettings { auth_token: Some("two words".to_string()), ..Default::default() };
+    assert!(spaced.validate().is_err());
+
+    let settings = ServerSettings { auth_token: Some("token".to_string()), ..Default::default() };
+    assert!(settings.validate().is_ok());
+    assert!(settings.resolved_auth_token().unwrap().matches("token"));
+}
+
+#[test]
+fn consent_must_last_at_least_a_second() {
+    let settings = ServerSettings { require_consent: true, consent_duration: Duration::ZERO, ..Default::default() };
+    assert!(settings.validate().unwrap_err().contains("consent duration"));
+
+    let settings = ServerSettings { require_consent: true, consent_duration: Duration::from_secs(60), ..Default::default() };
+    assert!(settings.validate().is_ok());
+}
+
+#[test]
+fn cached_webcams_must_last_at_least_a_second() {
+    let settings = ServerSettings { webcam_cache_ttl: Duration::from_millis(500), ..Default::default() };
+    assert!(settings.validate().unwrap_err().contains("webcam cache TTL"));
+    assert_eq!(ServerSettings::default().webcam_cache_ttl, Duration::from_secs(24 * 60 * 60));
+}
+
+#[test]
+fn honeypot_threshold_is_a_score() {
+    let settings = ServerSettings { honeypot_threshold: 1.5, ..Default::default() };
+    assert!(settings.validate().unwrap_err().contains("honeypot threshold"));
+    assert!(!ServerSettings::default().honeypot_strict);
+}
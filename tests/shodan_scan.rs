@@ -0,0 +1,100 @@
+//! On-demand scans against a stand-in for Shodan's API: targets checked
+//! before any credit is spent, the request's form, and scan statuses.
+
+mod common;
+
+use common::{serve, server_builder, shodan_client, HttpResponse, Requests};
+use mcp_webcam::shodan::{parse_scan_targets, MAX_SCAN_TARGETS};
+use mcp_webcam::{ScanStatus, ShodanError};
+use serde_json::{json, Value};
+
+/// Answer scan requests and status checks on loopback; returns the base
+/// URL and the requests received
+fn serve_shodan() -> (String, Requests) {
+    serve(|request| match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/shodan/scan") => HttpResponse::json(r#"{"count": 2, "id": "R2XRT5HH6X67PFAB", "credits_left": 98}"#),
+        ("GET", "/shodan/scan/R2XRT5HH6X67PFAB") => {
+            HttpResponse::json(r#"{"count": 2, "status": "PROCESSING", "id": "R2XRT5HH6X67PFAB", "created": "2026-10-16T08:17:43.794000"}"#)
+        }
+        ("GET", "/shodan/scan/DONE0000000000AB") => HttpResponse::json(r#"{"count": 1, "status": "DONE", "id": "DONE0000000000AB"}"#),
+        _ => HttpResponse::new("404 Not Found", "application/json", r#"{"error": "The scan ID could not be found"}"#),
+    })
+}
+
+fn call(base_url: &str, name: &str, arguments: Value) -> Value {
+    let server = server_builder(base_url).build().build_server().unwrap();
+    common::call(&server, name, arguments)
+}
+
+#[test]
+fn scan_targets_are_public_addresses() {
+    let ips = parse_scan_targets(&["203.0.113.7", " 2001:db8::2a ", "203.0.113.7"]).unwrap();
+    assert_eq!(ips.iter().map(|ip| ip.to_string()).collect::<Vec<_>>(), ["203.0.113.7", "2001:db8::2a"]);
+
+    assert!(parse_scan_targets(&["cam.example.net"]).unwrap_err().contains("not an IP address"));
+    assert!(parse_scan_targets(&["203.0.113.0/24"]).unwrap_err().contains("not an IP address"));
+    assert!(parse_scan_targets(&["192.168.1.20"]).unwrap_err().contains("private"));
+    assert!(parse_scan_targets(&["127.0.0.1"]).unwrap_err().contains("loopback"));
+    assert!(parse_scan_targets::<&str>(&[]).is_err());
+
+    let too_many: Vec<String> = (1..=MAX_SCAN_TARGETS + 1).map(|n| format!("203.0.113.{}", n)).collect();
+    assert!(parse_scan_targets(&too_many).unwrap_err().contains("At most"));
+}
+
+#[test]
+fn statuses_are_translated() {
+    assert_eq!(ScanStatus::parse("SUBMITTING"), ScanStatus::Submitting);
+    assert_eq!(ScanStatus::parse("QUEUE"), ScanStatus::Queued);
+    assert_eq!(ScanStatus::parse("PROCESSING"), ScanStatus::Processing);
+    assert_eq!(ScanStatus::parse("done"), ScanStatus::Done);
+    assert_eq!(ScanStatus::parse("EXPLODED"), ScanStatus::Unknown);
+    assert!(ScanStatus::Done.is_done());
+}
+
+#[tokio::test]
+async fn scans_are_posted_and_checked() {
+    let (base_url, requests) = serve_shodan();
+    let client = shodan_client(&base_url);
+
+    let scan = client.scan(&["203.0.113.7", "198.51.100.23"]).await.unwrap();
+    assert_eq!(scan.id, "R2XRT5HH6X67PFAB");
+    assert_eq!(scan.credits_left, 98);
+    let posted = &requests.all()[0];
+    assert_eq!((posted.method.as_str(), posted.path.as_str()), ("POST", "/shodan/scan"));
+    assert_eq!(posted.body, b"ips=203.0.113.7%2C198.51.100.23");
+
+    let status = client.scan_status("R2XRT5HH6X67PFAB").await.unwrap();
+    assert_eq!(status.status, ScanStatus::Processing);
+    assert_eq!(status.shodan_status, "PROCESSING");
+    assert_eq!(status.created.as_deref(), Some("2026-10-16T08:17:43.794000"));
+    assert!(matches!(client.scan_status("UNKNOWN0000000000").await, Err(ShodanError::NotFound(_))));
+
+    // Refused before any request
+    assert!(matches!(client.scan(&["10.0.0.1"]).await, Err(ShodanError::InvalidQuery(_))));
+    assert!(matches!(client.scan_status("../api-info").await, Err(ShodanError::InvalidQuery(_))));
+    assert_eq!(requests.all().len(), 3);
+}
+
+#[test]
+fn the_tools_request_and_report_scans() {
+    let (base_url, requests) = serve_shodan();
+
+    let refused = call(&base_url, "request_shodan_scan", json!({ "ips": ["203.0.113.7", "192.168.1.20"] }));
+    assert_eq!(refused["structuredContent"]["error"]["code"], "INVALID_PARAMS");
+    assert!(requests.all().is_empty());
+
+    let requested = call(&base_url, "request_shodan_scan", json!({ "ips": ["203.0.113.7", "198.51.100.23"] }));
+    assert_eq!(requested["isError"], false);
+    assert_eq!(requested["structuredContent"]["scan"]["id"], "R2XRT5HH6X67PFAB");
+    assert_eq!(requested["structuredContent"]["ips"], json!(["203.0.113.7", "198.51.100.23"]));
+
+    let processing = call(&base_url, "get_scan_status", json!({ "id": "R2XRT5HH6X67PFAB" }));
+    assert_eq!(processing["structuredContent"]["scan"]["status"], "processing");
+    assert_eq!(processing["structuredContent"]["done"], false);
+    let done = call(&base_url, "get_scan_status", json!({ "id": "DONE0000000000AB" }));
+    assert_eq!(done["structuredContent"]["scan"]["status"], "done");
+    assert_eq!(done["structuredContent"]["done"], true);
+
+    let unknown = call(&base_url, "get_scan_status", json!({ "id": "UNKNOWN0000000000" }));
+    assert_eq!(unknown["structuredContent"]["error"]["code"], "SHODAN_HOST_NOT_FOUND");
+}
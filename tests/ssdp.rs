@@ -0,0 +1,132 @@
+//! SSDP discovery against a stand-in camera on loopback: answers to the
+//! M-SEARCHes, the device description fetched once and parsed, devices
+//! that aren't cameras left out, and a camera answering ONVIF too merged
+//! with it on its IP address.
+
+mod common;
+
+use common::{serve, HttpResponse, Requests};
+use mcp_webcam::local_discovery::merge_cameras;
+use mcp_webcam::onvif::parse_probe_matches;
+use mcp_webcam::ssdp::{discover_via, parse_description, parse_search_response, search_message, CAMERA_DEVICE_TYPE, SOURCE};
+use mcp_webcam::{MockBackend, WebcamAccessType, WebcamMcpServer};
+use serde_json::json;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::time::Duration;
+
+const DESCRIPTION: &str = include_str!("fixtures/ssdp_device_description.xml");
+const LOOPBACK: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+fn search_answer(location: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nEXT:\r\nLOCATION: {}\r\nSERVER: Linux/3.0, UPnP/1.0, Portable SDK for UPnP devices/1.6.18\r\nST: urn:schemas-upnp-org:device:Basic:1\r\nUSN: uuid:5f9ec1b3-ed3e-11e2-8d4c-bcbac2a1b2c4::urn:schemas-upnp-org:device:Basic:1\r\n\r\n",
+        location
+    )
+}
+
+/// Serve the description to every request; returns its URL and the
+/// requests
+fn serve_description() -> (String, Requests) {
+    let (base, requests) = serve(|_| HttpResponse::new("200 OK", "text/xml", DESCRIPTION));
+    (format!("{}/upnp/device_description.xml", base), requests)
+}
+
+#[test]
+fn answers_and_descriptions_are_parsed() {
+    let search = search_message(CAMERA_DEVICE_TYPE, 2);
+    assert!(search.starts_with("M-SEARCH * HTTP/1.1\r\n"));
+    assert!(search.contains("MAN: \"ssdp:discover\"\r\nMX: 2\r\n"));
+    assert!(search.contains(&format!("ST: {}\r\n", CAMERA_DEVICE_TYPE)));
+
+    // Only 200 answers with a location are answers
+    assert!(parse_search_response("NOTIFY * HTTP/1.1\r\nLOCATION: http://127.0.0.1/\r\n\r\n", LOOPBACK).is_none());
+    assert!(parse_search_response("HTTP/1.1 200 OK\r\nST: upnp:rootdevice\r\n\r\n", LOOPBACK).is_none());
+    let answer = parse_search_response(&search_answer("http://127.0.0.1:49152/upnp/device_description.xml"), LOOPBACK).unwrap();
+    assert_eq!(answer.location, "http://127.0.0.1:49152/upnp/device_description.xml");
+    assert_eq!(answer.search_target.as_deref(), Some("urn:schemas-upnp-org:device:Basic:1"));
+    assert!(answer.server.unwrap().starts_with("Linux/3.0"));
+
+    let answer = parse_search_response(&search_answer("http://127.0.0.1:49152/upnp/device_description.xml"), LOOPBACK).unwrap();
+    let device = parse_description(DESCRIPTION, answer.clone());
+    assert_eq!(device.friendly_name.as_deref(), Some("Garage Camera & Drive"));
+    assert_eq!(device.manufacturer.as_deref(), Some("Hikvision"));
+    assert_eq!(device.model_name.as_deref(), Some("DS-2CD2142FWD-I"));
+    assert_eq!(device.presentation_url.as_deref(), Some("http://127.0.0.1:49152/doc/page/login.asp"));
+    assert!(device.is_camera());
+
+    let webcam = device.to_webcam();
+    assert_eq!(webcam.url, "http://127.0.0.1:49152/doc/page/login.asp");
+    assert_eq!(webcam.port, 49152);
+    assert_eq!(webcam.product.as_deref(), Some("DS-2CD2142FWD-I"));
+    assert_eq!(webcam.sources, vec![SOURCE.to_string()]);
+    assert!(matches!(webcam.access_type, WebcamAccessType::HTTP));
+    assert_eq!(webcam.classification.map(|c| c.vendor.name()), Some("hikvision"));
+
+    // A printer answering as a basic device is no camera
+    let printer = r#"<root><device><deviceType>urn:schemas-upnp-org:device:Basic:1</deviceType>
+        <friendlyName>Office Printer</friendlyName><manufacturer>Example Printers</manufacturer><modelName>LaserJet 4</modelName></device></root>"#;
+    assert!(!parse_description(printer, answer).is_camera());
+}
+
+#[tokio::test]
+async fn each_description_is_fetched_once() {
+    let (location, requests) = serve_description();
+    let camera = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = camera.local_addr().unwrap();
+    let answering = std::thread::spawn(move || {
+        let mut buffer = [0u8; 2048];
+        // One M-SEARCH per search target, each answered, and an answer
+        // describing another host, which is ignored
+        for _ in 0..2 {
+            let (_, from) = camera.recv_from(&mut buffer).unwrap();
+            camera.send_to(search_answer(&location).as_bytes(), from).unwrap();
+            camera.send_to(search_answer("http://192.0.2.50/description.xml").as_bytes(), from).unwrap();
+        }
+    });
+
+    let discovery = discover_via(&[Ipv4Addr::LOCALHOST], address, Duration::from_millis(700)).await;
+    answering.join().unwrap();
+    let discovery = discovery.unwrap();
+    assert_eq!(discovery.interfaces, vec![Ipv4Addr::LOCALHOST]);
+    assert_eq!(discovery.devices.len(), 1);
+    assert_eq!(discovery.devices[0].responder, LOOPBACK);
+    assert_eq!(discovery.devices[0].friendly_name.as_deref(), Some("Garage Camera & Drive"));
+    assert_eq!(requests.all().len(), 1);
+}
+
+#[test]
+fn a_camera_found_by_both_protocols_is_merged_on_ip() {
+    let onvif = parse_probe_matches(include_str!("fixtures/onvif_probe_matches.xml"), LOOPBACK);
+    let answer = parse_search_response(&search_answer("http://127.0.0.1:49152/upnp/device_description.xml"), LOOPBACK).unwrap();
+    let on_loopback = parse_description(DESCRIPTION, answer.clone());
+    let elsewhere = parse_description(DESCRIPTION, mcp_webcam::ssdp::SearchResponse {
+        location: "http://192.0.2.20:49152/upnp/device_description.xml".to_string(),
+        responder: "192.0.2.20".parse().unwrap(),
+        ..answer
+    });
+
+    let cameras = merge_cameras(onvif, vec![on_loopback, elsewhere]);
+    assert_eq!(cameras.len(), 2);
+    let both = &cameras[0];
+    assert_eq!(both.webcam.url, "http://127.0.0.1:8000/onvif/device_service");
+    assert_eq!(both.webcam.sources, vec!["onvif", "ssdp"]);
+    assert_eq!(both.name.as_deref(), Some("HIKVISION Front Door"));
+    assert!(both.onvif.is_some() && both.ssdp.is_some());
+    let ssdp_only = &cameras[1];
+    assert_eq!(ssdp_only.webcam.ip, "192.0.2.20");
+    assert_eq!(ssdp_only.name.as_deref(), Some("Garage Camera & Drive"));
+    assert!(ssdp_only.onvif.is_none());
+}
+
+#[test]
+fn unknown_protocols_are_refused() {
+    let server = WebcamMcpServer::builder().backend(Box::new(MockBackend::new(1))).preview(false).build().build_server().unwrap();
+    for protocols in [json!([]), json!(["mdns"]), json!(["ssdp", "bonjour"])] {
+        let request = json!({
+            "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": { "name": "discover_local_network_cameras", "arguments": { "protocols": protocols } }
+        });
+        let response = server.handle_message(&request.to_string()).unwrap();
+        assert_eq!(response["result"]["structuredContent"]["error"]["code"], "INVALID_PARAMS", "{}", protocols);
+    }
+}
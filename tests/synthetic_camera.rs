@@ -0,0 +1,361 @@
+//! End-to-end tests of the capture pipeline and MCP handlers against the
+//! synthetic camera. Run with `cargo test --features test_camera`.
+
+use mcp_webcam::image_processing::COLOR_BARS;
+use mcp_webcam::protocol::Server;
+use mcp_webcam::tool_schemas::validate;
+use mcp_webcam::webcam::{SYNTHETIC_CAMERA_INDEX, SYNTHETIC_CAMERA_NAME, SYNTHETIC_DEFAULT_RESOLUTION};
+use mcp_webcam::{CaptureOptions, CropRect, WebcamManager, WebcamMcpServer};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+fn capture(manager: &mut WebcamManager, options: &CaptureOptions) -> image::RgbImage {
+    manager
+        .capture_frame(Some(SYNTHETIC_CAMERA_INDEX), options)
+        .expect("synthetic capture")
+        .image
+}
+
+/// Channels within JPEG/resampling tolerance of the expected colour
+fn assert_close(actual: [u8; 3], expected: [u8; 3]) {
+    for (a, e) in actual.iter().zip(expected.iter()) {
+        assert!((*a as i32 - *e as i32).abs() <= 40, "{:?} is not close to {:?}", actual, expected);
+    }
+}
+
+fn call_tool(server: &Server, name: &str, arguments: Value) -> Value {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": { "name": name, "arguments": arguments }
+    });
+    let response = server.handle_message(&request.to_string()).expect("response");
+    response.get("result").cloned().unwrap_or_else(|| panic!("tool call failed: {}", response))
+}
+
+/// Check a `tools/call` result against the MCP `CallToolResult` schema
+fn assert_call_tool_result(tool: &str, result: &Value) {
+    let fields = result.as_object().unwrap_or_else(|| panic!("{}: result is not an object", tool));
+    for key in fields.keys() {
+        assert!(
+            ["content", "isError", "structuredContent", "_meta"].contains(&key.as_str()),
+            "{}: unexpected field '{}'", tool, key
+        );
+    }
+
+    let content = result["content"].as_array().unwrap_or_else(|| panic!("{}: content is not an array", tool));
+    for block in content {
+        let valid = match block["type"].as_str() {
+            Some("text") => block["text"].is_string(),
+            Some("image") | Some("audio") => block["data"].is_string() && block["mimeType"].is_string(),
+            Some("resource_link") => block["uri"].is_string() && block["name"].is_string(),
+            Some("resource") => {
+                let resource = &block["resource"];
+                resource["uri"].is_string() && (resource["text"].is_string() || resource["blob"].is_string())
+            }
+            _ => false,
+        };
+        assert!(valid, "{}: invalid content block {}", tool, block);
+    }
+
+    if let Some(is_error) = fields.get("isError") {
+        assert!(is_error.is_boolean(), "{}: isError is not a boolean", tool);
+    }
+    if let Some(structured) = fields.get("structuredContent") {
+        assert!(structured.is_object(), "{}: structuredContent is not an object", tool);
+    }
+    if result["isError"] == true {
+        assert!(content.iter().any(|block| block["type"] == "text"), "{}: error without text", tool);
+        assert!(result["structuredContent"]["error"]["code"].is_string(), "{}: error without code", tool);
+    }
+}
+
+#[test]
+fn synthetic_camera_is_listed() {
+    let cameras = WebcamManager::new().list_cameras().unwrap();
+    let camera = cameras.iter().find(|c| c.index == SYNTHETIC_CAMERA_INDEX).expect("synthetic camera listed");
+    assert_eq!(camera.name, SYNTHETIC_CAMERA_NAME);
+}
+
+#[test]
+fn frames_are_deterministic() {
+    let options = CaptureOptions::default();
+    let mut first = WebcamManager::new();
+    let mut second = WebcamManager::new();
+
+    let a1 = capture(&mut first, &options);
+    let b1 = capture(&mut second, &options);
+    assert_eq!(a1, b1);
+    assert_eq!(a1.dimensions(), SYNTHETIC_DEFAULT_RESOLUTION);
+
+    // The frame counter changes from one frame to the next
+    let a2 = capture(&mut first, &options);
+    assert_ne!(a1, a2);
+}
+
+#[test]
+fn resolution_rotation_and_crop_are_applied() {
+    let mut manager = WebcamManager::new();
+
+    let image = capture(&mut manager, &CaptureOptions { resolution: Some((320, 240)), ..Default::default() });
+    assert_eq!(image.dimensions(), (320, 240));
+    assert_close(image.get_pixel(10, 10).0, COLOR_BARS[0]);
+    assert_close(image.get_pixel(310, 10).0, COLOR_BARS[7]);
+
+    let image = capture(&mut manager, &CaptureOptions {
+        resolution: Some((320, 240)),
+        rotation: Some(90),
+        ..Default::default()
+    });
+    assert_eq!(image.dimensions(), (240, 320));
+    // The first (white) bar is now along the top
+    assert_close(image.get_pixel(120, 10).0, COLOR_BARS[0]);
+
+    let image = capture(&mut manager, &CaptureOptions {
+        resolution: Some((320, 240)),
+        rotation: Some(180),
+        crop: Some(CropRect { x: 0, y: 100, width: 40, height: 40 }),
+        ..Default::default()
+    });
+    assert_eq!(image.dimensions(), (40, 40));
+    // After a half turn the black bar is on the left
+    assert_close(image.get_pixel(20, 20).0, COLOR_BARS[7]);
+}
+
+#[test]
+fn encoded_capture_round_trips() {
+    let mut manager = WebcamManager::new();
+    let result = manager
+        .capture_image_with_options(Some(SYNTHETIC_CAMERA_INDEX), &CaptureOptions { quality: Some(90), ..Default::default() })
+        .unwrap();
+
+    assert_eq!(result.mime_type, "image/jpeg");
+    assert_eq!(result.quality, 90);
+    assert_eq!(result.camera_name.as_deref(), Some(SYNTHETIC_CAMERA_NAME));
+    let decoded = result.decode_image().unwrap();
+    assert_eq!(decoded.dimensions(), SYNTHETIC_DEFAULT_RESOLUTION);
+}
+
+#[test]
+fn sequence_frames_are_encoded_in_parallel_and_in_order() {
+    let options = CaptureOptions { resolution: Some((1920, 1080)), ..Default::default() };
+    let frames = 8;
+
+    // Baseline: capture everything, then encode frame by frame
+    let mut manager = WebcamManager::new();
+    let started = Instant::now();
+    let serial: Vec<Vec<u8>> = manager
+        .capture_sequence(Some(SYNTHETIC_CAMERA_INDEX), &options, frames, Duration::ZERO)
+        .unwrap()
+        .iter()
+        .map(|frame| frame.encode_jpeg().unwrap())
+        .collect();
+    let serial_time = started.elapsed();
+
+    let mut manager = WebcamManager::new();
+    let started = Instant::now();
+    let parallel = manager
+        .capture_sequence_with(Some(SYNTHETIC_CAMERA_INDEX), &options, frames, Duration::ZERO, |frame| {
+            frame.encode_jpeg()
+        })
+        .unwrap();
+    let parallel_time = started.elapsed();
+
+    // A fresh synthetic camera repeats the same frames, so matching bytes
+    // means the results came back in capture order
+    assert_eq!(parallel, serial);
+
+    println!("{} frames at 1080p: serial {:?}, parallel {:?}", frames, serial_time, parallel_time);
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if cores >= 4 {
+        assert!(parallel_time < serial_time, "parallel {:?} vs serial {:?}", parallel_time, serial_time);
+    }
+}
+
+#[test]
+fn shared_sequences_leave_the_manager_free_between_frames() {
+    let manager = Arc::new(Mutex::new(WebcamManager::new()));
+    let options = CaptureOptions::default();
+    let sequence = {
+        let manager = Arc::clone(&manager);
+        std::thread::spawn(move || {
+            WebcamManager::capture_sequence_shared(
+                &manager,
+                Some(SYNTHETIC_CAMERA_INDEX),
+                &options,
+                3,
+                Duration::from_millis(300),
+                &CancellationToken::new(),
+                |frame| Ok(frame.image.dimensions()),
+            )
+        })
+    };
+
+    // Another capture gets in during the waits instead of after the clip
+    std::thread::sleep(Duration::from_millis(150));
+    let started = Instant::now();
+    let image = capture(&mut manager.lock().unwrap(), &CaptureOptions::default());
+    assert!(started.elapsed() < Duration::from_millis(300), "waited {:?} for the manager", started.elapsed());
+    assert_eq!(image.dimensions(), SYNTHETIC_DEFAULT_RESOLUTION);
+    assert_eq!(sequence.join().unwrap().unwrap(), vec![SYNTHETIC_DEFAULT_RESOLUTION; 3]);
+}
+
+#[tokio::test]
+async fn mcp_capture_tools_use_synthetic_camera() {
+    let server = WebcamMcpServer::new().build_server().unwrap();
+
+    let listed = call_tool(&server, "list_cameras", json!({}));
+    let names: Vec<&str> = listed["structuredContent"]["cameras"].as_array().unwrap().iter()
+        .filter_map(|c| c["name"].as_str())
+        .collect();
+    assert!(names.contains(&SYNTHETIC_CAMERA_NAME));
+
+    let captured = call_tool(&server, "capture_image", json!({
+        "camera_index": SYNTHETIC_CAMERA_INDEX,
+        "width": 320,
+        "height": 240,
+        "rotation": 270
+    }));
+    assert_eq!(captured["content"][0]["type"], "image");
+    assert_eq!(captured["structuredContent"]["metadata"]["width"], 240);
+    assert_eq!(captured["structuredContent"]["metadata"]["height"], 320);
+    assert_eq!(captured["structuredContent"]["metadata"]["rotation"], 270);
+
+    // The capture is kept in history and can be fetched again
+    let capture_id = captured["structuredContent"]["metadata"]["capture_id"].as_str().unwrap();
+    let fetched = call_tool(&server, "get_capture", json!({ "capture_id": capture_id }));
+    assert_eq!(fetched["content"][0]["data"], captured["content"][0]["data"]);
+
+    let tested = call_tool(&server, "self_test", json!({ "camera_index": SYNTHETIC_CAMERA_INDEX }));
+    assert_eq!(tested["structuredContent"]["passed"], 1);
+}
+
+#[tokio::test]
+async fn server_stats_count_captures_and_failures() {
+    let server = WebcamMcpServer::new().build_server().unwrap();
+
+    call_tool(&server, "capture_image", json!({ "camera_index": SYNTHETIC_CAMERA_INDEX }));
+    call_tool(&server, "capture_image", json!({ "camera_index": SYNTHETIC_CAMERA_INDEX, "rotation": 45 }));
+    call_tool(&server, "capture_clip", json!({ "camera_index": SYNTHETIC_CAMERA_INDEX, "frames": 0 }));
+
+    let stats = &call_tool(&server, "get_server_stats", json!({}))["structuredContent"]["stats"];
+    assert_eq!(stats["captures_per_camera"][SYNTHETIC_CAMERA_INDEX.to_string()], 1);
+    assert_eq!(stats["failures_by_type"]["invalid_params"], 2);
+    assert!(stats["bytes_encoded"].as_u64().unwrap() > 0);
+    assert!(stats["average_capture_ms"].as_f64().unwrap() > 0.0);
+    assert_eq!(stats["shodan_queries"], 0);
+}
+
+#[tokio::test]
+async fn tool_errors_carry_stable_codes() {
+    let server = WebcamMcpServer::new().build_server().unwrap();
+
+    let invalid = call_tool(&server, "capture_image", json!({ "camera_index": SYNTHETIC_CAMERA_INDEX, "rotation": 45 }));
+    assert_eq!(invalid["isError"], true);
+    assert_eq!(invalid["structuredContent"]["error"]["code"], "INVALID_PARAMS");
+    assert!(invalid["structuredContent"]["error"]["message"].as_str().unwrap().contains("45"));
+
+    let missing = call_tool(&server, "get_capture", json!({ "capture_id": "nope" }));
+    assert_eq!(missing["isError"], true);
+    assert_eq!(missing["structuredContent"]["error"]["code"], "CAPTURE_NOT_FOUND");
+    assert_eq!(missing["structuredContent"]["error"]["details"]["capture_id"], "nope");
+
+    let captured = call_tool(&server, "capture_image", json!({ "camera_index": SYNTHETIC_CAMERA_INDEX }));
+    assert!(captured.get("isError").is_none());
+}
+
+#[tokio::test]
+async fn every_tool_returns_spec_shaped_results() {
+    let server = WebcamMcpServer::new().build_server().unwrap();
+    let camera = SYNTHETIC_CAMERA_INDEX;
+
+    let captured = call_tool(&server, "capture_image", json!({ "camera_index": camera }));
+    let capture_id = captured["structuredContent"]["metadata"]["capture_id"].as_str().unwrap().to_string();
+
+    // Tool, arguments and whether the call must fail; `None` where the
+    // outcome depends on the build or host
+    let calls = [
+        ("list_cameras", json!({}), Some(false)),
+        ("capture_image", json!({ "camera_index": camera, "quality": 70 }), Some(false)),
+        ("capture_image", json!({ "camera_index": camera, "as_resource": true }), Some(false)),
+        ("capture_image", json!({ "camera_index": camera, "rotation": 45 }), Some(true)),
+        ("scan_codes", json!({ "camera_index": camera }), None),
+        ("capture_clip", json!({ "camera_index": camera, "frames": 2, "interval_ms": 0 }), Some(false)),
+        ("capture_clip", json!({ "camera_index": camera, "frames": 0 }), Some(true)),
+        ("get_camera_info", json!({}), None),
+        ("set_camera_defaults", json!({ "camera_index": camera, "quality": 80 }), Some(false)),
+        ("set_camera_defaults", json!({}), Some(true)),
+        ("set_default_camera", json!({ "camera_index": camera }), Some(false)),
+        ("set_default_camera", json!({ "camera_index": 4242 }), Some(true)),
+        ("self_test", json!({ "camera_index": camera }), Some(false)),
+        ("benchmark_camera", json!({ "camera_index": camera, "duration_seconds": 0.1 }), None),
+        ("benchmark_camera", json!({ "camera_index": camera, "duration_seconds": 0 }), Some(true)),
+        ("list_captures", json!({}), Some(false)),
+        ("get_capture", json!({ "capture_id": capture_id }), Some(false)),
+        ("get_capture", json!({ "capture_id": "nope" }), Some(true)),
+        ("compare_captures", json!({ "capture_id": capture_id, "capture_new": true }), Some(false)),
+        ("compare_captures", json!({ "capture_id": capture_id }), Some(true)),
+        ("get_storage_info", json!({}), Some(false)),
+        ("get_audit_log", json!({ "limit": 10 }), Some(false)),
+        ("get_audit_log", json!({ "since": "not a time" }), Some(true)),
+        ("get_server_stats", json!({}), Some(false)),
+        ("get_server_info", json!({}), Some(false)),
+        ("health_check", json!({}), Some(false)),
+        ("configure_shodan", json!({}), Some(true)),
+        ("configure_shodan", json!({ "api_key": "key", "remove": true }), Some(true)),
+        ("stop_preview", json!({}), Some(false)),
+    ];
+
+    let listed = server
+        .handle_message(&json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" }).to_string())
+        .unwrap();
+    let output_schemas: HashMap<String, Value> = listed["result"]["tools"].as_array().unwrap().iter()
+        .filter(|tool| !tool["outputSchema"].is_null())
+        .map(|tool| (tool["name"].as_str().unwrap().to_string(), tool["outputSchema"].clone()))
+        .collect();
+
+    for (tool, arguments, fails) in calls {
+        let result = call_tool(&server, tool, arguments.clone());
+        assert_call_tool_result(tool, &result);
+        if let Some(fails) = fails {
+            assert_eq!(result["isError"] == true, fails, "{} {}: {}", tool, arguments, result);
+        }
+
+        // Successful results match the declared output schema
+        if result["isError"] != true {
+            if let Some(schema) = output_schemas.get(tool) {
+                if let Err(e) = validate(schema, &result["structuredContent"]) {
+                    panic!("{} {}: {}", tool, arguments, e);
+                }
+            } else {
+                assert!(result.get("structuredContent").is_none(), "{} has no output schema", tool);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn structured_content_is_repeated_as_text_for_older_clients() {
+    let server = WebcamMcpServer::new().build_server().unwrap();
+    let arguments = json!({ "camera_index": SYNTHETIC_CAMERA_INDEX });
+
+    // Before initialize the oldest supported protocol version is assumed
+    let content = &call_tool(&server, "self_test", arguments.clone())["content"];
+    let json_block = content.as_array().unwrap().last().unwrap()["text"].as_str().unwrap();
+    assert_eq!(serde_json::from_str::<Value>(json_block).unwrap()["passed"], 1);
+
+    let initialize = json!({
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": "initialize",
+        "params": { "protocolVersion": "2025-06-18", "capabilities": {} }
+    });
+    server.handle_message(&initialize.to_string()).unwrap();
+    let result = call_tool(&server, "self_test", arguments);
+    assert_eq!(result["content"].as_array().unwrap().len(), 1);
+    assert_eq!(result["structuredContent"]["passed"], 1);
+}
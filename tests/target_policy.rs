@@ -0,0 +1,315 @@
+//! The policy on which webcam URLs may be fetched: private ranges, schemes,
+//! redirects, host names resolved again when connecting or pinned for
+//! ffmpeg, proxies, and the operator's allowlist and blocklist, each
+//! refusal naming the rule behind it.
+
+mod common;
+
+use common::{serve, HttpResponse};
+use futures::future::BoxFuture;
+use mcp_webcam::target_policy::{restricted_range, HostLookup};
+use mcp_webcam::tool_error::{ErrorCode, ToolError};
+use mcp_webcam::{AuditLog, MockBackend, RemoteWebcam, ShodanClient, ShodanError, TargetPolicy, TargetRule, WebcamMcpServer};
+use serde_json::json;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const GIF: &str = "GIF89a\x01\0\x01\0";
+
+fn webcam(url: &str) -> RemoteWebcam {
+    RemoteWebcam { url: url.to_string(), ..common::webcam("192.0.2.1", 80) }
+}
+
+/// Answer each request for `/hop<n>` with a redirect to `location(n)`, and
+/// any other path with a GIF. Returns the server's base URL.
+fn serve_redirects(location: fn(&str, usize) -> String) -> String {
+    let (base, _) = serve(move |request| match request.path.strip_prefix("/hop").and_then(|n| n.parse::<usize>().ok()) {
+        Some(n) => HttpResponse::empty("302 Found").header("Location", &location(&request.base_url(), n)),
+        None => HttpResponse::new("200 OK", "image/gif", GIF),
+    });
+    base
+}
+
+#[test]
+fn non_public_ranges_are_named() {
+    let range = |ip: &str| restricted_range(ip.parse::<IpAddr>().unwrap());
+    assert_eq!(range("127.0.0.1"), Some("loopback"));
+    assert_eq!(range("10.1.2.3"), Some("private"));
+    assert_eq!(range("192.168.1.64"), Some("private"));
+    assert_eq!(range("169.254.169.254"), Some("link-local"));
+    assert_eq!(range("100.64.0.1"), Some("shared (carrier-grade NAT)"));
+    assert_eq!(range("0.0.0.0"), Some("unspecified"));
+    assert_eq!(range("::1"), Some("loopback"));
+    assert_eq!(range("fd00::1"), Some("private (unique local)"));
+    assert_eq!(range("fe80::1"), Some("link-local"));
+    assert_eq!(range("::ffff:192.168.1.1"), Some("private"));
+    assert_eq!(range("93.184.216.34"), None);
+    assert_eq!(range("2606:2800:220:1:248:1893:25c8:1946"), None);
+}
+
+#[tokio::test]
+async fn private_targets_and_other_schemes_are_refused_before_any_request() {
+    let client = ShodanClient::new("key".to_string());
+
+    let error = client.fetch_webcam_image(&webcam("http://169.254.169.254/latest/meta-data/")).await.unwrap_err();
+    match &error {
+        ShodanError::TargetBlocked { target, reason } => {
+            assert_eq!(target, "http://169.254.169.254/latest/meta-data/");
+            assert!(reason.contains("link-local") && reason.contains("--allow-private-targets"), "{}", reason);
+        }
+        other => panic!("expected TargetBlocked, got {:?}", other),
+    }
+    let tool_error = ToolError::from(&error);
+    assert_eq!(tool_error.code, ErrorCode::TargetBlocked);
+    assert!(tool_error.details.unwrap()["reason"].as_str().unwrap().contains("link-local"));
+
+    let error = client.fetch_webcam_image(&webcam("http://[::1]:8080/snapshot.jpg")).await.unwrap_err();
+    assert!(matches!(&error, ShodanError::TargetBlocked { reason, .. } if reason.contains("loopback")), "{:?}", error);
+
+    for url in ["file:///etc/passwd", "ftp://192.0.2.1/snapshot.jpg", "gopher://192.0.2.1/"] {
+        let error = client.fetch_webcam_image(&webcam(url)).await.unwrap_err();
+        assert!(matches!(&error, ShodanError::TargetBlocked { reason, .. } if reason.contains("scheme")), "{}: {:?}", url, error);
+    }
+
+    // Allowed, the local stand-in is reached
+    let base = serve_redirects(|base, _| format!("{}/snapshot.gif", base));
+    let local = ShodanClient::builder().allow_private_targets(true).build("key").unwrap();
+    let image = local.fetch_webcam_image(&webcam(&format!("{}/snapshot.gif", base))).await.unwrap();
+    assert_eq!(image.mime_type, "image/gif");
+}
+
+#[tokio::test]
+async fn redirects_are_checked_and_capped_at_three() {
+    let client = ShodanClient::builder().allow_private_targets(true).build("key").unwrap();
+
+    // hop1 -> hop2 -> hop3 -> image: three redirects are followed
+    let base = serve_redirects(|base, n| if n < 3 { format!("{}/hop{}", base, n + 1) } else { "/snapshot.gif".to_string() });
+    let image = client.fetch_webcam_image(&webcam(&format!("{}/hop1", base))).await.unwrap();
+    assert_eq!(image.mime_type, "image/gif");
+
+    // A fourth is not
+    let base = serve_redirects(|base, n| format!("{}/hop{}", base, n + 1));
+    let error = client.fetch_webcam_image(&webcam(&format!("{}/hop1", base))).await.unwrap_err();
+    assert!(matches!(&error, ShodanError::TargetBlocked { reason, .. } if reason == "more than 3 redirects"), "{:?}", error);
+
+    // Each target is checked like the first URL
+    let base = serve_redirects(|_, _| "file:///etc/passwd".to_string());
+    let error = client.fetch_webcam_image(&webcam(&format!("{}/hop1", base))).await.unwrap_err();
+    assert!(matches!(&error, ShodanError::TargetBlocked { target, .. } if target == "file:///etc/passwd"), "{:?}", error);
+}
+
+/// Answers every name with `answers` in turn, one per lookup and the last
+/// over again once they run out
+#[derive(Debug)]
+struct ScriptedLookup {
+    answers: Vec<IpAddr>,
+    lookups: AtomicUsize,
+}
+
+impl ScriptedLookup {
+    fn new(answers: &[&str]) -> Arc<Self> {
+        Arc::new(Self { answers: answers.iter().map(|ip| ip.parse().unwrap()).collect(), lookups: AtomicUsize::new(0) })
+    }
+}
+
+impl HostLookup for ScriptedLookup {
+    fn lookup(&self, _host: &str) -> BoxFuture<'static, std::io::Result<Vec<IpAddr>>> {
+        let index = self.lookups.fetch_add(1, Ordering::SeqCst).min(self.answers.len() - 1);
+        let answer = vec![self.answers[index]];
+        Box::pin(async move { Ok(answer) })
+    }
+}
+
+#[tokio::test]
+async fn connections_go_to_the_addresses_checked() {
+    let base = serve_redirects(|base, _| format!("{}/snapshot.gif", base));
+    let port = base.rsplit(':').next().unwrap().to_string();
+
+    // A name the system can't resolve reaches the stand-in on loopback
+    // only through the address the policy's lookup gave
+    let lookup = ScriptedLookup::new(&["127.0.0.1"]);
+    let client = ShodanClient::builder().allow_private_targets(true).host_lookup(lookup.clone()).build("key").unwrap();
+    let image = client.fetch_webcam_image(&webcam(&format!("http://camera.invalid:{}/snapshot.gif", port))).await.unwrap();
+    assert_eq!(image.mime_type, "image/gif");
+    assert!(lookup.lookups.load(Ordering::SeqCst) >= 1);
+
+    // A name answering the check with a public address and the connection
+    // with loopback is refused when connecting
+    let rebinding = ScriptedLookup::new(&["203.0.113.9", "127.0.0.1"]);
+    let client = ShodanClient::builder().host_lookup(rebinding.clone()).build("key").unwrap();
+    let error = client.fetch_webcam_image(&webcam(&format!("http://rebind.invalid:{}/snapshot.gif", port))).await.unwrap_err();
+    assert!(matches!(&error, ShodanError::TargetBlocked { reason, .. } if reason.contains("loopback")), "{:?}", error);
+    assert_eq!(rebinding.lookups.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn rtsp_snapshots_go_to_the_address_checked() {
+    // ffmpeg would resolve the name again, so the second answer is the
+    // one checked and handed to it
+    let rebinding = ScriptedLookup::new(&["203.0.113.9", "10.0.0.5"]);
+    let client = ShodanClient::builder().host_lookup(rebinding.clone()).build("key").unwrap();
+    let error = client.fetch_webcam_image(&webcam("rtsp://rebind.invalid:554/live")).await.unwrap_err();
+    assert!(matches!(&error, ShodanError::TargetBlocked { reason, .. } if reason.contains("private")), "{:?}", error);
+    assert_eq!(rebinding.lookups.load(Ordering::SeqCst), 2);
+}
+
+/// Answers the names listed with their address, and any other with none
+#[derive(Debug)]
+struct NamedLookup(Vec<(&'static str, IpAddr)>);
+
+impl HostLookup for NamedLookup {
+    fn lookup(&self, host: &str) -> BoxFuture<'static, std::io::Result<Vec<IpAddr>>> {
+        let answer = self.0.iter().filter(|(name, _)| *name == host).map(|(_, ip)| *ip).collect();
+        Box::pin(async move { Ok(answer) })
+    }
+}
+
+#[tokio::test]
+async fn proxies_may_be_private_and_webcams_behind_them_are_still_checked() {
+    let (proxy, requests) = serve(|_| HttpResponse::new("200 OK", "image/gif", GIF));
+    let port = proxy.rsplit(':').next().unwrap();
+    let lookup = NamedLookup(vec![
+        ("proxy.invalid", "127.0.0.1".parse().unwrap()),
+        ("camera.invalid", "203.0.113.9".parse().unwrap()),
+        ("lan.invalid", "10.0.0.5".parse().unwrap()),
+    ]);
+    let client = ShodanClient::builder()
+        .proxy(format!("http://proxy.invalid:{}", port))
+        .host_lookup(Arc::new(lookup))
+        .build("key")
+        .unwrap();
+
+    // The proxy is on loopback, yet reached
+    let image = client.fetch_webcam_image(&webcam("http://camera.invalid/snapshot.gif")).await.unwrap();
+    assert_eq!(image.mime_type, "image/gif");
+    assert_eq!(requests.paths(), vec!["http://camera.invalid/snapshot.gif"]);
+
+    // A webcam on the local network is refused before the proxy is asked
+    let error = client.fetch_webcam_image(&webcam("http://lan.invalid/snapshot.gif")).await.unwrap_err();
+    assert!(matches!(&error, ShodanError::TargetBlocked { reason, .. } if reason.contains("private")), "{:?}", error);
+    assert_eq!(requests.all().len(), 1);
+}
+
+#[test]
+fn captures_of_private_targets_fail_with_target_blocked() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(ShodanClient::new("test-key".to_string()))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": { "name": "capture_remote_image", "arguments": { "url": "http://10.0.0.5/snapshot.jpg" } }
+    });
+    let response = server.handle_message(&request.to_string()).unwrap();
+    let error = &response["result"]["structuredContent"]["error"];
+    assert_eq!(error["code"], "TARGET_BLOCKED");
+    assert_eq!(error["details"]["target"], "http://10.0.0.5/snapshot.jpg");
+}
+
+fn rules(list: &[&str]) -> Vec<TargetRule> {
+    list.iter().map(|rule| rule.parse().unwrap()).collect()
+}
+
+#[test]
+fn rules_are_networks_addresses_or_host_names() {
+    let ip = |text: &str| text.parse::<IpAddr>().unwrap();
+    let network: TargetRule = "203.0.113.0/24".parse().unwrap();
+    assert!(network.contains(ip("203.0.113.200")));
+    assert!(network.contains(ip("::ffff:203.0.113.1")));
+    assert!(!network.contains(ip("203.0.114.1")));
+    let single: TargetRule = "198.51.100.7".parse().unwrap();
+    assert_eq!(single.to_string(), "198.51.100.7/32");
+    assert!(single.contains(ip("198.51.100.7")) && !single.contains(ip("198.51.100.8")));
+    assert!("2001:db8::/32".parse::<TargetRule>().unwrap().contains(ip("2001:db8:1::5")));
+    assert!("0.0.0.0/0".parse::<TargetRule>().unwrap().contains(ip("192.0.2.1")));
+
+    let host: TargetRule = "Cam.Example.com.".parse().unwrap();
+    assert_eq!(host.to_string(), "cam.example.com");
+    assert!(host.matches_name("cam.example.com") && !host.matches_name("other.example.com"));
+    let wildcard: TargetRule = "*.example.com".parse().unwrap();
+    assert!(wildcard.matches_name("cam.example.com") && wildcard.matches_name("a.b.example.com"));
+    assert!(!wildcard.matches_name("example.com") && !wildcard.matches_name("badexample.com"));
+
+    for invalid in ["203.0.113.0/33", "fe80::/129", "not a host", "*.", "http://cam.example.com", ""] {
+        assert!(invalid.parse::<TargetRule>().is_err(), "{}", invalid);
+    }
+}
+
+#[tokio::test]
+async fn the_blocklist_beats_the_allowlist_and_allowed_ranges_may_be_private() {
+    let policy = TargetPolicy { allow: rules(&["203.0.113.0/24", "127.0.0.0/8"]), block: rules(&["203.0.113.66", "localhost"]), ..TargetPolicy::default() };
+    let reason = |result: Result<(), ShodanError>| match result {
+        Err(ShodanError::TargetBlocked { reason, .. }) => reason,
+        other => panic!("expected TargetBlocked, got {:?}", other),
+    };
+
+    assert!(policy.check("http://203.0.113.5/snapshot.jpg").await.is_ok());
+    // Private, but allowlisted without --allow-private-targets
+    assert!(policy.check("http://127.0.0.1:8080/snapshot.jpg").await.is_ok());
+    assert_eq!(reason(policy.check("http://198.51.100.1/").await), "198.51.100.1 is not on the allowlist");
+    assert_eq!(reason(policy.check("http://203.0.113.66/").await), "203.0.113.66 is on the blocklist (203.0.113.66/32)");
+    // Host names are refused as written, before they are resolved
+    assert_eq!(reason(policy.check("http://LOCALHOST:8080/").await), "localhost is on the blocklist (localhost)");
+    assert!(reason(policy.check("rtsp://localhost/stream").await).contains("blocklist"));
+    assert!(reason(policy.check_ip("rtsp://localhost/stream", "127.0.0.1".parse().unwrap())).contains("blocklist"));
+    assert!(policy.check_ip("rtsp://203.0.113.9/stream", "203.0.113.9".parse().unwrap()).is_ok());
+
+    // An allowlisted name is let through by the addresses it resolves to,
+    // which still have to be public unless private targets are allowed
+    let named = TargetPolicy { allow: rules(&["localhost"]), ..TargetPolicy::default() };
+    assert!(reason(named.check("http://localhost:8080/").await).contains("loopback"));
+    let named = TargetPolicy { allow_private: true, ..named };
+    assert!(named.check("http://localhost:8080/").await.is_ok());
+    assert!(reason(named.check("http://127.0.0.1:8080/").await).contains("not on the allowlist"));
+}
+
+#[tokio::test]
+async fn policy_decisions_are_audited_and_shown_by_get_target_policy() {
+    let dir = std::env::temp_dir().join(format!("mcp-webcam-target-rules-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let audit_log = AuditLog::open(dir.join("audit.jsonl"));
+    let base = serve_redirects(|base, _| format!("{}/snapshot.gif", base));
+    let client = ShodanClient::builder()
+        .target_rules(rules(&["127.0.0.1"]), rules(&["*.cameras.example.com"]))
+        .build("key")
+        .unwrap();
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(client)
+        .audit_log(audit_log.clone())
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+    let call = |tool: &str, arguments: serde_json::Value| {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": { "name": tool, "arguments": arguments } });
+        server.handle_message(&request.to_string()).unwrap()["result"].clone()
+    };
+
+    let shown = call("get_target_policy", json!({}));
+    let policy = &shown["structuredContent"]["target_policy"];
+    assert_eq!(policy["allowlist"], json!(["127.0.0.1/32"]));
+    assert_eq!(policy["blocklist"], json!(["*.cameras.example.com"]));
+    assert_eq!(policy["allow_private"], false);
+    assert_eq!(policy["max_redirects"], 3);
+
+    let refused = call("capture_remote_image", json!({ "url": "http://192.0.2.1/snapshot.jpg" }));
+    assert_eq!(refused["structuredContent"]["error"]["code"], "TARGET_BLOCKED");
+    let captured = call("capture_remote_image", json!({ "url": format!("{}/snapshot.gif", base) }));
+    assert_ne!(captured["isError"], true, "{}", captured);
+
+    let decisions: Vec<_> = audit_log.read(None, 100).unwrap().entries.into_iter().filter(|entry| entry.tool == "target_policy").collect();
+    assert_eq!(decisions.len(), 2);
+    assert!(!decisions[0].success);
+    assert_eq!(decisions[0].error_code.as_deref(), Some("TARGET_BLOCKED"));
+    assert_eq!(decisions[0].arguments["reason"], "192.0.2.1 is not on the allowlist");
+    assert_eq!(decisions[1].arguments["decision"], "allowed");
+    assert_eq!(decisions[1].url.as_deref(), Some(format!("{}/snapshot.gif", base).as_str()));
+    let _ = std::fs::remove_dir_all(dir);
+}
@@ -0,0 +1,120 @@
+//! HTTPS webcams with self-signed certificates: the certificate named in
+//! the error, and accepted only when a call asks.
+
+mod common;
+
+use mcp_webcam::certificate::certificate_names;
+use mcp_webcam::tool_error::{ErrorCode, ToolError};
+use mcp_webcam::{MockBackend, RemoteWebcam, ShodanClient, ShodanError, WebcamMcpServer};
+use serde_json::json;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::time::Duration;
+
+const CERTIFICATE: &[u8] = include_bytes!("fixtures/self_signed_webcam.der");
+const IDENTITY: &[u8] = include_bytes!("fixtures/self_signed_webcam.p12");
+const SUBJECT: &str = "C=GB, O=Example Cameras, CN=webcam.test";
+const GIF: &str = "GIF89a\x01\0\x01\0";
+
+/// A webcam serving a GIF over HTTPS with the self-signed certificate.
+/// Returns the snapshot's URL.
+fn serve_https() -> String {
+    let identity = native_tls::Identity::from_pkcs12(IDENTITY, "webcam").unwrap();
+    let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("https://{}/snapshot.gif", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { break };
+            let acceptor = acceptor.clone();
+            std::thread::spawn(move || {
+                // Clients that reject the certificate end the handshake
+                let Ok(stream) = acceptor.accept(stream) else { return };
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).map_or(false, |n| n > 2) {
+                    line.clear();
+                }
+                let response = format!("HTTP/1.1 200 OK\r\nContent-Type: image/gif\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", GIF.len(), GIF);
+                let _ = reader.get_mut().write_all(response.as_bytes());
+            });
+        }
+    });
+    url
+}
+
+fn webcam(url: &str) -> RemoteWebcam {
+    RemoteWebcam { url: url.to_string(), ..common::webcam("127.0.0.1", 443) }
+}
+
+fn local_client() -> ShodanClient {
+    ShodanClient::builder().allow_private_targets(true).build("key").unwrap()
+}
+
+#[test]
+fn certificate_names_are_read_from_der() {
+    let names = certificate_names(CERTIFICATE).unwrap();
+    assert_eq!(names.subject, SUBJECT);
+    assert_eq!(names.issuer, SUBJECT);
+    assert!(names.self_signed());
+    assert_eq!(certificate_names(&CERTIFICATE[..100]), None);
+    assert_eq!(certificate_names(b"not a certificate"), None);
+}
+
+#[tokio::test]
+async fn self_signed_certificates_are_named_in_the_error() {
+    let url = serve_https();
+    let error = local_client().fetch_webcam_image(&webcam(&url)).await.unwrap_err();
+    match &error {
+        ShodanError::TlsError { reason, certificate } => {
+            assert!(reason.to_lowercase().contains("certificate"), "{}", reason);
+            let certificate = certificate.as_ref().expect("the certificate's names");
+            assert_eq!(certificate.subject, SUBJECT);
+        }
+        other => panic!("expected TlsError, got {:?}", other),
+    }
+    let tool_error = ToolError::from(&error);
+    assert_eq!(tool_error.code, ErrorCode::TlsError);
+    let details = tool_error.details.unwrap();
+    assert_eq!(details["issuer"], SUBJECT);
+    assert_eq!(details["self_signed"], true);
+}
+
+#[tokio::test]
+async fn invalid_certificates_are_accepted_only_when_asked() {
+    let url = serve_https();
+    let image = local_client().accepting_invalid_certs(true).fetch_webcam_image(&webcam(&url)).await.unwrap();
+    assert_eq!(image.mime_type, "image/gif");
+    assert!(image.tls_unverified);
+
+    // A client accepting them by default still honors a call saying no
+    let accepting = ShodanClient::builder().allow_private_targets(true).accept_invalid_webcam_certs(true).build("key").unwrap();
+    assert!(accepting.accepts_invalid_certs());
+    let error = accepting.accepting_invalid_certs(false).fetch_webcam_image(&webcam(&url)).await.unwrap_err();
+    assert!(matches!(error, ShodanError::TlsError { .. }), "{:?}", error);
+}
+
+#[test]
+fn captures_note_an_unverified_certificate() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(local_client())
+        .host_cooldown(Duration::ZERO)
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+    let url = serve_https();
+    let capture = |arguments: serde_json::Value| {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": { "name": "capture_remote_image", "arguments": arguments } });
+        server.handle_message(&request.to_string()).unwrap()["result"].clone()
+    };
+
+    let refused = capture(json!({ "url": url }));
+    assert_eq!(refused["structuredContent"]["error"]["code"], "TLS_ERROR");
+    assert_eq!(refused["structuredContent"]["error"]["details"]["subject"], SUBJECT);
+
+    let accepted = capture(json!({ "url": url, "accept_invalid_certs": true }));
+    assert_eq!(accepted["metadata"]["tls_unverified"], true, "{}", accepted);
+    assert!(accepted["content"][1]["text"].as_str().unwrap().contains("not verified"));
+}
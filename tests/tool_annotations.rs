@@ -0,0 +1,47 @@
+//! Tool annotations advertised by `tools/list`.
+
+use mcp_webcam::WebcamMcpServer;
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn tools_list_carries_annotations() {
+    // Register the Shodan tools too; nothing is sent to Shodan here
+    std::env::set_var("SHODAN_API_KEY", "test-key");
+    let server = WebcamMcpServer::new().build_server().unwrap();
+
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" });
+    let response = server.handle_message(&request.to_string()).unwrap();
+    let tools = response["result"]["tools"].as_array().unwrap();
+    let annotations = |name: &str| -> &Value {
+        let tool = tools.iter().find(|t| t["name"] == name).unwrap_or_else(|| panic!("{} not listed", name));
+        &tool["annotations"]
+    };
+
+    for tool in tools {
+        assert!(tool["annotations"].is_object(), "{} has no annotations", tool["name"]);
+    }
+
+    for name in ["list_cameras", "get_camera_info"] {
+        assert_eq!(annotations(name)["readOnlyHint"], true, "{}", name);
+        assert_eq!(annotations(name)["openWorldHint"], false, "{}", name);
+    }
+
+    // Capturing turns the camera on, but destroys nothing
+    let capture = annotations("capture_image");
+    assert_eq!(capture["readOnlyHint"], false);
+    assert_eq!(capture["destructiveHint"], false);
+    assert_eq!(capture["openWorldHint"], false);
+
+    for name in ["search_webcams", "count_webcams", "capture_remote_image", "shodan_account_info", "shodan_host_info", "probe_webcam", "validate_webcams"] {
+        assert_eq!(annotations(name)["openWorldHint"], true, "{}", name);
+    }
+
+    // The webcam cache is local: listing it reads, clearing it destroys and
+    // exporting it only writes a new file
+    assert_eq!(annotations("list_remote_webcams")["readOnlyHint"], true);
+    assert_eq!(annotations("list_remote_webcams")["openWorldHint"], false);
+    assert_eq!(annotations("clear_remote_webcams")["destructiveHint"], true);
+    assert_eq!(annotations("export_webcams")["openWorldHint"], false);
+    assert_eq!(annotations("export_webcams")["destructiveHint"], false);
+    assert_eq!(annotations("get_target_policy")["readOnlyHint"], true);
+}
@@ -0,0 +1,47 @@
+//! The JSON Schema subset used for tool output schemas.
+
+use mcp_webcam::tool_schemas::{output_schemas, validate};
+use serde_json::json;
+
+#[test]
+fn validation_reports_the_offending_path() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "cameras": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": { "index": { "type": "integer" }, "name": { "type": ["string", "null"] } },
+                    "required": ["index"]
+                }
+            },
+            "format": { "type": "string", "enum": ["gif", "mjpeg"] },
+            "counts": { "type": "object", "additionalProperties": { "type": "integer" } }
+        },
+        "required": ["cameras"]
+    });
+
+    let valid = json!({ "cameras": [{ "index": 0, "name": null, "extra": true }], "format": "gif", "counts": { "0": 3 } });
+    assert_eq!(validate(&schema, &valid), Ok(()));
+
+    let cases = [
+        (json!({}), "$: missing required property 'cameras'"),
+        (json!({ "cameras": [{ "index": 1.5 }] }), "$.cameras[0].index: expected integer"),
+        (json!({ "cameras": [{}] }), "$.cameras[0]: missing required property 'index'"),
+        (json!({ "cameras": [], "format": "avi" }), "$.format: \"avi\" is not one of"),
+        (json!({ "cameras": [], "counts": { "0": "x" } }), "$.counts.0: expected integer"),
+    ];
+    for (value, expected) in cases {
+        let error = validate(&schema, &value).unwrap_err();
+        assert!(error.starts_with(expected), "{} does not start with {}", error, expected);
+    }
+}
+
+#[test]
+fn output_schemas_describe_objects() {
+    for (tool, schema) in output_schemas() {
+        assert_eq!(schema["type"], "object", "{}", tool);
+        assert!(!schema["required"].as_array().unwrap().is_empty(), "{}", tool);
+    }
+}
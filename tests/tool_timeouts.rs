@@ -0,0 +1,81 @@
+//! Per-tool timeouts enforced by the dispatch layer.
+
+mod common;
+
+use common::call;
+use mcp_webcam::protocol::{Server, ServerConfig};
+use mcp_webcam::{ConfigFile, MockBackend, WebcamMcpServer};
+use mcpr::schema::common::{Tool, ToolInputSchema};
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[test]
+fn slow_tool_fails_with_tool_timeout_and_is_cancelled() {
+    let tool = Tool {
+        name: "hang".to_string(),
+        description: None,
+        input_schema: ToolInputSchema {
+            r#type: "object".to_string(),
+            properties: Some([("api_key".to_string(), json!({ "type": "string" }))].into_iter().collect()),
+            required: None,
+        },
+    };
+    let config = ServerConfig::new().with_tool(tool).with_timeout("hang", Duration::from_millis(100));
+    let mut server = Server::new(config);
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let seen = Arc::clone(&cancelled);
+    server
+        .register_cancellable_tool_handler("hang", move |_params, cancel| {
+            let started = Instant::now();
+            while !cancel.is_cancelled() && started.elapsed() < Duration::from_secs(5) {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            seen.store(cancel.is_cancelled(), Ordering::SeqCst);
+            Ok(json!({ "content": [] }))
+        })
+        .unwrap();
+
+    let started = Instant::now();
+    // The key is left out of the timeout log line
+    let result = call(&server, "hang", json!({ "api_key": "secret" }));
+    assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+    assert_eq!(result["isError"], true);
+    let error = &result["structuredContent"]["error"];
+    assert_eq!(error["code"], "TOOL_TIMEOUT");
+    assert_eq!(error["details"], json!({ "tool": "hang", "timeout_ms": 100 }));
+
+    // The handler was told to stop
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(cancelled.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn timeouts_are_configurable_and_reported() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .tool_timeout("capture_image", Duration::from_secs(5))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let info = call(&server, "get_server_info", json!({}));
+    let timeouts = &info["structuredContent"]["server_info"]["tool_timeouts_ms"];
+    assert_eq!(timeouts["capture_image"], 5000);
+    assert_eq!(timeouts["search_webcams"], 60_000);
+    assert_eq!(timeouts["capture_remote_image"], 20_000);
+    assert!(timeouts.get("list_cameras").is_none());
+}
+
+#[test]
+fn config_file_timeouts_must_be_positive() {
+    let config: ConfigFile = toml::from_str("[tool_timeouts]\ncapture_image = 30\nsearch_webcams = 1.5\n").unwrap();
+    let timeouts = config.tool_timeouts().unwrap();
+    assert_eq!(timeouts["capture_image"], Duration::from_secs(30));
+    assert_eq!(timeouts["search_webcams"], Duration::from_millis(1500));
+
+    let config: ConfigFile = toml::from_str("[tool_timeouts]\ncapture_image = 0\n").unwrap();
+    assert!(config.tool_timeouts().unwrap_err().contains("capture_image"));
+}
@@ -0,0 +1,185 @@
+//! Full JSON-RPC round trips through `run_with_transport` and an in-memory
+//! transport.
+
+use mcp_webcam::protocol::Transport;
+use mcp_webcam::{MockBackend, ShodanClient, WebcamMcpServer};
+use mcpr::error::MCPError;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Replays queued client messages, `pause` apart, then reports the client
+/// as gone
+struct MemoryTransport {
+    incoming: Mutex<VecDeque<String>>,
+    sent: Arc<Mutex<Vec<Value>>>,
+    pause: Duration,
+}
+
+impl MemoryTransport {
+    /// Transport replaying `messages`, and the messages the server sent on it
+    fn new(messages: &[Value], pause: Duration) -> (Self, Arc<Mutex<Vec<Value>>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let transport = Self {
+            incoming: Mutex::new(messages.iter().map(Value::to_string).collect()),
+            sent: Arc::clone(&sent),
+            pause,
+        };
+        (transport, sent)
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn receive(&self) -> Result<Option<String>, MCPError> {
+        std::thread::sleep(self.pause);
+        Ok(self.incoming.lock().unwrap().pop_front())
+    }
+
+    fn send(&self, message: &Value) -> Result<(), MCPError> {
+        self.sent.lock().unwrap().push(message.clone());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn requests_are_served_over_a_custom_transport() {
+    let messages = [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2025-06-18" } }),
+        json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }),
+        json!({ "jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": { "name": "get_server_stats", "arguments": {} } }),
+        json!({ "jsonrpc": "2.0", "id": 4, "method": "no/such/method" }),
+        json!({ "jsonrpc": "2.0", "id": 5, "method": "tools/call", "params": { "name": "get_server_info", "arguments": {} } }),
+    ];
+    let (transport, sent) = MemoryTransport::new(&messages, Duration::ZERO);
+
+    WebcamMcpServer::new().run_with_transport(transport).await.unwrap();
+
+    let sent = sent.lock().unwrap();
+    // The notification gets no response
+    assert_eq!(sent.iter().map(|m| m["id"].clone()).collect::<Vec<_>>(), vec![json!(1), json!(2), json!(3), json!(4), json!(5)]);
+    assert_eq!(sent[0]["result"]["serverInfo"]["name"], env!("CARGO_PKG_NAME"));
+    assert_eq!(sent[0]["result"]["serverInfo"]["version"], env!("CARGO_PKG_VERSION"));
+    assert!(sent[1]["result"]["tools"].as_array().unwrap().iter().any(|t| t["name"] == "capture_image"));
+    assert_eq!(sent[2]["result"]["structuredContent"]["stats"]["captures_total"], 0);
+    assert_eq!(sent[3]["error"]["code"], -32601);
+
+    let info = &sent[4]["result"]["structuredContent"]["server_info"];
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert!(!info["git_commit"].as_str().unwrap().is_empty());
+    let features = info["features"].as_array().unwrap();
+    assert_eq!(features.contains(&json!("local_cameras")), cfg!(feature = "local_cameras"));
+    assert_eq!(features.contains(&json!("rtsp")), cfg!(feature = "rtsp"));
+    assert_eq!(features.contains(&json!("censys")), cfg!(feature = "censys"));
+}
+
+#[tokio::test]
+async fn removing_shodan_updates_the_tool_list_and_notifies() {
+    let messages = [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2025-06-18" } }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }),
+        json!({ "jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": { "name": "configure_shodan", "arguments": { "remove": true } } }),
+        json!({ "jsonrpc": "2.0", "id": 4, "method": "tools/list" }),
+        json!({ "jsonrpc": "2.0", "id": 5, "method": "tools/call", "params": { "name": "search_webcams", "arguments": {} } }),
+        json!({ "jsonrpc": "2.0", "id": 6, "method": "tools/call", "params": { "name": "get_server_info", "arguments": {} } }),
+    ];
+    let (transport, sent) = MemoryTransport::new(&messages, Duration::ZERO);
+
+    // Nothing is sent to Shodan: removing the key doesn't check it
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(ShodanClient::new("test-key".to_string()))
+        .preview(false)
+        .build();
+    server.run_with_transport(transport).await.unwrap();
+
+    let sent = sent.lock().unwrap();
+    assert_eq!(sent[0]["result"]["capabilities"]["tools"]["listChanged"], true);
+    let tool_names = |list: &Value| -> Vec<String> {
+        list["result"]["tools"].as_array().unwrap().iter()
+            .map(|t| t["name"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert!(tool_names(&sent[1]).contains(&"search_webcams".to_string()));
+
+    // The call's response is followed by the notification
+    assert_eq!(sent[2]["id"], 3);
+    assert_eq!(sent[2]["result"]["structuredContent"]["shodan_enabled"], false);
+    assert_eq!(sent[3], json!({ "jsonrpc": "2.0", "method": "notifications/tools/list_changed" }));
+
+    let tools = tool_names(&sent[4]);
+    assert!(!tools.contains(&"search_webcams".to_string()));
+    assert!(!tools.contains(&"capture_remote_image".to_string()));
+    assert!(tools.contains(&"configure_shodan".to_string()));
+
+    // The last two calls run alongside each other, so either may answer first
+    let response = |id: u64| sent.iter().find(|m| m["id"] == id).unwrap();
+    assert_eq!(response(5)["error"]["code"], -32602);
+    assert_eq!(response(6)["result"]["structuredContent"]["server_info"]["shodan_enabled"], false);
+    assert_eq!(sent.len(), 7);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cancellation_stops_a_running_clip() {
+    let clip = json!({ "name": "capture_clip", "arguments": { "frames": 50, "interval_ms": 400, "format": "mjpeg" } });
+    let messages = [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2025-06-18" } }),
+        json!({ "jsonrpc": "2.0", "id": "clip", "method": "tools/call", "params": clip }),
+        // Arrives while the ~20 s clip is being captured
+        json!({ "jsonrpc": "2.0", "method": "notifications/cancelled", "params": { "requestId": "clip", "reason": "changed my mind" } }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": { "name": "capture_image", "arguments": {} } }),
+    ];
+    let (transport, sent) = MemoryTransport::new(&messages, Duration::from_millis(300));
+
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .preview(false)
+        .build();
+    let started = Instant::now();
+    server.run_with_transport(transport).await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(10), "took {:?}", started.elapsed());
+
+    let sent = sent.lock().unwrap();
+    assert_eq!(sent.iter().map(|m| m["id"].clone()).collect::<Vec<_>>(), vec![json!(1), json!("clip"), json!(2)]);
+    assert_eq!(sent[1]["result"]["isError"], true);
+    assert_eq!(sent[1]["result"]["structuredContent"]["error"]["code"], "CANCELLED");
+
+    // The camera was released for the next call
+    assert_ne!(sent[2]["result"]["isError"], true, "{}", sent[2]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscribed_snapshots_are_announced_until_the_client_goes() {
+    let snapshot = "webcam://camera/0/snapshot";
+    let mut messages = vec![
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2025-06-18" } }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "resources/subscribe", "params": { "uri": snapshot } }),
+    ];
+    // Keep the client connected for a few snapshot intervals
+    messages.extend((3..8).map(|id| json!({ "jsonrpc": "2.0", "id": id, "method": "ping" })));
+    let (transport, sent) = MemoryTransport::new(&messages, Duration::from_millis(500));
+
+    let backend = MockBackend::new(1);
+    let stats = backend.stats();
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(backend))
+        .preview(false)
+        .snapshot_interval(Duration::from_secs(1))
+        .build();
+    server.run_with_transport(transport).await.unwrap();
+
+    let sent = sent.lock().unwrap();
+    assert_eq!(sent[0]["result"]["capabilities"]["resources"]["subscribe"], true);
+    assert_eq!(sent[1]["result"], json!({}));
+    let updates: Vec<&Value> = sent.iter().filter(|m| m["method"] == "notifications/resources/updated").collect();
+    assert!(updates.len() >= 2, "{:?}", *sent);
+    assert!(updates.iter().all(|m| m["params"]["uri"] == snapshot));
+
+    // Disconnecting stopped the snapshots, once any in progress finished
+    std::thread::sleep(Duration::from_millis(200));
+    let grabbed = stats.frames_grabbed.load(Ordering::Relaxed);
+    std::thread::sleep(Duration::from_millis(1500));
+    assert_eq!(stats.frames_grabbed.load(Ordering::Relaxed), grabbed);
+}
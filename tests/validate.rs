@@ -0,0 +1,142 @@
+//! Validating webcams against stand-ins on localhost: one serving a JPEG,
+//! one a 404 page, one that never answers and a port nobody listens on.
+
+mod common;
+
+use mcp_webcam::{MockBackend, RemoteWebcam, ShodanClient, ValidateOptions, WebcamCache, WebcamMcpServer};
+use serde_json::json;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+const JPEG: &[u8] = b"\xFF\xD8\xFF\xE0valid\xFF\xD9";
+
+/// Answer every request with `status`, `content_type` and `body`; `None`
+/// accepts connections and never answers
+fn serve(response: Option<(&'static str, &'static str, &'static [u8])>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let mut held = Vec::new();
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { break };
+            let Some((status, content_type, body)) = response else {
+                held.push(stream);
+                continue;
+            };
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).map_or(false, |n| n > 2) {
+                line.clear();
+            }
+            let head = format!(
+                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                content_type,
+                body.len()
+            );
+            let stream = reader.get_mut();
+            let _ = stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(body));
+        }
+    });
+    format!("http://{}/snapshot.jpg", address)
+}
+
+/// URL of a port nothing listens on
+fn closed() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}/snapshot.jpg", listener.local_addr().unwrap())
+}
+
+fn webcam(url: &str) -> RemoteWebcam {
+    let parsed = url::Url::parse(url).unwrap();
+    RemoteWebcam { url: parsed.to_string(), ..common::webcam(parsed.host_str().unwrap(), parsed.port().unwrap()) }
+}
+
+#[tokio::test]
+async fn validations_tell_images_errors_and_silence_apart_in_order() {
+    let webcams = vec![
+        webcam(&serve(Some(("200 OK", "image/jpeg", JPEG)))),
+        webcam(&serve(Some(("404 Not Found", "text/html", b"<html>not found</html>")))),
+        webcam(&serve(Some(("200 OK", "text/html", b"<html><form>login</form></html>")))),
+        webcam(&serve(None)),
+        webcam(&closed()),
+    ];
+    let client = ShodanClient::builder().allow_private_targets(true).build("key").unwrap();
+    let options = ValidateOptions { concurrency: 2, timeout: Duration::from_secs(1), ..ValidateOptions::default() };
+
+    let started = Instant::now();
+    let validations: Vec<_> = client.validate_webcams(&webcams, &options).await.into_iter().map(Result::unwrap).collect();
+    assert!(started.elapsed() < Duration::from_secs(5), "{:?}", started.elapsed());
+    assert_eq!(validations.len(), webcams.len());
+
+    let image = &validations[0];
+    assert!(image.reachable && image.image_served);
+    assert_eq!(image.http_status, Some(200));
+    assert_eq!(image.content_type.as_deref(), Some("image/jpeg"));
+    assert!(image.latency_ms.is_some());
+
+    let missing = &validations[1];
+    assert!(missing.reachable && !missing.image_served);
+    assert_eq!(missing.http_status, Some(404));
+
+    let login = &validations[2];
+    assert!(login.reachable && !login.image_served);
+    assert_eq!(login.content_type.as_deref(), Some("text/html"));
+
+    for silent in &validations[3..] {
+        assert!(!silent.reachable && !silent.image_served);
+        assert!(silent.error.is_some());
+    }
+    assert!(validations[3].error.as_deref().unwrap().contains("within 1 s"));
+}
+
+#[test]
+fn validations_are_kept_with_the_webcams_searches_found() {
+    let found = webcam("http://192.0.2.10:8080/snapshot.jpg");
+    let mut cache = WebcamCache::new();
+    cache.record_search(&[found.clone()], &["webcam".to_string()]);
+    assert_eq!(cache.last_search().len(), 1);
+
+    let client = ShodanClient::builder().allow_private_targets(true).build("key").unwrap();
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let validation = rt.block_on(client.validate_webcam(&webcam(&closed()), Duration::from_secs(1)));
+    assert!(cache.record_validation(&found.ip, found.port, validation.clone()));
+    assert!(!cache.record_validation("192.0.2.11", 80, validation.clone()));
+
+    // Found again, the webcam keeps its validation and discovery time
+    let discovered_at = cache.get(&found.ip, found.port).unwrap().discovered_at;
+    cache.record_search(&[found.clone()], &["Server: IP Webcam".to_string()]);
+    let cached = cache.get(&found.ip, found.port).unwrap();
+    assert_eq!(cached.validation.as_ref(), Some(&validation));
+    assert_eq!(cached.discovered_at, discovered_at);
+    assert_eq!(cached.queries, ["webcam", "Server: IP Webcam"]);
+    assert_eq!(cache.len(), 1);
+}
+
+#[test]
+fn validate_webcams_checks_the_webcams_given_or_asks_for_a_search() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(ShodanClient::builder().allow_private_targets(true).build("test-key").unwrap())
+        // Both webcams are on 127.0.0.1
+        .host_cooldown(Duration::ZERO)
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let nothing = common::call_structured(&server, "validate_webcams", json!({}));
+    assert_eq!(nothing["error"]["code"], "INVALID_PARAMS");
+
+    let url = serve(Some(("200 OK", "image/jpeg", JPEG)));
+    let result = common::call_structured(&server, "validate_webcams", json!({
+        "webcams": [{ "url": url }, { "url": closed() }],
+        "timeout_seconds": 2
+    }));
+    assert_eq!(result["summary"], json!({ "total": 2, "reachable": 1, "image_served": 1, "cooling_down": 0 }));
+    assert_eq!(result["results"][0]["url"], url);
+    assert_eq!(result["results"][0]["cached"], false);
+    assert_eq!(result["results"][0]["validation"]["http_status"], 200);
+    assert_eq!(result["results"][1]["validation"]["reachable"], false);
+}
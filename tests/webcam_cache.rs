@@ -0,0 +1,250 @@
+//! The cache of discovered webcams behind `list_remote_webcams`: filters,
+//! pages, expiry, the tools reading and clearing it, and its database.
+
+mod common;
+
+use chrono::Utc;
+use common::call;
+use mcp_webcam::shodan::GeoFilter;
+use mcp_webcam::webcam_cache::CacheFilter;
+use mcp_webcam::{MockBackend, RemoteWebcam, SearchFilters, ShodanClient, WebcamCache, WebcamMcpServer, WebcamValidation};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn webcam(ip: &str, country_code: &str, product: &str) -> RemoteWebcam {
+    RemoteWebcam {
+        location: Some(common::location(country_code)),
+        product: Some(product.to_string()),
+        ..common::webcam(ip, 80)
+    }
+}
+
+fn validation(reachable: bool) -> WebcamValidation {
+    WebcamValidation {
+        checked_at: Utc::now().to_rfc3339(),
+        reachable,
+        image_served: reachable,
+        http_status: reachable.then_some(200),
+        latency_ms: None,
+        content_type: None,
+        error: None,
+        width: None,
+        height: None,
+        size_bytes: None,
+        thumbnail: None,
+    }
+}
+
+fn cache() -> WebcamCache {
+    let mut cache = WebcamCache::new();
+    cache.record_search(
+        &[
+            webcam("192.0.2.1", "GB", "Hikvision IP Camera"),
+            webcam("192.0.2.2", "GB", "AXIS M1065"),
+            webcam("192.0.2.3", "DE", "Hikvision IP Camera"),
+        ],
+        &["webcam".to_string()],
+    );
+    cache.record_validation("192.0.2.1", 80, validation(true));
+    cache.record_validation("192.0.2.3", 80, validation(false));
+    cache
+}
+
+fn ips(cache: &mut WebcamCache, filter: &CacheFilter) -> Vec<String> {
+    cache.list(filter, 0, 10).webcams.into_iter().map(|cached| cached.webcam.ip).collect()
+}
+
+#[test]
+fn cached_webcams_are_filtered_by_country_product_and_reachability() {
+    let mut cache = cache();
+    assert_eq!(ips(&mut cache, &CacheFilter::default()).len(), 3);
+
+    let british = CacheFilter { filters: SearchFilters::new(Some("gb"), None, None).unwrap(), ..Default::default() };
+    assert_eq!(ips(&mut cache, &british), ["192.0.2.1", "192.0.2.2"]);
+
+    let hikvision = CacheFilter { product: Some("hikvision".to_string()), ..Default::default() };
+    assert_eq!(ips(&mut cache, &hikvision), ["192.0.2.1", "192.0.2.3"]);
+
+    // Unvalidated and unreachable webcams are left out alike
+    let reachable = CacheFilter { reachable_only: true, ..Default::default() };
+    assert_eq!(ips(&mut cache, &reachable), ["192.0.2.1"]);
+}
+
+#[test]
+fn a_radius_lists_the_nearest_cached_webcams_first() {
+    let at = |ip: &str, place: Option<(f64, f64)>| {
+        let mut webcam = webcam(ip, "GB", "AXIS M1065");
+        let location = webcam.location.as_mut().unwrap();
+        location.latitude = place.map(|(latitude, _)| latitude);
+        location.longitude = place.map(|(_, longitude)| longitude);
+        webcam
+    };
+    let mut cache = WebcamCache::new();
+    cache.record_search(
+        &[
+            at("192.0.2.1", Some((53.4808, -2.2426))), // Manchester
+            at("192.0.2.2", Some((51.4543, -0.9781))), // Reading
+            at("192.0.2.3", None),
+            at("192.0.2.4", Some((55.9533, -3.1883))), // Edinburgh
+        ],
+        &["webcam".to_string()],
+    );
+    // Within 300 km of London
+    let near = GeoFilter::new(51.5074, -0.1278, 300.0).unwrap();
+    let filter = CacheFilter { filters: SearchFilters::default().with_near(Some(near)), ..Default::default() };
+    let page = cache.list(&filter, 0, 10);
+    let ips: Vec<&str> = page.webcams.iter().map(|cached| cached.webcam.ip.as_str()).collect();
+    assert_eq!(ips, ["192.0.2.2", "192.0.2.1"]);
+    assert_eq!(page.total, 2);
+    assert_eq!(page.outside_radius, 2);
+    // Only webcams the rest of the filter lets through are counted
+    let german = CacheFilter { filters: SearchFilters::new(Some("DE"), None, None).unwrap().with_near(Some(near)), ..Default::default() };
+    assert_eq!(cache.list(&german, 0, 10).outside_radius, 0);
+}
+
+#[test]
+fn pages_cover_every_match_once() {
+    let mut cache = cache();
+    let first = cache.list(&CacheFilter::default(), 0, 2);
+    let second = cache.list(&CacheFilter::default(), 2, 2);
+    assert_eq!((first.total, first.webcams.len()), (3, 2));
+    assert_eq!((second.total, second.webcams.len()), (3, 1));
+    let mut seen: Vec<String> = first.webcams.iter().chain(&second.webcams).map(|cached| cached.webcam.ip.clone()).collect();
+    seen.dedup();
+    assert_eq!(seen.len(), 3);
+    assert!(cache.list(&CacheFilter::default(), 3, 2).webcams.is_empty());
+}
+
+#[test]
+fn webcams_no_search_finds_again_expire() {
+    let mut cache = WebcamCache::with_ttl(Duration::from_secs(60));
+    cache.record_search(&[webcam("192.0.2.1", "GB", "webcamXP")], &[]);
+    assert_eq!(cache.prune_expired_at(Utc::now() + chrono::Duration::seconds(30)), 0);
+    assert_eq!(cache.prune_expired_at(Utc::now() + chrono::Duration::seconds(61)), 1);
+    assert!(cache.is_empty());
+    assert!(cache.last_search().is_empty());
+
+    let mut cache = cache_with_two();
+    assert_eq!(cache.clear(), 2);
+    assert!(cache.is_empty());
+}
+
+fn cache_with_two() -> WebcamCache {
+    let mut cache = WebcamCache::new();
+    cache.record_search(&[webcam("192.0.2.1", "GB", "a"), webcam("192.0.2.2", "GB", "b")], &[]);
+    cache
+}
+
+#[test]
+fn listing_and_clearing_need_no_search_credits() {
+    let server = WebcamMcpServer::builder()
+        .backend(Box::new(MockBackend::new(1)))
+        .shodan_client(ShodanClient::new("test-key".to_string()))
+        .preview(false)
+        .build()
+        .build_server()
+        .unwrap();
+
+    let listed = call(&server, "list_remote_webcams", json!({}));
+    assert_eq!(listed["structuredContent"]["total"], 0);
+    assert_eq!(listed["structuredContent"]["next_offset"], Value::Null);
+
+    let bad_country = call(&server, "list_remote_webcams", json!({ "country": "Britain" }));
+    assert_eq!(bad_country["structuredContent"]["error"]["code"], "INVALID_PARAMS");
+
+    let cleared = call(&server, "clear_remote_webcams", json!({}));
+    assert_eq!(cleared["structuredContent"]["cleared"], 0);
+}
+
+fn db_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mcp-webcam-db-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn files_in(dir: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn saved_webcams_and_validations_are_there_after_a_restart() {
+    let dir = db_dir("round-trip");
+    let path = dir.join("state").join("remote_webcams.json");
+
+    let mut cache = WebcamCache::open(&path, Duration::from_secs(3600));
+    assert!(cache.is_empty());
+    cache.flush();
+    assert!(!path.exists(), "nothing changed, so nothing is written");
+
+    cache.record_search(&[webcam("192.0.2.1", "GB", "AXIS M1065"), webcam("192.0.2.2", "DE", "webcamXP")], &["webcam".to_string()]);
+    cache.record_validation("192.0.2.1", 80, validation(true));
+    cache.flush();
+    assert_eq!(files_in(path.parent().unwrap()), ["remote_webcams.json"]);
+
+    let saved: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(saved["version"], 1);
+    assert_eq!(saved["webcams"][0]["ip"], "192.0.2.1");
+
+    let mut reopened = WebcamCache::open(&path, Duration::from_secs(3600));
+    assert_eq!(reopened.len(), 2);
+    let original = cache.get("192.0.2.1", 80).unwrap();
+    let restored = reopened.get("192.0.2.1", 80).unwrap();
+    assert_eq!(restored.last_found_at, original.last_found_at);
+    assert_eq!(restored.validation, original.validation);
+    assert_eq!(restored.queries, ["webcam"]);
+
+    // Clearing empties the file too
+    reopened.clear();
+    reopened.flush();
+    assert!(WebcamCache::open(&path, Duration::from_secs(3600)).is_empty());
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn unversioned_webcam_lists_are_migrated() {
+    let dir = db_dir("migrate");
+    let path = dir.join("remote_webcams.json");
+    std::fs::write(&path, serde_json::to_string(&[webcam("192.0.2.1", "GB", "webcamXP")]).unwrap()).unwrap();
+
+    let mut cache = WebcamCache::open(&path, Duration::from_secs(3600));
+    let cached = cache.get("192.0.2.1", 80).unwrap();
+    assert_eq!(cached.webcam.product.as_deref(), Some("webcamXP"));
+    assert!(cached.validation.is_none());
+    assert_eq!(files_in(&dir), ["remote_webcams.json"]);
+
+    cache.record_validation("192.0.2.1", 80, validation(true));
+    cache.flush();
+    let saved: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(saved["version"], 1);
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn corrupted_and_newer_databases_are_moved_aside() {
+    let dir = db_dir("corrupt");
+    let path = dir.join("remote_webcams.json");
+
+    std::fs::write(&path, "{\"version\": 1, \"webcams\": [{\"ip\": ").unwrap();
+    let mut cache = WebcamCache::open(&path, Duration::from_secs(3600));
+    assert!(cache.is_empty());
+    let files = files_in(&dir);
+    assert_eq!(files.len(), 1);
+    assert!(files[0].starts_with("remote_webcams.json.corrupt-"), "{:?}", files);
+
+    // The cache still saves to the original file
+    cache.record_search(&[webcam("192.0.2.1", "GB", "webcamXP")], &[]);
+    cache.flush();
+    assert!(path.exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(&path, r#"{"version": 99, "webcams": []}"#).unwrap();
+    assert!(WebcamCache::open(&path, Duration::from_secs(3600)).is_empty());
+    let files = files_in(&dir);
+    assert!(files[0].starts_with("remote_webcams.json.v99-"), "{:?}", files);
+    let _ = std::fs::remove_dir_all(dir);
+}